@@ -0,0 +1,52 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use chip8::chip::Chip8;
+use chip8::platform::Platform;
+
+// How fast the machine runs and how quickly it's saved and put back, which is what the
+// frontends, rewind and the timeline spend their time on. `cargo bench` runs them all, and
+// `cargo bench -- <name>` just the ones whose names have it in. criterion keeps the last run
+// in target/criterion and says how each one's changed since.
+
+/// Draws the font's 0 all over the screen, stepping and adding a random number as it goes,
+/// so a frame is mostly drawing
+const DRAWING: [u16; 6] = [0xA000, 0xD015, 0x7003, 0x7101, 0xC2FF, 0x1202];
+
+/// Adds, shifts and compares without drawing anything
+const ARITHMETIC: [u16; 7] = [0x6001, 0x8014, 0x8106, 0x810E, 0x5010, 0x3FFF, 0x1202];
+
+
+/// A machine on platform with the opcodes loaded, running a thousand instructions a frame
+fn machine(platform: Platform, opcodes: &[u16]) -> Chip8 {
+    let rom: Vec<u8> = opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut chip = Chip8::with_platform(platform, false);
+    chip.load_rom_bytes(&rom);
+    chip.seed_rng(0);
+    chip.set_cycles_per_frame(1000);
+    return chip;
+}
+
+fn frames(c: &mut Criterion) {
+    let mut chip = machine(Platform::Chip8, &DRAWING);
+    c.bench_function("frame drawing", |b| b.iter(|| chip.run_frame().unwrap()));
+
+    // 00FF first for the big screen
+    let mut chip = machine(Platform::SuperChip, &[0x00FF, 0xA000, 0xD015, 0x7003, 0x7101, 0xC2FF, 0x1204]);
+    c.bench_function("frame drawing hires", |b| b.iter(|| chip.run_frame().unwrap()));
+
+    let mut chip = machine(Platform::Chip8, &ARITHMETIC);
+    c.bench_function("frame arithmetic", |b| b.iter(|| chip.run_frame().unwrap()));
+}
+
+fn snapshots(c: &mut Criterion) {
+    let mut chip = machine(Platform::Chip8, &DRAWING);
+    chip.run_frame().unwrap();
+    c.bench_function("snapshot", |b| b.iter(|| chip.snapshot()));
+
+    let snapshot = chip.snapshot();
+    c.bench_function("restore", |b| b.iter(|| chip.restore(&snapshot)));
+    c.bench_function("save state", |b| b.iter(|| snapshot.to_bytes()));
+}
+
+criterion_group!(benches, frames, snapshots);
+criterion_main!(benches);
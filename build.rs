@@ -0,0 +1,24 @@
+fn main() {
+    #[cfg(feature = "ffi")]
+    write_header();
+}
+
+/// Writes the C header for the ffi module to include/chip8.h
+#[cfg(feature = "ffi")]
+fn write_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+
+    // Only ffi.rs is read, so the libretro core's functions stay out of the header
+    let config = cbindgen::Config { usize_is_size_t: true, ..Default::default() };
+    let bindings = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/ffi.rs")
+        .with_language(cbindgen::Language::C)
+        .with_include_guard("CHIP8_H")
+        .with_autogen_warning("/* Generated by cbindgen from src/ffi.rs, don't edit by hand */")
+        .rename_item("Chip8Handle", "chip8_t")
+        .with_cpp_compat(true)
+        .generate()
+        .expect("couldn't generate include/chip8.h");
+    bindings.write_to_file("include/chip8.h");
+}
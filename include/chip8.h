@@ -0,0 +1,110 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The machine behind a chip8_t, along with the message of the last error it stopped with
+ */
+typedef struct chip8_t chip8_t;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Makes a machine for the named platform ("chip8", "superchip", "xochip" and so on, as for
+ * --platform), or plain CHIP-8 if platform is null. Returns null if the platform isn't known
+ *
+ * # Safety
+ * platform must be null or a nul terminated string
+ */
+struct chip8_t *chip8_new(const char *platform);
+
+/**
+ * Frees a machine made by chip8_new. Does nothing if chip is null
+ *
+ * # Safety
+ * chip must be null or from chip8_new, and not used again afterwards
+ */
+void chip8_free(struct chip8_t *chip);
+
+/**
+ * Loads len bytes of rom into memory at the platform's start address
+ *
+ * # Safety
+ * chip must be from chip8_new, and rom must point to len bytes
+ */
+void chip8_load_rom(struct chip8_t *chip, const uint8_t *rom, size_t len);
+
+/**
+ * Runs a single instruction. Returns false if it failed, see chip8_error
+ *
+ * # Safety
+ * chip must be from chip8_new
+ */
+bool chip8_step(struct chip8_t *chip);
+
+/**
+ * Runs one 60th of a second and ticks the timers. Returns false if an instruction failed,
+ * see chip8_error
+ *
+ * # Safety
+ * chip must be from chip8_new
+ */
+bool chip8_run_frame(struct chip8_t *chip);
+
+/**
+ * Counts the delay and sound timers down, for callers stepping by instruction
+ *
+ * # Safety
+ * chip must be from chip8_new
+ */
+void chip8_tick_timers(struct chip8_t *chip);
+
+/**
+ * The message of the last error an instruction failed with, or null if none has. It's
+ * valid until the next call that runs instructions
+ *
+ * # Safety
+ * chip must be from chip8_new
+ */
+const char *chip8_error(const struct chip8_t *chip);
+
+/**
+ * The screen, a byte per pixel row by row, with its size written to width and height. The
+ * resolution can change as the rom runs, and the pointer is only valid until the next call
+ * to chip8_framebuffer or chip8_free
+ *
+ * # Safety
+ * chip must be from chip8_new, and width and height must each be null or writable
+ */
+const uint8_t *chip8_framebuffer(const struct chip8_t *chip, size_t *width, size_t *height);
+
+/**
+ * Presses or releases a keypad key, 0 to F
+ *
+ * # Safety
+ * chip must be from chip8_new
+ */
+void chip8_key(struct chip8_t *chip, uint8_t key, bool pressed);
+
+/**
+ * Whether the sound timer is running, i.e. whether to beep
+ *
+ * # Safety
+ * chip must be from chip8_new
+ */
+bool chip8_sound(const struct chip8_t *chip);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
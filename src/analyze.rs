@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::chip::CYCLES_PER_FRAME;
+use crate::isa;
+use crate::platform::Platform;
+
+// The analysis decodes the rom linearly two bytes at a time from 0x200, so sprite and
+// other data mixed in with the code can show up as instructions it never actually
+// runs. It's meant as a quick hint of what a rom needs, not a proof.
+//
+// Roms were written for machines of very different speeds, from the COSMAC VIP's handful
+// of instructions a frame to XO-CHIP's Octo default of 1000, and many are only playable
+// near the speed they were written for. The suggested speed goes by the platform, and for
+// plain CHIP-8 by how the rom keeps time: one that never sets the delay timer is paced by
+// how fast instructions run and wants the VIP's speed, while one that waits for the delay
+// timer to run out every frame is held to 60Hz whatever the speed, so it can go faster.
+
+/// Instructions per frame for roms paced by instruction count, about what the VIP managed
+const VIP_SPEED: usize = 7;
+
+/// Instructions per frame for roms that wait on the delay timer, which keeps them to 60Hz
+const TIMED_SPEED: usize = 15;
+
+/// Instructions per frame for SUPER-CHIP roms, written for the much faster HP-48
+const SUPER_CHIP_SPEED: usize = 30;
+
+/// Instructions per frame for XO-CHIP and Mega-Chip roms, Octo's default for XO-CHIP
+const MODERN_SPEED: usize = 1000;
+
+/// The instructions the interpreter decodes but doesn't emulate, by mnemonic: XO-CHIP's bit
+/// planes, see superchip.rs
+const UNSUPPORTED: &[&str] = &["PLANE n"];
+
+
+/// The result of statically scanning a rom
+pub struct Analysis {
+    /// How many times each instruction appears, keyed by mnemonic
+    pub counts: BTreeMap<&'static str, usize>,
+    /// Every instruction from an extension of CHIP-8, as (address, opcode, platform)
+    pub extensions: Vec<(u16, u16, Platform)>,
+    /// Opcodes that don't decode to any known instruction, as (address, opcode)
+    pub unknown: Vec<(u16, u16)>,
+    /// Instructions the interpreter doesn't emulate, as (address, opcode), see UNSUPPORTED
+    pub unsupported: Vec<(u16, u16)>,
+    /// Patterns whose behaviour differs between interpreters, as (address, opcode, description)
+    pub quirks: Vec<(u16, u16, String)>,
+    /// The smallest platform that supports every instruction found
+    pub platform: Platform,
+    /// The instructions per frame the rom likely wants, and why
+    pub speed: usize,
+    pub speed_reason: &'static str,
+}
+
+/// Scans a rom and reports which instructions it uses and what platform it likely targets
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let mut analysis = Analysis {
+        counts: BTreeMap::new(),
+        extensions: Vec::new(),
+        unknown: Vec::new(),
+        unsupported: Vec::new(),
+        quirks: Vec::new(),
+        platform: Platform::Chip8,
+        speed: CYCLES_PER_FRAME,
+        speed_reason: "",
+    };
+
+    // Any Mega-Chip rom has to switch the mode on with MEGAON before using the rest of
+    // its instructions, without one 01NN-09NN are far more likely to be SYS or data.
+    // CHIP-8X roms have no such marker, so they're never decoded as one
+    // Hires roms start by jumping to the 1802 patch at 0x260, which isn't CHIP-8 code, so
+    // decoding skips to where the program itself starts at 0x2C0
+    let (decode_as, start) = if rom.starts_with(&[0x12, 0x60]) {
+        (Platform::HiresChip8, 0xC0)
+    } else if rom.chunks_exact(2).any(|word| word == [0x00, 0x11]) {
+        (Platform::MegaChip, 0)
+    } else {
+        (Platform::Chip8, 0)
+    };
+    if decode_as == Platform::HiresChip8 {
+        analysis.platform = Platform::HiresChip8;
+    }
+
+    // Whether the rom sets the delay timer (FX15), and waits on it by reading it (FX07) and
+    // skipping on it being 0 straight after
+    let mut sets_delay = false;
+    let mut waits_on_delay = false;
+    let mut previous = None;
+    let mut i = start;
+    while i + 1 < rom.len() {
+        let addr = 0x200 + i as u16;
+        let opcode = (rom[i] as u16) << 8 | rom[i + 1] as u16;
+
+        match isa::lookup(opcode, decode_as) {
+            Some(info) => {
+                *analysis.counts.entry(info.mnemonic).or_insert(0) += 1;
+                if info.platform != Platform::Chip8 {
+                    analysis.extensions.push((addr, opcode, info.platform));
+                    analysis.platform = analysis.platform.max(info.platform);
+                }
+                if UNSUPPORTED.contains(&info.mnemonic) {
+                    analysis.unsupported.push((addr, opcode));
+                }
+            },
+            None => analysis.unknown.push((addr, opcode)),
+        }
+
+        sets_delay |= opcode & 0xF0FF == 0xF015;
+        if let Some(previous) = previous.filter(|previous| previous & 0xF0FF == 0xF007) {
+            let x = previous & 0x0F00;
+            waits_on_delay |= opcode == 0x3000 | x || opcode == 0x4000 | x;
+        }
+
+        if let Some(quirk) = quirk(opcode, previous) {
+            analysis.quirks.push((addr, opcode, quirk));
+        }
+
+        previous = Some(opcode);
+        i += isa::length(opcode, decode_as);
+    }
+
+    (analysis.speed, analysis.speed_reason) = suggest_speed(analysis.platform, sets_delay, waits_on_delay);
+    return analysis;
+}
+
+/// Picks the instructions per frame for a rom, see the top of this file
+fn suggest_speed(platform: Platform, sets_delay: bool, waits_on_delay: bool) -> (usize, &'static str) {
+    return match platform {
+        Platform::XoChip | Platform::MegaChip => (MODERN_SPEED, "written for fast modern interpreters"),
+        Platform::SuperChip => (SUPER_CHIP_SPEED, "written for the HP-48's SUPER-CHIP"),
+        _ if waits_on_delay => (TIMED_SPEED, "waits on the delay timer, which holds it to 60Hz"),
+        _ if !sets_delay => (VIP_SPEED, "never sets the delay timer, so it's paced by instruction speed"),
+        _ => (CYCLES_PER_FRAME, "the usual CHIP-8 speed"),
+    };
+}
+
+/// Describes how an instruction depends on interpreter quirks, if it does
+fn quirk(opcode: u16, previous: Option<u16>) -> Option<String> {
+    let x = (opcode >> 8) & 0xF;
+    let y = (opcode >> 4) & 0xF;
+
+    return match opcode & 0xF00F {
+        // The COSMAC VIP shifts Vy into Vx, later interpreters shift Vx in place.
+        // Both agree when x == y or Vx was just loaded from Vy
+        0x8006 | 0x800E if x != y && previous != Some(0x8000 | x << 8 | y << 4) => Some(format!(
+            "shift without 8{x:X}{y:X}0 first, result depends on the shift quirk"
+        )),
+        0x8001..=0x8003 => {
+            Some("logic op, VF is reset to 0 on the COSMAC VIP but left alone elsewhere".to_string())
+        },
+        _ => match opcode & 0xF0FF {
+            0xF055 | 0xF065 => {
+                Some("register load/store, whether I is incremented depends on the memory quirk".to_string())
+            },
+            _ if opcode & 0xF000 == 0xB000 => Some(format!(
+                "jump with offset, adds V0 on CHIP-8 but V{x:X} on SUPER-CHIP"
+            )),
+            _ => None,
+        },
+    };
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Instructions used:")?;
+        for (mnemonic, count) in &self.counts {
+            writeln!(f, "  {mnemonic:<16} {count}")?;
+        }
+
+        if !self.extensions.is_empty() {
+            writeln!(f, "\nExtension instructions:")?;
+            for (addr, opcode, platform) in &self.extensions {
+                writeln!(f, "  {addr:04X}: {opcode:04X} ({platform})")?;
+            }
+        }
+
+        if !self.unknown.is_empty() {
+            writeln!(f, "\nUnknown opcodes (likely data):")?;
+            for (addr, opcode) in &self.unknown {
+                writeln!(f, "  {addr:04X}: {opcode:04X}")?;
+            }
+        }
+
+        if !self.quirks.is_empty() {
+            writeln!(f, "\nQuirk-sensitive instructions:")?;
+            for (addr, opcode, quirk) in &self.quirks {
+                writeln!(f, "  {addr:04X}: {opcode:04X} {quirk}")?;
+            }
+        }
+
+        if !self.unsupported.is_empty() {
+            writeln!(f, "\nNot emulated here, so the rom may not run as it should:")?;
+            for (addr, opcode) in &self.unsupported {
+                writeln!(f, "  {addr:04X}: {opcode:04X}")?;
+            }
+        }
+
+        writeln!(f, "\nRecommended platform: {}", self.platform)?;
+        writeln!(f, "Recommended speed: {} instructions per frame, {}", self.speed, self.speed_reason)?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,42 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::MissedTickBehavior;
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+
+// Running the machine inside an async application (a chat bot, a web server streaming the
+// screen) without giving it a blocking thread. Chip8::run waits on a tokio interval between
+// frames, so the runtime gets on with other tasks in the meantime. Only tokio's timer is
+// used, so it runs under any tokio runtime, current thread or multi-threaded.
+//
+// The machine is Send, so the future run returns can be spawned onto any runtime as long as
+// the frontend's futures are Send too.
+
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+
+/// Whatever shows the machine and feeds it input, called between frames
+pub trait AsyncFrontend {
+    /// Called after every frame to show it and set the keys for the next one. Returning
+    /// false stops the machine
+    fn frame(&mut self, chip: &mut Chip8) -> impl Future<Output = bool>;
+}
+
+impl Chip8 {
+    /// Runs the rom at 60 frames a second until the frontend stops it or an instruction
+    /// fails. A frontend that takes longer than a frame slows the machine down rather than
+    /// it running several frames at once to catch up
+    pub async fn run(&mut self, mut frontend: impl AsyncFrontend) -> Result<(), Chip8Error> {
+        let mut interval = tokio::time::interval(FRAME);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            self.run_frame()?;
+            if !frontend.frame(self).await {
+                return Ok(());
+            }
+        }
+    }
+}
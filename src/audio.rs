@@ -0,0 +1,220 @@
+use std::f64::consts::TAU;
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::chip::Chip8;
+
+// The 1-bit sound the audio backends play while the sound timer runs. Most platforms just
+// beep, and how the beep sounds is up to the player (see Beep). XO-CHIP roms can load a
+// 16 byte pattern with F002 that's played instead, a bit at a time and looping, at
+//
+//   4000 * 2 ^ ((pitch - 64) / 48) bits a second
+//
+// where FX3A sets the pitch (64 to start with, so 4000 bits a second). Octo games make
+// their music this way, changing the pattern and pitch every few frames.
+//
+// Either way the sound fades in over the attack when the timer starts and out over the
+// release when it stops, rather than cutting in and out with a click. Wav records the same
+// sound to a file, for chip8 --wav.
+
+/// The bits in an XO-CHIP audio pattern
+const PATTERN_BITS: f64 = 128.0;
+
+
+/// The shape of the beep's wave
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Self::Square),
+            "triangle" => Some(Self::Triangle),
+            "sine" => Some(Self::Sine),
+            _ => None,
+        };
+    }
+
+    /// The wave at phase (0 to 1 through a period), from -1 to 1
+    fn at(self, phase: f64) -> f64 {
+        return match self {
+            Self::Square if phase < 0.5 => 1.0,
+            Self::Square => -1.0,
+            Self::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Self::Sine => (phase * TAU).sin(),
+        };
+    }
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::Square => write!(f, "square"),
+            Self::Triangle => write!(f, "triangle"),
+            Self::Sine => write!(f, "sine"),
+        };
+    }
+}
+
+/// How the beep sounds, from the beep settings in a rom's profile:
+///
+///   beep = square        square, triangle or sine
+///   beep_hz = 440        its frequency
+///   beep_volume = 12     as a percentage of full scale, which also goes for XO-CHIP audio
+///   beep_attack = 2      milliseconds to fade in
+///   beep_release = 10    milliseconds to fade out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Beep {
+    pub waveform: Waveform,
+    pub hz: u32,
+    pub volume: u8,
+    pub attack_ms: u32,
+    pub release_ms: u32,
+}
+
+impl Default for Beep {
+    /// A square wave at A4
+    fn default() -> Self {
+        return Self { waveform: Waveform::Square, hz: 440, volume: 12, attack_ms: 2, release_ms: 10 };
+    }
+}
+
+impl Beep {
+    /// The settings Beep::set takes
+    pub const KEYS: [&'static str; 5] = ["beep", "beep_hz", "beep_volume", "beep_attack", "beep_release"];
+
+    /// Changes one of the settings in KEYS
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || value.parse::<u32>().map_err(|_| format!("invalid {key} '{value}'"));
+        match key {
+            "beep" => {
+                self.waveform = Waveform::from_name(value)
+                    .ok_or(format!("unknown waveform '{value}', expected square, triangle or sine"))?;
+            },
+            "beep_hz" => {
+                let hz = number().ok().filter(|hz| (20..=20_000).contains(hz));
+                self.hz = hz.ok_or(format!("invalid beep_hz '{value}', expected 20 to 20000"))?;
+            },
+            "beep_volume" => {
+                let volume = number().ok().filter(|volume| *volume <= 100);
+                self.volume = volume.ok_or(format!("invalid beep_volume '{value}', expected 0 to 100"))? as u8;
+            },
+            "beep_attack" => self.attack_ms = number()?,
+            "beep_release" => self.release_ms = number()?,
+            key => return Err(format!("unknown setting '{key}'")),
+        }
+        return Ok(());
+    }
+}
+
+/// How many bits of the audio pattern play each second at a pitch
+pub fn pattern_rate(pitch: u8) -> f64 {
+    return 4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0);
+}
+
+/// Turns the machine's sound into samples, keeping its place in the wave and the envelope
+/// from one call to the next so frames join up without clicking
+#[derive(Default)]
+pub struct Synth {
+    beep: Beep,
+    /// Where the wave is, in beep periods or bits of the pattern
+    phase: f64,
+    /// How far the envelope has faded in, from 0 to 1
+    level: f64,
+}
+
+impl Synth {
+    pub fn new(beep: Beep) -> Self {
+        return Self { beep, phase: 0.0, level: 0.0 };
+    }
+
+    pub fn beep(&self) -> Beep {
+        return self.beep;
+    }
+
+    pub fn set_beep(&mut self, beep: Beep) {
+        self.beep = beep;
+    }
+
+    /// Fills samples with the sound at sample_rate, silence once the sound timer has stopped
+    /// and the release has faded out
+    pub fn fill(&mut self, chip: &Chip8, sample_rate: u32, samples: &mut [i16]) {
+        let sounding = chip.sound() > 0;
+        if !sounding && self.level == 0.0 {
+            samples.fill(0);
+            return;
+        }
+
+        let rate = sample_rate as f64;
+        let fade = |ms: u32| 1.0 / (ms as f64 / 1000.0 * rate).max(1.0);
+        let (attack, release) = (fade(self.beep.attack_ms), fade(self.beep.release_ms));
+        let amplitude = self.beep.volume.min(100) as f64 / 100.0 * i16::MAX as f64;
+        let bits_per_sample = pattern_rate(chip.pitch()) / rate;
+        for sample in samples {
+            self.level = if sounding { (self.level + attack).min(1.0) } else { (self.level - release).max(0.0) };
+            let wave = match chip.audio_pattern() {
+                Some(pattern) => {
+                    let bit = self.phase as usize % PATTERN_BITS as usize;
+                    self.phase = (self.phase + bits_per_sample) % PATTERN_BITS;
+                    if (pattern[bit / 8] >> (7 - bit % 8)) & 1 == 1 { 1.0 } else { -1.0 }
+                },
+                None => {
+                    let wave = self.beep.waveform.at(self.phase % 1.0);
+                    self.phase = (self.phase + self.beep.hz as f64 / rate) % 1.0;
+                    wave
+                },
+            };
+            *sample = (wave * self.level * amplitude) as i16;
+        }
+    }
+}
+
+/// Records the sound frame by frame and writes it out as a WAV file, for capturing a
+/// headless run's audio without a sound card
+pub struct Wav {
+    synth: Synth,
+    samples: Vec<i16>,
+}
+
+impl Wav {
+    /// CD quality, which also divides evenly into 60 frames a second
+    pub const SAMPLE_RATE: u32 = 44100;
+
+    pub fn new(beep: Beep) -> Self {
+        return Self { synth: Synth::new(beep), samples: Vec::new() };
+    }
+
+    /// Records a frame's worth of sound as the machine is now. Call it once a frame
+    pub fn record(&mut self, chip: &Chip8) {
+        let start = self.samples.len();
+        self.samples.resize(start + Self::SAMPLE_RATE as usize / 60, 0);
+        self.synth.fill(chip, Self::SAMPLE_RATE, &mut self.samples[start..]);
+    }
+
+    /// Writes what's been recorded as 16-bit mono PCM
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let data_len = self.samples.len() as u32 * 2;
+        out.write_all(b"RIFF")?;
+        out.write_all(&(36 + data_len).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        // PCM, one channel, then the byte rate, bytes per sample and bits per sample
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&Self::SAMPLE_RATE.to_le_bytes())?;
+        out.write_all(&(Self::SAMPLE_RATE * 2).to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&data_len.to_le_bytes())?;
+        for sample in &self.samples {
+            out.write_all(&sample.to_le_bytes())?;
+        }
+        return Ok(());
+    }
+}
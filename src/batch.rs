@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::analyze;
+use crate::chip::{Chip8, Snapshot};
+use crate::persist;
+use crate::platform::Platform;
+use crate::soak::Ending;
+
+// Batches of headless runs spread over every core with rayon, for when throughput is all that
+// matters: fuzzing a corpus, rolling out an agent's episodes, soak testing a folder of roms.
+// run_many takes where each run starts (a rom or a save state), how many frames to run and
+// the keys to hold each frame, and gives back how every run went. Stats adds them up.
+//
+// Each run gets a machine of its own and nothing else, so one can't affect another, and one
+// that panics the interpreter is caught and reported as such rather than taking the batch
+// down. Runs end early the way soak.rs's do, when the rom fails, exits or halts (jumps to
+// the instruction it's on), since nothing after that would change. The pool in pool.rs is
+// the other way of running lots of machines, for keeping them going between calls.
+//
+// How many threads it uses is up to rayon, one per core unless RAYON_NUM_THREADS says.
+
+
+/// Where a run starts
+#[derive(Clone)]
+pub enum Start {
+    /// The rom on a fresh machine, on the platform and at the speed analyze.rs guesses for it
+    /// unless they're given, with CXNN's random numbers from the seed
+    Rom { rom: Arc<[u8]>, platform: Option<Platform>, speed: Option<usize>, seed: u64 },
+    /// A machine as a save state left it, at the default speed unless one's given
+    State { state: Arc<Snapshot>, speed: Option<usize> },
+}
+
+impl Start {
+    /// The rom with everything guessed and a seed of 0
+    pub fn rom(rom: &[u8]) -> Self {
+        return Start::Rom { rom: rom.into(), platform: None, speed: None, seed: 0 };
+    }
+
+    /// A save state as a file holds it, or a rom if it isn't one
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        return match Snapshot::from_bytes(bytes) {
+            Ok(state) => Start::State { state: Arc::new(state), speed: None },
+            Err(_) => Start::rom(bytes),
+        };
+    }
+
+    /// The same start with CXNN's random numbers from another seed. Save states already
+    /// carry their seed, so they're left alone
+    pub fn with_seed(&self, seed: u64) -> Self {
+        let mut start = self.clone();
+        if let Start::Rom { seed: old, .. } = &mut start {
+            *old = seed;
+        }
+        return start;
+    }
+
+    /// A machine ready to run
+    fn machine(&self) -> Chip8 {
+        return match self {
+            Start::Rom { rom, platform, speed, seed } => {
+                let (platform, speed) = match (platform, speed) {
+                    (Some(platform), Some(speed)) => (*platform, *speed),
+                    _ => {
+                        let analysis = analyze::analyze(rom);
+                        (platform.unwrap_or(analysis.platform), speed.unwrap_or(analysis.speed))
+                    },
+                };
+                let mut chip = Chip8::with_platform(platform, false);
+                chip.set_cycles_per_frame(speed);
+                chip.seed_rng(*seed);
+                chip.load_rom_bytes(rom);
+                chip
+            },
+            Start::State { state, speed } => {
+                let mut chip = Chip8::with_platform(state.platform(), false);
+                chip.restore(state);
+                if let Some(speed) = speed {
+                    chip.set_cycles_per_frame(*speed);
+                }
+                chip
+            },
+        };
+    }
+}
+
+/// How a run went
+pub struct Outcome {
+    /// How many frames ran in full
+    pub frames: usize,
+    pub instructions: u64,
+    pub ending: Ending,
+    /// The hash of the screen as the run left it
+    pub screen: u64,
+    /// How long it took on its thread
+    pub time: Duration,
+    /// The machine as the run left it
+    pub chip: Chip8,
+}
+
+impl Outcome {
+    /// One line on how the run went, e.g. "ran 600 frames, 9000 instructions"
+    pub fn summary(&self) -> String {
+        let ending = match &self.ending {
+            Ending::Ran => format!("ran {} frames", self.frames),
+            Ending::Halted => format!("halted in frame {}", self.frames),
+            Ending::Exited => format!("exited in frame {}", self.frames),
+            Ending::Failed(e) => format!("FAILED in frame {}, {}: {e}", self.frames, e.kind()),
+            Ending::Panicked(message) => format!("FAILED in frame {}, the interpreter panicked: {message}", self.frames),
+        };
+        return format!("{ending}, {} instructions, screen {:016x}", self.instructions, self.screen);
+    }
+
+    /// Runs the machine frame by frame, keeping count, until it's run them all or stops
+    fn run(&mut self, chip: &mut Chip8, frames: usize, keys: &[u16]) {
+        while self.frames < frames {
+            let held = keys.get(self.frames).copied().unwrap_or(0);
+            for key in 0..16 {
+                chip.set_key(key, held & 1 << key != 0);
+            }
+            for _ in 0..chip.cycles_per_frame() {
+                let pc = chip.pc();
+                let result = chip.execute();
+                self.instructions += 1;
+                if let Err(e) = result {
+                    self.ending = Ending::Failed(e);
+                    return;
+                }
+                if chip.pc() == pc && chip.opcode() >> 12 == 0x1 {
+                    self.ending = Ending::Halted;
+                    return;
+                }
+                if chip.exit_requested() {
+                    self.ending = Ending::Exited;
+                    return;
+                }
+            }
+            chip.tick_timers();
+            self.frames += 1;
+        }
+    }
+}
+
+/// Runs every start for up to `frames` frames at once across the cores, giving back how each
+/// went in the same order. Each run holds the keys its list of inputs says each frame, as a
+/// bitmask with bit n for key n, and nothing once the list runs out. There's one list for
+/// each start, or one for all of them, or none for nothing held at all
+pub fn run_many(starts: &[Start], frames: usize, inputs: &[Vec<u16>]) -> Vec<Outcome> {
+    return starts
+        .par_iter()
+        .enumerate()
+        .map(|(i, start)| {
+            let keys = match inputs {
+                [keys] => &keys[..],
+                inputs => inputs.get(i).map_or(&[][..], |keys| &keys[..]),
+            };
+            run(start, frames, keys)
+        })
+        .collect();
+}
+
+fn run(start: &Start, frames: usize, keys: &[u16]) -> Outcome {
+    let started = Instant::now();
+    let mut outcome = Outcome {
+        frames: 0,
+        instructions: 0,
+        ending: Ending::Ran,
+        screen: 0,
+        time: Duration::ZERO,
+        chip: Chip8::new(false),
+    };
+    let mut chip = match panic::catch_unwind(|| start.machine()) {
+        Ok(chip) => chip,
+        Err(panic) => {
+            outcome.ending = Ending::Panicked(panic_message(panic));
+            return outcome;
+        },
+    };
+    if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| outcome.run(&mut chip, frames, keys))) {
+        outcome.ending = Ending::Panicked(panic_message(panic));
+    }
+    outcome.screen = persist::hash(chip.framebuffer().bytes());
+    outcome.chip = chip;
+    outcome.time = started.elapsed();
+    return outcome;
+}
+
+fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+    return panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+}
+
+/// A batch's outcomes added up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub runs: usize,
+    /// How many runs ended each way
+    pub ran: usize,
+    pub halted: usize,
+    pub exited: usize,
+    pub failed: usize,
+    pub panicked: usize,
+    /// How many runs failed with each kind of error, by Chip8Error::kind
+    pub errors: BTreeMap<&'static str, usize>,
+    pub frames: u64,
+    pub instructions: u64,
+    /// How many different screens the runs ended on
+    pub screens: usize,
+    /// The time the runs took on their threads, added up, and the longest any one took
+    pub time: Duration,
+    pub longest: Duration,
+}
+
+impl Stats {
+    pub fn of(outcomes: &[Outcome]) -> Self {
+        let mut stats = Self { runs: outcomes.len(), ..Self::default() };
+        let mut screens = HashSet::new();
+        for outcome in outcomes {
+            match &outcome.ending {
+                Ending::Ran => stats.ran += 1,
+                Ending::Halted => stats.halted += 1,
+                Ending::Exited => stats.exited += 1,
+                Ending::Failed(e) => {
+                    stats.failed += 1;
+                    *stats.errors.entry(e.kind()).or_default() += 1;
+                },
+                Ending::Panicked(_) => stats.panicked += 1,
+            }
+            stats.frames += outcome.frames as u64;
+            stats.instructions += outcome.instructions;
+            screens.insert(outcome.screen);
+            stats.time += outcome.time;
+            stats.longest = stats.longest.max(outcome.time);
+        }
+        stats.screens = screens.len();
+        return stats;
+    }
+}
+
+/// A few lines on the batch, e.g. "12 runs: 10 ran, 1 halted, 0 exited, 1 failed, 0 panicked"
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} runs: {} ran, {} halted, {} exited, {} failed, {} panicked",
+            self.runs, self.ran, self.halted, self.exited, self.failed, self.panicked
+        )?;
+        for (kind, count) in &self.errors {
+            writeln!(f, "  {count} {kind}")?;
+        }
+        writeln!(f, "{} frames and {} instructions, ending on {} different screens", self.frames, self.instructions, self.screens)?;
+        let seconds = self.time.as_secs_f64();
+        let rate = if seconds > 0.0 { self.instructions as f64 / seconds } else { 0.0 };
+        return writeln!(
+            f,
+            "{:.2}s on the threads between them, {:.0} instructions a second each, the longest run {:.2}s",
+            seconds,
+            rate,
+            self.longest.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::movie::json_string;
+use crate::octo;
+
+// Measuring how fast the interpreter runs a rom, for chip8 --bench. The rom runs headless
+// and uncapped, and the machine's time is split three ways:
+//
+//   decode   fetching each instruction and working out what it is
+//   execute  running every instruction that doesn't draw
+//   draw     DXYN, clearing (00E0) and scrolling (00CN, 00DN, 00FB, 00FC), and handing
+//            the finished screen over at the end of each frame
+//
+// Timing every instruction takes time of its own, so a bench runs slower than the same rom
+// untimed. It's for comparing one build with another, not for quoting.
+//
+// Without a rom the bench runs SYNTHETIC, a fixed mix of sums, sprites and memory that never
+// waits on the keys or timers, so its numbers can be tracked from one version of the crate to
+// the next. --json gives them as a JSON object for scripts and CI to keep:
+//
+//   {"version": "0.1.0", "rom": "synthetic", "seconds": 5.000, "instructions": 123456789,
+//    "frames": 1234567, "draws": 1234, "ips": 24691357, "fps": 246913.5,
+//    "decode_ns_per_op": 9.1, "execute_ns_per_op": 12.3, "draw_ns_per_op": 250.7,
+//    "draws_per_second": 3988831, "error": null}
+//
+// decode_ns_per_op is how long decoding an instruction took, execute_ns_per_op running one
+// that doesn't draw and draw_ns_per_op one that does, counting the screen being handed over at
+// the end of each frame. draws_per_second is how many drawing instructions that comes to.
+
+/// The rom benched when there isn't one, see above
+const SYNTHETIC: &str = "
+: main
+  clear
+  loop
+    # Sums: shifts, logic and adds over the registers
+    v0 := 0
+    loop
+      v1 := v0
+      v1 += 7
+      v2 := v1
+      v2 <<= v2
+      v3 ^= v1
+      v3 += v2
+      v0 += 1
+      if v0 != 32 then
+    again
+
+    # Sprites: the screen tiled with 8x8 blocks
+    i := block
+    v4 := 0
+    v5 := 0
+    loop
+      sprite v4 v5 8
+      v4 += 8
+      if v4 == 64 then v5 += 8
+      if v4 == 64 then v4 := 0
+      if v5 != 32 then
+    again
+
+    # Memory: a number split into digits and back out of memory
+    i := digits
+    bcd v3
+    load v2
+    i := digits
+    save v2
+  again
+
+: block
+  0xFF 0x81 0xBD 0xA5 0xA5 0xBD 0x81 0xFF
+
+: digits
+  0 0 0
+";
+
+
+/// Where the machine's time has gone since Chip8::enable_timings
+#[derive(Clone, Debug, Default)]
+pub struct Timings {
+    pub decode: Duration,
+    pub execute: Duration,
+    pub draw: Duration,
+    pub instructions: u64,
+    /// How many of the instructions drew, cleared or scrolled the screen
+    pub draws: u64,
+    pub frames: u64,
+}
+
+impl Timings {
+    /// Adds an instruction that took decode to fetch and decode then ran for run
+    pub(crate) fn instruction(&mut self, opcode: u16, decode: Duration, run: Duration) {
+        self.instructions += 1;
+        self.decode += decode;
+        if draws(opcode) {
+            self.draws += 1;
+            self.draw += run;
+        } else {
+            self.execute += run;
+        }
+    }
+
+    /// Adds the end of a frame, which took took to hand the screen over
+    pub(crate) fn frame(&mut self, took: Duration) {
+        self.frames += 1;
+        self.draw += took;
+    }
+}
+
+/// Whether the instruction draws, clears or scrolls the screen
+pub(crate) fn draws(opcode: u16) -> bool {
+    return opcode >> 12 == 0xD || matches!(opcode, 0x00E0 | 0x00FB | 0x00FC) || opcode & 0xFFE0 == 0x00C0;
+}
+
+/// The rom benched when there isn't one
+pub fn synthetic_rom() -> Vec<u8> {
+    return octo::assemble(SYNTHETIC).expect("the synthetic rom assembles");
+}
+
+/// How a bench went
+pub struct Bench {
+    pub elapsed: Duration,
+    pub timings: Timings,
+    /// The error that stopped the rom early, if it was stopped
+    pub error: Option<Chip8Error>,
+}
+
+impl Bench {
+    pub fn instructions_per_second(&self) -> f64 {
+        return self.timings.instructions as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        return self.timings.frames as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON);
+    }
+
+    /// Drawing instructions run a second of the time spent drawing
+    pub fn draws_per_second(&self) -> f64 {
+        return self.timings.draws as f64 / self.timings.draw.as_secs_f64().max(f64::EPSILON);
+    }
+
+    /// The bench as a JSON object on one line, see the top of this file. rom is what was
+    /// benched, its file name or synthetic
+    pub fn json(&self, rom: &str) -> String {
+        let timings = &self.timings;
+        let per = |time: Duration, count: u64| time.as_nanos() as f64 / count.max(1) as f64;
+        return format!(
+            "{{\"version\": {}, \"rom\": {}, \"seconds\": {:.3}, \"instructions\": {}, \"frames\": {}, \"draws\": {}, \"ips\": {:.0}, \"fps\": {:.1}, \"decode_ns_per_op\": {:.1}, \"execute_ns_per_op\": {:.1}, \"draw_ns_per_op\": {:.1}, \"draws_per_second\": {:.0}, \"error\": {}}}",
+            json_string(env!("CARGO_PKG_VERSION")),
+            json_string(rom),
+            self.elapsed.as_secs_f64(),
+            timings.instructions,
+            timings.frames,
+            timings.draws,
+            self.instructions_per_second(),
+            self.frames_per_second(),
+            per(timings.decode, timings.instructions),
+            per(timings.execute, timings.instructions - timings.draws),
+            per(timings.draw, timings.draws),
+            self.draws_per_second(),
+            self.error.as_ref().map_or("null".to_string(), |e| json_string(&e.to_string())),
+        );
+    }
+
+    /// The bench as a single line of key=value pairs, for scripts tracking performance from
+    /// one release to the next. Times are in nanoseconds
+    pub fn summary(&self) -> String {
+        let timings = &self.timings;
+        return format!(
+            "seconds={:.3} instructions={} frames={} draws={} ips={:.0} fps={:.1} decode_ns={} execute_ns={} draw_ns={} error={}",
+            self.elapsed.as_secs_f64(),
+            timings.instructions,
+            timings.frames,
+            timings.draws,
+            self.instructions_per_second(),
+            self.frames_per_second(),
+            timings.decode.as_nanos(),
+            timings.execute.as_nanos(),
+            timings.draw.as_nanos(),
+            self.error.is_some(),
+        );
+    }
+}
+
+impl fmt::Display for Bench {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let timings = &self.timings;
+        writeln!(f, "Ran for {:.2}s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "  {:>14.0} instructions a second ({} in all)", self.instructions_per_second(), timings.instructions)?;
+        writeln!(f, "  {:>14.1} frames a second ({} in all)", self.frames_per_second(), timings.frames)?;
+        writeln!(f, "  {:>14.0} draws a second spent drawing ({} in all)", self.draws_per_second(), timings.draws)?;
+
+        let total = (timings.decode + timings.execute + timings.draw).as_secs_f64().max(f64::EPSILON);
+        let per_instruction = |time: Duration| time.as_nanos() as f64 / timings.instructions.max(1) as f64;
+        writeln!(f, "Time spent:")?;
+        for (name, time) in [("decode", timings.decode), ("execute", timings.execute), ("draw", timings.draw)] {
+            let share = time.as_secs_f64() / total * 100.0;
+            writeln!(f, "  {name:<8} {:>9.3}s {share:>5.1}%  {:>7.1}ns an instruction", time.as_secs_f64(), per_instruction(time))?;
+        }
+        if let Some(e) = &self.error {
+            writeln!(f, "Stopped early: {e}")?;
+        }
+        return Ok(());
+    }
+}
+
+/// Runs the machine as fast as it goes for length, or until an instruction fails
+pub fn bench(chip: &mut Chip8, length: Duration) -> Bench {
+    chip.enable_timings();
+    let start = Instant::now();
+    let mut error = None;
+    while start.elapsed() < length {
+        if let Err(e) = chip.run_frame() {
+            error = Some(e);
+            break;
+        }
+    }
+
+    return Bench {
+        elapsed: start.elapsed(),
+        timings: chip.timings().cloned().unwrap_or_default(),
+        error,
+    };
+}
@@ -0,0 +1,161 @@
+use ::bevy::image::ImageSampler;
+use ::bevy::prelude::*;
+use ::bevy::render::render_asset::RenderAssetUsages;
+use ::bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::platform::Platform;
+
+// Embeds a CHIP-8 game in a Bevy app. The plugin loads the rom into a Chip8, which it
+// inserts as a resource for other systems to read memory out of or poke, and spawns a
+// sprite showing the screen that can be moved and scaled like any other:
+//
+//   App::new()
+//       .add_plugins((DefaultPlugins, Chip8Plugin::new(&rom)))
+//       .add_systems(Startup, |mut commands: Commands| { commands.spawn(Camera2d); })
+//       .run();
+//
+// The machine runs at 60 frames a second whatever the app's frame rate, and the keys map
+// onto the keypad through the Chip8Keymap resource, the usual way by default:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+
+const KEYMAP: [(KeyCode, u8); 16] = [
+    (KeyCode::Digit1, 0x1), (KeyCode::Digit2, 0x2), (KeyCode::Digit3, 0x3), (KeyCode::Digit4, 0xC),
+    (KeyCode::KeyQ, 0x4), (KeyCode::KeyW, 0x5), (KeyCode::KeyE, 0x6), (KeyCode::KeyR, 0xD),
+    (KeyCode::KeyA, 0x7), (KeyCode::KeyS, 0x8), (KeyCode::KeyD, 0x9), (KeyCode::KeyF, 0xE),
+    (KeyCode::KeyZ, 0xA), (KeyCode::KeyX, 0x0), (KeyCode::KeyC, 0xB), (KeyCode::KeyV, 0xF),
+];
+
+const FRAME: f32 = 1.0 / 60.0;
+
+
+pub struct Chip8Plugin {
+    rom: Vec<u8>,
+    platform: Platform,
+    /// The size of the sprite in world units, kept whatever resolution the rom switches to
+    size: Vec2,
+}
+
+impl Chip8Plugin {
+    pub fn new(rom: &[u8]) -> Self {
+        return Self { rom: rom.to_vec(), platform: Platform::Chip8, size: Vec2::new(640.0, 320.0) };
+    }
+
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        return self;
+    }
+
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        return self;
+    }
+}
+
+impl Plugin for Chip8Plugin {
+    fn build(&self, app: &mut App) {
+        let mut chip = Chip8::with_platform(self.platform, false);
+        chip.load_rom_bytes(&self.rom);
+
+        let size = self.size;
+        app.insert_resource(chip)
+            .init_resource::<Chip8Keymap>()
+            .init_resource::<Chip8Status>()
+            .add_systems(Startup, move |commands: Commands, images: ResMut<Assets<Image>>, chip: Res<Chip8>| {
+                spawn_screen(commands, images, chip, size);
+            })
+            .add_systems(Update, (map_keys, step, update_screen).chain());
+    }
+}
+
+impl Resource for Chip8 {}
+
+/// Which keys press which keypad keys
+#[derive(Resource)]
+pub struct Chip8Keymap(pub Vec<(KeyCode, u8)>);
+
+impl Default for Chip8Keymap {
+    fn default() -> Self {
+        return Self(KEYMAP.to_vec());
+    }
+}
+
+/// Whether the machine is running. Setting paused stops it, and an error stops it until
+/// it's cleared
+#[derive(Resource, Default)]
+pub struct Chip8Status {
+    pub paused: bool,
+    pub error: Option<Chip8Error>,
+}
+
+/// Marks the sprite showing the screen
+#[derive(Component)]
+pub struct Chip8Screen;
+
+fn spawn_screen(mut commands: Commands, mut images: ResMut<Assets<Image>>, chip: Res<Chip8>, size: Vec2) {
+    let framebuffer = chip.framebuffer();
+    let mut image = Image::new_fill(
+        Extent3d { width: framebuffer.width() as u32, height: framebuffer.height() as u32, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[0, 0, 0, 0xFF],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // Keep the pixels sharp when the sprite is scaled up
+    image.sampler = ImageSampler::nearest();
+
+    let sprite = Sprite { image: images.add(image), custom_size: Some(size), ..default() };
+    commands.spawn((sprite, Chip8Screen));
+}
+
+fn map_keys(mut chip: ResMut<Chip8>, keymap: Res<Chip8Keymap>, input: Res<ButtonInput<KeyCode>>) {
+    let mut held = [false; 16];
+    for (key, chip_key) in &keymap.0 {
+        held[*chip_key as usize & 0xF] |= input.pressed(*key);
+    }
+    for (chip_key, held) in held.into_iter().enumerate() {
+        chip.set_key(chip_key as u8, held);
+    }
+}
+
+fn step(mut chip: ResMut<Chip8>, mut status: ResMut<Chip8Status>, time: Res<Time>, mut pending: Local<f32>) {
+    if status.paused || status.error.is_some() {
+        *pending = 0.0;
+        return;
+    }
+
+    // Don't try to catch up on more than a few frames after a stall
+    *pending = (*pending + time.delta_secs()).min(0.25);
+    while *pending >= FRAME {
+        *pending -= FRAME;
+        if let Err(e) = chip.run_frame() {
+            status.error = Some(e);
+            return;
+        }
+    }
+}
+
+fn update_screen(chip: Res<Chip8>, mut images: ResMut<Assets<Image>>, screens: Query<&Sprite, With<Chip8Screen>>) {
+    let framebuffer = chip.framebuffer();
+    let (width, height) = (framebuffer.width() as u32, framebuffer.height() as u32);
+    let rgb = chip.screen_rgb();
+
+    for sprite in &screens {
+        let Some(image) = images.get_mut(&sprite.image) else {
+            continue;
+        };
+
+        // The resolution can change as the rom runs (SUPER-CHIP, Mega-Chip)
+        if image.size() != UVec2::new(width, height) {
+            image.resize(Extent3d { width, height, depth_or_array_layers: 1 });
+        }
+        for (pixel, rgb) in image.data.chunks_exact_mut(4).zip(&rgb) {
+            pixel.copy_from_slice(&[(rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 0xFF]);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::Range;
+
+use crate::bench;
+use crate::chip::Chip8;
+use crate::isa;
+
+// How each frame's instructions were spent, for the debugger's frames command. Every frame
+// runs the same number of instructions, so what matters to a rom's author is how many of
+// them did anything: the rest went round a loop waiting for the next frame, which is the
+// headroom the rom has before it starts running slow on interpreters that go at that speed.
+//
+// Instructions are counted as draws (bench.rs's, so the same as --bench), as waiting, or as
+// the rom's logic. Waiting is FX0A with nothing pressed, and going round a short loop made
+// only of timer and key checks and skips, ending in a jump back, the usual way of waiting for
+// the delay timer:
+//
+//     loop: F007  V0 := delay
+//           3000  if V0 == 0 then skip
+//           1xxx  jump loop
+
+/// How many frames are kept, 10 seconds' worth
+const HISTORY: usize = 600;
+
+/// How many instructions back a jump can go and still be a wait loop
+const MAX_LOOP: u16 = 3;
+
+/// The widest a bar gets, in characters
+const BAR: usize = 60;
+
+
+#[derive(Default)]
+struct Frame {
+    number: u64,
+    draws: usize,
+    logic: usize,
+    waiting: usize,
+    /// How many times each instruction ran, by mnemonic
+    mnemonics: BTreeMap<&'static str, usize>,
+}
+
+#[derive(Default)]
+pub struct Budget {
+    frames: VecDeque<Frame>,
+    current: Frame,
+    /// The wait loops found so far
+    wait_loops: Vec<Range<u16>>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Counts an instruction that ran at pc, leaving the machine as it is now
+    pub fn instruction(&mut self, chip: &Chip8, pc: u16, opcode: u16) {
+        if bench::draws(opcode) {
+            self.current.draws += 1;
+        } else if self.waits(chip, pc, opcode) {
+            self.current.waiting += 1;
+        } else {
+            self.current.logic += 1;
+        }
+        let mnemonic = isa::lookup(opcode, chip.platform()).map_or("unknown", |info| info.mnemonic);
+        *self.current.mnemonics.entry(mnemonic).or_insert(0) += 1;
+    }
+
+    /// Finishes frame `number`. Frames from it on are dropped first, having been gone back over
+    pub fn end_frame(&mut self, number: u64) {
+        while self.frames.back().is_some_and(|frame| frame.number >= number) {
+            self.frames.pop_back();
+        }
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+        let frame = std::mem::take(&mut self.current);
+        self.frames.push_back(Frame { number, ..frame });
+    }
+
+    /// Forgets the frame that's partly run, when the machine's been moved somewhere else
+    pub fn restart_frame(&mut self) {
+        self.current = Frame::default();
+    }
+
+    /// The last `count` frames as a bar each, with budget instructions to a frame
+    pub fn graph(&self, count: usize, budget: usize) -> String {
+        if self.frames.is_empty() {
+            return "No frames have finished yet\n".to_string();
+        }
+        let mut out = format!("{budget} instructions a frame: # draws, = logic, . waiting\n");
+        let frames: Vec<&Frame> = self.frames.iter().rev().take(count).rev().collect();
+        // A character an instruction, or fewer when a frame's too many to fit
+        let scale = |n: usize| (n * BAR).div_ceil(budget.max(BAR));
+        for frame in &frames {
+            // Scaled by where each part ends so the rounding doesn't add up
+            let (draws, used) = (scale(frame.draws), scale(frame.draws + frame.logic));
+            let all = scale(frame.draws + frame.logic + frame.waiting);
+            let bar = format!("{}{}{}", "#".repeat(draws), "=".repeat(used - draws), ".".repeat(all - used));
+            let mut top: Vec<(&&str, &usize)> = frame.mnemonics.iter().collect();
+            top.sort_by(|a, b| b.1.cmp(a.1));
+            let top: Vec<String> = top.iter().take(3).map(|(mnemonic, n)| format!("{mnemonic} ({n})")).collect();
+            let used = frame.draws + frame.logic;
+            out += &format!("{:>7} {bar:<width$} {used:>4}/{budget}  {}\n", frame.number, top.join(", "), width = scale(budget));
+        }
+
+        let used: usize = frames.iter().map(|frame| frame.draws + frame.logic).sum();
+        let busy = frames.iter().filter(|frame| frame.waiting == 0).count();
+        out += &format!(
+            "{:.1} of {budget} used on average, {busy} of {} frames never waited\n",
+            used as f64 / frames.len() as f64,
+            frames.len()
+        );
+        return out;
+    }
+
+    fn waits(&mut self, chip: &Chip8, pc: u16, opcode: u16) -> bool {
+        if opcode & 0xF0FF == 0xF00A && chip.pc() == pc {
+            return true;
+        }
+        let to = opcode & 0xFFF;
+        if opcode >> 12 == 0x1 && to <= pc && pc - to <= MAX_LOOP * 2 {
+            let polls = (to..pc).step_by(2).all(|addr| match chip.read_mem(addr as u32, 2) {
+                [hi, lo] => polls(u16::from_be_bytes([*hi, *lo])),
+                _ => false,
+            });
+            if polls && !self.wait_loops.contains(&(to..pc + 2)) {
+                self.wait_loops.push(to..pc + 2);
+            }
+        }
+        return self.wait_loops.iter().any(|wait_loop| wait_loop.contains(&pc));
+    }
+}
+
+/// Whether the instruction only reads the timers or keys, or skips
+fn polls(opcode: u16) -> bool {
+    return matches!(opcode & 0xF0FF, 0xF007 | 0xF00A | 0xE09E | 0xE0A1)
+        || matches!(opcode >> 12, 0x3 | 0x4)
+        || matches!(opcode & 0xF00F, 0x5000 | 0x9000);
+}
@@ -0,0 +1,46 @@
+use crate::octo;
+
+// A few roms built into the binary, so there's something to play straight after installing
+// without hunting down roms first. They're Octo source (in bundled/), written for this
+// crate and in the public domain, and assembled when they're loaded. Anywhere a rom path is
+// taken they're `builtin:<name>`, `chip8 roms` lists them, and the gui has them in its
+// Roms menu.
+
+
+/// A built in rom
+pub struct Bundled {
+    pub name: &'static str,
+    pub description: &'static str,
+    source: &'static str,
+}
+
+pub static ROMS: [Bundled; 4] = [
+    Bundled { name: "logo", description: "CHIP-8 in big letters", source: include_str!("bundled/logo.8o") },
+    Bundled {
+        name: "tests",
+        description: "checks the base instructions, a tick or cross for each",
+        source: include_str!("bundled/tests.8o"),
+    },
+    Bundled {
+        name: "tour",
+        description: "a little of everything, the program chip8 tour steps through",
+        source: include_str!("bundled/tour.8o"),
+    },
+    Bundled {
+        name: "wall",
+        description: "one player pong, 1 and Q move the paddle",
+        source: include_str!("bundled/wall.8o"),
+    },
+];
+
+impl Bundled {
+    pub fn rom(&self) -> Vec<u8> {
+        return octo::assemble(self.source).expect("the built in roms assemble");
+    }
+}
+
+/// The built in rom a path names, if it's `builtin:<name>`
+pub fn find(path: &str) -> Option<&'static Bundled> {
+    let name = path.strip_prefix("builtin:")?;
+    return ROMS.iter().find(|rom| rom.name == name);
+}
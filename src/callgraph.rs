@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::flow::{self, Flow};
+use crate::isa::{self, Timing};
+use crate::platform::Platform;
+use crate::symbols::SymbolTable;
+
+// The subroutines in a rom and which call which (chip8 calls), for finding your way round an
+// old rom or documenting a new one. It goes by the control flow (flow.rs): the program from
+// where it starts is main, and everything 2NNN calls is a subroutine. A subroutine's code is
+// whatever it can reach before returning, without going into the subroutines it calls, so
+// code shared by two (one jumping into the other's tail) counts towards both.
+//
+// The report lists each with its size, who calls it and what it calls, and whether it draws
+// or reads the keys itself. The graph's in Graphviz's DOT, for `dot -Tsvg`.
+
+
+/// One subroutine, or main
+pub struct Subroutine {
+    pub addr: u16,
+    pub name: String,
+    /// How many bytes of instructions it reaches
+    pub size: usize,
+    /// The subroutines it calls, with the addresses it calls each from
+    pub calls: BTreeMap<u16, Vec<u16>>,
+    /// The subroutines that call it, with the addresses they call it from
+    pub callers: BTreeMap<u16, Vec<u16>>,
+    /// Whether any of its instructions draw, clear or scroll the screen
+    pub draws: bool,
+    /// Whether any of its instructions read the keypad
+    pub reads_keys: bool,
+    /// Whether it has computed jumps, which are only followed into a table of jumps
+    pub computed: bool,
+}
+
+pub struct CallGraph {
+    pub name: String,
+    pub platform: Platform,
+    /// Every subroutine and main, by address
+    pub subroutines: BTreeMap<u16, Subroutine>,
+}
+
+/// Finds the subroutines in the rom as the platform would run it, naming them from the symbols
+/// if given
+pub fn call_graph(name: &str, rom: &[u8], platform: Platform, symbols: Option<&SymbolTable>) -> CallGraph {
+    let flow = flow::trace(rom, platform);
+    let mut graph = CallGraph { name: name.to_string(), platform, subroutines: BTreeMap::new() };
+    for addr in [flow.entry].into_iter().chain(flow.subroutines.iter().copied()) {
+        let name = match symbols.and_then(|symbols| symbols.lookup(addr)) {
+            Some((name, 0)) => name.to_string(),
+            _ if addr == flow.entry => "main".to_string(),
+            _ => format!("sub_{addr:04X}"),
+        };
+        graph.subroutines.insert(addr, subroutine(rom, platform, &flow, addr, name));
+    }
+
+    let calls: Vec<(u16, u16, Vec<u16>)> = graph
+        .subroutines
+        .values()
+        .flat_map(|caller| caller.calls.iter().map(|(callee, sites)| (caller.addr, *callee, sites.clone())))
+        .collect();
+    for (caller, callee, sites) in calls {
+        if let Some(callee) = graph.subroutines.get_mut(&callee) {
+            callee.callers.insert(caller, sites);
+        }
+    }
+    return graph;
+}
+
+/// Follows a subroutine from addr to its returns, stepping over the calls it makes
+fn subroutine(rom: &[u8], platform: Platform, flow: &Flow, addr: u16, name: String) -> Subroutine {
+    let mut subroutine = Subroutine {
+        addr,
+        name,
+        size: 0,
+        calls: BTreeMap::new(),
+        callers: BTreeMap::new(),
+        draws: false,
+        reads_keys: false,
+        computed: false,
+    };
+    let mut seen = BTreeSet::new();
+    let mut queue = vec![addr];
+    while let Some(at) = queue.pop() {
+        let Some(&opcode) = flow.code.get(&at).filter(|_| seen.insert(at)) else {
+            continue;
+        };
+        subroutine.size += isa::length(opcode, platform);
+        if let Some(info) = isa::lookup(opcode, platform) {
+            subroutine.draws |= info.timing == Timing::Draw;
+            subroutine.reads_keys |= matches!(info.mnemonic.split(' ').next(), Some("SKP" | "SKNP" | "SKP2" | "SKNP2"))
+                || info.timing == Timing::WaitsForKey;
+        }
+        subroutine.computed |= flow.computed.contains(&at);
+
+        let (to, call) = flow.next(rom, platform, at, opcode);
+        if let Some(call) = call {
+            subroutine.calls.entry(call).or_default().push(at);
+        }
+        queue.extend(to);
+    }
+    return subroutine;
+}
+
+impl CallGraph {
+    /// The graph in Graphviz's DOT, each subroutine a box with its address, size and whether it
+    /// draws or reads the keys, and each call an arrow marked with how many places make it
+    pub fn dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph \"{}\" {{", self.name.replace('"', "'")).unwrap();
+        writeln!(dot, "    node [shape=box, fontname=monospace];").unwrap();
+        for subroutine in self.subroutines.values() {
+            let mut label = format!("{}\\n{:04X}, {} bytes", subroutine.name, subroutine.addr, subroutine.size);
+            let does = subroutine.does();
+            if !does.is_empty() {
+                write!(label, "\\n{does}").unwrap();
+            }
+            writeln!(dot, "    \"{}\" [label=\"{label}\"];", subroutine.name).unwrap();
+        }
+        for subroutine in self.subroutines.values() {
+            for (callee, sites) in &subroutine.calls {
+                let Some(callee) = self.subroutines.get(callee) else {
+                    continue;
+                };
+                let label = if sites.len() > 1 { format!(" [label=\"{}\"]", sites.len()) } else { String::new() };
+                writeln!(dot, "    \"{}\" -> \"{}\"{label};", subroutine.name, callee.name).unwrap();
+            }
+        }
+        writeln!(dot, "}}").unwrap();
+        return dot;
+    }
+
+    /// A summary of each subroutine: its size, callers and calls and what it does
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        let count = self.subroutines.len() - 1;
+        writeln!(report, "{} as {}: main and {count} subroutine{}", self.name, self.platform.name(), if count == 1 { "" } else { "s" }).unwrap();
+        for subroutine in self.subroutines.values() {
+            let mut heading = format!("\n{} at {:04X}, {} bytes", subroutine.name, subroutine.addr, subroutine.size);
+            let does = subroutine.does();
+            if !does.is_empty() {
+                write!(heading, ", {does}").unwrap();
+            }
+            writeln!(report, "{heading}").unwrap();
+            for (caller, sites) in &subroutine.callers {
+                writeln!(report, "  called by {} from {}", self.subroutines[caller].name, addresses(sites)).unwrap();
+            }
+            for (callee, sites) in &subroutine.calls {
+                let callee = self.subroutines.get(callee).map_or(format!("{callee:04X}"), |callee| callee.name.clone());
+                writeln!(report, "  calls {callee} from {}", addresses(sites)).unwrap();
+            }
+        }
+        return report;
+    }
+}
+
+impl Subroutine {
+    /// What it does that's worth knowing at a glance, e.g. "draws, reads keys"
+    fn does(&self) -> String {
+        let does = [(self.draws, "draws"), (self.reads_keys, "reads keys"), (self.computed, "computed jumps")];
+        return does.iter().filter(|(does, _)| *does).map(|(_, what)| *what).collect::<Vec<_>>().join(", ");
+    }
+}
+
+fn addresses(addrs: &[u16]) -> String {
+    let mut addrs = addrs.to_vec();
+    addrs.sort();
+    return addrs.iter().map(|addr| format!("{addr:04X}")).collect::<Vec<_>>().join(", ");
+}
@@ -0,0 +1,417 @@
+use std::path::Path;
+
+use crate::frontend::rotation::Rotation;
+use crate::octo;
+use crate::platform::Platform;
+
+// Octo cartridges, the GIFs Octo saves games as. The picture is the game's label, and the
+// game itself is hidden in the pixels: every pixel's palette index carries a nibble of it in
+// its low 4 bits, high nibble first, across every frame in turn. What that spells out is
+//
+//   length    4 bytes, big endian
+//   payload   length bytes of JSON: {"options": {...}, "program": "<Octo source>"}
+//
+// so a cartridge is the source rather than a rom, and is run through the assembler in
+// octo.rs. Of the options, tickrate is the speed, maxSize says which platform the game
+// was written for (3583 or 3584 for SUPER-CHIP, more than that for XO-CHIP) and
+// screenRotation how far the screen's turned.
+//
+// Only what Octo writes is read: no interlacing, and frames are taken whole rather than
+// drawn over the ones before.
+
+/// The biggest code GIF's LZW gets to, 12 bits
+const MAX_CODES: usize = 4096;
+
+
+/// A rom as loaded from a file, with what an Octo cartridge said about running it
+pub struct Cart {
+    pub rom: Vec<u8>,
+    /// Instructions per frame
+    pub speed: Option<usize>,
+    pub platform: Option<Platform>,
+    pub rotation: Option<Rotation>,
+}
+
+/// Whether a file is a GIF, and so possibly an Octo cartridge rather than a rom
+pub fn is_cart(bytes: &[u8]) -> bool {
+    return bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a");
+}
+
+/// Loads a rom file, which is taken as it is unless it's an Octo cartridge
+pub fn load(bytes: Vec<u8>) -> Result<Cart, String> {
+    if !is_cart(&bytes) {
+        return Ok(Cart { rom: bytes, speed: None, platform: None, rotation: None });
+    }
+
+    let pixels = gif_pixels(&bytes)?;
+    let data: Vec<u8> = pixels.chunks_exact(2).map(|pair| (pair[0] & 0xF) << 4 | (pair[1] & 0xF)).collect();
+    let Some((length, payload)) = data.split_first_chunk::<4>() else {
+        return Err("the GIF has nothing hidden in it, it isn't an Octo cartridge".to_string());
+    };
+    let payload = payload
+        .get(..u32::from_be_bytes(*length) as usize)
+        .ok_or("the GIF is shorter than its payload, it isn't an Octo cartridge")?;
+    let text = std::str::from_utf8(payload).map_err(|_| "the cartridge's payload isn't text".to_string())?;
+    let json = Json::parse(text).map_err(|e| format!("the cartridge's payload isn't valid JSON, {e}"))?;
+
+    let program = json.get("program").and_then(Json::as_str).ok_or("the cartridge has no program")?;
+    let rom = octo::assemble(program).map_err(|e| format!("the cartridge's program doesn't assemble, {e}"))?;
+    let options = json.get("options");
+    let number = |name: &str| options.and_then(|options| options.get(name)).and_then(Json::as_number);
+    let platform = match number("maxSize") {
+        Some(size) if size > 3584.0 => Some(Platform::XoChip),
+        Some(size) if size >= 3583.0 => Some(Platform::SuperChip),
+        _ => None,
+    };
+    let speed = number("tickrate").filter(|tickrate| *tickrate >= 1.0).map(|tickrate| tickrate as usize);
+    let rotation = number("screenRotation").and_then(|degrees| Rotation::from_degrees(degrees as u32));
+
+    return Ok(Cart { rom, speed, platform, rotation });
+}
+
+/// Reads a rom file, taking the first rom out of it if it's a ZIP archive (with the zip
+/// feature), for files dropped on a window
+pub fn read(path: &Path) -> Result<Cart, String> {
+    #[cfg_attr(not(feature = "zip"), allow(unused_mut))]
+    let mut bytes = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    #[cfg(feature = "zip")]
+    if crate::zip::is_zip(&bytes) {
+        bytes = crate::zip::extract(&bytes, None).map_err(|e| format!("{}: {e}", path.display()))?;
+    }
+    return load(bytes).map_err(|e| format!("{}: {e}", path.display()));
+}
+
+/// The palette indices of every frame of a GIF, one after another
+fn gif_pixels(gif: &[u8]) -> Result<Vec<u8>, String> {
+    let truncated = || "the GIF is cut short".to_string();
+    let byte = |at: usize| gif.get(at).copied().ok_or_else(truncated);
+    let table_size = |flags: u8| if flags & 0x80 != 0 { 3 << ((flags & 7) + 1) } else { 0 };
+
+    // The header and screen descriptor, then the global palette if there is one
+    let mut at = 13 + table_size(byte(10)?);
+    let mut pixels = Vec::new();
+    loop {
+        match byte(at)? {
+            // An extension, which is skipped
+            0x21 => at = skip_blocks(gif, at + 2).ok_or_else(truncated)?,
+            0x2C => {
+                at += 10 + table_size(byte(at + 9)?);
+                let min_size = byte(at)?;
+                let end = skip_blocks(gif, at + 1).ok_or_else(truncated)?;
+                let mut data = Vec::new();
+                let mut block = at + 1;
+                while gif[block] != 0 {
+                    data.extend_from_slice(&gif[block + 1..block + 1 + gif[block] as usize]);
+                    block += 1 + gif[block] as usize;
+                }
+                lzw_decode(min_size, &data, &mut pixels)?;
+                at = end;
+            },
+            0x3B => return Ok(pixels),
+            block => return Err(format!("unknown GIF block {block:02X}")),
+        }
+    }
+}
+
+/// Where the run of sub-blocks starting at at ends
+fn skip_blocks(gif: &[u8], mut at: usize) -> Option<usize> {
+    loop {
+        let size = *gif.get(at)? as usize;
+        at += 1 + size;
+        if size == 0 {
+            return Some(at);
+        }
+    }
+}
+
+/// Decodes GIF's variable width LZW onto the end of out
+fn lzw_decode(min_size: u8, data: &[u8], out: &mut Vec<u8>) -> Result<(), String> {
+    if !(1..=11).contains(&min_size) {
+        return Err(format!("invalid LZW code size {min_size}"));
+    }
+    let clear = 1usize << min_size;
+    let end = clear + 1;
+
+    // Every code's string is the string of the code before it plus a byte
+    let mut prefix = vec![0u16; MAX_CODES];
+    let mut suffix = vec![0u8; MAX_CODES];
+    let mut first = vec![0u8; MAX_CODES];
+    let mut length = vec![0usize; MAX_CODES];
+    for code in 0..clear {
+        (suffix[code], first[code], length[code]) = (code as u8, code as u8, 1);
+    }
+
+    let mut size = min_size as usize + 1;
+    let mut next = end + 1;
+    let mut previous: Option<usize> = None;
+    let mut bit = 0;
+    while bit + size <= data.len() * 8 {
+        let mut code = 0;
+        for i in 0..size {
+            code |= ((data[(bit + i) / 8] >> ((bit + i) % 8)) as usize & 1) << i;
+        }
+        bit += size;
+
+        if code == clear {
+            (size, next, previous) = (min_size as usize + 1, end + 1, None);
+            continue;
+        }
+        if code == end {
+            break;
+        }
+
+        // A code one past the last is the previous string plus its own first byte
+        let written = match previous {
+            Some(previous) if code == next => previous,
+            _ if code < next => code,
+            _ => return Err(format!("invalid LZW code {code}")),
+        };
+        let start = out.len();
+        out.resize(start + length[written], 0);
+        let mut string = written;
+        for i in (start..out.len()).rev() {
+            out[i] = suffix[string];
+            string = prefix[string] as usize;
+        }
+        if code == next {
+            out.push(first[written]);
+        }
+
+        // Each code after the first adds the previous string plus this one's first byte
+        if let Some(previous) = previous.filter(|_| next < MAX_CODES) {
+            let byte = if code == next { first[previous] } else { first[code] };
+            (prefix[next], suffix[next], first[next]) = (previous as u16, byte, first[previous]);
+            length[next] = length[previous] + 1;
+            next += 1;
+            if next == 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+        previous = Some(code);
+    }
+    return Ok(());
+}
+
+/// As much of JSON as a cartridge's payload needs
+enum Json {
+    /// null, true, false or an array, none of which the options have that are used
+    Other,
+    Number(f64),
+    String(String),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut chars = text.chars().peekable();
+        let value = Self::value(&mut chars)?;
+        skip_space(&mut chars);
+        if let Some(c) = chars.next() {
+            return Err(format!("unexpected '{c}' after the end"));
+        }
+        return Ok(value);
+    }
+
+    fn value(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Self, String> {
+        skip_space(chars);
+        let literal = |chars: &mut std::iter::Peekable<std::str::Chars>, word: &str, value: Json| {
+            if !word.chars().all(|c| chars.next() == Some(c)) {
+                return Err(format!("expected {word}"));
+            }
+            return Ok(value);
+        };
+        return match chars.peek() {
+            Some('n') => literal(chars, "null", Json::Other),
+            Some('t') => literal(chars, "true", Json::Other),
+            Some('f') => literal(chars, "false", Json::Other),
+            Some('"') => Ok(Json::String(string(chars)?)),
+            Some('[') => {
+                chars.next();
+                let mut first = true;
+                loop {
+                    skip_space(chars);
+                    if chars.next_if_eq(&']').is_some() {
+                        return Ok(Json::Other);
+                    }
+                    if !first && chars.next() != Some(',') {
+                        return Err("expected ',' or ']'".to_string());
+                    }
+                    Self::value(chars)?;
+                    first = false;
+                }
+            },
+            Some('{') => {
+                chars.next();
+                let mut members = Vec::new();
+                loop {
+                    skip_space(chars);
+                    if chars.next_if_eq(&'}').is_some() {
+                        return Ok(Json::Object(members));
+                    }
+                    if !members.is_empty() && chars.next() != Some(',') {
+                        return Err("expected ',' or '}'".to_string());
+                    }
+                    skip_space(chars);
+                    let name = string(chars)?;
+                    skip_space(chars);
+                    if chars.next() != Some(':') {
+                        return Err(format!("expected ':' after \"{name}\""));
+                    }
+                    members.push((name, Self::value(chars)?));
+                }
+            },
+            Some(_) => {
+                let mut number = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                    number.push(c);
+                }
+                number.parse().map(Json::Number).map_err(|_| format!("invalid value '{number}'"))
+            },
+            None => Err("unexpected end".to_string()),
+        };
+    }
+
+    fn get(&self, name: &str) -> Option<&Json> {
+        let Json::Object(members) = self else {
+            return None;
+        };
+        return members.iter().find(|(member, _)| member == name).map(|(_, value)| value);
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        return match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        };
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        return match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        };
+    }
+}
+
+fn skip_space(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+    if chars.next() != Some('"') {
+        return Err("expected a string".to_string());
+    }
+    let mut s = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(s),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => s.push('\n'),
+                't' => s.push('\t'),
+                'r' => s.push('\r'),
+                'b' => s.push('\u{8}'),
+                'f' => s.push('\u{c}'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape \\u{hex}"))?;
+                    s.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                },
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Codes written between clears, few enough that the code size never grows past 5 bits
+    const RUN: usize = 10;
+
+    /// A 16 colour GIF with one frame of pixels, LZW coded a pixel a code
+    fn gif(pixels: &[u8]) -> Vec<u8> {
+        let mut codes = Vec::new();
+        for (i, pixel) in pixels.iter().enumerate() {
+            if i % RUN == 0 {
+                codes.push(16);
+            }
+            codes.push(*pixel as u32);
+        }
+        codes.push(17);
+        let mut data = vec![0u8; (codes.len() * 5).div_ceil(8)];
+        for (i, code) in codes.iter().enumerate() {
+            for bit in 0..5 {
+                data[(i * 5 + bit) / 8] |= ((code >> bit & 1) as u8) << ((i * 5 + bit) % 8);
+            }
+        }
+
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([pixels.len() as u8, 0, 1, 0, 0x83, 0, 0]);
+        gif.extend([0; 48]);
+        // A graphic control extension, which is skipped
+        gif.extend([0x21, 0xF9, 4, 0, 0, 0, 0, 0]);
+        gif.extend([0x2C, 0, 0, 0, 0, pixels.len() as u8, 0, 1, 0, 0, 4]);
+        for block in data.chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.extend([0, 0x3B]);
+        return gif;
+    }
+
+    /// A cartridge hiding payload, with length bytes of it claimed
+    fn cartridge(payload: &str, length: u32) -> Vec<u8> {
+        let data: Vec<u8> = length.to_be_bytes().iter().chain(payload.as_bytes()).copied().collect();
+        return gif(&data.iter().flat_map(|byte| [byte >> 4, byte & 0xF]).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn a_cartridge_gives_up_its_program_and_options() {
+        let payload = r#"{"options": {"tickrate": 20, "maxSize": 3584, "screenRotation": 90, "touchInputMode": "none",
+            "enableXO": false, "palette": [1, 2]}, "program": ": main v0 := 1 # \"quoted\"\n loop again"}"#;
+        let cart = load(cartridge(payload, payload.len() as u32)).expect("the cartridge loads");
+        assert_eq!(cart.rom, [0x60, 0x01, 0x12, 0x02]);
+        assert_eq!((cart.speed, cart.platform), (Some(20), Some(Platform::SuperChip)));
+        assert_eq!(cart.rotation.map(Rotation::degrees), Some(90));
+
+        // A plain rom is left as it is, and a GIF without options still loads
+        assert_eq!(load(vec![0x00, 0xE0]).map(|cart| cart.rom), Ok(vec![0x00, 0xE0]));
+        let payload = r#"{"program": ": main exit"}"#;
+        let cart = load(cartridge(payload, payload.len() as u32)).expect("the cartridge loads");
+        assert_eq!((cart.rom, cart.speed, cart.platform), (vec![0x00, 0xFD], None, None));
+    }
+
+    #[test]
+    fn malformed_cartridges_are_errors() {
+        let good = r#"{"program": ": main exit"}"#;
+        let errors = [
+            (cartridge(good, 1000), "shorter than its payload"),
+            (cartridge("{\"program\": ", 12), "isn't valid JSON"),
+            (cartridge("{\"program\": 5}", 14), "has no program"),
+            (cartridge("{\"program\": \"jump\"}", 19), "doesn't assemble"),
+            (gif(&[1, 2]), "nothing hidden in it"),
+            (gif(&[]), "nothing hidden in it"),
+        ];
+        for (bytes, error) in errors {
+            let result = load(bytes).map(|cart| cart.rom);
+            assert!(result.as_ref().is_err_and(|e| e.contains(error)), "{error}: {result:?}");
+        }
+
+        let good = cartridge(good, good.len() as u32);
+        let cut = good[..good.len() - 20].to_vec();
+        assert_eq!(load(cut).err(), Some("the GIF is cut short".to_string()));
+        let mut unknown = good.clone();
+        let end = unknown.len() - 1;
+        unknown[end] = 0x99;
+        assert_eq!(load(unknown).err(), Some("unknown GIF block 99".to_string()));
+        let mut bad_size = good.clone();
+        // The LZW code size, after the header, palette, extension and image descriptor
+        bad_size[13 + 48 + 8 + 10] = 0;
+        assert_eq!(load(bad_size).err(), Some("invalid LZW code size 0".to_string()));
+        // A clear then 31, past the codes defined so far
+        let mut out = Vec::new();
+        assert_eq!(lzw_decode(4, &[0xF0, 0x03], &mut out), Err("invalid LZW code 31".to_string()));
+    }
+}
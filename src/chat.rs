@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use tungstenite::{Message, WebSocket};
+
+// A chat bridge (chip8 chat), so a Twitch channel, or any IRC channel, can play a rom being
+// streamed with chip8 stream by typing commands into chat. It joins the channel without
+// logging in, the way Twitch lets anyone read chat, and connects to the stream as any other
+// viewer does, pressing keys with the stream's "down A" and "up A" messages (see stream.rs).
+//
+// A command is ! and a keypad key in hex, like !4, or ! and a word. up, down, left and right
+// are 2 8 4 6, the keys most games move with, and --word <word>=<key> adds more or changes
+// them. Commands are voted on: the first starts a vote, and once it's been open for the vote
+// window the key with the most votes is held down for a moment, ties going to whichever got
+// there first. A window of 0 is anarchy, every command pressing its key as it comes. Each
+// chatter's commands only count once a debounce apart, so nobody can win a vote on their own
+// by spamming it.
+
+
+/// How often the bridge looks at chat and the stream
+const TICK: Duration = Duration::from_millis(10);
+
+
+/// How chat's commands are turned into key presses
+pub struct Settings {
+    /// How long a vote's open, zero for anarchy
+    pub window: Duration,
+    /// How long the winning key's held down
+    pub hold: Duration,
+    /// How long after a chatter's command counts before their next one can
+    pub debounce: Duration,
+    /// Commands besides the keys in hex, and the keys they press
+    pub words: Vec<(String, u8)>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Self {
+            window: Duration::from_secs(2),
+            hold: Duration::from_millis(250),
+            debounce: Duration::from_secs(1),
+            words: [("up", 0x2), ("down", 0x8), ("left", 0x4), ("right", 0x6)]
+                .into_iter()
+                .map(|(word, key)| (word.to_string(), key))
+                .collect(),
+        };
+    }
+}
+
+impl Settings {
+    /// The key a chat message is a command for, if it's one
+    pub fn command(&self, text: &str) -> Option<u8> {
+        let command = text.trim().strip_prefix('!')?.to_ascii_lowercase();
+        if let Some((_, key)) = self.words.iter().find(|(word, _)| *word == command) {
+            return Some(*key);
+        }
+        return (command.len() == 1).then(|| u8::from_str_radix(&command, 16).ok()).flatten();
+    }
+}
+
+/// The votes going on in chat, and the key they've pressed
+pub struct Votes {
+    settings: Settings,
+    /// When the vote open now closes
+    closes: Option<Instant>,
+    /// Each key voted for and how many votes it has, in the order they were first voted for
+    votes: Vec<(u8, usize)>,
+    /// When each chatter's last command that counted was
+    counted: HashMap<String, Instant>,
+    /// The key held down and until when
+    held: Option<(u8, Instant)>,
+}
+
+impl Votes {
+    pub fn new(settings: Settings) -> Self {
+        return Self { settings, closes: None, votes: Vec::new(), counted: HashMap::new(), held: None };
+    }
+
+    /// Counts a chatter's message, if it's a command and they haven't just sent one
+    pub fn message(&mut self, chatter: &str, text: &str, now: Instant) {
+        let Some(key) = self.settings.command(text) else {
+            return;
+        };
+        let chatter = chatter.to_ascii_lowercase();
+        if self.counted.get(&chatter).is_some_and(|counted| now < *counted + self.settings.debounce) {
+            return;
+        }
+        self.counted.insert(chatter, now);
+
+        if self.settings.window.is_zero() {
+            self.held = Some((key, now + self.settings.hold));
+            return;
+        }
+        self.closes.get_or_insert(now + self.settings.window);
+        match self.votes.iter_mut().find(|(voted, _)| *voted == key) {
+            Some((_, count)) => *count += 1,
+            None => self.votes.push((key, 1)),
+        }
+    }
+
+    /// The key that should be held down now, closing the vote if its window's up
+    pub fn update(&mut self, now: Instant) -> Option<u8> {
+        if self.closes.is_some_and(|closes| now >= closes) {
+            // max_by_key would go with the last of a tie
+            let winner = self.votes.iter().fold((0, 0), |winner, vote| if vote.1 > winner.1 { *vote } else { winner });
+            self.held = Some((winner.0, now + self.settings.hold));
+            self.closes = None;
+            self.votes.clear();
+        }
+        self.counted.retain(|_, counted| now < *counted + self.settings.debounce);
+        return self.held.filter(|(_, until)| now < *until).map(|(key, _)| key);
+    }
+}
+
+/// Bridges the chat in channel on the IRC server (e.g. irc.chat.twitch.tv:6667) to the stream
+/// at stream (e.g. 127.0.0.1:8080), until either goes away. Each key pressed is printed
+pub fn run(stream: &str, server: &str, channel: &str, settings: Settings) -> Result<(), String> {
+    let socket = TcpStream::connect(stream).map_err(|e| format!("Couldn't connect to the stream at {stream}: {e}"))?;
+    let (mut socket, _) = tungstenite::client(format!("ws://{stream}/"), socket)
+        .map_err(|e| format!("Couldn't connect to the stream at {stream}: {e}"))?;
+    socket.get_ref().set_nonblocking(true).map_err(|e| e.to_string())?;
+    let messages = join(server, channel)?;
+    println!("Bridging #{} on {server} to the stream at {stream}", channel.trim_start_matches('#'));
+
+    let mut votes = Votes::new(settings);
+    let mut held: Option<u8> = None;
+    loop {
+        let now = Instant::now();
+        loop {
+            match messages.try_recv() {
+                Ok((chatter, text)) => votes.message(&chatter, &text, now),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return Err(format!("Lost the connection to {server}")),
+            }
+        }
+
+        let pressed = votes.update(now);
+        if pressed != held {
+            if let Some(key) = held {
+                press(&mut socket, &format!("up {key:X}"))?;
+            }
+            if let Some(key) = pressed {
+                println!("Pressing {key:X}");
+                press(&mut socket, &format!("down {key:X}"))?;
+            }
+            held = pressed;
+        }
+        skip_frames(&mut socket)?;
+        std::thread::sleep(TICK);
+    }
+}
+
+/// Joins the channel on a thread of its own, handing back each message said in it with who
+/// said it
+fn join(server: &str, channel: &str) -> Result<Receiver<(String, String)>, String> {
+    let mut irc = TcpStream::connect(server).map_err(|e| format!("Couldn't connect to {server}: {e}"))?;
+    // justinfan and some digits is Twitch's nick for reading without logging in, and is as
+    // good a nick as any elsewhere
+    let nick = format!("justinfan{}", rand::random::<u32>() % 100_000);
+    let channel = channel.trim_start_matches('#').to_ascii_lowercase();
+    write!(irc, "NICK {nick}\r\nUSER {nick} 0 * :chip8\r\nJOIN #{channel}\r\n").map_err(|e| e.to_string())?;
+
+    let (sender, receiver) = mpsc::channel();
+    let reader = BufReader::new(irc.try_clone().map_err(|e| e.to_string())?);
+    std::thread::spawn(move || {
+        for line in reader.lines() {
+            let Ok(line) = line else {
+                return;
+            };
+            // Servers hang up on anyone who doesn't answer their pings
+            if let Some(token) = line.strip_prefix("PING ") {
+                if write!(irc, "PONG {token}\r\n").is_err() {
+                    return;
+                }
+                continue;
+            }
+            if let Some(message) = privmsg(&line) {
+                if sender.send(message).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    return Ok(receiver);
+}
+
+/// Who said what in a PRIVMSG line, ":nick!user@host PRIVMSG #channel :text", which Twitch
+/// can start with tags
+fn privmsg(line: &str) -> Option<(String, String)> {
+    let line = match line.strip_prefix('@') {
+        Some(tagged) => tagged.split_once(' ')?.1,
+        None => line,
+    };
+    let (prefix, rest) = line.strip_prefix(':')?.split_once(' ')?;
+    let (_, text) = rest.strip_prefix("PRIVMSG ")?.split_once(" :")?;
+    let nick = prefix.split('!').next()?;
+    return Some((nick.to_string(), text.to_string()));
+}
+
+fn press(socket: &mut WebSocket<TcpStream>, message: &str) -> Result<(), String> {
+    return match socket.send(Message::text(message)) {
+        Ok(()) => Ok(()),
+        // It's queued, and goes with the next send or flush
+        Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+        Err(e) => Err(format!("Lost the stream: {e}")),
+    };
+}
+
+/// Reads past the frames the stream sends, which the bridge has no use for but has to take
+/// so they don't back up
+fn skip_frames(socket: &mut WebSocket<TcpStream>) -> Result<(), String> {
+    loop {
+        match socket.read() {
+            Ok(Message::Close(_)) => return Err("The stream closed".to_string()),
+            Ok(_) => {},
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => break,
+            Err(e) => return Err(format!("Lost the stream: {e}")),
+        }
+    }
+    return match socket.flush() {
+        Ok(()) => Ok(()),
+        Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+        Err(e) => Err(format!("Lost the stream: {e}")),
+    };
+}
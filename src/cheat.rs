@@ -0,0 +1,276 @@
+use std::path::PathBuf;
+
+use crate::chip::Chip8;
+use crate::persist;
+
+// Memory searches, bookmarks and freezes for finding and pinning down the bytes a game keeps
+// its lives and score in, and cheat files for sharing what was found. A rom's cheats are kept
+// with its other saved data as <data dir>/<hash>.cht (`chip8 profile <rom>` shows where), one
+// cheat a line, the address and value in hex, whether it's frozen or written once, then what
+// it does:
+//
+//   # comments and blank lines are ignored
+//   2F0 09 freeze   Infinite lives
+//   2F2 99 once     A head start on the score
+//
+// Frozen cheats are written back every frame while they're on, and written once ones are
+// written when they're turned on. Cheats all start off, the debugger and gui turn them on.
+
+/// The ways a memory search can narrow down its candidate addresses, each comparing
+/// the current value of a byte against the value it had at the previous search step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+/// Whether a cheat keeps its value in memory or writes it once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    Freeze,
+    Once,
+}
+
+/// One line of a cheat file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub addr: u32,
+    pub value: u8,
+    pub kind: CheatKind,
+    pub description: String,
+}
+
+/// Parses the contents of a cheat file
+pub fn parse_cheats(text: &str) -> Result<Vec<Cheat>, String> {
+    let mut cheats = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [addr, value, kind, description @ ..] = &words[..] else {
+            return Err(format!("line {}: expected '<addr> <value> freeze|once <description>'", i + 1));
+        };
+        let hex = |n: &str| u32::from_str_radix(n.trim_start_matches("0x").trim_start_matches("0X"), 16).ok();
+        let addr = hex(addr).ok_or(format!("line {}: invalid address '{addr}'", i + 1))?;
+        let value = hex(value).and_then(|value| u8::try_from(value).ok()).ok_or(format!("line {}: invalid byte '{value}'", i + 1))?;
+        let kind = match *kind {
+            "freeze" => CheatKind::Freeze,
+            "once" => CheatKind::Once,
+            kind => return Err(format!("line {}: expected freeze or once, got '{kind}'", i + 1)),
+        };
+        cheats.push(Cheat { addr, value, kind, description: description.join(" ") });
+    }
+    return Ok(cheats);
+}
+
+/// Where the cheats for a rom are kept
+pub fn cheats_path(rom: &[u8]) -> PathBuf {
+    return persist::data_dir().join(format!("{}.cht", persist::rom_id(rom)));
+}
+
+/// Loads the cheats for a rom, none if it doesn't have a cheat file
+pub fn load_cheats(rom: &[u8]) -> Result<Vec<Cheat>, String> {
+    return match std::fs::read_to_string(cheats_path(rom)) {
+        Ok(text) => parse_cheats(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.to_string()),
+    };
+}
+
+/// A memory search plus the addresses the user has bookmarked or frozen.
+/// The usual workflow is to start a search, play until the value of interest
+/// (e.g. the number of lives) changes, filter, and repeat until only a handful
+/// of addresses are left, then freeze one of them
+pub struct CheatEngine {
+    snapshot: Vec<u8>,
+    candidates: Vec<u32>,
+    bookmarks: Vec<(u32, String)>,
+    freezes: Vec<(u32, u8)>,
+    /// The cheats from a cheat file, each with whether it's on
+    cheats: Vec<(Cheat, bool)>,
+}
+
+impl CheatEngine {
+    pub fn new() -> Self {
+        return Self {
+            snapshot: Vec::new(),
+            candidates: Vec::new(),
+            bookmarks: Vec::new(),
+            freezes: Vec::new(),
+            cheats: Vec::new(),
+        };
+    }
+
+    /// Starts a new search with every byte of memory as a candidate
+    pub fn start_search(&mut self, chip: &Chip8) {
+        self.snapshot = chip.read_mem(0, usize::MAX).to_vec();
+        self.candidates = (0..self.snapshot.len() as u32).collect();
+    }
+
+    /// Drops every candidate that doesn't pass the filter and remembers the current
+    /// memory so the next filter compares against it. Returns how many are left
+    pub fn filter(&mut self, chip: &Chip8, filter: SearchFilter) -> usize {
+        let memory = chip.read_mem(0, usize::MAX);
+
+        self.candidates.retain(|&addr| {
+            let old = self.snapshot[addr as usize];
+            let new = memory[addr as usize];
+            match filter {
+                SearchFilter::Equal(value) => new == value,
+                SearchFilter::Changed => new != old,
+                SearchFilter::Unchanged => new == old,
+                SearchFilter::Increased => new > old,
+                SearchFilter::Decreased => new < old,
+            }
+        });
+        self.snapshot = memory.to_vec();
+
+        return self.candidates.len();
+    }
+
+    pub fn candidates(&self) -> &[u32] {
+        return &self.candidates;
+    }
+
+    /// Bookmarks an address under a name, replacing any existing bookmark for it
+    pub fn bookmark(&mut self, addr: u32, label: &str) {
+        self.bookmarks.retain(|(a, _)| *a != addr);
+        self.bookmarks.push((addr, label.to_string()));
+        self.bookmarks.sort_by_key(|(a, _)| *a);
+    }
+
+    pub fn bookmarks(&self) -> &[(u32, String)] {
+        return &self.bookmarks;
+    }
+
+    /// Keeps the byte at addr set to value, replacing any existing freeze for it
+    pub fn freeze(&mut self, addr: u32, value: u8) {
+        self.unfreeze(addr);
+        self.freezes.push((addr, value));
+        self.freezes.sort_by_key(|(a, _)| *a);
+    }
+
+    pub fn unfreeze(&mut self, addr: u32) {
+        self.freezes.retain(|(a, _)| *a != addr);
+    }
+
+    pub fn freezes(&self) -> &[(u32, u8)] {
+        return &self.freezes;
+    }
+
+    /// Replaces the cheats with those from a cheat file, all off. The freezes of any that were
+    /// on are lifted
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) {
+        for i in 0..self.cheats.len() {
+            self.set_cheat_off(i);
+        }
+        self.cheats = cheats.into_iter().map(|cheat| (cheat, false)).collect();
+    }
+
+    pub fn cheats(&self) -> &[(Cheat, bool)] {
+        return &self.cheats;
+    }
+
+    /// Turns cheat i on or off. A frozen one is frozen or unfrozen, and one written once is
+    /// written and stays off, ready to be written again
+    pub fn set_cheat(&mut self, chip: &mut Chip8, i: usize, on: bool) {
+        let Some((cheat, _)) = self.cheats.get(i) else {
+            return;
+        };
+        let (addr, value) = (cheat.addr, cheat.value);
+        match (cheat.kind, on) {
+            (_, false) => self.set_cheat_off(i),
+            (CheatKind::Freeze, true) => {
+                self.freeze(addr, value);
+                self.cheats[i].1 = true;
+                chip.write_mem(addr, &[value]);
+            },
+            (CheatKind::Once, true) => {
+                chip.write_mem(addr, &[value]);
+            },
+        }
+    }
+
+    fn set_cheat_off(&mut self, i: usize) {
+        let (cheat, on) = &mut self.cheats[i];
+        if *on && cheat.kind == CheatKind::Freeze {
+            let addr = cheat.addr;
+            *on = false;
+            self.unfreeze(addr);
+        }
+    }
+
+    /// Writes every frozen value back into memory. This should be called at least
+    /// once a frame so the game never gets to act on the value it wrote
+    pub fn apply(&self, chip: &mut Chip8) {
+        for (addr, value) in &self.freezes {
+            chip.write_mem(*addr, &[*value]);
+        }
+    }
+}
+
+impl Default for CheatEngine {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+
+    #[test]
+    fn cheat_files_are_read_and_their_cheats_turned_on() {
+        let text = "# Lives and score\n\n  2F0 09 freeze   Infinite lives\n0x2f2 0X99 once A head start\n300 0 freeze\n";
+        let cheats = parse_cheats(text).expect("the cheats parse");
+        assert_eq!(
+            cheats,
+            [
+                Cheat { addr: 0x2F0, value: 0x09, kind: CheatKind::Freeze, description: "Infinite lives".to_string() },
+                Cheat { addr: 0x2F2, value: 0x99, kind: CheatKind::Once, description: "A head start".to_string() },
+                Cheat { addr: 0x300, value: 0x00, kind: CheatKind::Freeze, description: String::new() },
+            ]
+        );
+
+        let mut chip = Chip8::with_platform(Platform::Chip8, false);
+        let mut engine = CheatEngine::new();
+        engine.set_cheats(cheats);
+        engine.set_cheat(&mut chip, 0, true);
+        engine.set_cheat(&mut chip, 1, true);
+        assert_eq!(chip.read_mem(0x2F0, 3), [0x09, 0x00, 0x99]);
+        assert_eq!((engine.cheats()[0].1, engine.cheats()[1].1), (true, false));
+
+        // A frozen cheat is written back until it's turned off
+        chip.write_mem(0x2F0, &[0x01]);
+        engine.apply(&mut chip);
+        assert_eq!(chip.read_mem(0x2F0, 1), [0x09]);
+        engine.set_cheat(&mut chip, 0, false);
+        chip.write_mem(0x2F0, &[0x01]);
+        engine.apply(&mut chip);
+        assert_eq!(chip.read_mem(0x2F0, 1), [0x01]);
+        assert!(engine.freezes().is_empty());
+    }
+
+    #[test]
+    fn malformed_cheat_files_are_errors_on_their_line() {
+        let errors = [
+            ("2F0 09", "line 1: expected '<addr> <value> freeze|once <description>'"),
+            ("# fine\n2G0 09 freeze Lives", "line 2: invalid address '2G0'"),
+            ("2F0 100 freeze Lives", "line 1: invalid byte '100'"),
+            ("2F0 -1 once Lives", "line 1: invalid byte '-1'"),
+            ("2F0 09 always Lives", "line 1: expected freeze or once, got 'always'"),
+            ("2F0 09 Freeze Lives", "line 1: expected freeze or once, got 'Freeze'"),
+        ];
+        for (text, error) in errors {
+            assert_eq!(parse_cheats(text), Err(error.to_string()), "{text}");
+        }
+        assert_eq!(parse_cheats("# nothing but comments\n\n"), Ok(Vec::new()));
+    }
+}
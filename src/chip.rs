@@ -1,4 +1,165 @@
-use twelve_bit::u12;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt;
+use core::ops::Range;
+
+#[cfg(feature = "std")]
+use crate::bench::Timings;
+use crate::coverage::Coverage;
+use crate::drawmap::DrawMap;
+use crate::error::Chip8Error;
+use crate::font::{Fontset, BIG_FONT, BIG_FONT_ADDR};
+use crate::framebuffer::Framebuffer;
+use crate::patch::PatchRecord;
+use crate::platform::Platform;
+
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+mod chip8x;
+mod json;
+mod megachip;
+mod snapshot;
+mod superchip;
+
+pub use chip8x::Chip8X;
+pub use megachip::{BlendMode, DigitizedSound, MegaChip};
+pub use snapshot::Snapshot;
+pub use superchip::{HIRES_HEIGHT, HIRES_WIDTH};
+
+/// How many instructions run between each 60Hz timer tick, unless set_cycles_per_frame says
+/// otherwise
+pub const CYCLES_PER_FRAME: usize = 10;
+
+/// How many calls deep the stack goes unless set_stack_depth says otherwise, the 16 levels of
+/// the interpreters roms were written for
+pub const STACK_DEPTH: usize = 16;
+
+/// The deepest set_stack_depth lets the stack go, so SP still fits in a byte
+const MAX_STACK_DEPTH: usize = u8::MAX as usize;
+
+/// XO-CHIP's pitch before FX3A sets it, which plays the audio pattern at 4000 bits a second
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// How many finished frames a frame_rx subscriber can fall behind by before frames are missed
+#[cfg(feature = "std")]
+pub const FRAMES_BUFFERED: usize = 2;
+
+/// A callback run before or after every instruction, see Chip8::set_pre_exec_hook
+pub type ExecHook = Box<dyn FnMut(&Chip8State) + Send + Sync>;
+
+/// A callback run when an instruction writes over code, see Chip8::set_code_write_hook, or
+/// writes memory at all, see Chip8::set_write_hook
+pub type CodeWriteHook = Box<dyn FnMut(CodeWrite) + Send + Sync>;
+
+/// An instruction writing a byte of memory: one that's already run as part of an instruction
+/// for the code write hook, any byte for the write hook
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodeWrite {
+    /// The instruction that wrote it
+    pub pc: u16,
+    pub addr: u32,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// Stands in for memory over a mapped range when an instruction reads it, given the address
+/// and returning the byte read, see Chip8::map_reads
+pub type ReadHandler = Box<dyn FnMut(u32) -> u8 + Send + Sync>;
+
+/// Stands in for memory over a mapped range when an instruction writes it, given the address
+/// and the byte written, see Chip8::map_writes
+pub type WriteHandler = Box<dyn FnMut(u32, u8) + Send + Sync>;
+
+/// A callback that stands in for the machine code routine a SYS instruction calls.
+/// It gets the address of the routine and can change the machine however the routine would
+pub type SysHandler = Box<dyn FnMut(&mut Chip8, u16) + Send + Sync>;
+
+/// What to do with 0NNN (SYS addr), which on the COSMAC VIP jumped into a routine written
+/// in 1802 machine code
+pub enum SysPolicy {
+    /// Skip over it, like nearly every interpreter since the VIP
+    Ignore,
+    /// Stop with Chip8Error::MachineCodeCall, for catching roms that rely on it
+    Error,
+    /// Hand the address to a callback that emulates the routine
+    Call(SysHandler),
+}
+
+/// Where interpreters disagree over what an instruction does (see isa.rs' Quirk), each the
+/// way this interpreter's always done it unless turned on: 8XY6 and 8XYE shift Vx, BNNN adds
+/// V0, FX55 and FX65 leave I where it is, 8XY1 to 8XY3 leave VF alone and DXYN draws straight
+/// away. Clipping isn't one of them, sprites are always cut off at the edge of the screen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift Vy into Vx, as the VIP did
+    pub shift_vy: bool,
+    /// BNNN is BXNN, jumping to XNN plus Vx, as SUPER-CHIP did
+    pub jump_vx: bool,
+    /// FX55 and FX65 move I on past the last register they save or load, as the VIP did
+    pub memory_moves_i: bool,
+    /// 8XY1, 8XY2 and 8XY3 set VF to 0, as the VIP did
+    pub vf_reset: bool,
+    /// DXYN waits for the next frame if there's already been a draw this frame, so there's
+    /// at most one a frame, as on the VIP
+    pub display_wait: bool,
+}
+
+/// Where CXNN's random numbers come from: a seeded StdRng kept in the machine, or whatever
+/// set_rng was given. The seeded one's kept inline rather than boxed, so reseeding for every
+/// snapshot doesn't allocate
+#[allow(clippy::large_enum_variant)]
+enum Random {
+    Seeded(StdRng),
+    Custom(Box<dyn RngCore + Send + Sync>),
+}
+
+impl RngCore for Random {
+    fn next_u32(&mut self) -> u32 {
+        return match self {
+            Random::Seeded(rng) => rng.next_u32(),
+            Random::Custom(rng) => rng.next_u32(),
+        };
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        return match self {
+            Random::Seeded(rng) => rng.next_u64(),
+            Random::Custom(rng) => rng.next_u64(),
+        };
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            Random::Seeded(rng) => rng.fill_bytes(dest),
+            Random::Custom(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        return match self {
+            Random::Seeded(rng) => rng.try_fill_bytes(dest),
+            Random::Custom(rng) => rng.try_fill_bytes(dest),
+        };
+    }
+}
+
+/// What a call does when the stack's already as deep as it goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackPolicy {
+    /// Stop with Chip8Error::StackOverflow, as a real interpreter would crash
+    Error,
+    /// Forget the oldest return address to make room, so a rom that never returns from its
+    /// outermost calls keeps going
+    Wrap,
+}
 
 
 // http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
@@ -22,203 +183,949 @@ use twelve_bit::u12;
 /// opcode: stores the opcode of the current instruction
 /// ar: The address register (I) is used to read and write to memory
 /// pc: The program counter stores the address currently being executed
-/// sp: The stack pointer points to the topmost level of the stack
-/// stack: Used to store the address that the interpreter should return to when finished with a subroutine
 /// registers: 16 general purpose 8-bit registers, Vx, x being hex
-/// mem: 4 whole KB of RAM, in the layout shown above
 /// delay: Used for timings of events in games, can be written and read
 /// sound: Used for sound effects, When != 0, beeping is made. Ticks down at 60Hz and can only be set
-/// fontset: The ways to represent
-/// graphics: The array of 1s and 0s that make up whether pixels of the 64x32 screen is black or white
+/// sp: The stack pointer, how many return addresses are on the stack
+/// stack: Used to store the address that the interpreter should return to when finished with a subroutine,
+/// the last being the most recent call, with room for the deepest stack_depth allows
+/// stack_depth: How many calls deep the stack goes, 16 unless set_stack_depth says otherwise
+/// stack_policy: What a call does when the stack's full
+/// rpl: The SUPER-CHIP user flags (named after the HP-48's RPL), saved and restored by FX75/FX85
+/// mem: 4 whole KB of RAM, in the layout shown above (16MB on Mega-Chip)
+/// framebuffer: The pixels that make up the 64x32 screen (64x64 for hires roms, 256x192 in
+/// Mega-Chip mode, with colour zones on CHIP-8X). Instructions draw into it as they run
+/// front: A copy of framebuffer taken at the end of every frame, so whatever reads it never
+/// sees a frame half drawn
+/// frame_txs: The channels handed out by frame_rx, each sent every finished frame
+/// keys: Whether each of the 16 keys on the hex keypad is currently held down
+/// platform: Which variant of CHIP-8 is being interpreted
+/// chip8x: The second keypad and I/O port of CHIP-8X, only there on that platform
+/// megachip: The extra state of the Mega-Chip extensions, only there on that platform
+/// audio_pattern: The 16 bytes of sound XO-CHIP's F002 loaded, played a bit at a time while
+/// the sound timer runs instead of the plain beep (see audio.rs)
+/// pitch: How fast the audio pattern plays, set by XO-CHIP's FX3A
+/// sys_policy: How 0NNN calls into machine code are handled
+/// memory_protection: Whether instructions writing below the start of the rom are an error
+/// strict: Whether unknown opcodes, reaching past the end of memory and ignored 0NNN calls are
+/// errors, rather than skipped, wrapped around and ignored
+/// quirks: Which of the instructions interpreters disagree over run the other way
+/// drawn: Whether there's been a draw this frame, for the display wait quirk
+/// cycles_per_frame: How many instructions run_frame runs before ticking the timers, the
+/// speed of the machine
+/// exited: Whether the rom has asked to stop with SUPER-CHIP's 00FD. The machine stays on the
+/// 00FD and run_frame runs nothing more, for the frontend to close when it sees
+/// rng: Where CXNN gets its random numbers, seeded from the OS unless seed_rng or set_rng is
+/// called (without std there's no OS to ask, so it starts from a fixed seed)
+/// mapped_reads, mapped_writes: Handlers that instructions read and write through instead of
+/// memory over chosen ranges of addresses, latest mapped first
+/// timings: Where the time went decoding, executing and drawing, once enable_timings is
+/// called (see bench.rs)
+/// code: Which bytes have been fetched as part of an instruction, a bit each, and the hook told
+/// when an instruction writes over one, once set_code_write_hook is called
+/// write_hook: Told about every byte an instruction writes to memory, once set_write_hook is
+/// called (see tracedb.rs)
+///
+/// Roms that write over their own code (self-modifying code, common in older games) get what
+/// the VIP gave them: nothing's decoded ahead or cached, every instruction is fetched from
+/// memory as it runs, so a write takes effect the next time those bytes are fetched, even
+/// when that's the very next instruction. Writes through map_writes never reach memory, so
+/// never change the code. Anything that does keep decoded instructions (a JIT, the
+/// disassembly a debugger's showing) finds out what to throw away from the code write hook
+///
+/// The machine is Send and Sync, so it can be moved onto a thread of its own and driven from
+/// there (see thread.rs), which is why the hooks, SYS handler and RNG it holds have to be too
+///
+/// Nothing the machine keeps allocates as it runs, so a frame never touches the heap: the stack
+/// is as deep as it can go from the start, a seeded RNG is kept inline and the front buffer is
+/// copied over in place. What every instruction reads is at the top, laid out in order so it
+/// shares as few cache lines as it can (see benches/machine.rs)
+#[repr(C)]
 pub struct Chip8 {
     opcode: u16,
-    ar: u12::U12,
     pc: u16,
-    sp: u8,
-    stack: [u16; 16],
+    ar: u32,
     registers: [u8; 16],
-    mem: [u8; 4096],
     delay: u8,
     sound: u8,
-    fontset: [u8; 80],
-    graphics: [u8; 2048],
+    sp: u8,
+    drawn: bool,
+    platform: Platform,
+    quirks: Quirks,
+    exited: bool,
+    strict: bool,
+    memory_protection: bool,
+    cycles_per_frame: usize,
+    mem: Vec<u8>,
+    stack: [u16; MAX_STACK_DEPTH],
+    stack_depth: usize,
+    stack_policy: StackPolicy,
+    rpl: [u8; 16],
+    framebuffer: Framebuffer,
+    front: Framebuffer,
+    #[cfg(feature = "std")]
+    frame_txs: Vec<SyncSender<Framebuffer>>,
+    keys: [bool; 16],
+    chip8x: Option<Chip8X>,
+    megachip: Option<MegaChip>,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    sys_policy: SysPolicy,
+    rng: Random,
+    // Only printed with std
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
     debug: bool,
+    pre_exec_hook: Option<ExecHook>,
+    post_exec_hook: Option<ExecHook>,
+    coverage: Option<Coverage>,
+    draw_map: Option<DrawMap>,
+    mapped_reads: Vec<(Range<u32>, ReadHandler)>,
+    mapped_writes: Vec<(Range<u32>, WriteHandler)>,
+    #[cfg(feature = "std")]
+    timings: Option<Timings>,
+    code: Option<(Vec<u64>, CodeWriteHook)>,
+    write_hook: Option<CodeWriteHook>,
+}
+
+/// A read-only view of the whole machine, handed to the execution hooks so external
+/// tools (tracers, coverage, bots) can observe it without reaching into the interpreter
+pub struct Chip8State<'a> {
+    pub pc: u16,
+    pub opcode: u16,
+    pub ar: u32,
+    pub sp: u8,
+    /// The return addresses on the stack, outermost call first
+    pub stack: &'a [u16],
+    pub registers: &'a [u8; 16],
+    pub mem: &'a [u8],
+    pub delay: u8,
+    pub sound: u8,
+    pub framebuffer: &'a Framebuffer,
+    pub keys: &'a [bool; 16],
+}
+
+/// The whole machine as text, for bug reports and snapshot tests: the registers laid out as
+/// the debugger's regs shows them, the stack, the keys held and the screen with # for pixels
+/// that are on. Only what's in the state is shown and always the same way, so the same
+/// machine always prints the same
+impl fmt::Display for Chip8State<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "PC: {:04X}  OP: {:04X}  I: {:03X}  SP: {}  DT: {:02X}  ST: {:02X}",
+            self.pc, self.opcode, self.ar, self.sp, self.delay, self.sound
+        )?;
+        for (i, v) in self.registers.iter().enumerate() {
+            let end = if i % 8 == 7 { "\n" } else { "  " };
+            write!(f, "V{i:X}: {v:02X}{end}")?;
+        }
+
+        write!(f, "Stack:")?;
+        if self.sp == 0 {
+            write!(f, " empty")?;
+        }
+        for addr in self.stack {
+            write!(f, " {addr:04X}")?;
+        }
+        write!(f, "\nKeys:")?;
+        if !self.keys.contains(&true) {
+            write!(f, " none")?;
+        }
+        for (key, _) in self.keys.iter().enumerate().filter(|(_, &held)| held) {
+            write!(f, " {key:X}")?;
+        }
+        writeln!(f)?;
+
+        let border = "-".repeat(self.framebuffer.width());
+        writeln!(f, "+{border}+")?;
+        for row in self.framebuffer.to_ascii('#', '.').lines() {
+            writeln!(f, "|{row}|")?;
+        }
+        return writeln!(f, "+{border}+");
+    }
 }
 
+// Keeps anything that isn't Send or Sync from creeping into the machine unnoticed
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Chip8>();
+};
+
 impl Chip8 {
     pub fn new(debug: bool) -> Self {
-        let fontset = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-        ];
-
-        let mut mem: [u8; 4096] = [0; 4096];
-
-        for i in 0..fontset.len() {
-            mem[i] = fontset[i];
-        }
+        return Self::with_platform(Platform::Chip8, debug);
+    }
+
+    /// Creates an interpreter for one of the CHIP-8 variants
+    pub fn with_platform(platform: Platform, debug: bool) -> Self {
+        return Self::with_memory_size(platform, platform.memory_size(), debug);
+    }
+
+    /// Creates an interpreter with a different amount of memory than the platform normally
+    /// has, clamped between 4KB and 16MB
+    pub fn with_memory_size(platform: Platform, memory_size: usize, debug: bool) -> Self {
+        let mut mem = vec![0; memory_size.clamp(0x1000, 0x100_0000)];
+        mem[..80].copy_from_slice(Fontset::Standard.bytes());
+        mem[BIG_FONT_ADDR as usize..BIG_FONT_ADDR as usize + BIG_FONT.len()].copy_from_slice(&BIG_FONT);
+
+        let framebuffer = match platform {
+            Platform::Chip8X => Framebuffer::with_colour_zones(64, 32),
+            _ => Framebuffer::new(64, 32),
+        };
 
         return Self {
             opcode: 0,
-            ar: u12::MIN,
-            pc: 0x200,
-            sp: 0,
-            stack: [0; 16],
+            pc: platform.start_address(),
+            ar: 0,
             registers: [0; 16],
-            mem,
             delay: 0,
             sound: 0,
-            fontset,
-            graphics: [0; 2048],
+            sp: 0,
+            drawn: false,
+            platform,
+            quirks: Quirks::default(),
+            exited: false,
+            strict: false,
+            memory_protection: false,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            mem,
+            stack: [0; MAX_STACK_DEPTH],
+            stack_depth: STACK_DEPTH,
+            stack_policy: StackPolicy::Error,
+            rpl: [0; 16],
+            front: framebuffer.clone(),
+            framebuffer,
+            #[cfg(feature = "std")]
+            frame_txs: Vec::new(),
+            keys: [false; 16],
+            chip8x: (platform == Platform::Chip8X).then(Chip8X::new),
+            megachip: (platform == Platform::MegaChip).then(MegaChip::new),
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            sys_policy: SysPolicy::Ignore,
+            #[cfg(feature = "std")]
+            rng: Random::Seeded(StdRng::from_entropy()),
+            #[cfg(not(feature = "std"))]
+            rng: Random::Seeded(StdRng::seed_from_u64(0)),
             debug,
-        }
+            pre_exec_hook: None,
+            post_exec_hook: None,
+            coverage: None,
+            draw_map: None,
+            mapped_reads: Vec::new(),
+            mapped_writes: Vec::new(),
+            #[cfg(feature = "std")]
+            timings: None,
+            code: None,
+            write_hook: None,
+        };
     }
 
     /// Loads the rom with with the name given in the parameter
     /// It reads the binary file and converts it to a Vec<u8>
-    /// Then loops over the file and stores it in memory starting at 0x200
+    /// Then loops over the file and stores it in memory at the platform's start address.
+    /// With the zip feature the name can be an archive, or an entry in one as `pack.zip:BRIX`
+    #[cfg(feature = "std")]
     pub fn load_rom(&mut self, name: &str) -> Result<(), std::io::Error> {
-        let file = std::fs::read(format!("./roms/{name}").as_str())?;
+        #[cfg(feature = "zip")]
+        let (name, entry) = crate::zip::split_path(name);
+        #[cfg_attr(not(feature = "zip"), allow(unused_mut))]
+        let mut file = std::fs::read(format!("./roms/{name}").as_str())?;
+        #[cfg(feature = "zip")]
+        if crate::zip::is_zip(&file) {
+            file = crate::zip::extract(&file, entry).map_err(std::io::Error::other)?;
+        }
+
+        self.load_rom_bytes(&file);
+
+        return Ok(());
+    }
+
+    /// Copies an already loaded rom into memory at the platform's start address (0x200
+    /// on most), anything that doesn't fit in memory is cut off
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) {
+        self.write_mem(self.platform.start_address() as u32, rom);
+
+        // Hires roms start with a jump to a patch for the VIP interpreter at 0x260 that
+        // turns on the 64x64 "two page" display. That's 1802 machine code, so instead the
+        // display is switched here and the program run from where the patch would have
+        // handed over to it
+        if self.platform == Platform::HiresChip8 && rom.starts_with(&[0x12, 0x60]) {
+            self.framebuffer.resize(64, 64);
+            self.pc = 0x2C0;
+        }
+    }
+
+    /// Returns up to len bytes of memory starting at addr, cut short at the end of memory
+    pub fn read_mem(&self, addr: u32, len: usize) -> &[u8] {
+        let start = (addr as usize).min(self.mem.len());
+        let end = start.saturating_add(len).min(self.mem.len());
+        return &self.mem[start..end];
+    }
+
+    /// Writes the bytes into memory starting at addr. Any bytes that would land past
+    /// the end of memory are dropped, and the number actually written is returned
+    pub fn write_mem(&mut self, addr: u32, bytes: &[u8]) -> usize {
+        let start = (addr as usize).min(self.mem.len());
+        let count = bytes.len().min(self.mem.len() - start);
+        self.mem[start..start + count].copy_from_slice(&bytes[..count]);
+        return count;
+    }
+
+    /// Writes a patch's records over the rom where it's loaded, each at the platform's start
+    /// address plus its offset. Like write_mem, bytes that would land past the end of memory
+    /// are dropped, and the number actually written is returned
+    pub fn apply_patch(&mut self, records: &[PatchRecord]) -> usize {
+        let start = self.platform.start_address() as u32;
+        return records.iter().map(|record| self.write_mem(start.saturating_add(record.offset), &record.bytes)).sum();
+    }
+
+    /// Reads a byte for an instruction, addresses past the end of memory wrap around to
+    /// the start so a rom can never read outside of it
+    fn mem_at(&self, addr: usize) -> u8 {
+        return self.mem[addr % self.mem.len()];
+    }
+
+    /// Reads a byte for an instruction as data, through the handler if the address is mapped
+    fn load_mem_at(&mut self, addr: usize) -> u8 {
+        let addr = (addr % self.mem.len()) as u32;
+        if let Some((_, handler)) = self.mapped_reads.iter_mut().find(|(range, _)| range.contains(&addr)) {
+            return handler(addr);
+        }
+        return self.mem[addr as usize];
+    }
+
+    /// In strict mode, errors if len bytes from addr don't all fit in memory. The PC has to
+    /// be past the instruction, as it is while one runs
+    fn check_range(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if self.strict && addr + len > self.mem.len() {
+            return Err(Chip8Error::OutOfBounds { pc: self.instruction_pc(), addr: addr.max(self.mem.len()) as u32 });
+        }
+        return Ok(());
+    }
+
+    /// Writes a byte for an instruction, wrapping around like mem_at. With memory
+    /// protection on, writing below the platform's start address is an error
+    fn set_mem_at(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let addr = addr % self.mem.len();
+        if self.memory_protection && addr < self.platform.start_address() as usize {
+            return Err(Chip8Error::ProtectedWrite { pc: self.instruction_pc(), addr: addr as u32 });
+        }
+        if let Some((_, handler)) = self.mapped_writes.iter_mut().find(|(range, _)| range.contains(&(addr as u32))) {
+            handler(addr as u32, value);
+            return Ok(());
+        }
+        let old = core::mem::replace(&mut self.mem[addr], value);
+        let pc = self.instruction_pc();
+        if let Some((fetched, hook)) = &mut self.code {
+            if old != value && fetched[addr / 64] & 1 << (addr % 64) != 0 {
+                hook(CodeWrite { pc, addr: addr as u32, old, new: value });
+            }
+        }
+        if let Some(hook) = &mut self.write_hook {
+            hook(CodeWrite { pc, addr: addr as u32, old, new: value });
+        }
+        return Ok(());
+    }
+
+    /// Marks len bytes from addr as code, for the code write hook
+    fn fetched(&mut self, addr: usize, len: usize) {
+        let Some((fetched, _)) = &mut self.code else {
+            return;
+        };
+        for addr in addr..addr + len {
+            let addr = addr % self.mem.len();
+            fetched[addr / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    /// How many bytes of memory the machine has
+    pub fn memory_size(&self) -> usize {
+        return self.mem.len();
+    }
+
+    /// Formats a region of memory as a classic hex dump, 16 bytes a line:
+    /// 0200: 6E 05 65 00 6B 06 6A 00 A3 0C DA B1 7A 04 3A 40  n.e.k.j.....z.:@
+    pub fn dump_memory(&self, range: core::ops::Range<usize>) -> String {
+        let start = range.start.min(self.mem.len());
+        let end = range.end.min(self.mem.len());
+        let mut dump = String::new();
+
+        let mut addr = start;
+        while addr < end {
+            let row = self.read_mem(addr as u32, (end - addr).min(16));
+
+            dump.push_str(&format!("{addr:04X}: "));
+            for i in 0..16 {
+                match row.get(i) {
+                    Some(byte) => dump.push_str(&format!("{byte:02X} ")),
+                    None => dump.push_str("   "),
+                }
+            }
+            dump.push(' ');
+            for byte in row {
+                if byte.is_ascii_graphic() || *byte == b' ' {
+                    dump.push(*byte as char);
+                } else {
+                    dump.push('.');
+                }
+            }
+            dump.push('\n');
+
+            addr += 16;
+        }
+
+        return dump;
+    }
+
+    /// Borrows a read-only view of the current state of the machine
+    pub fn state(&self) -> Chip8State<'_> {
+        return Chip8State {
+            pc: self.pc,
+            opcode: self.opcode,
+            ar: self.ar,
+            sp: self.sp,
+            stack: self.call_stack(),
+            registers: &self.registers,
+            mem: &self.mem,
+            delay: self.delay,
+            sound: self.sound,
+            framebuffer: &self.framebuffer,
+            keys: &self.keys,
+        };
+    }
+
+    /// Sets a callback that's run before every instruction. The state it gets has pc
+    /// pointing at the instruction about to run and opcode set to that instruction
+    pub fn set_pre_exec_hook(&mut self, hook: impl FnMut(&Chip8State) + Send + Sync + 'static) {
+        self.pre_exec_hook = Some(Box::new(hook));
+    }
+
+    /// Sets a callback that's run after every instruction. The state it gets has opcode
+    /// set to the instruction that just ran and pc pointing at the next one
+    pub fn set_post_exec_hook(&mut self, hook: impl FnMut(&Chip8State) + Send + Sync + 'static) {
+        self.post_exec_hook = Some(Box::new(hook));
+    }
+
+    /// Sets a callback that's run whenever an instruction changes a byte of memory that's
+    /// already been fetched as part of an instruction, to drop whatever was decoded from it.
+    /// Only what's fetched from now on counts as code
+    pub fn set_code_write_hook(&mut self, hook: impl FnMut(CodeWrite) + Send + Sync + 'static) {
+        self.code = Some((vec![0; self.mem.len().div_ceil(64)], Box::new(hook)));
+    }
+
+    pub fn clear_code_write_hook(&mut self) {
+        self.code = None;
+    }
+
+    /// Sets a callback that's run for every byte an instruction writes to memory, with what
+    /// was there before, even when it's the same. Writes through map_writes don't count
+    pub fn set_write_hook(&mut self, hook: impl FnMut(CodeWrite) + Send + Sync + 'static) {
+        self.write_hook = Some(Box::new(hook));
+    }
+
+    pub fn clear_write_hook(&mut self) {
+        self.write_hook = None;
+    }
+
+    /// Removes both execution hooks
+    pub fn clear_exec_hooks(&mut self) {
+        self.pre_exec_hook = None;
+        self.post_exec_hook = None;
+    }
+
+    /// Maps the addresses in range to handler for reads, so pseudo-peripherals, input schemes
+    /// or a channel to the host can sit in memory. Whenever an instruction reads a byte there
+    /// as data (FX65, the sprite rows DXYN draws, XO-CHIP's F002) the handler gives it instead
+    /// of memory. Instructions are always fetched from memory itself, and tools reading
+    /// memory from outside (read_mem, the state hooks) see what's really there. A range
+    /// overlapping one mapped before takes over the overlap
+    pub fn map_reads(&mut self, range: Range<u32>, handler: impl FnMut(u32) -> u8 + Send + Sync + 'static) {
+        self.mapped_reads.insert(0, (range, Box::new(handler)));
+    }
+
+    /// Maps the addresses in range to handler for writes, the way map_reads does for reads.
+    /// Whenever an instruction writes a byte there (FX33, FX55) the handler gets it instead
+    /// of memory, which is left as it was
+    pub fn map_writes(&mut self, range: Range<u32>, handler: impl FnMut(u32, u8) + Send + Sync + 'static) {
+        self.mapped_writes.insert(0, (range, Box::new(handler)));
+    }
+
+    /// Unmaps every range, so instructions read and write plain memory everywhere again
+    pub fn clear_mapped(&mut self) {
+        self.mapped_reads.clear();
+        self.mapped_writes.clear();
+    }
+
+    /// Swaps the small font FX29 points into for another one, see font.rs
+    pub fn set_fontset(&mut self, fontset: &Fontset) {
+        self.mem[..80].copy_from_slice(fontset.bytes());
+    }
+
+    /// Turns memory protection on or off (the default). When on, an instruction writing
+    /// into the interpreter area below the start of the rom, e.g. FX33 with an unchecked
+    /// I, stops with Chip8Error::ProtectedWrite instead of corrupting the font
+    pub fn set_memory_protection(&mut self, enabled: bool) {
+        self.memory_protection = enabled;
+    }
+
+    pub fn memory_protection(&self) -> bool {
+        return self.memory_protection;
+    }
+
+    /// Turns strict mode on or off (the default). Strict is for developing roms: an unknown
+    /// opcode, an access past the end of memory or a 0NNN call that would be ignored stops
+    /// with an error. Otherwise, for playing old roms that were never that careful, unknown
+    /// opcodes are logged and skipped and memory wraps around to the start
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    pub fn strict(&self) -> bool {
+        return self.strict;
+    }
+
+    /// Sets which of the instructions interpreters disagree over run the other way, none of
+    /// them by default
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        return self.quirks;
+    }
+
+    /// Sets how many instructions run_frame runs each 60th of a second (CYCLES_PER_FRAME to
+    /// start with, and at least 1). Roms were written for machines of very different speeds,
+    /// see analyze.rs for picking one
+    pub fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.cycles_per_frame = cycles.max(1);
+    }
+
+    pub fn cycles_per_frame(&self) -> usize {
+        return self.cycles_per_frame;
+    }
+
+    /// Sets how 0NNN (SYS addr) is handled, it's ignored by default
+    pub fn set_sys_policy(&mut self, policy: SysPolicy) {
+        self.sys_policy = policy;
+    }
+
+    /// Sets how many calls deep the stack goes, 16 by default as on the original interpreters.
+    /// Some modern roms recurse deeper. It's kept between 1 and 255 so SP still fits in a byte,
+    /// and return addresses past a shallower depth are forgotten, oldest first
+    pub fn set_stack_depth(&mut self, depth: usize) {
+        self.stack_depth = depth.clamp(1, MAX_STACK_DEPTH);
+        let extra = (self.sp as usize).saturating_sub(self.stack_depth);
+        self.stack.copy_within(extra..self.sp as usize, 0);
+        self.sp -= extra as u8;
+    }
+
+    pub fn stack_depth(&self) -> usize {
+        return self.stack_depth;
+    }
+
+    /// Sets what a call does when the stack's full, an error by default
+    pub fn set_stack_policy(&mut self, policy: StackPolicy) {
+        self.stack_policy = policy;
+    }
+
+    pub fn stack_policy(&self) -> StackPolicy {
+        return self.stack_policy;
+    }
+
+    /// Seeds the random numbers CXNN draws, so runs with the same seed and input repeat exactly
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Random::Seeded(StdRng::seed_from_u64(seed));
+    }
+
+    /// Draws CXNN's random numbers from another source, e.g. a microcontroller's hardware RNG
+    pub fn set_rng(&mut self, rng: impl RngCore + Send + Sync + 'static) {
+        self.rng = Random::Custom(Box::new(rng));
+    }
+
+    /// Starts recording which addresses get executed and read as data
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new(self.mem.len()));
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        return self.coverage.as_ref();
+    }
+
+    /// Starts recording which pixels sprites draw to and collide on, frame by frame
+    pub fn enable_draw_map(&mut self) {
+        self.draw_map = Some(DrawMap::new(self.framebuffer.width() * self.framebuffer.height()));
+    }
+
+    pub fn disable_draw_map(&mut self) {
+        self.draw_map = None;
+    }
+
+    pub fn draw_map(&self) -> Option<&DrawMap> {
+        return self.draw_map.as_ref();
+    }
 
-        for i in 0..file.len() {
-            self.mem[0x200 + i] = file[i];
+    /// Starts timing each instruction and frame, which slows the machine down a little
+    #[cfg(feature = "std")]
+    pub fn enable_timings(&mut self) {
+        self.timings = Some(Timings::default());
+    }
+
+    #[cfg(feature = "std")]
+    pub fn timings(&self) -> Option<&Timings> {
+        return self.timings.as_ref();
+    }
+
+    /// Runs one 60th of a second: cycles_per_frame instructions followed by a timer tick. The
+    /// instructions left once the machine's idle aren't run, unless there are exec hooks
+    /// expecting every one
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        let hooked = self.pre_exec_hook.is_some() || self.post_exec_hook.is_some();
+        for _ in 0..self.cycles_per_frame {
+            if self.exited || (!hooked && self.idle()) {
+                break;
+            }
+            self.execute()?;
         }
+        self.tick_timers();
 
         return Ok(());
     }
 
+    /// Presses or releases one of the 16 keys on the keypad (0x0 to 0xF)
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keys[(key & 0xF) as usize] = pressed;
+    }
+
+    /// Counts the delay and sound timers down by one, should be called at 60Hz. This is also
+    /// the end of a frame, when the screen becomes the front buffer and goes out to frame_rx
+    pub fn tick_timers(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+        self.end_frame();
+    }
+
+    fn end_frame(&mut self) {
+        #[cfg(feature = "std")]
+        let started = self.timings.is_some().then(Instant::now);
+
+        self.front.clone_from(&self.framebuffer);
+        self.drawn = false;
+        if let Some(draw_map) = &mut self.draw_map {
+            draw_map.end_frame(self.framebuffer.width() * self.framebuffer.height());
+        }
+
+        // A subscriber that's fallen behind misses this frame, and one that's gone is dropped
+        #[cfg(feature = "std")]
+        self.frame_txs.retain(|frame_tx| match frame_tx.try_send(self.front.clone()) {
+            Ok(()) | Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+
+        #[cfg(feature = "std")]
+        if let (Some(timings), Some(started)) = (&mut self.timings, started) {
+            timings.frame(started.elapsed());
+        }
+    }
+
+    /// Subscribes to finished frames, each a copy of the screen as it was at the end of a
+    /// frame. Only the last FRAMES_BUFFERED are kept until they're received
+    #[cfg(feature = "std")]
+    pub fn frame_rx(&mut self) -> Receiver<Framebuffer> {
+        let (frame_tx, frame_rx) = mpsc::sync_channel(FRAMES_BUFFERED);
+        self.frame_txs.push(frame_tx);
+        return frame_rx;
+    }
+
+    pub fn pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    pub fn opcode(&self) -> u16 {
+        return self.opcode;
+    }
+
+    /// The address register (I)
+    pub fn ar(&self) -> u32 {
+        return self.ar;
+    }
+
+    pub fn platform(&self) -> Platform {
+        return self.platform;
+    }
+
+    /// The screen as it is right now, which mid frame can be partly drawn
+    pub fn framebuffer(&self) -> &Framebuffer {
+        return &self.framebuffer;
+    }
+
+    /// The screen as it was at the end of the last frame
+    pub fn front_buffer(&self) -> &Framebuffer {
+        return &self.front;
+    }
+
+    /// The screen as 0xRRGGBB colours row by row, ready for a frontend to draw. This is
+    /// the blended Mega-Chip screen when that's on, otherwise the framebuffer's colours
+    pub fn screen_rgb(&self) -> Vec<u32> {
+        if let Some(megachip) = self.megachip.as_ref().filter(|m| m.enabled) {
+            return megachip.screen().iter().map(|argb| argb & 0xFF_FFFF).collect();
+        }
+
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        let mut rgb = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                rgb.push(self.framebuffer.rgb(x, y));
+            }
+        }
+        return rgb;
+    }
+
+    /// The second keypad and I/O port, None on anything but CHIP-8X
+    pub fn chip8x(&self) -> Option<&Chip8X> {
+        return self.chip8x.as_ref();
+    }
+
+    /// The Mega-Chip palette, sound and blending state, None on the other platforms
+    pub fn megachip(&self) -> Option<&MegaChip> {
+        return self.megachip.as_ref();
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        return &self.registers;
+    }
+
+    /// Sets Vx, used by tools that poke at the machine from outside
+    pub fn set_register(&mut self, x: u8, value: u8) {
+        self.registers[(x & 0xF) as usize] = value;
+    }
+
+    /// Whether there's nothing for the machine to do until a key's pressed, or ever: it's on a
+    /// jump to itself, or waiting for a key with none held, and both timers have run down.
+    /// Running it on changes nothing, so frontends can wait on input rather than pacing frames
+    pub fn idle(&self) -> bool {
+        if self.delay != 0 || self.sound != 0 {
+            return false;
+        }
+        let pc = self.pc as usize;
+        let opcode = (self.mem_at(pc) as u16) << 8 | self.mem_at(pc + 1) as u16;
+        let spinning = self.pc <= 0xFFF && opcode == 0x1000 | self.pc;
+        let waiting = opcode & 0xF0FF == 0xF00A && !self.keys.contains(&true);
+        return spinning || waiting;
+    }
+
+    /// Whether the rom has run SUPER-CHIP's exit (00FD), and the frontend should save what it
+    /// keeps and close
+    pub fn exit_requested(&self) -> bool {
+        return self.exited;
+    }
+
+    /// The RPL user flags, which frontends save so they last between runs
+    pub fn rpl_flags(&self) -> &[u8; 16] {
+        return &self.rpl;
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: &[u8; 16]) {
+        self.rpl = *flags;
+    }
+
+    /// The return addresses currently on the stack, outermost call first
+    pub fn call_stack(&self) -> &[u16] {
+        return &self.stack[..self.sp as usize];
+    }
+
+    pub fn sp(&self) -> u8 {
+        return self.sp;
+    }
+
+    pub fn delay(&self) -> u8 {
+        return self.delay;
+    }
+
+    pub fn sound(&self) -> u8 {
+        return self.sound;
+    }
+
+    /// The XO-CHIP audio pattern, if the rom has loaded one
+    pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+        return self.audio_pattern.as_ref();
+    }
+
+    pub fn pitch(&self) -> u8 {
+        return self.pitch;
+    }
+
     /// Executes the next instruction
-    pub fn execute(&mut self) {
+    pub fn execute(&mut self) -> Result<(), Chip8Error> {
+        if let Some(mut hook) = self.pre_exec_hook.take() {
+            let mut state = self.state();
+            state.opcode = (self.mem_at(self.pc as usize) as u16) << 8 | self.mem_at(self.pc as usize + 1) as u16;
+            hook(&state);
+            self.pre_exec_hook = Some(hook);
+        }
+
+        if self.strict && self.pc as usize + 2 > self.mem.len() {
+            return Err(Chip8Error::OutOfBounds { pc: self.pc, addr: self.mem.len() as u32 });
+        }
+
+        #[cfg(feature = "std")]
+        let started = self.timings.is_some().then(Instant::now);
         self.get_next_instruction();
+        #[cfg(feature = "std")]
+        let decoded = self.timings.is_some().then(Instant::now);
 
+        #[cfg(feature = "std")]
         if self.debug {
-            println!(
-                "OPCODE: 0x{} {}, PC: {}, I: {:?}",
-                self.mem[self.pc as usize],
-                self.mem[(self.pc as usize) + 1],
-                self.pc,
-                self.ar
-            );
+            println!("OPCODE: {:04X}, PC: {:04X}, I: {:03X}", self.opcode, self.instruction_pc(), self.ar);
+        }
+
+        // On an error the PC is put back so the machine is left at the faulting instruction
+        if let Err(e) = self.execute_opcode() {
+            self.pc = self.instruction_pc();
+            return Err(e);
+        }
+
+        #[cfg(feature = "std")]
+        if let (Some(timings), Some(started), Some(decoded)) = (&mut self.timings, started, decoded) {
+            timings.instruction(self.opcode, decoded - started, decoded.elapsed());
+        }
+
+        if let Some(mut hook) = self.post_exec_hook.take() {
+            hook(&self.state());
+            self.post_exec_hook = Some(hook);
+        }
+
+        return Ok(());
+    }
+
+    fn execute_opcode(&mut self) -> Result<(), Chip8Error> {
+        if self.chip8x.is_some() && self.execute_chip8x() {
+            return Ok(());
+        }
+        if self.execute_superchip()? {
+            return Ok(());
         }
 
         match (self.opcode >> 12) & 0xF {
             0x0 => {
+                if self.megachip.is_some() && self.execute_megachip() {
+                    return Ok(());
+                }
                 match self.opcode {
                     0x00E0 => self.clear_display(),
+                    // The hires patch clears both pages of the display with 0230
+                    0x0230 if self.platform == Platform::HiresChip8 => self.clear_display(),
                     0x00EE => {
                         // Sets the PC to the address at the top of the stack
-                        for i in 0..self.stack.len() {
-                            if i == 0 {
-                                self.pc = self.stack[i - 1];
-                                self.sp -= 1;
-                                break;
-                            }
-                            if i == self.stack.len() - 1 {
-                                eprintln!("Stack overflow");
-                            }
+                        if self.sp == 0 {
+                            return Err(Chip8Error::StackUnderflow { pc: self.instruction_pc() });
                         }
-                        eprintln!("Stack is empty")
+                        self.sp -= 1;
+                        self.pc = self.stack[self.sp as usize];
                     },
-                    _ => eprint!("Unknown instruction")
+                    // SUPER-CHIP's exit, and the platforms built on it. Elsewhere it's a machine
+                    // code call like any other 0NNN. Staying on the exit means running on just
+                    // exits again
+                    0x00FD if self.platform.extends(Platform::SuperChip) => {
+                        self.exited = true;
+                        self.pc = self.instruction_pc();
+                    },
+                    _ => self.call_machine_code()?,
                 }
             },
-            0x1 => self.pc = self.opcode & 0xF,
+            0x1 => self.pc = self.opcode & 0x0FFF,
             0x2 => {
                 // Call address nnn
-                self.sp += 1;
-                // Put the PC on top of the stack
-                for i in 0..self.stack.len() {
-                    if self.stack[i] == 0 {
-                        self.stack[i] = self.pc;
-                        break;
+                if self.sp as usize >= self.stack_depth {
+                    match self.stack_policy {
+                        StackPolicy::Error => {
+                            return Err(Chip8Error::StackOverflow {
+                                pc: self.instruction_pc(),
+                                call_trace: self.call_stack().to_vec(),
+                            });
+                        },
+                        StackPolicy::Wrap => {
+                            self.stack.copy_within(1..self.sp as usize, 0);
+                            self.sp -= 1;
+                        },
                     }
                 }
+                // Put the PC on top of the stack
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
                 // Set the pc to the call address
-                self.pc = self.opcode >> 4;
+                self.pc = self.opcode & 0x0FFF;
             },
             0x3 => {
                 let x = ((self.opcode >> 8) & 0x0F) as u8;
                 let kk = (self.opcode & 0xFF) as u8;
                 if self.registers[x as usize] == kk {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
             0x4 => {
                 let x = ((self.opcode >> 8) & 0x0F) as u8;
                 let kk = (self.opcode & 0xFF) as u8;
                 if self.registers[x as usize] != kk {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
+            // Only N = 0 is a skip, the other 5XYN and 9XYN are left to the platforms that add them
+            0x5 | 0x9 if self.opcode & 0xF != 0 => self.unknown_instruction()?,
             0x5 => {
                 let vx = self.registers[((self.opcode >> 8) & 0x0F) as usize];
                 let vy = self.registers[((self.opcode >> 4) & 0x0F) as usize];
                 if vx == vy {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
-            0x6 => {
-                println!("{}", (self.opcode >> 8) & 0x0F);
-                self.registers[((self.opcode >> 8) & 0x0F) as usize] = (self.opcode & 0xFF) as u8
+            0x6 => self.registers[((self.opcode >> 8) & 0x0F) as usize] = (self.opcode & 0xFF) as u8,
+            0x7 => {
+                let x = ((self.opcode >> 8) & 0x0F) as usize;
+                self.registers[x] = self.registers[x].wrapping_add((self.opcode & 0xFF) as u8);
             },
-            0x7 => self.registers[((self.opcode >> 8) & 0x0F) as usize] += (self.opcode & 0xFF) as u8,
             0x8 => {
-                let vx = self.registers[((self.opcode >> 8) & 0x0F) as usize];
+                let x = ((self.opcode >> 8) & 0x0F) as usize;
+                let vx = self.registers[x];
                 let vy = self.registers[((self.opcode >> 4) & 0x0F) as usize];
-                match self.opcode >> 12 {
-                    0x0 => self.registers[((self.opcode >> 8) & 0x0F) as usize] = vy,
-                    0x1 => self.registers[((self.opcode >> 8) & 0x0F) as usize] = vx | vy,
-                    0x2 => self.registers[((self.opcode >> 8) & 0x0F) as usize] = vx & vy,
-                    0x3 => self.registers[((self.opcode >> 8) & 0x0F) as usize] = vx ^ vy,
-                    0x4 => {
-                        let mut result = vx as u16 + vy as u16;
-                        if result > 255 {
-                            self.registers[0xF] = 1;
-                            result %= 255;
-                        } else {
+                // The arithmetic sets VF after the result, so with VF as Vx the flag is what's left
+                match self.opcode & 0xF {
+                    0x0 => self.registers[x] = vy,
+                    0x1..=0x3 => {
+                        self.registers[x] = match self.opcode & 0xF {
+                            0x1 => vx | vy,
+                            0x2 => vx & vy,
+                            _ => vx ^ vy,
+                        };
+                        if self.quirks.vf_reset {
                             self.registers[0xF] = 0;
                         }
-                        self.registers[((self.opcode >> 8) & 0x0F) as usize] = result as u8;
+                    },
+                    0x4 => {
+                        let (result, carry) = vx.overflowing_add(vy);
+                        self.registers[x] = result;
+                        self.registers[0xF] = carry as u8;
                     },
                     0x5 => {
-                        if vx > vy {
-                            self.registers[0xF] = 1;
-                        } else {
-                            self.registers[0xF] = 0;
-                        }
-                        self.registers[((self.opcode >> 8) & 0x0F) as usize] = vx - vy;
+                        self.registers[x] = vx.wrapping_sub(vy);
+                        self.registers[0xF] = (vx >= vy) as u8;
                     },
                     0x6 => {
-                        self.registers[0xF] = vx & 1;
-                        self.registers[((self.opcode >> 8) & 0x0F) as usize] = vx >> 1;
+                        let shifted = if self.quirks.shift_vy { vy } else { vx };
+                        self.registers[x] = shifted >> 1;
+                        self.registers[0xF] = shifted & 1;
                     },
                     0x7 => {
-                        if vy > vx {
-                            self.registers[0xF] = 1;
-                        } else {
-                            self.registers[0xF] = 0;
-                        }
-                        self.registers[((self.opcode >> 8) & 0x0F) as usize] = vy - vx;
+                        self.registers[x] = vy.wrapping_sub(vx);
+                        self.registers[0xF] = (vy >= vx) as u8;
                     },
                     0xE => {
-                        self.registers[0xF] = (vx >> 7) & 1;
-                        self.registers[((self.opcode >> 8) & 0x0F) as usize] = vx << 1;
-                    }
-                    _ => eprintln!("Unknown instruction")
+                        let shifted = if self.quirks.shift_vy { vy } else { vx };
+                        self.registers[x] = shifted << 1;
+                        self.registers[0xF] = shifted >> 7;
+                    },
+                    _ => self.unknown_instruction()?,
                 }
             },
             0x9 => {
@@ -226,64 +1133,123 @@ impl Chip8 {
                 let vy = self.registers[((self.opcode >> 4) & 0x0F) as usize];
 
                 if vx != vy {
-                    self.pc += 2;
+                    self.skip();
                 }
             },
-            0xA => self.registers[usize::from(self.ar)] = (self.opcode & 0xF) as u8,
+            0xA => self.ar = (self.opcode & 0x0FFF) as u32,
             0xB => {
-                let v0 = self.registers[0x0];
-                self.pc = self.opcode & 0xF + v0 as u16;
+                let offset = if self.quirks.jump_vx { (self.opcode >> 8) & 0x0F } else { 0 };
+                self.pc = (self.opcode & 0x0FFF) + self.registers[offset as usize] as u16;
             },
             0xC => {
-                let rand_byte = rand::random::<u8>();
+                let rand_byte = self.rng.gen::<u8>();
                 let kk = (self.opcode & 0xFF) as u8;
                 self.registers[((self.opcode >> 8) & 0x0F) as usize] = rand_byte & kk;
             },
-            0xD => self.draw_sprite(),
+            0xD => {
+                // Waiting for the next frame is running this instruction again until it comes
+                if self.quirks.display_wait && self.drawn {
+                    self.pc = self.instruction_pc();
+                    return Ok(());
+                }
+                if self.megachip.as_ref().is_some_and(|m| m.enabled) {
+                    self.draw_megachip_sprite();
+                } else {
+                    self.draw_sprite()?;
+                }
+                self.drawn = true;
+            },
             0xE => {
+                let vx = self.registers[((self.opcode >> 8) & 0x0F) as usize];
                 match self.opcode & 0xFF {
-                    0x9E => todo!(),
-                    0xA1 => todo!(),
-                    _ => eprintln!("Unknown instruction")
+                    0x9E => {
+                        if self.keys[(vx & 0xF) as usize] {
+                            self.skip();
+                        }
+                    },
+                    0xA1 => {
+                        if !self.keys[(vx & 0xF) as usize] {
+                            self.skip();
+                        }
+                    },
+                    _ => self.unknown_instruction()?,
                 }
             },
             0xF => {
                 let vx = self.registers[((self.opcode >> 8) & 0x0F) as usize];
                 match self.opcode & 0xFF {
+                    0x00 if self.opcode == 0xF000 && self.platform == Platform::XoChip => {
+                        // Long I: the 16-bit address is the next two bytes
+                        self.check_range(self.pc as usize, 2)?;
+                        self.fetched(self.pc as usize, 2);
+                        self.ar = (self.mem_at(self.pc as usize) as u32) << 8 | self.mem_at(self.pc as usize + 1) as u32;
+                        self.step_pc(2);
+                    },
+                    0x02 if self.opcode == 0xF002 && self.platform == Platform::XoChip => {
+                        self.check_range(self.ar as usize, 16)?;
+                        let mut pattern = [0; 16];
+                        for (i, byte) in pattern.iter_mut().enumerate() {
+                            *byte = self.load_mem_at(self.ar as usize + i);
+                        }
+                        self.audio_pattern = Some(pattern);
+                    },
                     0x07 => self.registers[((self.opcode >> 8) & 0x0F) as usize] = self.delay,
-                    0x0A => todo!(),
-                    0x15 => self.sound = vx,
-                    0x18 => self.delay = vx,
-                    0x1E => self.ar = self.ar + u12::U12::from(vx),
-                    0x29 => self.ar = u12::U12::from(vx * 0x5),
+                    0x0A => {
+                        // Waits for a key press by running this instruction again until one is held
+                        match self.keys.iter().position(|&k| k) {
+                            Some(key) => self.registers[((self.opcode >> 8) & 0x0F) as usize] = key as u8,
+                            None => self.pc = self.instruction_pc(),
+                        }
+                    },
+                    0x15 => self.delay = vx,
+                    0x18 => self.sound = vx,
+                    0x3A if self.platform == Platform::XoChip => self.pitch = vx,
+                    0x1E => self.ar = self.ar.wrapping_add(vx as u32),
+                    0x29 => self.ar = (vx & 0xF) as u32 * 0x5,
+                    0x30 if self.platform.extends(Platform::SuperChip) => self.ar = BIG_FONT_ADDR + (vx & 0xF) as u32 * 10,
                     0x33 => {
-                        let value: Vec<u8> = vx
-                            .to_string()
-                            .chars()
-                            .map(|c| c.to_digit(10).unwrap() as u8)
-                            .collect::<Vec<u8>>()
-                            .into_iter()
-                            .rev()
-                            .collect();
-                        for i in 0..value.len() {
-                            self.mem[usize::from(self.ar) + i] = value[i];
+                        // Hundreds, tens then ones
+                        self.check_range(self.ar as usize, 3)?;
+                        for (i, digit) in [vx / 100, vx / 10 % 10, vx % 10].into_iter().enumerate() {
+                            self.set_mem_at(self.ar as usize + i, digit)?;
                         }
                     },
                     0x55 => {
+                        self.check_range(self.ar as usize, ((self.opcode >> 8) & 0x0F) as usize + 1)?;
                         for i in 0..=((self.opcode >> 8) & 0x0F) as usize {
-                            self.mem[usize::from(self.ar) + i] = self.registers[i];
+                            self.set_mem_at(self.ar as usize + i, self.registers[i])?;
+                        }
+                        if self.quirks.memory_moves_i {
+                            self.ar += ((self.opcode >> 8) & 0x0F) as u32 + 1;
                         }
                     },
+                    0x75 if self.platform.extends(Platform::SuperChip) => {
+                        let x = ((self.opcode >> 8) & 0x0F) as usize;
+                        self.rpl[..=x].copy_from_slice(&self.registers[..=x]);
+                    },
+                    0x85 if self.platform.extends(Platform::SuperChip) => {
+                        let x = ((self.opcode >> 8) & 0x0F) as usize;
+                        self.registers[..=x].copy_from_slice(&self.rpl[..=x]);
+                    },
                     0x65 => {
+                        self.check_range(self.ar as usize, ((self.opcode >> 8) & 0x0F) as usize + 1)?;
+                        if let Some(coverage) = &mut self.coverage {
+                            coverage.mark_read(self.ar as usize, ((self.opcode >> 8) & 0x0F) as usize + 1);
+                        }
                         for i in 0..=((self.opcode >> 8) & 0x0F) as usize {
-                            self.registers[i] = self.mem[usize::from(self.ar) + i];
+                            self.registers[i] = self.load_mem_at(self.ar as usize + i);
+                        }
+                        if self.quirks.memory_moves_i {
+                            self.ar += ((self.opcode >> 8) & 0x0F) as u32 + 1;
                         }
                     },
-                    _ => eprintln!("Unknown instruction")
+                    _ => self.unknown_instruction()?,
                 }
             }
             _ => {}
         }
+
+        return Ok(());
     }
 
     pub fn get_next_instruction(&mut self) {
@@ -292,48 +1258,116 @@ impl Chip8 {
 
         // Gets the first 8-bit instruction, left shift it to the first 8 bits, and then
         // bitwise OR the next instruction so it takes up the second 8 bits
-        self.opcode = (self.mem[i] as u16) << 8 | self.mem[i + 1] as u16;
+        self.opcode = (self.mem_at(i) as u16) << 8 | self.mem_at(i + 1) as u16;
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark_executed(i);
+        }
+        self.fetched(i, 2);
 
         // Increment the PC twice
-        self.pc += 2;
+        self.step_pc(2);
+    }
+
+    /// Moves the PC on by bytes, wrapping round to the start of memory rather than running off
+    /// the end of it (or out of the PC's 16 bits)
+    fn step_pc(&mut self, bytes: u16) {
+        self.pc = (self.pc.wrapping_add(bytes) as usize % self.mem.len()) as u16;
+    }
+
+    /// Where the instruction that's running is, the PC being past it while it runs
+    fn instruction_pc(&self) -> u16 {
+        return ((self.pc as usize + self.mem.len() - 2) % self.mem.len()) as u16;
+    }
+
+    /// Skips the next instruction, stepping over both halves of XO-CHIP's 4 byte F000 NNNN
+    fn skip(&mut self) {
+        let next = (self.mem_at(self.pc as usize) as u16) << 8 | self.mem_at(self.pc as usize + 1) as u16;
+        if self.platform == Platform::XoChip && next == 0xF000 {
+            self.step_pc(4);
+        } else {
+            self.step_pc(2);
+        }
+    }
+
+    /// Reports an opcode nothing decodes, which is then skipped over unless in strict mode
+    fn unknown_instruction(&self) -> Result<(), Chip8Error> {
+        if self.strict {
+            return Err(Chip8Error::UnknownInstruction { pc: self.instruction_pc(), opcode: self.opcode });
+        }
+        #[cfg(feature = "std")]
+        eprintln!("Unknown instruction {:04X} at {:04X}, skipped", self.opcode, self.instruction_pc());
+        return Ok(());
+    }
+
+    /// Runs 0NNN according to the SYS policy
+    fn call_machine_code(&mut self) -> Result<(), Chip8Error> {
+        let addr = self.opcode & 0x0FFF;
+        match &mut self.sys_policy {
+            SysPolicy::Ignore if !self.strict => {},
+            SysPolicy::Ignore | SysPolicy::Error => return Err(Chip8Error::MachineCodeCall { pc: self.instruction_pc(), addr }),
+            SysPolicy::Call(_) => {
+                // The handler needs the whole machine, so it's taken out while it runs
+                let policy = core::mem::replace(&mut self.sys_policy, SysPolicy::Ignore);
+                if let SysPolicy::Call(mut handler) = policy {
+                    handler(self, addr);
+                    self.sys_policy = SysPolicy::Call(handler);
+                }
+            },
+        }
+        return Ok(());
     }
 
     pub fn clear_display(&mut self) {
-        // Resets the graphics array to all 0s
-        self.graphics.fill(0);
+        // Resets the framebuffer to all 0s
+        self.framebuffer.clear();
+        if let Some(megachip) = &mut self.megachip {
+            megachip.clear_screen();
+        }
     }
 
-    fn draw_sprite(&mut self) {
-        let x = ((self.opcode >> 8) & 0x0F) as u8;
-        let y = ((self.opcode >> 4) & 0x0F) as u8;
-        let n = (self.opcode >> 12) as u8;
+    /// Draws the N row sprite at I to Vx, Vy, XORing it onto the screen. The position wraps
+    /// around the screen but the sprite doesn't, whatever goes past the edges is cut off.
+    /// VF is set if any pixel was turned off. On SUPER-CHIP, N of 0 draws a 16x16 sprite
+    fn draw_sprite(&mut self) -> Result<(), Chip8Error> {
+        let x = ((self.opcode >> 8) & 0x0F) as usize;
+        let y = ((self.opcode >> 4) & 0x0F) as usize;
+        let (n, sprite_width) = match self.opcode & 0x0F {
+            0 if self.superchip() => (16, 16),
+            n => (n as usize, 8),
+        };
+        let bytes = n * sprite_width / 8;
 
-        let x_coord = self.registers[x as usize] % 64;
-        let y_coord = self.registers[y as usize] % 32;
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        let x_coord = self.registers[x] as usize % width;
+        let y_coord = self.registers[y] as usize % height;
 
+        self.check_range(self.ar as usize, bytes)?;
         self.registers[0xF] = 0;
 
-        for row in 0..n {
-            let sprite = self.mem[usize::from(self.ar) + row as usize];
-            let mut bits = [0u8; 8];
-            for i in 0..8 {
-                bits[7 - i] = ((sprite >> i) & 1) as u8;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark_read(self.ar as usize, bytes);
+        }
+
+        for row in 0..n.min(height - y_coord) {
+            let mut sprite = 0u16;
+            for byte in 0..sprite_width / 8 {
+                sprite = sprite << 8 | self.load_mem_at(self.ar as usize + row * sprite_width / 8 + byte) as u16;
             }
-            for col in 0..8 {
-                let x_cor = x_coord + col;
-                let y_cor = y_coord + row;
-                if bits[col as usize] == 1 && self.graphics[(x_cor * 64 + y_cor) as usize] == 1 {
+            for col in 0..sprite_width.min(width - x_coord) {
+                if sprite & (1 << (sprite_width - 1 - col)) == 0 {
+                    continue;
+                }
+                let pixel = (y_coord + row) * width + x_coord + col;
+                let collision = self.framebuffer.flip(x_coord + col, y_coord + row);
+                if collision {
                     self.registers[0xF] = 1;
-                } else if bits[col as usize] == 1 {
-                    self.graphics[(x_cor * 64 + y_cor) as usize] = 1;
                 }
-                if y_cor > 63 {
-                    break;
+                if let Some(draw_map) = &mut self.draw_map {
+                    draw_map.mark(pixel, collision);
                 }
             }
-            if x_coord + row > 31 {
-                break;
-            }
         }
+        return Ok(());
     }
 }
\ No newline at end of file
@@ -0,0 +1,170 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::snapshot::Reader;
+use super::Chip8;
+use crate::framebuffer::{ZONE_HEIGHT, ZONE_WIDTH};
+
+// CHIP-8X (RCA, 1980) is the CHIP-8 of the VIP with the colour board (VP-590), the second
+// hex keypad (VP-580) and the simple sound board fitted. It replaces BNNN and adds:
+//
+//   02A0        step the background colour on to the next one
+//   5XY1        Vx = Vx + Vy, each nibble added on its own and kept to 3 bits
+//   BXY0        colour zones: low nibble of Vx is the leftmost zone column and high nibble
+//               how many more to the right, V(x+1) the same for zone rows, Vy the colour
+//   BXYN        colour the zones under an 8xN sprite at (Vx, V(x+1)) with Vy
+//   EXF2        skip if the key Vx is held on the second keypad
+//   EXF5        skip if the key Vx isn't held on the second keypad
+//   FXF8        output Vx to the I/O port
+//   FXFB        wait for a byte on the I/O port and put it in Vx
+//
+// The colours themselves live in the framebuffer's colour zones so frontends can draw them.
+
+
+/// The state CHIP-8X adds to the interpreter besides the colours
+#[derive(Clone)]
+pub struct Chip8X {
+    keys: [bool; 16],
+    output: Option<u8>,
+    input: Option<u8>,
+}
+
+impl Chip8X {
+    pub fn new() -> Self {
+        return Self {
+            keys: [false; 16],
+            output: None,
+            input: None,
+        };
+    }
+
+    /// Whether each key on the second keypad is held down
+    pub fn keys(&self) -> &[bool; 16] {
+        return &self.keys;
+    }
+
+    /// The byte sent to the I/O port that FXFB hasn't picked up yet
+    pub fn port_input(&self) -> Option<u8> {
+        return self.input;
+    }
+
+    /// Appends the state to a save state, as keys (16 bytes, 1 for held), then the port's
+    /// output and input, each a u8 1 and the byte if there is one or a 0
+    pub(super) fn write(&self, out: &mut Vec<u8>) {
+        out.extend(self.keys.iter().map(|held| *held as u8));
+        for byte in [self.output, self.input] {
+            match byte {
+                Some(byte) => out.extend_from_slice(&[1, byte]),
+                None => out.push(0),
+            }
+        }
+    }
+
+    pub(super) fn read(reader: &mut Reader) -> Result<Self, String> {
+        let keys = reader.array::<16>()?.map(|held| held != 0);
+        let mut port = [None, None];
+        for byte in &mut port {
+            if reader.u8()? != 0 {
+                *byte = Some(reader.u8()?);
+            }
+        }
+        let [output, input] = port;
+        return Ok(Self { keys, output, input });
+    }
+}
+
+impl Default for Chip8X {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Chip8 {
+    /// Presses or releases one of the 16 keys on the second keypad, only used by CHIP-8X
+    pub fn set_second_key(&mut self, key: u8, pressed: bool) {
+        if let Some(chip8x) = &mut self.chip8x {
+            chip8x.keys[(key & 0xF) as usize] = pressed;
+        }
+    }
+
+    /// Puts a byte on the I/O port for FXFB to pick up
+    pub fn send_port_input(&mut self, byte: u8) {
+        if let Some(chip8x) = &mut self.chip8x {
+            chip8x.input = Some(byte);
+        }
+    }
+
+    /// Takes the last byte the rom wrote to the I/O port with FXF8, if any
+    pub fn take_port_output(&mut self) -> Option<u8> {
+        return self.chip8x.as_mut().and_then(|chip8x| chip8x.output.take());
+    }
+
+    /// Runs the opcode if it's one of the CHIP-8X instructions, returning whether it was
+    pub(super) fn execute_chip8x(&mut self) -> bool {
+        let x = ((self.opcode >> 8) & 0x0F) as usize;
+        let y = ((self.opcode >> 4) & 0x0F) as usize;
+        let vx = self.registers[x];
+        let vy = self.registers[y];
+        let Some(chip8x) = &mut self.chip8x else {
+            return false;
+        };
+
+        match self.opcode & 0xF000 {
+            0x0000 if self.opcode == 0x02A0 => {
+                if let Some(zones) = self.framebuffer.colour_zones_mut() {
+                    zones.step_background();
+                }
+            },
+            0x5000 if self.opcode & 0xF == 0x1 => {
+                let high = ((vx & 0x70) + (vy & 0x70)) & 0x70;
+                let low = ((vx & 0x07) + (vy & 0x07)) & 0x07;
+                self.registers[x] = high | low;
+            },
+            0xB000 => {
+                let n = (self.opcode & 0xF) as usize;
+                let vx1 = self.registers[(x + 1) & 0xF] as usize;
+                let vx = vx as usize;
+                let columns = self.framebuffer.width().div_ceil(ZONE_WIDTH);
+                let Some(zones) = self.framebuffer.colour_zones_mut() else {
+                    return true;
+                };
+                if n == 0 {
+                    for zy in (vx1 & 0xF)..=(vx1 & 0xF) + (vx1 >> 4) {
+                        for zx in (vx & 0xF)..=(vx & 0xF) + (vx >> 4) {
+                            zones.set_zone_colour(zx, zy, vy);
+                        }
+                    }
+                } else {
+                    // The sprite's 8 pixels wide, so it's over a second column of zones unless
+                    // it lines up with one, which past the right edge is the first
+                    for zy in vx1 / ZONE_HEIGHT..=(vx1 + n - 1) / ZONE_HEIGHT {
+                        for zx in vx / ZONE_WIDTH..=(vx + 7) / ZONE_WIDTH {
+                            zones.set_zone_colour(zx % columns, zy, vy);
+                        }
+                    }
+                }
+            },
+            0xE000 if self.opcode & 0xFF == 0xF2 => {
+                if chip8x.keys[(vx & 0xF) as usize] {
+                    self.skip();
+                }
+            },
+            0xE000 if self.opcode & 0xFF == 0xF5 => {
+                if !chip8x.keys[(vx & 0xF) as usize] {
+                    self.skip();
+                }
+            },
+            0xF000 if self.opcode & 0xFF == 0xF8 => chip8x.output = Some(vx),
+            0xF000 if self.opcode & 0xFF == 0xFB => {
+                // Waits for input by running this instruction again until a byte arrives
+                match chip8x.input.take() {
+                    Some(byte) => self.registers[x] = byte,
+                    None => self.pc = self.instruction_pc(),
+                }
+            },
+            _ => return false,
+        }
+
+        return true;
+    }
+}
@@ -0,0 +1,401 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use super::{Chip8, MAX_STACK_DEPTH};
+use crate::platform::Platform;
+
+// The machine as JSON, for scripts and notebooks looking at a run without linking against the
+// crate (chip8 --dump-state-on-exit writes it when a rom stops). It's one object:
+//
+//   version      1, going up if what's here changes meaning
+//   platform     the platform's name, e.g. "SUPER-CHIP"
+//   pc, opcode, i, sp                              numbers
+//   v, stack, rpl                                  arrays of numbers, the stack outermost first
+//   delay, sound                                   the timers
+//   keys         an array of 16 booleans, true for held
+//   exited       whether the rom ran 00FD
+//   memory       every byte of memory, base64
+//   framebuffer  {width, height, pixels}, the pixels a byte each row by row from the top left
+//                (0 off, otherwise the planes that are on), base64
+//
+// States in JSON load back too, ours and other emulators', so a game can carry on here from
+// where it was left somewhere else and two emulators' states can be compared with chip8 diff.
+// Save state loaders take them wherever they take save states (see snapshot.rs). Other
+// emulators name things their own way, so each field goes by any of the names they use for
+// it, in any case, and only pc, i and v have to be there:
+//
+//   pc, program_counter        i, index, index_register      v, registers, regs, V0-VF
+//   delay, dt, delay_timer     sound, st, sound_timer        stack, keys, keypad, rpl, flags
+//   memory, mem, ram           framebuffer, display, screen, gfx
+//
+// Numbers can be strings of hex ("0x200", "#200" or "200", as traces write them). Memory and
+// the screen can be base64 or arrays of bytes, memory from address 0, and the screen an object
+// as above, rows of pixels, or all of them in one array for a 64x32 or 128x64 screen. The
+// random numbers' seed and the extension chips' state aren't in JSON, so they start afresh.
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+
+impl Chip8 {
+    /// The machine's state as a JSON object, see the top of chip/json.rs
+    pub fn export_state_json(&self) -> String {
+        let state = self.state();
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(json, "  \"version\": 1,").unwrap();
+        writeln!(json, "  \"platform\": \"{}\",", self.platform.name()).unwrap();
+        writeln!(json, "  \"pc\": {},", state.pc).unwrap();
+        writeln!(json, "  \"opcode\": {},", state.opcode).unwrap();
+        writeln!(json, "  \"i\": {},", state.ar).unwrap();
+        writeln!(json, "  \"sp\": {},", state.sp).unwrap();
+        writeln!(json, "  \"v\": [{}],", list(state.registers.iter().map(|v| *v as u32))).unwrap();
+        writeln!(json, "  \"stack\": [{}],", list(state.stack.iter().map(|addr| *addr as u32))).unwrap();
+        writeln!(json, "  \"rpl\": [{}],", list(self.rpl_flags().iter().map(|flag| *flag as u32))).unwrap();
+        writeln!(json, "  \"delay\": {},", state.delay).unwrap();
+        writeln!(json, "  \"sound\": {},", state.sound).unwrap();
+        let keys: Vec<&str> = state.keys.iter().map(|held| if *held { "true" } else { "false" }).collect();
+        writeln!(json, "  \"keys\": [{}],", keys.join(", ")).unwrap();
+        writeln!(json, "  \"exited\": {},", self.exit_requested()).unwrap();
+        writeln!(json, "  \"memory\": \"{}\",", base64(state.mem)).unwrap();
+        let framebuffer = state.framebuffer;
+        writeln!(
+            json,
+            "  \"framebuffer\": {{\"width\": {}, \"height\": {}, \"pixels\": \"{}\"}}",
+            framebuffer.width(),
+            framebuffer.height(),
+            base64(&framebuffer.to_bytes())
+        )
+        .unwrap();
+        writeln!(json, "}}").unwrap();
+        return json;
+    }
+
+    /// Makes a machine in the state a JSON object describes, as export_state_json writes it or
+    /// in the shapes other emulators do (see the top of chip/json.rs)
+    pub fn from_state_json(text: &str) -> Result<Self, String> {
+        let json = Parser { text: text.as_bytes(), at: 0 }.parse()?;
+        if !matches!(json, Json::Object(_)) {
+            return Err("the state isn't a JSON object".to_string());
+        }
+        let platform = match json.field(&["platform"]) {
+            Some(Json::String(name)) => Platform::from_name(&name.replace(' ', "")).ok_or(format!("unknown platform '{name}'"))?,
+            Some(_) => return Err("platform isn't a name".to_string()),
+            None => Platform::Chip8,
+        };
+        let memory = json.bytes(&["memory", "mem", "ram"])?;
+        if memory.as_ref().is_some_and(|memory| memory.len() > 0x100_0000) {
+            return Err("memory is bigger than the 16MB a machine can have".to_string());
+        }
+        let mut chip = match &memory {
+            Some(memory) if memory.len() > platform.memory_size() => Chip8::with_memory_size(platform, memory.len(), false),
+            _ => Chip8::with_platform(platform, false),
+        };
+        if let Some(memory) = memory {
+            chip.mem[..memory.len()].copy_from_slice(&memory);
+        }
+
+        chip.pc = json.number(&["pc", "program_counter"], 0xFFFF)?.ok_or("there's no pc")? as u16;
+        chip.ar = json.number(&["i", "index", "index_register"], u32::MAX)?.ok_or("there's no i")?;
+        let registers = match json.numbers(&["v", "registers", "regs"], 0xFF)? {
+            Some(registers) => registers,
+            // One field a register
+            None => (0..16)
+                .map(|x| json.number(&[&format!("v{x:x}")], 0xFF).and_then(|v| v.ok_or("there's no v".to_string())))
+                .collect::<Result<_, _>>()?,
+        };
+        chip.registers = registers.iter().map(|v| *v as u8).collect::<Vec<_>>().try_into().map_err(|_| "v isn't 16 registers")?;
+        if let Some(opcode) = json.number(&["opcode", "op"], 0xFFFF)? {
+            chip.opcode = opcode as u16;
+        }
+        if let Some(stack) = json.numbers(&["stack"], 0xFFFF)? {
+            // Emulators with a fixed stack keep all of it, only the first sp entries are calls
+            let sp = json.number(&["sp", "stack_pointer"], stack.len() as u32)?.map_or(stack.len(), |sp| sp as usize);
+            if sp > MAX_STACK_DEPTH {
+                return Err(format!("the stack is {sp} calls deep, past the {MAX_STACK_DEPTH} it can go"));
+            }
+            for (level, addr) in chip.stack.iter_mut().zip(&stack[..sp]) {
+                *level = *addr as u16;
+            }
+            chip.sp = sp as u8;
+            chip.stack_depth = chip.stack_depth.max(sp);
+        }
+        chip.delay = json.number(&["delay", "dt", "delay_timer"], 0xFF)?.unwrap_or(0) as u8;
+        chip.sound = json.number(&["sound", "st", "sound_timer"], 0xFF)?.unwrap_or(0) as u8;
+        if let Some(keys) = json.field(&["keys", "keypad"]) {
+            let Json::Array(keys) = keys else {
+                return Err("keys isn't an array".to_string());
+            };
+            for (held, key) in chip.keys.iter_mut().zip(keys) {
+                *held = matches!(key, Json::Bool(true)) || matches!(key, Json::Number(n) if *n != 0.0);
+            }
+        }
+        if let Some(rpl) = json.numbers(&["rpl", "flags"], 0xFF)? {
+            for (flag, value) in chip.rpl.iter_mut().zip(rpl) {
+                *flag = value as u8;
+            }
+        }
+        chip.exited = matches!(json.field(&["exited"]), Some(Json::Bool(true)));
+        if let Some(screen) = json.field(&["framebuffer", "display", "screen", "gfx"]) {
+            let (width, height, pixels) = screen.screen(chip.framebuffer.width(), chip.framebuffer.height())?;
+            chip.framebuffer.resize(width, height);
+            chip.framebuffer.set_bytes(&pixels);
+            chip.front = chip.framebuffer.clone();
+        }
+        return Ok(chip);
+    }
+}
+
+/// A JSON value
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// The field going by any of the names, in any case
+    fn field(&self, names: &[&str]) -> Option<&Json> {
+        let Json::Object(fields) = self else {
+            return None;
+        };
+        return fields.iter().find(|(key, _)| names.iter().any(|name| key.eq_ignore_ascii_case(name))).map(|(_, value)| value);
+    }
+
+    /// The field as a number up to max, if it's there
+    fn number(&self, names: &[&str], max: u32) -> Result<Option<u32>, String> {
+        return self.field(names).map(|value| value.as_number(names[0], max)).transpose();
+    }
+
+    /// The field as an array of numbers up to max, if it's there
+    fn numbers(&self, names: &[&str], max: u32) -> Result<Option<Vec<u32>>, String> {
+        return match self.field(names) {
+            Some(Json::Array(values)) => values.iter().map(|value| value.as_number(names[0], max)).collect::<Result<_, _>>().map(Some),
+            Some(_) => Err(format!("{} isn't an array", names[0])),
+            None => Ok(None),
+        };
+    }
+
+    /// The field as bytes, from base64 or an array, if it's there
+    fn bytes(&self, names: &[&str]) -> Result<Option<Vec<u8>>, String> {
+        return self.field(names).map(|value| value.as_bytes(names[0])).transpose();
+    }
+
+    fn as_number(&self, name: &str, max: u32) -> Result<u32, String> {
+        let n = match self {
+            Json::Number(n) if *n >= 0.0 && *n <= max as f64 && *n == (*n as u32) as f64 => Some(*n as u32),
+            Json::String(hex) => {
+                let digits = hex.trim_start_matches("0x").trim_start_matches("0X").trim_start_matches('#');
+                u32::from_str_radix(digits, 16).ok().filter(|n| *n <= max)
+            },
+            _ => None,
+        };
+        return n.ok_or(format!("{name} has a value that isn't a number from 0 to {max}"));
+    }
+
+    fn as_bytes(&self, name: &str) -> Result<Vec<u8>, String> {
+        return match self {
+            Json::String(text) => from_base64(text).ok_or(format!("{name} isn't base64")),
+            Json::Array(values) => values.iter().map(|value| value.as_number(name, 0xFF).map(|byte| byte as u8)).collect(),
+            _ => Err(format!("{name} isn't base64 or an array of bytes")),
+        };
+    }
+
+    /// The screen's width, height and pixels, sized by what the JSON says or else by the
+    /// screen the machine has
+    fn screen(&self, width: usize, height: usize) -> Result<(usize, usize, Vec<u8>), String> {
+        let (width, height, pixels) = match self {
+            Json::Object(_) => {
+                let width = self.number(&["width", "w"], 1024)?.ok_or("the screen has no width")? as usize;
+                let height = self.number(&["height", "h"], 1024)?.ok_or("the screen has no height")? as usize;
+                (width, height, self.bytes(&["pixels", "data"])?.ok_or("the screen has no pixels")?)
+            },
+            Json::Array(rows) if rows.iter().all(|row| matches!(row, Json::Array(_))) && !rows.is_empty() => {
+                let rows = rows.iter().map(|row| row.as_bytes("a row of the screen")).collect::<Result<Vec<_>, _>>()?;
+                let width = rows[0].len();
+                (width, rows.len(), rows.concat())
+            },
+            _ => {
+                let pixels = self.as_bytes("the screen")?;
+                match pixels.len() {
+                    len if len == width * height => (width, height, pixels),
+                    len if len == 128 * 64 => (128, 64, pixels),
+                    len => return Err(format!("the screen's {len} pixels aren't {width}x{height} or 128x64")),
+                }
+            },
+        };
+        if pixels.len() != width * height {
+            return Err(format!("the screen's {} pixels aren't {width}x{height}", pixels.len()));
+        }
+        return Ok((width, height, pixels));
+    }
+}
+
+/// Reads JSON a value at a time
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    /// The one value in the text
+    fn parse(mut self) -> Result<Json, String> {
+        let value = self.value()?;
+        self.skip_space();
+        if self.at < self.text.len() {
+            return Err(format!("there's more after the JSON at byte {}", self.at));
+        }
+        return Ok(value);
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_space();
+        return match self.text.get(self.at) {
+            Some(b'{') => {
+                self.at += 1;
+                let mut fields = Vec::new();
+                while !self.end(b'}', fields.is_empty())? {
+                    self.skip_space();
+                    let Json::String(key) = self.value()? else {
+                        return Err(format!("expected a field's name at byte {}", self.at));
+                    };
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                }
+                Ok(Json::Object(fields))
+            },
+            Some(b'[') => {
+                self.at += 1;
+                let mut values = Vec::new();
+                while !self.end(b']', values.is_empty())? {
+                    values.push(self.value()?);
+                }
+                Ok(Json::Array(values))
+            },
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.word("true", Json::Bool(true)),
+            Some(b'f') => self.word("false", Json::Bool(false)),
+            Some(b'n') => self.word("null", Json::Null),
+            Some(_) => {
+                let start = self.at;
+                while self.text.get(self.at).is_some_and(|c| c.is_ascii_digit() || b"+-.eE".contains(c)) {
+                    self.at += 1;
+                }
+                let number = core::str::from_utf8(&self.text[start..self.at]).unwrap_or_default();
+                number.parse().map(Json::Number).map_err(|_| format!("expected a value at byte {start}"))
+            },
+            None => Err("the JSON ends before it's finished".to_string()),
+        };
+    }
+
+    /// Whether the array or object ends here rather than going on to its next element,
+    /// stepping over the comma before it if it's not the first
+    fn end(&mut self, end: u8, first: bool) -> Result<bool, String> {
+        self.skip_space();
+        if self.text.get(self.at) == Some(&end) {
+            self.at += 1;
+            return Ok(true);
+        }
+        if !first {
+            self.expect(b',')?;
+        }
+        return Ok(false);
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.at += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let c = *self.text.get(self.at).ok_or("a string doesn't end")?;
+            self.at += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.text.get(self.at).ok_or("a string doesn't end")?;
+                    self.at += 1;
+                    let c = match escaped {
+                        b'n' => '\n',
+                        b't' => '\t',
+                        b'r' => '\r',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'u' => {
+                            let hex = self.text.get(self.at..self.at + 4).ok_or("a string doesn't end")?;
+                            self.at += 4;
+                            let code = core::str::from_utf8(hex).ok().and_then(|hex| u32::from_str_radix(hex, 16).ok());
+                            code.and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        },
+                        c => c as char,
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                },
+                c => bytes.push(c),
+            }
+        }
+        return String::from_utf8(bytes).map_err(|_| "a string isn't UTF-8".to_string());
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if !self.text[self.at..].starts_with(word.as_bytes()) {
+            return Err(format!("expected a value at byte {}", self.at));
+        }
+        self.at += word.len();
+        return Ok(value);
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        self.skip_space();
+        if self.text.get(self.at) != Some(&c) {
+            return Err(format!("expected '{}' at byte {}", c as char, self.at));
+        }
+        self.at += 1;
+        return Ok(());
+    }
+
+    fn skip_space(&mut self) {
+        while self.text.get(self.at).is_some_and(u8::is_ascii_whitespace) {
+            self.at += 1;
+        }
+    }
+}
+
+/// Numbers as the inside of a JSON array
+fn list(numbers: impl Iterator<Item = u32>) -> String {
+    return numbers.map(|n| n.to_string()).collect::<Vec<_>>().join(", ");
+}
+
+/// Standard base64 with padding, written out here so exporting doesn't need the base64 crate
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| bits | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    return encoded;
+}
+
+/// Decodes base64, with or without padding, or None if it isn't
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=').collect();
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let bits = chunk.iter().enumerate().try_fold(0u32, |bits, (i, c)| {
+            let value = BASE64.iter().position(|digit| digit == c)? as u32;
+            return Some(bits | value << (18 - 6 * i));
+        })?;
+        for i in 0..chunk.len().checked_sub(1)? {
+            bytes.push((bits >> (16 - 8 * i)) as u8);
+        }
+    }
+    return Some(bytes);
+}
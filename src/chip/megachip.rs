@@ -0,0 +1,304 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::snapshot::Reader;
+use super::Chip8;
+
+// Mega-Chip (Revival Studios, 2007) extends SUPER-CHIP with a 256x192 screen whose
+// sprites are made of bytes, each an index into a 256 colour ARGB palette, and
+// adds 8-bit digitized sound. Its extra instructions all live in the 0NNN space:
+//
+//   0010        MEGAOFF   back to the normal low resolution mode
+//   0011        MEGAON    switch to 256x192 Mega-Chip mode
+//   00BN        SCRU N    scroll the screen up N lines
+//   01NN NNNN   LDHI      I = NNNNNN, a 24-bit address (4 byte instruction)
+//   02NN        LDPAL NN  load NN ARGB colours from I into the palette, from index 1 on
+//   03NN        SPRW NN   sprite width for DXYN (0 means 256)
+//   04NN        SPRH NN   sprite height for DXYN (0 means 256)
+//   05NN        ALPHA NN  opacity sprites are drawn with
+//   060N        DIGISND N play the sound at I, once if N is 1 or looping if 0
+//   0700        STOPSND   stop the digitized sound
+//   080N        BMODE N   how sprites are blended onto the screen, see BlendMode
+//   09NN        CCOL NN   palette index that sets VF when a sprite is drawn over it
+//
+// Digitized sounds start with a header of a 16-bit sample rate and a 24-bit length,
+// followed by the 8-bit unsigned samples.
+
+pub(super) const WIDTH: usize = 256;
+pub(super) const HEIGHT: usize = 192;
+
+
+/// How Mega-Chip sprites are mixed with what's already on the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Percent25,
+    Percent50,
+    Percent75,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    /// The blend mode BMODE N picks, anything unknown being Normal
+    fn from_bmode(n: u8) -> Self {
+        return match n {
+            1 => BlendMode::Percent25,
+            2 => BlendMode::Percent50,
+            3 => BlendMode::Percent75,
+            4 => BlendMode::Add,
+            5 => BlendMode::Multiply,
+            _ => BlendMode::Normal,
+        };
+    }
+}
+
+/// A digitized sound started by DIGISND, for the audio backend to play
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigitizedSound {
+    /// Where the samples start in memory, just past the header
+    pub addr: u32,
+    pub sample_rate: u16,
+    pub length: u32,
+    pub looping: bool,
+}
+
+/// The state the Mega-Chip extensions add to the interpreter
+#[derive(Clone)]
+pub struct MegaChip {
+    pub(super) enabled: bool,
+    palette: [u32; 256],
+    sprite_width: usize,
+    sprite_height: usize,
+    alpha: u8,
+    blend_mode: BlendMode,
+    collision_colour: u8,
+    screen: Vec<u32>,
+    sound: Option<DigitizedSound>,
+}
+
+impl MegaChip {
+    pub fn new() -> Self {
+        return Self {
+            enabled: false,
+            palette: [0; 256],
+            sprite_width: 8,
+            sprite_height: 8,
+            alpha: 0xFF,
+            blend_mode: BlendMode::Normal,
+            collision_colour: 0,
+            screen: vec![0; WIDTH * HEIGHT],
+            sound: None,
+        };
+    }
+
+    /// Whether Mega-Chip mode has been switched on by MEGAON
+    pub fn enabled(&self) -> bool {
+        return self.enabled;
+    }
+
+    /// The palette as 0xAARRGGBB colours, index 0 is transparent
+    pub fn palette(&self) -> &[u32; 256] {
+        return &self.palette;
+    }
+
+    /// The blended 256x192 screen as 0xAARRGGBB colours, row by row
+    pub fn screen(&self) -> &[u32] {
+        return &self.screen;
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        return self.blend_mode;
+    }
+
+    pub fn alpha(&self) -> u8 {
+        return self.alpha;
+    }
+
+    /// The digitized sound that's playing, if any
+    pub fn sound(&self) -> Option<&DigitizedSound> {
+        return self.sound.as_ref();
+    }
+
+    pub(super) fn clear_screen(&mut self) {
+        self.screen.fill(0);
+    }
+
+    /// Mixes a sprite colour onto a screen colour using the blend mode and alpha
+    fn blend(&self, dst: u32, src: u32) -> u32 {
+        let mut out = 0xFF00_0000;
+        for shift in [16, 8, 0] {
+            let d = (dst >> shift) & 0xFF;
+            let s = (src >> shift) & 0xFF;
+            let mixed = match self.blend_mode {
+                BlendMode::Normal => s,
+                BlendMode::Percent25 => (d * 3 + s) / 4,
+                BlendMode::Percent50 => (d + s) / 2,
+                BlendMode::Percent75 => (d + s * 3) / 4,
+                BlendMode::Add => (d + s).min(0xFF),
+                BlendMode::Multiply => d * s / 0xFF,
+            };
+            let alpha = self.alpha as u32;
+            out |= ((mixed * alpha + d * (0xFF - alpha)) / 0xFF) << shift;
+        }
+        return out;
+    }
+
+    /// Appends the state to a save state: MEGAON as a u8, the palette as 256 u32s, the
+    /// sprite width and height as u32s, the alpha, BMODE and CCOL bytes, the screen as u32s,
+    /// then u8 1 if a sound is playing followed by its address u32, rate u16, length u32 and
+    /// whether it loops
+    pub(super) fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.enabled as u8);
+        for colour in self.palette.iter().chain(&[self.sprite_width as u32, self.sprite_height as u32]) {
+            out.extend_from_slice(&colour.to_le_bytes());
+        }
+        out.extend_from_slice(&[self.alpha, self.blend_mode as u8, self.collision_colour]);
+        for colour in &self.screen {
+            out.extend_from_slice(&colour.to_le_bytes());
+        }
+        out.push(self.sound.is_some() as u8);
+        if let Some(sound) = &self.sound {
+            out.extend_from_slice(&sound.addr.to_le_bytes());
+            out.extend_from_slice(&sound.sample_rate.to_le_bytes());
+            out.extend_from_slice(&sound.length.to_le_bytes());
+            out.push(sound.looping as u8);
+        }
+    }
+
+    pub(super) fn read(reader: &mut Reader) -> Result<Self, String> {
+        let mut megachip = Self::new();
+        megachip.enabled = reader.u8()? != 0;
+        for colour in &mut megachip.palette {
+            *colour = reader.u32()?;
+        }
+        megachip.sprite_width = reader.u32()? as usize;
+        megachip.sprite_height = reader.u32()? as usize;
+        megachip.alpha = reader.u8()?;
+        megachip.blend_mode = BlendMode::from_bmode(reader.u8()?);
+        megachip.collision_colour = reader.u8()?;
+        for colour in &mut megachip.screen {
+            *colour = reader.u32()?;
+        }
+        if reader.u8()? != 0 {
+            megachip.sound = Some(DigitizedSound {
+                addr: reader.u32()?,
+                sample_rate: reader.u16()?,
+                length: reader.u32()?,
+                looping: reader.u8()? != 0,
+            });
+        }
+        return Ok(megachip);
+    }
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Chip8 {
+    /// Runs the opcode if it's one of the Mega-Chip 0NNN instructions, returning whether it was
+    pub(super) fn execute_megachip(&mut self) -> bool {
+        let nn = (self.opcode & 0xFF) as usize;
+        let Some(megachip) = &mut self.megachip else {
+            return false;
+        };
+
+        match self.opcode & 0xFF00 {
+            0x0000 => match self.opcode {
+                0x0010 => {
+                    megachip.enabled = false;
+                    self.framebuffer.resize(64, 32);
+                },
+                0x0011 => {
+                    megachip.enabled = true;
+                    megachip.clear_screen();
+                    self.framebuffer.resize(WIDTH, HEIGHT);
+                },
+                0x00B0..=0x00BF if megachip.enabled => {
+                    let lines = nn & 0xF;
+                    megachip.screen.copy_within(lines * WIDTH.., 0);
+                    megachip.screen[(HEIGHT - lines) * WIDTH..].fill(0);
+                    self.framebuffer.scroll(0, -(lines as isize));
+                },
+                _ => return false,
+            },
+            0x0100 => {
+                // The low 16 bits of the address are in the next two bytes
+                self.fetched(self.pc as usize, 2);
+                let low = (self.mem_at(self.pc as usize) as u32) << 8 | self.mem_at(self.pc as usize + 1) as u32;
+                self.ar = (nn as u32) << 16 | low;
+                self.step_pc(2);
+            },
+            0x0200 => {
+                for i in 0..nn.min(255) {
+                    let at = self.ar as usize + i * 4;
+                    let colour = self.mem.get(at..at + 4).unwrap_or(&[0; 4]);
+                    megachip.palette[i + 1] = u32::from_be_bytes([colour[0], colour[1], colour[2], colour[3]]);
+                }
+            },
+            0x0300 => megachip.sprite_width = if nn == 0 { 256 } else { nn },
+            0x0400 => megachip.sprite_height = if nn == 0 { 256 } else { nn },
+            0x0500 => megachip.alpha = nn as u8,
+            0x0600 => {
+                let at = self.ar as usize;
+                let header = self.mem.get(at..at + 5).unwrap_or(&[0; 5]);
+                megachip.sound = Some(DigitizedSound {
+                    addr: self.ar + 5,
+                    sample_rate: (header[0] as u16) << 8 | header[1] as u16,
+                    length: (header[2] as u32) << 16 | (header[3] as u32) << 8 | header[4] as u32,
+                    looping: nn & 0xF == 0,
+                });
+            },
+            0x0700 => megachip.sound = None,
+            0x0800 => megachip.blend_mode = BlendMode::from_bmode(nn as u8 & 0xF),
+            0x0900 => megachip.collision_colour = nn as u8,
+            _ => return false,
+        }
+
+        return true;
+    }
+
+    /// DXYN in Mega-Chip mode: draws a SPRW x SPRH sprite of palette indexes from I at
+    /// (Vx, Vy). Index 0 is transparent, and VF is set if any pixel lands on one that
+    /// has the collision colour
+    pub(super) fn draw_megachip_sprite(&mut self) {
+        let x_coord = self.registers[((self.opcode >> 8) & 0x0F) as usize] as usize;
+        let y_coord = self.registers[((self.opcode >> 4) & 0x0F) as usize] as usize;
+        let Some(megachip) = &mut self.megachip else {
+            return;
+        };
+
+        self.registers[0xF] = 0;
+
+        if let Some(coverage) = &mut self.coverage {
+            coverage.mark_read(self.ar as usize, megachip.sprite_width * megachip.sprite_height);
+        }
+
+        for row in 0..megachip.sprite_height {
+            for col in 0..megachip.sprite_width {
+                let (x, y) = (x_coord + col, y_coord + row);
+                let at = self.ar as usize + row * megachip.sprite_width + col;
+                let colour = self.mem.get(at).copied().unwrap_or(0);
+                if colour == 0 || x >= WIDTH || y >= HEIGHT {
+                    continue;
+                }
+
+                let collision = self.framebuffer.get(x, y) == megachip.collision_colour;
+                if collision {
+                    self.registers[0xF] = 1;
+                }
+                self.framebuffer.set(x, y, colour);
+                if let Some(draw_map) = &mut self.draw_map {
+                    draw_map.mark(y * WIDTH + x, collision);
+                }
+
+                let dst = megachip.screen[y * WIDTH + x];
+                megachip.screen[y * WIDTH + x] = megachip.blend(dst, megachip.palette[colour as usize]);
+            }
+        }
+    }
+}
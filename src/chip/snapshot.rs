@@ -0,0 +1,641 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use rand::RngCore;
+
+use super::{Chip8, Chip8X, MegaChip, StackPolicy, DEFAULT_PITCH};
+use crate::framebuffer::{Framebuffer, ZONE_HEIGHT, ZONE_WIDTH};
+use crate::platform::Platform;
+
+// Snapshots saved as files (save states) are little endian binary, a header and then the
+// machine's state, packed:
+//
+//   "C8SV"       magic
+//   version      u8, 5
+//   platform     u8, its place in PLATFORMS
+//   packing      u8, how the state is packed: 0 for not at all, 1 for run lengths or 2 for
+//                DEFLATE (with the compression feature)
+//   length       u32, the state's length unpacked
+//   state        the packed state
+//
+// Run length packing is a control byte followed by either control + 1 bytes as they are
+// (0-127), or one byte to repeat control - 125 times (128-255). Memory and the screen are
+// mostly runs of 0s, so save states shrink to a fraction of their size. With the compression
+// feature they're deflated instead, which takes one of BRIX from 4.7KB to about 510 bytes,
+// where run lengths manage 760.
+//
+// The state is
+//
+//   opcode u16, I u32, PC u16, SP u8, the SP return addresses on the stack u16 each,
+//   V0-VF, RPL flags, DT, ST
+//   keys         16 bytes, 1 for held
+//   rng seed     u64
+//   memory       u32 length, then the bytes
+//   screen       the framebuffer, then the front buffer, each as
+//                u32 width, u32 height, u8 1 if the pixels are a bit each (8 to a byte, the
+//                first in the top bit) or 0 if they're a byte each, the pixels, then u8 1 if
+//                there are colour zones followed by the background and a byte for each zone,
+//                row by row
+//   CHIP-8X      u8 1 if it's there, followed by its state, see chip8x.rs
+//   Mega-Chip    u8 1 if it's there, followed by its state, see megachip.rs
+//   XO-CHIP      u8 1 if an audio pattern was loaded followed by its 16 bytes, then the
+//                pitch u8
+//
+// Save states from older releases still load, the versions before 4 being
+//
+//   1   "C8ST", the version, the platform and the state unpacked, without the XO-CHIP audio
+//       (which loads as never having been set)
+//   2   the same as 1 with the XO-CHIP audio
+//   3   "C8SV" with the packing, and all 16 levels of the stack whatever SP was, from
+//       before the stack could be deeper
+//   4   the stack as deep as SP, and the screens a byte a pixel whatever was on them
+//
+// A change to the state goes in a new version, with from_bytes reading the versions before
+// as they were and filling in whatever's new. It reads states in JSON as well, from other
+// emulators or exported from here, see json.rs.
+
+const MAGIC: &[u8; 4] = b"C8SV";
+const VERSION: u8 = 5;
+
+/// The magic of versions 1 and 2, before the header had the packing
+const OLD_MAGIC: &[u8; 4] = b"C8ST";
+
+/// How the state is packed, in the header
+const UNPACKED: u8 = 0;
+const RUN_LENGTHS: u8 = 1;
+const DEFLATE: u8 = 2;
+
+/// The biggest a state unpacks to: the most memory a machine can have plus plenty for the
+/// rest, so a corrupt length can't ask for gigabytes
+const MAX_STATE: usize = 0x100_0000 + 0x10_0000;
+
+const PLATFORMS: [Platform; 6] = [
+    Platform::Chip8, Platform::HiresChip8, Platform::Chip8X, Platform::SuperChip, Platform::XoChip, Platform::MegaChip,
+];
+
+
+/// A copy of everything that changes as the machine runs, taken by Chip8::snapshot and put
+/// back by Chip8::restore. How the machine is set up (hooks, SYS policy, memory protection,
+/// coverage and the draw map) isn't part of it. The screens are kept a bit a pixel as the
+/// machine keeps them, which takes a CHIP-8 snapshot from 9.6KB to 6.2KB
+#[derive(Clone)]
+pub struct Snapshot {
+    opcode: u16,
+    ar: u32,
+    pc: u16,
+    stack: Vec<u16>,
+    registers: [u8; 16],
+    rpl: [u8; 16],
+    mem: Vec<u8>,
+    delay: u8,
+    sound: u8,
+    framebuffer: Framebuffer,
+    front: Framebuffer,
+    keys: [bool; 16],
+    platform: Platform,
+    chip8x: Option<Chip8X>,
+    megachip: Option<MegaChip>,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
+    /// What the random number generator was seeded with when the snapshot was taken
+    rng_seed: u64,
+}
+
+impl Chip8 {
+    /// Takes a snapshot of the machine to restore later. The random number generator can't be
+    /// copied, so it's reseeded from itself and the seed kept, which makes CXNN give the same
+    /// numbers after restoring as it did the first time. That replaces an RNG from set_rng
+    pub fn snapshot(&mut self) -> Snapshot {
+        let rng_seed = self.rng.next_u64();
+        self.seed_rng(rng_seed);
+
+        return Snapshot {
+            opcode: self.opcode,
+            ar: self.ar,
+            pc: self.pc,
+            stack: self.call_stack().to_vec(),
+            registers: self.registers,
+            rpl: self.rpl,
+            mem: self.mem.clone(),
+            delay: self.delay,
+            sound: self.sound,
+            framebuffer: self.framebuffer.clone(),
+            front: self.front.clone(),
+            keys: self.keys,
+            platform: self.platform,
+            chip8x: self.chip8x.clone(),
+            megachip: self.megachip.clone(),
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            rng_seed,
+        };
+    }
+
+    /// Puts the machine back as it was when the snapshot was taken
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.opcode = snapshot.opcode;
+        self.ar = snapshot.ar;
+        self.pc = snapshot.pc;
+        self.stack[..snapshot.stack.len()].copy_from_slice(&snapshot.stack);
+        self.sp = snapshot.stack.len() as u8;
+        // A snapshot with more calls than the stack here goes deep deepens it to hold them all,
+        // unless calls wrap anyway, when the oldest are forgotten as a call past the depth would
+        match self.stack_policy {
+            StackPolicy::Error => self.stack_depth = self.stack_depth.max(self.sp as usize),
+            StackPolicy::Wrap => self.set_stack_depth(self.stack_depth),
+        }
+        self.registers = snapshot.registers;
+        self.rpl = snapshot.rpl;
+        self.mem.clone_from(&snapshot.mem);
+        // The memory's been replaced, maybe by more or less of it, so none of it's been
+        // fetched as code yet
+        if let Some((fetched, _)) = &mut self.code {
+            fetched.clear();
+            fetched.resize(self.mem.len().div_ceil(64), 0);
+        }
+        self.delay = snapshot.delay;
+        self.sound = snapshot.sound;
+        self.framebuffer.clone_from(&snapshot.framebuffer);
+        self.front.clone_from(&snapshot.front);
+        self.keys = snapshot.keys;
+        self.platform = snapshot.platform;
+        self.chip8x.clone_from(&snapshot.chip8x);
+        self.megachip.clone_from(&snapshot.megachip);
+        self.audio_pattern = snapshot.audio_pattern;
+        self.pitch = snapshot.pitch;
+        // Going back to a state is going back to before the rom exited
+        self.exited = false;
+        self.seed_rng(snapshot.rng_seed);
+    }
+}
+
+impl Snapshot {
+    pub fn platform(&self) -> Platform {
+        return self.platform;
+    }
+
+    pub fn pc(&self) -> u16 {
+        return self.pc;
+    }
+
+    pub fn opcode(&self) -> u16 {
+        return self.opcode;
+    }
+
+    /// The address register (I)
+    pub fn ar(&self) -> u32 {
+        return self.ar;
+    }
+
+    pub fn sp(&self) -> u8 {
+        return self.stack.len() as u8;
+    }
+
+    /// The return addresses on the stack, outermost call first
+    pub fn call_stack(&self) -> &[u16] {
+        return &self.stack;
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        return &self.registers;
+    }
+
+    pub fn rpl_flags(&self) -> &[u8; 16] {
+        return &self.rpl;
+    }
+
+    pub fn mem(&self) -> &[u8] {
+        return &self.mem;
+    }
+
+    pub fn delay(&self) -> u8 {
+        return self.delay;
+    }
+
+    pub fn sound(&self) -> u8 {
+        return self.sound;
+    }
+
+    /// The screen as it's being drawn
+    pub fn framebuffer(&self) -> &Framebuffer {
+        return &self.framebuffer;
+    }
+
+    pub fn keys(&self) -> &[bool; 16] {
+        return &self.keys;
+    }
+
+    /// Encodes the snapshot as a save state, in the format at the top of this file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        #[cfg(feature = "compression")]
+        return self.encode(DEFLATE);
+        #[cfg(not(feature = "compression"))]
+        return self.encode(RUN_LENGTHS);
+    }
+
+    /// Encodes the snapshot as a save state with the state left unpacked, for packing some
+    /// other way (see rewind.rs)
+    pub fn to_unpacked_bytes(&self) -> Vec<u8> {
+        return self.encode(UNPACKED);
+    }
+
+    fn encode(&self, packing: u8) -> Vec<u8> {
+        let state = self.state_bytes();
+        let mut out = Vec::with_capacity(state.len() / 4 + 16);
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(PLATFORMS.iter().position(|platform| *platform == self.platform).unwrap_or(0) as u8);
+        out.push(packing);
+        out.extend_from_slice(&(state.len() as u32).to_le_bytes());
+        match packing {
+            UNPACKED => out.extend_from_slice(&state),
+            #[cfg(feature = "compression")]
+            DEFLATE => out.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(&state, 6)),
+            _ => pack(&state, &mut out),
+        }
+        return out;
+    }
+
+    /// Roughly how many bytes the snapshot takes up
+    pub fn size(&self) -> usize {
+        return core::mem::size_of::<Self>() + self.mem.len() + self.framebuffer.size() + self.front.size();
+    }
+
+    /// The state, everything after the header
+    fn state_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.mem.len() + self.framebuffer.bytes().len() * 2 + 256);
+        out.extend_from_slice(&self.opcode.to_le_bytes());
+        out.extend_from_slice(&self.ar.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.push(self.stack.len() as u8);
+        for addr in &self.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.extend_from_slice(&self.registers);
+        out.extend_from_slice(&self.rpl);
+        out.push(self.delay);
+        out.push(self.sound);
+        out.extend(self.keys.iter().map(|held| *held as u8));
+        out.extend_from_slice(&self.rng_seed.to_le_bytes());
+        out.extend_from_slice(&(self.mem.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.mem);
+        write_framebuffer(&mut out, &self.framebuffer);
+        write_framebuffer(&mut out, &self.front);
+
+        out.push(self.chip8x.is_some() as u8);
+        if let Some(chip8x) = &self.chip8x {
+            chip8x.write(&mut out);
+        }
+        out.push(self.megachip.is_some() as u8);
+        if let Some(megachip) = &self.megachip {
+            megachip.write(&mut out);
+        }
+        out.push(self.audio_pattern.is_some() as u8);
+        if let Some(pattern) = &self.audio_pattern {
+            out.extend_from_slice(pattern);
+        }
+        out.push(self.pitch);
+        return out;
+    }
+
+    /// Decodes a save state written by to_bytes, by this release or an older one, or a state in
+    /// JSON (see json.rs)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.trim_ascii_start().starts_with(b"{") {
+            let text = core::str::from_utf8(bytes).map_err(|_| "the JSON state isn't UTF-8")?;
+            return Ok(Chip8::from_state_json(text)?.snapshot());
+        }
+        let mut reader = Reader { bytes };
+        let magic = reader.take(4)?;
+        if magic != MAGIC && magic != OLD_MAGIC {
+            return Err("not a save state".to_string());
+        }
+        let version = reader.u8()?;
+        let supported = if magic == OLD_MAGIC { 1..=2 } else { 3..=VERSION };
+        if !supported.contains(&version) {
+            return Err(format!("save state version {version} isn't supported, only up to {VERSION}"));
+        }
+        let platform = *PLATFORMS.get(reader.u8()? as usize).ok_or("unknown platform")?;
+        if magic == OLD_MAGIC {
+            return Self::from_state(&mut reader, version, platform);
+        }
+
+        let packing = reader.u8()?;
+        let len = reader.u32()? as usize;
+        if len > MAX_STATE {
+            return Err(format!("the save state says it's {len} bytes, more than any machine's state"));
+        }
+        let state = match packing {
+            UNPACKED => reader.take(len)?.to_vec(),
+            RUN_LENGTHS => unpack(reader.take(reader.bytes.len())?, len)?,
+            #[cfg(feature = "compression")]
+            DEFLATE => miniz_oxide::inflate::decompress_to_vec_with_limit(reader.take(reader.bytes.len())?, len)
+                .map_err(|e| format!("the save state doesn't inflate: {e}"))?,
+            #[cfg(not(feature = "compression"))]
+            DEFLATE => return Err("the save state is deflated, which needs the compression feature".to_string()),
+            packing => return Err(format!("the save state is packed in an unknown way ({packing})")),
+        };
+        if state.len() != len {
+            return Err("the save state's length doesn't match what it unpacks to".to_string());
+        }
+        return Self::from_state(&mut Reader { bytes: &state }, version, platform);
+    }
+
+    /// Decodes the state after the header, as the version of the format given laid it out
+    fn from_state(reader: &mut Reader, version: u8, platform: Platform) -> Result<Self, String> {
+        let opcode = reader.u16()?;
+        let ar = reader.u32()?;
+        let pc = reader.u16()?;
+        let sp = reader.u8()?;
+        if version < 4 && sp > 16 {
+            return Err(format!("the stack pointer is {sp}, past the 16 levels of the stack"));
+        }
+        // Before 4 all 16 levels were saved, whatever was on them
+        let levels = if version < 4 { 16 } else { sp as usize };
+        let mut stack = Vec::with_capacity(levels);
+        for _ in 0..levels {
+            stack.push(reader.u16()?);
+        }
+        stack.truncate(sp as usize);
+        let registers = reader.array()?;
+        let rpl = reader.array()?;
+        let delay = reader.u8()?;
+        let sound = reader.u8()?;
+        let keys = reader.array::<16>()?.map(|held| held != 0);
+        let rng_seed = reader.u64()?;
+        let mem_len = reader.u32()? as usize;
+        if !(0x1000..=0x100_0000).contains(&mem_len) {
+            return Err(format!("{mem_len} bytes of memory is outside what a machine can have"));
+        }
+        let mem = reader.take(mem_len)?.to_vec();
+        let framebuffer = read_framebuffer(reader, version)?;
+        let front = read_framebuffer(reader, version)?;
+        let chip8x = match reader.u8()? {
+            0 => None,
+            _ => Some(Chip8X::read(reader)?),
+        };
+        let megachip = match reader.u8()? {
+            0 => None,
+            _ => Some(MegaChip::read(reader)?),
+        };
+        let (audio_pattern, pitch) = match version {
+            1 => (None, DEFAULT_PITCH),
+            _ => {
+                let audio_pattern = match reader.u8()? {
+                    0 => None,
+                    _ => Some(reader.array()?),
+                };
+                (audio_pattern, reader.u8()?)
+            },
+        };
+        if !reader.bytes.is_empty() {
+            return Err("the save state has extra bytes at the end".to_string());
+        }
+
+        return Ok(Self {
+            opcode, ar, pc, stack, registers, rpl, mem, delay, sound, framebuffer, front, keys, platform, chip8x,
+            megachip, audio_pattern, pitch, rng_seed,
+        });
+    }
+}
+
+/// Packs bytes as run lengths, see the top of this file
+fn pack(bytes: &[u8], out: &mut Vec<u8>) {
+    let mut at = 0;
+    // Where the bytes that aren't in a run started
+    let mut literal = 0;
+    while at < bytes.len() {
+        let run = bytes[at..].iter().take(130).take_while(|&&byte| byte == bytes[at]).count();
+        if run < 3 && at - literal < 128 {
+            at += 1;
+            continue;
+        }
+        for chunk in bytes[literal..at].chunks(128) {
+            out.push(chunk.len() as u8 - 1);
+            out.extend_from_slice(chunk);
+        }
+        if run >= 3 {
+            out.push(run as u8 + 125);
+            out.push(bytes[at]);
+            at += run;
+        }
+        literal = at;
+    }
+    for chunk in bytes[literal..].chunks(128) {
+        out.push(chunk.len() as u8 - 1);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Unpacks run lengths, stopping with an error rather than going past len bytes
+fn unpack(packed: &[u8], len: usize) -> Result<Vec<u8>, String> {
+    let corrupt = || "the save state's packing is corrupt".to_string();
+    let mut out = Vec::with_capacity(len);
+    let mut at = 0;
+    while at < packed.len() {
+        let control = packed[at] as usize;
+        if control < 128 {
+            out.extend_from_slice(packed.get(at + 1..at + 2 + control).ok_or_else(corrupt)?);
+            at += 2 + control;
+        } else {
+            let byte = *packed.get(at + 1).ok_or_else(corrupt)?;
+            out.resize(out.len() + control - 125, byte);
+            at += 2;
+        }
+        if out.len() > len {
+            return Err(corrupt());
+        }
+    }
+    return Ok(out);
+}
+
+fn write_framebuffer(out: &mut Vec<u8>, framebuffer: &Framebuffer) {
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    out.extend_from_slice(&(width as u32).to_le_bytes());
+    out.extend_from_slice(&(height as u32).to_le_bytes());
+    out.push(!framebuffer.is_colour() as u8);
+    out.extend_from_slice(framebuffer.bytes());
+    out.push(framebuffer.colour_zones().is_some() as u8);
+    if let Some(zones) = framebuffer.colour_zones() {
+        out.push(zones.background());
+        for zy in 0..height.div_ceil(ZONE_HEIGHT) {
+            for zx in 0..width.div_ceil(ZONE_WIDTH) {
+                out.push(zones.zone_colour(zx * ZONE_WIDTH, zy * ZONE_HEIGHT));
+            }
+        }
+    }
+}
+
+fn read_framebuffer(reader: &mut Reader, version: u8) -> Result<Framebuffer, String> {
+    let width = reader.u32()? as usize;
+    let height = reader.u32()? as usize;
+    // Mega-Chip's 256x192 is the biggest any platform draws
+    if width > 256 || height > 192 {
+        return Err(format!("a {width}x{height} screen is bigger than any platform's"));
+    }
+    // Before 5 the pixels were always a byte each
+    let one_bit = version >= 5 && reader.u8()? != 0;
+    let pixels = match one_bit {
+        true => reader.take((width * height).div_ceil(8))?,
+        false => reader.take(width * height)?,
+    };
+    let mut framebuffer = match reader.u8()? {
+        0 => Framebuffer::new(width, height),
+        _ => {
+            let mut framebuffer = Framebuffer::with_colour_zones(width, height);
+            let background = reader.u8()?;
+            let zones = framebuffer.colour_zones_mut().expect("it was made with colour zones");
+            while zones.background() != background % 4 {
+                zones.step_background();
+            }
+            for zy in 0..height.div_ceil(ZONE_HEIGHT) {
+                for zx in 0..width.div_ceil(ZONE_WIDTH) {
+                    zones.set_zone_colour(zx, zy, reader.u8()?);
+                }
+            }
+            framebuffer
+        },
+    };
+    match one_bit {
+        true => framebuffer.set_bits(pixels),
+        false => framebuffer.set_bytes(pixels),
+    }
+    return Ok(framebuffer);
+}
+
+/// Reads a save state from the front, failing when it runs out
+pub(super) struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(super) fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.bytes.len() {
+            return Err("the save state ends too soon".to_string());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        return Ok(taken);
+    }
+
+    pub(super) fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        return Ok(self.take(N)?.try_into().expect("took exactly N bytes"));
+    }
+
+    pub(super) fn u8(&mut self) -> Result<u8, String> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    pub(super) fn u16(&mut self) -> Result<u16, String> {
+        return Ok(u16::from_le_bytes(self.array()?));
+    }
+
+    pub(super) fn u32(&mut self) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.array()?));
+    }
+
+    pub(super) fn u64(&mut self) -> Result<u64, String> {
+        return Ok(u64::from_le_bytes(self.array()?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A CHIP-8 save state laid out as version wrote it, by hand rather than by to_bytes: I
+    /// 2F0, PC 206, V0-VF 1 to 16, one call from 202 on the stack, AB at 300 and the top
+    /// left pixel on
+    fn old_save_state(version: u8) -> Vec<u8> {
+        let mut state = Vec::new();
+        state.extend_from_slice(&0xD015_u16.to_le_bytes());
+        state.extend_from_slice(&0x2F0_u32.to_le_bytes());
+        state.extend_from_slice(&0x206_u16.to_le_bytes());
+        state.push(1);
+        // Before 4 all 16 levels, the ones past SP left as they were
+        let levels: &[u16] = if version < 4 { &[0x202, 0x204, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] } else { &[0x202] };
+        for addr in levels {
+            state.extend_from_slice(&addr.to_le_bytes());
+        }
+        state.extend(1..=16);
+        state.extend([0; 16]);
+        state.extend([5, 7]);
+        state.extend([0; 16]);
+        state.extend_from_slice(&42_u64.to_le_bytes());
+        let mut mem = vec![0; 0x1000];
+        mem[0x300] = 0xAB;
+        state.extend_from_slice(&0x1000_u32.to_le_bytes());
+        state.extend_from_slice(&mem);
+        // The screen and the front buffer, a byte a pixel with no colour zones
+        for _ in 0..2 {
+            state.extend_from_slice(&64_u32.to_le_bytes());
+            state.extend_from_slice(&32_u32.to_le_bytes());
+            state.push(1);
+            state.extend([0; 64 * 32 - 1]);
+            state.push(0);
+        }
+        // No CHIP-8X or Mega-Chip, and from 2 no XO-CHIP audio at the default pitch
+        state.extend([0, 0]);
+        if version >= 2 {
+            state.extend([0, DEFAULT_PITCH]);
+        }
+
+        let mut bytes = Vec::new();
+        if version < 3 {
+            bytes.extend_from_slice(OLD_MAGIC);
+            bytes.extend([version, 0]);
+            bytes.extend_from_slice(&state);
+            return bytes;
+        }
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend([version, 0, RUN_LENGTHS]);
+        bytes.extend_from_slice(&(state.len() as u32).to_le_bytes());
+        pack(&state, &mut bytes);
+        return bytes;
+    }
+
+    #[test]
+    fn old_save_states_still_load() {
+        for version in 1..=4 {
+            let snapshot = Snapshot::from_bytes(&old_save_state(version)).unwrap_or_else(|e| panic!("version {version}: {e}"));
+            let mut chip = Chip8::with_platform(Platform::Chip8, false);
+            chip.restore(&snapshot);
+            assert_eq!((chip.pc(), chip.ar(), chip.opcode()), (0x206, 0x2F0, 0xD015), "version {version}");
+            assert_eq!(chip.registers(), &core::array::from_fn(|x| x as u8 + 1), "version {version}");
+            assert_eq!((chip.delay(), chip.sound()), (5, 7), "version {version}");
+            assert_eq!(chip.call_stack(), &[0x202], "version {version}");
+            assert_eq!((chip.mem[0x300], chip.mem.len()), (0xAB, 0x1000), "version {version}");
+            assert_eq!((chip.framebuffer().get(0, 0), chip.framebuffer().lit()), (1, 1), "version {version}");
+            assert_eq!(snapshot.audio_pattern, None, "version {version}");
+        }
+
+        // Before 4 SP couldn't be past the 16 levels
+        let mut bytes = old_save_state(1);
+        bytes[6 + 8] = 17;
+        assert!(Snapshot::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn restoring_a_deeper_stack_follows_the_stack_policy() {
+        let mut deep = Chip8::with_platform(Platform::Chip8, false);
+        deep.set_stack_depth(32);
+        deep.load_rom_bytes(&[0x22, 0x00]);
+        for _ in 0..20 {
+            deep.execute().expect("the call runs");
+        }
+        let snapshot = deep.snapshot();
+
+        // Stopping on a full stack, the stack deepens to hold the calls
+        let mut chip = Chip8::with_platform(Platform::Chip8, false);
+        chip.restore(&snapshot);
+        assert_eq!((chip.call_stack().len(), chip.stack_depth()), (20, 20));
+
+        // Wrapping, the oldest calls are forgotten
+        let mut chip = Chip8::with_platform(Platform::Chip8, false);
+        chip.set_stack_policy(StackPolicy::Wrap);
+        chip.restore(&snapshot);
+        assert_eq!((chip.call_stack().len(), chip.stack_depth()), (16, 16));
+        assert_eq!(chip.call_stack(), &snapshot.call_stack()[4..]);
+    }
+}
@@ -0,0 +1,77 @@
+use super::Chip8;
+use crate::error::Chip8Error;
+use crate::platform::Platform;
+
+// SUPER-CHIP (Erik Bryntse, 1991, for the HP-48) doubles the screen to 128x64 and adds
+// scrolling, 16x16 sprites, a big font (FX30), the RPL flags (FX75/FX85) and exiting (00FD).
+// XO-CHIP and Mega-Chip build on it, so it's all there on those too. The display ones are:
+//
+//   00CN        scroll the screen down N pixels
+//   00DN        scroll the screen up N pixels, XO-CHIP only
+//   00FB        scroll the screen right 4 pixels
+//   00FC        scroll the screen left 4 pixels
+//   00FE        switch to the 64x32 screen
+//   00FF        switch to the 128x64 screen
+//   DXY0        draw a 16x16 sprite, 2 bytes a row (see Chip8::draw_sprite)
+//
+// Switching resolution clears the screen, as Octo does, and 16x16 sprites are drawn in
+// either resolution rather than SUPER-CHIP 1.1's 8x16 in the small one. Scrolling goes by
+// pixels of whichever resolution the screen's in. XO-CHIP adds saving and loading a range
+// of registers:
+//
+//   5XY2        store Vx to Vy in memory from I, backwards if x > y, leaving I alone
+//   5XY3        load Vx to Vy from memory from I, the same way
+//
+// Its bit planes aren't emulated, everything's drawn to the one plane and selecting them with
+// FN01 does nothing.
+
+
+/// The SUPER-CHIP screen, twice the size each way of CHIP-8's
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+impl Chip8 {
+    /// Whether the SUPER-CHIP instructions run, which is on SUPER-CHIP and the platforms built
+    /// on it, unless Mega-Chip mode's taken over the screen
+    pub(super) fn superchip(&self) -> bool {
+        return self.platform.extends(Platform::SuperChip) && !self.megachip.as_ref().is_some_and(|m| m.enabled);
+    }
+
+    /// Runs the opcode if it's one of the SUPER-CHIP or XO-CHIP instructions above, returning
+    /// whether it was
+    pub(super) fn execute_superchip(&mut self) -> Result<bool, Chip8Error> {
+        if !self.superchip() {
+            return Ok(false);
+        }
+        let n = (self.opcode & 0xF) as isize;
+        match self.opcode {
+            0x00C0..=0x00CF => self.framebuffer.scroll(0, n),
+            0x00D0..=0x00DF if self.platform == Platform::XoChip => self.framebuffer.scroll(0, -n),
+            0x00FB => self.framebuffer.scroll(4, 0),
+            0x00FC => self.framebuffer.scroll(-4, 0),
+            0x00FE => self.framebuffer.resize(64, 32),
+            0x00FF => self.framebuffer.resize(HIRES_WIDTH, HIRES_HEIGHT),
+            opcode if opcode & 0xF00E == 0x5002 && self.platform == Platform::XoChip => {
+                let x = ((opcode >> 8) & 0xF) as usize;
+                let y = ((opcode >> 4) & 0xF) as usize;
+                let count = x.abs_diff(y) + 1;
+                self.check_range(self.ar as usize, count)?;
+                if opcode & 0xF == 0x3 {
+                    if let Some(coverage) = &mut self.coverage {
+                        coverage.mark_read(self.ar as usize, count);
+                    }
+                }
+                for i in 0..count {
+                    let register = if x <= y { x + i } else { x - i };
+                    match opcode & 0xF {
+                        0x2 => self.set_mem_at(self.ar as usize + i, self.registers[register])?,
+                        _ => self.registers[register] = self.load_mem_at(self.ar as usize + i),
+                    }
+                }
+            },
+            opcode if opcode & 0xF0FF == 0xF001 && self.platform == Platform::XoChip => {},
+            _ => return Ok(false),
+        }
+        return Ok(true);
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::frontend::input::InputProfile;
+use crate::persist;
+
+// The config holds settings for every rom, where the per rom profiles (profile.rs) hold
+// settings for one. It lives in the config directory as <config dir>/config, and running
+// chip8 with nothing after it the first time walks through writing it (`chip8 setup` does it
+// again any time). One setting a line:
+//
+//   # comments and blank lines are ignored
+//   roms = /home/me/roms   where roms are looked for when they're not found by the path
+//                          given, and what chip8 on its own offers to play
+//   input = split          the input profile for roms whose profile doesn't say (see input.rs)
+//   frontend = gui         what chip8 on its own plays roms in, one of FRONTENDS
+//
+// --input and a rom's own profile still come first.
+
+/// The frontends chip8 on its own can play roms in, by the command that runs them, and what
+/// each is. Only the ones built in are offered
+pub const FRONTENDS: [(&str, &str); 4] = [
+    ("gui", "a window with the debugger's panels around the game"),
+    ("window", "a plain window with nothing but the game"),
+    ("play", "a window drawn on the GPU, with filters for scaling it up"),
+    ("term", "the game drawn in the terminal, for kitty, iTerm2 or sixel terminals"),
+];
+
+
+/// The settings for every rom
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// The folder roms are kept in
+    pub roms: Option<PathBuf>,
+    /// The input profile, if not the standard one
+    pub input: Option<&'static InputProfile>,
+    /// The frontend chip8 on its own plays roms in, by its command
+    pub frontend: Option<&'static str>,
+}
+
+impl Config {
+    /// Parses the contents of a config
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected '<setting> = <value>'", i + 1))?;
+            match key.trim() {
+                "roms" => config.roms = Some(PathBuf::from(value.trim())),
+                "input" => {
+                    let input = InputProfile::from_name(value.trim());
+                    config.input = Some(input.ok_or(format!("line {}: unknown input profile '{}'", i + 1, value.trim()))?);
+                },
+                "frontend" => {
+                    let frontend = FRONTENDS.iter().map(|(name, _)| *name).find(|name| *name == value.trim());
+                    config.frontend = Some(frontend.ok_or(format!("line {}: unknown frontend '{}'", i + 1, value.trim()))?);
+                },
+                key => return Err(format!("line {}: unknown setting '{key}'", i + 1)),
+            }
+        }
+
+        return Ok(config);
+    }
+
+    /// Where the config is kept
+    pub fn path() -> PathBuf {
+        return persist::config_dir().join("config");
+    }
+
+    /// Loads the config, or None if there isn't one yet
+    pub fn load() -> Result<Option<Self>, String> {
+        return match std::fs::read_to_string(Self::path()) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    /// Writes the config, creating the config directory if needed
+    pub fn save(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(persist::config_dir())?;
+        return std::fs::write(Self::path(), self.to_string());
+    }
+
+    /// The roms in the roms folder, by path and sorted by name. Anything that isn't a file or
+    /// is hidden is left out
+    pub fn list_roms(&self) -> Result<Vec<PathBuf>, String> {
+        let Some(dir) = &self.roms else {
+            return Ok(Vec::new());
+        };
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+        let mut roms: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && !path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')))
+            .collect();
+        roms.sort();
+        return Ok(roms);
+    }
+}
+
+/// The frontends in FRONTENDS built into this chip8
+pub fn available_frontends() -> Vec<(&'static str, &'static str)> {
+    let built = [cfg!(feature = "gui"), cfg!(feature = "minifb"), cfg!(feature = "pixels"), cfg!(feature = "terminal")];
+    return FRONTENDS.into_iter().zip(built).filter(|(_, built)| *built).map(|(frontend, _)| frontend).collect();
+}
+
+impl fmt::Display for Config {
+    /// The config as it's written to its file
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "# chip8's settings for every rom, see `chip8 setup`")?;
+        if let Some(roms) = &self.roms {
+            writeln!(f, "roms = {}", roms.display())?;
+        }
+        if let Some(input) = self.input {
+            writeln!(f, "input = {}", input.name)?;
+        }
+        if let Some(frontend) = self.frontend {
+            writeln!(f, "frontend = {frontend}")?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,123 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::ops::Range;
+
+/// Counts how many times each byte of memory has been executed as an instruction
+/// and read as data (sprites drawn by DXYN, registers loaded by FX65)
+pub struct Coverage {
+    executed: Vec<u32>,
+    read: Vec<u32>,
+}
+
+impl Coverage {
+    pub fn new(size: usize) -> Self {
+        return Self {
+            executed: vec![0; size],
+            read: vec![0; size],
+        };
+    }
+
+    /// Marks both bytes of the instruction at addr as executed
+    pub fn mark_executed(&mut self, addr: usize) {
+        for a in addr..(addr + 2).min(self.executed.len()) {
+            self.executed[a] = self.executed[a].saturating_add(1);
+        }
+    }
+
+    /// Marks len bytes starting at addr as read as data
+    pub fn mark_read(&mut self, addr: usize, len: usize) {
+        for a in addr..(addr + len).min(self.read.len()) {
+            self.read[a] = self.read[a].saturating_add(1);
+        }
+    }
+
+    pub fn executed(&self, addr: usize) -> u32 {
+        return self.executed.get(addr).copied().unwrap_or(0);
+    }
+
+    pub fn read(&self, addr: usize) -> u32 {
+        return self.read.get(addr).copied().unwrap_or(0);
+    }
+
+    /// A text report of the range, a summary followed by a map of 64 bytes a line where
+    /// each byte is X (executed), r (read as data), B (both) or . (never touched)
+    pub fn report(&self, range: Range<usize>) -> String {
+        let range = range.start.min(self.executed.len())..range.end.min(self.executed.len());
+        let total = range.len().max(1);
+        let executed = range.clone().filter(|&a| self.executed[a] > 0).count();
+        let read = range.clone().filter(|&a| self.read[a] > 0).count();
+        let untouched = range.clone().filter(|&a| self.executed[a] == 0 && self.read[a] == 0).count();
+
+        let mut report = format!(
+            "Coverage of {:04X}-{:04X} ({} bytes)\n  executed: {executed} ({:.1}%)\n  read as data: {read} ({:.1}%)\n  untouched: {untouched} ({:.1}%)\n\n",
+            range.start,
+            range.end.saturating_sub(1),
+            range.len(),
+            executed as f64 * 100.0 / total as f64,
+            read as f64 * 100.0 / total as f64,
+            untouched as f64 * 100.0 / total as f64,
+        );
+
+        let mut addr = range.start;
+        while addr < range.end {
+            report.push_str(&format!("{addr:04X}: "));
+            for a in addr..(addr + 64).min(range.end) {
+                report.push(match (self.executed[a] > 0, self.read[a] > 0) {
+                    (true, true) => 'B',
+                    (true, false) => 'X',
+                    (false, true) => 'r',
+                    (false, false) => '.',
+                });
+            }
+            report.push('\n');
+            addr += 64;
+        }
+
+        return report;
+    }
+
+    /// An HTML heat map of the range, 16 bytes a row. Executed bytes are shaded red and
+    /// data reads blue, darker the more often they were hit, with the counts on hover
+    #[cfg(feature = "std")]
+    pub fn html(&self, range: Range<usize>, mem: &[u8]) -> String {
+        let range = range.start.min(self.executed.len())..range.end.min(self.executed.len());
+        let max_executed = range.clone().map(|a| self.executed[a]).max().unwrap_or(0).max(1) as f64;
+        let max_read = range.clone().map(|a| self.read[a]).max().unwrap_or(0).max(1) as f64;
+
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>CHIP-8 coverage</title>\n\
+             <style>body { font-family: monospace; } td { padding: 2px 4px; text-align: center; }</style>\n\
+             </head>\n<body>\n<table>\n",
+        );
+
+        let mut addr = range.start;
+        while addr < range.end {
+            html.push_str(&format!("<tr><th>{addr:04X}</th>"));
+            for a in addr..(addr + 16).min(range.end) {
+                // Scale on a log curve so a handful of hits still shows up next to hot loops
+                let heat = |count: u32, max: f64| (count as f64).ln_1p() / max.ln_1p();
+                let red = heat(self.executed[a], max_executed);
+                let blue = heat(self.read[a], max_read);
+                let colour = format!(
+                    "rgb({}, {}, {})",
+                    255 - (blue * 200.0) as u8,
+                    255 - (red.max(blue) * 200.0) as u8,
+                    255 - (red * 200.0) as u8,
+                );
+                html.push_str(&format!(
+                    "<td style=\"background: {colour}\" title=\"{a:04X}: executed {}, read {}\">{:02X}</td>",
+                    self.executed[a],
+                    self.read[a],
+                    mem.get(a).copied().unwrap_or(0),
+                ));
+            }
+            html.push_str("</tr>\n");
+            addr += 16;
+        }
+
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        return html;
+    }
+}
@@ -0,0 +1,927 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::budget::Budget;
+use crate::cheat::{Cheat, CheatEngine, CheatKind, SearchFilter};
+use crate::chip::{Chip8, CodeWrite, Snapshot};
+use crate::diff;
+use crate::error::Chip8Error;
+use crate::expr::{Expr, Watch};
+use crate::isa;
+use crate::persist;
+use crate::platform::Platform;
+use crate::rewind::Rewind;
+use crate::symbols::{self, SymbolTable};
+use crate::tracedb::{Query, RegisterChange, TraceDb, TraceLog, TraceRecord};
+
+const HELP: &str = "\
+Commands:
+  s, step [n]              Execute n instructions (default 1)
+  sb, step-back [n]        Undo the last n instructions (default 1)
+  c, continue [n]          Run until a breakpoint, for at most n instructions (default 1000000)
+  rc, reverse-continue     Go back to the last time a breakpoint was hit
+  frames [n]               Graph how the last n frames (default 16) spent their instructions:
+                           drawing, the rom's logic, or waiting for the next frame
+  history                  Show how far back the history goes and the memory it takes up
+  trace on [dir]           Record every instruction from now on to a trace on disk, kept in dir
+                           or the rom's folder under traces in the data directory, starting
+                           it again
+  trace off                Stop recording
+  trace                    Show what the trace holds
+  query [frames] <question>
+                           Ask the trace about what's run, e.g. query last write 3A0 or
+                           query frames set VF by DXYN. query on its own lists the questions
+  b, break <addr> [if <e>] Stop before the instruction at addr runs, only when e is true if given
+  b, break on <op> [if <e>]
+                           Stop before an instruction matching op runs: an opcode pattern
+                           with X, Y, N and K as wildcards (DXYN, FX0A, 00E0), a mnemonic
+                           (DRW, CALL, SKP) or unknown for opcodes the platform can't decode
+  b, break if <e>          Stop before any instruction runs when e is true
+  breaks                   Show the breakpoints
+  delete <n>               Remove breakpoint n
+  p, print <e>             Evaluate an expression, e.g. p [I] + V0
+  w, watch <e>             Show an expression after every step and continue, marking it
+                           with * when its value changes
+  watches                  Show the watched expressions
+  unwatch <n>              Stop watching expression n
+  r, regs                  Show the registers
+  state                    Show the whole machine: registers, stack, keys and the screen
+  screen [on] [off]        Show the screen as text, with # and . for pixels on and off
+                           unless other characters are given
+  bt, backtrace            Show the call stack
+  di, disasm [addr] [n]    Disassemble n instructions (default 8) from addr (default PC)
+  explain [opcode]         Explain the instruction at PC, or any opcode: its operands, the
+                           quirks that change what it does and how long it takes
+  symbols <file>           Load a symbol file to name addresses in backtraces
+  save <file>              Save the machine's state to a file, one without a folder in its
+                           name to the states folder in the data directory
+  load <file>              Put the machine back as it was when a state was saved, or as a
+                           state in JSON from another emulator says
+  diff <file>              Show what's changed since a state was saved
+  diff back <n>            Show what the last n instructions changed
+  peek <addr> [len]        Hex dump len bytes (default 16) starting at addr
+  poke <addr> <byte>...    Write bytes into memory starting at addr
+  asm <addr> <instruction> Assemble an instruction written as disasm writes them over the one
+                           at addr, e.g. asm 2A4 LD V3, #10
+  dump [start] [end]       Hex dump a range of memory (default the whole program space)
+  search start             Start a new memory search with every address as a candidate
+  search <filter>          Narrow the search: eq <value>, changed, unchanged, inc, dec
+  search list              Show the remaining candidates and their values
+  mark <addr> [label]      Bookmark an address
+  marks                    Show the bookmarks and their values
+  freeze <addr> [value]    Keep an address at a value (default its current value)
+  unfreeze <addr>          Stop freezing an address
+  freezes                  Show the frozen addresses
+  cheats                   Show the rom's cheats, from its cheat file, and which are on
+  cheat <n> [on|off]       Turn cheat n on (the default) or off. Written once cheats are
+                           written again each time they're turned on
+  h, help                  Show this message
+  q, quit                  Exit the debugger
+Numbers are read as hex, with or without a leading 0x
+
+Going backwards runs forward from a snapshot taken every 1000 instructions, as far back as
+the last 64MB of snapshots go (about 16 million instructions with 4KB of memory). Timers
+tick every frame's worth of instructions (--speed), as they would at full speed
+
+Expressions use V0-VF or V[x], I, PC, SP or stack_depth, DT, ST, opcode (the next
+instruction) and [addr] or mem[addr] for memory, with Rust's operators and precedence,
+e.g. V3 == 20 && DT == 0 or opcode & 0F000 == 0D000
+
+Instructions that write over code that's already run are reported after step and continue,
+since what's disassembled there from before won't be what runs next time";
+
+/// How many instructions continue runs for if it isn't told, about half an hour of game time
+const CONTINUE_LIMIT: usize = 1_000_000;
+
+/// How often the rewind history takes a snapshot, in instructions, and how much memory its
+/// snapshots can take up
+const REWIND_INTERVAL: u64 = 1000;
+const REWIND_MEMORY: usize = 64 * 1024 * 1024;
+
+/// How many frames the frames command graphs if it isn't told
+const FRAMES: usize = 16;
+
+/// How many writes over code are listed after a step or continue, the rest just counted
+const CODE_WRITES_SHOWN: usize = 8;
+
+/// How many instructions query lists, the rest just counted
+const ANSWERS_SHOWN: usize = 64;
+
+
+/// Stops continue before an instruction runs, at addr or anywhere if there isn't one, when
+/// the instruction matches the opcode and the condition is true, for those that are set
+struct Breakpoint {
+    addr: Option<u32>,
+    opcode: Option<OpcodeMatch>,
+    condition: Option<Expr>,
+}
+
+/// Which instructions a breakpoint stops at, wherever they are, or a trace query asks about
+pub enum OpcodeMatch {
+    /// Opcodes where opcode & mask == pattern, written like DXYN
+    Pattern { mask: u16, pattern: u16, text: String },
+    /// Instructions with this mnemonic, e.g. DRW
+    Mnemonic(String),
+    /// Opcodes that don't decode on the platform
+    Unknown,
+}
+
+impl OpcodeMatch {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let upper = text.to_ascii_uppercase();
+        if upper == "UNKNOWN" {
+            return Ok(OpcodeMatch::Unknown);
+        }
+
+        // Four hex digits or wildcards is a pattern, anything else is taken as a mnemonic
+        let is_pattern = upper.len() == 4 && upper.chars().all(|c| c.is_ascii_hexdigit() || "XYNK".contains(c));
+        if !is_pattern {
+            let known = isa::OPCODES.iter().any(|info| info.mnemonic.split(' ').next() == Some(upper.as_str()));
+            if !known {
+                return Err(format!("'{text}' isn't an opcode pattern like DXYN, a mnemonic like DRW or unknown"));
+            }
+            return Ok(OpcodeMatch::Mnemonic(upper));
+        }
+
+        let (mut mask, mut pattern) = (0, 0);
+        for c in upper.chars() {
+            let digit = c.to_digit(16).filter(|_| !"XYNK".contains(c));
+            mask = mask << 4 | if digit.is_some() { 0xF } else { 0 };
+            pattern = pattern << 4 | digit.unwrap_or(0) as u16;
+        }
+        return Ok(OpcodeMatch::Pattern { mask, pattern, text: upper });
+    }
+
+    pub fn matches(&self, opcode: u16, platform: Platform) -> bool {
+        return match self {
+            OpcodeMatch::Pattern { mask, pattern, .. } => opcode & mask == *pattern,
+            OpcodeMatch::Mnemonic(mnemonic) => isa::lookup(opcode, platform)
+                .is_some_and(|info| info.mnemonic.split(' ').next() == Some(mnemonic.as_str())),
+            OpcodeMatch::Unknown => isa::lookup(opcode, platform).is_none(),
+        };
+    }
+}
+
+impl std::fmt::Display for OpcodeMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            OpcodeMatch::Pattern { text, .. } => f.write_str(text),
+            OpcodeMatch::Mnemonic(mnemonic) => f.write_str(mnemonic),
+            OpcodeMatch::Unknown => f.write_str("unknown opcodes"),
+        };
+    }
+}
+
+/// An interactive command line debugger wrapped around a Chip8
+pub struct Debugger {
+    chip: Chip8,
+    cheats: CheatEngine,
+    symbols: Option<SymbolTable>,
+    breakpoints: Vec<Breakpoint>,
+    watches: Vec<Watch>,
+    /// Instructions run since the debugger started, which is what the history goes by
+    executed: u64,
+    rewind: Rewind,
+    budget: Budget,
+    /// Writes over code since the last step or continue reported them
+    code_writes: Arc<Mutex<Vec<CodeWrite>>>,
+    /// The trace being recorded since trace on, where it's kept if trace on isn't told, and
+    /// the bytes the instruction running has written, for it
+    trace: Option<TraceLog>,
+    trace_dir: Option<PathBuf>,
+    writes: Arc<Mutex<Vec<CodeWrite>>>,
+}
+
+impl Debugger {
+    pub fn new(mut chip: Chip8) -> Self {
+        let code_writes = Arc::new(Mutex::new(Vec::new()));
+        let writes = code_writes.clone();
+        chip.set_code_write_hook(move |write| writes.lock().unwrap().push(write));
+        return Self {
+            rewind: Rewind::new(REWIND_MEMORY),
+            chip,
+            cheats: CheatEngine::new(),
+            symbols: None,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            executed: 0,
+            budget: Budget::new(),
+            code_writes,
+            trace: None,
+            trace_dir: None,
+            writes: Arc::new(Mutex::new(Vec::new())),
+        };
+    }
+
+    pub fn chip(&self) -> &Chip8 {
+        return &self.chip;
+    }
+
+    /// Gives the cheat and freeze commands the cheats from a cheat file, all off
+    pub fn set_cheats(&mut self, cheats: Vec<Cheat>) {
+        self.cheats.set_cheats(cheats);
+    }
+
+    /// Names addresses in backtraces using the symbol table
+    pub fn set_symbols(&mut self, symbols: SymbolTable) {
+        self.symbols = Some(symbols);
+    }
+
+    /// Keeps what trace on records in dir if it isn't given a folder, e.g. trace_dir(rom)
+    pub fn set_trace_dir(&mut self, dir: PathBuf) {
+        self.trace_dir = Some(dir);
+    }
+
+    /// Reads commands from stdin until the user quits or the input ends
+    pub fn run(&mut self) {
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+
+        loop {
+            print!("(chip8) ");
+            let _ = std::io::stdout().flush();
+
+            let Some(Ok(line)) = lines.next() else {
+                break;
+            };
+
+            match self.command(&line) {
+                Ok(true) => continue,
+                Ok(false) => break,
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+
+    /// Runs a single debugger command, returning whether the debugger should keep going
+    pub fn command(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(n) => n.parse::<usize>().map_err(|_| format!("invalid count '{n}'"))?,
+                    None => 1,
+                };
+                for _ in 0..count {
+                    if let Err(e) = self.execute() {
+                        println!("{e}");
+                        print!("{}", self.backtrace());
+                        break;
+                    }
+                }
+                print!("{}", self.code_writes());
+                println!("{}", self.registers());
+                self.show_watches();
+            },
+            "sb" | "step-back" => {
+                let count = match args.first() {
+                    Some(n) => n.parse::<u64>().map_err(|_| format!("invalid count '{n}'"))?,
+                    None => 1,
+                };
+                if count > self.executed {
+                    return Err(format!("Only {} instructions have run", self.executed));
+                }
+                self.rewind_to(self.executed - count)?;
+                println!("{}", self.registers());
+                self.show_watches();
+            },
+            "rc" | "reverse-continue" => {
+                self.reverse_continue();
+                // Going back over them doesn't undo anything worth reporting
+                self.code_writes.lock().unwrap().clear();
+                println!("{}", self.registers());
+                self.show_watches();
+            },
+            "c" | "continue" => {
+                let limit = match args.first() {
+                    Some(n) => n.parse::<usize>().map_err(|_| format!("invalid count '{n}'"))?,
+                    None => CONTINUE_LIMIT,
+                };
+                self.continue_for(limit);
+                print!("{}", self.code_writes());
+                println!("{}", self.registers());
+                self.show_watches();
+            },
+            "frames" => {
+                let count = match args.first() {
+                    Some(n) => n.parse::<usize>().map_err(|_| format!("invalid count '{n}'"))?,
+                    None => FRAMES,
+                };
+                print!("{}", self.budget.graph(count, self.chip.cycles_per_frame()));
+            },
+            "history" => match (self.rewind.oldest(), self.rewind.newest()) {
+                (Some(oldest), Some(newest)) => println!(
+                    "{} snapshots from instruction {oldest} to {newest}, taking up {}KB of {}KB",
+                    self.rewind.len(),
+                    self.rewind.memory_used() / 1024,
+                    self.rewind.memory_limit() / 1024
+                ),
+                _ => println!("There's no history yet"),
+            },
+            "trace" => match &args[..] {
+                ["on", dir @ ..] if dir.len() <= 1 => {
+                    let dir = match dir.first() {
+                        Some(dir) => PathBuf::from(dir),
+                        None => self.trace_dir.clone().ok_or("Usage: trace on <dir>, there's no rom to keep it with")?,
+                    };
+                    self.trace = None;
+                    let trace = TraceLog::create(&dir).map_err(|e| format!("An error occured when starting the trace: {e}"))?;
+                    self.trace = Some(trace);
+                    let writes = self.writes.clone();
+                    self.chip.set_write_hook(move |write| writes.lock().unwrap().push(write));
+                    println!("Tracing to {}", dir.display());
+                },
+                ["off"] => {
+                    self.stop_tracing();
+                    println!("Tracing off");
+                },
+                [] => {
+                    let trace = self.open_trace()?;
+                    let state = if self.trace.is_some() { "on" } else { "off" };
+                    match trace.steps() {
+                        Some((first, last)) => println!(
+                            "Tracing {state}, {} instructions from #{first} to #{last}, taking up {}KB",
+                            trace.len(),
+                            trace.size() / 1024
+                        ),
+                        None => println!("Tracing {state}, nothing's been recorded"),
+                    }
+                },
+                _ => return Err("Usage: trace on [dir], trace off or trace".to_string()),
+            },
+            "query" => {
+                let (frames, question) = match &args[..] {
+                    ["frames", question @ ..] => (true, question),
+                    question => (false, question),
+                };
+                let query = Query::parse(question)?;
+                let answers = self
+                    .open_trace()?
+                    .query(&query, self.chip.platform())
+                    .map_err(|e| format!("An error occured when reading the trace: {e}"))?;
+                if answers.is_empty() {
+                    println!("Nothing in the trace");
+                } else if frames {
+                    println!("Frames {}", crate::tracedb::frame_ranges(&answers));
+                } else {
+                    for answer in answers.iter().take(ANSWERS_SHOWN) {
+                        println!("{answer}");
+                    }
+                    if answers.len() > ANSWERS_SHOWN {
+                        println!("...and {} more", answers.len() - ANSWERS_SHOWN);
+                    }
+                }
+            },
+            "b" | "break" => {
+                let usage = "Usage: break <addr> [if <expression>], break on <opcode> [if <expression>] or break if <expression>";
+                let (mut addr, mut opcode) = (None, None);
+                let rest = match &args[..] {
+                    ["on", pattern, rest @ ..] => {
+                        opcode = Some(OpcodeMatch::parse(pattern)?);
+                        rest
+                    },
+                    ["if", ..] => &args[..],
+                    [at, rest @ ..] => {
+                        addr = Some(parse_number(at)? as u32);
+                        rest
+                    },
+                    [] => return Err(usage.to_string()),
+                };
+                let condition = match rest {
+                    [] if addr.is_some() || opcode.is_some() => None,
+                    ["if", condition @ ..] if !condition.is_empty() => Some(Expr::parse(&condition.join(" "))?),
+                    _ => return Err(usage.to_string()),
+                };
+                self.breakpoints.push(Breakpoint { addr, opcode, condition });
+                println!("Breakpoint {}: {}", self.breakpoints.len() - 1, self.describe(self.breakpoints.len() - 1));
+            },
+            "breaks" => {
+                for n in 0..self.breakpoints.len() {
+                    println!("{n}: {}", self.describe(n));
+                }
+            },
+            "delete" => {
+                let n = args.first().ok_or("Usage: delete <n>")?;
+                let n = n.parse::<usize>().map_err(|_| format!("invalid breakpoint '{n}'"))?;
+                if n >= self.breakpoints.len() {
+                    return Err(format!("There's no breakpoint {n}"));
+                }
+                self.breakpoints.remove(n);
+            },
+            "p" | "print" => {
+                if args.is_empty() {
+                    return Err("Usage: print <expression>".to_string());
+                }
+                let value = Expr::parse(&args.join(" "))?.eval(&self.chip);
+                println!("{value:X} ({value})");
+            },
+            "w" | "watch" => {
+                if args.is_empty() {
+                    return Err("Usage: watch <expression>".to_string());
+                }
+                let mut watch = Watch::new(Expr::parse(&args.join(" "))?);
+                watch.update(&self.chip);
+                self.watches.push(watch);
+                println!("{}", self.describe_watch(self.watches.len() - 1, None));
+            },
+            "watches" => {
+                for n in 0..self.watches.len() {
+                    println!("{}", self.describe_watch(n, None));
+                }
+            },
+            "unwatch" => {
+                let n = args.first().ok_or("Usage: unwatch <n>")?;
+                let n = n.parse::<usize>().map_err(|_| format!("invalid watch '{n}'"))?;
+                if n >= self.watches.len() {
+                    return Err(format!("There's no watch {n}"));
+                }
+                self.watches.remove(n);
+            },
+            "r" | "regs" => println!("{}", self.registers()),
+            "state" => print!("{}", self.chip.state()),
+            "screen" => {
+                let on = args.first().and_then(|arg| arg.chars().next()).unwrap_or('#');
+                let off = args.get(1).and_then(|arg| arg.chars().next()).unwrap_or('.');
+                print!("{}", self.chip.framebuffer().to_ascii(on, off));
+            },
+            "di" | "disasm" => {
+                let start = match args.first() {
+                    Some(addr) => parse_number(addr)?,
+                    None => self.chip.pc() as usize,
+                };
+                let count = match args.get(1) {
+                    Some(n) => parse_number(n)?,
+                    None => 8,
+                };
+                for i in 0..count {
+                    let addr = (start + i * 2) as u32;
+                    let bytes = self.chip.read_mem(addr, 2);
+                    if bytes.len() < 2 {
+                        break;
+                    }
+                    let opcode = (bytes[0] as u16) << 8 | bytes[1] as u16;
+                    println!("{addr:04X}: {opcode:04X}  {}", isa::disassemble(opcode, self.chip.platform()));
+                }
+            },
+            "explain" => {
+                let opcode = match args.first() {
+                    Some(opcode) => u16::try_from(parse_number(opcode)?).map_err(|_| format!("invalid opcode '{opcode}'"))?,
+                    None => match *self.chip.read_mem(self.chip.pc() as u32, 2) {
+                        [high, low] => (high as u16) << 8 | low as u16,
+                        _ => return Err("PC is past the end of memory".to_string()),
+                    },
+                };
+                println!("{}", isa::explain(opcode, self.chip.platform()));
+            },
+            "bt" | "backtrace" => print!("{}", self.backtrace()),
+            "symbols" => {
+                let path = args.first().ok_or("Usage: symbols <file>")?;
+                self.symbols = Some(SymbolTable::load(path)?);
+            },
+            "save" => {
+                let path = persist::state_path(args.first().ok_or("Usage: save <file>")?);
+                let snapshot = self.snapshot_here();
+                path.parent()
+                    .map_or(Ok(()), std::fs::create_dir_all)
+                    .and_then(|()| std::fs::write(&path, snapshot.to_bytes()))
+                    .map_err(|e| format!("An error occured when saving the state: {e}"))?;
+                println!("Saved to {}", path.display());
+            },
+            "load" => {
+                let snapshot = read_state(args.first().ok_or("Usage: load <file>")?)?;
+                self.chip.restore(&snapshot);
+                self.changed_by_hand();
+                println!("{}", self.registers());
+            },
+            "diff" => {
+                let snapshot = self.snapshot_here();
+                let before = match &args[..] {
+                    ["back", n] => {
+                        let n = n.parse::<u64>().map_err(|_| format!("invalid count '{n}'"))?;
+                        if n > self.executed {
+                            return Err(format!("Only {} instructions have run", self.executed));
+                        }
+                        // Visiting the past and coming straight back, which the snapshot
+                        // just taken makes exact
+                        let now = self.executed;
+                        self.rewind_to(now - n)?;
+                        let before = self.chip.snapshot();
+                        self.chip.restore(&snapshot);
+                        self.executed = now;
+                        before
+                    },
+                    [path] => read_state(path)?,
+                    _ => return Err("Usage: diff <file> or diff back <n>".to_string()),
+                };
+                match diff::diff(&before, &snapshot).as_str() {
+                    "" => println!("Nothing's changed"),
+                    report => print!("{report}"),
+                }
+            },
+            "peek" => {
+                let addr = parse_number(args.first().ok_or("Usage: peek <addr> [len]")?)?;
+                let len = match args.get(1) {
+                    Some(len) => parse_number(len)?,
+                    None => 16,
+                };
+                print!("{}", self.chip.dump_memory(addr..addr.saturating_add(len)));
+            },
+            "poke" => {
+                if args.len() < 2 {
+                    return Err("Usage: poke <addr> <byte>...".to_string());
+                }
+                let addr = parse_number(args[0])?;
+                let bytes = args[1..]
+                    .iter()
+                    .map(|b| parse_number(b).map(|b| b as u8))
+                    .collect::<Result<Vec<u8>, String>>()?;
+                let written = self.chip.write_mem(addr as u32, &bytes);
+                self.changed_by_hand();
+                if written < bytes.len() {
+                    println!("Only {written} of {} bytes fit in memory", bytes.len());
+                }
+            },
+            "asm" => {
+                let (addr, words) = args.split_first().filter(|(_, words)| !words.is_empty()).ok_or("Usage: asm <addr> <instruction>")?;
+                let addr = parse_number(addr)? as u32;
+                let opcode = isa::assemble(&words.join(" "), self.chip.platform())?;
+                if self.chip.read_mem(addr, 2).len() < 2 {
+                    return Err(format!("{addr:04X} is past the end of memory"));
+                }
+                self.chip.write_mem(addr, &opcode.to_be_bytes());
+                self.changed_by_hand();
+                println!("{addr:04X}: {opcode:04X}  {}", isa::disassemble(opcode, self.chip.platform()));
+            },
+            "dump" => {
+                let start = match args.first() {
+                    Some(start) => parse_number(start)?,
+                    None => self.chip.platform().start_address() as usize,
+                };
+                let end = match args.get(1) {
+                    Some(end) => parse_number(end)?,
+                    None => 0x1000,
+                };
+                print!("{}", self.chip.dump_memory(start..end));
+            },
+            "search" => self.search(&args)?,
+            "mark" => {
+                let addr = parse_number(args.first().ok_or("Usage: mark <addr> [label]")?)?;
+                self.cheats.bookmark(addr as u32, &args[1..].join(" "));
+            },
+            "marks" => {
+                for (addr, label) in self.cheats.bookmarks() {
+                    println!("{addr:04X}: {:02X}  {label}", self.peek(*addr));
+                }
+            },
+            "freeze" => {
+                let addr = parse_number(args.first().ok_or("Usage: freeze <addr> [value]")?)? as u32;
+                let value = match args.get(1) {
+                    Some(value) => parse_number(value)? as u8,
+                    None => self.peek(addr),
+                };
+                self.cheats.freeze(addr, value);
+                self.cheats.apply(&mut self.chip);
+                self.changed_by_hand();
+            },
+            "unfreeze" => {
+                let addr = parse_number(args.first().ok_or("Usage: unfreeze <addr>")?)?;
+                self.cheats.unfreeze(addr as u32);
+                self.changed_by_hand();
+            },
+            "freezes" => {
+                for (addr, value) in self.cheats.freezes() {
+                    println!("{addr:04X}: {value:02X}");
+                }
+            },
+            "cheats" => {
+                if self.cheats.cheats().is_empty() {
+                    println!("The rom has no cheats");
+                }
+                for (i, (cheat, on)) in self.cheats.cheats().iter().enumerate() {
+                    let kind = match cheat.kind {
+                        CheatKind::Freeze if *on => "frozen",
+                        CheatKind::Freeze => "off",
+                        CheatKind::Once => "once",
+                    };
+                    let line = format!("{i}: {:04X} = {:02X} {kind:<6} {}", cheat.addr, cheat.value, cheat.description);
+                    println!("{}", line.trim_end());
+                }
+            },
+            "cheat" => {
+                let usage = "Usage: cheat <n> [on|off]";
+                let i = args.first().ok_or(usage)?.parse::<usize>().map_err(|_| usage)?;
+                if i >= self.cheats.cheats().len() {
+                    return Err(format!("There's no cheat {i}"));
+                }
+                let on = match args.get(1) {
+                    None | Some(&"on") => true,
+                    Some(&"off") => false,
+                    _ => return Err(usage.to_string()),
+                };
+                self.cheats.set_cheat(&mut self.chip, i, on);
+                self.changed_by_hand();
+            },
+            "h" | "help" => println!("{HELP}"),
+            "q" | "quit" => return Ok(false),
+            _ => return Err(format!("Unknown command '{command}', try 'help'")),
+        }
+
+        return Ok(true);
+    }
+
+    /// Runs until a breakpoint is hit, an instruction fails, or limit instructions have run.
+    /// The timers tick every cycles_per_frame instructions, as they would at full speed
+    fn continue_for(&mut self, limit: usize) {
+        for i in 0..limit {
+            // Breakpoints at the instruction continue starts from are stepped over, or
+            // continuing from one would never get anywhere
+            if i > 0 {
+                if let Some(n) = self.breakpoint_hit() {
+                    println!("Breakpoint {n} hit: {}", self.describe(n));
+                    return;
+                }
+            }
+
+            if let Err(e) = self.execute() {
+                println!("{e}");
+                print!("{}", self.backtrace());
+                return;
+            }
+        }
+        println!("No breakpoint hit in {limit} instructions");
+    }
+
+    /// Runs a single instruction, keeping the history for going backwards
+    fn execute(&mut self) -> Result<(), Chip8Error> {
+        // Coming back through a snapshot puts it back, which changes nothing but the random
+        // number generator, reseeded as it was the first time through
+        if let Some(snapshot) = self.rewind.at(self.executed) {
+            self.chip.restore(&snapshot);
+        } else if self.executed.is_multiple_of(REWIND_INTERVAL) && self.rewind.newest().is_none_or(|newest| newest < self.executed) {
+            let snapshot = self.chip.snapshot();
+            self.rewind.push(self.executed, snapshot);
+        }
+
+        let pc = self.chip.pc();
+        let opcode = (self.peek(pc as u32) as u16) << 8 | self.peek(pc as u32 + 1) as u16;
+        let registers = *self.chip.registers();
+        let result = self.chip.execute();
+        self.budget.instruction(&self.chip, pc, opcode);
+        let cycles = self.chip.cycles_per_frame() as u64;
+        if self.trace.is_some() {
+            self.record(registers, pc, opcode, self.executed / cycles);
+        }
+        self.cheats.apply(&mut self.chip);
+        self.executed += 1;
+        if self.executed.is_multiple_of(cycles) {
+            self.chip.tick_timers();
+            self.budget.end_frame(self.executed / cycles - 1);
+        }
+        return result;
+    }
+
+    /// Takes the machine back to how it was after `to` instructions, by restoring the last
+    /// snapshot before then and running forward to it. Errors along the way happened the
+    /// first time through too, so they're run past
+    fn rewind_to(&mut self, to: u64) -> Result<(), String> {
+        let Some((at, snapshot)) = self.rewind.before(to) else {
+            return Err(match self.rewind.oldest() {
+                Some(oldest) => format!("The history only goes back to instruction {oldest}"),
+                None => "There's no history to go back through".to_string(),
+            });
+        };
+        self.chip.restore(&snapshot);
+        self.executed = at;
+        self.budget.restart_frame();
+        while self.executed < to {
+            let _ = self.execute();
+        }
+        // They were reported the first time through
+        self.code_writes.lock().unwrap().clear();
+        return Ok(());
+    }
+
+    /// Goes back to the last time before now that a breakpoint stopped the machine, a stretch
+    /// between snapshots at a time, or to the start of the history if none ever did
+    fn reverse_continue(&mut self) {
+        let mut end = self.executed;
+        while let Some(start) = end.checked_sub(1).and_then(|last| self.rewind.taken_before(last)) {
+            self.rewind_to(start).expect("the snapshot was just found");
+            let mut hit = None;
+            while self.executed < end {
+                if let Some(n) = self.breakpoint_hit() {
+                    hit = Some((self.executed, n));
+                }
+                let _ = self.execute();
+            }
+
+            if let Some((at, n)) = hit {
+                self.rewind_to(at).expect("the snapshot before it was run through");
+                println!("Breakpoint {n} hit: {}", self.describe(n));
+                return;
+            }
+            end = start;
+        }
+
+        if end < self.executed {
+            self.rewind_to(end).expect("the snapshot was run through");
+        }
+        println!("No breakpoint hit since instruction {end}, the start of the history");
+    }
+
+    /// Snapshots the machine after it's been changed by hand, dropping the history after
+    /// this point. Going back before the change undoes it, but running forward from an older
+    /// snapshot couldn't redo it
+    fn changed_by_hand(&mut self) {
+        self.snapshot_here();
+        if let Some(trace) = &mut self.trace {
+            if let Err(e) = trace.truncate(self.executed) {
+                println!("An error occured when writing the trace, so it's stopped: {e}");
+                self.stop_tracing();
+            }
+        }
+    }
+
+    /// Adds the instruction that's just run to the trace, given the registers before it ran.
+    /// If the trace can't be written it's stopped
+    fn record(&mut self, before: [u8; 16], pc: u16, opcode: u16, frame: u64) {
+        let Some(trace) = &mut self.trace else {
+            return;
+        };
+        let registers = (0..16u8)
+            .map(|register| RegisterChange { register, old: before[register as usize], new: self.chip.registers()[register as usize] })
+            .filter(|change| change.old != change.new)
+            .collect();
+        let writes = std::mem::take(&mut *self.writes.lock().unwrap());
+        let record = TraceRecord { step: self.executed, frame, pc, opcode, ar: self.chip.ar(), registers, writes };
+        if let Err(e) = trace.record(record, before) {
+            println!("An error occured when writing the trace, so it's stopped: {e}");
+            self.stop_tracing();
+        }
+    }
+
+    fn stop_tracing(&mut self) {
+        self.trace = None;
+        self.chip.clear_write_hook();
+        self.writes.lock().unwrap().clear();
+    }
+
+    /// The trace as far as it's been recorded, or what's left from the last time if tracing's
+    /// off
+    fn open_trace(&mut self) -> Result<TraceDb, String> {
+        let dir: &Path = match (&mut self.trace, &self.trace_dir) {
+            (Some(trace), _) => {
+                trace.flush().map_err(|e| format!("An error occured when writing the trace: {e}"))?;
+                trace.dir()
+            },
+            (None, Some(dir)) => dir,
+            (None, None) => return Err("There's no trace, start one with trace on".to_string()),
+        };
+        return TraceDb::open(dir).map_err(|e| format!("An error occured when reading the trace: {e}"));
+    }
+
+    /// Takes a snapshot and keeps it in the history. Taking one reseeds the random number
+    /// generator, which running forward from an older snapshot wouldn't, so it has to be kept
+    /// to stay on the same path
+    fn snapshot_here(&mut self) -> Snapshot {
+        let snapshot = self.chip.snapshot();
+        self.rewind.push(self.executed, snapshot.clone());
+        return snapshot;
+    }
+
+    /// The writes over code since they were last reported, one a line, e.g.
+    /// "0210 wrote over code at 0234: 12 -> 34"
+    fn code_writes(&self) -> String {
+        let writes = std::mem::take(&mut *self.code_writes.lock().unwrap());
+        let mut text = String::new();
+        for write in writes.iter().take(CODE_WRITES_SHOWN) {
+            let addr = match &self.symbols {
+                Some(symbols) => symbols.describe(write.addr as u16),
+                None => format!("{:04X}", write.addr),
+            };
+            text += &format!("{:04X} wrote over code at {addr}: {:02X} -> {:02X}\n", write.pc, write.old, write.new);
+        }
+        if writes.len() > CODE_WRITES_SHOWN {
+            let more = writes.len() - CODE_WRITES_SHOWN;
+            text += &format!("...and {more} more write{} over code\n", if more == 1 { "" } else { "s" });
+        }
+        return text;
+    }
+
+    /// The first breakpoint that stops the machine where it is now
+    fn breakpoint_hit(&self) -> Option<usize> {
+        let pc = self.chip.pc() as u32;
+        let opcode = (self.peek(pc) as u16) << 8 | self.peek(pc + 1) as u16;
+        return self.breakpoints.iter().position(|breakpoint| {
+            breakpoint.addr.is_none_or(|addr| addr == pc)
+                && breakpoint.opcode.as_ref().is_none_or(|op| op.matches(opcode, self.chip.platform()))
+                && breakpoint.condition.as_ref().is_none_or(|condition| condition.test(&self.chip))
+        });
+    }
+
+    fn describe(&self, n: usize) -> String {
+        let breakpoint = &self.breakpoints[n];
+        let mut description = match (breakpoint.addr, &breakpoint.opcode) {
+            (Some(addr), Some(opcode)) => format!("{addr:04X} on {opcode}"),
+            (Some(addr), None) => format!("{addr:04X}"),
+            (None, Some(opcode)) => format!("on {opcode}"),
+            (None, None) => "anywhere".to_string(),
+        };
+        if let Some(condition) = &breakpoint.condition {
+            description.push_str(&format!(" if {condition}"));
+        }
+        return description;
+    }
+
+    /// Evaluates the watches again and shows them, marking the ones that changed
+    fn show_watches(&mut self) {
+        for n in 0..self.watches.len() {
+            let before = self.watches[n].update(&self.chip);
+            println!("{}", self.describe_watch(n, before));
+        }
+    }
+
+    fn describe_watch(&self, n: usize, before: Option<u32>) -> String {
+        let watch = &self.watches[n];
+        let value = watch.value().unwrap_or(0);
+        return match before {
+            Some(before) => format!("*{n}: {} = {value:X} ({value}), was {before:X}", watch.expr()),
+            None => format!(" {n}: {} = {value:X} ({value})", watch.expr()),
+        };
+    }
+
+    fn search(&mut self, args: &[&str]) -> Result<(), String> {
+        let filter = match args {
+            ["start"] => {
+                self.cheats.start_search(&self.chip);
+                println!("{} candidates", self.cheats.candidates().len());
+                return Ok(());
+            },
+            ["list"] => {
+                for addr in self.cheats.candidates().iter().take(64) {
+                    println!("{addr:04X}: {:02X}", self.peek(*addr));
+                }
+                if self.cheats.candidates().len() > 64 {
+                    println!("... and {} more", self.cheats.candidates().len() - 64);
+                }
+                return Ok(());
+            },
+            ["eq", value] => SearchFilter::Equal(parse_number(value)? as u8),
+            ["changed"] => SearchFilter::Changed,
+            ["unchanged"] => SearchFilter::Unchanged,
+            ["inc"] => SearchFilter::Increased,
+            ["dec"] => SearchFilter::Decreased,
+            _ => return Err("Usage: search start|list|eq <value>|changed|unchanged|inc|dec".to_string()),
+        };
+
+        let remaining = self.cheats.filter(&self.chip, filter);
+        println!("{remaining} candidates");
+
+        return Ok(());
+    }
+
+    fn backtrace(&self) -> String {
+        return symbols::backtrace(self.chip.pc(), self.chip.call_stack(), self.symbols.as_ref());
+    }
+
+    fn peek(&self, addr: u32) -> u8 {
+        return self.chip.read_mem(addr, 1).first().copied().unwrap_or(0);
+    }
+
+    fn registers(&self) -> String {
+        let mut registers = format!(
+            "PC: {:04X}  OP: {:04X}  I: {:03X}  SP: {}  DT: {:02X}  ST: {:02X}\n",
+            self.chip.pc(),
+            self.chip.opcode(),
+            self.chip.ar(),
+            self.chip.sp(),
+            self.chip.delay(),
+            self.chip.sound()
+        );
+        for (i, v) in self.chip.registers().iter().enumerate() {
+            registers.push_str(&format!("V{i:X}: {v:02X}  "));
+            if i == 7 {
+                registers.push('\n');
+            }
+        }
+        return registers.trim_end().to_string();
+    }
+}
+
+/// Reads a save state written by save
+fn read_state(path: &str) -> Result<Snapshot, String> {
+    let bytes = std::fs::read(persist::find_state(path)).map_err(|e| format!("An error occured when reading the state: {e}"))?;
+    return Snapshot::from_bytes(&bytes).map_err(|e| format!("An error occured when reading the state: {e}"));
+}
+
+/// Parses a hex number as typed by the user, e.g. 200, 0x200 or 0X200
+pub fn parse_number(text: &str) -> Result<usize, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    return usize::from_str_radix(digits, 16).map_err(|_| format!("invalid number '{text}'"));
+}
@@ -0,0 +1,173 @@
+use std::fmt::Write;
+
+use crate::chip::Snapshot;
+use crate::framebuffer::Framebuffer;
+
+// What changed between two snapshots of a machine, for answering questions like "what did
+// frame 101 do?" from two save states. The report reads like this:
+//
+//   Registers:
+//     PC  0204 -> 0206
+//     V3  00 -> 05
+//   Stack: 0204 -> 0204 0310
+//   Memory, 3 ranges:
+//     0300-0302  00 00 00 -> 01 02 03
+//     0340       00 -> 09
+//     0E00-0EFF  256 bytes, 40 different
+//   Screen, 12 pixels in 2 regions:
+//     x 8-15, y 0-4      10 pixels
+//     x 30-31, y 12      2 pixels
+//
+// Differing bytes of memory closer together than MEMORY_GAP are grouped into one range, and
+// changed pixels that touch (diagonals included) into one region.
+
+/// Unchanged bytes between two differing ones that still keep them in the same range
+const MEMORY_GAP: usize = 8;
+
+/// Ranges of memory up to this long show their bytes, longer ones just count them
+const MEMORY_SHOWN: usize = 16;
+
+/// How many ranges and regions are listed before the rest are only counted
+const LISTED: usize = 32;
+
+
+/// Describes how b differs from a, as above. It's empty if they're the same
+pub fn diff(a: &Snapshot, b: &Snapshot) -> String {
+    let mut report = String::new();
+    if a.platform() != b.platform() {
+        writeln!(report, "Platform: {} -> {}", a.platform(), b.platform()).unwrap();
+    }
+    registers(&mut report, a, b);
+    if a.call_stack() != b.call_stack() {
+        writeln!(report, "Stack: {} -> {}", stack(a.call_stack()), stack(b.call_stack())).unwrap();
+    }
+    memory(&mut report, a.mem(), b.mem());
+    screen(&mut report, a.framebuffer(), b.framebuffer());
+    return report;
+}
+
+fn registers(report: &mut String, a: &Snapshot, b: &Snapshot) {
+    let mut changed = vec![
+        ("PC", a.pc() as u32, b.pc() as u32, 4),
+        ("OP", a.opcode() as u32, b.opcode() as u32, 4),
+        ("I", a.ar(), b.ar(), 4),
+        ("SP", a.sp() as u32, b.sp() as u32, 1),
+        ("DT", a.delay() as u32, b.delay() as u32, 2),
+        ("ST", a.sound() as u32, b.sound() as u32, 2),
+    ];
+    let names = ["V0", "V1", "V2", "V3", "V4", "V5", "V6", "V7", "V8", "V9", "VA", "VB", "VC", "VD", "VE", "VF"];
+    for (x, name) in names.into_iter().enumerate() {
+        changed.push((name, a.registers()[x] as u32, b.registers()[x] as u32, 2));
+    }
+    changed.retain(|(_, a, b, _)| a != b);
+
+    let flags = (0..16).filter(|x| a.rpl_flags()[*x] != b.rpl_flags()[*x]);
+    let flags: Vec<String> = flags.map(|x| format!("R{x:X} {:02X} -> {:02X}", a.rpl_flags()[x], b.rpl_flags()[x])).collect();
+    let keys = |snapshot: &Snapshot| (0..16).filter(|key| snapshot.keys()[*key]).map(|key| format!("{key:X}")).collect::<String>();
+    if changed.is_empty() && flags.is_empty() && a.keys() == b.keys() {
+        return;
+    }
+
+    report.push_str("Registers:\n");
+    for (name, a, b, digits) in changed {
+        writeln!(report, "  {name:<3} {a:0digits$X} -> {b:0digits$X}").unwrap();
+    }
+    if !flags.is_empty() {
+        writeln!(report, "  RPL {}", flags.join(", ")).unwrap();
+    }
+    if a.keys() != b.keys() {
+        writeln!(report, "  Keys held [{}] -> [{}]", keys(a), keys(b)).unwrap();
+    }
+}
+
+fn stack(call_stack: &[u16]) -> String {
+    if call_stack.is_empty() {
+        return "empty".to_string();
+    }
+    return call_stack.iter().map(|addr| format!("{addr:04X}")).collect::<Vec<_>>().join(" ");
+}
+
+fn memory(report: &mut String, a: &[u8], b: &[u8]) {
+    if a.len() != b.len() {
+        writeln!(report, "Memory size: {} -> {} bytes, comparing the first {}", a.len(), b.len(), a.len().min(b.len())).unwrap();
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for addr in (0..a.len().min(b.len())).filter(|addr| a[*addr] != b[*addr]) {
+        match ranges.last_mut() {
+            Some((_, end)) if addr - *end <= MEMORY_GAP => *end = addr,
+            _ => ranges.push((addr, addr)),
+        }
+    }
+    if ranges.is_empty() {
+        return;
+    }
+
+    writeln!(report, "Memory, {} range{}:", ranges.len(), if ranges.len() == 1 { "" } else { "s" }).unwrap();
+    for (start, end) in ranges.iter().take(LISTED) {
+        let (before, after) = (&a[*start..=*end], &b[*start..=*end]);
+        let at = if start == end { format!("{start:04X}") } else { format!("{start:04X}-{end:04X}") };
+        write!(report, "  {at:<9}  ").unwrap();
+        if before.len() <= MEMORY_SHOWN {
+            writeln!(report, "{} -> {}", hex(before), hex(after)).unwrap();
+        } else {
+            let different = before.iter().zip(after).filter(|(a, b)| a != b).count();
+            writeln!(report, "{} bytes, {different} different", before.len()).unwrap();
+        }
+    }
+    if ranges.len() > LISTED {
+        writeln!(report, "  and {} more", ranges.len() - LISTED).unwrap();
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>().join(" ");
+}
+
+fn screen(report: &mut String, a: &Framebuffer, b: &Framebuffer) {
+    let (width, height) = (a.width(), a.height());
+    if (width, height) != (b.width(), b.height()) {
+        writeln!(report, "Screen: {width}x{height} -> {}x{}", b.width(), b.height()).unwrap();
+        return;
+    }
+
+    // Flood fills each group of touching changed pixels, keeping its bounds and size
+    let mut changed: Vec<bool> = a.to_bytes().into_iter().zip(b.to_bytes()).map(|(a, b)| a != b).collect();
+    let total = changed.iter().filter(|changed| **changed).count();
+    if total == 0 {
+        return;
+    }
+    let mut regions = Vec::new();
+    for start in 0..changed.len() {
+        if !changed[start] {
+            continue;
+        }
+        changed[start] = false;
+        let (mut left, mut top, mut right, mut bottom, mut pixels) = (width, height, 0, 0, 0);
+        let mut todo = vec![start];
+        while let Some(pixel) = todo.pop() {
+            let (x, y) = (pixel % width, pixel / width);
+            (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+            pixels += 1;
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    if changed[ny * width + nx] {
+                        changed[ny * width + nx] = false;
+                        todo.push(ny * width + nx);
+                    }
+                }
+            }
+        }
+        regions.push((left, top, right, bottom, pixels));
+    }
+
+    writeln!(report, "Screen, {total} pixel{} in {} region{}:", if total == 1 { "" } else { "s" }, regions.len(), if regions.len() == 1 { "" } else { "s" }).unwrap();
+    for (left, top, right, bottom, pixels) in regions.iter().take(LISTED) {
+        let span = |from: &usize, to: &usize| if from == to { format!("{from}") } else { format!("{from}-{to}") };
+        let at = format!("x {}, y {}", span(left, right), span(top, bottom));
+        writeln!(report, "  {at:<18} {pixels} pixel{}", if *pixels == 1 { "" } else { "s" }).unwrap();
+    }
+    if regions.len() > LISTED {
+        writeln!(report, "  and {} more", regions.len() - LISTED).unwrap();
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+use crate::chip::Chip8;
+use crate::platform::Platform;
+
+// Differential testing: a rom is run on the interpreter and on Reference, a second CHIP-8
+// written as plainly as possible and sharing no code with chip.rs, comparing the whole
+// machine after every instruction. The first instruction they disagree on is almost always
+// a bug in one of them, and random roms (see fuzzgen.rs) go through rarely used
+// instructions far more than real games do. Emulators elsewhere can be compared against the
+// same way through the trace protocol, see trace.rs and chip8 difftest --against.
+//
+// Reference follows the choices chip.rs makes for plain CHIP-8 rather than the original
+// VIP's: 8XY6 and 8XYE shift Vx, FX55 and FX65 leave I alone, and sprites are cut off at the
+// edges. Both run strict, so anything chip.rs would skip over stops the run instead, and a
+// run where both stop on the same instruction agrees. Nothing is pressed, and CXNN takes the
+// interpreter's random number since the two can't draw the same ones.
+
+/// How many instructions a rom is run for if it doesn't stop first
+pub const STEPS: usize = 100_000;
+
+
+/// Where the interpreter and Reference first disagreed
+pub struct Divergence {
+    /// Instructions run before this one, which both agreed on
+    pub step: usize,
+    pub pc: u16,
+    pub opcode: u16,
+    /// What differed, as (what, the interpreter's, Reference's)
+    pub differences: Vec<(String, String, String)>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Diverged at instruction {}, {:04X} at {:04X}:", self.step, self.opcode, self.pc)?;
+        for (what, interpreter, reference) in &self.differences {
+            writeln!(f, "  {what}: interpreter {interpreter}, reference {reference}")?;
+        }
+        return Ok(());
+    }
+}
+
+/// How far a run agreed, and what stopped it if it wasn't running out of steps
+pub struct Agreed {
+    pub steps: usize,
+    pub stopped: Option<String>,
+}
+
+/// Runs the rom on both for up to `steps` instructions, ticking the timers every
+/// cycles_per_frame instructions
+pub fn run(rom: &[u8], steps: usize, seed: u64) -> Result<Agreed, Box<Divergence>> {
+    let mut chip = Chip8::with_platform(Platform::Chip8, false);
+    chip.set_strict(true);
+    chip.seed_rng(seed);
+    chip.load_rom_bytes(rom);
+    // Fonts and loading aren't what's being compared, so Reference starts from the same memory
+    let mut reference = Reference::new(chip.read_mem(0, chip.memory_size()));
+
+    for step in 0..steps {
+        let (pc, opcode) = (reference.pc, reference.fetch().unwrap_or_default());
+        let interpreter = chip.execute();
+        let random = chip.registers()[(opcode >> 8 & 0xF) as usize];
+        let expected = reference.execute(random);
+
+        let mut differences = compare(&chip, &reference);
+        match (&interpreter, &expected) {
+            (Err(e), Ok(())) => differences.insert(0, ("error".to_string(), e.to_string(), "none".to_string())),
+            (Ok(()), Err(e)) => differences.insert(0, ("error".to_string(), "none".to_string(), e.clone())),
+            _ => {},
+        }
+        if !differences.is_empty() {
+            return Err(Box::new(Divergence { step, pc, opcode, differences }));
+        }
+        if let Err(e) = interpreter {
+            return Ok(Agreed { steps: step, stopped: Some(e.to_string()) });
+        }
+
+        if (step + 1) % chip.cycles_per_frame() == 0 {
+            chip.tick_timers();
+            reference.tick_timers();
+        }
+    }
+    return Ok(Agreed { steps, stopped: None });
+}
+
+/// Everything that's different between the two machines
+fn compare(chip: &Chip8, reference: &Reference) -> Vec<(String, String, String)> {
+    let mut differences = Vec::new();
+    differ(&mut differences, "PC", chip.pc(), reference.pc);
+    differ(&mut differences, "I", chip.ar(), reference.i);
+    for x in (0..16).filter(|x| chip.registers()[*x] != reference.v[*x]) {
+        differ(&mut differences, &format!("V{x:X}"), chip.registers()[x], reference.v[x]);
+    }
+    differ(&mut differences, "stack", chip.call_stack(), &reference.stack[..]);
+    differ(&mut differences, "DT", chip.delay(), reference.delay);
+    differ(&mut differences, "ST", chip.sound(), reference.sound);
+
+    // Comparing them whole first is a lot quicker than looking for the first difference
+    let mem = chip.read_mem(0, chip.memory_size());
+    if mem != reference.mem {
+        let addr = mem.iter().zip(&reference.mem).position(|(a, b)| a != b).expect("they're the same size");
+        differ(&mut differences, &format!("memory at {addr:04X}"), mem[addr], reference.mem[addr]);
+    }
+    let pixels = chip.framebuffer().to_bytes();
+    if pixels != reference.screen {
+        let pixel = pixels.iter().zip(&reference.screen).position(|(a, b)| a != b).expect("they're the same size");
+        let at = format!("pixel {},{}", pixel % WIDTH, pixel / WIDTH);
+        differ(&mut differences, &at, pixels[pixel], reference.screen[pixel]);
+    }
+    return differences;
+}
+
+/// Notes down a difference, in hex, if there is one. This runs after every instruction, so
+/// nothing's formatted unless it differs
+fn differ<T: PartialEq + fmt::Debug>(differences: &mut Vec<(String, String, String)>, what: &str, interpreter: T, reference: T) {
+    if interpreter != reference {
+        differences.push((what.to_string(), format!("{interpreter:02X?}"), format!("{reference:02X?}")));
+    }
+}
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 32;
+
+/// A minimal CHIP-8, checked against rather than run. Errors are plain descriptions, since
+/// only whether it stopped is compared
+struct Reference {
+    mem: Vec<u8>,
+    v: [u8; 16],
+    i: u32,
+    pc: u16,
+    stack: Vec<u16>,
+    delay: u8,
+    sound: u8,
+    screen: [u8; WIDTH * HEIGHT],
+}
+
+impl Reference {
+    fn new(mem: &[u8]) -> Self {
+        return Self {
+            mem: mem.to_vec(),
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: Vec::new(),
+            delay: 0,
+            sound: 0,
+            screen: [0; WIDTH * HEIGHT],
+        };
+    }
+
+    fn fetch(&self) -> Result<u16, String> {
+        let bytes = self.read(self.pc as u32, 2)?;
+        return Ok(u16::from_be_bytes([bytes[0], bytes[1]]));
+    }
+
+    fn read(&self, addr: u32, len: usize) -> Result<&[u8], String> {
+        return self.mem.get(addr as usize..addr as usize + len).ok_or(format!("{addr:04X} is out of memory"));
+    }
+
+    fn tick_timers(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+
+    /// Runs one instruction, with `random` as what CXNN draws before it's masked. On an error
+    /// the PC is left at the instruction, like the interpreter does
+    fn execute(&mut self, random: u8) -> Result<(), String> {
+        let pc = self.pc;
+        let result = self.execute_opcode(random);
+        if result.is_err() {
+            self.pc = pc;
+        }
+        return result;
+    }
+
+    fn execute_opcode(&mut self, random: u8) -> Result<(), String> {
+        let opcode = self.fetch()?;
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = (opcode & 0xF) as usize;
+        let nn = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let (vx, vy) = (self.v[x], self.v[y]);
+        self.pc += 2;
+
+        match (opcode >> 12, x, y, n) {
+            (0x0, 0x0, 0xE, 0x0) => self.screen = [0; WIDTH * HEIGHT],
+            (0x0, 0x0, 0xE, 0xE) => self.pc = self.stack.pop().ok_or("return with nothing on the stack")?,
+            (0x1, ..) => self.pc = nnn,
+            (0x2, ..) => {
+                if self.stack.len() == 16 {
+                    return Err("call with the stack full".to_string());
+                }
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            },
+            (0x3, ..) => self.skip_if(vx == nn),
+            (0x4, ..) => self.skip_if(vx != nn),
+            (0x5, _, _, 0x0) => self.skip_if(vx == vy),
+            (0x6, ..) => self.v[x] = nn,
+            (0x7, ..) => self.v[x] = vx.wrapping_add(nn),
+            (0x8, _, _, 0x0) => self.v[x] = vy,
+            (0x8, _, _, 0x1) => self.v[x] = vx | vy,
+            (0x8, _, _, 0x2) => self.v[x] = vx & vy,
+            (0x8, _, _, 0x3) => self.v[x] = vx ^ vy,
+            (0x8, _, _, 0x4) => self.set_with_flag(x, vx as u16 + vy as u16, (vx as u16 + vy as u16 > 0xFF) as u8),
+            (0x8, _, _, 0x5) => self.set_with_flag(x, vx.wrapping_sub(vy) as u16, (vx >= vy) as u8),
+            (0x8, _, _, 0x6) => self.set_with_flag(x, (vx / 2) as u16, vx % 2),
+            (0x8, _, _, 0x7) => self.set_with_flag(x, vy.wrapping_sub(vx) as u16, (vy >= vx) as u8),
+            (0x8, _, _, 0xE) => self.set_with_flag(x, (vx as u16 * 2) & 0xFF, (vx >= 0x80) as u8),
+            (0x9, _, _, 0x0) => self.skip_if(vx != vy),
+            (0xA, ..) => self.i = nnn as u32,
+            (0xB, ..) => self.pc = nnn + self.v[0] as u16,
+            (0xC, ..) => self.v[x] = random & nn,
+            (0xD, ..) => self.draw(vx as usize % WIDTH, vy as usize % HEIGHT, n)?,
+            // Nothing's ever pressed
+            (0xE, _, 0x9, 0xE) => {},
+            (0xE, _, 0xA, 0x1) => self.pc += 2,
+            (0xF, _, 0x0, 0x7) => self.v[x] = self.delay,
+            (0xF, _, 0x0, 0xA) => self.pc -= 2,
+            (0xF, _, 0x1, 0x5) => self.delay = vx,
+            (0xF, _, 0x1, 0x8) => self.sound = vx,
+            (0xF, _, 0x1, 0xE) => self.i += vx as u32,
+            (0xF, _, 0x2, 0x9) => self.i = (vx % 16) as u32 * 5,
+            (0xF, _, 0x3, 0x3) => {
+                self.read(self.i, 3)?;
+                let at = self.i as usize;
+                self.mem[at..at + 3].copy_from_slice(&[vx / 100, vx / 10 % 10, vx % 10]);
+            },
+            (0xF, _, 0x5, 0x5) => {
+                self.read(self.i, x + 1)?;
+                let at = self.i as usize;
+                self.mem[at..=at + x].copy_from_slice(&self.v[..=x]);
+            },
+            (0xF, _, 0x6, 0x5) => {
+                let values = self.read(self.i, x + 1)?.to_vec();
+                self.v[..=x].copy_from_slice(&values);
+            },
+            _ => return Err(format!("{opcode:04X} isn't an instruction")),
+        }
+        return Ok(());
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc += 2;
+        }
+    }
+
+    /// Sets Vx and then VF, so the flag wins when x is F
+    fn set_with_flag(&mut self, x: usize, value: u16, flag: u8) {
+        self.v[x] = value as u8;
+        self.v[0xF] = flag;
+    }
+
+    fn draw(&mut self, left: usize, top: usize, rows: usize) -> Result<(), String> {
+        let sprite = self.read(self.i, rows)?.to_vec();
+        self.v[0xF] = 0;
+        for (dy, row) in sprite.iter().enumerate() {
+            for dx in 0..8 {
+                let (x, y) = (left + dx, top + dy);
+                if row & (0x80 >> dx) == 0 || x >= WIDTH || y >= HEIGHT {
+                    continue;
+                }
+                if self.screen[y * WIDTH + x] == 1 {
+                    self.v[0xF] = 1;
+                }
+                self.screen[y * WIDTH + x] ^= 1;
+            }
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::flow::{self, Flow};
+use crate::isa::{self, Operand};
+use crate::platform::Platform;
+use crate::symbols::SymbolTable;
+
+// Disassembling a whole rom (chip8 disasm). Only the instructions the program can reach are
+// disassembled, going by its control flow (flow.rs), and everything else is listed as data,
+// so sprites and tables don't come out as nonsense instructions. Addresses are labelled with
+// what gets there: main where the program starts, sub_ for what's called, label_ for what's
+// jumped to, table_ for where computed jumps go and data_ for the data between and where I's
+// pointed. A symbol file's names are used instead where it has one for the address.
+//
+//   ; BRIX as CHIP-8: 134 instructions from 0200, 12 bytes of data
+//   main:
+//     0200: 6E05  LD VE, #05
+//   ...
+//     0220: 22F6  CALL sub_02F6
+//   ...
+//   data_030C:
+//     030C: E0 00
+
+
+/// How many bytes of data each line holds
+const DATA_ROW: usize = 8;
+
+
+/// Lists the rom as the platform would run it, naming addresses from the symbols if given
+pub fn disassemble(name: &str, rom: &[u8], platform: Platform, symbols: Option<&SymbolTable>) -> String {
+    let flow = flow::trace(rom, platform);
+    let labels = labels(rom, platform, &flow, symbols);
+    let data: usize = (flow.start..flow.end).filter(|addr| !flow.is_code(*addr, platform)).count();
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "; {name} as {}: {} instructions from {:04X}, {data} bytes of data",
+        platform.name(),
+        flow.code.len(),
+        flow.entry
+    )
+    .unwrap();
+
+    let mut addr = flow.start;
+    let mut inside = None;
+    while (addr as u32) < flow.end as u32 {
+        if let Some(label) = labels.get(&addr) {
+            writeln!(text, "{label}:").unwrap();
+        }
+
+        let Some(&opcode) = flow.code.get(&addr) else {
+            // Data runs until the next instruction or label
+            let next = flow.code.range(addr..).map(|(at, _)| *at).next().unwrap_or(flow.end);
+            let next = labels.range(addr + 1..).map(|(at, _)| *at).next().map_or(next, |label| label.min(next));
+            for row in (addr..next).step_by(DATA_ROW) {
+                let bytes = &rom[(row - flow.start) as usize..(next.min(row + DATA_ROW as u16) - flow.start) as usize];
+                let bytes = bytes.iter().map(|byte| format!("{byte:02X}")).collect::<Vec<_>>().join(" ");
+                writeln!(text, "  {row:04X}: {bytes}").unwrap();
+            }
+            addr = next;
+            continue;
+        };
+
+        let mut line = format!("  {addr:04X}: {opcode:04X}  {}", instruction(rom, platform, &flow, &labels, addr, opcode));
+        if let Some(outer) = inside.take() {
+            write!(line, "  ; inside the instruction at {outer:04X}").unwrap();
+        } else if flow.computed.contains(&addr) {
+            write!(line, "  ; computed jump, V0 picks where").unwrap();
+        } else if flow.unknown.contains(&addr) {
+            write!(line, "  ; nothing decodes this, and the program stops here").unwrap();
+        }
+        writeln!(text, "{}", line.trim_end()).unwrap();
+
+        // An instruction something jumps into the middle of is listed after it
+        let length = isa::length(opcode, platform) as u16;
+        addr = match flow.code.range(addr + 1..addr.saturating_add(length)).next() {
+            Some((overlapping, _)) => {
+                inside = Some(addr);
+                *overlapping
+            },
+            None => addr.saturating_add(length),
+        };
+    }
+    return text;
+}
+
+/// The name of every address something goes to
+fn labels(rom: &[u8], platform: Platform, flow: &Flow, symbols: Option<&SymbolTable>) -> BTreeMap<u16, String> {
+    let mut labels = BTreeMap::new();
+    for (addr, opcode) in &flow.code {
+        // I pointed at data, a sprite or something loaded or saved
+        let pointed = match opcode & 0xF000 {
+            0xA000 => Some(opcode & 0xFFF),
+            _ if *opcode == 0xF000 => flow.opcode(rom, addr + 2),
+            _ => None,
+        };
+        if let Some(data) = pointed.filter(|data| !flow.code.contains_key(data)) {
+            labels.insert(data, format!("data_{data:04X}"));
+        }
+    }
+    let mut addr = flow.start;
+    while addr < flow.end {
+        if !flow.is_code(addr, platform) && (addr == flow.start || flow.is_code(addr - 1, platform)) {
+            labels.insert(addr, format!("data_{addr:04X}"));
+        }
+        addr += 1;
+    }
+    for (addr, opcode) in &flow.code {
+        if flow.computed.contains(addr) {
+            labels.insert(opcode & 0xFFF, format!("table_{:04X}", opcode & 0xFFF));
+        }
+    }
+    for label in &flow.labels {
+        labels.insert(*label, format!("label_{label:04X}"));
+    }
+    for subroutine in &flow.subroutines {
+        labels.insert(*subroutine, format!("sub_{subroutine:04X}"));
+    }
+    labels.insert(flow.entry, "main".to_string());
+    labels.retain(|addr, _| (flow.start..flow.end).contains(addr));
+
+    if let Some(symbols) = symbols {
+        for (addr, label) in labels.iter_mut() {
+            if let Some((name, 0)) = symbols.lookup(*addr) {
+                *label = name.to_string();
+            }
+        }
+    }
+    return labels;
+}
+
+/// The instruction with the addresses it goes to or points I at named, and XO-CHIP's long I
+/// filled in
+fn instruction(rom: &[u8], platform: Platform, flow: &Flow, labels: &BTreeMap<u16, String>, addr: u16, opcode: u16) -> String {
+    let text = isa::disassemble(opcode, platform);
+    let Some(info) = isa::lookup(opcode, platform) else {
+        return text;
+    };
+    if info.operands().any(|operand| operand == Operand::Addr) {
+        if let Some(label) = labels.get(&info.value(Operand::Addr, opcode)) {
+            return text.replace(&info.format(Operand::Addr, opcode), label);
+        }
+    }
+    if opcode == 0xF000 {
+        if let Some(long) = flow.opcode(rom, addr + 2) {
+            let long = labels.get(&long).cloned().unwrap_or(format!("#{long:04X}"));
+            return text.replace("long", &long);
+        }
+    }
+    return text;
+}
@@ -0,0 +1,59 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Remembers, for each pixel of the screen, the last frame a sprite toggled it and the last
+/// frame it was in a collision (a sprite pixel landing on one that's already set, which is
+/// what sets VF). Indexed the same as Framebuffer::to_bytes, y * width + x
+pub struct DrawMap {
+    frame: u64,
+    /// The frame plus one that each pixel was last toggled in, 0 for never
+    toggled: Vec<u64>,
+    /// The same for collisions
+    collided: Vec<u64>,
+}
+
+impl DrawMap {
+    pub fn new(pixels: usize) -> Self {
+        return Self { frame: 0, toggled: vec![0; pixels], collided: vec![0; pixels] };
+    }
+
+    /// Marks a pixel as drawn to this frame, and as collided if it was
+    pub fn mark(&mut self, pixel: usize, collision: bool) {
+        if let Some(toggled) = self.toggled.get_mut(pixel) {
+            *toggled = self.frame + 1;
+        }
+        if collision {
+            if let Some(collided) = self.collided.get_mut(pixel) {
+                *collided = self.frame + 1;
+            }
+        }
+    }
+
+    /// Moves on to the next frame. A screen that's changed resolution starts afresh, since
+    /// the old pixels don't line up with the new ones
+    pub fn end_frame(&mut self, pixels: usize) {
+        self.frame += 1;
+        if pixels != self.toggled.len() {
+            *self = Self { frame: self.frame, ..Self::new(pixels) };
+        }
+    }
+
+    /// How many frames have ended since the map was made
+    pub fn frame(&self) -> u64 {
+        return self.frame;
+    }
+
+    /// How many frames ago the pixel was last toggled, 0 being the current frame
+    pub fn toggled_ago(&self, pixel: usize) -> Option<u64> {
+        return self.ago(self.toggled.get(pixel).copied().unwrap_or(0));
+    }
+
+    /// How many frames ago the pixel was last in a collision, 0 being the current frame
+    pub fn collided_ago(&self, pixel: usize) -> Option<u64> {
+        return self.ago(self.collided.get(pixel).copied().unwrap_or(0));
+    }
+
+    fn ago(&self, stamp: u64) -> Option<u64> {
+        return (stamp != 0).then(|| self.frame + 1 - stamp);
+    }
+}
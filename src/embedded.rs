@@ -0,0 +1,63 @@
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::framebuffer::Framebuffer;
+
+// Running the interpreter without an OS, e.g. on a microcontroller with the crate built
+// without its std feature. The board supplies a clock, a display and a keypad by
+// implementing these traits and run() drives the machine at 60 frames a second. Random
+// numbers come from whatever is given to Chip8::set_rng (the RP2040's ring oscillator, the
+// ESP32's RNG peripheral), and roms are loaded from bytes with Chip8::load_rom_bytes,
+// usually straight out of flash with include_bytes!.
+
+
+/// A clock that only counts up
+pub trait Clock {
+    /// Microseconds since some fixed point, e.g. boot
+    fn now_us(&mut self) -> u64;
+
+    /// Waits until now_us reaches us. Spins by default, boards can sleep instead
+    fn wait_until(&mut self, us: u64) {
+        while self.now_us() < us {}
+    }
+}
+
+/// Somewhere to show the screen
+pub trait Display {
+    /// Shows the screen as it is at the end of a frame
+    fn show(&mut self, framebuffer: &Framebuffer);
+}
+
+/// The hex keypad
+pub trait Keypad {
+    /// Which of the 16 keys are held down, indexed by key
+    fn keys(&mut self) -> [bool; 16];
+}
+
+#[cfg(feature = "embedded-graphics")]
+pub mod graphics;
+
+const FRAME_US: u64 = 1_000_000 / 60;
+
+
+/// Runs the rom until an instruction fails and returns the error. Each frame reads the
+/// keypad, runs the machine and shows the screen, then waits for the next 60th of a second
+pub fn run(chip: &mut Chip8, clock: &mut impl Clock, display: &mut impl Display, keypad: &mut impl Keypad) -> Chip8Error {
+    let mut next_frame = clock.now_us();
+    loop {
+        for (key, held) in keypad.keys().into_iter().enumerate() {
+            chip.set_key(key as u8, held);
+        }
+        if let Err(e) = chip.run_frame() {
+            return e;
+        }
+        display.show(chip.framebuffer());
+
+        // Fall back in step rather than racing to catch up after a stall
+        next_frame += FRAME_US;
+        let now = clock.now_us();
+        if next_frame < now {
+            next_frame = now;
+        }
+        clock.wait_until(next_frame);
+    }
+}
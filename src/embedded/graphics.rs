@@ -0,0 +1,83 @@
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Size;
+use embedded_graphics::primitives::Rectangle;
+
+use super::Display;
+use crate::framebuffer::Framebuffer;
+
+// Shows the screen on anything embedded-graphics can draw to, so the display drivers for
+// the SSD1306, ST7789 and the rest work without any glue. The screen is scaled up by the
+// largest whole number that fits and centred: 64x32 fills a 128x64 SSD1306 at 2x, and sits
+// in the middle of a 240x240 ST7789 at 3x. Pixels are drawn in one of two colours, so
+// CHIP-8X colours and the Mega-Chip screen aren't shown.
+//
+// Drivers that draw into a buffer and flush it to the panel (like the ssd1306 crate's
+// buffered mode) need a Display of their own that calls draw and then flushes.
+
+
+/// Draws the screen onto target, scaled up to fit and centred, in the on and off colours
+pub fn draw<D: DrawTarget>(framebuffer: &Framebuffer, target: &mut D, on: D::Color, off: D::Color) -> Result<(), D::Error> {
+    let bounds = target.bounding_box();
+    let (width, height) = (framebuffer.width() as u32, framebuffer.height() as u32);
+    let scale = (bounds.size.width / width).min(bounds.size.height / height).max(1);
+    let size = Size::new(width * scale, height * scale);
+    let area = Rectangle::new(bounds.top_left + bounds.size.saturating_sub(size) / 2, size);
+
+    // fill_contiguous takes the pixels row by row, which is far quicker over SPI or I2C
+    // than drawing them one at a time
+    let colours = (0..size.height).flat_map(move |y| {
+        (0..size.width).map(move |x| match framebuffer.get((x / scale) as usize, (y / scale) as usize) {
+            0 => off,
+            _ => on,
+        })
+    });
+    return target.fill_contiguous(&area, colours);
+}
+
+/// A DrawTarget as the Display that embedded::run shows the screen on
+pub struct DrawTargetDisplay<D: DrawTarget> {
+    target: D,
+    on: D::Color,
+    off: D::Color,
+    /// The resolution last drawn, so the border can be cleared when it changes
+    size: Option<(usize, usize)>,
+    error: Option<D::Error>,
+}
+
+impl<D: DrawTarget> DrawTargetDisplay<D> {
+    pub fn new(target: D, on: D::Color, off: D::Color) -> Self {
+        return Self { target, on, off, size: None, error: None };
+    }
+
+    pub fn target(&mut self) -> &mut D {
+        return &mut self.target;
+    }
+
+    pub fn into_inner(self) -> D {
+        return self.target;
+    }
+
+    /// The last error drawing failed with, if any since this was last called
+    pub fn take_error(&mut self) -> Option<D::Error> {
+        return self.error.take();
+    }
+}
+
+impl<D: DrawTarget> Display for DrawTargetDisplay<D> {
+    fn show(&mut self, framebuffer: &Framebuffer) {
+        // The resolution can change as the rom runs (SUPER-CHIP), leaving the old screen
+        // showing around the edges of a smaller one
+        let size = (framebuffer.width(), framebuffer.height());
+        if self.size != Some(size) {
+            self.size = Some(size);
+            if let Err(e) = self.target.clear(self.off) {
+                self.error = Some(e);
+                return;
+            }
+        }
+
+        if let Err(e) = draw(framebuffer, &mut self.target, self.on, self.off) {
+            self.error = Some(e);
+        }
+    }
+}
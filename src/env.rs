@@ -0,0 +1,143 @@
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::persist;
+use crate::platform::Platform;
+
+// A headless environment for training agents to play roms, shaped after Gym's: reset()
+// starts an episode, and step() holds some keys down for a few frames and returns what the
+// screen looks like afterwards. The machine's random numbers come from a seed, so an agent
+// replaying the same actions from the same seed sees exactly the same episode.
+//
+// Actions are the held keys as a bitmask, bit n for key n, so 0x0012 holds 1 and 4.
+
+
+/// Called after every step with the machine, to say whether the episode is over
+pub type DoneFn = Box<dyn Fn(&Chip8) -> bool>;
+
+pub struct Env {
+    rom: Vec<u8>,
+    platform: Platform,
+    seed: u64,
+    /// How many frames each step holds the action for
+    frame_skip: usize,
+    /// Episodes are cut off after this many frames, if set
+    max_frames: Option<usize>,
+    /// Instructions a frame, the machine's default if it isn't set
+    speed: Option<usize>,
+    done_fn: Option<DoneFn>,
+    chip: Chip8,
+    frames: usize,
+    error: Option<Chip8Error>,
+}
+
+impl Env {
+    /// Makes an environment for the rom, ready to step without calling reset first
+    pub fn new(rom: &[u8], platform: Platform) -> Self {
+        let mut env = Self {
+            rom: rom.to_vec(),
+            platform,
+            seed: 0,
+            frame_skip: 1,
+            max_frames: None,
+            speed: None,
+            done_fn: None,
+            chip: Chip8::with_platform(platform, false),
+            frames: 0,
+            error: None,
+        };
+        env.reset();
+        return env;
+    }
+
+    pub fn with_frame_skip(mut self, frames: usize) -> Self {
+        self.frame_skip = frames.max(1);
+        return self;
+    }
+
+    pub fn with_max_frames(mut self, frames: usize) -> Self {
+        self.max_frames = Some(frames);
+        return self;
+    }
+
+    /// Runs this many instructions a frame, starting the episode over
+    pub fn with_speed(mut self, speed: usize) -> Self {
+        self.speed = Some(speed);
+        self.reset();
+        return self;
+    }
+
+    /// Ends episodes once done returns true, e.g. when the lives counter in memory hits 0
+    pub fn with_done(mut self, done: impl Fn(&Chip8) -> bool + 'static) -> Self {
+        self.done_fn = Some(Box::new(done));
+        return self;
+    }
+
+    /// Sets the seed for the next episodes, taking effect on the next reset
+    pub fn seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Starts a new episode from the rom's first instruction and returns the screen
+    pub fn reset(&mut self) -> Vec<u8> {
+        self.chip = Chip8::with_platform(self.platform, false);
+        self.chip.seed_rng(self.seed);
+        if let Some(speed) = self.speed {
+            self.chip.set_cycles_per_frame(speed);
+        }
+        self.chip.load_rom_bytes(&self.rom);
+        self.frames = 0;
+        self.error = None;
+        return self.observation();
+    }
+
+    /// Holds the keys in actions down for frame_skip frames, and returns the screen, a hash
+    /// of it, and whether the episode is over. Stepping a finished episode does nothing
+    pub fn step(&mut self, actions: u16) -> (Vec<u8>, u64, bool) {
+        for key in 0..16 {
+            self.chip.set_key(key, actions & (1 << key) != 0);
+        }
+
+        for _ in 0..self.frame_skip {
+            if self.done() {
+                break;
+            }
+            if let Err(e) = self.chip.run_frame() {
+                self.error = Some(e);
+            }
+            self.frames += 1;
+        }
+
+        let observation = self.observation();
+        let hash = persist::hash(&observation);
+        return (observation, hash, self.done());
+    }
+
+    /// Whether the episode is over, because the rom failed or exited, ran out of frames, or the
+    /// done function says so
+    pub fn done(&self) -> bool {
+        return self.error.is_some()
+            || self.chip.exit_requested()
+            || self.max_frames.is_some_and(|max| self.frames >= max)
+            || self.done_fn.as_ref().is_some_and(|done| done(&self.chip));
+    }
+
+    /// The error the rom stopped with, if it's failed
+    pub fn error(&self) -> Option<&Chip8Error> {
+        return self.error.as_ref();
+    }
+
+    /// Frames run since the last reset
+    pub fn frames(&self) -> usize {
+        return self.frames;
+    }
+
+    /// The machine, for reading scores and such out of memory
+    pub fn chip(&self) -> &Chip8 {
+        return &self.chip;
+    }
+
+    /// The screen, a byte per pixel row by row
+    fn observation(&self) -> Vec<u8> {
+        return self.chip.framebuffer().to_bytes();
+    }
+}
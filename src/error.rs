@@ -0,0 +1,63 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Errors the interpreter can hit while running a rom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// A CALL was made with all 16 stack levels already in use. call_trace holds the
+    /// return addresses that were on the stack, outermost call first
+    StackOverflow { pc: u16, call_trace: Vec<u16> },
+    /// A RET was made with nothing on the stack to return to
+    StackUnderflow { pc: u16 },
+    /// A SYS (0NNN) call into machine code was made with SysPolicy::Error set
+    MachineCodeCall { pc: u16, addr: u16 },
+    /// An instruction wrote into the interpreter area below the start of the rom (where
+    /// the font lives) with memory protection on
+    ProtectedWrite { pc: u16, addr: u32 },
+    /// An opcode that isn't an instruction on the platform, in strict mode
+    UnknownInstruction { pc: u16, opcode: u16 },
+    /// An instruction (or fetching one) reached past the end of memory in strict mode. addr is
+    /// the first address past the end it would have used
+    OutOfBounds { pc: u16, addr: u32 },
+}
+
+impl Chip8Error {
+    /// What kind of error it is, for grouping errors across roms
+    pub fn kind(&self) -> &'static str {
+        return match self {
+            Chip8Error::StackOverflow { .. } => "stack overflow",
+            Chip8Error::StackUnderflow { .. } => "stack underflow",
+            Chip8Error::MachineCodeCall { .. } => "machine code call",
+            Chip8Error::ProtectedWrite { .. } => "protected write",
+            Chip8Error::UnknownInstruction { .. } => "unknown instruction",
+            Chip8Error::OutOfBounds { .. } => "out of bounds",
+        };
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Chip8Error::StackOverflow { pc, call_trace } => {
+                write!(f, "Stack overflow at {pc:04X}, call trace (return addresses):")?;
+                for addr in call_trace {
+                    write!(f, " {addr:04X}")?;
+                }
+                Ok(())
+            },
+            Chip8Error::StackUnderflow { pc } => write!(f, "Stack underflow at {pc:04X}, RET with an empty stack"),
+            Chip8Error::MachineCodeCall { pc, addr } => {
+                write!(f, "SYS {addr:03X} at {pc:04X} calls a machine code routine, which can't be run")
+            },
+            Chip8Error::ProtectedWrite { pc, addr } => {
+                write!(f, "Write to protected address {addr:04X} at {pc:04X}, inside the interpreter/font area")
+            },
+            Chip8Error::UnknownInstruction { pc, opcode } => write!(f, "Unknown instruction {opcode:04X} at {pc:04X}"),
+            Chip8Error::OutOfBounds { pc, addr } => {
+                write!(f, "Memory access at {pc:04X} goes past the end of memory, to {addr:04X}")
+            },
+        };
+    }
+}
+
+impl core::error::Error for Chip8Error {}
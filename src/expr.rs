@@ -0,0 +1,392 @@
+use crate::chip::Chip8;
+
+// A small expression language for breakpoint conditions, e.g.
+//
+//   V3 == 20 && DT == 0
+//   opcode & 0F000 == 0D000
+//   [I] > 0x7F || PC == 2A4
+//   V[mem[I]] * 2
+//
+// Names are V0-VF, I, PC, SP (or stack_depth), DT, ST and opcode (the instruction at PC,
+// about to run). [addr] or mem[addr] reads the byte of memory at addr, and V[x] reads
+// register x, giving 0 past VF. Numbers are hex like everywhere else in the
+// debugger, with or without 0x, but one starting with a letter needs a leading 0 to tell it
+// apart from a name (0FF, not FF). Names are case insensitive.
+//
+// Values are u32 and arithmetic wraps. Comparisons and logic give 1 or 0, and anything not
+// 0 is true. Operators bind as tightly as in Rust, so & comes before ==, unlike C:
+//
+//   ! - ~         (unary)
+//   * / %         (dividing by 0 gives 0)
+//   + -
+//   << >>
+//   &
+//   ^
+//   |
+//   == != < <= > >=
+//   &&
+//   ||
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+    Opcode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unary {
+    Not,
+    Neg,
+    BitNot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Binary {
+    Mul, Div, Rem,
+    Add, Sub,
+    Shl, Shr,
+    BitAnd,
+    BitXor,
+    BitOr,
+    Eq, Ne, Lt, Le, Gt, Ge,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(u32),
+    Var(Var),
+    Memory(Box<Node>),
+    Register(Box<Node>),
+    Unary(Unary, Box<Node>),
+    Binary(Binary, Box<Node>, Box<Node>),
+}
+
+/// A parsed expression, evaluated against a machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenise(source)?;
+        let mut parser = Parser { tokens: &tokens, at: 0 };
+        let root = parser.expr(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(format!("unexpected '{token}' in '{source}'"));
+        }
+        return Ok(Self { source: source.trim().to_string(), root });
+    }
+
+    /// The expression as it was typed
+    pub fn source(&self) -> &str {
+        return &self.source;
+    }
+
+    pub fn eval(&self, chip: &Chip8) -> u32 {
+        return eval(&self.root, chip);
+    }
+
+    /// Whether the expression is true (not 0) for the machine as it is
+    pub fn test(&self, chip: &Chip8) -> bool {
+        return self.eval(chip) != 0;
+    }
+}
+
+/// An expression kept an eye on while the machine runs, remembering its last value to tell
+/// when it changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watch {
+    expr: Expr,
+    value: Option<u32>,
+}
+
+impl Watch {
+    pub fn new(expr: Expr) -> Self {
+        return Self { expr, value: None };
+    }
+
+    pub fn expr(&self) -> &Expr {
+        return &self.expr;
+    }
+
+    /// The value as of the last update, None before the first
+    pub fn value(&self) -> Option<u32> {
+        return self.value;
+    }
+
+    /// Evaluates the expression again, returning the value it had before if it's changed
+    pub fn update(&mut self, chip: &Chip8) -> Option<u32> {
+        let value = self.expr.eval(chip);
+        let before = self.value.replace(value);
+        return before.filter(|before| *before != value);
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(&self.source);
+    }
+}
+
+fn eval(node: &Node, chip: &Chip8) -> u32 {
+    let peek = |addr: u32| chip.read_mem(addr, 1).first().copied().unwrap_or(0) as u32;
+    return match node {
+        Node::Number(n) => *n,
+        Node::Var(var) => match var {
+            Var::V(x) => chip.registers()[*x as usize] as u32,
+            Var::I => chip.ar(),
+            Var::Pc => chip.pc() as u32,
+            Var::Sp => chip.sp() as u32,
+            Var::Dt => chip.delay() as u32,
+            Var::St => chip.sound() as u32,
+            Var::Opcode => peek(chip.pc() as u32) << 8 | peek(chip.pc() as u32 + 1),
+        },
+        Node::Memory(addr) => peek(eval(addr, chip)),
+        Node::Register(x) => chip.registers().get(eval(x, chip) as usize).copied().unwrap_or(0) as u32,
+        Node::Unary(op, operand) => {
+            let value = eval(operand, chip);
+            match op {
+                Unary::Not => (value == 0) as u32,
+                Unary::Neg => value.wrapping_neg(),
+                Unary::BitNot => !value,
+            }
+        },
+        // Evaluated lazily, so [I] in `I < 1000 && [I] == 0` is only read when it's in range
+        Node::Binary(Binary::And, a, b) => (eval(a, chip) != 0 && eval(b, chip) != 0) as u32,
+        Node::Binary(Binary::Or, a, b) => (eval(a, chip) != 0 || eval(b, chip) != 0) as u32,
+        Node::Binary(op, a, b) => {
+            let (a, b) = (eval(a, chip), eval(b, chip));
+            match op {
+                Binary::Mul => a.wrapping_mul(b),
+                Binary::Div => a.checked_div(b).unwrap_or(0),
+                Binary::Rem => a.checked_rem(b).unwrap_or(0),
+                Binary::Add => a.wrapping_add(b),
+                Binary::Sub => a.wrapping_sub(b),
+                Binary::Shl => a.checked_shl(b).unwrap_or(0),
+                Binary::Shr => a.checked_shr(b).unwrap_or(0),
+                Binary::BitAnd => a & b,
+                Binary::BitXor => a ^ b,
+                Binary::BitOr => a | b,
+                Binary::Eq => (a == b) as u32,
+                Binary::Ne => (a != b) as u32,
+                Binary::Lt => (a < b) as u32,
+                Binary::Le => (a <= b) as u32,
+                Binary::Gt => (a > b) as u32,
+                Binary::Ge => (a >= b) as u32,
+                Binary::And | Binary::Or => unreachable!("handled lazily above"),
+            }
+        },
+    };
+}
+
+/// Splits the source into numbers, names and operators
+fn tokenise(source: &str) -> Result<Vec<String>, String> {
+    const OPERATORS: [&str; 24] = [
+        "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+        "<", ">", "+", "-", "*", "/", "%", "&", "^", "|", "!", "~", "(", ")", "[", "]",
+    ];
+
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphanumeric() || c == '_' {
+            rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len())
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            op.len()
+        } else {
+            return Err(format!("unexpected '{c}' in '{source}'"));
+        };
+        tokens.push(rest[..len].to_string());
+        rest = rest[len..].trim_start();
+    }
+    return Ok(tokens);
+}
+
+/// Operators that take two operands, with how tightly they bind (higher first)
+fn binary(token: &str) -> Option<(Binary, u8)> {
+    return Some(match token {
+        "*" => (Binary::Mul, 9),
+        "/" => (Binary::Div, 9),
+        "%" => (Binary::Rem, 9),
+        "+" => (Binary::Add, 8),
+        "-" => (Binary::Sub, 8),
+        "<<" => (Binary::Shl, 7),
+        ">>" => (Binary::Shr, 7),
+        "&" => (Binary::BitAnd, 6),
+        "^" => (Binary::BitXor, 5),
+        "|" => (Binary::BitOr, 4),
+        "==" => (Binary::Eq, 3),
+        "!=" => (Binary::Ne, 3),
+        "<" => (Binary::Lt, 3),
+        "<=" => (Binary::Le, 3),
+        ">" => (Binary::Gt, 3),
+        ">=" => (Binary::Ge, 3),
+        "&&" => (Binary::And, 2),
+        "||" => (Binary::Or, 1),
+        _ => return None,
+    });
+}
+
+struct Parser<'a> {
+    tokens: &'a [String],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        return self.tokens.get(self.at).map(String::as_str);
+    }
+
+    fn next(&mut self) -> Result<&str, String> {
+        let token = self.tokens.get(self.at).ok_or("the expression ends too soon")?;
+        self.at += 1;
+        return Ok(token);
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        return match self.next()? {
+            token if token == expected => Ok(()),
+            token => Err(format!("expected '{expected}' but found '{token}'")),
+        };
+    }
+
+    /// Parses operators binding at least as tightly as min, by precedence climbing
+    fn expr(&mut self, min: u8) -> Result<Node, String> {
+        let mut left = self.unary()?;
+        while let Some((op, precedence)) = self.peek().and_then(binary) {
+            if precedence < min {
+                break;
+            }
+            self.at += 1;
+            // Everything is left associative, so the right side only takes tighter operators
+            let right = self.expr(precedence + 1)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        return Ok(left);
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        let token = self.next()?.to_string();
+        let op = match token.as_str() {
+            "!" => Unary::Not,
+            "-" => Unary::Neg,
+            "~" => Unary::BitNot,
+            "(" => {
+                let inner = self.expr(0)?;
+                self.expect(")")?;
+                return Ok(inner);
+            },
+            "[" => {
+                let addr = self.expr(0)?;
+                self.expect("]")?;
+                return Ok(Node::Memory(Box::new(addr)));
+            },
+            // mem[addr] and V[x], which need telling apart from names before they're read as one
+            name if self.peek() == Some("[") && ["MEM", "V"].contains(&name.to_ascii_uppercase().as_str()) => {
+                self.at += 1;
+                let inner = Box::new(self.expr(0)?);
+                self.expect("]")?;
+                return Ok(if name.eq_ignore_ascii_case("V") { Node::Register(inner) } else { Node::Memory(inner) });
+            },
+            _ => return atom(&token),
+        };
+        return Ok(Node::Unary(op, Box::new(self.unary()?)));
+    }
+}
+
+/// A number or a name
+fn atom(token: &str) -> Result<Node, String> {
+    if token.starts_with(|c: char| c.is_ascii_digit()) {
+        let digits = token.trim_start_matches("0x").trim_start_matches("0X");
+        let digits = if digits.is_empty() { "0" } else { digits };
+        return u32::from_str_radix(digits, 16).map(Node::Number).map_err(|_| format!("invalid number '{token}'"));
+    }
+
+    let var = match token.to_ascii_uppercase().as_str() {
+        "I" => Var::I,
+        "PC" => Var::Pc,
+        "SP" | "STACK_DEPTH" => Var::Sp,
+        "DT" => Var::Dt,
+        "ST" => Var::St,
+        "OPCODE" | "OP" => Var::Opcode,
+        name if name.len() == 2 && name.starts_with('V') => match u8::from_str_radix(&name[1..], 16) {
+            Ok(x) => Var::V(x),
+            Err(_) => return Err(format!("unknown name '{token}'")),
+        },
+        _ if token.chars().all(|c| c.is_ascii_hexdigit()) => {
+            return Err(format!("unknown name '{token}', write numbers starting with a letter with a leading 0 (0{token})"));
+        },
+        _ => return Err(format!("unknown name '{token}'")),
+    };
+    return Ok(Node::Var(var));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+
+    /// A CHIP-8 machine with V3 at 20, I at 300 and 0x80 there, about to run 6A12
+    fn machine() -> Chip8 {
+        let mut chip = Chip8::with_platform(Platform::Chip8, false);
+        chip.load_rom_bytes(&[0xA3, 0x00, 0x6A, 0x12]);
+        chip.execute().expect("ANNN runs");
+        chip.set_register(3, 0x20);
+        chip.write_mem(0x300, &[0x80]);
+        return chip;
+    }
+
+    fn eval(source: &str) -> u32 {
+        return Expr::parse(source).expect("the expression parses").eval(&machine());
+    }
+
+    #[test]
+    fn expressions_read_the_machine_and_bind_as_in_rust() {
+        assert!(Expr::parse("V3 == 20 && DT == 0").unwrap().test(&machine()));
+        assert_eq!(eval("opcode & 0F000 == 6000"), 1);
+        assert_eq!(eval("[I] > 0x7F || PC == 2A4"), 1);
+        assert_eq!(eval("mem[I] + v[3]"), 0xA0);
+        assert_eq!(eval("V[0F0]"), 0);
+        assert_eq!((eval("pc"), eval("i"), eval("sp"), eval("op")), (0x202, 0x300, 0, 0x6A12));
+        // Precedence and associativity
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("10 - 4 - 2"), 0xA);
+        assert_eq!(eval("1 << 4 | 1"), 0x11);
+        // Wrapping, and dividing by 0 gives 0
+        assert_eq!(eval("0 - 1"), u32::MAX);
+        assert_eq!((eval("5 / 0"), eval("5 % 0"), eval("1 << 20")), (0, 0, 0));
+        assert_eq!((eval("!0"), eval("!7"), eval("-1"), eval("~0")), (1, 0, u32::MAX, u32::MAX));
+        assert_eq!(Expr::parse("  V3  ==  20 ").unwrap().source(), "V3  ==  20");
+    }
+
+    #[test]
+    fn malformed_expressions_are_errors() {
+        for source in ["", "V3 ==", "(V0", "[I", "V[1", "1 2", ")", "V3 $ 2", "VG", "V10", "foo", "0xZZ", "opcode & F000"] {
+            assert!(Expr::parse(source).is_err(), "'{source}' parsed");
+        }
+        // A number starting with a letter needs a leading 0, which the error says
+        assert!(Expr::parse("FF").unwrap_err().contains("0FF"));
+        assert_eq!(Expr::parse("0FF").map(|expr| expr.root), Ok(Node::Number(0xFF)));
+    }
+
+    #[test]
+    fn a_watch_tells_when_its_value_changes() {
+        let mut chip = machine();
+        let mut watch = Watch::new(Expr::parse("VA").unwrap());
+        assert_eq!((watch.update(&chip), watch.value()), (None, Some(0)));
+        assert_eq!(watch.update(&chip), None);
+        chip.execute().expect("6A12 runs");
+        assert_eq!((watch.update(&chip), watch.value()), (Some(0), Some(0x12)));
+    }
+}
@@ -0,0 +1,150 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+
+use crate::chip::Chip8;
+use crate::platform::Platform;
+
+// A C API for embedding the emulator from other languages. Build the shared library with
+// `cargo rustc --release --lib --crate-type cdylib --features ffi`, which also writes the
+// header for it to include/chip8.h with cbindgen.
+//
+// A chip8_t is made with chip8_new and freed with chip8_free, and every other function takes
+// one. Nothing here is thread safe, each chip8_t must only be used from one thread at a time.
+
+
+/// The machine behind a chip8_t, along with the message of the last error it stopped with
+pub struct Chip8Handle {
+    chip: Chip8,
+    error: Option<CString>,
+    /// The screen a byte a pixel, as chip8_framebuffer last unpacked it
+    screen: RefCell<Vec<u8>>,
+}
+
+impl Chip8Handle {
+    fn run(&mut self, f: impl FnOnce(&mut Chip8) -> Result<(), crate::error::Chip8Error>) -> bool {
+        return match f(&mut self.chip) {
+            Ok(()) => true,
+            Err(e) => {
+                self.error = CString::new(e.to_string()).ok();
+                false
+            },
+        };
+    }
+}
+
+/// Makes a machine for the named platform ("chip8", "superchip", "xochip" and so on, as for
+/// --platform), or plain CHIP-8 if platform is null. Returns null if the platform isn't known
+///
+/// # Safety
+/// platform must be null or a nul terminated string
+#[no_mangle]
+pub unsafe extern "C" fn chip8_new(platform: *const c_char) -> *mut Chip8Handle {
+    let platform = if platform.is_null() {
+        Some(Platform::Chip8)
+    } else {
+        CStr::from_ptr(platform).to_str().ok().and_then(Platform::from_name)
+    };
+    let Some(platform) = platform else {
+        return std::ptr::null_mut();
+    };
+    return Box::into_raw(Box::new(Chip8Handle { chip: Chip8::with_platform(platform, false), error: None, screen: RefCell::default() }));
+}
+
+/// Frees a machine made by chip8_new. Does nothing if chip is null
+///
+/// # Safety
+/// chip must be null or from chip8_new, and not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip: *mut Chip8Handle) {
+    if !chip.is_null() {
+        drop(Box::from_raw(chip));
+    }
+}
+
+/// Loads len bytes of rom into memory at the platform's start address
+///
+/// # Safety
+/// chip must be from chip8_new, and rom must point to len bytes
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip: *mut Chip8Handle, rom: *const u8, len: usize) {
+    (*chip).chip.load_rom_bytes(std::slice::from_raw_parts(rom, len));
+}
+
+/// Runs a single instruction. Returns false if it failed, see chip8_error
+///
+/// # Safety
+/// chip must be from chip8_new
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip: *mut Chip8Handle) -> bool {
+    return (*chip).run(|chip| chip.execute());
+}
+
+/// Runs one 60th of a second and ticks the timers. Returns false if an instruction failed,
+/// see chip8_error
+///
+/// # Safety
+/// chip must be from chip8_new
+#[no_mangle]
+pub unsafe extern "C" fn chip8_run_frame(chip: *mut Chip8Handle) -> bool {
+    return (*chip).run(|chip| chip.run_frame());
+}
+
+/// Counts the delay and sound timers down, for callers stepping by instruction
+///
+/// # Safety
+/// chip must be from chip8_new
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip: *mut Chip8Handle) {
+    (*chip).chip.tick_timers();
+}
+
+/// The message of the last error an instruction failed with, or null if none has. It's
+/// valid until the next call that runs instructions
+///
+/// # Safety
+/// chip must be from chip8_new
+#[no_mangle]
+pub unsafe extern "C" fn chip8_error(chip: *const Chip8Handle) -> *const c_char {
+    return match &(*chip).error {
+        Some(error) => error.as_ptr(),
+        None => std::ptr::null(),
+    };
+}
+
+/// The screen, a byte per pixel row by row, with its size written to width and height. The
+/// resolution can change as the rom runs, and the pointer is only valid until the next call
+/// to chip8_framebuffer or chip8_free
+///
+/// # Safety
+/// chip must be from chip8_new, and width and height must each be null or writable
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip: *const Chip8Handle, width: *mut usize, height: *mut usize) -> *const u8 {
+    let framebuffer = (*chip).chip.framebuffer();
+    if !width.is_null() {
+        *width = framebuffer.width();
+    }
+    if !height.is_null() {
+        *height = framebuffer.height();
+    }
+    let mut screen = (*chip).screen.borrow_mut();
+    *screen = framebuffer.to_bytes();
+    return screen.as_ptr();
+}
+
+/// Presses or releases a keypad key, 0 to F
+///
+/// # Safety
+/// chip must be from chip8_new
+#[no_mangle]
+pub unsafe extern "C" fn chip8_key(chip: *mut Chip8Handle, key: u8, pressed: bool) {
+    (*chip).chip.set_key(key & 0xF, pressed);
+}
+
+/// Whether the sound timer is running, i.e. whether to beep
+///
+/// # Safety
+/// chip must be from chip8_new
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound(chip: *const Chip8Handle) -> bool {
+    return (*chip).chip.sound() > 0;
+}
@@ -0,0 +1,167 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::isa;
+use crate::platform::Platform;
+
+// Control flow through a rom: which of its bytes are instructions the program can actually
+// reach, found by following it from where it starts the way it would run rather than
+// decoding every two bytes, so the sprites and tables mixed in with the code are left out.
+// Jumps and calls are followed, skips go both ways and returns end a path. There's no telling
+// statically where BNNN's computed jumps go, so they're followed to the address they add V0
+// to, and when that's a table of jumps, the usual way they're used, to every jump in it. Like
+// the analysis it's a hint: code only reached through a computed jump any other way is missed.
+//
+// Hires roms start by jumping to the 1802 patch at 0x260, which isn't CHIP-8, so they're
+// followed from 0x2C0 where the patch hands over, as the interpreter runs them.
+
+
+/// The instructions reachable in a rom, and how they're reached
+pub struct Flow {
+    /// Where the rom's loaded, and where its last byte ends
+    pub start: u16,
+    pub end: u16,
+    /// Where the program starts running
+    pub entry: u16,
+    /// Every instruction reachable, by address
+    pub code: BTreeMap<u16, u16>,
+    /// The instruction each one was first reached from, all but the entry point's
+    pub from: BTreeMap<u16, u16>,
+    /// Where each run of instructions that's only entered at the top starts: the entry point,
+    /// every jump and call target, where calls come back to and both sides of a skip
+    pub blocks: BTreeSet<u16>,
+    /// The targets of 2NNN
+    pub subroutines: BTreeSet<u16>,
+    /// The targets of 1NNN
+    pub labels: BTreeSet<u16>,
+    /// The BNNN instructions, whose targets depend on V0 and are only followed as far as the
+    /// address it's added to and the jumps in a table there
+    pub computed: BTreeSet<u16>,
+    /// Reached opcodes nothing on the platform decodes, which end their path
+    pub unknown: BTreeSet<u16>,
+    /// Addresses outside the rom the program would run, with the instruction that leads there
+    pub outside: Vec<(u16, u16)>,
+}
+
+/// Follows the rom from where it starts as it would run on the platform
+pub fn trace(rom: &[u8], platform: Platform) -> Flow {
+    let start = platform.start_address();
+    let end = start.saturating_add(rom.len().min(u16::MAX as usize) as u16);
+    let entry = if platform == Platform::HiresChip8 && rom.starts_with(&[0x12, 0x60]) { 0x2C0 } else { start };
+    let mut flow = Flow {
+        start,
+        end,
+        entry,
+        code: BTreeMap::new(),
+        from: BTreeMap::new(),
+        blocks: BTreeSet::from([entry]),
+        subroutines: BTreeSet::new(),
+        labels: BTreeSet::new(),
+        computed: BTreeSet::new(),
+        unknown: BTreeSet::new(),
+        outside: Vec::new(),
+    };
+
+    let mut queue = vec![(entry, None)];
+    while let Some((addr, from)) = queue.pop() {
+        if flow.code.contains_key(&addr) {
+            continue;
+        }
+        let Some(opcode) = flow.opcode(rom, addr) else {
+            if let Some(from) = from {
+                flow.outside.push((from, addr));
+            }
+            continue;
+        };
+        flow.code.insert(addr, opcode);
+        if let Some(from) = from {
+            flow.from.insert(addr, from);
+        }
+
+        let Some(info) = isa::lookup(opcode, platform) else {
+            flow.unknown.insert(addr);
+            continue;
+        };
+        let (to, call) = flow.next(rom, platform, addr, opcode);
+        match info.mnemonic {
+            "JP addr" => {
+                flow.labels.insert(opcode & 0xFFF);
+            },
+            "JP V0, addr" => {
+                flow.computed.insert(addr);
+            },
+            _ => {},
+        }
+        // Going anywhere but on to the next instruction starts a block
+        let after = addr.wrapping_add(isa::length(opcode, platform) as u16);
+        if to != [after] || call.is_some() {
+            flow.blocks.extend(&to);
+        }
+        if let Some(call) = call {
+            flow.subroutines.insert(call);
+            flow.blocks.insert(call);
+        }
+        queue.extend(to.into_iter().chain(call).map(|to| (to, Some(addr))));
+    }
+    flow.outside.sort();
+    return flow;
+}
+
+/// Whether the instruction skips the next one on a condition
+pub fn is_skip(info: &isa::OpcodeInfo) -> bool {
+    return matches!(info.mnemonic.split(' ').next(), Some("SE" | "SNE" | "SKP" | "SKNP" | "SKP2" | "SKNP2"));
+}
+
+impl Flow {
+    /// Where the program can go from an instruction: the instructions that can run after it,
+    /// and the subroutine it calls if it's a call, which comes back to the instruction after
+    pub fn next(&self, rom: &[u8], platform: Platform, addr: u16, opcode: u16) -> (Vec<u16>, Option<u16>) {
+        let after = addr.wrapping_add(isa::length(opcode, platform) as u16);
+        let Some(info) = isa::lookup(opcode, platform) else {
+            return (Vec::new(), None);
+        };
+        let target = opcode & 0xFFF;
+        return match info.mnemonic {
+            "RET" | "EXIT" => (Vec::new(), None),
+            "JP addr" => (vec![target], None),
+            "CALL addr" => (vec![after], Some(target)),
+            "JP V0, addr" => {
+                let mut to = vec![target];
+                let mut entry = target;
+                while self.opcode(rom, entry).is_some_and(|opcode| opcode & 0xF000 == 0x1000) {
+                    if entry != target {
+                        to.push(entry);
+                    }
+                    entry += 2;
+                }
+                (to, None)
+            },
+            _ if is_skip(info) => {
+                // Skipping over XO-CHIP's F000 NNNN skips all 4 bytes of it
+                let skipped = match self.opcode(rom, after) {
+                    Some(skipped) => after.wrapping_add(isa::length(skipped, platform) as u16),
+                    None => after.wrapping_add(2),
+                };
+                (vec![after, skipped], None)
+            },
+            _ => (vec![after], None),
+        };
+    }
+
+    /// The opcode at addr, if both its bytes are in the rom
+    pub fn opcode(&self, rom: &[u8], addr: u16) -> Option<u16> {
+        if addr < self.start || addr as u32 + 2 > self.end as u32 {
+            return None;
+        }
+        let i = (addr - self.start) as usize;
+        return Some((rom[i] as u16) << 8 | rom[i + 1] as u16);
+    }
+
+    /// Whether the byte at addr is part of a reachable instruction
+    pub fn is_code(&self, addr: u16, platform: Platform) -> bool {
+        return self
+            .code
+            .range(..=addr)
+            .next_back()
+            .is_some_and(|(at, opcode)| (addr as u32) < *at as u32 + isa::length(*opcode, platform) as u32);
+    }
+}
@@ -0,0 +1,370 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// The VIP colour board used by CHIP-8X doesn't colour single pixels. The screen is split
+// into zones 8 pixels wide and 4 high, each with its own foreground colour, and every pixel
+// that's off shows the one background colour. The colours are the board's fixed set:
+//
+//   foreground  0 black  1 red  2 blue  3 violet  4 green  5 yellow  6 aqua  7 white
+//   background  0 blue   1 black  2 green  3 red
+
+/// The foreground colours as 0xRRGGBB
+pub const FOREGROUND_COLOURS: [u32; 8] = [
+    0x000000, 0xFF0000, 0x0000FF, 0xFF00FF, 0x00FF00, 0xFFFF00, 0x00FFFF, 0xFFFFFF,
+];
+
+/// The background colours as 0xRRGGBB
+pub const BACKGROUND_COLOURS: [u32; 4] = [0x000080, 0x000000, 0x008000, 0x800000];
+
+pub const ZONE_WIDTH: usize = 8;
+pub const ZONE_HEIGHT: usize = 4;
+
+
+/// The screen of the interpreter, stored row by row a bit a pixel, eight to a byte with the
+/// first in the top bit, so a 64x32 screen is 256 bytes. On the classic platforms a pixel is
+/// either 0 (off) or 1 (on). In Mega-Chip mode it's an index into the Mega-Chip palette, and
+/// once a pixel's set to more than 1 the screen's kept a byte a pixel until it's resized.
+/// Frontends that want a byte a pixel, and the C API, get one from to_bytes
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    /// Whether the pixels are a byte each rather than a bit
+    colour: bool,
+    pixels: Vec<u8>,
+    colour_zones: Option<ColourZones>,
+}
+
+impl Clone for Framebuffer {
+    fn clone(&self) -> Self {
+        return Self {
+            width: self.width,
+            height: self.height,
+            colour: self.colour,
+            pixels: self.pixels.clone(),
+            colour_zones: self.colour_zones.clone(),
+        };
+    }
+
+    /// Copies the screen over without making new room for it, which is what the end of every
+    /// frame does to the front buffer
+    fn clone_from(&mut self, source: &Self) {
+        self.width = source.width;
+        self.height = source.height;
+        self.colour = source.colour;
+        self.pixels.clone_from(&source.pixels);
+        self.colour_zones.clone_from(&source.colour_zones);
+    }
+}
+
+/// The per-zone colours of a CHIP-8X screen, see the top of this file
+pub struct ColourZones {
+    background: u8,
+    zones: Vec<u8>,
+    columns: usize,
+}
+
+impl Clone for ColourZones {
+    fn clone(&self) -> Self {
+        return Self { background: self.background, zones: self.zones.clone(), columns: self.columns };
+    }
+
+    /// Copies the zones over without making new room for them
+    fn clone_from(&mut self, source: &Self) {
+        self.background = source.background;
+        self.zones.clone_from(&source.zones);
+        self.columns = source.columns;
+    }
+}
+
+impl ColourZones {
+    fn new(width: usize, height: usize) -> Self {
+        let columns = width.div_ceil(ZONE_WIDTH);
+        let rows = height.div_ceil(ZONE_HEIGHT);
+        // The colour board powers up with every zone red
+        return Self {
+            background: 0,
+            zones: vec![1; columns * rows],
+            columns,
+        };
+    }
+
+    /// The background colour, an index into BACKGROUND_COLOURS
+    pub fn background(&self) -> u8 {
+        return self.background;
+    }
+
+    /// Moves on to the next of the 4 background colours
+    pub fn step_background(&mut self) {
+        self.background = (self.background + 1) % BACKGROUND_COLOURS.len() as u8;
+    }
+
+    /// The foreground colour of the zone the pixel x, y is in, an index into FOREGROUND_COLOURS
+    pub fn zone_colour(&self, x: usize, y: usize) -> u8 {
+        let at = (y / ZONE_HEIGHT) * self.columns + x / ZONE_WIDTH;
+        return self.zones.get(at).copied().unwrap_or(0);
+    }
+
+    /// Sets the foreground colour of the zone at column zx and row zy (in zones, not pixels)
+    pub fn set_zone_colour(&mut self, zx: usize, zy: usize, colour: u8) {
+        if zx < self.columns {
+            if let Some(zone) = self.zones.get_mut(zy * self.columns + zx) {
+                *zone = colour % FOREGROUND_COLOURS.len() as u8;
+            }
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        return Self {
+            width,
+            height,
+            colour: false,
+            pixels: vec![0; (width * height).div_ceil(8)],
+            colour_zones: None,
+        };
+    }
+
+    /// Creates a screen with CHIP-8X colour zones
+    pub fn with_colour_zones(width: usize, height: usize) -> Self {
+        let mut framebuffer = Self::new(width, height);
+        framebuffer.colour_zones = Some(ColourZones::new(width, height));
+        return framebuffer;
+    }
+
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    pub fn height(&self) -> usize {
+        return self.height;
+    }
+
+    /// The pixels as they're stored: a bit each, see Framebuffer, unless is_colour
+    pub fn bytes(&self) -> &[u8] {
+        return &self.pixels;
+    }
+
+    /// Whether the pixels are stored a byte each, as a Mega-Chip screen drawn in colour is
+    pub fn is_colour(&self) -> bool {
+        return self.colour;
+    }
+
+    /// All the pixels a byte each, row by row from the top left
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.colour {
+            return self.pixels.clone();
+        }
+        return (0..self.width * self.height).map(|i| self.pixels[i / 8] >> (7 - i % 8) & 1).collect();
+    }
+
+    /// How many pixels are on
+    pub fn lit(&self) -> usize {
+        if self.colour {
+            return self.pixels.iter().filter(|&&pixel| pixel != 0).count();
+        }
+        return self.pixels.iter().map(|byte| byte.count_ones() as usize).sum();
+    }
+
+    /// The zone colours, only there on CHIP-8X
+    pub fn colour_zones(&self) -> Option<&ColourZones> {
+        return self.colour_zones.as_ref();
+    }
+
+    pub(crate) fn colour_zones_mut(&mut self) -> Option<&mut ColourZones> {
+        return self.colour_zones.as_mut();
+    }
+
+    /// The colour the pixel at x, y shows as 0xRRGGBB. Without colour zones pixels are
+    /// white when on and black when off
+    pub fn rgb(&self, x: usize, y: usize) -> u32 {
+        let on = self.get(x, y) != 0;
+        return match &self.colour_zones {
+            Some(zones) if on => FOREGROUND_COLOURS[zones.zone_colour(x, y) as usize],
+            Some(zones) => BACKGROUND_COLOURS[zones.background() as usize],
+            None if on => 0xFFFFFF,
+            None => 0x000000,
+        };
+    }
+
+    /// Gets the pixel at x, y, or 0 if it's off the screen
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let i = y * self.width + x;
+        if self.colour {
+            return self.pixels[i];
+        }
+        return self.pixels[i / 8] >> (7 - i % 8) & 1;
+    }
+
+    /// Sets the pixel at x, y, ignoring anything off the screen
+    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        if value > 1 && !self.colour {
+            self.pixels = self.to_bytes();
+            self.colour = true;
+        }
+        let i = y * self.width + x;
+        match self.colour {
+            true => self.pixels[i] = value,
+            false => self.pixels[i / 8] = self.pixels[i / 8] & !(0x80 >> (i % 8)) | value << (7 - i % 8),
+        }
+    }
+
+    /// Turns the pixel at x, y on if it's off and off if it's on, returning whether it was on.
+    /// This is what drawing a sprite does to each of its pixels
+    pub fn flip(&mut self, x: usize, y: usize) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let i = y * self.width + x;
+        if self.colour {
+            let on = self.pixels[i] == 1;
+            self.pixels[i] ^= 1;
+            return on;
+        }
+        let bit = 0x80 >> (i % 8);
+        let on = self.pixels[i / 8] & bit != 0;
+        self.pixels[i / 8] ^= bit;
+        return on;
+    }
+
+    /// The screen as text, a line a row with on for pixels that are on and off for the rest.
+    /// For asserting on what's drawn in headless tests, or pasting a screen into an issue
+    pub fn to_ascii(&self, on: char, off: char) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+        for y in 0..self.height {
+            text.extend((0..self.width).map(|x| if self.get(x, y) != 0 { on } else { off }));
+            text.push('\n');
+        }
+        return text;
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+    }
+
+    /// Moves everything on the screen right by dx and down by dy pixels (left and up when
+    /// negative). What goes off the edge is lost and what's uncovered is off
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        // Each pixel's moved from one before it when scrolling right or down, so those go
+        // from the end to be read before they're written over, and the rest from the start
+        let backwards = dy > 0 || (dy == 0 && dx > 0);
+        for i in 0..width * height {
+            let i = if backwards { width * height - 1 - i } else { i };
+            let (x, y) = (i % width, i / width);
+            let (from_x, from_y) = (x - dx, y - dy);
+            let on_screen = (0..width).contains(&from_x) && (0..height).contains(&from_y);
+            let pixel = if on_screen { self.get(from_x as usize, from_y as usize) } else { 0 };
+            self.set(x as usize, y as usize, pixel);
+        }
+    }
+
+    /// Changes the resolution, which also clears the screen, goes back to a bit a pixel and
+    /// resets any colour zones
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.colour = false;
+        self.pixels.clear();
+        self.pixels.resize((width * height).div_ceil(8), 0);
+        if self.colour_zones.is_some() {
+            self.colour_zones = Some(ColourZones::new(width, height));
+        }
+    }
+
+    /// Roughly how many bytes it takes up
+    pub fn size(&self) -> usize {
+        return core::mem::size_of::<Self>()
+            + self.pixels.len()
+            + self.colour_zones.as_ref().map_or(0, |zones| zones.zones.len());
+    }
+
+    /// Sets every pixel from a bit each, packed as they're stored
+    pub(crate) fn set_bits(&mut self, bits: &[u8]) {
+        self.colour = false;
+        self.pixels.clear();
+        self.pixels.extend_from_slice(bits);
+        self.pixels.resize((self.width * self.height).div_ceil(8), 0);
+    }
+
+    /// Sets every pixel from a byte each, keeping them a bit each if they're all 0 or 1
+    pub(crate) fn set_bytes(&mut self, pixels: &[u8]) {
+        let len = self.width * self.height;
+        if pixels.iter().all(|&pixel| pixel <= 1) {
+            self.colour = false;
+            self.pixels.clear();
+            self.pixels.resize(len.div_ceil(8), 0);
+            for (i, &pixel) in pixels.iter().take(len).enumerate() {
+                self.pixels[i / 8] |= pixel << (7 - i % 8);
+            }
+        } else {
+            self.colour = true;
+            self.pixels.clear();
+            self.pixels.extend_from_slice(pixels);
+            self.pixels.resize(len, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixels_are_kept_a_bit_each_until_one_has_a_colour() {
+        let mut framebuffer = Framebuffer::new(64, 32);
+        assert_eq!(framebuffer.bytes().len(), 256);
+        framebuffer.set(9, 0, 1);
+        assert_eq!(framebuffer.bytes()[1], 0b0100_0000);
+        assert!(framebuffer.flip(9, 0));
+        assert!(!framebuffer.flip(10, 0));
+        assert_eq!((framebuffer.get(9, 0), framebuffer.get(10, 0)), (0, 1));
+        // Off the screen is off, and drawing there does nothing
+        assert!(!framebuffer.flip(64, 0));
+        assert_eq!(framebuffer.get(0, 32), 0);
+
+        framebuffer.set(3, 1, 0xC4);
+        assert!(framebuffer.is_colour());
+        assert_eq!(framebuffer.bytes().len(), 64 * 32);
+        assert_eq!((framebuffer.get(10, 0), framebuffer.get(3, 1), framebuffer.lit()), (1, 0xC4, 2));
+        framebuffer.resize(64, 32);
+        assert!(!framebuffer.is_colour());
+        assert_eq!((framebuffer.bytes().len(), framebuffer.lit()), (256, 0));
+    }
+
+    #[test]
+    fn scrolling_moves_every_pixel_and_clears_behind_it() {
+        let mut framebuffer = Framebuffer::new(16, 4);
+        framebuffer.set(0, 0, 1);
+        framebuffer.set(15, 3, 1);
+        framebuffer.scroll(4, 1);
+        assert_eq!(framebuffer.to_ascii('#', '.'), "................\n....#...........\n................\n................\n");
+        framebuffer.scroll(-4, -1);
+        assert_eq!(framebuffer.to_ascii('#', '.'), "#...............\n................\n................\n................\n");
+        framebuffer.scroll(-1, 0);
+        assert_eq!(framebuffer.lit(), 0);
+    }
+
+    #[test]
+    fn bytes_come_back_as_they_went_in() {
+        let mut framebuffer = Framebuffer::new(8, 2);
+        let pixels = [1, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        framebuffer.set_bytes(&pixels);
+        assert_eq!(framebuffer.bytes(), &[0b1001_1000, 0b0000_0001]);
+        assert_eq!(framebuffer.to_bytes(), pixels);
+        framebuffer.set_bits(&[0xFF]);
+        assert_eq!(framebuffer.to_bytes(), [[1; 8], [0; 8]].concat());
+
+        let colours = [0, 7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 200];
+        framebuffer.set_bytes(&colours);
+        assert!(framebuffer.is_colour());
+        assert_eq!(framebuffer.to_bytes(), colours);
+    }
+}
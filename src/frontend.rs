@@ -0,0 +1,25 @@
+// The lightweight frontends that just play a rom in a window (or the terminal), each behind
+// the feature of the same name. The egui frontend with the debugger panels is in gui.rs.
+// They all pace frames with limiter.rs, and Tab cycles through the limits while playing.
+// driver.rs has the loop the terminal and minifb ones hand their frames to, while pixels
+// runs its frames from winit's event loop. controls.rs has the hotkeys they share for
+// pausing and, where there's sound, muting and the volume, input.rs which keys are whose in
+// two player games, and rotation.rs turns the screen for games made for a display on its
+// side. filter.rs has the scaling filters the GPU backed ones (and the egui frontend) can
+// show the screen through, playlist.rs is kiosk mode, playing through a list of roms, and
+// settings.rs is the pause menu for changing how a rom plays while it's running.
+
+pub mod controls;
+pub mod driver;
+pub mod filter;
+pub mod input;
+pub mod limiter;
+#[cfg(feature = "minifb")]
+pub mod minifb;
+#[cfg(feature = "pixels")]
+pub mod pixels;
+pub mod playlist;
+pub mod rotation;
+pub mod settings;
+#[cfg(feature = "terminal")]
+pub mod terminal;
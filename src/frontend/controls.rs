@@ -0,0 +1,211 @@
+use crate::audio::Beep;
+use crate::chip::Chip8;
+use super::input::{self, InputProfile, Keypad, Port};
+use super::rotation::Rotation;
+use super::settings::Palette;
+
+// The hotkeys every frontend has on top of the keypad, and pausing while the window isn't
+// focused so a game doesn't carry on (or keep beeping) behind other windows:
+//
+//   M        mute
+//   - =      volume down and up
+//   P        pause
+//
+// Pausing on focus loss can be turned off with `pause_on_focus_loss = false` in the rom's
+// profile. Being paused silences the sound as well as stopping the machine. The windowed
+// frontends make no sound, so they only offer P, and their status leaves the volume out.
+//
+// They also carry how far the rom wants the screen turned (see rotation.rs), which keys are
+// whose (see input.rs) and the palette (see settings.rs), since like the beep they come from
+// the rom's profile and every frontend needs them. CHIP-8X's I/O port (see input.rs) is kept
+// here too, so every frontend can connect it.
+
+/// How far the volume hotkeys move the volume, in percent
+const VOLUME_STEP: u8 = 5;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    Mute,
+    VolumeDown,
+    VolumeUp,
+    Pause,
+}
+
+impl Hotkey {
+    /// The hotkey a character typed is, if it's one
+    pub fn from_char(c: char) -> Option<Self> {
+        return match c.to_ascii_lowercase() {
+            'm' => Some(Self::Mute),
+            '-' => Some(Self::VolumeDown),
+            '=' | '+' => Some(Self::VolumeUp),
+            'p' => Some(Self::Pause),
+            _ => None,
+        };
+    }
+}
+
+/// The state the hotkeys and focus changes control
+pub struct Controls {
+    beep: Beep,
+    muted: bool,
+    paused: bool,
+    unfocused: bool,
+    pause_on_focus_loss: bool,
+    rotation: Rotation,
+    input: &'static InputProfile,
+    palette: Palette,
+    silent: bool,
+    port: Port,
+}
+
+impl Controls {
+    /// Starts with the rom's beep, unmuted and running
+    pub fn new(beep: Beep, pause_on_focus_loss: bool) -> Self {
+        return Self {
+            beep,
+            muted: false,
+            paused: false,
+            unfocused: false,
+            pause_on_focus_loss,
+            rotation: Rotation::default(),
+            input: &input::STANDARD,
+            palette: Palette::default(),
+            silent: false,
+            port: Port::default(),
+        };
+    }
+
+    /// Turns the screen, and the directional keys with it
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        return self;
+    }
+
+    /// Uses the input profile for the rom's keys rather than the standard one
+    pub fn with_input(mut self, input: &'static InputProfile) -> Self {
+        self.input = input;
+        return self;
+    }
+
+    /// Shows the screen in the palette's colours rather than white on black
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        return self;
+    }
+
+    /// Connects CHIP-8X's I/O port rather than leaving it unconnected
+    pub fn with_port(mut self, port: Port) -> Self {
+        self.port = port;
+        return self;
+    }
+
+    /// Passes bytes through CHIP-8X's I/O port, once a frame
+    pub fn exchange_port(&mut self, chip: &mut Chip8) -> Result<(), String> {
+        return self.port.exchange(chip);
+    }
+
+    /// For frontends that can't make a sound, which ignore the sound hotkeys and say nothing
+    /// of the volume
+    pub fn silent(mut self) -> Self {
+        self.silent = true;
+        return self;
+    }
+
+    /// Whether the frontend makes any sound, so the volume and beep are worth changing
+    pub fn sounds(&self) -> bool {
+        return !self.silent;
+    }
+
+    pub fn palette(&self) -> Palette {
+        return self.palette;
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn input(&self) -> &'static InputProfile {
+        return self.input;
+    }
+
+    pub fn set_input(&mut self, input: &'static InputProfile) {
+        self.input = input;
+    }
+
+    /// The keypad and key on it a keyboard key presses, through the input profile and then the
+    /// rotation
+    pub fn key(&self, name: &str) -> Option<(Keypad, u8)> {
+        return self.input.key(name).map(|(keypad, key)| (keypad, self.rotation.key(key)));
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        return self.rotation;
+    }
+
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    pub fn hotkey(&mut self, hotkey: Hotkey) {
+        if self.silent && hotkey != Hotkey::Pause {
+            return;
+        }
+        match hotkey {
+            Hotkey::Mute => self.muted = !self.muted,
+            Hotkey::VolumeDown => self.beep.volume = self.beep.volume.saturating_sub(VOLUME_STEP),
+            Hotkey::VolumeUp => self.beep.volume = (self.beep.volume + VOLUME_STEP).min(100),
+            Hotkey::Pause => self.paused = !self.paused,
+        }
+    }
+
+    /// Tells the controls the window gained or lost focus
+    pub fn set_focused(&mut self, focused: bool) {
+        self.unfocused = !focused;
+    }
+
+    /// Whether the machine should be stopped, by P or by the window losing focus
+    pub fn paused(&self) -> bool {
+        return self.paused || (self.pause_on_focus_loss && self.unfocused);
+    }
+
+    pub fn muted(&self) -> bool {
+        return self.muted;
+    }
+
+    /// Whether there should be any sound at all
+    pub fn audible(&self) -> bool {
+        return !self.muted && !self.paused() && self.beep.volume > 0;
+    }
+
+    /// The beep as it's set, muted or not
+    pub fn beep_settings(&self) -> Beep {
+        return self.beep;
+    }
+
+    pub fn set_beep(&mut self, beep: Beep) {
+        self.beep = beep;
+    }
+
+    /// The beep as it should sound right now, at no volume while muted or paused
+    pub fn beep(&self) -> Beep {
+        let mut beep = self.beep;
+        if !self.audible() {
+            beep.volume = 0;
+        }
+        return beep;
+    }
+
+    /// What the controls are doing, for a title bar: paused, muted or the volume, or just
+    /// running or paused without sound
+    pub fn status(&self) -> String {
+        if self.silent {
+            return if self.paused() { "paused" } else { "running" }.to_string();
+        }
+        let sound = if self.muted { "muted".to_string() } else { format!("volume {}%", self.beep.volume) };
+        if self.paused() {
+            return format!("paused, {sound}");
+        }
+        return sound;
+    }
+}
@@ -0,0 +1,93 @@
+use super::input::Held;
+use super::limiter::{FrameLimit, Limiter};
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+
+// The loop for frontends that can own their loop, so each one only has to say how it shows
+// the screen, reads the keys and makes the beep. run() reads the keys, runs a frame unless
+// the frontend's paused, hands the sound timer to the audio and the screen to the display,
+// then waits for the next frame with a Limiter at whatever limit the frontend asks for that
+// frame, so a hotkey cycling it takes effect straight away. Vsync is left to the display,
+// which waits for the refresh when it presents, so the limiter doesn't wait as well. While
+// the machine's idle the limiter rests rather than spinning, still reading the keys every
+// 60th of a second.
+//
+// A rom that runs SUPER-CHIP's exit (00FD) stops the loop once its last frame's shown, for
+// the frontend's caller to save what the rom keeps and close. A rom that stops with an error
+// stays on its last frame, still shown and still taking input, until the frontend says to
+// stop, and the error's returned then. The terminal and minifb frontends are built this way.
+// The pixels window and the egui frontend (gui.rs) aren't and can't be: winit and eframe own
+// their event loops and call into the frontend, so those run frames when the event loop says
+// and pace them with limiter.rs themselves.
+
+
+/// What a frontend does each frame, with run() driving it
+pub trait Frontend {
+    /// Handles whatever's happened since the last frame and says which keys are held on each
+    /// keypad, or None to stop
+    fn input(&mut self, chip: &Chip8) -> Result<Option<Held>, String>;
+
+    /// Shows the screen as it is at the end of a frame
+    fn display(&mut self, chip: &Chip8) -> Result<(), String>;
+
+    /// Called every frame with whether the sound timer's running, to start or stop the beep.
+    /// Silent by default
+    fn audio(&mut self, _sounding: bool) -> Result<(), String> {
+        return Ok(());
+    }
+
+    /// How frames are paced, asked every frame. 60 a second by default
+    fn limit(&self) -> FrameLimit {
+        return FrameLimit::Fixed;
+    }
+
+    /// Whether the machine should stand still for now. The screen's still shown and the
+    /// keys still read
+    fn paused(&self) -> bool {
+        return false;
+    }
+
+    /// Changes the machine as the frontend's been asked to since the last frame, e.g. from its
+    /// pause menu. Nothing by default
+    fn adjust(&mut self, _chip: &mut Chip8) {}
+
+    /// Passes bytes through CHIP-8X's I/O port after each frame. Not connected by default
+    fn port(&mut self, _chip: &mut Chip8) -> Result<(), String> {
+        return Ok(());
+    }
+}
+
+
+/// Plays the rom through the frontend until it says to stop or the rom exits. Errors from
+/// the frontend stop it straight away, an error from the rom once the frontend stops
+pub fn run(frontend: &mut impl Frontend, chip: &mut Chip8) -> Result<(), String> {
+    let mut limiter = Limiter::new(frontend.limit());
+    let mut error: Option<Chip8Error> = None;
+    while let Some(keys) = frontend.input(chip)? {
+        frontend.adjust(chip);
+        keys.apply(chip);
+        if error.is_none() && !frontend.paused() {
+            error = chip.run_frame().err();
+            frontend.port(chip)?;
+        }
+        frontend.audio(chip.sound() > 0 && error.is_none() && !frontend.paused())?;
+        frontend.display(chip)?;
+        if chip.exit_requested() {
+            break;
+        }
+
+        if frontend.limit() != limiter.limit() {
+            limiter.set_limit(frontend.limit());
+        }
+        if chip.idle() || error.is_some() {
+            limiter.rest();
+        } else {
+            limiter.wait();
+        }
+    }
+
+    return match error {
+        Some(e) => Err(e.to_string()),
+        None => Ok(()),
+    };
+}
@@ -0,0 +1,94 @@
+use std::fmt;
+
+// Filters for how the screen's scaled up in the GPU backed frontends. Nearest is each pixel
+// as a plain block. Scanlines darken the bottom row of each block, like the gaps between a
+// CRT's lines, and the LCD grid darkens the bottom row and right column, like the gaps
+// between a handheld's cells.
+//
+// It's done to the image handed to the GPU, scaled up by a whole number first so the gaps
+// have somewhere to go, and the machine's own screen is never touched, so headless runs,
+// screenshots and hashes are the same whatever the filter.
+
+/// The most a filtered screen is scaled up before the GPU takes over, which keeps the image
+/// small enough to make every frame. The gaps get wider past this rather than more detailed
+pub const MAX_FACTOR: usize = 8;
+
+/// How bright the gaps are, as a fraction of the pixel they're part of
+const SCANLINE: u32 = 50;
+const GRID: u32 = 65;
+
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    #[default]
+    Nearest,
+    Scanlines,
+    Lcd,
+}
+
+impl Filter {
+    pub const ALL: [Self; 3] = [Self::Nearest, Self::Scanlines, Self::Lcd];
+
+    /// Parses nearest, scanlines or lcd
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name.to_ascii_lowercase().as_str() {
+            "nearest" => Some(Self::Nearest),
+            "scanlines" => Some(Self::Scanlines),
+            "lcd" => Some(Self::Lcd),
+            _ => None,
+        };
+    }
+
+    /// The next filter along, for a key that cycles through them
+    pub fn next(self) -> Self {
+        return match self {
+            Self::Nearest => Self::Scanlines,
+            Self::Scanlines => Self::Lcd,
+            Self::Lcd => Self::Nearest,
+        };
+    }
+
+    /// How much to scale a screen up by before filtering it, when it's shown `scale` times
+    /// its size. Nearest leaves scaling to the GPU
+    pub fn factor(self, scale: f32) -> usize {
+        return match self {
+            Self::Nearest => 1,
+            _ => (scale as usize).clamp(1, MAX_FACTOR),
+        };
+    }
+
+    /// Scales the screen, 0xRRGGBB pixels `width` to a row, up by `factor` and filters it
+    /// into `rgba`, which has to be the size of the scaled up screen
+    pub fn apply(self, screen: &[u32], width: usize, factor: usize, rgba: &mut [u8]) {
+        let row = width * factor * 4;
+        for (y, line) in screen.chunks_exact(width).enumerate() {
+            for dy in 0..factor {
+                let at = (y * factor + dy) * row;
+                let texels = rgba[at..at + row].chunks_exact_mut(4);
+                for (i, texel) in texels.enumerate() {
+                    let dx = i % factor;
+                    // A block 1 texel wide has no room for a gap
+                    let last = |d: usize| factor > 1 && d == factor - 1;
+                    let percent = match self {
+                        Self::Scanlines if last(dy) => SCANLINE,
+                        Self::Lcd if last(dy) || last(dx) => GRID,
+                        _ => 100,
+                    };
+                    let rgb = line[i / factor];
+                    let shade = |channel: u32| ((channel & 0xFF) * percent / 100) as u8;
+                    texel.copy_from_slice(&[shade(rgb >> 16), shade(rgb >> 8), shade(rgb), 0xFF]);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::Nearest => write!(f, "nearest"),
+            Self::Scanlines => write!(f, "scanlines"),
+            Self::Lcd => write!(f, "LCD grid"),
+        };
+    }
+}
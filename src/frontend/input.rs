@@ -0,0 +1,311 @@
+use std::collections::VecDeque;
+use std::io::Write;
+
+use crate::chip::Chip8;
+
+// Input profiles: which keys on the keypad belong to which player, and what presses them
+// for each. Every player has keyboard keys and a gamepad of their own, so in a two player
+// game like PONG2 (1 and 4 for the left paddle, C and D for the right) one player can be on
+// the keyboard and the other on a gamepad, or on the arrow keys across the same keyboard.
+//
+// Keyboard keys go by name, the character on them or up, down, left and right for the
+// arrows, and each frontend knows which of its keys are which. The desktop frontends share
+// one keyboard between the players and have no gamepads. libretro has both, gamepad n is
+// whatever's plugged into port n.
+//
+// The profile for a rom comes from --input, then `input = <name>` in its profile, and is
+// standard if neither says:
+//
+//   standard   one player with the keypad on 1-4 Q-R A-F Z-V, and a gamepad's d-pad on 2 4
+//              6 8, A 5, B 0, X A, Y B, L 1, R 3, Start F and Select E
+//   split      player 1 on the keypad's left three columns, player 2 on the C to F column:
+//              4 R F V or the arrows, and a second gamepad's d-pad on C and D, A E and B F
+//   pong       player 1 on 1 and 4, 1 and Q or a gamepad's d-pad, and player 2 on C and D,
+//              4 and R, the up and down arrows or a second gamepad's d-pad
+//   chip8x     player 1 on the keypad as standard has it, player 2 on CHIP-8X's second
+//              keypad, 6-9 Y-O H-L N-. laid out the same way, or a second gamepad as
+//              standard has it
+//
+// A player's keys are on the first keypad unless the profile puts them on the second, which
+// only CHIP-8X has (see chip/chip8x.rs). A key the profile uses is a keypad key before it's
+// a hotkey, so in the terminal M doesn't mute with the chip8x profile.
+//
+// CHIP-8X's I/O port is connected to files given with --port-in and --port-out: the rom
+// reads the input file a byte at a time with FXFB, and what it puts out with FXF8 is written
+// to the output file. The port's looked at once a frame, so a rom putting out more than a
+// byte a frame only has its last one written.
+
+
+/// The buttons on a gamepad, as libretro has them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PadButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    X,
+    Y,
+    L,
+    R,
+    Start,
+    Select,
+}
+
+/// Which of the machine's keypads a player's keys are on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keypad {
+    First,
+    /// CHIP-8X's second keypad, nothing on other platforms
+    Second,
+}
+
+impl Keypad {
+    /// Presses or releases a key on the keypad
+    pub fn press(self, chip: &mut Chip8, key: u8, pressed: bool) {
+        match self {
+            Keypad::First => chip.set_key(key, pressed),
+            Keypad::Second => chip.set_second_key(key, pressed),
+        }
+    }
+}
+
+/// Which keys are held on each keypad
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Held {
+    pub first: [bool; 16],
+    pub second: [bool; 16],
+}
+
+impl Held {
+    /// Holds the key down if `held`, leaving it as it is otherwise
+    pub fn hold(&mut self, (keypad, key): (Keypad, u8), held: bool) {
+        let keys = match keypad {
+            Keypad::First => &mut self.first,
+            Keypad::Second => &mut self.second,
+        };
+        keys[(key & 0xF) as usize] |= held;
+    }
+
+    /// Presses and releases every key on both keypads to match
+    pub fn apply(&self, chip: &mut Chip8) {
+        for key in 0..16 {
+            Keypad::First.press(chip, key, self.first[key as usize]);
+            Keypad::Second.press(chip, key, self.second[key as usize]);
+        }
+    }
+}
+
+/// CHIP-8X's I/O port as the frontends connect it, the bytes waiting to go in and where the
+/// ones coming out go
+#[derive(Default)]
+pub struct Port {
+    input: VecDeque<u8>,
+    output: Option<Box<dyn Write>>,
+}
+
+impl Port {
+    pub fn new(input: Vec<u8>, output: Option<Box<dyn Write>>) -> Self {
+        return Self { input: input.into(), output };
+    }
+
+    /// Sends the rom the next byte once it's picked up the last, and writes out the byte it
+    /// put out since the last frame, if it did
+    pub fn exchange(&mut self, chip: &mut Chip8) -> Result<(), String> {
+        if chip.chip8x().is_some_and(|chip8x| chip8x.port_input().is_none()) {
+            if let Some(byte) = self.input.pop_front() {
+                chip.send_port_input(byte);
+            }
+        }
+        if let Some(byte) = chip.take_port_output() {
+            if let Some(output) = &mut self.output {
+                output
+                    .write_all(&[byte])
+                    .and_then(|_| output.flush())
+                    .map_err(|e| format!("An error occured when writing the I/O port's output: {e}"))?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// What presses one player's keys
+#[derive(Debug, PartialEq, Eq)]
+pub struct Player {
+    pub keypad: Keypad,
+    /// Keyboard keys by name, and the keypad keys they press
+    pub keyboard: &'static [(&'static str, u8)],
+    /// The buttons on the player's own gamepad, and the keypad keys they press
+    pub pad: &'static [(PadButton, u8)],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InputProfile {
+    pub name: &'static str,
+    /// Who plays on which keys, in a line
+    pub description: &'static str,
+    pub players: &'static [Player],
+}
+
+/// The keypad on the left of a QWERTY keyboard, the usual way
+const KEYBOARD: [(&str, u8); 16] = [
+    ("1", 0x1), ("2", 0x2), ("3", 0x3), ("4", 0xC),
+    ("q", 0x4), ("w", 0x5), ("e", 0x6), ("r", 0xD),
+    ("a", 0x7), ("s", 0x8), ("d", 0x9), ("f", 0xE),
+    ("z", 0xA), ("x", 0x0), ("c", 0xB), ("v", 0xF),
+];
+
+/// A gamepad on the keys most games use for movement and action
+const PAD: [(PadButton, u8); 12] = [
+    (PadButton::Up, 0x2), (PadButton::Down, 0x8), (PadButton::Left, 0x4), (PadButton::Right, 0x6),
+    (PadButton::A, 0x5), (PadButton::B, 0x0), (PadButton::X, 0xA), (PadButton::Y, 0xB),
+    (PadButton::L, 0x1), (PadButton::R, 0x3), (PadButton::Start, 0xF), (PadButton::Select, 0xE),
+];
+
+/// The second keypad on the right of a QWERTY keyboard, laid out like KEYBOARD
+const SECOND_KEYBOARD: [(&str, u8); 16] = [
+    ("6", 0x1), ("7", 0x2), ("8", 0x3), ("9", 0xC),
+    ("y", 0x4), ("u", 0x5), ("i", 0x6), ("o", 0xD),
+    ("h", 0x7), ("j", 0x8), ("k", 0x9), ("l", 0xE),
+    ("n", 0xA), ("m", 0x0), (",", 0xB), (".", 0xF),
+];
+
+pub const STANDARD: InputProfile = InputProfile {
+    name: "standard",
+    description: "one player with the keypad on 1-4 Q-R A-F Z-V",
+    players: &[Player { keypad: Keypad::First, keyboard: &KEYBOARD, pad: &PAD }],
+};
+
+pub const SPLIT: InputProfile = InputProfile {
+    name: "split",
+    description: "two players, the keypad's C to F column on 4 R F V or the arrows",
+    players: &[
+        Player {
+            keypad: Keypad::First,
+            keyboard: &[
+                ("1", 0x1), ("2", 0x2), ("3", 0x3),
+                ("q", 0x4), ("w", 0x5), ("e", 0x6),
+                ("a", 0x7), ("s", 0x8), ("d", 0x9),
+                ("z", 0xA), ("x", 0x0), ("c", 0xB),
+            ],
+            pad: &[
+                (PadButton::Up, 0x2), (PadButton::Down, 0x8), (PadButton::Left, 0x4), (PadButton::Right, 0x6),
+                (PadButton::A, 0x5), (PadButton::B, 0x0), (PadButton::X, 0xA), (PadButton::Y, 0xB),
+            ],
+        },
+        Player {
+            keypad: Keypad::First,
+            keyboard: &[
+                ("4", 0xC), ("r", 0xD), ("f", 0xE), ("v", 0xF),
+                ("up", 0xC), ("down", 0xD), ("left", 0xE), ("right", 0xF),
+            ],
+            pad: &[(PadButton::Up, 0xC), (PadButton::Down, 0xD), (PadButton::A, 0xE), (PadButton::B, 0xF)],
+        },
+    ],
+};
+
+pub const PONG: InputProfile = InputProfile {
+    name: "pong",
+    description: "two players on 1 and Q, and 4 and R or the up and down arrows",
+    players: &[
+        Player {
+            keypad: Keypad::First,
+            keyboard: &[("1", 0x1), ("q", 0x4)],
+            pad: &[(PadButton::Up, 0x1), (PadButton::Down, 0x4)],
+        },
+        Player {
+            keypad: Keypad::First,
+            keyboard: &[("4", 0xC), ("r", 0xD), ("up", 0xC), ("down", 0xD)],
+            pad: &[(PadButton::Up, 0xC), (PadButton::Down, 0xD)],
+        },
+    ],
+};
+
+pub const CHIP8X: InputProfile = InputProfile {
+    name: "chip8x",
+    description: "two players, the second on CHIP-8X's second keypad on 6-9 Y-O H-L N-.",
+    players: &[
+        Player { keypad: Keypad::First, keyboard: &KEYBOARD, pad: &PAD },
+        Player { keypad: Keypad::Second, keyboard: &SECOND_KEYBOARD, pad: &PAD },
+    ],
+};
+
+pub const PROFILES: [&InputProfile; 4] = [&STANDARD, &SPLIT, &PONG, &CHIP8X];
+
+
+impl InputProfile {
+    pub fn from_name(name: &str) -> Option<&'static Self> {
+        return PROFILES.into_iter().find(|profile| profile.name.eq_ignore_ascii_case(name));
+    }
+
+    /// The keypad and key on it a keyboard key presses, if any player has it
+    pub fn key(&self, name: &str) -> Option<(Keypad, u8)> {
+        return self.players.iter().find_map(|player| {
+            let (_, key) = player.keyboard.iter().find(|(key, _)| *key == name)?;
+            return Some((player.keypad, *key));
+        });
+    }
+
+    /// The keypad and key on it a button on gamepad `pad` presses, if there's a player for
+    /// that gamepad
+    pub fn button(&self, pad: usize, button: PadButton) -> Option<(Keypad, u8)> {
+        let player = self.players.get(pad)?;
+        let (_, key) = player.pad.iter().find(|(pressed, _)| *pressed == button)?;
+        return Some((player.keypad, *key));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+
+    #[test]
+    fn the_chip8x_profile_puts_player_2_on_the_second_keypad() {
+        assert_eq!(CHIP8X.key("q"), Some((Keypad::First, 0x4)));
+        assert_eq!(CHIP8X.key("y"), Some((Keypad::Second, 0x4)));
+        assert_eq!(CHIP8X.button(1, PadButton::Up), Some((Keypad::Second, 0x2)));
+        assert_eq!(STANDARD.key("y"), None);
+
+        let mut chip = Chip8::with_platform(Platform::Chip8X, false);
+        let mut held = Held::default();
+        held.hold(CHIP8X.key("m").unwrap(), true);
+        held.hold(CHIP8X.key("1").unwrap(), true);
+        held.apply(&mut chip);
+        assert!(chip.chip8x().unwrap().keys()[0x0]);
+        assert!(!chip.chip8x().unwrap().keys()[0x1]);
+        assert!(chip.state().keys[0x1]);
+        assert!(!chip.state().keys[0x0]);
+    }
+
+    /// Output shared with the test after it's handed to a port
+    struct Shared(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(bytes);
+            return Ok(bytes.len());
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            return Ok(());
+        }
+    }
+
+    #[test]
+    fn the_port_passes_bytes_in_and_out_a_frame_at_a_time() {
+        let written = std::rc::Rc::default();
+        let mut port = Port::new(vec![0x12, 0x34], Some(Box::new(Shared(std::rc::Rc::clone(&written)))));
+        let mut chip = Chip8::with_platform(Platform::Chip8X, false);
+        // Read a byte from the port, add 1 and put it out, forever, from 0x300 as CHIP-8X starts
+        chip.load_rom_bytes(&[0xF0, 0xFB, 0x70, 0x01, 0xF0, 0xF8, 0x13, 0x00]);
+        for _ in 0..4 {
+            port.exchange(&mut chip).unwrap();
+            chip.run_frame().unwrap();
+        }
+        port.exchange(&mut chip).unwrap();
+        assert_eq!(*written.borrow(), [0x13, 0x35]);
+    }
+}
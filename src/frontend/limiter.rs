@@ -0,0 +1,157 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// How the frontends pace frames. The machine's timers count down once a frame, so games
+// expect 60 frames a second, but it can also be synced to the display, run as fast as it
+// goes for benchmarking, or run at any other rate to speed a game up or slow it down.
+//
+// Sleeping alone overshoots by a millisecond or more on most systems, which is enough to
+// make 60Hz stutter, so the limiter sleeps until just before a frame's due and spins the
+// rest of the way. While the machine's idle (Chip8::idle, stopped or waiting for a key)
+// nothing's moving for that to matter, so it rests instead: sleeping the whole way, and
+// never more often than 60 times a second, so a finished game doesn't keep a core busy.
+
+/// How long before a frame's due the limiter stops sleeping and spins
+const SPIN: Duration = Duration::from_millis(2);
+
+/// The shortest time between frames while resting
+const REST: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameLimit {
+    /// A frame for every refresh of the display. Frontends that can't wait for the display
+    /// fall back to Fixed
+    Vsync,
+    /// 60 frames a second, what the timers were made for
+    Fixed,
+    /// As many frames a second as the machine can run
+    Uncapped,
+    /// Some other number of frames a second
+    Fps(u32),
+}
+
+impl FrameLimit {
+    /// Parses vsync, 60 (or fixed), uncapped, or any other number of frames a second
+    pub fn from_name(name: &str) -> Option<Self> {
+        return match name.to_ascii_lowercase().as_str() {
+            "vsync" => Some(Self::Vsync),
+            "60" | "fixed" => Some(Self::Fixed),
+            "uncapped" => Some(Self::Uncapped),
+            fps => fps.parse().ok().filter(|fps| *fps > 0).map(Self::Fps),
+        };
+    }
+
+    /// The next limit along, for a key that cycles through them. A custom rate goes back to
+    /// vsync
+    pub fn next(self) -> Self {
+        return match self {
+            Self::Vsync => Self::Fixed,
+            Self::Fixed => Self::Uncapped,
+            Self::Uncapped | Self::Fps(_) => Self::Vsync,
+        };
+    }
+
+    /// The limit for a frontend that can't wait for the display, which runs vsync at 60Hz
+    pub fn without_vsync(self) -> Self {
+        return match self {
+            Self::Vsync => Self::Fixed,
+            limit => limit,
+        };
+    }
+
+    /// How long a frame lasts, or None if frames aren't timed (vsync and uncapped)
+    pub fn frame(self) -> Option<Duration> {
+        return match self {
+            Self::Vsync | Self::Uncapped => None,
+            Self::Fixed => Some(Duration::from_nanos(1_000_000_000 / 60)),
+            Self::Fps(fps) => Some(Duration::from_nanos(1_000_000_000 / fps.max(1) as u64)),
+        };
+    }
+}
+
+impl fmt::Display for FrameLimit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Self::Vsync => write!(f, "vsync"),
+            Self::Fixed => write!(f, "60Hz"),
+            Self::Uncapped => write!(f, "uncapped"),
+            Self::Fps(fps) => write!(f, "{fps}fps"),
+        };
+    }
+}
+
+/// Keeps a frontend's frames to a FrameLimit. Vsync is left to the frontend, to the
+/// limiter it's the same as uncapped
+pub struct Limiter {
+    limit: FrameLimit,
+    next_frame: Instant,
+}
+
+impl Limiter {
+    pub fn new(limit: FrameLimit) -> Self {
+        return Self { limit, next_frame: Instant::now() };
+    }
+
+    pub fn limit(&self) -> FrameLimit {
+        return self.limit;
+    }
+
+    /// Switches to another limit, starting with a frame straight away
+    pub fn set_limit(&mut self, limit: FrameLimit) {
+        self.limit = limit;
+        self.next_frame = Instant::now();
+    }
+
+    /// When an event loop should wake up to spin the rest of the way to the next frame, or
+    /// None if it shouldn't wait at all
+    pub fn wake(&self) -> Option<Instant> {
+        return self.limit.frame().map(|_| self.next_frame - SPIN);
+    }
+
+    /// Whether a frame is due, moving on to the one after if it is
+    pub fn due(&mut self) -> bool {
+        let Some(frame) = self.limit.frame() else {
+            return true;
+        };
+        let now = Instant::now();
+        if now < self.next_frame {
+            return false;
+        }
+
+        // Fall back in step rather than racing to catch up after a stall
+        self.next_frame += frame;
+        if self.next_frame < now {
+            self.next_frame = now + frame;
+        }
+        return true;
+    }
+
+    /// When the next frame's due while the machine's idle: when it would be anyway, but no
+    /// sooner than REST from now
+    pub fn rest_until(&self) -> Instant {
+        let rest = Instant::now() + REST;
+        return match self.limit.frame() {
+            Some(_) => self.next_frame.max(rest),
+            None => rest,
+        };
+    }
+
+    /// Sleeps until rest_until, for when the machine's idle, and moves on to the next frame
+    pub fn rest(&mut self) {
+        std::thread::sleep(self.rest_until().saturating_duration_since(Instant::now()));
+        self.due();
+    }
+
+    /// Waits until a frame is due, sleeping most of the way then spinning
+    pub fn wait(&mut self) {
+        while !self.due() {
+            let left = self.next_frame.saturating_duration_since(Instant::now());
+            if left > SPIN {
+                std::thread::sleep(left - SPIN);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use ::minifb::{Key, KeyRepeat, Scale, ScaleMode, Window, WindowOptions};
+
+use super::controls::{Controls, Hotkey};
+use super::driver::{self, Frontend};
+use super::input::Held;
+use super::limiter::FrameLimit;
+use crate::chip::Chip8;
+use crate::netplay::Session;
+
+// A window with nothing but the game in it, for when SDL2 or a GPU isn't available. minifb
+// is pure Rust on every platform so it needs no system libraries to build. minifb doesn't
+// say when files are dropped on the window, so unlike the other windows this one can't
+// switch roms that way. It's also the window netplay (see netplay.rs) is played in. Escape
+// closes the window, Tab cycles the frame limit (shown in the title), P pauses (minifb makes
+// no sound, so there are no sound hotkeys), and the keys map onto the keypad through the
+// rom's input profile (see input.rs), by default the usual way:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+
+/// The keys input profiles can use, by the names they go by there
+const KEYMAP: [(Key, &str); 36] = [
+    (Key::Key1, "1"), (Key::Key2, "2"), (Key::Key3, "3"), (Key::Key4, "4"),
+    (Key::Q, "q"), (Key::W, "w"), (Key::E, "e"), (Key::R, "r"),
+    (Key::A, "a"), (Key::S, "s"), (Key::D, "d"), (Key::F, "f"),
+    (Key::Z, "z"), (Key::X, "x"), (Key::C, "c"), (Key::V, "v"),
+    (Key::Key6, "6"), (Key::Key7, "7"), (Key::Key8, "8"), (Key::Key9, "9"),
+    (Key::Y, "y"), (Key::U, "u"), (Key::I, "i"), (Key::O, "o"),
+    (Key::H, "h"), (Key::J, "j"), (Key::K, "k"), (Key::L, "l"),
+    (Key::N, "n"), (Key::M, "m"), (Key::Comma, ","), (Key::Period, "."),
+    (Key::Up, "up"), (Key::Down, "down"), (Key::Left, "left"), (Key::Right, "right"),
+];
+
+const HOTKEYS: [(Key, Hotkey); 1] = [(Key::P, Hotkey::Pause)];
+
+
+/// Turns a scale factor into the nearest one minifb supports, 0 fits the window to the screen
+pub fn scale(factor: usize) -> Scale {
+    return match factor {
+        0 => Scale::FitScreen,
+        1 => Scale::X1,
+        2..=3 => Scale::X2,
+        4..=7 => Scale::X4,
+        8..=15 => Scale::X8,
+        16..=31 => Scale::X16,
+        _ => Scale::X32,
+    };
+}
+
+/// Plays the rom in a window until it's closed or the rom exits. A rom that stops with an
+/// error leaves the window showing its last frame until it's closed, and the error is
+/// returned. minifb can't wait for the display, so vsync runs at 60Hz. With a netplay
+/// session each frame runs with both sides' keys once the other side's are in, and losing
+/// the other side or going out of sync with it closes the window with the error
+pub fn run(chip: &mut Chip8, scale: Scale, limit: FrameLimit, controls: Controls, netplay: Option<&mut Session>) -> Result<(), String> {
+    let options = WindowOptions {
+        resize: true,
+        scale,
+        scale_mode: ScaleMode::AspectRatioStretch,
+        ..WindowOptions::default()
+    };
+    let framebuffer = chip.framebuffer();
+    let (mut width, mut height) = (framebuffer.width(), framebuffer.height());
+    if controls.rotation().sideways() {
+        (width, height) = (height, width);
+    }
+    let mut window = Window::new("chip8", width, height, options)
+        .map_err(|e| e.to_string())?;
+    // driver.rs paces frames instead of minifb
+    window.set_target_fps(0);
+
+    let mut frontend = Minifb {
+        window,
+        limit: limit.without_vsync(),
+        controls: controls.silent(),
+        title: String::new(),
+        netplay,
+        lockstep: None,
+    };
+    return driver::run(&mut frontend, chip);
+}
+
+/// The window as driver.rs drives it
+struct Minifb<'a> {
+    window: Window,
+    limit: FrameLimit,
+    controls: Controls,
+    title: String,
+    netplay: Option<&'a mut Session>,
+    /// Both sides' keys for this frame once the other side's are in, when playing netplay
+    lockstep: Option<u16>,
+}
+
+impl Frontend for Minifb<'_> {
+    fn input(&mut self, _chip: &Chip8) -> Result<Option<Held>, String> {
+        if !self.window.is_open() || self.window.is_key_down(Key::Escape) {
+            return Ok(None);
+        }
+        if self.window.is_key_pressed(Key::Tab, KeyRepeat::No) {
+            self.limit = self.limit.next().without_vsync();
+        }
+        for (key, hotkey) in HOTKEYS {
+            if self.window.is_key_pressed(key, KeyRepeat::No) {
+                self.controls.hotkey(hotkey);
+            }
+        }
+        self.controls.set_focused(self.window.is_active());
+        let mut status = format!("chip8 - {}, {}", self.limit, self.controls.status());
+        if let Some(session) = &self.netplay {
+            status += &format!(", player {}", session.player());
+        }
+        if status != self.title {
+            self.window.set_title(&status);
+            self.title = status;
+        }
+
+        let mut held = Held::default();
+        for (key, name) in KEYMAP {
+            if let Some(chip_key) = self.controls.key(name) {
+                held.hold(chip_key, self.window.is_key_down(key));
+            }
+        }
+        let Some(session) = self.netplay.as_deref_mut() else {
+            return Ok(Some(held));
+        };
+        // Only the first keypad goes over the network, and in lockstep the frame runs with
+        // both sides' keys on it
+        let mask = held.first.into_iter().enumerate().fold(0, |mask, (chip_key, held)| mask | (held as u16) << chip_key);
+        session.update(mask)?;
+        self.lockstep = session.keys();
+        let mut both = Held::default();
+        for chip_key in 0..16 {
+            both.first[chip_key] = self.lockstep.unwrap_or(mask) & (1 << chip_key) != 0;
+        }
+        return Ok(Some(both));
+    }
+
+    /// The resolution can change as the rom runs (SUPER-CHIP, Mega-Chip), minifb scales
+    /// whatever size it's given to the window
+    fn display(&mut self, chip: &Chip8) -> Result<(), String> {
+        let framebuffer = chip.framebuffer();
+        let (screen, width, height) =
+            self.controls.rotation().apply(&self.controls.palette().screen(chip), framebuffer.width(), framebuffer.height());
+        return self.window.update_with_buffer(&screen, width, height).map_err(|e| e.to_string());
+    }
+
+    fn limit(&self) -> FrameLimit {
+        return self.limit;
+    }
+
+    /// Netplay's frames wait for the other side's keys
+    fn paused(&self) -> bool {
+        return self.controls.paused() || (self.netplay.is_some() && self.lockstep.is_none());
+    }
+
+    fn port(&mut self, chip: &mut Chip8) -> Result<(), String> {
+        return match self.netplay.as_deref_mut() {
+            Some(session) => session.advance(chip),
+            None => self.controls.exchange_port(chip),
+        };
+    }
+}
@@ -0,0 +1,274 @@
+use std::path::Path;
+use std::time::Instant;
+
+use ::pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::{Fullscreen, Window, WindowBuilder};
+
+use super::controls::{Controls, Hotkey};
+use super::filter::Filter;
+use super::limiter::{FrameLimit, Limiter};
+use super::playlist::Playlist;
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+
+// A GPU backed window using pixels on top of winit. pixels scales the screen up by the
+// largest whole number that fits the window and centres it, so pixels stay square and
+// sharp at any window size or DPI. F11 toggles fullscreen, Tab cycles the frame limit and F9
+// the scaling filter in filter.rs (both shown in the title), P pauses (there's no sound, so
+// none of the sound hotkeys in controls.rs), Escape closes the window, a rom dropped on the
+// window is switched to, and the keys map onto the keypad through the rom's input profile
+// (see input.rs), by default the usual way:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+
+/// The keys input profiles can use, by the names they go by there
+const KEYMAP: [(KeyCode, &str); 36] = [
+    (KeyCode::Digit1, "1"), (KeyCode::Digit2, "2"), (KeyCode::Digit3, "3"), (KeyCode::Digit4, "4"),
+    (KeyCode::KeyQ, "q"), (KeyCode::KeyW, "w"), (KeyCode::KeyE, "e"), (KeyCode::KeyR, "r"),
+    (KeyCode::KeyA, "a"), (KeyCode::KeyS, "s"), (KeyCode::KeyD, "d"), (KeyCode::KeyF, "f"),
+    (KeyCode::KeyZ, "z"), (KeyCode::KeyX, "x"), (KeyCode::KeyC, "c"), (KeyCode::KeyV, "v"),
+    (KeyCode::Digit6, "6"), (KeyCode::Digit7, "7"), (KeyCode::Digit8, "8"), (KeyCode::Digit9, "9"),
+    (KeyCode::KeyY, "y"), (KeyCode::KeyU, "u"), (KeyCode::KeyI, "i"), (KeyCode::KeyO, "o"),
+    (KeyCode::KeyH, "h"), (KeyCode::KeyJ, "j"), (KeyCode::KeyK, "k"), (KeyCode::KeyL, "l"),
+    (KeyCode::KeyN, "n"), (KeyCode::KeyM, "m"), (KeyCode::Comma, ","), (KeyCode::Period, "."),
+    (KeyCode::ArrowUp, "up"), (KeyCode::ArrowDown, "down"), (KeyCode::ArrowLeft, "left"), (KeyCode::ArrowRight, "right"),
+];
+
+const HOTKEYS: [(KeyCode, Hotkey); 1] = [(KeyCode::KeyP, Hotkey::Pause)];
+
+
+/// Plays the rom in a window scaled up by scale, through the filter, until it's closed or
+/// the rom exits. A rom that stops with an error leaves the window showing its last frame
+/// until it's closed, and the error is returned. Files dropped on the window are opened with
+/// open, given the machine the rom it makes replaces. With a playlist its roms are opened the
+/// same way as each one's time comes, and one stopping with an error or exiting is moved on
+/// from instead
+pub fn run(
+    chip: &mut Chip8,
+    scale: u32,
+    limit: FrameLimit,
+    mut filter: Filter,
+    controls: Controls,
+    open: &mut dyn FnMut(&Path, &Chip8) -> Result<Chip8, String>,
+    mut playlist: Option<&mut Playlist>,
+) -> Result<(), String> {
+    let event_loop = EventLoop::new().map_err(|e| e.to_string())?;
+    let mut controls = controls.silent();
+
+    let framebuffer = chip.framebuffer();
+    let (mut screen_width, mut screen_height) = (framebuffer.width() as u32, framebuffer.height() as u32);
+    if controls.rotation().sideways() {
+        (screen_width, screen_height) = (screen_height, screen_width);
+    }
+    let window = WindowBuilder::new()
+        .with_title("chip8")
+        .with_inner_size(LogicalSize::new(screen_width * scale.max(1), screen_height * scale.max(1)))
+        .with_min_inner_size(LogicalSize::new(screen_width, screen_height))
+        .build(&event_loop)
+        .map_err(|e| e.to_string())?;
+
+    let mut limiter = Limiter::new(limit);
+    // The size of the image pixels is given, the screen scaled up by the filter's factor
+    let (mut width, mut height) = (screen_width, screen_height);
+    // Only ever None while the surface is being made again
+    let mut pixels = Some(surface(&window, width, height, limit)?);
+    let mut title = String::new();
+
+    let mut error: Option<Chip8Error> = None;
+    let mut failure: Option<String> = None;
+
+    event_loop
+        .run(|event, target| match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => target.exit(),
+                WindowEvent::Focused(focused) => controls.set_focused(focused),
+                // A rom that can't be opened leaves the one playing as it was
+                WindowEvent::DroppedFile(path) => match open(&path, chip) {
+                    Ok(opened) => {
+                        *chip = opened;
+                        error = None;
+                    },
+                    Err(e) => eprintln!("An error occured when loading {}: {e}", path.display()),
+                },
+                // Resized also comes after a DPI change, with the new physical size
+                WindowEvent::Resized(size) => {
+                    let Some(pixels) = &mut pixels else {
+                        return;
+                    };
+                    if let Err(e) = pixels.resize_surface(size.width.max(1), size.height.max(1)) {
+                        failure = Some(e.to_string());
+                        target.exit();
+                    }
+                },
+                WindowEvent::KeyboardInput {
+                    event: KeyEvent { physical_key: PhysicalKey::Code(code), state, repeat: false, .. },
+                    ..
+                } => {
+                    let pressed = state == ElementState::Pressed;
+                    match code {
+                        KeyCode::Escape => target.exit(),
+                        KeyCode::F11 if pressed => {
+                            let fullscreen = match window.fullscreen() {
+                                Some(_) => None,
+                                None => Some(Fullscreen::Borderless(None)),
+                            };
+                            window.set_fullscreen(fullscreen);
+                        },
+                        KeyCode::F9 if pressed => {
+                            filter = filter.next();
+                            window.request_redraw();
+                        },
+                        KeyCode::Tab if pressed => {
+                            limiter.set_limit(limiter.limit().next());
+                            // Presenting waits for the display or not depending on how the
+                            // surface was made, so it's made again. The old one has to go first,
+                            // a window can only have one
+                            pixels = None;
+                            match surface(&window, width, height, limiter.limit()) {
+                                Ok(surface) => pixels = Some(surface),
+                                Err(e) => {
+                                    failure = Some(e);
+                                    target.exit();
+                                },
+                            }
+                        },
+                        _ => {
+                            if let Some((_, hotkey)) = HOTKEYS.iter().find(|(k, _)| *k == code && pressed) {
+                                controls.hotkey(*hotkey);
+                            }
+                            let name = KEYMAP.iter().find(|(k, _)| *k == code).map(|(_, name)| *name);
+                            if let Some((keypad, key)) = name.and_then(|name| controls.key(name)) {
+                                keypad.press(chip, key, pressed);
+                            }
+                        },
+                    }
+                },
+                WindowEvent::RedrawRequested => {
+                    let Some(pixels) = &mut pixels else {
+                        return;
+                    };
+                    // The resolution can change as the rom runs (SUPER-CHIP, Mega-Chip), and the
+                    // filter's factor with the window's size
+                    let framebuffer = chip.framebuffer();
+                    let (screen, screen_width, screen_height) =
+                        controls.rotation().apply(&controls.palette().screen(chip), framebuffer.width(), framebuffer.height());
+                    let (screen_width, screen_height) = (screen_width as u32, screen_height as u32);
+                    let size = window.inner_size();
+                    let scale = (size.width as f32 / screen_width as f32).min(size.height as f32 / screen_height as f32);
+                    let factor = filter.factor(scale);
+                    let buffer = (screen_width * factor as u32, screen_height * factor as u32);
+                    if buffer != (width, height) {
+                        (width, height) = buffer;
+                        if let Err(e) = pixels.resize_buffer(width, height) {
+                            failure = Some(e.to_string());
+                            target.exit();
+                            return;
+                        }
+                    }
+
+                    let brightness = playlist.as_ref().map_or(1.0, |playlist| playlist.brightness(Instant::now()));
+                    let dim = |channel: u32| ((channel & 0xFF) as f32 * brightness) as u32;
+                    let screen: Vec<u32> = screen.iter().map(|rgb| dim(rgb >> 16) << 16 | dim(rgb >> 8) << 8 | dim(*rgb)).collect();
+                    filter.apply(&screen, screen_width as usize, factor, pixels.frame_mut());
+                    if let Err(e) = pixels.render() {
+                        failure = Some(e.to_string());
+                        target.exit();
+                    }
+                },
+                _ => {},
+            },
+            // With vsync rendering waits for the display, which holds this to its refresh
+            // rate. Otherwise the loop sleeps until just before the next frame then spins
+            Event::AboutToWait => {
+                if let Some(playlist) = &mut playlist {
+                    let now = Instant::now();
+                    if let Some(e) = &error {
+                        eprintln!("{}: {e}", playlist.current().display());
+                    }
+                    if error.is_some() || chip.exit_requested() || playlist.due(chip, now) {
+                        error = None;
+                        // Roms that can't be opened are dropped from the list
+                        loop {
+                            let path = playlist.advance(now).to_path_buf();
+                            match open(&path, chip) {
+                                Ok(opened) => {
+                                    *chip = opened;
+                                    break;
+                                },
+                                Err(e) => eprintln!("An error occured when loading {}: {e}", path.display()),
+                            }
+                            if !playlist.remove() {
+                                target.exit();
+                                return;
+                            }
+                        }
+                    }
+                }
+
+                let playing = playlist.as_ref().and_then(|playlist| playlist.current().file_name());
+                let playing = playing.map_or(String::new(), |name| format!("{} - ", name.to_string_lossy()));
+                let status = format!("chip8 - {playing}{}, {filter}, {}", limiter.limit(), controls.status());
+                if status != title {
+                    window.set_title(&status);
+                    title = status;
+                }
+                // Paused, there's nothing to do until the next key or focus change
+                if controls.paused() {
+                    target.set_control_flow(ControlFlow::Wait);
+                    return;
+                }
+                if limiter.due() {
+                    if error.is_none() {
+                        error = chip.run_frame().err();
+                        if let Err(e) = controls.exchange_port(chip) {
+                            failure = Some(e);
+                            target.exit();
+                            return;
+                        }
+                    }
+                    if chip.exit_requested() && playlist.is_none() {
+                        target.exit();
+                        return;
+                    }
+                    window.request_redraw();
+                }
+                // An idle machine only needs looking at again for the next key
+                if chip.idle() || error.is_some() {
+                    target.set_control_flow(ControlFlow::WaitUntil(limiter.rest_until()));
+                    return;
+                }
+                match limiter.wake() {
+                    Some(wake) if wake > Instant::now() => target.set_control_flow(ControlFlow::WaitUntil(wake)),
+                    _ => target.set_control_flow(ControlFlow::Poll),
+                }
+            },
+            _ => {},
+        })
+        .map_err(|e| e.to_string())?;
+
+    if let Some(failure) = failure {
+        return Err(failure);
+    }
+    return match error {
+        Some(e) => Err(e.to_string()),
+        None => Ok(()),
+    };
+}
+
+/// Makes the surface the screen's drawn on, presenting in step with the display for vsync
+/// and as soon as it's drawn otherwise
+fn surface(window: &Window, width: u32, height: u32, limit: FrameLimit) -> Result<Pixels, String> {
+    let size = window.inner_size();
+    let surface = SurfaceTexture::new(size.width, size.height, window);
+    return PixelsBuilder::new(width, height, surface)
+        .enable_vsync(limit == FrameLimit::Vsync)
+        .build()
+        .map_err(|e| e.to_string());
+}
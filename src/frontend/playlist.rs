@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::chip::Chip8;
+
+// Kiosk mode (chip8 kiosk): playing through a list of roms one after another and round
+// again, for demo booths, or for leaving a library running overnight to see what falls over.
+// Each rom plays for a set time, or until the keypad's 1 and F (1 and V on a keyboard) are
+// held together to skip it. A rom that stops with an error is moved on from straight away.
+//
+// Between roms the screen fades out and the next one fades in, the way arcade attract modes
+// go from one game to the next.
+
+/// How long fading out or in takes
+const FADE: Duration = Duration::from_millis(500);
+
+/// The keypad keys held together to skip to the next rom, 1 and F
+const SKIP: [u8; 2] = [0x1, 0xF];
+
+
+pub struct Playlist {
+    roms: Vec<PathBuf>,
+    current: usize,
+    /// How long each rom plays for
+    each: Duration,
+    started: Instant,
+    ends: Instant,
+}
+
+impl Playlist {
+    /// Plays the roms in order for `each` apiece, starting now with the first
+    pub fn new(roms: Vec<PathBuf>, each: Duration) -> Self {
+        let now = Instant::now();
+        return Self { roms, current: 0, each, started: now, ends: now + each };
+    }
+
+    /// The rom that's playing
+    pub fn current(&self) -> &Path {
+        return &self.roms[self.current];
+    }
+
+    pub fn len(&self) -> usize {
+        return self.roms.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.roms.is_empty();
+    }
+
+    /// Whether it's time for the next rom, checking for the skip keys on the way. Holding
+    /// them starts fading out rather than cutting straight to the next
+    pub fn due(&mut self, chip: &Chip8, now: Instant) -> bool {
+        if SKIP.iter().all(|key| chip.state().keys[*key as usize]) && self.ends > now + FADE {
+            self.ends = now + FADE;
+        }
+        return now >= self.ends;
+    }
+
+    /// Moves on to the next rom, round to the first after the last, and returns it
+    pub fn advance(&mut self, now: Instant) -> &Path {
+        self.current = (self.current + 1) % self.roms.len();
+        self.started = now;
+        self.ends = now + self.each;
+        return self.current();
+    }
+
+    /// Drops the rom that's playing, when it can't be loaded. Returns whether any are left
+    pub fn remove(&mut self) -> bool {
+        self.roms.remove(self.current);
+        self.current = self.current.checked_sub(1).unwrap_or(self.roms.len().saturating_sub(1));
+        return !self.roms.is_empty();
+    }
+
+    /// How bright the screen is, from 0 to 1, fading in at the start of each rom and out at
+    /// the end
+    pub fn brightness(&self, now: Instant) -> f32 {
+        let since = now.saturating_duration_since(self.started);
+        let left = self.ends.saturating_duration_since(now);
+        return (since.min(left).as_secs_f32() / FADE.as_secs_f32()).min(1.0);
+    }
+}
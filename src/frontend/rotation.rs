@@ -0,0 +1,71 @@
+use std::fmt;
+
+// Turning the screen for games made to be played on a display on its side, as some Octo jam
+// games are (Octo cartridges say so with screenRotation). The screen's turned clockwise by
+// 90, 180 or 270 degrees on its way to the window, and the directional keys are turned with
+// it so up is still whichever way is up on screen. They're W A S D, 5 7 8 9 on the keypad,
+// the way Octo lays them out.
+//
+// The rotation for a rom comes from --rotate, then `rotation = <degrees>` in its profile,
+// then its cartridge.
+
+/// The directional keys clockwise from up, as they are with the screen upright
+const DIRECTIONS: [u8; 4] = [0x5, 0x9, 0x8, 0x7];
+
+
+/// How far the screen's turned clockwise, in quarter turns
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Rotation {
+    quarters: u8,
+}
+
+impl Rotation {
+    pub const ALL: [Self; 4] = [Self { quarters: 0 }, Self { quarters: 1 }, Self { quarters: 2 }, Self { quarters: 3 }];
+
+    /// Parses 0, 90, 180 or 270 degrees
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        return (degrees.is_multiple_of(90) && degrees < 360).then_some(Self { quarters: (degrees / 90) as u8 });
+    }
+
+    pub fn degrees(self) -> u32 {
+        return self.quarters as u32 * 90;
+    }
+
+    /// Whether the width and height swap over
+    pub fn sideways(self) -> bool {
+        return self.quarters % 2 == 1;
+    }
+
+    /// Turns the screen, `width` pixels to a row, returning it with its new width and height
+    pub fn apply<T: Copy>(self, screen: &[T], width: usize, height: usize) -> (Vec<T>, usize, usize) {
+        let (turned_width, turned_height) = if self.sideways() { (height, width) } else { (width, height) };
+        let mut turned = Vec::with_capacity(screen.len());
+        for y in 0..turned_height {
+            for x in 0..turned_width {
+                let (from_x, from_y) = match self.quarters {
+                    0 => (x, y),
+                    1 => (y, height - 1 - x),
+                    2 => (width - 1 - x, height - 1 - y),
+                    _ => (width - 1 - y, x),
+                };
+                turned.push(screen[from_y * width + from_x]);
+            }
+        }
+        return (turned, turned_width, turned_height);
+    }
+
+    /// The keypad key the rom gets when `key` is pressed, which is different for the
+    /// directional keys when the screen's turned
+    pub fn key(self, key: u8) -> u8 {
+        return match DIRECTIONS.iter().position(|direction| *direction == key) {
+            Some(on_screen) => DIRECTIONS[(on_screen + 4 - self.quarters as usize) % 4],
+            None => key,
+        };
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{}°", self.degrees());
+    }
+}
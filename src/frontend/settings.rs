@@ -0,0 +1,302 @@
+use std::fmt::{self, Write};
+
+use super::controls::Controls;
+use super::input;
+use crate::audio::Waveform;
+use crate::chip::{Chip8, Quirks, StackPolicy};
+use crate::profile::Profile;
+
+// The pause menu, opened with F1 in the gui and the terminal, for changing how a rom plays
+// without starting it again. Every change takes effect straight away, and saving writes them
+// all to the rom's profile (see profile.rs) for next time:
+//
+//   Speed            instructions a frame
+//   Palette          the colours of pixels that are on and off
+//   Keys             the input profile (see input.rs)
+//   Volume, Beep     the beep's volume and waveform (see audio.rs)
+//   Protect memory, Strict, Stack overflow
+//                    what happens when a rom misbehaves
+//   Shift source, Jump offset, Memory moves I, VF reset, Display wait
+//                    the quirks, where interpreters disagree over what an instruction does
+//                    (see chip.rs' Quirks), for roms written for one that isn't the default
+//
+// Palettes only recolour screens that are plainly on and off, CHIP-8X and Mega-Chip bring
+// their own colours.
+
+/// The palettes the menu goes through, by name
+pub const PALETTES: [(&str, Palette); 6] = [
+    ("white", Palette { on: 0xFFFFFF, off: 0x000000 }),
+    ("amber", Palette { on: 0xFFB000, off: 0x1A0F00 }),
+    ("green", Palette { on: 0x33FF66, off: 0x001A08 }),
+    ("lcd", Palette { on: 0x0F380F, off: 0x9BBC0F }),
+    ("octo", Palette { on: 0xFFCC00, off: 0x996600 }),
+    ("paper", Palette { on: 0x000000, off: 0xFFFFFF }),
+];
+
+
+/// The colours of pixels that are on and off, as 0xRRGGBB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub on: u32,
+    pub off: u32,
+}
+
+impl Default for Palette {
+    /// White on black
+    fn default() -> Self {
+        return PALETTES[0].1;
+    }
+}
+
+impl Palette {
+    /// Reads a palette as profiles give it, a name from PALETTES or the on colour and then the
+    /// off one in hex, e.g. FFB000 1A0F00
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some((_, palette)) = PALETTES.iter().find(|(name, _)| name.eq_ignore_ascii_case(text.trim())) {
+            return Some(*palette);
+        }
+        let colour = |hex: &str| u32::from_str_radix(hex.trim_start_matches('#'), 16).ok().filter(|rgb| *rgb <= 0xFFFFFF);
+        let (on, off) = text.trim().split_once(char::is_whitespace)?;
+        return Some(Self { on: colour(on)?, off: colour(off.trim())? });
+    }
+
+    /// The palette's name if it's one of PALETTES, otherwise its colours
+    pub fn name(&self) -> String {
+        return match PALETTES.iter().find(|(_, palette)| palette == self) {
+            Some((name, _)) => name.to_string(),
+            None => self.to_string(),
+        };
+    }
+
+    /// The machine's screen as 0xRRGGBB, in the palette's colours if it's plainly on and off
+    pub fn screen(&self, chip: &Chip8) -> Vec<u32> {
+        let mut rgb = chip.screen_rgb();
+        let coloured = chip.framebuffer().colour_zones().is_some() || chip.megachip().is_some_and(|megachip| megachip.enabled());
+        if !coloured && *self != Self::default() {
+            for pixel in &mut rgb {
+                *pixel = if *pixel == 0 { self.off } else { self.on };
+            }
+        }
+        return rgb;
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:06X} {:06X}", self.on, self.off);
+    }
+}
+
+/// One of the settings in the pause menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Speed,
+    Palette,
+    Keys,
+    Volume,
+    Beep,
+    ProtectMemory,
+    Strict,
+    StackOverflow,
+    ShiftSource,
+    JumpOffset,
+    MemoryMovesI,
+    VfReset,
+    DisplayWait,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 13] = [
+        Setting::Speed, Setting::Palette, Setting::Keys, Setting::Volume, Setting::Beep, Setting::ProtectMemory,
+        Setting::Strict, Setting::StackOverflow, Setting::ShiftSource, Setting::JumpOffset, Setting::MemoryMovesI,
+        Setting::VfReset, Setting::DisplayWait,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Setting::Speed => "Speed",
+            Setting::Palette => "Palette",
+            Setting::Keys => "Keys",
+            Setting::Volume => "Volume",
+            Setting::Beep => "Beep",
+            Setting::ProtectMemory => "Protect memory",
+            Setting::Strict => "Strict",
+            Setting::StackOverflow => "Stack overflow",
+            Setting::ShiftSource => "Shift source",
+            Setting::JumpOffset => "Jump offset",
+            Setting::MemoryMovesI => "Memory moves I",
+            Setting::VfReset => "VF reset",
+            Setting::DisplayWait => "Display wait",
+        };
+    }
+
+    /// The quirk it switches, if it's one of them
+    fn quirk(self, quirks: &mut Quirks) -> Option<&mut bool> {
+        return match self {
+            Setting::ShiftSource => Some(&mut quirks.shift_vy),
+            Setting::JumpOffset => Some(&mut quirks.jump_vx),
+            Setting::MemoryMovesI => Some(&mut quirks.memory_moves_i),
+            Setting::VfReset => Some(&mut quirks.vf_reset),
+            Setting::DisplayWait => Some(&mut quirks.display_wait),
+            _ => None,
+        };
+    }
+
+    /// Whether it's a setting for the sound, which frontends without any leave out
+    pub fn sound(&self) -> bool {
+        return matches!(self, Setting::Volume | Setting::Beep);
+    }
+
+    /// What the setting's set to, as the menu shows it
+    pub fn value(&self, chip: &Chip8, controls: &Controls) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        return match self {
+            Setting::Speed => format!("{} instructions a frame", chip.cycles_per_frame()),
+            Setting::Palette => controls.palette().name(),
+            Setting::Keys => controls.input().name.to_string(),
+            Setting::Volume => format!("{}%", controls.beep_settings().volume),
+            Setting::Beep => controls.beep_settings().waveform.to_string(),
+            Setting::ProtectMemory => on_off(chip.memory_protection()),
+            Setting::Strict => on_off(chip.strict()),
+            Setting::StackOverflow => match chip.stack_policy() {
+                StackPolicy::Error => "error".to_string(),
+                StackPolicy::Wrap => "wrap".to_string(),
+            },
+            Setting::ShiftSource => if chip.quirks().shift_vy { "VY" } else { "VX" }.to_string(),
+            Setting::JumpOffset => if chip.quirks().jump_vx { "VX" } else { "V0" }.to_string(),
+            _ => on_off(self.quirk(&mut chip.quirks()).is_some_and(|on| *on)),
+        };
+    }
+
+    /// Moves the setting on to its next value, or back to the one before
+    pub fn change(&self, chip: &mut Chip8, controls: &mut Controls, forward: bool) {
+        // The next of a list of choices after current, going round
+        fn next<T: PartialEq + Copy>(choices: &[T], current: T, forward: bool) -> T {
+            let i = choices.iter().position(|choice| *choice == current).unwrap_or(0);
+            let i = if forward { i + 1 } else { i + choices.len() - 1 };
+            return choices[i % choices.len()];
+        }
+
+        let mut beep = controls.beep_settings();
+        match self {
+            Setting::Speed => {
+                let speed = chip.cycles_per_frame();
+                let step = (speed / 10).max(1);
+                chip.set_cycles_per_frame(if forward { speed + step } else { speed.saturating_sub(step) });
+            },
+            Setting::Palette => {
+                let palettes = PALETTES.map(|(_, palette)| palette);
+                controls.set_palette(next(&palettes, controls.palette(), forward));
+            },
+            Setting::Keys => {
+                let profiles = input::PROFILES.map(|profile| profile.name);
+                let name = next(&profiles, controls.input().name, forward);
+                controls.set_input(input::InputProfile::from_name(name).unwrap_or(&input::STANDARD));
+            },
+            Setting::Volume => {
+                beep.volume = if forward { (beep.volume + 5).min(100) } else { beep.volume.saturating_sub(5) };
+                controls.set_beep(beep);
+            },
+            Setting::Beep => {
+                beep.waveform = next(&[Waveform::Square, Waveform::Triangle, Waveform::Sine], beep.waveform, forward);
+                controls.set_beep(beep);
+            },
+            Setting::ProtectMemory => chip.set_memory_protection(!chip.memory_protection()),
+            Setting::Strict => chip.set_strict(!chip.strict()),
+            Setting::StackOverflow => {
+                let policy = next(&[StackPolicy::Error, StackPolicy::Wrap], chip.stack_policy(), forward);
+                chip.set_stack_policy(policy);
+            },
+            _ => {
+                let mut quirks = chip.quirks();
+                if let Some(on) = self.quirk(&mut quirks) {
+                    *on = !*on;
+                }
+                chip.set_quirks(quirks);
+            },
+        }
+    }
+
+    /// The profile setting it's saved as
+    fn key(&self) -> &'static str {
+        return match self {
+            Setting::Speed => "speed",
+            Setting::Palette => "palette",
+            Setting::Keys => "input",
+            Setting::Volume => "beep_volume",
+            Setting::Beep => "beep",
+            Setting::ProtectMemory => "memory_protection",
+            Setting::Strict => "strict",
+            Setting::StackOverflow => "stack_overflow",
+            Setting::ShiftSource => "shift_vy",
+            Setting::JumpOffset => "jump_vx",
+            Setting::MemoryMovesI => "memory_moves_i",
+            Setting::VfReset => "vf_reset",
+            Setting::DisplayWait => "display_wait",
+        };
+    }
+
+    /// The value it's saved with, as profiles read it
+    fn saved(&self, chip: &Chip8, controls: &Controls) -> String {
+        return match self {
+            Setting::Speed => chip.cycles_per_frame().to_string(),
+            Setting::Volume => controls.beep_settings().volume.to_string(),
+            Setting::ProtectMemory => chip.memory_protection().to_string(),
+            Setting::Strict => chip.strict().to_string(),
+            _ => match self.quirk(&mut chip.quirks()) {
+                Some(on) => on.to_string(),
+                None => self.value(chip, controls),
+            },
+        };
+    }
+}
+
+/// Saves every setting to the rom's profile, saying where to
+pub fn save(rom: &[u8], chip: &Chip8, controls: &Controls) -> Result<String, String> {
+    for setting in Setting::ALL {
+        Profile::save_setting(rom, setting.key(), &setting.saved(chip, controls))
+            .map_err(|e| format!("An error occured when saving the settings: {e}"))?;
+    }
+    return Ok(format!("Saved to {}", Profile::path(rom).display()));
+}
+
+/// The pause menu for frontends that draw it as text, the setting picked and how the last
+/// save went
+#[derive(Default)]
+pub struct Menu {
+    pub open: bool,
+    selected: usize,
+    message: Option<String>,
+}
+
+impl Menu {
+    pub fn selected(&self) -> Setting {
+        return Setting::ALL[self.selected];
+    }
+
+    /// Picks the setting above or below, going round
+    pub fn select(&mut self, down: bool) {
+        let count = Setting::ALL.len();
+        self.selected = if down { (self.selected + 1) % count } else { (self.selected + count - 1) % count };
+    }
+
+    /// Shows how saving went
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /// The menu a line at a time, with > by the setting picked
+    pub fn text(&self, chip: &Chip8, controls: &Controls) -> String {
+        let mut text = String::new();
+        writeln!(text, "Paused, F1 or Escape to go back to the game\n").unwrap();
+        for (i, setting) in Setting::ALL.iter().enumerate() {
+            let cursor = if i == self.selected { ">" } else { " " };
+            writeln!(text, "{cursor} {:<16}{}", setting.name(), setting.value(chip, controls)).unwrap();
+        }
+        writeln!(text, "\nUp and down pick, left and right change, S saves them to the rom's profile").unwrap();
+        if let Some(message) = &self.message {
+            writeln!(text, "{message}").unwrap();
+        }
+        return text;
+    }
+}
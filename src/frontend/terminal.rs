@@ -0,0 +1,427 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags};
+use crossterm::event::{PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use crossterm::{cursor, terminal};
+
+use super::controls::{Controls, Hotkey};
+use super::driver::{self, Frontend};
+use super::input::{Held, Keypad};
+use super::limiter::FrameLimit;
+use super::rotation::Rotation;
+use super::settings::{self, Menu, Palette, Setting};
+use crate::chip::Chip8;
+
+// Plays the rom right in the terminal, drawn as real pixels with whichever graphics
+// protocol the terminal speaks: Kitty's, iTerm2's inline images (also understood by WezTerm
+// and Konsole), or Sixel (xterm -ti vt340, foot, mlterm, Windows Terminal). Escape or
+// Ctrl-C quits, Tab cycles the frame limit (shown in the title), F1 pauses with the menu of
+// settings (settings.rs) drawn as text in place of the game, M, -, = and P are the hotkeys in
+// controls.rs, and the keys map onto the keypad through the rom's input profile (see
+// input.rs), by default the usual way:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+//
+// Most terminals only say when a key goes down, so a key stays held for a moment after
+// each press and the terminal's key repeat keeps it held. Terminals that report releases
+// (Kitty's keyboard protocol) release it straight away.
+//
+// The only sound a terminal can make is its bell, which rings when the sound timer starts
+// unless it's muted.
+
+
+/// How long a key stays held after a press without a release, long enough to bridge the
+/// pause before the terminal's key repeat starts
+const HOLD: Duration = Duration::from_millis(500);
+
+
+/// The terminal graphics protocols images can be drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+impl Protocol {
+    /// Looks a protocol up by the name used on the command line
+    pub fn from_name(name: &str) -> Option<Protocol> {
+        return match name.to_ascii_lowercase().as_str() {
+            "kitty" => Some(Protocol::Kitty),
+            "iterm" | "iterm2" => Some(Protocol::Iterm2),
+            "sixel" => Some(Protocol::Sixel),
+            _ => None,
+        };
+    }
+
+    /// Guesses the protocol from the environment the terminal sets up. Sixel is the
+    /// fallback, being the one most terminals that do graphics at all understand
+    pub fn detect() -> Protocol {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        if !var("KITTY_WINDOW_ID").is_empty() || var("TERM") == "xterm-kitty" || var("TERM_PROGRAM") == "ghostty" {
+            return Protocol::Kitty;
+        }
+        if matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm") || !var("KONSOLE_VERSION").is_empty() {
+            return Protocol::Iterm2;
+        }
+        return Protocol::Sixel;
+    }
+}
+
+/// Encodes the screen in the palette, turned and scaled up by scale, as an escape sequence
+/// drawing it at the cursor
+pub fn encode(chip: &Chip8, protocol: Protocol, scale: usize, rotation: Rotation, palette: Palette) -> Result<Vec<u8>, String> {
+    let framebuffer = chip.framebuffer();
+    let scale = scale.max(1);
+    let (rgb, screen_width, screen_height) = rotation.apply(&palette.screen(chip), framebuffer.width(), framebuffer.height());
+    let (width, height) = (screen_width * scale, screen_height * scale);
+    let pixel = |x: usize, y: usize| rgb[y / scale * screen_width + x / scale];
+
+    let mut out = Vec::new();
+    match protocol {
+        Protocol::Kitty => {
+            // Images go in chunks of at most 4096 base64 bytes, m=1 on all but the last. Using
+            // the same image id each frame replaces the last one rather than stacking them up
+            let png = BASE64.encode(png(width, height, pixel)?);
+            let chunks: Vec<&[u8]> = png.as_bytes().chunks(4096).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = (i + 1 < chunks.len()) as u8;
+                if i == 0 {
+                    write!(out, "\x1b_Ga=T,f=100,i=1,q=2,C=1,m={more};").unwrap();
+                } else {
+                    write!(out, "\x1b_Gm={more};").unwrap();
+                }
+                out.extend_from_slice(chunk);
+                out.extend_from_slice(b"\x1b\\");
+            }
+        },
+        Protocol::Iterm2 => {
+            let png = png(width, height, pixel)?;
+            write!(
+                out,
+                "\x1b]1337;File=inline=1;size={};width={width}px;height={height}px;doNotMoveCursor=1:{}\x07",
+                png.len(),
+                BASE64.encode(&png)
+            )
+            .unwrap();
+        },
+        Protocol::Sixel => sixel(&mut out, width, height, pixel),
+    }
+    return Ok(out);
+}
+
+/// Encodes an image as an RGB PNG
+fn png(width: usize, height: usize, pixel: impl Fn(usize, usize) -> u32) -> Result<Vec<u8>, String> {
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let colour = pixel(x, y);
+            rgb.extend([(colour >> 16) as u8, (colour >> 8) as u8, colour as u8]);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&rgb).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    return Ok(png);
+}
+
+/// Encodes an image as Sixel. Each band of 6 rows is drawn a colour at a time, with a
+/// character a column whose bits say which of the 6 pixels are that colour
+fn sixel(out: &mut Vec<u8>, width: usize, height: usize, pixel: impl Fn(usize, usize) -> u32) {
+    // Sixel has 256 colour registers. Screens with more colours than that (blended
+    // Mega-Chip sprites) are cut down to 3-3-2 bit colour first
+    let pixel = &pixel;
+    let mut pixels: Vec<u32> = (0..height).flat_map(|y| (0..width).map(move |x| pixel(x, y))).collect();
+    let mut palette = colours(&pixels);
+    if palette.len() > 256 {
+        pixels.iter_mut().for_each(|pixel| *pixel &= 0xE0E0C0);
+        palette = colours(&pixels);
+    }
+    let registers: HashMap<u32, usize> = palette.iter().enumerate().map(|(register, rgb)| (*rgb, register)).collect();
+
+    write!(out, "\x1bPq\"1;1;{width};{height}").unwrap();
+    for (register, rgb) in palette.iter().enumerate() {
+        // Colours are given as percentages
+        let percent = |shift: u32| (rgb >> shift & 0xFF) * 100 / 255;
+        write!(out, "#{register};2;{};{};{}", percent(16), percent(8), percent(0)).unwrap();
+    }
+
+    for band in (0..height).step_by(6) {
+        // The sixels of each colour in the band, by register
+        let mut sixels: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for row in 0..(height - band).min(6) {
+            for x in 0..width {
+                let register = registers[&pixels[(band + row) * width + x]];
+                sixels.entry(register).or_insert_with(|| vec![0; width])[x] |= 1 << row;
+            }
+        }
+
+        for (i, (register, sixels)) in sixels.iter().enumerate() {
+            // Back to the start of the band to draw the next colour over it
+            if i > 0 {
+                out.push(b'$');
+            }
+            write!(out, "#{register}").unwrap();
+            for run in sixels.chunk_by(|a, b| a == b) {
+                let c = 63 + run[0];
+                if run.len() > 3 {
+                    write!(out, "!{}{}", run.len(), c as char).unwrap();
+                } else {
+                    out.extend(run.iter().map(|_| c));
+                }
+            }
+        }
+        out.push(b'-');
+    }
+    out.extend_from_slice(b"\x1b\\");
+}
+
+/// The distinct colours in pixels, in the order they first appear
+fn colours(pixels: &[u32]) -> Vec<u32> {
+    let mut seen = HashSet::new();
+    return pixels.iter().copied().filter(|rgb| seen.insert(*rgb)).collect();
+}
+
+/// Plays the rom in the terminal until Escape or Ctrl-C, or the rom exits. A rom that stops
+/// with an error leaves its last frame showing until then, and the error is returned. A
+/// terminal can't wait for the display, so vsync runs at 60Hz. The pause menu saves to rom's
+/// profile
+pub fn run(
+    chip: &mut Chip8,
+    rom: &[u8],
+    protocol: Protocol,
+    scale: usize,
+    limit: FrameLimit,
+    controls: Controls,
+) -> Result<(), String> {
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    crossterm::execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide, event::EnableFocusChange)
+        .map_err(|e| e.to_string())?;
+    if releases {
+        let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+        crossterm::execute!(stdout, PushKeyboardEnhancementFlags(flags)).map_err(|e| e.to_string())?;
+    }
+
+    let result = play(chip, rom, protocol, scale, limit, controls);
+
+    // Put the terminal back however the game ended
+    if releases {
+        let _ = crossterm::execute!(stdout, PopKeyboardEnhancementFlags);
+    }
+    let _ = crossterm::execute!(stdout, event::DisableFocusChange, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    return result;
+}
+
+/// The terminal as a Frontend for the driver's loop
+struct Terminal {
+    stdout: std::io::Stdout,
+    protocol: Protocol,
+    scale: usize,
+    limit: FrameLimit,
+    controls: Controls,
+    /// When each key on the first and then the second keypad stops being held
+    held_until: [[Option<Instant>; 16]; 2],
+    last_screen: Vec<u32>,
+    title: String,
+    beeping: bool,
+    rom: Vec<u8>,
+    menu: Menu,
+    /// The menu as last drawn
+    menu_shown: String,
+    /// Changes made in the menu since the last frame, and whether to save
+    changes: Vec<(Setting, bool)>,
+    save: bool,
+}
+
+fn play(
+    chip: &mut Chip8,
+    rom: &[u8],
+    protocol: Protocol,
+    scale: usize,
+    limit: FrameLimit,
+    controls: Controls,
+) -> Result<(), String> {
+    let mut terminal = Terminal {
+        stdout: std::io::stdout(),
+        protocol,
+        scale,
+        limit: limit.without_vsync(),
+        controls,
+        held_until: [[None; 16]; 2],
+        last_screen: Vec::new(),
+        title: String::new(),
+        beeping: false,
+        rom: rom.to_vec(),
+        menu: Menu::default(),
+        menu_shown: String::new(),
+        changes: Vec::new(),
+        save: false,
+    };
+    return driver::run(&mut terminal, chip);
+}
+
+impl Frontend for Terminal {
+    fn input(&mut self, _chip: &Chip8) -> Result<Option<Held>, String> {
+        while event::poll(Duration::ZERO).map_err(|e| e.to_string())? {
+            let key = match event::read().map_err(|e| e.to_string())? {
+                Event::Key(key) => key,
+                Event::FocusGained => {
+                    self.controls.set_focused(true);
+                    continue;
+                },
+                Event::FocusLost => {
+                    self.controls.set_focused(false);
+                    continue;
+                },
+                _ => continue,
+            };
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if ctrl_c || (key.code == KeyCode::Esc && !self.menu.open) {
+                return Ok(None);
+            }
+            if key.kind == KeyEventKind::Release {
+                if let Some((keypad, key)) = key_name(key.code).and_then(|name| self.controls.key(&name)) {
+                    self.held_until[keypad as usize][key as usize] = None;
+                }
+                continue;
+            }
+            if key.code == KeyCode::F(1) || (self.menu.open && key.code == KeyCode::Esc) {
+                self.menu.open = !self.menu.open;
+                self.held_until = [[None; 16]; 2];
+                // The game's drawn over the menu again
+                self.last_screen.clear();
+                self.menu_shown.clear();
+                crossterm::execute!(self.stdout, terminal::Clear(terminal::ClearType::All)).map_err(|e| e.to_string())?;
+                continue;
+            }
+            if self.menu.open {
+                match key.code {
+                    KeyCode::Up => self.menu.select(false),
+                    KeyCode::Down => self.menu.select(true),
+                    KeyCode::Left => self.changes.push((self.menu.selected(), false)),
+                    KeyCode::Right | KeyCode::Enter => self.changes.push((self.menu.selected(), true)),
+                    KeyCode::Char('s' | 'S') => self.save = true,
+                    _ => {},
+                }
+                continue;
+            }
+            if key.code == KeyCode::Tab {
+                self.limit = self.limit.next().without_vsync();
+                continue;
+            }
+            // A key the input profile uses is a keypad key before it's a hotkey
+            if let Some((keypad, chip_key)) = key_name(key.code).and_then(|name| self.controls.key(&name)) {
+                self.held_until[keypad as usize][chip_key as usize] = Some(Instant::now() + HOLD);
+                continue;
+            }
+            if let Some(hotkey) = match key.code {
+                KeyCode::Char(c) => Hotkey::from_char(c),
+                _ => None,
+            } {
+                self.controls.hotkey(hotkey);
+            }
+        }
+
+        let now = Instant::now();
+        let mut held = Held::default();
+        for (keypad, held_until) in [Keypad::First, Keypad::Second].into_iter().zip(&mut self.held_until) {
+            for (key, until) in held_until.iter_mut().enumerate() {
+                if until.is_some_and(|until| until <= now) {
+                    *until = None;
+                }
+                held.hold((keypad, key as u8), until.is_some());
+            }
+        }
+        return Ok(Some(held));
+    }
+
+    fn port(&mut self, chip: &mut Chip8) -> Result<(), String> {
+        return self.controls.exchange_port(chip);
+    }
+
+    fn display(&mut self, chip: &Chip8) -> Result<(), String> {
+        let status = format!("chip8 - {}, {}", self.limit, self.controls.status());
+        if status != self.title {
+            crossterm::execute!(self.stdout, terminal::SetTitle(&status)).map_err(|e| e.to_string())?;
+            self.title = status;
+        }
+
+        if self.menu.open {
+            let menu = self.menu.text(chip, &self.controls);
+            if menu != self.menu_shown {
+                crossterm::queue!(self.stdout, cursor::MoveTo(0, 0), terminal::Clear(terminal::ClearType::All))
+                    .map_err(|e| e.to_string())?;
+                // Raw mode doesn't go back to the start of the line by itself
+                self.stdout.write_all(menu.replace('\n', "\r\n").as_bytes()).map_err(|e| e.to_string())?;
+                self.stdout.flush().map_err(|e| e.to_string())?;
+                self.menu_shown = menu;
+            }
+            return Ok(());
+        }
+
+        // Only redraw when something's changed, images are a lot to send every frame
+        let screen = self.controls.palette().screen(chip);
+        if screen != self.last_screen {
+            let image = encode(chip, self.protocol, self.scale, self.controls.rotation(), self.controls.palette())?;
+            crossterm::queue!(self.stdout, cursor::MoveTo(0, 0)).map_err(|e| e.to_string())?;
+            self.stdout.write_all(&image).map_err(|e| e.to_string())?;
+            self.stdout.flush().map_err(|e| e.to_string())?;
+            self.last_screen = screen;
+        }
+        return Ok(());
+    }
+
+    /// Rings the bell when the sound timer starts
+    fn audio(&mut self, sounding: bool) -> Result<(), String> {
+        if sounding && !self.beeping && self.controls.audible() {
+            self.stdout.write_all(b"\x07").map_err(|e| e.to_string())?;
+        }
+        self.beeping = sounding;
+        return Ok(());
+    }
+
+    fn limit(&self) -> FrameLimit {
+        return self.limit;
+    }
+
+    fn paused(&self) -> bool {
+        return self.controls.paused() || self.menu.open;
+    }
+
+    fn adjust(&mut self, chip: &mut Chip8) {
+        for (setting, forward) in self.changes.drain(..) {
+            setting.change(chip, &mut self.controls, forward);
+        }
+        if std::mem::take(&mut self.save) {
+            let saved = settings::save(&self.rom, chip, &self.controls);
+            self.menu.set_message(saved.unwrap_or_else(|e| e));
+        }
+    }
+}
+
+/// A key by the name input profiles give it
+fn key_name(code: KeyCode) -> Option<String> {
+    return match code {
+        KeyCode::Char(c) => Some(c.to_ascii_lowercase().to_string()),
+        KeyCode::Up => Some("up".to_string()),
+        KeyCode::Down => Some("down".to_string()),
+        KeyCode::Left => Some("left".to_string()),
+        KeyCode::Right => Some("right".to_string()),
+        _ => None,
+    };
+}
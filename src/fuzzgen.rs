@@ -0,0 +1,165 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Random roms for stress testing the interpreter (chip8 fuzzgen, and chip8 difftest --random).
+// Random bytes mostly fall over within a few instructions, on an unknown opcode or a return
+// with nothing on the stack, so these are random programs instead: they only use plain
+// CHIP-8 instructions, never modify their own code, and run until they're stopped.
+//
+// A rom is a main block, a few subroutines and some data, laid out in that order from 0x200:
+//
+// - Jumps only go forward within their block, and the main block ends by jumping back to its
+//   start, so every loop goes through the whole of main.
+// - Subroutines end in a return and only call the subroutines after them, so calls can't
+//   recurse and the stack never holds more than there are subroutines.
+// - I is only ever set to somewhere near the start of the data or to a font character, with
+//   at most one FX1E after it, so loads, stores and sprites stay inside memory.
+// - A skip is always followed by a single plain instruction, never a jump, call, return or
+//   the two instructions BNNN needs.
+// - Nothing waits for a key, since nothing presses one.
+
+/// The most subroutines a rom has, well under the 16 calls the stack holds
+const MAX_SUBROUTINES: usize = 8;
+
+/// The longest main block, so the rom fits in memory with the most and longest subroutines
+pub const MAX_INSTRUCTIONS: usize = 512;
+
+/// How big the data is, enough for I to be anywhere in its first 16 bytes plus an FX1E of up
+/// to 255 and a 16 byte load or store from there
+const DATA: usize = 0x120;
+
+
+/// One instruction, or a few that have to stay together, before addresses are worked out
+enum Item {
+    Op(u16),
+    /// A skip and the instruction it skips
+    Skip(u16, u16),
+    /// A jump to an item further on in the same block
+    Jump(usize),
+    /// BNNN to an item further on in the same block, after setting V0 to the offset
+    JumpV0(usize, u8),
+    Call(usize),
+    Return,
+    /// ANNN somewhere in the data's first 16 bytes, and an FX1E after it if there's an x
+    Index(u16, Option<u8>),
+}
+
+impl Item {
+    fn len(&self) -> u16 {
+        return match self {
+            Self::Skip(..) | Self::JumpV0(..) => 4,
+            Self::Index(_, Some(_)) => 4,
+            _ => 2,
+        };
+    }
+}
+
+/// A rom from the seed with a main block of `instructions` items, up to MAX_INSTRUCTIONS
+pub fn generate(seed: u64, instructions: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let subroutines = rng.gen_range(0..=MAX_SUBROUTINES);
+
+    let mut blocks = vec![block(&mut rng, instructions.clamp(1, MAX_INSTRUCTIONS), 0, subroutines)];
+    for n in 0..subroutines {
+        let len = rng.gen_range(4..=32);
+        let mut sub = block(&mut rng, len, n + 1, subroutines);
+        sub.push(Item::Return);
+        blocks.push(sub);
+    }
+    blocks[0].push(Item::Jump(0));
+
+    // Where each block and each item in it starts
+    let mut starts = Vec::new();
+    let mut addr = 0x200;
+    for block in &blocks {
+        let mut items = Vec::new();
+        for item in block {
+            items.push(addr);
+            addr += item.len();
+        }
+        starts.push(items);
+    }
+    let data = addr;
+
+    let mut rom = Vec::new();
+    let mut emit = |opcode: u16| rom.extend_from_slice(&opcode.to_be_bytes());
+    for (b, block) in blocks.iter().enumerate() {
+        for item in block {
+            match *item {
+                Item::Op(opcode) => emit(opcode),
+                Item::Skip(skip, opcode) => {
+                    emit(skip);
+                    emit(opcode);
+                },
+                Item::Jump(to) => emit(0x1000 | starts[b][to]),
+                Item::JumpV0(to, offset) => {
+                    emit(0x6000 | offset as u16);
+                    emit(0xB000 | (starts[b][to] - offset as u16));
+                },
+                Item::Call(sub) => emit(0x2000 | starts[sub + 1][0]),
+                Item::Return => emit(0x00EE),
+                Item::Index(offset, add) => {
+                    emit(0xA000 | (data + offset));
+                    if let Some(x) = add {
+                        emit(0xF01E | (x as u16) << 8);
+                    }
+                },
+            }
+        }
+    }
+    rom.extend((0..DATA).map(|_| rng.gen::<u8>()));
+    return rom;
+}
+
+/// A block of `len` items, where block `n` can call subroutines n to `subroutines` - 1
+fn block(rng: &mut StdRng, len: usize, n: usize, subroutines: usize) -> Vec<Item> {
+    let mut items = Vec::with_capacity(len);
+    for i in 0..len {
+        // Jumps need something further on to land on
+        let room = len - i - 1;
+        let item = match rng.gen_range(0..20) {
+            0 if room > 0 => Item::Jump(rng.gen_range(i + 1..len)),
+            1 if room > 0 => Item::JumpV0(rng.gen_range(i + 1..len), rng.gen_range(0..8)),
+            2 if n < subroutines => Item::Call(rng.gen_range(n..subroutines)),
+            3..=5 => Item::Skip(skip(rng), plain(rng)),
+            6 | 7 => Item::Index(rng.gen_range(0..16), rng.gen_bool(0.3).then(|| rng.gen_range(0..16))),
+            _ => Item::Op(plain(rng)),
+        };
+        items.push(item);
+    }
+    return items;
+}
+
+/// An instruction that doesn't change the flow of the program
+fn plain(rng: &mut StdRng) -> u16 {
+    let x = rng.gen_range(0..16) << 8;
+    let y = rng.gen_range(0..16) << 4;
+    let nn = rng.gen::<u8>() as u16;
+    return match rng.gen_range(0..16) {
+        0 => 0x00E0,
+        1 | 2 => 0x6000 | x | nn,
+        3 | 4 => 0x7000 | x | nn,
+        5..=7 => 0x8000 | x | y | [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE][rng.gen_range(0..9)],
+        8 => 0xC000 | x | nn,
+        9 | 10 => 0xD000 | x | y | rng.gen_range(0..16),
+        11 => 0xF007 | x,
+        12 => [0xF015, 0xF018][rng.gen_range(0..2)] | x,
+        13 => 0xF029 | x,
+        14 => 0xF033 | x,
+        _ => [0xF055, 0xF065][rng.gen_range(0..2)] | x,
+    };
+}
+
+fn skip(rng: &mut StdRng) -> u16 {
+    let x = rng.gen_range(0..16) << 8;
+    let y = rng.gen_range(0..16) << 4;
+    let nn = rng.gen::<u8>() as u16;
+    return match rng.gen_range(0..6) {
+        0 => 0x3000 | x | nn,
+        1 => 0x4000 | x | nn,
+        2 => 0x5000 | x | y,
+        3 => 0x9000 | x | y,
+        4 => 0xE09E | x,
+        _ => 0xE0A1 | x,
+    };
+}
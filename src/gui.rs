@@ -0,0 +1,1257 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use eframe::egui;
+
+use crate::analyze;
+use crate::chip::Chip8;
+use crate::config::Config;
+use crate::audio::Beep;
+use crate::cart::{self, Cart};
+use crate::cheat::{self, CheatEngine, CheatKind};
+use crate::expr::{Expr, Watch};
+use crate::frontend::controls::{Controls, Hotkey};
+use crate::frontend::filter::Filter;
+use crate::frontend::input::{self, Held, Keypad};
+use crate::frontend::limiter::FrameLimit;
+use crate::frontend::rotation::Rotation;
+use crate::frontend::settings::{self, Palette, Setting};
+use crate::isa;
+use crate::octo;
+use crate::profile::Profile;
+use crate::symbols;
+use crate::timeline::{Event, EventKind, Timeline};
+use crate::tour;
+
+// The desktop frontend: the game in the middle with the debugger panels as windows that
+// can be opened from the View menu and dragged anywhere around it. F1 pauses with the menu of
+// settings (frontend/settings.rs), P pauses (the gui makes no sound, so it has none of the
+// sound hotkeys in controls.rs) while nothing's being typed into, a rom (or a zipped one, or
+// an Octo cartridge) dropped on the window is switched to, and keys map onto the keypad
+// through the rom's input profile (see frontend/input.rs), by default the usual way:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+//
+// The Timeline window shows what happened in which frame, and clicking or dragging through it
+// takes the game back to that frame exactly as it was (see timeline.rs), paused there.
+
+/// The keys input profiles can use, by the names they give them
+const KEYMAP: [(egui::Key, &str); 36] = [
+    (egui::Key::Num1, "1"), (egui::Key::Num2, "2"), (egui::Key::Num3, "3"), (egui::Key::Num4, "4"),
+    (egui::Key::Q, "q"), (egui::Key::W, "w"), (egui::Key::E, "e"), (egui::Key::R, "r"),
+    (egui::Key::A, "a"), (egui::Key::S, "s"), (egui::Key::D, "d"), (egui::Key::F, "f"),
+    (egui::Key::Z, "z"), (egui::Key::X, "x"), (egui::Key::C, "c"), (egui::Key::V, "v"),
+    (egui::Key::Num6, "6"), (egui::Key::Num7, "7"), (egui::Key::Num8, "8"), (egui::Key::Num9, "9"),
+    (egui::Key::Y, "y"), (egui::Key::U, "u"), (egui::Key::I, "i"), (egui::Key::O, "o"),
+    (egui::Key::H, "h"), (egui::Key::J, "j"), (egui::Key::K, "k"), (egui::Key::L, "l"),
+    (egui::Key::N, "n"), (egui::Key::M, "m"), (egui::Key::Comma, ","), (egui::Key::Period, "."),
+    (egui::Key::ArrowUp, "up"), (egui::Key::ArrowDown, "down"), (egui::Key::ArrowLeft, "left"), (egui::Key::ArrowRight, "right"),
+];
+
+const HOTKEYS: [(egui::Key, Hotkey); 1] = [(egui::Key::P, Hotkey::Pause)];
+
+/// The keypad as laid out on the COSMAC VIP, row by row
+const KEYPAD: [u8; 16] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+/// How many bytes the memory panel shows, 16 to a row
+const MEMORY_ROWS: u32 = 16;
+
+/// The memory panel's highlights: the instruction at PC, the byte at I, and bytes the rom
+/// wrote in the last WRITE_FADE seconds, which the watch panel also uses for values that
+/// changed
+const PC_COLOUR: egui::Color32 = egui::Color32::from_rgb(40, 70, 130);
+const I_COLOUR: egui::Color32 = egui::Color32::from_rgb(110, 60, 20);
+const WRITE_COLOUR: egui::Color32 = egui::Color32::from_rgb(255, 200, 60);
+const WRITE_FADE: f64 = 1.0;
+
+/// How many sprites the sprite panel shows one after another from its address, and how big
+/// their pixels are
+const SPRITES_SHOWN: u32 = 8;
+const SPRITE_PIXEL: f32 = 6.0;
+
+/// Speeds the settings can jump straight to, in instructions per frame, for the machines
+/// roms were written for
+const SPEED_PRESETS: [(usize, &str); 4] = [(7, "VIP"), (15, "fast VIP"), (30, "SUPER-CHIP"), (1000, "XO-CHIP")];
+
+/// How long an uncapped machine runs each repaint, leaving the rest of the display's frame
+/// for drawing the gui
+const UNCAPPED_BUDGET: Duration = Duration::from_millis(12);
+
+/// How often the gui repaints while the machine's idle or stopped
+const IDLE_REPAINT: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// How often chip8 dev looks at whether the source has been saved, in seconds
+const SOURCE_POLL: f64 = 0.5;
+
+/// The heat map overlay's colours for pixels recently drawn to and collided on, fading out
+/// over the chosen number of frames
+const DRAW_HEAT: egui::Color32 = egui::Color32::from_rgb(255, 160, 0);
+const COLLISION_HEAT: egui::Color32 = egui::Color32::from_rgb(255, 0, 60);
+
+/// The timeline's colours for each kind of event, in the order of EventKind::ALL, and how
+/// tall each kind's row of its strip is
+const EVENT_COLOURS: [egui::Color32; 4] = [
+    egui::Color32::from_rgb(255, 160, 0),
+    egui::Color32::from_rgb(80, 200, 255),
+    egui::Color32::from_rgb(120, 230, 120),
+    egui::Color32::from_rgb(220, 120, 255),
+];
+const TIMELINE_ROW: f32 = 12.0;
+
+
+/// Which of the debugger windows are open
+#[derive(Default)]
+struct Panels {
+    registers: bool,
+    memory: bool,
+    disassembly: bool,
+    stack: bool,
+    sprites: bool,
+    watches: bool,
+    cheats: bool,
+    keypad: bool,
+    settings: bool,
+    tour: bool,
+    timeline: bool,
+}
+
+/// Makes a machine with a rom loaded, set up the way its cartridge says if it came in one
+type Loader = Box<dyn Fn(&Cart) -> Chip8>;
+
+/// Looks at the machine as it's left when the window closes
+type OnExit = Box<dyn Fn(&Chip8)>;
+
+/// The Octo source chip8 dev is running
+struct Source {
+    path: PathBuf,
+    /// When it was last saved as of the last look, and when that look was in egui's time
+    modified: Option<SystemTime>,
+    checked: f64,
+}
+
+pub struct Gui {
+    chip: Chip8,
+    screen: Option<egui::TextureHandle>,
+    panels: Panels,
+    running: bool,
+    error: Option<String>,
+    /// The rom, if the gui was told it, for suggesting and saving its speed
+    rom: Option<Vec<u8>>,
+    /// The speed analyze suggests for the rom, and why
+    suggested_speed: Option<(usize, &'static str)>,
+    /// How saving the speed to the rom's profile went
+    speed_saved: Option<String>,
+    limit: FrameLimit,
+    filter: Filter,
+    /// How many times its size the screen was shown at last repaint, which the filter
+    /// scales it up by as near as it can
+    screen_scale: f32,
+    /// How saving the rotation to the rom's profile went
+    rotation_saved: Option<String>,
+    controls: Controls,
+    /// Makes a machine for another rom the same way the first was made, if the gui can switch
+    loader: Option<Loader>,
+    /// Called with the machine as it is when the window closes
+    exit: Option<OnExit>,
+    source: Option<Source>,
+    /// Time not yet run, so the machine keeps to the limit whatever the display's refresh rate
+    pending: f32,
+    /// When the second being counted started in egui's time, and the frames and
+    /// instructions run since then
+    counted: (f64, u32, u64),
+    /// How many frames and instructions the second before that ran
+    frame_rate: u32,
+    instruction_rate: u64,
+    /// Whether the frame and instruction rates are shown over the screen
+    hud: bool,
+    /// Whether the pause menu's open, and how saving its settings went
+    pause_menu: bool,
+    settings_saved: Option<String>,
+    /// Whether the screen is overlaid with where sprites were drawn in the last heat_frames
+    heat_map: bool,
+    heat_frames: u64,
+    memory_addr: u32,
+    /// The byte being edited in the memory panel and the hex typed so far
+    memory_edit: Option<(u32, String)>,
+    /// What the memory panel showed last time and where from, to spot writes by comparing
+    memory_shown: (u32, Vec<u8>),
+    /// When each byte shown was last seen to change, in egui's time
+    memory_written: Vec<f64>,
+    /// Where the sprite panel reads sprites from, and whether that follows I
+    sprite_addr: u32,
+    sprite_follow: bool,
+    /// The height of the sprites shown, 0 for SUPER-CHIP's 16x16 sprites as with DXY0
+    sprite_rows: u8,
+    /// The watched expressions, each with when it last changed in egui's time
+    watches: Vec<(Watch, f64)>,
+    /// The expression being typed into the watch panel, and why it didn't parse if it didn't
+    watch_input: String,
+    watch_error: Option<String>,
+    /// The rom's cheats and the freezes they hold, and why its cheat file didn't load if it
+    /// didn't
+    cheats: CheatEngine,
+    cheats_error: Option<String>,
+    keypad_clicked: Option<u8>,
+    /// What the tour's last step changed
+    tour_changes: Option<String>,
+    /// What's happened in which frame, and which kinds of event the timeline shows, in the
+    /// order of EventKind::ALL
+    timeline: Timeline,
+    timeline_shown: [bool; 4],
+}
+
+impl Gui {
+    pub fn new(chip: Chip8) -> Self {
+        return Self {
+            memory_addr: chip.pc() as u32,
+            chip,
+            screen: None,
+            panels: Panels::default(),
+            running: true,
+            error: None,
+            rom: None,
+            suggested_speed: None,
+            speed_saved: None,
+            limit: FrameLimit::Fixed,
+            filter: Filter::Nearest,
+            screen_scale: 1.0,
+            rotation_saved: None,
+            controls: Controls::new(Beep::default(), true).silent(),
+            loader: None,
+            exit: None,
+            source: None,
+            pending: 0.0,
+            counted: (0.0, 0, 0),
+            frame_rate: 0,
+            instruction_rate: 0,
+            hud: false,
+            pause_menu: false,
+            settings_saved: None,
+            heat_map: false,
+            heat_frames: 30,
+            memory_edit: None,
+            memory_shown: (0, Vec::new()),
+            memory_written: Vec::new(),
+            sprite_addr: 0,
+            sprite_follow: true,
+            sprite_rows: 5,
+            watches: Vec::new(),
+            watch_input: String::new(),
+            watch_error: None,
+            cheats: CheatEngine::new(),
+            cheats_error: None,
+            keypad_clicked: None,
+            tour_changes: None,
+            timeline: Timeline::new(),
+            timeline_shown: [true; 4],
+        };
+    }
+
+    /// Tells the gui which rom is running, so the settings can suggest a speed for it and
+    /// save the one chosen to its profile
+    pub fn with_rom(mut self, rom: &[u8]) -> Self {
+        self.set_rom(rom);
+        return self;
+    }
+
+    /// Paces the machine by limit instead of at 60Hz. egui repaints in step with the display,
+    /// so vsync runs a frame each repaint
+    pub fn with_limit(mut self, limit: FrameLimit) -> Self {
+        self.limit = limit;
+        return self;
+    }
+
+    /// Shows the screen through the filter rather than as plain blocks
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        return self;
+    }
+
+    /// Starts the hotkeys from the rom's beep and focus setting rather than the defaults
+    pub fn with_controls(mut self, controls: Controls) -> Self {
+        self.controls = controls.silent();
+        return self;
+    }
+
+    /// Lets the gui switch to other roms, from the Roms menu or dropped on the window, putting
+    /// them on machines made by load
+    pub fn with_loader(mut self, load: impl Fn(&Cart) -> Chip8 + 'static) -> Self {
+        self.loader = Some(Box::new(load));
+        return self;
+    }
+
+    /// Hands the machine to exit as it is when the window closes, e.g. to write out its state
+    pub fn with_exit(mut self, exit: impl Fn(&Chip8) + 'static) -> Self {
+        self.exit = Some(Box::new(exit));
+        return self;
+    }
+
+    /// Runs the Octo source at path instead of a rom, for chip8 dev, which needs a loader
+    /// first. It's assembled and loaded straight away and again every time it's saved.
+    /// Assembler errors show where the rom's errors do, with the last program that assembled
+    /// left running
+    pub fn with_source(mut self, path: &Path) -> Self {
+        self.source = Some(Source { path: path.to_path_buf(), modified: None, checked: 0.0 });
+        self.reload();
+        return self;
+    }
+
+    /// Opens the window and runs until it's closed
+    pub fn run(self) -> Result<(), String> {
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default().with_inner_size([960.0, 600.0]),
+            ..Default::default()
+        };
+        return eframe::run_native("chip8", options, Box::new(|_| Ok(Box::new(self)))).map_err(|e| e.to_string());
+    }
+
+    /// Runs a single instruction, pausing with the error if it fails, or when the rom exits
+    fn step(&mut self) {
+        let (pc, sp, sound) = (self.chip.pc(), self.chip.sp(), self.chip.sound());
+        let result = self.chip.execute();
+        self.timeline.instruction(&self.chip, pc, sp, sound);
+        if let Err(e) = result {
+            self.error = Some(format!("{e}\n{}", symbols::backtrace(self.chip.pc(), self.chip.call_stack(), None)));
+            self.running = false;
+        } else if self.chip.exit_requested() {
+            self.error = Some(format!("The rom exited with 00FD at {:04X}", self.chip.pc()));
+            self.running = false;
+        }
+    }
+
+    fn run_frames(&mut self, dt: f32) {
+        match self.limit.frame() {
+            Some(frame) => {
+                let frame = frame.as_secs_f32();
+                self.pending = (self.pending + dt).min(0.25);
+                while self.pending >= frame && self.running {
+                    self.pending -= frame;
+                    self.run_frame();
+                }
+            },
+            None if self.limit == FrameLimit::Vsync => self.run_frame(),
+            None => {
+                let start = Instant::now();
+                while start.elapsed() < UNCAPPED_BUDGET && self.running && !self.chip.idle() {
+                    self.run_frame();
+                }
+            },
+        }
+    }
+
+    fn run_frame(&mut self) {
+        self.timeline.start_frame(&mut self.chip);
+        for _ in 0..self.chip.cycles_per_frame() {
+            if !self.running {
+                return;
+            }
+            // Running on from idle changes nothing, so the rest of the frame is skipped
+            if self.chip.idle() {
+                break;
+            }
+            self.step();
+            self.counted.2 += 1;
+        }
+        self.cheats.apply(&mut self.chip);
+        self.chip.tick_timers();
+        self.counted.1 += 1;
+        self.timeline.end_frame();
+    }
+
+    /// Takes the machine to the start of a frame in the timeline, running forward from the
+    /// snapshot before it, and pauses there
+    fn seek(&mut self, frame: u64) {
+        let Some(frame) = self.timeline.seek(&mut self.chip, frame) else {
+            return;
+        };
+        self.running = true;
+        self.error = None;
+        while self.timeline.frame() < frame && self.running {
+            self.run_frame();
+        }
+        self.running = false;
+    }
+
+    fn set_rom(&mut self, rom: &[u8]) {
+        let analysis = analyze::analyze(rom);
+        self.suggested_speed = Some((analysis.speed, analysis.speed_reason));
+        self.speed_saved = None;
+        self.rotation_saved = None;
+        self.settings_saved = None;
+        match cheat::load_cheats(rom) {
+            Ok(cheats) => {
+                self.cheats.set_cheats(cheats);
+                self.cheats_error = None;
+            },
+            Err(e) => {
+                self.cheats.set_cheats(Vec::new());
+                self.cheats_error = Some(format!("{}: {e}", cheat::cheats_path(rom).display()));
+            },
+        }
+        self.rom = Some(rom.to_vec());
+    }
+
+    /// Looks at whether the source has been saved since it was last assembled, every SOURCE_POLL
+    /// seconds, and reloads it if it has
+    fn watch_source(&mut self, now: f64) {
+        let Some(source) = &mut self.source else {
+            return;
+        };
+        if now - source.checked < SOURCE_POLL {
+            return;
+        }
+        source.checked = now;
+        let modified = std::fs::metadata(&source.path).and_then(|metadata| metadata.modified()).ok();
+        if modified != source.modified {
+            self.reload();
+        }
+    }
+
+    /// Assembles the source and starts it from scratch, or shows why it didn't assemble
+    fn reload(&mut self) {
+        let Some(source) = &mut self.source else {
+            return;
+        };
+        source.modified = std::fs::metadata(&source.path).and_then(|metadata| metadata.modified()).ok();
+        let assembled = std::fs::read_to_string(&source.path)
+            .map_err(|e| format!("{}: {e}", source.path.display()))
+            .and_then(|text| octo::assemble(&text).map_err(|e| format!("{}: {e}", source.path.display())));
+        match assembled {
+            Ok(rom) => self.switch_rom(&Cart { rom, speed: None, platform: None, rotation: None }),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Switches to the first file dropped on the window, showing why if it can't be read
+    fn open_dropped(&mut self, ctx: &egui::Context) {
+        let dropped = ctx.input_mut(|i| std::mem::take(&mut i.raw.dropped_files));
+        let Some(path) = dropped.into_iter().find_map(|file| file.path) else {
+            return;
+        };
+        match cart::read(&path) {
+            Ok(cart) => self.switch_rom(&cart),
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Starts another rom from scratch on a machine from the loader
+    fn switch_rom(&mut self, cart: &Cart) {
+        let Some(load) = &self.loader else {
+            return;
+        };
+        self.chip = load(cart);
+        self.timeline = Timeline::new();
+        self.memory_addr = self.chip.pc() as u32;
+        self.running = true;
+        self.error = None;
+        self.set_rom(&cart.rom);
+        let profile = Profile::load(&cart.rom).unwrap_or_default();
+        self.controls.set_rotation(profile.rotation.or(cart.rotation).unwrap_or_default());
+        let config = Config::load().ok().flatten().unwrap_or_default();
+        self.controls.set_input(profile.input.or(config.input).unwrap_or(&input::STANDARD));
+        self.controls.set_palette(profile.palette.unwrap_or_default());
+    }
+
+    /// Counts the frames and instructions run each second, for the settings and the HUD
+    fn count_frames(&mut self, now: f64) {
+        let (start, frames, instructions) = self.counted;
+        if now - start >= 1.0 {
+            self.frame_rate = frames;
+            self.instruction_rate = instructions;
+            self.counted = (now, 0, 0);
+        }
+    }
+
+    fn update_controls(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.key_pressed(egui::Key::F1)) {
+            self.pause_menu = !self.pause_menu;
+        }
+        if ctx.memory(|memory| memory.focused().is_none()) {
+            for (key, hotkey) in HOTKEYS {
+                if ctx.input(|i| i.key_pressed(key)) {
+                    self.controls.hotkey(hotkey);
+                }
+            }
+        }
+        self.controls.set_focused(ctx.input(|i| i.viewport().focused).unwrap_or(true));
+    }
+
+    fn update_keys(&mut self, ctx: &egui::Context) {
+        // The keyboard's directional keys turn with the screen, the keypad panel's don't
+        let mut held = Held::default();
+        for (key, name) in KEYMAP {
+            if let Some(chip_key) = self.controls.key(name) {
+                held.hold(chip_key, ctx.input(|i| i.key_down(key)));
+            }
+        }
+        if let Some(clicked) = self.keypad_clicked {
+            held.hold((Keypad::First, clicked), true);
+        }
+        held.apply(&mut self.chip);
+    }
+
+    /// The screen as an image, scaled up by factor through the filter if that's more than 1
+    fn screen_image(&self, factor: usize) -> egui::ColorImage {
+        let framebuffer = self.chip.framebuffer();
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let colour = match framebuffer.colour_zones() {
+                    // CHIP-8X brings its own colours, otherwise the palette setting is used
+                    Some(_) => colour(framebuffer.rgb(x, y)),
+                    None if framebuffer.get(x, y) != 0 => colour(self.controls.palette().on),
+                    None => colour(self.controls.palette().off),
+                };
+                pixels.push(self.heat(colour, y * width + x));
+            }
+        }
+        let (pixels, width, height) = self.controls.rotation().apply(&pixels, width, height);
+        if factor == 1 {
+            return egui::ColorImage { size: [width, height], pixels };
+        }
+
+        let screen: Vec<u32> = pixels.iter().map(|colour| u32::from_be_bytes([0, colour.r(), colour.g(), colour.b()])).collect();
+        let mut rgba = vec![0; width * factor * height * factor * 4];
+        self.filter.apply(&screen, width, factor, &mut rgba);
+        return egui::ColorImage::from_rgba_unmultiplied([width * factor, height * factor], &rgba);
+    }
+
+    /// Tints a pixel of the screen with the heat map overlay, collisions over draws, the
+    /// more recent the stronger
+    fn heat(&self, colour: egui::Color32, pixel: usize) -> egui::Color32 {
+        let Some(draw_map) = self.chip.draw_map().filter(|_| self.heat_map) else {
+            return colour;
+        };
+
+        let strength = |ago: u64| 1.0 - ago as f32 / self.heat_frames as f32;
+        let heat = match (draw_map.collided_ago(pixel), draw_map.toggled_ago(pixel)) {
+            (Some(ago), _) if ago < self.heat_frames => (COLLISION_HEAT, strength(ago)),
+            (_, Some(ago)) if ago < self.heat_frames => (DRAW_HEAT, strength(ago)),
+            _ => return colour,
+        };
+        return colour.lerp_to_gamma(heat.0, heat.1 * 0.8);
+    }
+
+    fn menu(&mut self, ui: &mut egui::Ui) {
+        egui::menu::bar(ui, |ui| {
+            ui.menu_button("View", |ui| {
+                ui.checkbox(&mut self.panels.registers, "Registers");
+                ui.checkbox(&mut self.panels.memory, "Memory");
+                ui.checkbox(&mut self.panels.disassembly, "Disassembly");
+                ui.checkbox(&mut self.panels.stack, "Stack");
+                ui.checkbox(&mut self.panels.sprites, "Sprites");
+                ui.checkbox(&mut self.panels.watches, "Watches");
+                ui.checkbox(&mut self.panels.cheats, "Cheats");
+                ui.checkbox(&mut self.panels.keypad, "Keypad");
+                ui.checkbox(&mut self.panels.settings, "Settings");
+                ui.checkbox(&mut self.panels.tour, "Tour");
+                ui.checkbox(&mut self.panels.timeline, "Timeline");
+                ui.separator();
+                ui.checkbox(&mut self.hud, "Performance HUD");
+            });
+            #[cfg(feature = "bundled")]
+            if self.loader.is_some() {
+                ui.menu_button("Roms", |ui| {
+                    for bundled in &crate::bundled::ROMS {
+                        if ui.button(bundled.name).on_hover_text(bundled.description).clicked() {
+                            self.switch_rom(&Cart { rom: bundled.rom(), speed: None, platform: None, rotation: None });
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+            ui.separator();
+            if ui.button(if self.running { "Pause" } else { "Run" }).clicked() {
+                self.running = !self.running;
+                self.error = None;
+            }
+            if ui.add_enabled(!self.running, egui::Button::new("Step")).clicked() {
+                self.step();
+                self.timeline.changed_by_hand(&mut self.chip);
+            }
+            ui.label(self.controls.status());
+            if let Some(error) = &self.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, error.lines().next().unwrap_or_default());
+            }
+        });
+    }
+
+    fn registers(&self, ui: &mut egui::Ui) {
+        egui::Grid::new("registers").striped(true).show(ui, |ui| {
+            for (i, value) in self.chip.registers().iter().enumerate() {
+                ui.monospace(format!("V{i:X}"));
+                ui.monospace(format!("{value:02X}"));
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+        ui.separator();
+        ui.monospace(format!("PC {:04X}  I {:04X}  SP {:X}", self.chip.pc(), self.chip.ar(), self.chip.sp()));
+        ui.monospace(format!("DT {:02X}    ST {:02X}", self.chip.delay(), self.chip.sound()));
+    }
+
+    fn memory(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Start");
+            ui.add(egui::DragValue::new(&mut self.memory_addr).hexadecimal(4, false, true).speed(16));
+            if ui.button("Go to PC").clicked() {
+                self.memory_addr = self.chip.pc() as u32;
+            }
+            if ui.button("Go to I").clicked() {
+                self.memory_addr = self.chip.ar();
+            }
+        });
+
+        let start = self.memory_addr & !0xF;
+        let bytes = self.chip.read_mem(start, MEMORY_ROWS as usize * 16).to_vec();
+        let now = ui.input(|i| i.time);
+        self.spot_writes(start, &bytes, now);
+
+        let (pc, i) = (self.chip.pc() as u32, self.chip.ar());
+        egui::Grid::new("memory").spacing([6.0, 2.0]).show(ui, |ui| {
+            for (row, chunk) in bytes.chunks(16).enumerate() {
+                let row_addr = start + row as u32 * 16;
+                ui.monospace(format!("{row_addr:04X}"));
+                for (col, byte) in chunk.iter().enumerate() {
+                    let addr = row_addr + col as u32;
+                    if self.memory_edit.as_ref().is_some_and(|(edit_addr, _)| *edit_addr == addr) {
+                        self.edit_byte(ui);
+                        continue;
+                    }
+
+                    let mut text = egui::RichText::new(format!("{byte:02X}")).monospace();
+                    if addr == pc || addr == pc + 1 {
+                        text = text.background_color(PC_COLOUR);
+                    } else if addr == i {
+                        text = text.background_color(I_COLOUR);
+                    }
+                    if now - self.memory_written[row * 16 + col] < WRITE_FADE {
+                        text = text.color(WRITE_COLOUR);
+                    }
+                    if ui.add(egui::Label::new(text).sense(egui::Sense::click())).clicked() {
+                        self.memory_edit = Some((addr, String::new()));
+                    }
+                }
+                ui.end_row();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(" PC ").monospace().background_color(PC_COLOUR));
+            ui.label(egui::RichText::new(" I ").monospace().background_color(I_COLOUR));
+            ui.label(egui::RichText::new("written").monospace().color(WRITE_COLOUR));
+            ui.label("Click a byte to edit it");
+        });
+    }
+
+    /// Compares the bytes shown against last time to find the ones that have been written
+    fn spot_writes(&mut self, start: u32, bytes: &[u8], now: f64) {
+        let (shown_start, shown) = &self.memory_shown;
+        if *shown_start != start || shown.len() != bytes.len() {
+            // Moving the view starts afresh, nothing on the new page has been seen change yet
+            self.memory_written = vec![f64::NEG_INFINITY; bytes.len()];
+        } else {
+            for (i, (old, new)) in shown.iter().zip(bytes).enumerate() {
+                if old != new {
+                    self.memory_written[i] = now;
+                }
+            }
+        }
+        self.memory_shown = (start, bytes.to_vec());
+    }
+
+    /// The hex editor cell for the byte being edited. Typing two digits writes the byte and
+    /// moves on to the next one, Enter writes a single digit, and Escape stops editing
+    fn edit_byte(&mut self, ui: &mut egui::Ui) {
+        let Some((addr, text)) = &mut self.memory_edit else {
+            return;
+        };
+        let edit = egui::TextEdit::singleline(text)
+            .char_limit(2)
+            .desired_width(16.0)
+            .font(egui::TextStyle::Monospace)
+            .margin(egui::Margin::ZERO);
+        let response = ui.add(edit);
+        response.request_focus();
+        text.retain(|c| c.is_ascii_hexdigit());
+
+        let addr = *addr;
+        let enter = ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.memory_edit = None;
+        } else if text.len() == 2 || (enter && !text.is_empty()) {
+            let value = u8::from_str_radix(text, 16).expect("only hex digits are kept");
+            self.chip.write_mem(addr, &[value]);
+            self.timeline.changed_by_hand(&mut self.chip);
+            self.memory_edit = Some((addr + 1, String::new()));
+        }
+    }
+
+    fn disassembly(&self, ui: &mut egui::Ui) {
+        let pc = self.chip.pc() as u32;
+        for row in 0..16 {
+            let addr = pc + row * 2;
+            let bytes = self.chip.read_mem(addr, 2);
+            if bytes.len() < 2 {
+                break;
+            }
+            let opcode = (bytes[0] as u16) << 8 | bytes[1] as u16;
+            let text = format!(
+                "{} {addr:04X}: {opcode:04X}  {}",
+                if row == 0 { ">" } else { " " },
+                isa::disassemble(opcode, self.chip.platform())
+            );
+            ui.monospace(text).on_hover_text(isa::explain(opcode, self.chip.platform()));
+        }
+    }
+
+    fn stack(&self, ui: &mut egui::Ui) {
+        ui.monospace(symbols::backtrace(self.chip.pc(), self.chip.call_stack(), None));
+    }
+
+    /// Memory drawn as sprites. Following I, the size comes from the instruction at PC when
+    /// it's a DXYN, so the first sprite is exactly what's about to be drawn
+    fn sprites(&mut self, ui: &mut egui::Ui) {
+        if self.sprite_follow {
+            self.sprite_addr = self.chip.ar();
+            let pc = self.chip.pc() as u32;
+            if let [high, low] = *self.chip.read_mem(pc, 2) {
+                if high & 0xF0 == 0xD0 {
+                    self.sprite_rows = low & 0xF;
+                }
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.sprite_follow, "Follow I");
+            let addr = egui::DragValue::new(&mut self.sprite_addr).hexadecimal(4, false, true);
+            ui.add_enabled(!self.sprite_follow, addr);
+        });
+        ui.add_enabled(!self.sprite_follow, egui::Slider::new(&mut self.sprite_rows, 0..=15).text("rows (0 for 16x16)"));
+
+        let (width, rows, len) = match self.sprite_rows {
+            0 => (16, 16, 32),
+            rows => (8, rows as usize, rows as u32),
+        };
+        ui.horizontal_wrapped(|ui| {
+            for n in 0..SPRITES_SHOWN {
+                let addr = self.sprite_addr + n * len;
+                let bytes = self.chip.read_mem(addr, len as usize);
+                if bytes.len() < len as usize {
+                    break;
+                }
+                ui.vertical(|ui| {
+                    ui.monospace(format!("{addr:04X}"));
+                    self.sprite(ui, bytes, width, rows);
+                });
+            }
+        });
+    }
+
+    /// Paints one sprite, a byte a row (two for 16 pixel wide sprites) most significant
+    /// bit leftmost, the way DXYN reads it
+    fn sprite(&self, ui: &mut egui::Ui, bytes: &[u8], width: usize, rows: usize) {
+        let size = egui::vec2(width as f32, rows as f32) * SPRITE_PIXEL;
+        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, colour(self.controls.palette().off));
+
+        let bytes_per_row = width / 8;
+        for (y, row) in bytes.chunks(bytes_per_row).take(rows).enumerate() {
+            let bits = row.iter().fold(0u32, |bits, byte| bits << 8 | *byte as u32);
+            for x in 0..width {
+                if bits & (1 << (width - 1 - x)) != 0 {
+                    let min = rect.min + egui::vec2(x as f32, y as f32) * SPRITE_PIXEL;
+                    let pixel = egui::Rect::from_min_size(min, egui::vec2(SPRITE_PIXEL, SPRITE_PIXEL));
+                    painter.rect_filled(pixel, 0.0, colour(self.controls.palette().on));
+                }
+            }
+        }
+    }
+
+    /// Evaluates the watches against the machine as it is now, noting which have changed
+    fn update_watches(&mut self, now: f64) {
+        for (watch, changed) in &mut self.watches {
+            if watch.update(&self.chip).is_some() {
+                *changed = now;
+            }
+        }
+    }
+
+    fn watch_panel(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let response = ui.add(egui::TextEdit::singleline(&mut self.watch_input).hint_text("e.g. mem[I] or V[0A] * 2"));
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.button("Watch").clicked() || entered {
+                match Expr::parse(&self.watch_input) {
+                    Ok(expr) => {
+                        let mut watch = Watch::new(expr);
+                        watch.update(&self.chip);
+                        self.watches.push((watch, f64::NEG_INFINITY));
+                        self.watch_input.clear();
+                        self.watch_error = None;
+                    },
+                    Err(e) => self.watch_error = Some(e),
+                }
+            }
+        });
+        if let Some(error) = &self.watch_error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        }
+
+        let now = ui.input(|i| i.time);
+        let mut removed = None;
+        egui::Grid::new("watches").striped(true).show(ui, |ui| {
+            for (n, (watch, changed)) in self.watches.iter().enumerate() {
+                let value = watch.value().unwrap_or(0);
+                let mut text = egui::RichText::new(format!("{value:X} ({value})")).monospace();
+                if now - *changed < WRITE_FADE {
+                    text = text.color(WRITE_COLOUR);
+                }
+                ui.monospace(watch.expr().source());
+                ui.label(text);
+                if ui.small_button("x").clicked() {
+                    removed = Some(n);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(n) = removed {
+            self.watches.remove(n);
+        }
+    }
+
+    /// The rom's cheats, frozen ones as toggles and written once ones as buttons
+    fn cheat_panel(&mut self, ui: &mut egui::Ui) {
+        if let Some(error) = &self.cheats_error {
+            ui.colored_label(egui::Color32::LIGHT_RED, error);
+        } else if self.cheats.cheats().is_empty() {
+            let path = self.rom.as_deref().map(cheat::cheats_path);
+            ui.label(match path {
+                Some(path) => format!("The rom has no cheats, they go in {}", path.display()),
+                None => "The rom has no cheats".to_string(),
+            });
+        }
+
+        let mut toggled = None;
+        egui::Grid::new("cheats").striped(true).show(ui, |ui| {
+            for (i, (cheat, on)) in self.cheats.cheats().iter().enumerate() {
+                ui.monospace(format!("{:04X} = {:02X}", cheat.addr, cheat.value));
+                match cheat.kind {
+                    CheatKind::Freeze => {
+                        let mut on = *on;
+                        if ui.checkbox(&mut on, &cheat.description).changed() {
+                            toggled = Some((i, on));
+                        }
+                    },
+                    CheatKind::Once => {
+                        let label = if cheat.description.is_empty() { "Write" } else { &cheat.description };
+                        if ui.button(label).clicked() {
+                            toggled = Some((i, true));
+                        }
+                    },
+                }
+                ui.end_row();
+            }
+        });
+        if let Some((i, on)) = toggled {
+            self.cheats.set_cheat(&mut self.chip, i, on);
+        }
+    }
+
+    fn keypad(&mut self, ui: &mut egui::Ui) {
+        self.keypad_clicked = None;
+        egui::Grid::new("keypad").show(ui, |ui| {
+            for (i, key) in KEYPAD.iter().enumerate() {
+                let held = self.chip.state().keys[*key as usize];
+                let button = egui::Button::new(format!("{key:X}")).selected(held).min_size(egui::vec2(32.0, 32.0));
+                if ui.add(button).is_pointer_button_down_on() {
+                    self.keypad_clicked = Some(*key);
+                }
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+
+    /// Calibrating the speed: a slider, the machines roms were written for, what the rom
+    /// looks like it wants, and saving the speed to its profile for next time
+    fn speed(&mut self, ui: &mut egui::Ui) {
+        let mut speed = self.chip.cycles_per_frame();
+        ui.add(egui::Slider::new(&mut speed, 1..=1000).logarithmic(true).text("instructions per frame"));
+        ui.horizontal(|ui| {
+            for (preset, name) in SPEED_PRESETS {
+                if ui.selectable_label(speed == preset, name).on_hover_text(format!("{preset} instructions per frame")).clicked() {
+                    speed = preset;
+                }
+            }
+        });
+        if let Some((suggested, reason)) = self.suggested_speed {
+            ui.horizontal(|ui| {
+                ui.label(format!("Suggested {suggested}: {reason}"));
+                if ui.add_enabled(speed != suggested, egui::Button::new("Use")).clicked() {
+                    speed = suggested;
+                }
+            });
+        }
+        if speed != self.chip.cycles_per_frame() {
+            self.chip.set_cycles_per_frame(speed);
+            self.speed_saved = None;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} instructions a second", speed * 60));
+            if let Some(rom) = &self.rom {
+                if ui.button("Save for this rom").clicked() {
+                    self.speed_saved = Some(match Profile::save_setting(rom, "speed", &speed.to_string()) {
+                        Ok(()) => format!("Saved to {}", Profile::path(rom).display()),
+                        Err(e) => format!("An error occured when saving the profile: {e}"),
+                    });
+                }
+            }
+        });
+        if let Some(saved) = &self.speed_saved {
+            ui.label(saved);
+        }
+    }
+
+    /// Choosing how frames are paced, with how many are actually running
+    fn frame_limit(&mut self, ui: &mut egui::Ui) {
+        let mut limit = self.limit;
+        ui.horizontal(|ui| {
+            ui.label("Frames");
+            for choice in [FrameLimit::Vsync, FrameLimit::Fixed, FrameLimit::Uncapped] {
+                ui.selectable_value(&mut limit, choice, choice.to_string());
+            }
+            let custom = matches!(limit, FrameLimit::Fps(_));
+            if ui.selectable_label(custom, "custom").clicked() && !custom {
+                limit = FrameLimit::Fps(30);
+            }
+            if let FrameLimit::Fps(fps) = &mut limit {
+                ui.add(egui::DragValue::new(fps).range(1..=1000).suffix(" fps"));
+            }
+        });
+        if limit != self.limit {
+            self.limit = limit;
+            self.pending = 0.0;
+        }
+        ui.label(format!("Running {} frames a second", self.frame_rate));
+        ui.checkbox(&mut self.hud, "Show frames and instructions a second over the screen");
+    }
+
+    /// The instructions interpreters disagree over, each switched the other way straight away
+    fn quirks(&mut self, ui: &mut egui::Ui) {
+        let mut quirks = self.chip.quirks();
+        ui.collapsing("Quirks", |ui| {
+            ui.checkbox(&mut quirks.shift_vy, "8XY6 and 8XYE shift VY rather than VX");
+            ui.checkbox(&mut quirks.jump_vx, "BNNN adds VX rather than V0");
+            ui.checkbox(&mut quirks.memory_moves_i, "FX55 and FX65 move I on");
+            ui.checkbox(&mut quirks.vf_reset, "8XY1 to 8XY3 set VF to 0");
+            ui.checkbox(&mut quirks.display_wait, "DXYN waits for the next frame after a draw");
+        });
+        if quirks != self.chip.quirks() {
+            self.chip.set_quirks(quirks);
+        }
+    }
+
+    /// Turning the screen, and saving the rotation to the rom's profile for next time
+    fn rotation(&mut self, ui: &mut egui::Ui) {
+        let mut rotation = self.controls.rotation();
+        ui.horizontal(|ui| {
+            ui.label("Rotation");
+            for choice in Rotation::ALL {
+                ui.selectable_value(&mut rotation, choice, choice.to_string());
+            }
+            if let Some(rom) = &self.rom {
+                if ui.button("Save for this rom").clicked() {
+                    let degrees = rotation.degrees().to_string();
+                    self.rotation_saved = Some(match Profile::save_setting(rom, "rotation", &degrees) {
+                        Ok(()) => format!("Saved to {}", Profile::path(rom).display()),
+                        Err(e) => format!("An error occured when saving the profile: {e}"),
+                    });
+                }
+            }
+        });
+        if rotation != self.controls.rotation() {
+            self.controls.set_rotation(rotation);
+            self.rotation_saved = None;
+        }
+        if let Some(saved) = &self.rotation_saved {
+            ui.label(saved);
+        }
+    }
+
+    fn settings(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!("Platform: {}", self.chip.platform()));
+        self.speed(ui);
+        self.frame_limit(ui);
+        let palette = self.controls.palette();
+        let (mut on, mut off) = (colour(palette.on), colour(palette.off));
+        ui.horizontal(|ui| {
+            ui.label("Pixel on");
+            ui.color_edit_button_srgba(&mut on);
+            ui.label("off");
+            ui.color_edit_button_srgba(&mut off);
+        });
+        let rgb = |colour: egui::Color32| u32::from_be_bytes([0, colour.r(), colour.g(), colour.b()]);
+        self.controls.set_palette(Palette { on: rgb(on), off: rgb(off) });
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            for choice in Filter::ALL {
+                ui.selectable_value(&mut self.filter, choice, choice.to_string());
+            }
+        });
+        self.rotation(ui);
+        let mut memory_protection = self.chip.memory_protection();
+        if ui.checkbox(&mut memory_protection, "Protect the interpreter and font area").changed() {
+            self.chip.set_memory_protection(memory_protection);
+        }
+        self.quirks(ui);
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut self.heat_map, "Heat map of draws and collisions").changed() {
+                if self.heat_map {
+                    self.chip.enable_draw_map();
+                } else {
+                    self.chip.disable_draw_map();
+                }
+            }
+            ui.add_enabled(self.heat_map, egui::Slider::new(&mut self.heat_frames, 1..=240).text("frames"));
+        });
+    }
+
+    /// The pause menu: every setting with buttons going back and on through its values, taking
+    /// effect straight away, and saving them all to the rom's profile
+    fn pause_menu(&mut self, ui: &mut egui::Ui) {
+        egui::Grid::new("pause_menu").num_columns(4).show(ui, |ui| {
+            let sounds = self.controls.sounds();
+            for setting in Setting::ALL.into_iter().filter(|setting| sounds || !setting.sound()) {
+                ui.label(setting.name());
+                if ui.button("<").clicked() {
+                    setting.change(&mut self.chip, &mut self.controls, false);
+                    self.settings_saved = None;
+                }
+                ui.label(setting.value(&self.chip, &self.controls));
+                if ui.button(">").clicked() {
+                    setting.change(&mut self.chip, &mut self.controls, true);
+                    self.settings_saved = None;
+                }
+                ui.end_row();
+            }
+        });
+        ui.separator();
+        if let Some(rom) = &self.rom {
+            if ui.button("Save to the rom's profile").clicked() {
+                self.settings_saved = Some(settings::save(rom, &self.chip, &self.controls).unwrap_or_else(|e| e));
+            }
+        }
+        if let Some(saved) = &self.settings_saved {
+            ui.label(saved);
+        }
+        ui.label("F1 to go back to the game");
+    }
+
+    /// The guided tour: what the instruction at PC does, and stepping it to see what it
+    /// changes, for the tour's own program or any other rom
+    fn tour(&mut self, ui: &mut egui::Ui) {
+        if self.loader.is_some() && ui.button("Start the tour's program").clicked() {
+            self.switch_rom(&Cart { rom: tour::rom(), speed: None, platform: None, rotation: None });
+            self.running = false;
+            self.tour_changes = None;
+        }
+        let (instruction, lesson) = tour::explain(&self.chip);
+        ui.monospace(instruction);
+        ui.label(lesson);
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!self.running, egui::Button::new("Step")).clicked() {
+                match tour::step(&mut self.chip) {
+                    Ok(changes) => self.tour_changes = Some(changes),
+                    Err(e) => self.error = Some(e.to_string()),
+                }
+            }
+            if self.running {
+                ui.label("Pause to step through it");
+            }
+        });
+        if let Some(changes) = &self.tour_changes {
+            ui.separator();
+            ui.label("What the last step changed:");
+            ui.monospace(changes.trim_end());
+        }
+    }
+
+    /// The timeline: a strip with a row for each kind of event and a tick for each frame one
+    /// happened in, then the events one a line. Clicking or dragging on the strip, moving the
+    /// slider or clicking an event goes to its frame
+    fn timeline(&mut self, ui: &mut egui::Ui) {
+        let (first, end) = self.timeline.frames();
+        if first == end {
+            ui.label("Nothing's run yet");
+            return;
+        }
+        ui.horizontal(|ui| {
+            for ((kind, shown), colour) in EventKind::ALL.iter().zip(&mut self.timeline_shown).zip(EVENT_COLOURS) {
+                ui.checkbox(shown, egui::RichText::new(kind.name()).color(colour));
+            }
+        });
+        let shown: Vec<(u64, Event)> = self
+            .timeline
+            .events()
+            .iter()
+            .filter(|(_, event)| self.timeline_shown[event.kind() as usize])
+            .copied()
+            .collect();
+
+        let size = egui::vec2(ui.available_width().max(240.0), TIMELINE_ROW * EventKind::ALL.len() as f32);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(24));
+        let span = (end - first) as f32;
+        let x = |frame: u64| rect.left() + (frame - first) as f32 / span * rect.width();
+        // Only one tick's drawn for each column of pixels in a row, however many land there
+        let mut drawn = vec![[false; 4]; rect.width() as usize + 1];
+        for (frame, event) in &shown {
+            let row = event.kind() as usize;
+            let column = ((x(*frame) - rect.left()) as usize).min(rect.width() as usize);
+            if !std::mem::replace(&mut drawn[column][row], true) {
+                let top = rect.top() + row as f32 * TIMELINE_ROW;
+                painter.vline(x(*frame), top..=top + TIMELINE_ROW, egui::Stroke::new(1.0, EVENT_COLOURS[row]));
+            }
+        }
+        painter.vline(x(self.timeline.frame()), rect.y_range(), egui::Stroke::new(2.0, egui::Color32::WHITE));
+        if let Some(pos) = response.interact_pointer_pos() {
+            let frame = first + ((pos.x - rect.left()) / rect.width() * span).round().max(0.0) as u64;
+            if frame != self.timeline.frame() {
+                self.seek(frame);
+            }
+        }
+
+        let mut frame = self.timeline.frame();
+        ui.horizontal(|ui| {
+            let before = shown.iter().rev().find(|(at, _)| *at < frame).map(|(at, _)| *at);
+            if ui.add_enabled(before.is_some(), egui::Button::new("Previous event")).clicked() {
+                frame = before.unwrap_or(frame);
+            }
+            let after = shown.iter().find(|(at, _)| *at > frame).map(|(at, _)| *at);
+            if ui.add_enabled(after.is_some(), egui::Button::new("Next event")).clicked() {
+                frame = after.unwrap_or(frame);
+            }
+            ui.add(egui::Slider::new(&mut frame, first..=end).text("frame"));
+        });
+
+        ui.separator();
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical().max_height(240.0).show_rows(ui, row_height, shown.len(), |ui, rows| {
+            for (at, event) in &shown[rows] {
+                let text = egui::RichText::new(format!("{at:>6}  {event}")).monospace().color(EVENT_COLOURS[event.kind() as usize]);
+                if ui.selectable_label(*at == self.timeline.frame(), text).clicked() {
+                    frame = *at;
+                }
+            }
+        });
+        if frame != self.timeline.frame() {
+            self.seek(frame);
+        }
+    }
+}
+
+impl eframe::App for Gui {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Some(exit) = &self.exit {
+            exit(&self.chip);
+        }
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.update_keys(ctx);
+        self.update_controls(ctx);
+        if self.running && !self.controls.paused() && !self.pause_menu {
+            let dt = ctx.input(|i| i.stable_dt);
+            self.timeline.take_over(&mut self.chip);
+            self.run_frames(dt);
+            if let Err(e) = self.controls.exchange_port(&mut self.chip) {
+                self.error = Some(e);
+                self.running = false;
+            }
+        }
+        self.watch_source(ctx.input(|i| i.time));
+        if self.loader.is_some() {
+            self.open_dropped(ctx);
+        }
+        self.update_watches(ctx.input(|i| i.time));
+        self.count_frames(ctx.input(|i| i.time));
+
+        // A filtered screen is already scaled up by a whole number, and the rest of the way
+        // is smoothed so the gaps don't come out different widths
+        let factor = self.filter.factor(self.screen_scale);
+        let image = self.screen_image(factor);
+        let texture = if factor > 1 { egui::TextureOptions::LINEAR } else { egui::TextureOptions::NEAREST };
+        match &mut self.screen {
+            Some(screen) => screen.set(image, texture),
+            None => self.screen = Some(ctx.load_texture("screen", image, texture)),
+        }
+
+        egui::TopBottomPanel::top("menu").show(ctx, |ui| self.menu(ui));
+
+        // The windows are shown with a copy of their open flag, since the contents need
+        // the rest of self
+        let mut panels = std::mem::take(&mut self.panels);
+        egui::Window::new("Registers").open(&mut panels.registers).show(ctx, |ui| self.registers(ui));
+        egui::Window::new("Memory").open(&mut panels.memory).show(ctx, |ui| self.memory(ui));
+        egui::Window::new("Disassembly").open(&mut panels.disassembly).show(ctx, |ui| self.disassembly(ui));
+        egui::Window::new("Stack").open(&mut panels.stack).show(ctx, |ui| self.stack(ui));
+        egui::Window::new("Sprites").open(&mut panels.sprites).show(ctx, |ui| self.sprites(ui));
+        egui::Window::new("Watches").open(&mut panels.watches).show(ctx, |ui| self.watch_panel(ui));
+        egui::Window::new("Cheats").open(&mut panels.cheats).show(ctx, |ui| self.cheat_panel(ui));
+        egui::Window::new("Keypad").open(&mut panels.keypad).show(ctx, |ui| self.keypad(ui));
+        egui::Window::new("Settings").open(&mut panels.settings).show(ctx, |ui| self.settings(ui));
+        egui::Window::new("Tour").open(&mut panels.tour).show(ctx, |ui| self.tour(ui));
+        egui::Window::new("Timeline").open(&mut panels.timeline).show(ctx, |ui| self.timeline(ui));
+        self.panels = panels;
+        let mut pause_menu = self.pause_menu;
+        egui::Window::new("Paused")
+            .open(&mut pause_menu)
+            .collapsible(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| self.pause_menu(ui));
+        self.pause_menu &= pause_menu;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(screen) = &self.screen {
+                // Scale the screen up as far as it'll fit while keeping its shape
+                let size = screen.size_vec2() / factor as f32;
+                let scale = (ui.available_width() / size.x).min(ui.available_height() / size.y).max(1.0);
+                ui.centered_and_justified(|ui| ui.image((screen.id(), size * scale)));
+                self.screen_scale = scale;
+            }
+            if self.hud {
+                let hud = format!("{} fps\n{} ips", self.frame_rate, self.instruction_rate);
+                let at = ui.max_rect().left_top() + egui::vec2(8.0, 8.0);
+                let font = egui::FontId::monospace(14.0);
+                ui.painter().text(at, egui::Align2::LEFT_TOP, hud, font, egui::Color32::YELLOW);
+            }
+            if self.loader.is_some() && ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                let rect = ui.max_rect();
+                ui.painter().rect_filled(rect, 0.0, egui::Color32::from_black_alpha(160));
+                let font = egui::FontId::proportional(24.0);
+                ui.painter().text(rect.center(), egui::Align2::CENTER_CENTER, "Drop to play", font, egui::Color32::WHITE);
+            }
+        });
+
+        if self.running && !self.chip.idle() && !self.pause_menu {
+            ctx.request_repaint();
+        } else {
+            ctx.request_repaint_after(IDLE_REPAINT);
+        }
+    }
+}
+
+/// A palette colour, 0xRRGGBB, as egui's
+fn colour(rgb: u32) -> egui::Color32 {
+    return egui::Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+}
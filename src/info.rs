@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::analyze::{self, Analysis};
+use crate::digest;
+use crate::persist;
+use crate::platform::Platform;
+
+// What chip8 info says about a rom, for cataloguing collections and for bug reports: its
+// size and checksums, what it seems to be written for going by analyze.rs, where it starts,
+// and any text in it. Roms often carry their title, author or a copyright notice as plain
+// ASCII, which is picked out the way `strings` does it.
+
+/// The shortest run of printable characters that's taken as text rather than chance, and
+/// how many of them have to be letters
+const MIN_STRING: usize = 5;
+const MIN_LETTERS: usize = 4;
+
+
+pub struct Info {
+    pub size: usize,
+    pub sha1: [u8; 20],
+    pub sha256: [u8; 32],
+    /// What the rom's saves and profile are named after
+    pub id: String,
+    pub analysis: Analysis,
+    /// Where the rom starts running, and where to if it starts with a jump
+    pub entry: u16,
+    pub jumps_to: Option<u16>,
+    /// Runs of text, with the address each starts at
+    pub strings: Vec<(u16, String)>,
+}
+
+impl Info {
+    pub fn new(rom: &[u8]) -> Self {
+        let analysis = analyze::analyze(rom);
+        // Hires roms start past the VIP patch the interpreter skips, see Chip8::load_rom_bytes
+        let hires = analysis.platform == Platform::HiresChip8 && rom.starts_with(&[0x12, 0x60]);
+        let entry = if hires { 0x2C0 } else { analysis.platform.start_address() };
+        let at = (entry - 0x200) as usize;
+        let first = rom.get(at..at + 2).map(|word| (word[0] as u16) << 8 | word[1] as u16);
+        let jumps_to = first.filter(|opcode| opcode & 0xF000 == 0x1000).map(|opcode| opcode & 0xFFF);
+
+        let mut strings = Vec::new();
+        let mut start = 0;
+        for (i, byte) in rom.iter().chain([&0]).enumerate() {
+            if (0x20..0x7F).contains(byte) {
+                continue;
+            }
+            let text = &rom[start..i];
+            // Sprite data is often printable too, text is mostly letters
+            let letters = text.iter().filter(|byte| byte.is_ascii_alphabetic()).count();
+            if text.len() >= MIN_STRING && letters >= MIN_LETTERS && letters * 2 >= text.len() {
+                strings.push((0x200 + start as u16, String::from_utf8_lossy(text).into_owned()));
+            }
+            start = i + 1;
+        }
+
+        return Self {
+            size: rom.len(),
+            sha1: digest::sha1(rom),
+            sha256: digest::sha256(rom),
+            id: persist::rom_id(rom),
+            analysis,
+            entry,
+            jumps_to,
+            strings,
+        };
+    }
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Size:        {} bytes ({:#X})", self.size, self.size)?;
+        writeln!(f, "SHA-1:       {}", digest::hex(&self.sha1))?;
+        writeln!(f, "SHA-256:     {}", digest::hex(&self.sha256))?;
+        writeln!(f, "Id:          {}", self.id)?;
+
+        writeln!(f, "Platform:    {}", self.analysis.platform)?;
+        let mut extensions: BTreeMap<Platform, usize> = BTreeMap::new();
+        for (_, _, platform) in &self.analysis.extensions {
+            *extensions.entry(*platform).or_insert(0) += 1;
+        }
+        if !extensions.is_empty() {
+            let counts: Vec<String> = extensions.iter().map(|(platform, count)| format!("{platform} ({count})")).collect();
+            writeln!(f, "Extensions:  {}", counts.join(", "))?;
+        }
+        writeln!(f, "Speed:       {} instructions per frame, {}", self.analysis.speed, self.analysis.speed_reason)?;
+
+        match self.jumps_to {
+            Some(to) => writeln!(f, "Entry point: {:04X}, jumping straight to {to:04X}", self.entry)?,
+            None => writeln!(f, "Entry point: {:04X}", self.entry)?,
+        }
+
+        if !self.strings.is_empty() {
+            writeln!(f, "Strings:")?;
+            for (addr, text) in &self.strings {
+                writeln!(f, "  {addr:04X}: {text}")?;
+            }
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,482 @@
+use std::fmt::Write;
+
+use crate::platform::Platform;
+
+// The instruction set as data: every instruction of CHIP-8 and the extensions, with its
+// operands, the quirks interpreters disagree over for it and how long it takes. The
+// interpreter's disassembler, debugger, analysis and tour all work from it, and it's public
+// so assemblers, linters and the like can too. Operand kinds come from the mnemonic, the
+// words Vx, Vy, byte, addr, n and long are operands and the rest are written as they are.
+//
+// assemble() is disassemble() backwards, so an instruction can be disassembled, edited and
+// put back: every opcode assembles back from what it disassembles to on each platform, the
+// unknown ones as DW. Mnemonics are matched in the same order lookup() goes in, and anything
+// that would assemble to an opcode decoding as another instruction (SYS #0E0, which is CLS)
+// is refused, so what assembles always disassembles to what was written.
+
+
+/// One instruction of the instruction set: an opcode matches it when
+/// opcode & mask == pattern
+#[derive(Debug, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub mask: u16,
+    pub pattern: u16,
+    pub mnemonic: &'static str,
+    pub platform: Platform,
+    /// The quirks interpreters disagree over for it
+    pub quirks: &'static [Quirk],
+    pub timing: Timing,
+}
+
+/// The operands that can appear in a mnemonic, and where in the opcode they come from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// Vx, a register from the second digit
+    Vx,
+    /// Vy, a register from the third digit
+    Vy,
+    /// byte, the last two digits
+    Byte,
+    /// addr, the last three digits
+    Addr,
+    /// n, a digit not taken by a register, the last one but for PLANE's
+    Nibble,
+    /// long, the 2 bytes after the opcode
+    Long,
+}
+
+/// Where interpreters disagree over what an instruction does, named as in Timendus' quirks test
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quirk {
+    /// Whether 8XY1, 8XY2 and 8XY3 set VF to 0
+    VfReset,
+    /// Whether FX55 and FX65 move I on
+    Memory,
+    /// Whether DXYN waits for the next frame
+    DisplayWait,
+    /// Whether sprites are cut off at the edge of the screen or wrap round
+    Clipping,
+    /// Whether 8XY6 and 8XYE shift VY or VX
+    Shifting,
+    /// Whether BNNN adds V0 or VX
+    Jumping,
+}
+
+/// How long an instruction takes to run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Timing {
+    /// One of the frame's instructions, like any other
+    Instruction,
+    /// One of the frame's instructions, but drawing is where real machines spent their time
+    Draw,
+    /// Runs again every instruction until a key's held
+    WaitsForKey,
+}
+
+const fn op(mask: u16, pattern: u16, mnemonic: &'static str, platform: Platform) -> OpcodeInfo {
+    return OpcodeInfo { mask, pattern, mnemonic, platform, quirks: &[], timing: Timing::Instruction };
+}
+
+impl OpcodeInfo {
+    const fn quirks(self, quirks: &'static [Quirk]) -> Self {
+        return Self { quirks, ..self };
+    }
+
+    const fn timing(self, timing: Timing) -> Self {
+        return Self { timing, ..self };
+    }
+}
+
+/// Every instruction. Mnemonics follow Cowgod's reference
+/// (http://devernay.free.fr/hacks/chip8/C8TECH10.HTM) and Octo's for the extensions. The
+/// more specific patterns come first, so lookup finds e.g. 00E0 before the catch-all 0NNN
+pub const OPCODES: &[OpcodeInfo] = &[
+    op(0xFFFF, 0x00E0, "CLS", Platform::Chip8).timing(Timing::Draw),
+    op(0xFFFF, 0x0230, "CLS (hires)", Platform::HiresChip8).timing(Timing::Draw),
+    op(0xFFFF, 0x02A0, "COLB", Platform::Chip8X),
+    op(0xFFFF, 0x00EE, "RET", Platform::Chip8),
+    op(0xFFFF, 0x0010, "MEGAOFF", Platform::MegaChip),
+    op(0xFFFF, 0x0011, "MEGAON", Platform::MegaChip),
+    op(0xFFF0, 0x00B0, "SCRU n", Platform::MegaChip).timing(Timing::Draw),
+    op(0xFFF0, 0x00C0, "SCD n", Platform::SuperChip).timing(Timing::Draw),
+    op(0xFFF0, 0x00D0, "SCU n", Platform::XoChip).timing(Timing::Draw),
+    op(0xFFFF, 0x00FB, "SCR", Platform::SuperChip).timing(Timing::Draw),
+    op(0xFFFF, 0x00FC, "SCL", Platform::SuperChip).timing(Timing::Draw),
+    op(0xFFFF, 0x00FD, "EXIT", Platform::SuperChip),
+    op(0xFFFF, 0x00FE, "LOW", Platform::SuperChip),
+    op(0xFFFF, 0x00FF, "HIGH", Platform::SuperChip),
+    op(0xFF00, 0x0100, "LDHI I, byte, long", Platform::MegaChip),
+    op(0xFF00, 0x0200, "LDPAL byte", Platform::MegaChip),
+    op(0xFF00, 0x0300, "SPRW byte", Platform::MegaChip),
+    op(0xFF00, 0x0400, "SPRH byte", Platform::MegaChip),
+    op(0xFF00, 0x0500, "ALPHA byte", Platform::MegaChip),
+    op(0xFFF0, 0x0600, "DIGISND n", Platform::MegaChip),
+    op(0xFFFF, 0x0700, "STOPSND", Platform::MegaChip),
+    op(0xFFF0, 0x0800, "BMODE n", Platform::MegaChip),
+    op(0xFF00, 0x0900, "CCOL byte", Platform::MegaChip),
+    op(0xF000, 0x0000, "SYS addr", Platform::Chip8),
+    op(0xF000, 0x1000, "JP addr", Platform::Chip8),
+    op(0xF000, 0x2000, "CALL addr", Platform::Chip8),
+    op(0xF000, 0x3000, "SE Vx, byte", Platform::Chip8),
+    op(0xF000, 0x4000, "SNE Vx, byte", Platform::Chip8),
+    op(0xF00F, 0x5000, "SE Vx, Vy", Platform::Chip8),
+    op(0xF00F, 0x5001, "ADD Vx, Vy (nibbles)", Platform::Chip8X),
+    op(0xF00F, 0x5002, "SAVE Vx - Vy", Platform::XoChip),
+    op(0xF00F, 0x5003, "LOAD Vx - Vy", Platform::XoChip),
+    op(0xF000, 0x6000, "LD Vx, byte", Platform::Chip8),
+    op(0xF000, 0x7000, "ADD Vx, byte", Platform::Chip8),
+    op(0xF00F, 0x8000, "LD Vx, Vy", Platform::Chip8),
+    op(0xF00F, 0x8001, "OR Vx, Vy", Platform::Chip8).quirks(&[Quirk::VfReset]),
+    op(0xF00F, 0x8002, "AND Vx, Vy", Platform::Chip8).quirks(&[Quirk::VfReset]),
+    op(0xF00F, 0x8003, "XOR Vx, Vy", Platform::Chip8).quirks(&[Quirk::VfReset]),
+    op(0xF00F, 0x8004, "ADD Vx, Vy", Platform::Chip8),
+    op(0xF00F, 0x8005, "SUB Vx, Vy", Platform::Chip8),
+    op(0xF00F, 0x8006, "SHR Vx, Vy", Platform::Chip8).quirks(&[Quirk::Shifting]),
+    op(0xF00F, 0x8007, "SUBN Vx, Vy", Platform::Chip8),
+    op(0xF00F, 0x800E, "SHL Vx, Vy", Platform::Chip8).quirks(&[Quirk::Shifting]),
+    op(0xF00F, 0x9000, "SNE Vx, Vy", Platform::Chip8),
+    op(0xF000, 0xA000, "LD I, addr", Platform::Chip8),
+    op(0xF00F, 0xB000, "COL Vx, Vy", Platform::Chip8X),
+    op(0xF000, 0xB000, "COL Vx, Vy, n", Platform::Chip8X),
+    op(0xF000, 0xB000, "JP V0, addr", Platform::Chip8).quirks(&[Quirk::Jumping]),
+    op(0xF000, 0xC000, "RND Vx, byte", Platform::Chip8),
+    op(0xF00F, 0xD000, "DRW Vx, Vy, 0", Platform::SuperChip).quirks(&[Quirk::Clipping]).timing(Timing::Draw),
+    op(0xF000, 0xD000, "DRW Vx, Vy, n", Platform::Chip8).quirks(&[Quirk::DisplayWait, Quirk::Clipping]).timing(Timing::Draw),
+    op(0xF0FF, 0xE09E, "SKP Vx", Platform::Chip8),
+    op(0xF0FF, 0xE0A1, "SKNP Vx", Platform::Chip8),
+    op(0xF0FF, 0xE0F2, "SKP2 Vx", Platform::Chip8X),
+    op(0xF0FF, 0xE0F5, "SKNP2 Vx", Platform::Chip8X),
+    op(0xFFFF, 0xF000, "LD I, long", Platform::XoChip),
+    op(0xF0FF, 0xF001, "PLANE n", Platform::XoChip),
+    op(0xFFFF, 0xF002, "AUDIO", Platform::XoChip),
+    op(0xF0FF, 0xF007, "LD Vx, DT", Platform::Chip8),
+    op(0xF0FF, 0xF00A, "LD Vx, K", Platform::Chip8).timing(Timing::WaitsForKey),
+    op(0xF0FF, 0xF015, "LD DT, Vx", Platform::Chip8),
+    op(0xF0FF, 0xF018, "LD ST, Vx", Platform::Chip8),
+    op(0xF0FF, 0xF01E, "ADD I, Vx", Platform::Chip8),
+    op(0xF0FF, 0xF029, "LD F, Vx", Platform::Chip8),
+    op(0xF0FF, 0xF030, "LD HF, Vx", Platform::SuperChip),
+    op(0xF0FF, 0xF033, "LD B, Vx", Platform::Chip8),
+    op(0xF0FF, 0xF03A, "PITCH Vx", Platform::XoChip),
+    op(0xF0FF, 0xF055, "LD [I], Vx", Platform::Chip8).quirks(&[Quirk::Memory]),
+    op(0xF0FF, 0xF065, "LD Vx, [I]", Platform::Chip8).quirks(&[Quirk::Memory]),
+    op(0xF0FF, 0xF075, "LD R, Vx", Platform::SuperChip),
+    op(0xF0FF, 0xF085, "LD Vx, R", Platform::SuperChip),
+    op(0xF0FF, 0xF0F8, "OUT Vx", Platform::Chip8X),
+    op(0xF0FF, 0xF0FB, "IN Vx", Platform::Chip8X),
+];
+
+/// Whether the instruction can be decoded for a platform, which is whether the interpreter
+/// runs it there: elsewhere the opcode is another platform's instruction, SYS or unknown
+pub fn decodes_on(info: &OpcodeInfo, platform: Platform) -> bool {
+    return platform.extends(info.platform);
+}
+
+/// Finds the instruction an opcode belongs to when decoding for the platform, if any
+pub fn lookup(opcode: u16, platform: Platform) -> Option<&'static OpcodeInfo> {
+    return OPCODES
+        .iter()
+        .filter(|info| decodes_on(info, platform))
+        .find(|info| opcode & info.mask == info.pattern);
+}
+
+/// How many bytes the instruction takes up, XO-CHIP's F000 NNNN and Mega-Chip's
+/// 01NN NNNN are the only 4 byte ones
+pub fn length(opcode: u16, platform: Platform) -> usize {
+    if opcode == 0xF000 || (platform == Platform::MegaChip && opcode & 0xFF00 == 0x0100) {
+        return 4;
+    }
+    return 2;
+}
+
+/// Formats an opcode as assembly with its operands filled in, e.g. 6E05 as `LD VE, #05`.
+/// Unknown opcodes come out as a raw word and the second half of 4 byte instructions
+/// isn't read, so it's left as `long`
+pub fn disassemble(opcode: u16, platform: Platform) -> String {
+    let Some(info) = lookup(opcode, platform) else {
+        return format!("DW #{opcode:04X}");
+    };
+
+    let mut text = String::new();
+    for (i, word) in info.mnemonic.split(' ').enumerate() {
+        if i > 0 {
+            text.push(' ');
+        }
+        let (word, comma) = match word.strip_suffix(',') {
+            Some(word) => (word, ","),
+            None => (word, ""),
+        };
+        match Operand::from_word(word) {
+            Some(operand) => text.push_str(&info.format(operand, opcode)),
+            None => text.push_str(word),
+        }
+        text.push_str(comma);
+    }
+    return text;
+}
+
+/// Turns an instruction written as disassemble() writes it back into its opcode on the
+/// platform. Words are matched ignoring case and numbers are hex, with or without a #. The
+/// second half of a 4 byte instruction is left as `long`, as disassemble() leaves it
+pub fn assemble(text: &str, platform: Platform) -> Result<u16, String> {
+    let words: Vec<&str> = text.split_whitespace().map(|word| word.trim_end_matches(',')).collect();
+    if let [dw, word] = words[..] {
+        if dw.eq_ignore_ascii_case("DW") {
+            return parse_hex(word, 0xFFFF).ok_or(format!("'{word}' isn't a word"));
+        }
+    }
+
+    let mut decodes_as = None;
+    for info in OPCODES.iter().filter(|info| decodes_on(info, platform)) {
+        let Some(opcode) = info.encode(&words) else {
+            continue;
+        };
+        match lookup(opcode, platform) {
+            Some(found) if found == info => return Ok(opcode),
+            found => decodes_as = decodes_as.or(Some((opcode, found))),
+        }
+    }
+    return Err(match decodes_as {
+        Some((opcode, Some(found))) => format!("'{text}' would be {opcode:04X}, which is {} on {}", found.mnemonic, platform.name()),
+        Some((opcode, None)) => format!("'{text}' would be {opcode:04X}, which nothing on {} decodes", platform.name()),
+        None => format!("'{text}' isn't an instruction on {}", platform.name()),
+    });
+}
+
+/// A number written in hex, with or without a #, if it's no more than max
+fn parse_hex(text: &str, max: u16) -> Option<u16> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    return u16::from_str_radix(digits, 16).ok().filter(|value| *value <= max && !digits.starts_with('+'));
+}
+
+/// Everything known about the instruction an opcode decodes to on the platform: what it
+/// disassembles to, its operands, the quirks that change what it does and how long it takes
+pub fn explain(opcode: u16, platform: Platform) -> String {
+    let Some(info) = lookup(opcode, platform) else {
+        return format!("{opcode:04X}: nothing on {} decodes it", platform.name());
+    };
+
+    let mut text = String::new();
+    writeln!(text, "{opcode:04X}  {}", disassemble(opcode, platform)).unwrap();
+    writeln!(text, "{}  {}, from {}", info.form(), info.mnemonic, info.platform.name()).unwrap();
+    let operands = info.operands().collect::<Vec<_>>();
+    if !operands.is_empty() {
+        writeln!(text, "Operands:").unwrap();
+        for operand in operands {
+            writeln!(text, "  {:<5} {:<5} {}", operand.word(), info.format(operand, opcode), operand.description()).unwrap();
+        }
+    }
+    if !info.quirks.is_empty() {
+        writeln!(text, "Quirks:").unwrap();
+        for quirk in info.quirks {
+            writeln!(text, "  {}: {}", quirk.name(), quirk.description()).unwrap();
+        }
+    }
+    write!(text, "Timing: {}", info.timing.description()).unwrap();
+    if length(opcode, platform) == 4 {
+        write!(text, ", and it takes up 4 bytes").unwrap();
+    }
+    return text;
+}
+
+impl OpcodeInfo {
+    /// The operands in the mnemonic, in order
+    pub fn operands(&self) -> impl Iterator<Item = Operand> {
+        return self.mnemonic.split(' ').filter_map(|word| Operand::from_word(word.trim_end_matches(',')));
+    }
+
+    /// The opcode with its operands as letters, e.g. DXYN or 8XY6
+    pub fn form(&self) -> String {
+        let operands = self.operands().collect::<Vec<_>>();
+        return (0..4)
+            .map(|digit| {
+                let shift = 12 - digit * 4;
+                if (self.mask >> shift) & 0xF == 0xF {
+                    return char::from_digit(((self.pattern >> shift) & 0xF) as u32, 16).unwrap().to_ascii_uppercase();
+                }
+                return match digit {
+                    1 if operands.contains(&Operand::Vx) => 'X',
+                    2 if operands.contains(&Operand::Vy) => 'Y',
+                    _ => 'N',
+                };
+            })
+            .collect();
+    }
+
+    /// The opcode for the words of an instruction, split on spaces with the commas taken off,
+    /// if they're this one's
+    fn encode(&self, words: &[&str]) -> Option<u16> {
+        let mnemonic: Vec<&str> = self.mnemonic.split(' ').map(|word| word.trim_end_matches(',')).collect();
+        if mnemonic.len() != words.len() {
+            return None;
+        }
+
+        let mut opcode = self.pattern;
+        for (expected, word) in mnemonic.iter().zip(words) {
+            let Some(operand) = Operand::from_word(expected) else {
+                if !expected.eq_ignore_ascii_case(word) {
+                    return None;
+                }
+                continue;
+            };
+            let value = match operand {
+                Operand::Vx | Operand::Vy => {
+                    let digit = word.strip_prefix(['V', 'v']).filter(|digit| digit.len() == 1)?;
+                    u16::from_str_radix(digit, 16).ok()?
+                },
+                Operand::Byte => parse_hex(word, 0xFF)?,
+                Operand::Addr => parse_hex(word, 0xFFF)?,
+                Operand::Nibble => parse_hex(word, 0xF)?,
+                Operand::Long if word.eq_ignore_ascii_case("long") => 0,
+                Operand::Long => return None,
+            };
+            opcode |= match operand {
+                Operand::Vx => value << 8,
+                Operand::Vy => value << 4,
+                Operand::Nibble if self.mask & 0xF == 0xF => value << 8,
+                _ => value,
+            };
+        }
+        return Some(opcode);
+    }
+
+    /// An operand's value in an opcode of this instruction, 0 for long
+    pub fn value(&self, operand: Operand, opcode: u16) -> u16 {
+        return match operand {
+            Operand::Vx => (opcode >> 8) & 0xF,
+            Operand::Vy => (opcode >> 4) & 0xF,
+            Operand::Byte => opcode & 0xFF,
+            Operand::Addr => opcode & 0xFFF,
+            // PLANE n is FN01
+            Operand::Nibble if self.mask & 0xF == 0xF => (opcode >> 8) & 0xF,
+            Operand::Nibble => opcode & 0xF,
+            Operand::Long => 0,
+        };
+    }
+
+    /// An operand as the disassembler writes it
+    pub fn format(&self, operand: Operand, opcode: u16) -> String {
+        let value = self.value(operand, opcode);
+        return match operand {
+            Operand::Vx | Operand::Vy => format!("V{value:X}"),
+            Operand::Byte => format!("#{value:02X}"),
+            Operand::Addr => format!("#{value:03X}"),
+            Operand::Nibble => format!("{value:X}"),
+            Operand::Long => "long".to_string(),
+        };
+    }
+}
+
+impl Operand {
+    /// The operand a word of a mnemonic is, if it's one
+    pub fn from_word(word: &str) -> Option<Self> {
+        return match word {
+            "Vx" => Some(Operand::Vx),
+            "Vy" => Some(Operand::Vy),
+            "byte" => Some(Operand::Byte),
+            "addr" => Some(Operand::Addr),
+            "n" => Some(Operand::Nibble),
+            "long" => Some(Operand::Long),
+            _ => None,
+        };
+    }
+
+    /// How the operand's written in mnemonics
+    pub fn word(&self) -> &'static str {
+        return match self {
+            Operand::Vx => "Vx",
+            Operand::Vy => "Vy",
+            Operand::Byte => "byte",
+            Operand::Addr => "addr",
+            Operand::Nibble => "n",
+            Operand::Long => "long",
+        };
+    }
+
+    pub fn description(&self) -> &'static str {
+        return match self {
+            Operand::Vx => "a register, the opcode's second digit",
+            Operand::Vy => "a register, the opcode's third digit",
+            Operand::Byte => "a byte, the opcode's last two digits",
+            Operand::Addr => "an address, the opcode's last three digits",
+            Operand::Nibble => "a number from 0 to F",
+            Operand::Long => "the 2 bytes after the opcode",
+        };
+    }
+}
+
+impl Quirk {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Quirk::VfReset => "vF reset",
+            Quirk::Memory => "memory",
+            Quirk::DisplayWait => "display wait",
+            Quirk::Clipping => "clipping",
+            Quirk::Shifting => "shifting",
+            Quirk::Jumping => "jumping",
+        };
+    }
+
+    /// What the interpreters that disagree do, and what this one does
+    pub fn description(&self) -> &'static str {
+        return match self {
+            Quirk::VfReset => "the COSMAC VIP sets VF to 0 as well, later interpreters and this one leave it unless the VF reset quirk's on",
+            Quirk::Memory => "the COSMAC VIP moves I on past the registers, SUPER-CHIP and this interpreter leave it unless the memory moves I quirk's on",
+            Quirk::DisplayWait => "the COSMAC VIP waits for the next frame to draw, so a frame draws a sprite at most, this interpreter only waits with the display wait quirk on",
+            Quirk::Clipping => "sprites past the edge of the screen are cut off here, some interpreters wrap them round to the other side",
+            Quirk::Shifting => "the COSMAC VIP shifts VY into VX, SUPER-CHIP and this interpreter shift VX where it is unless the shift source quirk's on",
+            Quirk::Jumping => "CHIP-8 and this interpreter add V0, SUPER-CHIP adds VX, the first digit of the address, as this one does with the jump offset quirk on",
+        };
+    }
+}
+
+impl Timing {
+    pub fn description(&self) -> &'static str {
+        return match self {
+            Timing::Instruction => "one of the frame's instructions (--speed of them run a frame)",
+            Timing::Draw => "one of the frame's instructions here, where on real machines drawing took the longest",
+            Timing::WaitsForKey => "runs again, one of the frame's instructions each time, until a key's held",
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip8;
+    use crate::error::Chip8Error;
+
+    const PLATFORMS: [Platform; 6] = [
+        Platform::Chip8, Platform::HiresChip8, Platform::Chip8X, Platform::SuperChip, Platform::XoChip, Platform::MegaChip,
+    ];
+
+    #[test]
+    fn every_opcode_assembles_back_from_its_disassembly() {
+        for platform in PLATFORMS {
+            for opcode in 0..=0xFFFF {
+                let text = disassemble(opcode, platform);
+                assert_eq!(assemble(&text, platform), Ok(opcode), "{text} on {}", platform.name());
+            }
+        }
+
+        assert_eq!(assemble("drw va, vb, #f", Platform::Chip8), Ok(0xDABF));
+        assert!(assemble("SYS #0E0", Platform::Chip8).is_err());
+        assert!(assemble("JP V0, #300", Platform::Chip8X).is_err());
+        for text in ["", "NOP", "LD VG, #12", "LD V1, #123", "JP #1000", "ADD V1", "CLS V0"] {
+            assert!(assemble(text, Platform::Chip8).is_err(), "'{text}' assembled");
+        }
+    }
+    #[test]
+    fn decodes_what_the_interpreter_runs() {
+        for platform in PLATFORMS {
+            for opcode in 0..=0xFFFF_u16 {
+                // 4KB is enough for any one instruction, and quicker to set up than Mega-Chip's 16MB
+                let mut chip = Chip8::with_memory_size(platform, 0x1000, false);
+                chip.set_strict(true);
+                chip.load_rom_bytes(&opcode.to_be_bytes());
+                let unknown = matches!(chip.execute(), Err(Chip8Error::UnknownInstruction { .. }));
+                assert_eq!(lookup(opcode, platform).is_none(), unknown, "{opcode:04X} on {}", platform.name());
+            }
+        }
+    }
+}
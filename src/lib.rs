@@ -0,0 +1,110 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analyze;
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+#[cfg(feature = "std")]
+pub mod audio;
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(feature = "bevy_chip8")]
+pub mod bevy;
+#[cfg(feature = "std")]
+pub mod budget;
+#[cfg(feature = "bundled")]
+pub mod bundled;
+#[cfg(feature = "std")]
+pub mod callgraph;
+#[cfg(feature = "std")]
+pub mod cart;
+#[cfg(feature = "chat")]
+pub mod chat;
+#[cfg(feature = "std")]
+pub mod cheat;
+pub mod chip;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod disasm;
+#[cfg(feature = "std")]
+pub mod differential;
+#[cfg(feature = "std")]
+pub mod digest;
+pub mod drawmap;
+pub mod embedded;
+#[cfg(feature = "std")]
+pub mod env;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod expr;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod font;
+pub mod framebuffer;
+#[cfg(feature = "std")]
+pub mod flow;
+#[cfg(feature = "std")]
+pub mod fuzzgen;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "std")]
+pub mod info;
+#[cfg(feature = "std")]
+pub mod isa;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "std")]
+pub mod netplay;
+#[cfg(feature = "std")]
+pub mod octo;
+pub mod patch;
+#[cfg(feature = "std")]
+pub mod persist;
+pub mod platform;
+#[cfg(feature = "std")]
+pub mod pool;
+#[cfg(feature = "std")]
+pub mod profile;
+#[cfg(feature = "std")]
+pub mod replay;
+pub mod rewind;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "std")]
+pub mod soak;
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod symbols;
+#[cfg(feature = "std")]
+pub mod thread;
+#[cfg(feature = "std")]
+pub mod timeline;
+#[cfg(feature = "std")]
+pub mod tour;
+#[cfg(feature = "std")]
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod tracedb;
+#[cfg(feature = "zip")]
+pub mod zip;
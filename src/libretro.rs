@@ -0,0 +1,488 @@
+use std::cell::RefCell;
+use std::ffi::{c_char, c_uint, c_void, CStr};
+
+use crate::audio::{Beep, Synth};
+use crate::chip::Chip8;
+use crate::frontend::input::{self, Held, InputProfile, PadButton};
+use crate::platform::Platform;
+use crate::profile::Profile;
+
+// A libretro core, so the emulator can be loaded in RetroArch and other libretro frontends.
+// Build it with `cargo rustc --release --lib --crate-type cdylib --features libretro` and
+// load libchip8.so (.dll, .dylib) as a core. The platform is picked with the chip8_platform
+// core option and the RPL flags are kept as the save RAM, so the frontend saves them with
+// the rest of its saves. Save states aren't supported yet. The beep is the one in the rom's
+// profile unless the beep core options say otherwise, and they can be changed mid-game.
+//
+// The keyboard and joypads map onto the keypad through the input profile in the rom's profile
+// (see frontend/input.rs), each player on the joypad in their own port. By default that's
+// one player with the keyboard the usual way and the joypad on the keys most games use for
+// movement and action:
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R         d-pad 2 4 6 8, A 5, B 0, X A, Y B, L 1, R 3,
+//   7 8 9 E        A S D F         Start F, Select E
+//   A 0 B F        Z X C V
+//
+// The types and constants are the parts of libretro.h the core needs, written out by hand.
+
+
+const RETRO_API_VERSION: c_uint = 1;
+
+const RETRO_ENVIRONMENT_SHUTDOWN: c_uint = 7;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const RETRO_ENVIRONMENT_SET_GEOMETRY: c_uint = 37;
+
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_DEVICE_KEYBOARD: c_uint = 3;
+
+const RETRO_MEMORY_SAVE_RAM: c_uint = 0;
+
+const RETRO_REGION_NTSC: c_uint = 0;
+
+const SAMPLE_RATE: u32 = 44100;
+
+/// Joypad button ids and the buttons they are
+const JOYPAD: [(c_uint, PadButton); 12] = [
+    (4, PadButton::Up), (5, PadButton::Down), (6, PadButton::Left), (7, PadButton::Right),
+    (8, PadButton::A), (0, PadButton::B), (9, PadButton::X), (1, PadButton::Y),
+    (10, PadButton::L), (11, PadButton::R), (3, PadButton::Start), (2, PadButton::Select),
+];
+
+/// The beep core options and the profile settings they stand in for, unless they're set to
+/// "profile"
+const BEEP_OPTIONS: [(&CStr, &str); 3] = [
+    (c"chip8_beep", "beep"), (c"chip8_beep_hz", "beep_hz"), (c"chip8_beep_volume", "beep_volume"),
+];
+
+/// The keyboard keys input profiles can use, by the names they give them. libretro numbers
+/// the keys with characters on by their ASCII codes
+const KEYMAP: [(c_uint, &str); 36] = [
+    (b'1' as c_uint, "1"), (b'2' as c_uint, "2"), (b'3' as c_uint, "3"), (b'4' as c_uint, "4"),
+    (b'q' as c_uint, "q"), (b'w' as c_uint, "w"), (b'e' as c_uint, "e"), (b'r' as c_uint, "r"),
+    (b'a' as c_uint, "a"), (b's' as c_uint, "s"), (b'd' as c_uint, "d"), (b'f' as c_uint, "f"),
+    (b'z' as c_uint, "z"), (b'x' as c_uint, "x"), (b'c' as c_uint, "c"), (b'v' as c_uint, "v"),
+    (b'6' as c_uint, "6"), (b'7' as c_uint, "7"), (b'8' as c_uint, "8"), (b'9' as c_uint, "9"),
+    (b'y' as c_uint, "y"), (b'u' as c_uint, "u"), (b'i' as c_uint, "i"), (b'o' as c_uint, "o"),
+    (b'h' as c_uint, "h"), (b'j' as c_uint, "j"), (b'k' as c_uint, "k"), (b'l' as c_uint, "l"),
+    (b'n' as c_uint, "n"), (b'm' as c_uint, "m"), (b',' as c_uint, ","), (b'.' as c_uint, "."),
+    (273, "up"), (274, "down"), (276, "left"), (275, "right"),
+];
+
+type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn = unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = unsafe extern "C" fn();
+type InputStateFn = unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    geometry: GameGeometry,
+    timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct Variable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+/// Everything the core keeps between calls. libretro calls a core from one thread, so it's
+/// kept thread local rather than behind a lock, which Chip8 couldn't go behind anyway
+#[derive(Default)]
+struct Core {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    rom: Vec<u8>,
+    chip: Option<Chip8>,
+    /// Set once the rom stops with an error, after which the last frame stays on screen
+    stopped: bool,
+    /// The RPL flags as the frontend sees them, read into the machine before its first frame
+    save_ram: [u8; 16],
+    save_ram_loaded: bool,
+    size: (usize, usize),
+    /// The beep, or XO-CHIP's audio pattern
+    synth: Synth,
+    /// The beep from the rom's profile, which the core options can override
+    profile_beep: Beep,
+    /// The input profile from the rom's profile, if it has one
+    input: Option<&'static InputProfile>,
+}
+
+thread_local! {
+    static CORE: RefCell<Core> = RefCell::new(Core::default());
+}
+
+impl Core {
+    fn environment(&self, cmd: c_uint, data: *mut c_void) -> bool {
+        return match self.environment {
+            Some(environment) => unsafe { environment(cmd, data) },
+            None => false,
+        };
+    }
+
+    /// The value of a core option, if the frontend says
+    fn variable(&self, key: &CStr) -> Option<String> {
+        let mut variable = Variable { key: key.as_ptr(), value: std::ptr::null() };
+        if !self.environment(RETRO_ENVIRONMENT_GET_VARIABLE, &mut variable as *mut Variable as *mut c_void)
+            || variable.value.is_null()
+        {
+            return None;
+        }
+        let value = unsafe { CStr::from_ptr(variable.value) };
+        return value.to_str().ok().map(str::to_string);
+    }
+
+    /// The platform picked in the core options, CHIP-8 if the frontend doesn't say
+    fn platform(&self) -> Platform {
+        return self.variable(c"chip8_platform").as_deref().and_then(Platform::from_name).unwrap_or(Platform::Chip8);
+    }
+
+    /// The profile's beep with whatever the core options change
+    fn beep(&self) -> Beep {
+        let mut beep = self.profile_beep;
+        for (key, setting) in BEEP_OPTIONS {
+            if let Some(value) = self.variable(key).filter(|value| value != "profile") {
+                let _ = beep.set(setting, &value);
+            }
+        }
+        return beep;
+    }
+
+    fn start(&mut self) {
+        let mut chip = Chip8::with_platform(self.platform(), false);
+        chip.load_rom_bytes(&self.rom);
+        self.chip = Some(chip);
+        self.synth = Synth::new(self.beep());
+        self.stopped = false;
+        self.save_ram_loaded = false;
+    }
+
+    fn press_keys(&self, chip: &mut Chip8) {
+        let Some(input_state) = self.input_state else {
+            return;
+        };
+        let profile = self.input.unwrap_or(&input::STANDARD);
+        let mut held = Held::default();
+        for port in 0..profile.players.len() {
+            for (id, button) in JOYPAD {
+                if let Some(key) = profile.button(port, button) {
+                    held.hold(key, unsafe { input_state(port as c_uint, RETRO_DEVICE_JOYPAD, 0, id) } != 0);
+                }
+            }
+        }
+        for (code, name) in KEYMAP {
+            if let Some(key) = profile.key(name) {
+                held.hold(key, unsafe { input_state(0, RETRO_DEVICE_KEYBOARD, 0, code) } != 0);
+            }
+        }
+        held.apply(chip);
+    }
+
+    fn run_frame(&mut self) {
+        if let Some(input_poll) = self.input_poll {
+            unsafe { input_poll() };
+        }
+
+        let Some(mut chip) = self.chip.take() else {
+            return;
+        };
+        let mut updated = false;
+        if self.environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, &mut updated as *mut bool as *mut c_void) && updated {
+            self.synth.set_beep(self.beep());
+        }
+        if !self.save_ram_loaded {
+            chip.set_rpl_flags(&self.save_ram);
+            self.save_ram_loaded = true;
+        }
+
+        self.press_keys(&mut chip);
+        if !self.stopped {
+            if let Err(e) = chip.run_frame() {
+                eprintln!("chip8: {e}");
+                self.stopped = true;
+            }
+            // The rom exiting asks the frontend to shut the core down, which saves the SRAM
+            if chip.exit_requested() {
+                self.stopped = true;
+                self.environment(RETRO_ENVIRONMENT_SHUTDOWN, std::ptr::null_mut());
+            }
+        }
+        self.save_ram = *chip.rpl_flags();
+
+        self.send_video(&chip);
+        self.send_audio(&chip);
+        self.chip = Some(chip);
+    }
+
+    fn send_video(&mut self, chip: &Chip8) {
+        let framebuffer = chip.framebuffer();
+        let size = (framebuffer.width(), framebuffer.height());
+        // The resolution can change as the rom runs (SUPER-CHIP, Mega-Chip)
+        if size != self.size {
+            self.size = size;
+            let mut geometry = geometry(size);
+            self.environment(RETRO_ENVIRONMENT_SET_GEOMETRY, &mut geometry as *mut GameGeometry as *mut c_void);
+        }
+
+        if let Some(video_refresh) = self.video_refresh {
+            let rgb = chip.screen_rgb();
+            let (width, height) = size;
+            unsafe { video_refresh(rgb.as_ptr() as *const c_void, width as c_uint, height as c_uint, width * 4) };
+        }
+    }
+
+    fn send_audio(&mut self, chip: &Chip8) {
+        let Some(audio_sample_batch) = self.audio_sample_batch else {
+            return;
+        };
+        let mut mono = vec![0; SAMPLE_RATE as usize / 60];
+        if !self.stopped {
+            self.synth.fill(chip, SAMPLE_RATE, &mut mono);
+        }
+        let samples: Vec<i16> = mono.iter().flat_map(|sample| [*sample, *sample]).collect();
+
+        // Frontends can take fewer frames than they're given, so keep offering the rest
+        let mut sent = 0;
+        while sent < samples.len() / 2 {
+            let taken = unsafe { audio_sample_batch(samples[sent * 2..].as_ptr(), samples.len() / 2 - sent) };
+            if taken == 0 {
+                break;
+            }
+            sent += taken;
+        }
+    }
+}
+
+fn geometry((width, height): (usize, usize)) -> GameGeometry {
+    return GameGeometry {
+        base_width: width as c_uint,
+        base_height: height as c_uint,
+        max_width: 256,
+        max_height: 192,
+        aspect_ratio: width as f32 / height as f32,
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    return RETRO_API_VERSION;
+}
+
+/// # Safety
+/// environment must be a libretro environment callback
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: EnvironmentFn) {
+    let variables = [
+        Variable {
+            key: c"chip8_platform".as_ptr(),
+            value: c"Platform; chip8|hires|chip8x|superchip|xochip|megachip".as_ptr(),
+        },
+        Variable { key: c"chip8_beep".as_ptr(), value: c"Beep waveform; profile|square|triangle|sine".as_ptr() },
+        Variable { key: c"chip8_beep_hz".as_ptr(), value: c"Beep frequency (Hz); profile|220|330|440|523|660|880".as_ptr() },
+        Variable { key: c"chip8_beep_volume".as_ptr(), value: c"Beep volume (%); profile|0|6|12|25|50|75|100".as_ptr() },
+        Variable { key: std::ptr::null(), value: std::ptr::null() },
+    ];
+    environment(RETRO_ENVIRONMENT_SET_VARIABLES, variables.as_ptr() as *mut c_void);
+    CORE.with_borrow_mut(|core| core.environment = Some(environment));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: VideoRefreshFn) {
+    CORE.with_borrow_mut(|core| core.video_refresh = Some(video_refresh));
+}
+
+/// Unused, the audio is sent a frame at a time through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: AudioSampleBatchFn) {
+    CORE.with_borrow_mut(|core| core.audio_sample_batch = Some(audio_sample_batch));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: InputPollFn) {
+    CORE.with_borrow_mut(|core| core.input_poll = Some(input_poll));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: InputStateFn) {
+    CORE.with_borrow_mut(|core| core.input_state = Some(input_state));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    CORE.with_borrow_mut(|core| *core = Core::default());
+}
+
+/// # Safety
+/// info must point to a retro_system_info
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: c"chip8".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"ch8|c8|sc8|xo8|mc8|c8x|rom".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// info must point to a retro_system_av_info
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let size = CORE.with_borrow(|core| match &core.chip {
+        Some(chip) => (chip.framebuffer().width(), chip.framebuffer().height()),
+        None => (64, 32),
+    });
+    *info = SystemAvInfo {
+        geometry: geometry(size),
+        timing: SystemTiming { fps: 60.0, sample_rate: SAMPLE_RATE as f64 },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    CORE.with_borrow_mut(|core| {
+        // The save RAM already holds the flags as of the last frame, so they survive
+        if core.chip.is_some() {
+            core.start();
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    CORE.with_borrow_mut(|core| core.run_frame());
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    return 0;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize(_data: *mut c_void, _size: usize) -> bool {
+    return false;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unserialize(_data: *const c_void, _size: usize) -> bool {
+    return false;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// game must be null or point to a retro_game_info whose data is size bytes long
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    if game.is_null() || (*game).data.is_null() {
+        return false;
+    }
+    let rom = std::slice::from_raw_parts((*game).data as *const u8, (*game).size).to_vec();
+
+    return CORE.with_borrow_mut(|core| {
+        let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+        if !core.environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, &mut format as *mut c_uint as *mut c_void) {
+            eprintln!("chip8: the frontend doesn't support XRGB8888");
+            return false;
+        }
+        let profile = Profile::load(&rom).unwrap_or_default();
+        core.profile_beep = profile.beep;
+        core.input = profile.input;
+        core.rom = rom;
+        core.save_ram = [0; 16];
+        core.start();
+        core.size = (0, 0);
+        return true;
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_game_type: c_uint, _info: *const GameInfo, _num_info: usize) -> bool {
+    return false;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    CORE.with_borrow_mut(|core| {
+        core.chip = None;
+        core.rom.clear();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    return RETRO_REGION_NTSC;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    if id != RETRO_MEMORY_SAVE_RAM {
+        return std::ptr::null_mut();
+    }
+    // The save RAM lives in the thread local, which stays put for the life of the thread
+    return CORE.with_borrow_mut(|core| core.save_ram.as_mut_ptr() as *mut c_void);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    return match id {
+        RETRO_MEMORY_SAVE_RAM => 16,
+        _ => 0,
+    };
+}
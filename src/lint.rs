@@ -0,0 +1,317 @@
+use std::fmt;
+
+use crate::flow::{self, Flow};
+use crate::isa;
+use crate::platform::Platform;
+
+// The linter (chip8 lint) looks through a rom for things that are probably mistakes, or that
+// only work on some interpreters. It goes by the control flow (flow.rs), so only the
+// instructions the program can reach are looked at, and within each run of them that's only
+// entered at the top it keeps track of the registers and I set to constants, which is what
+// most sprite draws and memory writes go by. What it finds:
+//
+//   - code nothing reaches, runs of unreached instructions ending in a return or jump
+//   - jumps into the middle of another instruction
+//   - running, jumping or reading sprites and registers past the end of the rom
+//   - sprites drawn at a position off the screen
+//   - memory writes over the program's own instructions
+//   - instructions whose result depends on one of the quirks (see isa::Quirk), when what
+//     follows makes use of it
+//
+// Like the analysis it's a hint rather than a proof. Computed jumps are only followed into a
+// table of jumps (see flow.rs), so code they reach any other way shows up as unreachable.
+
+/// How many instructions in a row need to look like code before they're called unreachable
+/// code rather than data
+const UNREACHABLE_RUN: usize = 3;
+
+
+/// Something in the rom that looks wrong
+pub struct Warning {
+    /// The instruction it's about
+    pub addr: u16,
+    pub message: String,
+}
+
+/// What the registers and I are known to hold at a point in a run of instructions
+#[derive(Clone, Copy)]
+struct Known {
+    registers: [Option<u8>; 16],
+    i: Option<u16>,
+    /// The last FX55 or FX65, which on the COSMAC VIP moved I on, while I hasn't been set since
+    stored: Option<u16>,
+    /// The last 8XY1, 8XY2 or 8XY3, which on the COSMAC VIP set VF to 0, while VF hasn't been
+    /// set since
+    logic: Option<u16>,
+}
+
+impl Known {
+    const NOTHING: Known = Known { registers: [None; 16], i: None, stored: None, logic: None };
+}
+
+/// Looks through the rom as the platform would run it, returning the warnings in address order
+pub fn lint(rom: &[u8], platform: Platform) -> Vec<Warning> {
+    let flow = flow::trace(rom, platform);
+    let mut warnings = Vec::new();
+
+    for (from, to) in &flow.outside {
+        let message = match flow.code.get(from).map(|opcode| opcode & 0xFFF) {
+            Some(target) if target == *to && matches!(from_mnemonic(&flow, *from, platform), "JP addr" | "CALL addr") => {
+                if *to < flow.start {
+                    format!("jumps to {to:04X}, below the rom in the interpreter's memory")
+                } else {
+                    format!("jumps to {to:04X}, past the end of the rom")
+                }
+            },
+            _ => format!("runs on past the end of the rom to {to:04X}"),
+        };
+        warnings.push(Warning { addr: *from, message });
+    }
+
+    for addr in flow.code.keys() {
+        if flow.code.contains_key(&addr.wrapping_add(1)) {
+            let inside = addr.wrapping_add(1);
+            let from = flow.from.get(&inside).map_or(String::new(), |from| format!(" from {from:04X}"));
+            warnings.push(Warning {
+                addr: inside,
+                message: format!("is reached{from}, but it's in the middle of the instruction at {addr:04X}"),
+            });
+        }
+    }
+
+    unreachable(rom, platform, &flow, &mut warnings);
+    known_values(rom, platform, &flow, &mut warnings);
+
+    warnings.sort_by_key(|warning| warning.addr);
+    return warnings;
+}
+
+fn from_mnemonic(flow: &Flow, addr: u16, platform: Platform) -> &'static str {
+    return flow.code.get(&addr).and_then(|opcode| isa::lookup(*opcode, platform)).map_or("", |info| info.mnemonic);
+}
+
+/// Warns of runs of unreached bytes that decode as instructions and end the way code does,
+/// with a return or a jump into the rom, as data rarely does
+fn unreachable(rom: &[u8], platform: Platform, flow: &Flow, warnings: &mut Vec<Warning>) {
+    let mut addr = flow.start;
+    while (addr as u32) < flow.end as u32 {
+        if flow.is_code(addr, platform) {
+            addr += 1;
+            continue;
+        }
+
+        let run_start = addr;
+        let mut count = 0;
+        let mut ends_like_code = false;
+        while let Some(opcode) = flow.opcode(rom, addr) {
+            if flow.is_code(addr + 1, platform) {
+                break;
+            }
+            let Some(info) = isa::lookup(opcode, platform).filter(|_| opcode != 0) else {
+                break;
+            };
+            count += 1;
+            addr += isa::length(opcode, platform) as u16;
+            ends_like_code = match info.mnemonic {
+                "RET" | "EXIT" => true,
+                "JP addr" => (flow.start..flow.end).contains(&(opcode & 0xFFF)),
+                _ => false,
+            };
+            if ends_like_code {
+                break;
+            }
+        }
+        if ends_like_code && count >= UNREACHABLE_RUN {
+            warnings.push(Warning {
+                addr: run_start,
+                message: format!("{count} instructions up to {:04X} look like code, but nothing reaches them", addr - 1),
+            });
+        }
+        if addr == run_start {
+            addr += 1;
+        }
+    }
+}
+
+/// Goes through each run of instructions with what's known of the registers and I, warning of
+/// sprites off the screen or past the rom, writes over code and the quirks relied on
+fn known_values(rom: &[u8], platform: Platform, flow: &Flow, warnings: &mut Vec<Warning>) {
+    let (width, height) = screen(platform);
+    let mut known = Known::NOTHING;
+    let mut previous = None;
+    let mut expected = None;
+    for (&addr, &opcode) in &flow.code {
+        if flow.blocks.contains(&addr) || expected != Some(addr) {
+            known = Known::NOTHING;
+            previous = None;
+        }
+        let length = isa::length(opcode, platform) as u16;
+        expected = Some(addr.wrapping_add(length));
+        let Some(info) = isa::lookup(opcode, platform) else {
+            continue;
+        };
+        if flow::is_skip(info) || matches!(info.mnemonic, "JP addr" | "CALL addr" | "RET" | "EXIT" | "JP V0, addr") {
+            expected = None;
+        }
+
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let mut warn = |message: String| warnings.push(Warning { addr, message });
+
+        if let Some(stored) = known.stored.filter(|_| uses_i(opcode)) {
+            warn(format!(
+                "goes by I after the {:04X} at {stored:04X}, which moves I on past the registers on the COSMAC VIP but not here (the memory quirk)",
+                flow.code[&stored]
+            ));
+            known.stored = None;
+        }
+        if let Some(logic) = known.logic.filter(|_| reads_vf(opcode)) {
+            warn(format!(
+                "reads VF after the {:04X} at {logic:04X}, which sets VF to 0 on the COSMAC VIP but not here (the vF reset quirk)",
+                flow.code[&logic]
+            ));
+            known.logic = None;
+        }
+
+        match opcode & 0xF000 {
+            0x6000 => known.registers[x] = Some(opcode as u8),
+            0x7000 => known.registers[x] = known.registers[x].map(|vx| vx.wrapping_add(opcode as u8)),
+            0x8000 => match opcode & 0xF {
+                0x0 => known.registers[x] = known.registers[y],
+                0x1..=0x3 => {
+                    known.registers[x] = None;
+                    known.logic = Some(addr);
+                },
+                shift => {
+                    if matches!(shift, 0x6 | 0xE) && x != y && previous != Some(0x8000 | opcode & 0x0FF0) {
+                        warn(format!(
+                            "shifts V{y:X} into V{x:X} on the COSMAC VIP but V{x:X} where it is here (the shifting quirk), 8{x:X}{y:X}0 first would make them agree"
+                        ));
+                    }
+                    known.registers[x] = None;
+                    known.registers[0xF] = None;
+                },
+            },
+            0xA000 => known.i = Some(opcode & 0xFFF),
+            0xB000 if info.mnemonic == "JP V0, addr" && x != 0 => {
+                warn(format!("jumps to {:03X} plus V0, but plus V{x:X} on SUPER-CHIP (the jumping quirk)", opcode & 0xFFF));
+            },
+            0xC000 => known.registers[x] = None,
+            0xD000 => {
+                let (vx, vy) = (known.registers[x], known.registers[y]);
+                if vx.is_some_and(|vx| vx as u16 >= width) || vy.is_some_and(|vy| vy as u16 >= height) {
+                    let at = |v: Option<u8>| v.map_or("?".to_string(), |v| v.to_string());
+                    warn(format!(
+                        "draws a sprite at ({}, {}), off the {width}x{height} screen, which wraps round onto it here but isn't drawn at all by some interpreters",
+                        at(vx),
+                        at(vy)
+                    ));
+                }
+                let rows = match opcode & 0xF {
+                    0 if platform >= Platform::SuperChip => 32,
+                    rows => rows,
+                };
+                if let Some(i) = known.i {
+                    warn_past_end(flow, i, rows, "draws a sprite", &mut warn);
+                }
+                known.registers[0xF] = None;
+                known.logic = None;
+            },
+            0xF000 => match opcode & 0xFF {
+                0x07 | 0x0A => known.registers[x] = None,
+                0x1E => known.i = known.i.zip(known.registers[x]).map(|(i, vx)| i.wrapping_add(vx as u16)),
+                0x29 | 0x30 => known.i = None,
+                0x00 if opcode == 0xF000 => known.i = flow.opcode(rom, addr + 2),
+                0x33 | 0x55 => {
+                    let len = if opcode & 0xFF == 0x33 { 3 } else { x as u16 + 1 };
+                    if let Some(i) = known.i {
+                        let written = i..i.saturating_add(len);
+                        if let Some(code) = written.clone().find(|addr| flow.is_code(*addr, platform)) {
+                            warn(format!("writes to {:04X}-{:04X}, over the instruction at {code:04X}", written.start, written.end - 1));
+                        }
+                    }
+                    if opcode & 0xFF == 0x55 {
+                        known.stored = Some(addr);
+                    }
+                },
+                0x65 => {
+                    if let Some(i) = known.i {
+                        warn_past_end(flow, i, x as u16 + 1, "loads registers", &mut warn);
+                    }
+                    known.registers[..=x].fill(None);
+                    known.stored = Some(addr);
+                },
+                0x85 => known.registers[..=x].fill(None),
+                _ => {},
+            },
+            _ => {},
+        }
+        if sets_i(opcode) {
+            known.stored = None;
+        }
+        if writes_vf(opcode) {
+            known.logic = None;
+        }
+        previous = Some(opcode);
+    }
+}
+
+fn warn_past_end(flow: &Flow, i: u16, len: u16, what: &str, warn: &mut impl FnMut(String)) {
+    let last = i as u32 + len as u32 - 1;
+    if i >= flow.start && last >= flow.end as u32 {
+        warn(format!("{what} from {i:04X} to {last:04X}, but the rom ends at {:04X}", flow.end - 1));
+    }
+}
+
+/// The biggest screen the platform can have
+fn screen(platform: Platform) -> (u16, u16) {
+    return match platform {
+        Platform::Chip8 | Platform::Chip8X => (64, 32),
+        Platform::HiresChip8 => (64, 64),
+        Platform::SuperChip | Platform::XoChip => (128, 64),
+        Platform::MegaChip => (256, 192),
+    };
+}
+
+/// Whether the instruction reads memory at I or adds to it
+fn uses_i(opcode: u16) -> bool {
+    return opcode & 0xF000 == 0xD000 || matches!(opcode & 0xF0FF, 0xF01E | 0xF033 | 0xF055 | 0xF065);
+}
+
+/// Whether the instruction sets I outright
+fn sets_i(opcode: u16) -> bool {
+    return opcode & 0xF000 == 0xA000 || opcode == 0xF000 || matches!(opcode & 0xF0FF, 0xF029 | 0xF030);
+}
+
+/// Whether the instruction reads VF
+fn reads_vf(opcode: u16) -> bool {
+    let x = (opcode >> 8) & 0xF == 0xF;
+    let y = (opcode >> 4) & 0xF == 0xF;
+    return match opcode & 0xF000 {
+        0x3000 | 0x4000 | 0x7000 | 0xE000 => x,
+        0x5000 | 0x9000 | 0xD000 => x || y,
+        // 8FY0 only writes it
+        0x8000 => y || (x && opcode & 0xF != 0),
+        0xF000 => x && matches!(opcode & 0xFF, 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x3A | 0x55 | 0x75 | 0xF8),
+        _ => false,
+    };
+}
+
+/// Whether the instruction sets VF on every interpreter, as a result or a flag. 8XY1 to 8XY3
+/// don't count, setting VF to 0 being the quirk
+fn writes_vf(opcode: u16) -> bool {
+    let x = (opcode >> 8) & 0xF == 0xF;
+    return match opcode & 0xF000 {
+        0x6000 | 0x7000 | 0xC000 => x,
+        0x8000 => !matches!(opcode & 0xF, 0x1..=0x3),
+        0xD000 => true,
+        0xF000 => opcode & 0xFF == 0x65 || x && matches!(opcode & 0xFF, 0x07 | 0x0A | 0x85),
+        _ => false,
+    };
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{:04X}: {}", self.addr, self.message);
+    }
+}
@@ -1,18 +1,1794 @@
-mod chip;
+use chip8::audio::Wav;
+use chip8::chip::Chip8;
+use chip8::chip::Snapshot;
+use chip8::config::Config;
+use chip8::debugger::{self, Debugger};
+use chip8::diff;
+use chip8::font::Fontset;
+use chip8::frontend::filter::Filter;
+use chip8::frontend::input::InputProfile;
+use chip8::frontend::limiter::FrameLimit;
+use chip8::frontend::rotation::Rotation;
+use chip8::persist;
+use chip8::platform::Platform;
+use chip8::profile::Profile;
+use chip8::symbols::{self, SymbolTable};
+use chip8::trace;
 
-use chip::Chip8;
+/// Options that can go anywhere on the command line and apply to every rom loaded
+struct Options {
+    /// --platform <name>, otherwise the one an Octo cartridge asks for, otherwise CHIP-8
+    platform: Option<Platform>,
+    /// --font <name|file>, the standard font if it wasn't given
+    font: Fontset,
+    /// --speed <instructions per frame>, otherwise it's the rom's profile or analyze's guess
+    speed: Option<usize>,
+    /// --limit <vsync|60|uncapped|fps>, how the frontends pace frames, 60Hz if it wasn't given.
+    /// It's still accepted in builds without a frontend, it just doesn't do anything
+    #[cfg_attr(not(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal")), allow(dead_code))]
+    limit: FrameLimit,
+    /// --filter <nearest|scanlines|lcd>, how the GPU backed frontends scale the screen up,
+    /// nearest if it wasn't given
+    #[cfg_attr(not(any(feature = "gui", feature = "pixels")), allow(dead_code))]
+    filter: Filter,
+    /// --rotate <degrees>, how far the frontends turn the screen, otherwise it's the rom's
+    /// profile or cartridge
+    #[cfg_attr(not(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal")), allow(dead_code))]
+    rotation: Option<Rotation>,
+    /// --input <standard|split|pong|chip8x>, which keys are whose in the frontends, otherwise
+    /// it's the rom's profile
+    #[cfg_attr(not(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal")), allow(dead_code))]
+    input: Option<&'static InputProfile>,
+    /// --port-in <file> and --port-out <file>, what the frontends connect CHIP-8X's I/O port
+    /// to: the bytes FXFB reads, and where what FXF8 puts out is written
+    #[cfg_attr(not(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal")), allow(dead_code))]
+    port: (Option<String>, Option<String>),
+    /// --wav <file>, where headless runs (script, coverage, movie) write the sound to
+    wav: Option<String>,
+    /// --strict, unknown opcodes, reaching past the end of memory and ignored 0NNN calls stop
+    /// the rom with an error instead of being skipped over
+    strict: bool,
+    /// --dump-state-on-exit <file>, where the machine's state is written as JSON (see
+    /// chip/json.rs) when a run ends, - for standard output
+    dump_state: Option<String>,
+}
+
+/// How long the main block of generated roms is if it isn't said
+const FUZZ_INSTRUCTIONS: usize = 256;
+
+/// How many frames chip8 soak runs each rom for if it isn't said, about half an hour
+const SOAK_FRAMES: usize = 100_000;
+
+/// How many frames chip8 batch runs each rom or state for if it isn't said, a minute
+#[cfg(feature = "batch")]
+const BATCH_FRAMES: usize = 3600;
+
+static OPTIONS: std::sync::OnceLock<Options> = std::sync::OnceLock::new();
 
 fn main() {
-    let mut chip = Chip8::new(true);
-    chip.clear_display();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+
+    let platform = take_option(&mut args, "--platform").map(|name| {
+        Platform::from_name(&name).unwrap_or_else(|| {
+            eprintln!("Unknown platform '{name}', expected chip8, hires, chip8x, schip, xochip or megachip");
+            std::process::exit(2);
+        })
+    });
+    // A font that isn't one of the built in names is read from a file
+    let font = match take_option(&mut args, "--font") {
+        Some(name) => {
+            let font = Fontset::from_name(&name).map(Ok).unwrap_or_else(|| Fontset::load(&name));
+            font.unwrap_or_else(|e| {
+                eprintln!("Unknown font '{name}', expected standard, vip, dream6800, eti660 or an 80 byte file: {e}");
+                std::process::exit(2);
+            })
+        },
+        None => Fontset::Standard,
+    };
+    let speed = take_option(&mut args, "--speed").map(|speed| {
+        speed.parse::<usize>().ok().filter(|speed| *speed > 0).unwrap_or_else(|| {
+            eprintln!("Invalid speed '{speed}', expected a number of instructions per frame");
+            std::process::exit(2);
+        })
+    });
+    let limit = match take_option(&mut args, "--limit") {
+        Some(name) => FrameLimit::from_name(&name).unwrap_or_else(|| {
+            eprintln!("Unknown frame limit '{name}', expected vsync, 60, uncapped or a number of frames a second");
+            std::process::exit(2);
+        }),
+        None => FrameLimit::Fixed,
+    };
+    let filter = match take_option(&mut args, "--filter") {
+        Some(name) => Filter::from_name(&name).unwrap_or_else(|| {
+            eprintln!("Unknown filter '{name}', expected nearest, scanlines or lcd");
+            std::process::exit(2);
+        }),
+        None => Filter::Nearest,
+    };
+    let rotation = take_option(&mut args, "--rotate").map(|degrees| {
+        degrees.parse().ok().and_then(Rotation::from_degrees).unwrap_or_else(|| {
+            eprintln!("Invalid rotation '{degrees}', expected 0, 90, 180 or 270 degrees");
+            std::process::exit(2);
+        })
+    });
+    let input = take_option(&mut args, "--input").map(|name| {
+        InputProfile::from_name(&name).unwrap_or_else(|| {
+            eprintln!("Unknown input profile '{name}', expected standard, split, pong or chip8x");
+            std::process::exit(2);
+        })
+    });
+    // --data-dir <dir> keeps the data and the config there rather than where the platform
+    // keeps them (see persist.rs)
+    if let Some(dir) = take_option(&mut args, "--data-dir") {
+        persist::set_dir(std::path::Path::new(&dir));
+    }
+    let port = (take_option(&mut args, "--port-in"), take_option(&mut args, "--port-out"));
+    let wav = take_option(&mut args, "--wav");
+    let strict = take_flag(&mut args, "--strict");
+    let dump_state = take_option(&mut args, "--dump-state-on-exit");
+    let _ = OPTIONS.set(Options { platform, font, speed, limit, filter, rotation, input, port, wav, strict, dump_state });
+
+    if let Some(seconds) = take_option(&mut args, "--bench") {
+        bench(&args, &seconds);
+        return;
+    }
+
+    match args.first().map(String::as_str) {
+        Some("verify") => verify(&args[1..]),
+        Some("difftest") => difftest(&args[1..]),
+        Some("fuzzgen") => fuzzgen(&args[1..]),
+        Some("dump") => dump(&args[1..]),
+        Some("debug") => debug(&args[1..]),
+        Some("diff") => diff(&args[1..]),
+        Some("coverage") => coverage(&args[1..]),
+        Some("movie") => movie(&args[1..]),
+        Some("analyze") => analyze(&args[1..]),
+        Some("lint") => lint(&args[1..]),
+        Some("disasm") => disasm(&args[1..]),
+        Some("calls") => calls(&args[1..]),
+        Some("profile") => profile(&args[1..]),
+        Some("info") => info(&args[1..]),
+        Some("test") => test(&args[1..]),
+        Some("soak") => soak(&args[1..]),
+        #[cfg(feature = "batch")]
+        Some("batch") => batch(&args[1..]),
+        Some("tour") => tour(),
+        Some("setup") => {
+            setup();
+        },
+        Some("dirs") => dirs(),
+        #[cfg(feature = "bundled")]
+        Some("roms") => roms(),
+        #[cfg(feature = "scripting")]
+        Some("script") => script(&args[1..]),
+        #[cfg(feature = "gui")]
+        Some("gui") => gui(&args[1..]),
+        #[cfg(feature = "gui")]
+        Some("dev") => dev(&args[1..]),
+        #[cfg(feature = "minifb")]
+        Some("window") => window(&args[1..]),
+        #[cfg(feature = "minifb")]
+        Some("netplay") => netplay(&args[1..]),
+        #[cfg(feature = "pixels")]
+        Some("play") => play(&args[1..]),
+        #[cfg(feature = "pixels")]
+        Some("kiosk") => kiosk(&args[1..]),
+        #[cfg(feature = "terminal")]
+        Some("term") => term(&args[1..]),
+        #[cfg(feature = "server")]
+        Some("serve") => serve(&args[1..]),
+        #[cfg(feature = "stream")]
+        Some("stream") => stream(&args[1..]),
+        #[cfg(feature = "chat")]
+        Some("chat") => chat(&args[1..]),
+        _ => run(),
+    }
+}
+
+/// chip8
+/// Sets chip8 up the first time it's run (see setup()), then lists the roms in the roms folder
+/// and plays the one picked in the config's frontend. Without a frontend built in it's run
+/// headless until it exits
+fn run() {
+    let config = match Config::load() {
+        Ok(Some(config)) => config,
+        Ok(None) => setup(),
+        Err(e) => {
+            eprintln!("An error occured when loading the config {}: {e}", Config::path().display());
+            std::process::exit(2);
+        },
+    };
+    let rom_path = pick_rom(&config);
+    let frontend = config.frontend.or(chip8::config::available_frontends().first().map(|(name, _)| *name));
+    #[cfg_attr(not(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal")), allow(unused_variables))]
+    let args = [rom_path.clone()];
+    match frontend {
+        #[cfg(feature = "gui")]
+        Some("gui") => return gui(&args),
+        #[cfg(feature = "minifb")]
+        Some("window") => return window(&args),
+        #[cfg(feature = "pixels")]
+        Some("play") => return play(&args),
+        #[cfg(feature = "terminal")]
+        Some("term") => return term(&args),
+        _ => {},
+    }
+
+    let mut chip = load(&rom_path);
+    while !chip.exit_requested() {
+        chip.get_next_instruction();
+
+        if let Err(e) = chip.execute() {
+            eprintln!("{e}");
+            eprint!("{}", symbols::backtrace(chip.pc(), chip.call_stack(), None));
+            dump_state(&chip);
+            std::process::exit(1);
+        }
+    }
+    dump_state(&chip);
+}
+
+/// chip8 --bench <seconds> [rom] [--json]
+/// Runs the rom headless as fast as it goes for a number of seconds and reports how fast
+/// that was and where the time went, then the same as one line of key=value pairs. Without a
+/// rom it's the built in synthetic one, on CHIP-8 whatever the options, so the numbers can be
+/// compared between versions. --json prints them as a JSON object instead
+fn bench(args: &[String], seconds: &str) {
+    let mut args = args.to_vec();
+    let json = take_flag(&mut args, "--json");
+    let (mut chip, rom) = match &args[..] {
+        [] => {
+            let mut chip = Chip8::with_platform(Platform::Chip8, false);
+            chip.load_rom_bytes(&chip8::bench::synthetic_rom());
+            (chip, "synthetic".to_string())
+        },
+        [rom_path] => (load(rom_path), file_name(rom_path)),
+        _ => {
+            eprintln!("Usage: chip8 --bench <seconds> [rom] [--json]");
+            std::process::exit(2);
+        },
+    };
+    let seconds = seconds.parse::<f64>().ok().filter(|seconds| seconds.is_finite() && *seconds > 0.0).unwrap_or_else(|| {
+        eprintln!("invalid number of seconds '{seconds}'");
+        std::process::exit(2);
+    });
+
+    // A fixed seed so the rom takes the same path every bench
+    chip.seed_rng(0);
+    let bench = chip8::bench::bench(&mut chip, std::time::Duration::from_secs_f64(seconds));
+    if json {
+        println!("{}", bench.json(&rom));
+    } else {
+        print!("{bench}");
+        println!("{}", bench.summary());
+    }
+}
+
+/// chip8 verify <rom> <reference-trace>
+/// Runs the rom against a trace exported from another emulator and stops at the first
+/// instruction where the two disagree
+fn verify(args: &[String]) {
+    let [rom_path, trace_path] = args else {
+        eprintln!("Usage: chip8 verify <rom> <reference-trace>");
+        std::process::exit(2);
+    };
+
+    let trace = std::fs::read_to_string(trace_path)
+        .map_err(|e| e.to_string())
+        .and_then(|t| trace::parse_trace(&t))
+        .unwrap_or_else(|e| {
+            eprintln!("An error occured when reading the trace: {e}");
+            std::process::exit(2);
+        });
+
+    let mut chip = load(rom_path);
+
+    match trace::verify(&mut chip, &trace) {
+        Ok(steps) => println!("OK: {steps} instructions matched the reference trace"),
+        Err(divergence) => {
+            print_divergence(&divergence);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_divergence(divergence: &trace::Divergence) {
+    println!(
+        "Diverged at instruction {} (trace line {}):",
+        divergence.step, divergence.line
+    );
+    println!("  expected: {}", divergence.expected);
+    println!("  actual:   {}", divergence.actual);
+    if let Some(error) = &divergence.error {
+        println!("  error: {error}");
+    }
+    for (field, expected, actual) in divergence.expected.diff(&divergence.actual) {
+        println!("  {field:>3}: expected {expected}, got {actual}");
+    }
+}
+
+/// chip8 difftest <rom>... [--steps <n>] [--against <command>]
+/// chip8 difftest --random <count> [--seed <n>] [--steps <n>]
+/// Runs roms on the interpreter and the reference CHIP-8 in differential.rs side by side,
+/// stopping at the first instruction they disagree on. --random generates that many roms
+/// with seeds counting up from --seed (see fuzzgen.rs), saving any that diverge as
+/// difftest-<seed>.ch8 to run again. With --against the command is run with each rom's path
+/// after it and has to print a trace (see trace.rs) to compare against instead
+fn difftest(args: &[String]) {
+    let mut args = args.to_vec();
+    let number = |value: Option<String>, default: u64| match value {
+        Some(value) => value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("Invalid number '{value}'");
+            std::process::exit(2);
+        }),
+        None => default,
+    };
+    let steps = number(take_option(&mut args, "--steps"), chip8::differential::STEPS as u64) as usize;
+    let seed = number(take_option(&mut args, "--seed"), 0);
+    let random = take_option(&mut args, "--random").map(|count| number(Some(count), 0));
+    let against = take_option(&mut args, "--against");
+    // The seeds can't count up past the last u64
+    let seeds = random.map(|count| seed.checked_add(count).map(|end| seed..end));
+    if random.is_some() != args.is_empty() || (random.is_some() && against.is_some()) || matches!(seeds, Some(None)) {
+        eprintln!("Usage: chip8 difftest <rom>... [--steps <n>] [--against <command>]");
+        eprintln!("       chip8 difftest --random <count> [--seed <n>] [--steps <n>]");
+        std::process::exit(2);
+    }
+
+    let mut roms: Vec<(String, Vec<u8>)> = Vec::new();
+    if let Some(Some(seeds)) = seeds {
+        for seed in seeds {
+            roms.push((format!("difftest-{seed}.ch8"), chip8::fuzzgen::generate(seed, FUZZ_INSTRUCTIONS)));
+        }
+    }
+    for rom_path in &args {
+        let rom = read_rom(rom_path).unwrap_or_else(|e| {
+            eprintln!("An error occured when loading the rom: {e}");
+            std::process::exit(2);
+        });
+        roms.push((rom_path.clone(), rom));
+    }
+
+    let mut diverged = 0;
+    for (name, rom) in &roms {
+        if let Some(command) = &against {
+            let mut words = command.split_whitespace();
+            let output = std::process::Command::new(words.next().unwrap_or_default()).args(words).arg(name).output();
+            let trace = output
+                .map_err(|e| e.to_string())
+                .and_then(|output| trace::parse_trace(&String::from_utf8_lossy(&output.stdout)));
+            let trace = trace.unwrap_or_else(|e| {
+                eprintln!("An error occured when running '{command}': {e}");
+                std::process::exit(2);
+            });
+            let mut chip = load_bytes(rom, None, None);
+            match trace::verify(&mut chip, &trace) {
+                Ok(steps) => println!("{name}: ok, {steps} instructions agreed"),
+                Err(divergence) => {
+                    println!("{name}:");
+                    print_divergence(&divergence);
+                    diverged += 1;
+                },
+            }
+            continue;
+        }
+
+        match chip8::differential::run(rom, steps, seed) {
+            Ok(agreed) => match agreed.stopped {
+                Some(stopped) => println!("{name}: ok, {} instructions agreed, then both stopped: {stopped}", agreed.steps),
+                None => println!("{name}: ok, {} instructions agreed", agreed.steps),
+            },
+            Err(divergence) => {
+                print!("{name}: {divergence}");
+                if random.is_some() {
+                    if let Err(e) = std::fs::write(name, rom) {
+                        eprintln!("An error occured when saving the rom: {e}");
+                    }
+                }
+                diverged += 1;
+            },
+        }
+    }
+    println!("{} agreed, {diverged} diverged", roms.len() - diverged);
+    if diverged > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// chip8 fuzzgen <file> [--seed <n>] [--instructions <n>]
+/// Writes a random but well behaved rom for stress testing (see fuzzgen.rs). Without a seed
+/// one is picked and printed, so the rom can be made again
+fn fuzzgen(args: &[String]) {
+    let mut args = args.to_vec();
+    let seed = take_option(&mut args, "--seed");
+    let instructions = take_option(&mut args, "--instructions");
+    let [path] = &args[..] else {
+        eprintln!("Usage: chip8 fuzzgen <file> [--seed <n>] [--instructions <n>]");
+        std::process::exit(2);
+    };
+    let number = |value: &str| {
+        value.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("Invalid number '{value}'");
+            std::process::exit(2);
+        })
+    };
+
+    let seed = match seed {
+        Some(seed) => number(&seed),
+        None => {
+            let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+            let seed = now.as_nanos() as u64;
+            eprintln!("Seed {seed}");
+            seed
+        },
+    };
+    let instructions = instructions.map_or(FUZZ_INSTRUCTIONS, |n| number(&n) as usize);
+    if let Err(e) = std::fs::write(path, chip8::fuzzgen::generate(seed, instructions)) {
+        eprintln!("An error occured when writing the rom: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 dump <rom> [start] [end]
+/// Prints a hex dump of memory straight after the rom is loaded
+fn dump(args: &[String]) {
+    let Some(rom_path) = args.first() else {
+        eprintln!("Usage: chip8 dump <rom> [start] [end]");
+        std::process::exit(2);
+    };
+
+    let start = number_arg(args, 1, 0);
+    let end = number_arg(args, 2, 0x1000);
+
+    let chip = load(rom_path);
+    print!("{}", chip.dump_memory(start..end));
+}
+
+/// chip8 debug <rom> [symbols]
+/// Opens the interactive debugger with the rom, and optionally a symbol file, loaded
+fn debug(args: &[String]) {
+    let (rom_path, symbols_path) = match args {
+        [rom] => (rom, None),
+        [rom, symbols] => (rom, Some(symbols)),
+        _ => {
+            eprintln!("Usage: chip8 debug <rom> [symbols]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut debugger = Debugger::new(load(rom_path));
+    let rom = read_rom(rom_path).unwrap_or_default();
+    debugger.set_trace_dir(chip8::tracedb::trace_dir(&rom));
+    match chip8::cheat::load_cheats(&rom) {
+        Ok(cheats) => debugger.set_cheats(cheats),
+        Err(e) => eprintln!("An error occured when loading the cheats {}: {e}", chip8::cheat::cheats_path(&rom).display()),
+    }
+    if let Some(symbols_path) = symbols_path {
+        match SymbolTable::load(symbols_path) {
+            Ok(symbols) => debugger.set_symbols(symbols),
+            Err(e) => {
+                eprintln!("An error occured when loading the symbols: {e}");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    debugger.run();
+    save(debugger.chip(), rom_path);
+    dump_state(debugger.chip());
+}
+
+/// chip8 diff <a.state> <b.state>
+/// Shows what's different between two save states, as saved by the debugger's save command.
+/// States it saved to the states folder can be given by name
+fn diff(args: &[String]) {
+    let [a, b] = args else {
+        eprintln!("Usage: chip8 diff <a.state> <b.state>");
+        std::process::exit(2);
+    };
+
+    let read = |path: &String| {
+        let bytes = std::fs::read(persist::find_state(path)).map_err(|e| e.to_string());
+        return bytes.and_then(|bytes| Snapshot::from_bytes(&bytes)).unwrap_or_else(|e| {
+            eprintln!("An error occured when reading {path}: {e}");
+            std::process::exit(1);
+        });
+    };
+    match diff::diff(&read(a), &read(b)).as_str() {
+        "" => println!("The states are the same"),
+        report => print!("{report}"),
+    }
+}
+
+/// chip8 script <rom> <script> [frames] [--inputs <file>]
+/// Runs the rom headless for a number of frames (default 600, 10 seconds) with the
+/// script's hooks attached, exiting with an error if the script throws one. The keys an
+/// inputs file gives (in replay.rs's format) are held each frame, for on_key to see
+#[cfg(feature = "scripting")]
+fn script(args: &[String]) {
+    let mut args = args.to_vec();
+    let inputs = take_option(&mut args, "--inputs").map(|path| {
+        let replay = std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| chip8::replay::Replay::parse(&text));
+        replay.map(|replay| replay.inputs).unwrap_or_else(|e| {
+            eprintln!("An error occured when reading the inputs {path}: {e}");
+            std::process::exit(2);
+        })
+    });
+    let (rom_path, script_path) = match &args[..] {
+        [rom, script] | [rom, script, _] => (rom, script),
+        _ => {
+            eprintln!("Usage: chip8 script <rom> <script> [frames] [--inputs <file>]");
+            std::process::exit(2);
+        }
+    };
+    let frames = match args.get(2) {
+        Some(frames) => frames.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("invalid frame count '{frames}'");
+            std::process::exit(2);
+        }),
+        None => 600,
+    };
+
+    let mut host = std::fs::read_to_string(script_path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| chip8::script::ScriptHost::new(&source))
+        .unwrap_or_else(|e| {
+            eprintln!("An error occured when loading the script: {e}");
+            std::process::exit(2);
+        });
+    let mut chip = load(rom_path);
+    let mut wav = start_wav(rom_path);
+
+    let result = (|| {
+        for frame in 0..frames {
+            if let Some(inputs) = &inputs {
+                // Nothing's held once the inputs run out
+                let held = inputs.get(frame as usize).copied().unwrap_or(0);
+                for key in 0..16 {
+                    chip.set_key(key, held & 1 << key != 0);
+                }
+            }
+            host.keys_changed(&mut chip)?;
+            for _ in 0..chip.cycles_per_frame() {
+                let pc = chip.pc();
+                chip.execute().map_err(|e| {
+                    format!("{e}\n{}", symbols::backtrace(chip.pc(), chip.call_stack(), None))
+                })?;
+                let opcode = chip.opcode();
+                host.on_instruction(&mut chip, pc, opcode)?;
+                host.keys_changed(&mut chip)?;
+            }
+            chip.tick_timers();
+            if let Some(wav) = &mut wav {
+                wav.record(&chip);
+            }
+            host.on_frame(&mut chip, frame)?;
+            host.keys_changed(&mut chip)?;
+
+            if host.stopped() || chip.exit_requested() {
+                break;
+            }
+        }
+        return Ok::<(), String>(());
+    })();
+
+    save(&chip, rom_path);
+    dump_state(&chip);
+    finish_wav(wav);
+
+    if let Err(e) = result {
+        eprintln!("Script error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 gui <rom>
+/// Plays the rom in a window with the debugger panels alongside it
+#[cfg(feature = "gui")]
+fn gui(args: &[String]) {
+    let [rom_path] = args else {
+        eprintln!("Usage: chip8 gui <rom>");
+        std::process::exit(2);
+    };
+
+    let rom = read_rom(rom_path).unwrap_or_default();
+    let options = OPTIONS.get().expect("options are parsed first");
+    let gui = chip8::gui::Gui::new(load(rom_path))
+        .with_rom(&rom)
+        .with_limit(options.limit)
+        .with_filter(options.filter)
+        .with_controls(controls(rom_path))
+        .with_loader(|cart: &chip8::cart::Cart| load_bytes(&cart.rom, cart.platform, cart.speed))
+        .with_exit(dump_state);
+    if let Err(e) = gui.run() {
+        eprintln!("An error occured in the window: {e}");
+        std::process::exit(1);
+    }
+}
 
-    if let Err(e) = chip.load_rom("BRIX") {
+/// chip8 dev <source.8o>
+/// Assembles the Octo source and plays it in the gui, assembling and reloading it again
+/// each time it's saved
+#[cfg(feature = "gui")]
+fn dev(args: &[String]) {
+    let [source] = args else {
+        eprintln!("Usage: chip8 dev <source.8o>");
+        std::process::exit(2);
+    };
+
+    let options = OPTIONS.get().expect("options are parsed first");
+    let gui = chip8::gui::Gui::new(load_bytes(&[], None, None))
+        .with_limit(options.limit)
+        .with_filter(options.filter)
+        .with_loader(|cart: &chip8::cart::Cart| load_bytes(&cart.rom, cart.platform, cart.speed))
+        .with_source(std::path::Path::new(source));
+    if let Err(e) = gui.run() {
+        eprintln!("An error occured in the window: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 window <rom> [scale]
+/// Plays the rom in a plain window, scaled up 8 times by default (0 fits it to the screen)
+#[cfg(feature = "minifb")]
+fn window(args: &[String]) {
+    let (rom_path, scale) = match args {
+        [rom] => (rom, 8),
+        [rom, scale] => (rom, scale.parse::<usize>().unwrap_or_else(|_| {
+            eprintln!("invalid scale '{scale}'");
+            std::process::exit(2);
+        })),
+        _ => {
+            eprintln!("Usage: chip8 window <rom> [scale]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut chip = load(rom_path);
+    let limit = OPTIONS.get().expect("options are parsed first").limit;
+    let controls = controls(rom_path);
+    let result = chip8::frontend::minifb::run(&mut chip, chip8::frontend::minifb::scale(scale), limit, controls, None);
+    save(&chip, rom_path);
+    dump_state(&chip);
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 netplay <rom> host <addr> | chip8 netplay <rom> join <addr>
+/// Plays the rom with someone on another machine (see netplay.rs), hosting on addr (e.g.
+/// 0.0.0.0:6465) for them to join or joining the game they're hosting there
+#[cfg(feature = "minifb")]
+fn netplay(args: &[String]) {
+    use chip8::netplay::Session;
+
+    let (rom_path, hosting, addr) = match args {
+        [rom, mode, addr] if mode == "host" => (rom, true, addr),
+        [rom, mode, addr] if mode == "join" => (rom, false, addr),
+        _ => {
+            eprintln!("Usage: chip8 netplay <rom> host <addr> | chip8 netplay <rom> join <addr>");
+            std::process::exit(2);
+        }
+    };
+
+    let cart = read_cart(rom_path).unwrap_or_else(|e| {
         eprintln!("An error occured when loading the rom: {e}");
+        std::process::exit(2);
+    });
+    let mut chip = fresh(&cart.rom, cart.platform, cart.speed, &load_profile(&cart.rom));
+    let session = if hosting {
+        println!("Waiting for the other player to join on {addr}");
+        Session::host(addr, &mut chip)
+    } else {
+        println!("Joining {addr}");
+        Session::join(addr, &mut chip)
+    };
+    let mut session = session.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    println!("Playing as player {}", session.player());
+
+    let limit = OPTIONS.get().expect("options are parsed first").limit;
+    let result = chip8::frontend::minifb::run(&mut chip, chip8::frontend::minifb::scale(8), limit, controls(rom_path), Some(&mut session));
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 play <rom> [scale]
+/// Plays the rom in a GPU backed window, scaled up 8 times to start with
+#[cfg(feature = "pixels")]
+fn play(args: &[String]) {
+    let (rom_path, scale) = match args {
+        [rom] => (rom, 8),
+        [rom, scale] => (rom, scale.parse::<u32>().unwrap_or_else(|_| {
+            eprintln!("invalid scale '{scale}'");
+            std::process::exit(2);
+        })),
+        _ => {
+            eprintln!("Usage: chip8 play <rom> [scale]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut chip = load(rom_path);
+    let options = OPTIONS.get().expect("options are parsed first");
+    let controls = controls(rom_path);
+    // A rom dropped on the window takes over, saving the one it replaces first
+    let mut playing = rom_path.clone();
+    let mut open = |path: &std::path::Path, chip: &Chip8| {
+        let cart = read_cart(&path.to_string_lossy())?;
+        save(chip, &playing);
+        playing = path.to_string_lossy().into_owned();
+        return Ok(load_bytes(&cart.rom, cart.platform, cart.speed));
+    };
+    let result = chip8::frontend::pixels::run(&mut chip, scale, options.limit, options.filter, controls, &mut open, None);
+    save(&chip, &playing);
+    dump_state(&chip);
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 kiosk <rom|dir>... [--seconds <n>]
+/// Plays through the roms in a GPU backed window, each for a number of seconds (default 60)
+/// or until 1 and F are held, then round again (see playlist.rs). The roms in a directory
+/// are played in name order
+#[cfg(feature = "pixels")]
+fn kiosk(args: &[String]) {
+    use chip8::frontend::playlist::Playlist;
+
+    let mut args = args.to_vec();
+    let seconds = take_option(&mut args, "--seconds").map_or(60.0, |seconds| {
+        seconds.parse::<f64>().ok().filter(|seconds| seconds.is_finite() && *seconds > 0.0).unwrap_or_else(|| {
+            eprintln!("Invalid number of seconds '{seconds}'");
+            std::process::exit(2);
+        })
+    });
+    if args.is_empty() {
+        eprintln!("Usage: chip8 kiosk <rom|dir>... [--seconds <n>]");
+        std::process::exit(2);
+    }
+
+    let mut roms = Vec::new();
+    for arg in &args {
+        let path = std::path::PathBuf::from(arg);
+        match std::fs::read_dir(&path) {
+            Ok(entries) => {
+                let mut files: Vec<_> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+                files.retain(|file| file.is_file());
+                files.sort();
+                roms.extend(files);
+            },
+            Err(_) => roms.push(path),
+        }
+    }
+
+    let mut playlist = Playlist::new(roms, std::time::Duration::from_secs_f64(seconds));
+    let mut open = |path: &std::path::Path, _: &Chip8| {
+        let cart = read_cart(&path.to_string_lossy())?;
+        return Ok(load_bytes(&cart.rom, cart.platform, cart.speed));
+    };
+    // The first rom that loads starts it off
+    let mut chip = loop {
+        if playlist.is_empty() {
+            eprintln!("None of the roms could be loaded");
+            std::process::exit(1);
+        }
+        let path = playlist.current().to_path_buf();
+        match open(&path, &Chip8::new(false)) {
+            Ok(chip) => break chip,
+            Err(e) => eprintln!("An error occured when loading {}: {e}", path.display()),
+        }
+        if playlist.remove() {
+            playlist.advance(std::time::Instant::now());
+        }
+    };
+
+    let options = OPTIONS.get().expect("options are parsed first");
+    // Nobody's looking after a kiosk to click back on the window, so it keeps going unfocused
+    let controls = chip8::frontend::controls::Controls::new(chip8::audio::Beep::default(), false);
+    let result = chip8::frontend::pixels::run(&mut chip, 8, options.limit, options.filter, controls, &mut open, Some(&mut playlist));
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 term <rom> [scale] [kitty|iterm2|sixel]
+/// Plays the rom in the terminal as real pixels, scaled up 8 times to start with and drawn
+/// with the graphics protocol the terminal looks like it supports unless one's given
+#[cfg(feature = "terminal")]
+fn term(args: &[String]) {
+    use chip8::frontend::terminal::{self, Protocol};
+
+    let usage = || -> ! {
+        eprintln!("Usage: chip8 term <rom> [scale] [kitty|iterm2|sixel]");
+        std::process::exit(2);
+    };
+    let Some((rom_path, rest)) = args.split_first() else {
+        usage();
+    };
+    let mut scale = 8;
+    let mut protocol = Protocol::detect();
+    for arg in rest {
+        if let Ok(factor) = arg.parse::<usize>() {
+            scale = factor;
+        } else if let Some(named) = Protocol::from_name(arg) {
+            protocol = named;
+        } else {
+            usage();
+        }
+    }
+
+    let mut chip = load(rom_path);
+    let limit = OPTIONS.get().expect("options are parsed first").limit;
+    let controls = controls(rom_path);
+    let rom = read_rom(rom_path).unwrap_or_default();
+    let result = terminal::run(&mut chip, &rom, protocol, scale, limit, controls);
+    save(&chip, rom_path);
+    dump_state(&chip);
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 serve <addr> [rom]
+/// Serves JSON-RPC on addr (e.g. 127.0.0.1:6464) for controlling the machine remotely,
+/// with the rom loaded to start with if one's given
+#[cfg(feature = "server")]
+fn serve(args: &[String]) {
+    let platform = OPTIONS.get().expect("options are parsed first").platform.unwrap_or(Platform::Chip8);
+    let (addr, chip) = match args {
+        [addr] => (addr, Chip8::with_platform(platform, false)),
+        [addr, rom] => (addr, load(rom)),
+        _ => {
+            eprintln!("Usage: chip8 serve <addr> [rom]");
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(e) = chip8::server::Server::new(chip).serve(addr.as_str()) {
+        eprintln!("An error occured while serving on {addr}: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 stream <addr> <rom>
+/// Runs the rom headless and streams it over a WebSocket on addr, with a page to watch and
+/// play it from a browser at http://<addr>/
+#[cfg(feature = "stream")]
+fn stream(args: &[String]) {
+    let [addr, rom] = args else {
+        eprintln!("Usage: chip8 stream <addr> <rom>");
+        std::process::exit(2);
+    };
+
+    let mut chip = load(rom);
+    if let Err(e) = chip8::stream::serve(&mut chip, addr.as_str()) {
+        eprintln!("An error occured while streaming on {addr}: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 chat <stream-addr> <irc-server> <channel> [--window <ms>] [--hold <ms>]
+///            [--debounce <ms>] [--word <word>=<key>]...
+/// Lets the channel's chat play the rom chip8 stream is streaming on stream-addr, by voting
+/// on keys with commands like !4 or !left (see chat.rs)
+#[cfg(feature = "chat")]
+fn chat(args: &[String]) {
+    use chip8::chat::Settings;
+
+    let mut args = args.to_vec();
+    let mut settings = Settings::default();
+    let millis = |option: &str, value: String| {
+        return value.parse().map(std::time::Duration::from_millis).unwrap_or_else(|_| {
+            eprintln!("Invalid {option} '{value}', expected a number of milliseconds");
+            std::process::exit(2);
+        });
+    };
+    if let Some(window) = take_option(&mut args, "--window") {
+        settings.window = millis("--window", window);
+    }
+    if let Some(hold) = take_option(&mut args, "--hold") {
+        settings.hold = millis("--hold", hold);
+    }
+    if let Some(debounce) = take_option(&mut args, "--debounce") {
+        settings.debounce = millis("--debounce", debounce);
+    }
+    while let Some(word) = take_option(&mut args, "--word") {
+        let parsed = word.split_once('=').and_then(|(name, key)| {
+            let key = u8::from_str_radix(key, 16).ok().filter(|key| *key < 16)?;
+            return Some((name.trim_start_matches('!').to_ascii_lowercase(), key));
+        });
+        let Some((name, key)) = parsed else {
+            eprintln!("Invalid word '{word}', expected <word>=<key in hex>, e.g. jump=5");
+            std::process::exit(2);
+        };
+        settings.words.retain(|(existing, _)| *existing != name);
+        settings.words.push((name, key));
+    }
+    let [stream, server, channel] = &args[..] else {
+        eprintln!("Usage: chip8 chat <stream-addr> <irc-server> <channel> [--window <ms>] [--hold <ms>] [--debounce <ms>] [--word <word>=<key>]...");
+        std::process::exit(2);
+    };
+
+    if let Err(e) = chip8::chat::run(stream, server, channel, settings) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// chip8 coverage <rom> [frames] [--html <file>]
+/// Runs the rom headless for a number of frames (default 600) and reports which parts
+/// of it were executed or read as data, optionally writing an HTML heat map as well
+fn coverage(args: &[String]) {
+    let mut positional = Vec::new();
+    let mut html_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--html" => html_path = args.next(),
+            _ => positional.push(arg),
+        }
+    }
+
+    let (rom_path, frames) = match positional[..] {
+        [rom] => (rom, 600),
+        [rom, frames] => (rom, frames.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("invalid frame count '{frames}'");
+            std::process::exit(2);
+        })),
+        _ => {
+            eprintln!("Usage: chip8 coverage <rom> [frames] [--html <file>]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut chip = load(rom_path);
+    let rom_len = std::fs::metadata(rom_path).map(|m| m.len() as usize).unwrap_or(0);
+    chip.enable_coverage();
+    let mut wav = start_wav(rom_path);
+
+    for _ in 0..frames {
+        if let Err(e) = chip.run_frame() {
+            eprintln!("{e}");
+            break;
+        }
+        if let Some(wav) = &mut wav {
+            wav.record(&chip);
+        }
+        if chip.exit_requested() {
+            break;
+        }
+    }
+    finish_wav(wav);
+    dump_state(&chip);
+
+    let start = chip.platform().start_address() as usize;
+    let range = start..start + rom_len;
+    let coverage = chip.coverage().expect("coverage was enabled");
+    print!("{}", coverage.report(range.clone()));
+
+    if let Some(html_path) = html_path {
+        let html = coverage.html(range.clone(), chip.read_mem(0, usize::MAX));
+        if let Err(e) = std::fs::write(html_path, html) {
+            eprintln!("An error occured when writing the heat map: {e}");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// chip8 movie <rom> <file> [frames]
+/// Runs the rom headless for a number of frames (default 600) and records it to play back
+/// in a terminal, as an asciinema cast if the file ends in .cast or bare ANSI escapes if not
+fn movie(args: &[String]) {
+    let (rom_path, movie_path, frames) = match args {
+        [rom, file] => (rom, file, 600),
+        [rom, file, frames] => (rom, file, frames.parse::<u64>().unwrap_or_else(|_| {
+            eprintln!("invalid frame count '{frames}'");
+            std::process::exit(2);
+        })),
+        _ => {
+            eprintln!("Usage: chip8 movie <rom> <file> [frames]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut chip = load(rom_path);
+    let mut movie = chip8::movie::Movie::new();
+    let mut wav = start_wav(rom_path);
+    movie.record(&chip);
+    for _ in 0..frames {
+        if let Err(e) = chip.run_frame() {
+            eprintln!("{e}");
+            break;
+        }
+        movie.record(&chip);
+        if let Some(wav) = &mut wav {
+            wav.record(&chip);
+        }
+        if chip.exit_requested() {
+            break;
+        }
+    }
+    finish_wav(wav);
+    dump_state(&chip);
+
+    let result = std::fs::File::create(movie_path).and_then(|file| {
+        let mut out = std::io::BufWriter::new(file);
+        if movie_path.ends_with(".cast") {
+            movie.write_cast(&mut out)?;
+        } else {
+            movie.write_ansi(&mut out)?;
+        }
+        return std::io::Write::flush(&mut out);
+    });
+    if let Err(e) = result {
+        eprintln!("An error occured when writing the movie: {e}");
+        std::process::exit(1);
     }
+}
+
+/// chip8 analyze <rom>
+/// Statically scans the rom and reports the instructions it uses, any that need an
+/// extended platform, and anything that behaves differently between interpreters
+fn analyze(args: &[String]) {
+    let [rom_path] = args else {
+        eprintln!("Usage: chip8 analyze <rom>");
+        std::process::exit(2);
+    };
+
+    let rom = read_rom(rom_path).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the rom: {e}");
+        std::process::exit(2);
+    });
+
+    print!("{}", chip8::analyze::analyze(&rom));
+}
 
+/// chip8 lint <rom>
+/// Looks through the rom for likely mistakes and reliance on quirks, following its control
+/// flow on the platform from --platform, its cartridge or the analysis. Exits 1 if there are any
+fn lint(args: &[String]) {
+    let [rom_path] = args else {
+        eprintln!("Usage: chip8 lint <rom>");
+        std::process::exit(2);
+    };
+
+    let (rom, platform, _) = read_for_tracing(rom_path, None);
+    let warnings = chip8::lint::lint(&rom, platform);
+    for warning in &warnings {
+        println!("{warning}");
+    }
+    if warnings.is_empty() {
+        println!("Nothing found on {platform}");
+    } else {
+        println!("{} warning{} on {platform}", warnings.len(), if warnings.len() == 1 { "" } else { "s" });
+        std::process::exit(1);
+    }
+}
+
+/// chip8 disasm <rom> [symbols]
+/// Disassembles the instructions the rom can reach, following its control flow on the platform
+/// from --platform, its cartridge or the analysis, and lists the rest as data. Addresses in the
+/// symbol file get its names
+fn disasm(args: &[String]) {
+    let (rom_path, symbols) = match args {
+        [rom_path] => (rom_path, None),
+        [rom_path, symbols] => (rom_path, Some(symbols)),
+        _ => {
+            eprintln!("Usage: chip8 disasm <rom> [symbols]");
+            std::process::exit(2);
+        },
+    };
+
+    let (rom, platform, symbols) = read_for_tracing(rom_path, symbols);
+    print!("{}", chip8::disasm::disassemble(&file_name(rom_path), &rom, platform, symbols.as_ref()));
+}
+
+/// chip8 calls <rom> [symbols] [--dot]
+/// Reports the rom's subroutines, their sizes, callers and calls and whether they draw or read
+/// the keys, going by its control flow like disasm. --dot prints the call graph for Graphviz
+/// instead
+fn calls(args: &[String]) {
+    let mut args = args.to_vec();
+    let dot = take_flag(&mut args, "--dot");
+    let (rom_path, symbols) = match &args[..] {
+        [rom_path] => (rom_path, None),
+        [rom_path, symbols] => (rom_path, Some(symbols)),
+        _ => {
+            eprintln!("Usage: chip8 calls <rom> [symbols] [--dot]");
+            std::process::exit(2);
+        },
+    };
+
+    let (rom, platform, symbols) = read_for_tracing(rom_path, symbols);
+    let graph = chip8::callgraph::call_graph(&file_name(rom_path), &rom, platform, symbols.as_ref());
+    if dot {
+        print!("{}", graph.dot());
+    } else {
+        print!("{}", graph.report());
+    }
+}
+
+/// Reads a rom to trace its control flow, with the platform it's for from --platform, its
+/// cartridge or the analysis, and the symbol file if there is one
+fn read_for_tracing(rom_path: &str, symbols: Option<&String>) -> (Vec<u8>, Platform, Option<SymbolTable>) {
+    let cart = read_cart(rom_path).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the rom: {e}");
+        std::process::exit(2);
+    });
+    let symbols = symbols.map(|path| {
+        SymbolTable::load(path).unwrap_or_else(|e| {
+            eprintln!("An error occured when loading the symbols: {e}");
+            std::process::exit(2);
+        })
+    });
+    let options = OPTIONS.get().expect("options are parsed first");
+    let platform = options.platform.or(cart.platform).unwrap_or_else(|| chip8::analyze::analyze(&cart.rom).platform);
+    return (cart.rom, platform, symbols);
+}
+
+/// The rom's file name without the directories
+fn file_name(rom_path: &str) -> String {
+    return std::path::Path::new(rom_path).file_name().map_or(rom_path.to_string(), |name| name.to_string_lossy().to_string());
+}
+
+/// chip8 profile <rom>
+/// Shows where the rom's profile and cheats live and what the profile currently sets
+fn profile(args: &[String]) {
+    let [rom_path] = args else {
+        eprintln!("Usage: chip8 profile <rom>");
+        std::process::exit(2);
+    };
+
+    let rom = read_rom(rom_path).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the rom: {e}");
+        std::process::exit(2);
+    });
+
+    println!("Profile: {}", Profile::path(&rom).display());
+    let profile = load_profile(&rom);
+    for range in profile.persist {
+        println!("  persist = {:X}-{:X}", range.start, range.end - 1);
+    }
+    if let Some(speed) = profile.speed {
+        println!("  speed = {speed}");
+    }
+    if let Some(pause) = profile.pause_on_focus_loss {
+        println!("  pause_on_focus_loss = {pause}");
+    }
+    if profile.beep != chip8::audio::Beep::default() {
+        let beep = profile.beep;
+        println!("  beep = {}", beep.waveform);
+        println!("  beep_hz = {}", beep.hz);
+        println!("  beep_volume = {}", beep.volume);
+        println!("  beep_attack = {}", beep.attack_ms);
+        println!("  beep_release = {}", beep.release_ms);
+    }
+    println!("Cheats: {}", chip8::cheat::cheats_path(&rom).display());
+}
+
+/// chip8 info <rom>
+/// Prints the rom's size, checksums, the platform it seems to be for, where it starts and any
+/// text in it, for cataloguing roms and for bug reports
+fn info(args: &[String]) {
+    let [rom_path] = args else {
+        eprintln!("Usage: chip8 info <rom>");
+        std::process::exit(2);
+    };
+
+    let cart = read_cart(rom_path).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the rom: {e}");
+        std::process::exit(2);
+    });
+
+    print!("{}", chip8::info::Info::new(&cart.rom));
+    // There's no rom database, so what's known beyond the rom itself is what its cartridge
+    // and profile say
+    if let Some(platform) = cart.platform {
+        println!("Cartridge:   {platform}");
+    }
+    if let Some(speed) = cart.speed {
+        println!("Cartridge:   {speed} instructions per frame");
+    }
+    if Profile::path(&cart.rom).exists() {
+        println!("Profile:     {}", Profile::path(&cart.rom).display());
+    }
+    if chip8::cheat::cheats_path(&cart.rom).exists() {
+        println!("Cheats:      {}", chip8::cheat::cheats_path(&cart.rom).display());
+    }
+}
+
+/// chip8 test <corpus> [--update]
+/// Runs every replay in the corpus directory and checks the screen after each frame against
+/// its hashes, reporting the frame each failing one first diverged at. Replays without hashes
+/// have them recorded, and --update records them all again
+fn test(args: &[String]) {
+    let mut args = args.to_vec();
+    let update = take_flag(&mut args, "--update");
+    let [dir] = &args[..] else {
+        eprintln!("Usage: chip8 test <corpus> [--update]");
+        std::process::exit(2);
+    };
+
+    let cases = chip8::replay::corpus(std::path::Path::new(dir)).unwrap_or_else(|e| {
+        eprintln!("An error occured when reading the corpus: {e}");
+        std::process::exit(2);
+    });
+
+    let mut failed = 0;
+    for case in &cases {
+        let outcome = case.run(update);
+        println!("{}: {outcome}", case.name);
+        if !outcome.passed() {
+            failed += 1;
+        }
+    }
+    println!("{} passed, {failed} failed", cases.len() - failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// chip8 setup
+/// Asks where the roms are, which keys to play on and which frontend to play in, and writes
+/// the answers to the config (see config.rs). Enter keeps what's in brackets, the answer from
+/// last time or a guess
+fn setup() -> Config {
+    use chip8::config;
+    use chip8::frontend::input;
+
+    let mut config = load_config();
+    println!("Setting up chip8, press Enter to keep what's in brackets.");
+
+    let guess = config.roms.clone().or(Some(std::path::PathBuf::from("roms")).filter(|roms| roms.is_dir()));
     loop {
-        chip.get_next_instruction();
+        let shown = guess.as_ref().map_or("none".to_string(), |roms| roms.display().to_string());
+        let answer = ask(&format!("\nWhich folder are your roms in? [{shown}] "));
+        let roms = if answer.is_empty() { guess.clone() } else { Some(std::path::PathBuf::from(answer)) };
+        match roms {
+            Some(roms) if !roms.is_dir() => println!("There's no folder at {}", roms.display()),
+            // Kept absolute so chip8 finds them from anywhere
+            roms => {
+                config.roms = roms.map(|roms| std::path::absolute(&roms).unwrap_or(roms));
+                break;
+            },
+        }
+    }
+
+    println!("\nWhich keys do you play on?");
+    for (i, profile) in input::PROFILES.iter().enumerate() {
+        println!("  {}. {:<10} {}", i + 1, profile.name, profile.description);
+    }
+    let names: Vec<&str> = input::PROFILES.iter().map(|profile| profile.name).collect();
+    let chosen = choose(&names, config.input.unwrap_or(&input::STANDARD).name);
+    config.input = Some(input::PROFILES[chosen]).filter(|input| *input != &input::STANDARD);
+
+    let frontends = config::available_frontends();
+    if !frontends.is_empty() {
+        println!("\nWhich frontend do you play in?");
+        for (i, (name, description)) in frontends.iter().enumerate() {
+            println!("  {}. {name:<10} {description}", i + 1);
+        }
+        let names: Vec<&str> = frontends.iter().map(|(name, _)| *name).collect();
+        let chosen = choose(&names, config.frontend.filter(|frontend| names.contains(frontend)).unwrap_or(names[0]));
+        config.frontend = Some(names[chosen]);
+    }
+
+    if let Err(e) = config.save() {
+        eprintln!("An error occured when saving the config {}: {e}", Config::path().display());
+        std::process::exit(1);
+    }
+    println!("\nSaved to {}, `chip8 setup` changes it.", Config::path().display());
+    return config;
+}
+
+/// chip8 dirs
+/// Shows where chip8 keeps what it saves and its config
+fn dirs() {
+    println!("Data:   {}", persist::data_dir().display());
+    println!("States: {}", persist::data_dir().join("states").display());
+    println!("Config: {}", Config::path().display());
+}
+
+/// Asks which of the choices by number or name until one's given, Enter taking current
+fn choose(choices: &[&str], current: &str) -> usize {
+    loop {
+        let answer = ask(&format!("[{current}] "));
+        let answer = if answer.is_empty() { current } else { answer.as_str() };
+        let by_number = answer.parse::<usize>().ok().filter(|n| (1..=choices.len()).contains(n)).map(|n| n - 1);
+        match by_number.or(choices.iter().position(|choice| choice.eq_ignore_ascii_case(answer))) {
+            Some(i) => return i,
+            None => println!("Pick 1 to {}, or type its name", choices.len()),
+        }
+    }
+}
+
+/// Prints the question and reads the answer, trimmed. There's no going on without one, so the
+/// input ending exits
+fn ask(question: &str) -> String {
+    use std::io::Write;
+
+    print!("{question}");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).unwrap_or(0) == 0 {
+        println!();
+        std::process::exit(0);
+    }
+    return answer.trim().to_string();
+}
+
+/// Lists the roms in the roms folder and asks which to play
+fn pick_rom(config: &Config) -> String {
+    let roms = config.list_roms().unwrap_or_else(|e| {
+        eprintln!("An error occured when reading the roms folder {e}");
+        std::process::exit(2);
+    });
+    if roms.is_empty() {
+        let folder = config.roms.as_ref().map_or("There's no roms folder".to_string(), |roms| format!("There are no roms in {}", roms.display()));
+        eprintln!("{folder}, `chip8 setup` changes it, or give one to a frontend like `chip8 gui <rom>`");
+        std::process::exit(2);
+    }
+
+    println!("Which rom do you want to play?");
+    for (i, rom) in roms.iter().enumerate() {
+        println!("  {}. {}", i + 1, rom.file_name().unwrap_or_default().to_string_lossy());
+    }
+    let names: Vec<String> = roms.iter().map(|rom| rom.file_name().unwrap_or_default().to_string_lossy().to_string()).collect();
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    return roms[choose(&names, names[0])].display().to_string();
+}
+
+/// chip8 tour
+/// Steps through a little program an instruction at a time, explaining each one before it
+/// runs and showing what it changed after (see tour.rs)
+fn tour() {
+    use std::io::{BufRead, Write};
+
+    use chip8::tour;
+
+    let rom = tour::rom();
+    let mut chip = fresh(&rom, None, None, &load_profile(&rom));
+    println!("A tour of CHIP-8, an instruction at a time. Press Enter to run each one, or type q and Enter to stop.");
+    let mut lines = std::io::stdin().lock().lines();
+    while !tour::finished(&chip) {
+        print!("\n{}> ", tour::describe(&chip));
+        let _ = std::io::stdout().flush();
+        let Some(Ok(line)) = lines.next() else {
+            return;
+        };
+        if line.trim() == "q" {
+            return;
+        }
+
+        let changes = tour::step(&mut chip).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        });
+        println!("\nWhat it changed:");
+        for line in changes.lines() {
+            println!("  {line}");
+        }
+        if changes.contains("Screen") {
+            print!("{}", chip.framebuffer().to_ascii('#', '.'));
+        }
+    }
+    print!("\n{}", tour::describe(&chip));
+    println!("\nThat's the end of the tour, the program's stopped by jumping to itself forever.");
+}
+
+/// chip8 soak <roms-dir> [--frames <n>] [--reports <dir>]
+/// Runs every rom in the directory headless for a number of frames (default 100,000) and
+/// writes a report and a save state to the reports directory (default soak-reports) for each
+/// one that fails or halts (see soak.rs). Without --platform each rom runs on the platform
+/// analyze guesses for it
+fn soak(args: &[String]) {
+    let mut args = args.to_vec();
+    let frames = take_option(&mut args, "--frames").map_or(SOAK_FRAMES, |frames| {
+        frames.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid number of frames '{frames}'");
+            std::process::exit(2);
+        })
+    });
+    let reports = std::path::PathBuf::from(take_option(&mut args, "--reports").unwrap_or("soak-reports".to_string()));
+    let [dir] = &args[..] else {
+        eprintln!("Usage: chip8 soak <roms-dir> [--frames <n>] [--reports <dir>]");
+        std::process::exit(2);
+    };
+
+    let entries = std::fs::read_dir(dir).unwrap_or_else(|e| {
+        eprintln!("An error occured when reading {dir}: {e}");
+        std::process::exit(2);
+    });
+    let mut roms: Vec<_> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    roms.retain(|rom| rom.is_file());
+    roms.sort();
+    if let Err(e) = std::fs::create_dir_all(&reports) {
+        eprintln!("An error occured when creating {}: {e}", reports.display());
+        std::process::exit(2);
+    }
+
+    let (mut failed, mut halted) = (0, 0);
+    for rom in &roms {
+        let name = rom.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let cart = match read_cart(&rom.to_string_lossy()) {
+            Ok(cart) => cart,
+            Err(e) => {
+                println!("{name}: FAILED, couldn't be loaded: {e}");
+                failed += 1;
+                continue;
+            },
+        };
+        let platform = cart.platform.or(Some(chip8::analyze::analyze(&cart.rom).platform));
+        let mut chip = load_bytes(&cart.rom, platform, cart.speed);
+        let soak = chip8::soak::run(&mut chip, frames);
+        println!("{name}: {}", soak.summary());
+        if matches!(soak.ending, chip8::soak::Ending::Ran) {
+            continue;
+        }
+
+        if soak.failed() {
+            failed += 1;
+        } else {
+            halted += 1;
+        }
+        let report = reports.join(format!("{name}.txt"));
+        let state = reports.join(format!("{name}.state"));
+        let written = std::fs::write(&report, soak.report(&rom.to_string_lossy(), &chip))
+            .and_then(|()| std::fs::write(&state, chip.snapshot().to_bytes()));
+        if let Err(e) = written {
+            eprintln!("An error occured when writing the report for {name}: {e}");
+        }
+    }
+    println!("{} ran, {halted} halted, {failed} failed", roms.len() - halted - failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// chip8 batch <rom|state>... [--frames <n>] [--runs <n>] [--inputs <file>]
+/// Runs every rom and save state headless for a number of frames (default 3600), all at once
+/// across the cores (see batch.rs). Each is run --runs times (default 1) with CXNN seeded 0, 1,
+/// 2 and so on, holding the keys an inputs file gives (in replay.rs's format) each frame. Roms
+/// go by --platform and --speed, or else their cartridge or analyze's guesses
+#[cfg(feature = "batch")]
+fn batch(args: &[String]) {
+    use chip8::batch::{self, Start, Stats};
+    use std::sync::Arc;
+
+    let mut args = args.to_vec();
+    let number = |name: &str, value: Option<String>, default: usize| {
+        value.map_or(default, |value| {
+            value.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid {name} '{value}'");
+                std::process::exit(2);
+            })
+        })
+    };
+    let frames = number("number of frames", take_option(&mut args, "--frames"), BATCH_FRAMES);
+    let runs = number("number of runs", take_option(&mut args, "--runs"), 1);
+    let inputs = take_option(&mut args, "--inputs").map(|path| {
+        let replay = std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| chip8::replay::Replay::parse(&text));
+        replay.map(|replay| replay.inputs).unwrap_or_else(|e| {
+            eprintln!("An error occured when reading the inputs {path}: {e}");
+            std::process::exit(2);
+        })
+    });
+    if args.is_empty() {
+        eprintln!("Usage: chip8 batch <rom|state>... [--frames <n>] [--runs <n>] [--inputs <file>]");
+        std::process::exit(2);
+    }
+
+    let options = OPTIONS.get().expect("options are parsed first");
+    let mut starts = Vec::new();
+    for path in &args {
+        let bytes = read_file(path).unwrap_or_else(|e| {
+            eprintln!("An error occured when reading {path}: {e}");
+            std::process::exit(2);
+        });
+        let start = match Start::from_bytes(&bytes) {
+            Start::State { state, .. } => Start::State { state, speed: options.speed },
+            Start::Rom { .. } => {
+                let cart = read_cart(path).unwrap_or_else(|e| {
+                    eprintln!("An error occured when loading {path}: {e}");
+                    std::process::exit(2);
+                });
+                let platform = options.platform.or(cart.platform);
+                Start::Rom { rom: Arc::from(cart.rom), platform, speed: options.speed.or(cart.speed), seed: 0 }
+            },
+        };
+        starts.extend((0..runs).map(|seed| (path, seed, start.with_seed(seed as u64))));
+    }
+
+    let (names, starts): (Vec<_>, Vec<_>) = starts.into_iter().map(|(path, seed, start)| ((path, seed), start)).unzip();
+    let started = std::time::Instant::now();
+    let outcomes = batch::run_many(&starts, frames, &inputs.into_iter().collect::<Vec<_>>());
+    let elapsed = started.elapsed();
+    for ((path, seed), outcome) in names.iter().zip(&outcomes) {
+        match runs {
+            1 => println!("{path}: {}", outcome.summary()),
+            _ => println!("{path} seed {seed}: {}", outcome.summary()),
+        }
+    }
+    let stats = Stats::of(&outcomes);
+    print!("\n{stats}");
+    println!("{:.2}s in all", elapsed.as_secs_f64());
+    if stats.failed + stats.panicked > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// chip8 roms
+/// Lists the roms built in, which can be loaded anywhere a rom is as builtin:<name>
+#[cfg(feature = "bundled")]
+fn roms() {
+    for rom in &chip8::bundled::ROMS {
+        println!("builtin:{:<8} {}", rom.name, rom.description);
+    }
+}
+
+/// Creates a fresh interpreter with the rom at the given path loaded, exiting if it can't be read
+fn load(rom_path: &str) -> Chip8 {
+    let cart = read_cart(rom_path).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the rom: {e}");
+        std::process::exit(2);
+    });
+    return load_bytes(&cart.rom, cart.platform, cart.speed);
+}
+
+/// Creates a fresh interpreter with the rom loaded and set up by the options and its profile,
+/// and what was saved for it last time restored. The platform and speed are what the rom
+/// itself asks for, if it's an Octo cartridge that says, which the options and profile take
+/// precedence over
+fn load_bytes(rom: &[u8], platform: Option<Platform>, speed: Option<usize>) -> Chip8 {
+    let profile = load_profile(rom);
+    let mut chip = fresh(rom, platform, speed, &profile);
+    if let Some(flags) = persist::load_rpl_flags(rom) {
+        chip.set_rpl_flags(&flags);
+    }
+
+    // The saved ranges are only restored if they still add up to what the profile asks
+    // for, a changed profile would otherwise scatter them to the wrong addresses
+    let total: usize = profile.persist.iter().map(|range| range.len()).sum();
+    if let Some(saved) = persist::load_memory(rom).filter(|saved| saved.len() == total) {
+        let mut at = 0;
+        for range in &profile.persist {
+            chip.write_mem(range.start, &saved[at..at + range.len()]);
+            at += range.len();
+        }
+    }
+
+    return chip;
+}
+
+/// Creates a fresh interpreter with the rom loaded and set up by the options and its profile,
+/// without anything saved for it
+fn fresh(rom: &[u8], platform: Option<Platform>, speed: Option<usize>, profile: &Profile) -> Chip8 {
+    let options = OPTIONS.get().expect("options are parsed first");
+    let mut chip = Chip8::with_platform(options.platform.or(platform).unwrap_or(Platform::Chip8), false);
+    chip.set_fontset(&options.font);
+    chip.set_strict(options.strict || profile.strict.unwrap_or(false));
+    chip.set_memory_protection(profile.memory_protection.unwrap_or(false));
+    chip.set_quirks(profile.quirks);
+    chip.load_rom_bytes(rom);
+
+    // The speed is --speed if it was given, then the profile's, then the cartridge's, then a
+    // guess from the rom
+    let speed = options.speed.or(profile.speed).or(speed);
+    chip.set_cycles_per_frame(speed.unwrap_or_else(|| chip8::analyze::analyze(rom).speed));
+    if let Some(depth) = profile.stack {
+        chip.set_stack_depth(depth);
+    }
+    if let Some(policy) = profile.stack_overflow {
+        chip.set_stack_policy(policy);
+    }
+
+    // Patching the rom in memory rather than before it's loaded keeps it the same rom, to
+    // find its profile and what's saved for it by
+    for path in &profile.patches {
+        let records = std::fs::read(path).map_err(|e| e.to_string()).and_then(|bytes| chip8::patch::parse_ips(&bytes));
+        let records = records.unwrap_or_else(|e| {
+            eprintln!("An error occured when loading the patch {}: {e}", path.display());
+            std::process::exit(2);
+        });
+        let total: usize = records.iter().map(|record| record.bytes.len()).sum();
+        if chip.apply_patch(&records) < total {
+            eprintln!("The patch {} goes past the end of memory, and what does is left out", path.display());
+        }
+    }
+    return chip;
+}
+
+/// Reads a rom file (out of its archive, if it's zipped), assembling it if it's an Octo cartridge,
+/// with what the cartridge says about running it
+fn read_cart(rom_path: &str) -> Result<chip8::cart::Cart, String> {
+    #[cfg(feature = "zip")]
+    let (rom_path, entry) = chip8::zip::split_path(rom_path);
+    #[cfg_attr(not(feature = "zip"), allow(unused_mut))]
+    let mut bytes = read_file(rom_path)?;
+    #[cfg(feature = "zip")]
+    if chip8::zip::is_zip(&bytes) {
+        bytes = chip8::zip::extract(&bytes, entry)?;
+    }
+    return chip8::cart::load(bytes);
+}
+
+/// Reads a file, from the config's roms folder if it isn't where the path says, or downloads it
+/// if it's a web address and the net feature is on, or assembles it if it's a built in rom
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    #[cfg(feature = "bundled")]
+    if path.starts_with("builtin:") {
+        let names: Vec<&str> = chip8::bundled::ROMS.iter().map(|rom| rom.name).collect();
+        let bundled = chip8::bundled::find(path).ok_or(format!("there's no {path}, try {}", names.join(", ")))?;
+        return Ok(bundled.rom());
+    }
+    #[cfg(feature = "net")]
+    if chip8::net::is_url(path) {
+        return chip8::net::fetch(path);
+    }
+    // Roms that aren't where the path says are looked for in the roms folder
+    let bytes = std::fs::read(path);
+    if let (Err(e), Some(roms)) = (&bytes, load_config().roms) {
+        if e.kind() == std::io::ErrorKind::NotFound && std::path::Path::new(path).is_relative() {
+            if let Ok(bytes) = std::fs::read(roms.join(path)) {
+                return Ok(bytes);
+            }
+        }
+    }
+    return bytes.map_err(|e| e.to_string());
+}
+
+/// Reads a rom file, assembling it if it's an Octo cartridge
+fn read_rom(rom_path: &str) -> Result<Vec<u8>, String> {
+    return read_cart(rom_path).map(|cart| cart.rom);
+}
+
+/// The frontends' hotkey controls, starting from the beep and focus setting in the rom's
+/// profile. The screen's turned by --rotate, or else the profile's or the cartridge's rotation,
+/// and the keys are split up by --input, or else the profile's or the config's input profile.
+/// CHIP-8X's I/O port is connected to --port-in and --port-out
+#[cfg(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal"))]
+fn controls(rom_path: &str) -> chip8::frontend::controls::Controls {
+    let cart = read_cart(rom_path).ok();
+    let profile = load_profile(cart.as_ref().map_or(&[], |cart| &cart.rom));
+    let options = OPTIONS.get().expect("options are parsed first");
+    let rotation = options.rotation.or(profile.rotation).or(cart.and_then(|cart| cart.rotation));
+    let input = options.input.or(profile.input).or(load_config().input).unwrap_or(&chip8::frontend::input::STANDARD);
+    return chip8::frontend::controls::Controls::new(profile.beep, profile.pause_on_focus_loss.unwrap_or(true))
+        .with_rotation(rotation.unwrap_or_default())
+        .with_input(input)
+        .with_palette(profile.palette.unwrap_or_default())
+        .with_port(port(options));
+}
+
+/// CHIP-8X's I/O port, reading from --port-in and writing to --port-out if they're given
+#[cfg(any(feature = "gui", feature = "minifb", feature = "pixels", feature = "terminal"))]
+fn port(options: &Options) -> chip8::frontend::input::Port {
+    let (input, output) = &options.port;
+    let input = input.as_ref().map_or(Ok(Vec::new()), std::fs::read);
+    let output = output.as_ref().map(std::fs::File::create).transpose();
+    return match (input, output) {
+        (Ok(input), Ok(output)) => {
+            chip8::frontend::input::Port::new(input, output.map(|file| Box::new(file) as Box<dyn std::io::Write>))
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("An error occured when opening the I/O port's files: {e}");
+            std::process::exit(2);
+        },
+    };
+}
+
+/// The config, or the defaults before chip8's been set up
+fn load_config() -> Config {
+    return Config::load().map(Option::unwrap_or_default).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the config {}: {e}", Config::path().display());
+        std::process::exit(2);
+    });
+}
+
+fn load_profile(rom: &[u8]) -> Profile {
+    return Profile::load(rom).unwrap_or_else(|e| {
+        eprintln!("An error occured when loading the profile {}: {e}", Profile::path(rom).display());
+        std::process::exit(2);
+    });
+}
+
+/// Saves what the rom wants kept for next time, the RPL flags and the memory its profile
+/// persists, once a run is over
+fn save(chip: &Chip8, rom_path: &str) {
+    let Ok(rom) = read_rom(rom_path) else {
+        return;
+    };
+    if chip.rpl_flags().iter().any(|&flag| flag != 0) || persist::load_rpl_flags(&rom).is_some() {
+        if let Err(e) = persist::save_rpl_flags(&rom, chip.rpl_flags()) {
+            eprintln!("An error occured when saving the RPL flags: {e}");
+        }
+    }
+
+    let profile = load_profile(&rom);
+    if !profile.persist.is_empty() {
+        let mut saved = Vec::new();
+        for range in &profile.persist {
+            saved.extend_from_slice(chip.read_mem(range.start, range.len()));
+        }
+        if let Err(e) = persist::save_memory(&rom, &saved) {
+            eprintln!("An error occured when saving memory: {e}");
+        }
+    }
+}
+
+/// Writes the machine's state as JSON where --dump-state-on-exit says, if it was given
+fn dump_state(chip: &Chip8) {
+    let Some(path) = &OPTIONS.get().expect("options are parsed first").dump_state else {
+        return;
+    };
+    let json = chip.export_state_json();
+    if path == "-" {
+        print!("{json}");
+    } else if let Err(e) = std::fs::write(path, json) {
+        eprintln!("An error occured when writing the state to {path}: {e}");
+    }
+}
 
-        chip.execute();
+/// Removes `name <value>` from the arguments, returning the value if it was there
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    let value = args.get(i + 1).cloned().unwrap_or_default();
+    args.drain(i..(i + 2).min(args.len()));
+    return Some(value);
+}
+
+/// Removes a flag that takes no value from the arguments, returning whether it was there
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let Some(i) = args.iter().position(|a| a == name) else {
+        return false;
+    };
+    args.remove(i);
+    return true;
+}
+
+/// Starts recording the sound if --wav was given, beeping the way the rom's profile says
+fn start_wav(rom_path: &str) -> Option<Wav> {
+    OPTIONS.get().expect("options are parsed first").wav.as_ref()?;
+    let rom = read_rom(rom_path).unwrap_or_default();
+    return Some(Wav::new(load_profile(&rom).beep));
+}
+
+/// Writes the sound recorded since start_wav to the --wav file
+fn finish_wav(wav: Option<Wav>) {
+    let (Some(wav), Some(path)) = (wav, &OPTIONS.get().expect("options are parsed first").wav) else {
+        return;
+    };
+    let result = std::fs::File::create(path).and_then(|file| {
+        let mut out = std::io::BufWriter::new(file);
+        wav.write(&mut out)?;
+        return std::io::Write::flush(&mut out);
+    });
+    if let Err(e) = result {
+        eprintln!("An error occured when writing the sound: {e}");
+        std::process::exit(1);
     }
 }
+
+/// Reads an optional hex number from the arguments, exiting if it isn't valid
+fn number_arg(args: &[String], index: usize, default: usize) -> usize {
+    let Some(arg) = args.get(index) else {
+        return default;
+    };
+
+    return debugger::parse_number(arg).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(2);
+    });
+}
@@ -0,0 +1,147 @@
+use std::fmt::Write as _;
+use std::io::Write;
+
+use crate::chip::Chip8;
+
+// Recording a run as text for replaying in a terminal, or pasting into docs and issues:
+// either an asciinema cast (https://docs.asciinema.org/manual/asciicast/v2/) that plays back
+// in time with asciinema play or the web player, or a bare stream of ANSI escapes that can
+// be cat'ed into a terminal.
+//
+// The screen is drawn with half blocks in 24 bit colour, two pixels to a character, so a
+// 64x32 screen takes 64x16 characters. Only frames where the screen changed are kept, and
+// only the characters that changed in them are redrawn.
+
+const FPS: f64 = 60.0;
+
+
+pub struct Movie {
+    /// Each kept frame's number and the escapes that draw it over the one before
+    frames: Vec<(u64, String)>,
+    /// The frames recorded so far, kept or not
+    recorded: u64,
+    /// The screen as of the last kept frame, and its width
+    last_screen: Vec<u32>,
+    last_width: usize,
+    /// The most characters across and rows down any frame needed
+    columns: usize,
+    rows: usize,
+}
+
+impl Movie {
+    pub fn new() -> Self {
+        return Self { frames: Vec::new(), recorded: 0, last_screen: Vec::new(), last_width: 0, columns: 0, rows: 0 };
+    }
+
+    /// Records the screen as it is now, as the next frame. Call it once a frame
+    pub fn record(&mut self, chip: &Chip8) {
+        let frame = self.recorded;
+        self.recorded += 1;
+
+        let screen = chip.screen_rgb();
+        let framebuffer = chip.framebuffer();
+        let width = framebuffer.width();
+        if screen == self.last_screen && width == self.last_width {
+            return;
+        }
+
+        // A new resolution means starting again with a clear screen
+        let resized = width != self.last_width || screen.len() != self.last_screen.len();
+        let mut out = String::new();
+        if resized {
+            out.push_str("\x1b[0m\x1b[2J");
+        }
+
+        let height = screen.len() / width;
+        let rows = height.div_ceil(2);
+        for row in 0..rows {
+            let mut colours = None;
+            let mut cursor_here = false;
+            for x in 0..width {
+                let top = screen[row * 2 * width + x];
+                let bottom = screen.get((row * 2 + 1) * width + x).copied().unwrap_or(0);
+                let unchanged = !resized
+                    && self.last_screen[row * 2 * width + x] == top
+                    && self.last_screen.get((row * 2 + 1) * width + x).copied().unwrap_or(0) == bottom;
+                if unchanged {
+                    cursor_here = false;
+                    continue;
+                }
+
+                if !cursor_here {
+                    write!(out, "\x1b[{};{}H", row + 1, x + 1).unwrap();
+                    cursor_here = true;
+                }
+                if colours != Some((top, bottom)) {
+                    write!(out, "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m", top >> 16, top >> 8 & 0xFF, top & 0xFF, bottom >> 16, bottom >> 8 & 0xFF, bottom & 0xFF).unwrap();
+                    colours = Some((top, bottom));
+                }
+                out.push('▀');
+            }
+        }
+        out.push_str("\x1b[0m");
+
+        self.columns = self.columns.max(width);
+        self.rows = self.rows.max(rows);
+        self.frames.push((frame, out));
+        self.last_screen = screen;
+        self.last_width = width;
+    }
+
+    /// How many frames have been recorded
+    pub fn len(&self) -> u64 {
+        return self.recorded;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.recorded == 0;
+    }
+
+    /// Writes the movie as an asciinema v2 cast
+    pub fn write_cast(&self, out: &mut impl Write) -> std::io::Result<()> {
+        // The cursor is hidden for the run and put back at the bottom at the end
+        writeln!(out, r#"{{"version": 2, "width": {}, "height": {}}}"#, self.columns, self.rows + 1)?;
+        writeln!(out, "[0.0, \"o\", {}]", json_string("\x1b[?25l"))?;
+        for (frame, escapes) in &self.frames {
+            writeln!(out, "[{:.6}, \"o\", {}]", *frame as f64 / FPS, json_string(escapes))?;
+        }
+        let end = format!("\x1b[{};1H\x1b[?25h", self.rows + 1);
+        writeln!(out, "[{:.6}, \"o\", {}]", self.recorded as f64 / FPS, json_string(&end))?;
+        return Ok(());
+    }
+
+    /// Writes the movie as a bare stream of ANSI escapes, which plays back as fast as the
+    /// terminal can draw it
+    pub fn write_ansi(&self, out: &mut impl Write) -> std::io::Result<()> {
+        out.write_all(b"\x1b[?25l")?;
+        for (_, escapes) in &self.frames {
+            out.write_all(escapes.as_bytes())?;
+        }
+        write!(out, "\x1b[{};1H\x1b[?25h", self.rows + 1)?;
+        return Ok(());
+    }
+}
+
+impl Default for Movie {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Quotes text as a JSON string
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
@@ -0,0 +1,117 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::persist;
+
+// Loading roms from the web, so a link from a rom archive can be tried without saving it
+// first, e.g. `chip8 play https://example.com/brix.ch8`. Downloads are kept in the cache
+// directory ($XDG_CACHE_HOME/chip8/downloads, or ~/.cache/chip8/downloads) under a hash of
+// the URL, and read from there after the first time.
+//
+// http:// is fetched here over a plain socket. There's no TLS in the build, so https:// is
+// handed to the system's curl, which comes with everything from Windows 10 on. Either way
+// redirects are followed and nothing bigger than MAX_DOWNLOAD is taken.
+
+/// The most that's downloaded, enough for the biggest Mega-Chip rom
+const MAX_DOWNLOAD: usize = 16 << 20;
+
+/// How long connecting, and then each read, can take
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many redirects are followed before giving up
+const MAX_REDIRECTS: u8 = 5;
+
+
+/// Whether a rom path is a web address rather than a file
+pub fn is_url(path: &str) -> bool {
+    return path.starts_with("http://") || path.starts_with("https://");
+}
+
+/// Where downloaded roms are kept
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        return PathBuf::from(dir).join("chip8/downloads");
+    }
+    if let Some(home) = std::env::var_os("HOME") {
+        return PathBuf::from(home).join(".cache/chip8/downloads");
+    }
+    return PathBuf::from(".chip8/downloads");
+}
+
+/// Downloads the rom at url, or reads it from the cache if it's been downloaded before
+pub fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    let cached = cache_dir().join(persist::rom_id(url.as_bytes()));
+    if let Ok(rom) = std::fs::read(&cached) {
+        return Ok(rom);
+    }
+
+    let rom = download(url, MAX_REDIRECTS)?;
+    // The cache is only there to save time, a rom that can't be kept is just downloaded again
+    let _ = std::fs::create_dir_all(cache_dir()).and_then(|_| std::fs::write(&cached, &rom));
+    return Ok(rom);
+}
+
+fn download(url: &str, redirects: u8) -> Result<Vec<u8>, String> {
+    if url.starts_with("https://") {
+        let output = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--location"])
+            .args(["--max-redirs", &redirects.to_string(), "--max-filesize", &MAX_DOWNLOAD.to_string()])
+            .args(["--connect-timeout", &TIMEOUT.as_secs().to_string(), url])
+            .output()
+            .map_err(|e| format!("https needs curl, which couldn't be run: {e}"))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        return Ok(output.stdout);
+    }
+
+    let rest = url.strip_prefix("http://").ok_or(format!("'{url}' isn't an http:// or https:// address"))?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let addr = if host.contains(':') { host.to_string() } else { format!("{host}:80") };
+    let addr = addr
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or(format!("couldn't find {host}"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| format!("couldn't connect to {host}: {e}"))?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+
+    // HTTP/1.0 so the body comes as it is rather than chunked, ending when the server hangs up
+    let request = format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: chip8\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+    let mut response = Vec::new();
+    // The headers are allowed 64K on top of the body
+    stream.take((MAX_DOWNLOAD + 0x10000) as u64).read_to_end(&mut response).map_err(|e| e.to_string())?;
+
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or(format!("{host} didn't send a response"))?;
+    let head = String::from_utf8_lossy(&response[..split]).into_owned();
+    let body = &response[split + 4..];
+    let mut lines = head.lines();
+    let status = lines.next().unwrap_or_default();
+    let code = status.split_whitespace().nth(1).unwrap_or_default();
+
+    if matches!(code, "301" | "302" | "303" | "307" | "308") {
+        if redirects == 0 {
+            return Err("too many redirects".to_string());
+        }
+        let location = lines
+            .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("location")))
+            .map(|(_, location)| location.trim())
+            .ok_or(format!("{host} redirected without saying where to"))?;
+        let location = if location.starts_with('/') { format!("http://{host}{location}") } else { location.to_string() };
+        return download(&location, redirects - 1);
+    }
+    if code != "200" {
+        return Err(format!("{host} answered {}", status.split_once(' ').map_or(status, |(_, reason)| reason)));
+    }
+    if body.len() > MAX_DOWNLOAD {
+        return Err(format!("the download is over {} bytes, too big to be a rom", MAX_DOWNLOAD));
+    }
+    return Ok(body.to_vec());
+}
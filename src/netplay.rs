@@ -0,0 +1,345 @@
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::chip::Chip8;
+use crate::persist;
+
+// Netplay (chip8 netplay), experimental: two machines running the same rom in lockstep over
+// UDP, so two people can play PONG2 or the like from different places. Neither side sends
+// the other its screen, just the keys held each frame. Both run every frame with both sets of
+// keys held and so stay in step, the way replays (replay.rs) do.
+//
+// One side hosts on a port and the other joins it. Joining checks both sides have the same
+// machine to start from, the same rom, platform, font and speed, and the host hands over the
+// seed for the random numbers. Saved RPL flags and memory aren't loaded since the other side
+// wouldn't have them.
+//
+// Keys held here are sent for INPUT_DELAY frames on, which gives them time to get there
+// before they're wanted, and a frame doesn't run until the other side's keys for it are in,
+// so pausing one side pauses both. Every packet repeats the last HISTORY frames of keys, so a
+// lost one is made up for by the next. Packets also carry a hash of the machine after the
+// last frame run, and the first frame the two sides' hashes differ ends the game as out of
+// sync, which only happens when the two interpreters don't agree.
+//
+// Packets, little endian:
+//
+//   H <setup hash: u64>                          joining, sent until it's welcomed
+//   W <setup hash: u64> <seed: u64>              the host's answer
+//   I <frame: u64> <count: u8> <keys: u16>...    keys held for the count frames up to frame,
+//     [<frame: u64> <hash: u64>]                 and the hash after the last frame run
+
+
+/// How many frames on the keys held now are for
+pub const INPUT_DELAY: u64 = 3;
+
+/// How many frames of keys each packet repeats
+const HISTORY: u64 = 16;
+
+/// How many frames of keys and hashes are kept, well past where either side could be
+const KEPT: u64 = 128;
+
+/// How often joining asks again while nothing's come back
+const HELLO_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long joining keeps asking before giving up
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the other side can go quiet before the game's given up on
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const HELLO: u8 = b'H';
+const WELCOME: u8 = b'W';
+const INPUTS: u8 = b'I';
+
+
+pub struct Session {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    /// 0 for the host, 1 for whoever joined
+    player: usize,
+    setup: u64,
+    seed: u64,
+    /// The frame to run next
+    frame: u64,
+    /// The keys each side held for each frame, by frame
+    local: BTreeMap<u64, u16>,
+    remote: BTreeMap<u64, u16>,
+    /// The hash of the machine after each frame, here and on the other side
+    hashes: BTreeMap<u64, u64>,
+    remote_hashes: BTreeMap<u64, u64>,
+    heard: Instant,
+}
+
+impl Session {
+    /// Waits on addr (e.g. 0.0.0.0:6465) for the other player to join, and seeds the machine
+    /// for the game
+    pub fn host(addr: &str, chip: &mut Chip8) -> Result<Self, String> {
+        let socket = UdpSocket::bind(addr).map_err(|e| format!("Couldn't listen on {addr}: {e}"))?;
+        let setup = setup(chip);
+        let seed = rand::random();
+        let mut packet = [0; 64];
+        loop {
+            let (len, from) = socket.recv_from(&mut packet).map_err(|e| e.to_string())?;
+            if len != 9 || packet[0] != HELLO {
+                continue;
+            }
+            socket.send_to(&welcome(setup, seed), from).map_err(|e| e.to_string())?;
+            if read_u64(&packet[1..]) != setup {
+                return Err(format!("{from} is running a different rom, or with a different platform, font or speed"));
+            }
+            chip.seed_rng(seed);
+            return Self::new(socket, from, 0, setup, seed);
+        }
+    }
+
+    /// Joins the game hosted on addr, and seeds the machine for it
+    pub fn join(addr: &str, chip: &mut Chip8) -> Result<Self, String> {
+        let peer = addr
+            .to_socket_addrs()
+            .map_err(|e| format!("Couldn't find {addr}: {e}"))?
+            .next()
+            .ok_or(format!("Couldn't find {addr}"))?;
+        let local = if peer.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+        let socket = UdpSocket::bind(local).map_err(|e| e.to_string())?;
+        socket.set_read_timeout(Some(HELLO_INTERVAL)).map_err(|e| e.to_string())?;
+        let setup = setup(chip);
+        let mut hello = vec![HELLO];
+        hello.extend_from_slice(&setup.to_le_bytes());
+
+        let started = Instant::now();
+        let mut packet = [0; 64];
+        while started.elapsed() < CONNECT_TIMEOUT {
+            socket.send_to(&hello, peer).map_err(|e| e.to_string())?;
+            let (len, from) = match socket.recv_from(&mut packet) {
+                Ok(received) => received,
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+                // Windows reports the host not listening yet as a reset connection
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            if from != peer || len != 17 || packet[0] != WELCOME {
+                continue;
+            }
+            if read_u64(&packet[1..]) != setup {
+                return Err(format!("{addr} is running a different rom, or with a different platform, font or speed"));
+            }
+            let seed = read_u64(&packet[9..]);
+            chip.seed_rng(seed);
+            return Self::new(socket, peer, 1, setup, seed);
+        }
+        return Err(format!("Nothing came back from {addr}, is it hosting?"));
+    }
+
+    fn new(socket: UdpSocket, peer: SocketAddr, player: usize, setup: u64, seed: u64) -> Result<Self, String> {
+        socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+        let mut session = Self {
+            socket,
+            peer,
+            player,
+            setup,
+            seed,
+            frame: 0,
+            local: BTreeMap::new(),
+            remote: BTreeMap::new(),
+            hashes: BTreeMap::new(),
+            remote_hashes: BTreeMap::new(),
+            heard: Instant::now(),
+        };
+        // Nothing's held for the frames before the first keys arrive
+        for frame in 0..INPUT_DELAY {
+            session.local.insert(frame, 0);
+            session.remote.insert(frame, 0);
+        }
+        return Ok(session);
+    }
+
+    /// Which player this side is, 1 for the host and 2 for whoever joined
+    pub fn player(&self) -> usize {
+        return self.player + 1;
+    }
+
+    /// Sends the keys held here, as a bitmask (bit n for key n), and takes in whatever's come
+    /// from the other side. It's called every time round the frontend's loop, paused or not,
+    /// so the other side knows this one's still there
+    pub fn update(&mut self, held: u16) -> Result<(), String> {
+        self.local.entry(self.frame + INPUT_DELAY).or_insert(held);
+        self.send()?;
+        self.receive()?;
+        if self.heard.elapsed() > TIMEOUT {
+            return Err(format!("Lost the other player, nothing's come from them for {} seconds", TIMEOUT.as_secs()));
+        }
+        return Ok(());
+    }
+
+    /// Both sides' keys for the next frame, once the other side's are in
+    pub fn keys(&self) -> Option<u16> {
+        return Some(self.local.get(&self.frame)? | self.remote.get(&self.frame)?);
+    }
+
+    /// Moves on after the machine's run a frame with keys(), checking it against the other side
+    pub fn advance(&mut self, chip: &Chip8) -> Result<(), String> {
+        self.hashes.insert(self.frame, frame_hash(chip));
+        self.check(self.frame)?;
+        self.frame += 1;
+        let old = self.frame.saturating_sub(KEPT);
+        self.local = self.local.split_off(&old);
+        self.remote = self.remote.split_off(&old);
+        self.hashes = self.hashes.split_off(&old);
+        self.remote_hashes = self.remote_hashes.split_off(&old);
+        return Ok(());
+    }
+
+    /// Fails once both sides have a hash for the frame and they differ
+    fn check(&self, frame: u64) -> Result<(), String> {
+        return match (self.hashes.get(&frame), self.remote_hashes.get(&frame)) {
+            (Some(hash), Some(remote)) if hash != remote => {
+                Err(format!("Out of sync with the other player from frame {frame}, the two machines have gone different ways"))
+            },
+            _ => Ok(()),
+        };
+    }
+
+    fn send(&self) -> Result<(), String> {
+        let last = self.frame + INPUT_DELAY;
+        let first = (last + 1).saturating_sub(HISTORY);
+        let mut packet = vec![INPUTS];
+        packet.extend_from_slice(&last.to_le_bytes());
+        packet.push((last - first + 1) as u8);
+        for frame in first..=last {
+            packet.extend_from_slice(&self.local.get(&frame).copied().unwrap_or_default().to_le_bytes());
+        }
+        if let Some((frame, hash)) = self.hashes.last_key_value() {
+            packet.extend_from_slice(&frame.to_le_bytes());
+            packet.extend_from_slice(&hash.to_le_bytes());
+        }
+        return match self.socket.send_to(&packet, self.peer) {
+            Ok(_) => Ok(()),
+            // A full send buffer loses the packet the way the network can, the next one repeats it
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    fn receive(&mut self) -> Result<(), String> {
+        let mut packet = [0; 512];
+        loop {
+            let (len, from) = match self.socket.recv_from(&mut packet) {
+                Ok(received) => received,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e.to_string()),
+            };
+            if from != self.peer {
+                continue;
+            }
+            let packet = &packet[..len];
+            match packet.first() {
+                // The welcome was lost, so the other side's still asking
+                Some(&HELLO) if self.player == 0 => {
+                    let _ = self.socket.send_to(&welcome(self.setup, self.seed), self.peer);
+                },
+                Some(&INPUTS) if len >= 10 => self.read_inputs(&packet[1..])?,
+                _ => continue,
+            }
+            self.heard = Instant::now();
+        }
+    }
+
+    fn read_inputs(&mut self, packet: &[u8]) -> Result<(), String> {
+        let last = read_u64(packet);
+        let count = packet[8] as u64;
+        let keys = &packet[9..];
+        // No real peer gets to the last frame there is, so a packet that says it has is dropped
+        let Some(past_last) = last.checked_add(1) else {
+            return Ok(());
+        };
+        if count == 0 || count > past_last || keys.len() < count as usize * 2 {
+            return Ok(());
+        }
+        let first = past_last - count;
+        for (frame, keys) in (first..=last).zip(keys.chunks_exact(2)) {
+            if frame >= self.frame {
+                self.remote.entry(frame).or_insert(u16::from_le_bytes([keys[0], keys[1]]));
+            }
+        }
+        let hash = &keys[count as usize * 2..];
+        if hash.len() == 16 {
+            let frame = read_u64(hash);
+            self.remote_hashes.insert(frame, read_u64(&hash[8..]));
+            self.check(frame)?;
+        }
+        return Ok(());
+    }
+}
+
+/// A hash of what the machine's started with, to check both sides have the same
+fn setup(chip: &Chip8) -> u64 {
+    let mut bytes = chip.read_mem(0, chip.memory_size()).to_vec();
+    bytes.extend_from_slice(chip.platform().name().as_bytes());
+    bytes.extend_from_slice(&(chip.cycles_per_frame() as u64).to_le_bytes());
+    return persist::hash(&bytes);
+}
+
+/// A hash of the machine after a frame. Memory's left out, Mega-Chip's would take too long
+/// to hash every frame, but a desync soon shows in the registers or on screen
+fn frame_hash(chip: &Chip8) -> u64 {
+    let mut bytes = chip.framebuffer().bytes().to_vec();
+    bytes.extend_from_slice(chip.registers());
+    bytes.extend_from_slice(&chip.pc().to_le_bytes());
+    bytes.extend_from_slice(&chip.ar().to_le_bytes());
+    bytes.extend_from_slice(&[chip.sp(), chip.delay(), chip.sound()]);
+    return persist::hash(&bytes);
+}
+
+fn welcome(setup: u64, seed: u64) -> Vec<u8> {
+    let mut packet = vec![WELCOME];
+    packet.extend_from_slice(&setup.to_le_bytes());
+    packet.extend_from_slice(&seed.to_le_bytes());
+    return packet;
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    return u64::from_le_bytes(bytes[..8].try_into().unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A session with itself as the other side, which is enough to feed it packets
+    fn session() -> Session {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        return Session::new(socket, addr, 0, 0, 0).unwrap();
+    }
+
+    /// An inputs packet, after its type byte, with keys for the frames up to last
+    fn inputs(last: u64, keys: &[u16]) -> Vec<u8> {
+        let mut packet = last.to_le_bytes().to_vec();
+        packet.push(keys.len() as u8);
+        for held in keys {
+            packet.extend_from_slice(&held.to_le_bytes());
+        }
+        return packet;
+    }
+
+    #[test]
+    fn the_other_sides_keys_are_kept_by_frame() {
+        let mut session = session();
+        session.read_inputs(&inputs(INPUT_DELAY + 1, &[0x0001, 0x0030])).unwrap();
+        assert_eq!(session.remote.get(&INPUT_DELAY), Some(&0x0001));
+        assert_eq!(session.remote.get(&(INPUT_DELAY + 1)), Some(&0x0030));
+    }
+
+    #[test]
+    fn a_packet_with_a_hostile_frame_number_is_dropped() {
+        let mut session = session();
+        let remote = session.remote.clone();
+        session.read_inputs(&inputs(u64::MAX, &[0xFFFF])).unwrap();
+        session.read_inputs(&inputs(1, &[0xFFFF; 3])).unwrap();
+        session.read_inputs(&inputs(5, &[])).unwrap();
+        assert_eq!(session.remote, remote);
+    }
+}
@@ -0,0 +1,626 @@
+use std::collections::HashMap;
+
+// An assembler for Octo (https://github.com/JohnEarnest/Octo), the language most new
+// CHIP-8, SUPER-CHIP and XO-CHIP games are written in, for chip8 dev. It takes the language
+// itself:
+//
+//   : label               a label, which called by name is a subroutine
+//   :alias name vX        another name for a register
+//   :const name value     a name for a number
+//   :org addr             assemble from addr onwards
+//   :call addr, :byte n   a call or byte that can't be written plainly
+//   if vX == n then ...   == != < > <= >= key -key, then one statement or begin ... else ... end
+//   loop ... while ... again
+//
+// and every statement for the instructions up to XO-CHIP. Numbers on their own are bytes
+// of data. The program starts at a `: main` label, with a jump to it at 0x200 unless it's
+// the first thing in the source.
+//
+// Macros, :calc, :unpack, :next, :stringmode and :assert aren't supported and are an
+// error rather than being skipped, since a program without them wouldn't work anyway.
+
+/// Where programs are assembled to
+const ORIGIN: usize = 0x200;
+
+
+/// An if ... begin, else or loop waiting for its end, with the jumps to fill in once its
+/// end is known
+enum Block {
+    If { line: usize, jump: usize },
+    Else { line: usize, jump: usize },
+    Loop { line: usize, start: u16, exits: Vec<usize> },
+}
+
+/// A register or a byte, one side of a comparison
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(u16),
+    Byte(u16),
+}
+
+/// The instructions a condition becomes: any that work it out first (comparisons go through
+/// VF), then a skip taken when it's false and one taken when it's true
+struct Condition {
+    prelude: Vec<u16>,
+    unless: u16,
+    when: u16,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    next: usize,
+    rom: Vec<u8>,
+    /// Where in rom the next byte goes
+    at: usize,
+    labels: HashMap<&'a str, u16>,
+    consts: HashMap<&'a str, u16>,
+    aliases: HashMap<&'a str, u8>,
+    /// Names used before their label, with the line, where in rom they go and whether
+    /// they're a 16 bit address (i := long) rather than a 12 bit one
+    fixups: Vec<(usize, &'a str, usize, bool)>,
+    blocks: Vec<Block>,
+}
+
+/// Assembles Octo source into a rom to load at 0x200. Errors say which line they're on
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let tokens: Vec<(usize, &str)> = source
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let code = line.split('#').next().unwrap_or_default();
+            return code.split_whitespace().map(move |word| (i + 1, word));
+        })
+        .collect();
+
+    let main_first = matches!(tokens[..], [(_, ":"), (_, "main"), ..]);
+    let mut asm = Assembler {
+        tokens,
+        next: 0,
+        rom: Vec::new(),
+        at: 0,
+        labels: HashMap::new(),
+        consts: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+    };
+    if !main_first {
+        asm.fixups.push((0, "main", 0, false));
+        asm.emit(0x1000);
+    }
+
+    while let Some((line, word)) = asm.tokens.get(asm.next).copied() {
+        asm.next += 1;
+        asm.statement(line, word)?;
+    }
+
+    if let Some(block) = asm.blocks.last() {
+        return Err(match block {
+            Block::If { line, .. } | Block::Else { line, .. } => format!("line {line}: if ... begin without an end"),
+            Block::Loop { line, .. } => format!("line {line}: loop without an again"),
+        });
+    }
+
+    for (line, name, at, long) in std::mem::take(&mut asm.fixups) {
+        let Some(&addr) = asm.labels.get(name) else {
+            return Err(match line {
+                0 => "there's no ': main' label for the program to start from".to_string(),
+                line => format!("line {line}: undefined name '{name}'"),
+            });
+        };
+        if long {
+            asm.rom[at..at + 2].copy_from_slice(&addr.to_be_bytes());
+        } else {
+            if addr > 0xFFF {
+                return Err(format!("line {line}: '{name}' is at {addr:04X}, past where NNN reaches"));
+            }
+            asm.rom[at] |= (addr >> 8) as u8;
+            asm.rom[at + 1] = addr as u8;
+        }
+    }
+
+    return Ok(asm.rom);
+}
+
+impl<'a> Assembler<'a> {
+    fn token(&mut self) -> Result<(usize, &'a str), String> {
+        let Some(&token) = self.tokens.get(self.next) else {
+            let line = self.tokens.last().map_or(1, |(line, _)| *line);
+            return Err(format!("line {line}: unexpected end of the source"));
+        };
+        self.next += 1;
+        return Ok(token);
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        return self.tokens.get(self.next).map(|(_, word)| *word);
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let (line, word) = self.token()?;
+        if word != expected {
+            return Err(format!("line {line}: expected '{expected}', found '{word}'"));
+        }
+        return Ok(());
+    }
+
+    fn here(&self) -> u16 {
+        return (ORIGIN + self.at) as u16;
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        if self.at >= self.rom.len() {
+            self.rom.resize(self.at + 1, 0);
+        }
+        self.rom[self.at] = byte;
+        self.at += 1;
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        for byte in opcode.to_be_bytes() {
+            self.emit_byte(byte);
+        }
+    }
+
+    /// Fills in the address of a jump emitted earlier at at
+    fn patch(&mut self, at: usize, addr: u16) {
+        self.rom[at] = 0x10 | (addr >> 8) as u8 & 0xF;
+        self.rom[at + 1] = addr as u8;
+    }
+
+    fn as_register(&self, word: &str) -> Option<u8> {
+        if let Some(&register) = self.aliases.get(word) {
+            return Some(register);
+        }
+        let digit = word.strip_prefix(['v', 'V'])?;
+        return u8::from_str_radix(digit, 16).ok().filter(|_| digit.len() == 1);
+    }
+
+    fn register(&mut self) -> Result<u8, String> {
+        let (line, word) = self.token()?;
+        return self.as_register(word).ok_or(format!("line {line}: expected a register, found '{word}'"));
+    }
+
+    /// A number, or a constant or label already defined
+    fn value_of(&self, word: &str) -> Option<i64> {
+        if let Some(&value) = self.consts.get(word).or(self.labels.get(word)) {
+            return Some(value as i64);
+        }
+        let (negative, digits) = match word.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, word),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+            i64::from_str_radix(hex, 16).ok()?
+        } else if let Some(binary) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+            i64::from_str_radix(binary, 2).ok()?
+        } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+            digits.parse::<i64>().ok()?
+        } else {
+            return None;
+        };
+        return Some(if negative { -value } else { value });
+    }
+
+    fn value(&mut self) -> Result<u16, String> {
+        let (line, word) = self.token()?;
+        let value = self.value_of(word).ok_or(format!("line {line}: expected a number, found '{word}'"))?;
+        return u16::try_from(value).map_err(|_| format!("line {line}: {value} doesn't fit in 16 bits"));
+    }
+
+    /// A byte, where negative numbers wrap round as they would in a register
+    fn byte_of(&self, line: usize, word: &str) -> Result<u16, String> {
+        let value = self.value_of(word).ok_or(format!("line {line}: expected a number, found '{word}'"))?;
+        if !(-128..=255).contains(&value) {
+            return Err(format!("line {line}: {value} doesn't fit in a byte"));
+        }
+        return Ok(value as u8 as u16);
+    }
+
+    fn byte(&mut self) -> Result<u16, String> {
+        let (line, word) = self.token()?;
+        return self.byte_of(line, word);
+    }
+
+    fn nibble(&mut self) -> Result<u16, String> {
+        let (line, word) = self.token()?;
+        let value = self.value_of(word).ok_or(format!("line {line}: expected a number, found '{word}'"))?;
+        if !(0..=15).contains(&value) {
+            return Err(format!("line {line}: {value} doesn't fit in a nibble"));
+        }
+        return Ok(value as u16);
+    }
+
+    /// Emits opcode with an address in its NNN, filled in later if it's a label that
+    /// hasn't been reached yet
+    fn address_of(&mut self, line: usize, word: &'a str, opcode: u16) -> Result<(), String> {
+        match self.value_of(word) {
+            Some(addr) if (0..=0xFFF).contains(&addr) => self.emit(opcode | addr as u16),
+            Some(addr) => return Err(format!("line {line}: {addr} is past where NNN reaches")),
+            None if word.starts_with(|c: char| c.is_ascii_digit() || c == '-') => {
+                return Err(format!("line {line}: expected an address, found '{word}'"));
+            },
+            None => {
+                self.fixups.push((line, word, self.at, false));
+                self.emit(opcode);
+            },
+        }
+        return Ok(());
+    }
+
+    fn address(&mut self, opcode: u16) -> Result<(), String> {
+        let (line, word) = self.token()?;
+        return self.address_of(line, word, opcode);
+    }
+
+    fn statement(&mut self, line: usize, word: &'a str) -> Result<(), String> {
+        match word {
+            ":" => {
+                let (line, name) = self.token()?;
+                if self.labels.insert(name, self.here()).is_some() {
+                    return Err(format!("line {line}: '{name}' is already defined"));
+                }
+            },
+            ":alias" => {
+                let (_, name) = self.token()?;
+                let register = self.register()?;
+                self.aliases.insert(name, register);
+            },
+            ":const" => {
+                let (_, name) = self.token()?;
+                let value = self.value()?;
+                self.consts.insert(name, value);
+            },
+            ":org" => {
+                let addr = self.value()? as usize;
+                if addr < ORIGIN {
+                    return Err(format!("line {line}: can't assemble below {ORIGIN:03X}"));
+                }
+                self.at = addr - ORIGIN;
+            },
+            ":call" => self.address(0x2000)?,
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit_byte(byte as u8);
+            },
+            // Debugging hints for Octo's own debugger, which have no use here
+            ":breakpoint" => {
+                self.token()?;
+            },
+            ":monitor" => {
+                self.token()?;
+                self.token()?;
+            },
+            ":macro" | ":calc" | ":unpack" | ":next" | ":stringmode" | ":assert" => {
+                return Err(format!("line {line}: {word} isn't supported"));
+            },
+            "return" | ";" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "audio" => self.emit(0xF002),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00C0 | n);
+            },
+            "scroll-up" => {
+                let n = self.nibble()?;
+                self.emit(0x00D0 | n);
+            },
+            "plane" => {
+                let n = self.nibble()?;
+                self.emit(0xF001 | n << 8);
+            },
+            "jump" => self.address(0x1000)?,
+            "jump0" => self.address(0xB000)?,
+            "native" => self.address(0x0000)?,
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.register()? as u16;
+                let opcode = match word {
+                    "bcd" => 0xF033,
+                    "saveflags" => 0xF075,
+                    _ => 0xF085,
+                };
+                self.emit(opcode | x << 8);
+            },
+            "save" | "load" => {
+                let x = self.register()? as u16;
+                if self.peek() == Some("-") {
+                    self.next += 1;
+                    let y = self.register()? as u16;
+                    self.emit(0x5000 | x << 8 | y << 4 | if word == "save" { 2 } else { 3 });
+                } else {
+                    self.emit(if word == "save" { 0xF055 } else { 0xF065 } | x << 8);
+                }
+            },
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.nibble()?;
+                self.emit(0xD000 | x << 8 | y << 4 | n);
+            },
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()? as u16;
+                let opcode = match word {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A,
+                };
+                self.emit(opcode | x << 8);
+            },
+            "i" => self.index()?,
+            "if" => {
+                let condition = self.condition()?;
+                for opcode in condition.prelude {
+                    self.emit(opcode);
+                }
+                let (line, word) = self.token()?;
+                match word {
+                    "then" => self.emit(condition.unless),
+                    "begin" => {
+                        self.emit(condition.when);
+                        self.blocks.push(Block::If { line, jump: self.at });
+                        self.emit(0x1000);
+                    },
+                    word => return Err(format!("line {line}: expected 'then' or 'begin', found '{word}'")),
+                }
+            },
+            "else" => {
+                let Some(Block::If { line: if_line, jump }) = self.blocks.pop() else {
+                    return Err(format!("line {line}: else without an if ... begin"));
+                };
+                self.blocks.push(Block::Else { line: if_line, jump: self.at });
+                self.emit(0x1000);
+                self.patch(jump, self.here());
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump, .. } | Block::Else { jump, .. }) => self.patch(jump, self.here()),
+                _ => return Err(format!("line {line}: end without an if ... begin")),
+            },
+            "loop" => self.blocks.push(Block::Loop { line, start: self.here(), exits: Vec::new() }),
+            "while" => {
+                let condition = self.condition()?;
+                for opcode in condition.prelude {
+                    self.emit(opcode);
+                }
+                self.emit(condition.when);
+                let at = self.at;
+                let Some(Block::Loop { exits, .. }) =
+                    self.blocks.iter_mut().rev().find(|block| matches!(block, Block::Loop { .. }))
+                else {
+                    return Err(format!("line {line}: while outside a loop"));
+                };
+                exits.push(at);
+                self.emit(0x1000);
+            },
+            "again" => {
+                let Some(Block::Loop { start, exits, .. }) = self.blocks.pop() else {
+                    return Err(format!("line {line}: again without a loop"));
+                };
+                self.emit(0x1000 | start);
+                for exit in exits {
+                    self.patch(exit, self.here());
+                }
+            },
+            word => {
+                if let Some(x) = self.as_register(word) {
+                    self.register_statement(x as u16)?;
+                } else if self.value_of(word).is_some() && !self.labels.contains_key(word) {
+                    let byte = self.byte_of(line, word)?;
+                    self.emit_byte(byte as u8);
+                } else {
+                    // Anything else is a subroutine being called by name
+                    self.address_of(line, word, 0x2000)?;
+                }
+            },
+        }
+        return Ok(());
+    }
+
+    /// The statements that start with i
+    fn index(&mut self) -> Result<(), String> {
+        let (line, op) = self.token()?;
+        match op {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next += 1;
+                    let x = self.register()? as u16;
+                    self.emit(0xF029 | x << 8);
+                },
+                Some("bighex") => {
+                    self.next += 1;
+                    let x = self.register()? as u16;
+                    self.emit(0xF030 | x << 8);
+                },
+                Some("long") => {
+                    self.next += 1;
+                    self.emit(0xF000);
+                    let (line, word) = self.token()?;
+                    match self.value_of(word) {
+                        Some(addr) => {
+                            let addr = u16::try_from(addr).map_err(|_| format!("line {line}: {addr} isn't an address"))?;
+                            self.emit(addr);
+                        },
+                        None => {
+                            self.fixups.push((line, word, self.at, true));
+                            self.emit(0);
+                        },
+                    }
+                },
+                _ => self.address(0xA000)?,
+            },
+            "+=" => {
+                let x = self.register()? as u16;
+                self.emit(0xF01E | x << 8);
+            },
+            op => return Err(format!("line {line}: expected ':=' or '+=' after i, found '{op}'")),
+        }
+        return Ok(());
+    }
+
+    /// The statements that start with a register
+    fn register_statement(&mut self, x: u16) -> Result<(), String> {
+        let (line, op) = self.token()?;
+        let (rhs_line, rhs) = self.token()?;
+        let y = self.as_register(rhs).map(|y| (y as u16) << 4);
+        let opcode = match (op, y) {
+            (":=", Some(y)) => 0x8000 | y,
+            (":=", None) => match rhs {
+                "random" => 0xC000 | self.byte()?,
+                "delay" => 0xF007,
+                "key" => 0xF00A,
+                rhs => 0x6000 | self.byte_of(rhs_line, rhs)?,
+            },
+            ("+=", Some(y)) => 0x8004 | y,
+            ("+=", None) => 0x7000 | self.byte_of(rhs_line, rhs)?,
+            ("-=", Some(y)) => 0x8005 | y,
+            ("-=", None) => 0x7000 | (self.byte_of(rhs_line, rhs)? as u8).wrapping_neg() as u16,
+            ("=-", Some(y)) => 0x8007 | y,
+            ("|=", Some(y)) => 0x8001 | y,
+            ("&=", Some(y)) => 0x8002 | y,
+            ("^=", Some(y)) => 0x8003 | y,
+            (">>=", Some(y)) => 0x8006 | y,
+            ("<<=", Some(y)) => 0x800E | y,
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(format!("line {rhs_line}: {op} takes a register, found '{rhs}'"));
+            },
+            (op, _) => return Err(format!("line {line}: unknown operator '{op}'")),
+        };
+        self.emit(opcode | x << 8);
+        return Ok(());
+    }
+
+    fn condition(&mut self) -> Result<Condition, String> {
+        let x = self.register()? as u16;
+        let (line, op) = self.token()?;
+        let (pressed, not_pressed) = (0xE09E | x << 8, 0xE0A1 | x << 8);
+        match op {
+            "key" => return Ok(Condition { prelude: Vec::new(), unless: not_pressed, when: pressed }),
+            "-key" => return Ok(Condition { prelude: Vec::new(), unless: pressed, when: not_pressed }),
+            _ => {},
+        }
+
+        let (rhs_line, rhs) = self.token()?;
+        let rhs = match self.as_register(rhs) {
+            Some(y) => Operand::Register(y as u16),
+            None => Operand::Byte(self.byte_of(rhs_line, rhs)?),
+        };
+        let (equal, not_equal) = match rhs {
+            Operand::Register(y) => (0x5000 | x << 8 | y << 4, 0x9000 | x << 8 | y << 4),
+            Operand::Byte(n) => (0x3000 | x << 8 | n, 0x4000 | x << 8 | n),
+        };
+        return match op {
+            "==" => Ok(Condition { prelude: Vec::new(), unless: not_equal, when: equal }),
+            "!=" => Ok(Condition { prelude: Vec::new(), unless: equal, when: not_equal }),
+            "<" | ">" | "<=" | ">=" => {
+                // Subtracting right from left leaves VF 1 when left >= right, with the sides
+                // swapped round for > and <=
+                let (left, right) = match op {
+                    ">" | "<=" => (rhs, Operand::Register(x)),
+                    _ => (Operand::Register(x), rhs),
+                };
+                let prelude = match (left, right) {
+                    (Operand::Register(l), Operand::Register(r)) => vec![0x8F00 | l << 4, 0x8F05 | r << 4],
+                    (Operand::Byte(n), Operand::Register(r)) => vec![0x6F00 | n, 0x8F05 | r << 4],
+                    (Operand::Register(l), Operand::Byte(n)) => vec![0x6F00 | n, 0x8F07 | l << 4],
+                    (Operand::Byte(_), Operand::Byte(_)) => unreachable!("one side is always x"),
+                };
+                let (vf_set, vf_clear) = (0x3F01, 0x4F01);
+                match op {
+                    ">=" | "<=" => Ok(Condition { prelude, unless: vf_clear, when: vf_set }),
+                    _ => Ok(Condition { prelude, unless: vf_set, when: vf_clear }),
+                }
+            },
+            op => Err(format!("line {line}: unknown comparison '{op}'")),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip8;
+    use crate::platform::Platform;
+
+    /// Assembles source and runs steps instructions of it on CHIP-8
+    fn run(source: &str, steps: usize) -> Chip8 {
+        let mut chip = Chip8::with_platform(Platform::Chip8, false);
+        chip.load_rom_bytes(&assemble(source).expect("the source assembles"));
+        for _ in 0..steps {
+            chip.execute().expect("the instruction runs");
+        }
+        return chip;
+    }
+
+    #[test]
+    fn statements_labels_and_data_assemble_to_their_opcodes() {
+        let source = ": main v0 := 5 v1 += v0 i := sprite sprite v0 v1 5 loop again : sprite 0xF0 0x90";
+        let rom = [0x60, 0x05, 0x81, 0x04, 0xA2, 0x0A, 0xD0, 0x15, 0x12, 0x08, 0xF0, 0x90];
+        assert_eq!(assemble(source), Ok(rom.to_vec()));
+        // Without main first there's a jump to it, and a name on its own is a call
+        assert_eq!(assemble(": sub return : main sub"), Ok(vec![0x12, 0x04, 0x00, 0xEE, 0x22, 0x02]));
+        assert_eq!(
+            assemble(": main if v0 == 1 begin v1 := 2 else v1 := 3 end"),
+            Ok(vec![0x30, 0x01, 0x12, 0x08, 0x61, 0x02, 0x12, 0x0A, 0x61, 0x03])
+        );
+        assert_eq!(
+            assemble(":alias x v3 :const speed 2 : main x -= speed i := long data : data 0b1010 # a comment"),
+            Ok(vec![0x12, 0x02, 0x73, 0xFE, 0xF0, 0x00, 0x02, 0x08, 0x0A])
+        );
+        assert_eq!(assemble(": main :org 0x204 :byte 7"), Ok(vec![0, 0, 0, 0, 7]));
+    }
+
+    #[test]
+    fn conditions_and_loops_run_as_they_read() {
+        let source = "
+            : main
+                v0 := 5
+                if v0 < 6 then v1 += 1
+                if v0 > 4 then v1 += 2
+                if v0 <= 5 then v1 += 4
+                if v0 >= 6 then v1 += 8
+                if v0 != 5 then v1 += 16
+                loop
+                    v2 += 1
+                    while v2 != 10
+                again
+            : done jump done
+        ";
+        let chip = run(source, 100);
+        assert_eq!((chip.registers()[1], chip.registers()[2]), (7, 10));
+    }
+
+    #[test]
+    fn malformed_source_is_an_error_on_its_line() {
+        let errors = [
+            ("", "there's no ': main' label"),
+            ("\n: main\njump nowhere", "line 3: undefined name 'nowhere'"),
+            (": main\nif v0 == 1 begin", "line 2: if ... begin without an end"),
+            (": main loop", "line 1: loop without an again"),
+            (": main again", "again without a loop"),
+            (": main else", "else without an if ... begin"),
+            (": main end", "end without an if ... begin"),
+            (": main while v0 == 1", "while outside a loop"),
+            (": main : main", "'main' is already defined"),
+            (": main v0 := 256", "doesn't fit in a byte"),
+            (": main v0 ?= 1", "unknown operator '?='"),
+            (": main v0 |= 1", "|= takes a register"),
+            (": main if v0 ~ 1 then", "unknown comparison '~'"),
+            (": main scroll-down 16", "doesn't fit in a nibble"),
+            (": main jump 0x1000", "past where NNN reaches"),
+            (": main :org 0x100", "can't assemble below 200"),
+            (": main :macro", ":macro isn't supported"),
+            (": main i -= v0", "expected ':=' or '+=' after i"),
+            (": main sprite v0 v1", "unexpected end of the source"),
+            (": main sprite v0 vg 1", "expected a register, found 'vg'"),
+        ];
+        for (source, error) in errors {
+            let result = assemble(source);
+            assert!(result.as_ref().is_err_and(|e| e.contains(error)), "'{source}' gave {result:?}");
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Patches for roms, the community's bug fixes or your own tweaks, applied as a rom's loaded
+// rather than by editing the file, so the rom keeps its identity and with it its profile and
+// what's saved for it. A profile's patch lines name IPS files (profile.rs), and
+// Chip8::apply_patch writes their records over the rom in memory.
+//
+// IPS is the format patches for old console roms come in, and small enough to write by hand:
+//
+//   "PATCH"      magic
+//   records      each a u24 offset into the rom and a u16 length, big endian, then that
+//                many bytes, or a length of 0 followed by a u16 count and one byte to
+//                repeat count times
+//   "EOF"        the end, optionally followed by a u24 length to cut the rom down to,
+//                which is ignored since the rom's already in memory
+//
+// An offset that happens to be 454F46 ("EOF") can't be written, as in every IPS patch.
+
+const MAGIC: &[u8; 5] = b"PATCH";
+const EOF: &[u8; 3] = b"EOF";
+
+
+/// Bytes to write over a rom, starting offset bytes into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchRecord {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+/// Reads the records of an IPS patch
+pub fn parse_ips(mut bytes: &[u8]) -> Result<Vec<PatchRecord>, String> {
+    if take(&mut bytes, MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        return Err("not an IPS patch".to_string());
+    }
+
+    let mut records = Vec::new();
+    loop {
+        let offset = take(&mut bytes, 3)?;
+        if offset == EOF {
+            return Ok(records);
+        }
+        let offset = number(offset);
+        let record = match number(take(&mut bytes, 2)?) {
+            0 => {
+                let count = number(take(&mut bytes, 2)?) as usize;
+                vec![take(&mut bytes, 1)?[0]; count]
+            },
+            len => take(&mut bytes, len as usize)?.to_vec(),
+        };
+        if record.is_empty() {
+            return Err(format!("the record at {offset:06X} is empty"));
+        }
+        records.push(PatchRecord { offset, bytes: record });
+    }
+}
+
+/// Takes the next len bytes off the front
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if bytes.len() < len {
+        return Err("the patch ends before its EOF".to_string());
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    return Ok(taken);
+}
+
+/// A big endian number
+fn number(bytes: &[u8]) -> u32 {
+    return bytes.iter().fold(0, |n, byte| n << 8 | *byte as u32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip::Chip8;
+    use crate::platform::Platform;
+
+    #[test]
+    fn records_and_runs_are_read_and_written_over_the_rom() {
+        let ips = b"PATCH\x00\x00\x02\x00\x02\x60\x05\x00\x01\x00\x00\x00\x00\x03\xAA\x00\x00\x04\x00\x01\xE0EOF\x00\x10\x00";
+        let records = parse_ips(ips).expect("the patch parses");
+        assert_eq!(
+            records,
+            [
+                PatchRecord { offset: 2, bytes: vec![0x60, 0x05] },
+                PatchRecord { offset: 0x100, bytes: vec![0xAA; 3] },
+                PatchRecord { offset: 4, bytes: vec![0xE0] },
+            ]
+        );
+        assert_eq!(parse_ips(b"PATCHEOF"), Ok(Vec::new()));
+
+        let mut chip = Chip8::with_platform(Platform::Chip8, false);
+        chip.load_rom_bytes(&[0x00, 0xE0, 0x60, 0x01, 0x00, 0x00]);
+        assert_eq!(chip.apply_patch(&records), 6);
+        assert_eq!(chip.read_mem(0x200, 6), [0x00, 0xE0, 0x60, 0x05, 0xE0, 0x00]);
+        assert_eq!(chip.read_mem(0x300, 4), [0xAA, 0xAA, 0xAA, 0x00]);
+        // What would go past the end of memory is dropped
+        assert_eq!(chip.apply_patch(&[PatchRecord { offset: 0xDFE, bytes: vec![1, 2, 3, 4] }]), 2);
+    }
+
+    #[test]
+    fn malformed_patches_are_errors() {
+        let errors: [(&[u8], &str); 7] = [
+            (b"", "not an IPS patch"),
+            (b"PATCX\x00\x00\x00\x00\x01\x00EOF", "not an IPS patch"),
+            (b"PATCH", "the patch ends before its EOF"),
+            (b"PATCH\x00\x00\x02\x00\x03\x60\x05", "the patch ends before its EOF"),
+            (b"PATCH\x00\x00\x02\x00\x00\x00\x04", "the patch ends before its EOF"),
+            (b"PATCH\x00\x00\x02\x00\x00\x00\x00\xFFEOF", "the record at 000002 is empty"),
+            (b"PATCH\x00\x00\x02\x00\x01\x60EO", "the patch ends before its EOF"),
+        ];
+        for (ips, error) in errors {
+            assert_eq!(parse_ips(ips), Err(error.to_string()), "{ips:?}");
+        }
+    }
+}
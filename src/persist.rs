@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Anything that should outlive a run is kept per rom in the data directory, in files named
+// after a hash of the rom so renaming or moving it doesn't lose anything:
+//
+//   <hash>.rpl       the 16 RPL user flags saved by FX75, as raw bytes
+//   <hash>.sav       the memory ranges the rom's profile persists, one after the other
+//   <hash>.profile   the rom's settings, see profile.rs
+//   <hash>.cht       the rom's cheats, see cheat.rs
+//   states/          save states the debugger's save was given a bare name for
+//   traces/<hash>/   what the debugger's trace on records, see tracedb.rs
+//
+// Settings for every rom are kept apart from that in the config directory, see config.rs.
+// Both go where the platform keeps such things:
+//
+//   Linux and others   $XDG_DATA_HOME/chip8 or ~/.local/share/chip8, and $XDG_CONFIG_HOME/chip8
+//                      or ~/.config/chip8
+//   macOS              ~/Library/Application Support/chip8 for both
+//   Windows            %APPDATA%\chip8 for both
+//
+// unless --data-dir says otherwise, which puts both in the one directory, e.g. to keep chip8
+// and everything it saves together on a USB stick.
+
+/// The directory --data-dir gave, if it was given
+static OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+
+/// Keeps the data and the config in dir rather than where the platform keeps them, from now
+/// on. Only the first call counts
+pub fn set_dir(dir: &Path) {
+    let _ = OVERRIDE.set(dir.to_path_buf());
+}
+
+/// Where per rom data is stored
+pub fn data_dir() -> PathBuf {
+    return platform_dir("XDG_DATA_HOME", ".local/share");
+}
+
+/// Where the config is kept
+pub fn config_dir() -> PathBuf {
+    return platform_dir("XDG_CONFIG_HOME", ".config");
+}
+
+/// Where a save state goes by the name it was given: a bare name's kept in the data
+/// directory's states folder, with .state added if it hasn't got an extension, and anything
+/// with a folder in it is left where it says
+pub fn state_path(name: &str) -> PathBuf {
+    let path = Path::new(name);
+    if path.components().count() != 1 || path.is_absolute() {
+        return path.to_path_buf();
+    }
+    let path = data_dir().join("states").join(path);
+    if path.extension().is_none() {
+        return path.with_extension("state");
+    }
+    return path;
+}
+
+/// Where to read a save state from: the file the name says if there is one, for states saved
+/// before they went in the states folder, and otherwise where state_path puts it
+pub fn find_state(name: &str) -> PathBuf {
+    if Path::new(name).is_file() {
+        return PathBuf::from(name);
+    }
+    return state_path(name);
+}
+
+/// The chip8 directory where the platform keeps data or config, going by the XDG variable and
+/// the folder under the home directory on Linux and the like
+fn platform_dir(xdg: &str, home_folder: &str) -> PathBuf {
+    if let Some(dir) = OVERRIDE.get() {
+        return dir.clone();
+    }
+    let var = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty()).map(PathBuf::from);
+    if cfg!(windows) {
+        if let Some(dir) = var("APPDATA") {
+            return dir.join("chip8");
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = var("HOME") {
+            return home.join("Library/Application Support/chip8");
+        }
+    } else {
+        if let Some(dir) = var(xdg) {
+            return dir.join("chip8");
+        }
+        if let Some(home) = var("HOME") {
+            return home.join(home_folder).join("chip8");
+        }
+    }
+    return PathBuf::from(".chip8");
+}
+
+/// The 64-bit FNV-1a hash of some bytes. It's written out by hand rather than using std's
+/// hasher, whose output can change between Rust versions and would orphan everything
+/// already saved
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    return hash;
+}
+
+/// Identifies a rom by its contents, as 16 hex digits of its hash
+pub fn rom_id(rom: &[u8]) -> String {
+    return format!("{:016x}", hash(rom));
+}
+
+fn rpl_path(rom: &[u8]) -> PathBuf {
+    return data_dir().join(format!("{}.rpl", rom_id(rom)));
+}
+
+/// Reads the RPL flags saved for a rom, if it's saved any
+pub fn load_rpl_flags(rom: &[u8]) -> Option<[u8; 16]> {
+    let bytes = std::fs::read(rpl_path(rom)).ok()?;
+    let mut flags = [0; 16];
+    let len = bytes.len().min(16);
+    flags[..len].copy_from_slice(&bytes[..len]);
+    return Some(flags);
+}
+
+/// Saves the RPL flags for a rom, creating the data directory if needed
+pub fn save_rpl_flags(rom: &[u8], flags: &[u8; 16]) -> std::io::Result<()> {
+    std::fs::create_dir_all(data_dir())?;
+    return std::fs::write(rpl_path(rom), flags);
+}
+
+fn sav_path(rom: &[u8]) -> PathBuf {
+    return data_dir().join(format!("{}.sav", rom_id(rom)));
+}
+
+/// Reads the saved memory for a rom, if it's saved any
+pub fn load_memory(rom: &[u8]) -> Option<Vec<u8>> {
+    return std::fs::read(sav_path(rom)).ok();
+}
+
+/// Saves the persisted memory ranges of a rom, creating the data directory if needed
+pub fn save_memory(rom: &[u8], bytes: &[u8]) -> std::io::Result<()> {
+    std::fs::create_dir_all(data_dir())?;
+    return std::fs::write(sav_path(rom), bytes);
+}
@@ -0,0 +1,75 @@
+use core::fmt;
+
+/// The CHIP-8 variants, in order of how much they extend the original instruction set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    Chip8,
+    HiresChip8,
+    Chip8X,
+    SuperChip,
+    XoChip,
+    MegaChip,
+}
+
+impl Platform {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::HiresChip8 => "HIRES CHIP-8",
+            Platform::Chip8X => "CHIP-8X",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+            Platform::MegaChip => "MEGA-CHIP",
+        };
+    }
+
+    /// Parses a platform name as typed on the command line, e.g. chip8, hires, chip8x, schip or megachip
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase().replace(['-', '_'], "");
+        return match name.as_str() {
+            "chip8" => Some(Platform::Chip8),
+            "hires" | "hireschip8" | "chip8hires" => Some(Platform::HiresChip8),
+            "chip8x" => Some(Platform::Chip8X),
+            "superchip" | "schip" => Some(Platform::SuperChip),
+            "xochip" => Some(Platform::XoChip),
+            "megachip" | "megachip8" => Some(Platform::MegaChip),
+            _ => None,
+        };
+    }
+
+    /// How many bytes of RAM the platform has by default. XO-CHIP can address 64KB with
+    /// its long I, and Mega-Chip's I register is 24 bits wide so it gets a full 16MB to
+    /// hold its sprites and digitized sound
+    pub fn memory_size(&self) -> usize {
+        return match self {
+            Platform::XoChip => 0x1_0000,
+            Platform::MegaChip => 0x100_0000,
+            _ => 0x1000,
+        };
+    }
+
+    /// Whether the platform runs base's instructions: every platform runs CHIP-8's, XO-CHIP
+    /// and Mega-Chip are built on SUPER-CHIP so run its too, and the rest only their own
+    pub fn extends(&self, base: Platform) -> bool {
+        return match base {
+            Platform::Chip8 => true,
+            Platform::SuperChip => matches!(self, Platform::SuperChip | Platform::XoChip | Platform::MegaChip),
+            _ => *self == base,
+        };
+    }
+
+    /// Where roms are loaded and execution starts. The CHIP-8X interpreter is bigger
+    /// than the original, so its programs start a page later
+    pub fn start_address(&self) -> u16 {
+        return match self {
+            Platform::Chip8X => 0x300,
+            _ => 0x200,
+        };
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "{}", self.name());
+    }
+}
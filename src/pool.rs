@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::persist;
+use crate::platform::Platform;
+
+// Running lots of machines side by side, for fuzzing campaigns, training populations of
+// agents, and running test suites. Machines are spread across a fixed number of worker
+// threads, which each own theirs for the life of the pool, so the pool is driven with
+// commands rather than by handing out the machines themselves. Roms are cached by hash, so
+// a thousand machines playing the same rom share one copy of it.
+//
+// Every frame a machine runs, the hash of its screen is recorded for harvest() to collect.
+// Comparing these across machines (or against a known good run) is usually all a fuzzer or
+// test runner needs, without copying whole screens between threads.
+
+
+/// What harvest() collects from a machine
+#[derive(Debug, Clone, Default)]
+pub struct Harvest {
+    /// The hash of the screen after each frame run since the last harvest
+    pub hashes: Vec<u64>,
+    /// The error the machine stopped with, if it has. Stopped machines don't run any more
+    pub error: Option<Chip8Error>,
+}
+
+enum Command {
+    Add { id: usize, rom: Arc<[u8]>, platform: Platform, seed: u64 },
+    Speed { id: usize, speed: usize },
+    Keys { id: usize, keys: u16 },
+    Run { frames: usize },
+    Harvest,
+}
+
+enum Reply {
+    Ran,
+    Harvested(Vec<(usize, Harvest)>),
+}
+
+struct Worker {
+    commands: Sender<Command>,
+    replies: Receiver<Reply>,
+    thread: JoinHandle<()>,
+}
+
+/// One machine, living on a worker thread
+struct Instance {
+    id: usize,
+    chip: Chip8,
+    harvest: Harvest,
+}
+
+pub struct EmulatorPool {
+    workers: Vec<Worker>,
+    roms: HashMap<u64, Arc<[u8]>>,
+    len: usize,
+}
+
+impl EmulatorPool {
+    /// Starts a pool with the given number of worker threads, or one per core for 0
+    pub fn new(threads: usize) -> Self {
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        };
+
+        let workers = (0..threads)
+            .map(|_| {
+                let (commands, command_rx) = mpsc::channel();
+                let (reply_tx, replies) = mpsc::channel();
+                let thread = std::thread::spawn(move || work(command_rx, reply_tx));
+                Worker { commands, replies, thread }
+            })
+            .collect();
+        return Self { workers, roms: HashMap::new(), len: 0 };
+    }
+
+    /// Adds a machine running the rom with its random numbers from seed, returning its id.
+    /// Ids count up from 0 in the order machines are added
+    pub fn add(&mut self, rom: &[u8], platform: Platform, seed: u64) -> usize {
+        let rom = self.roms.entry(persist::hash(rom)).or_insert_with(|| rom.into()).clone();
+        let id = self.len;
+        self.len += 1;
+        self.send(id, Command::Add { id, rom, platform, seed });
+        return id;
+    }
+
+    /// How many machines are in the pool
+    pub fn len(&self) -> usize {
+        return self.len;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len == 0;
+    }
+
+    /// How many different roms the machines are running between them
+    pub fn roms(&self) -> usize {
+        return self.roms.len();
+    }
+
+    /// Sets how many instructions a machine runs each frame (CYCLES_PER_FRAME to start with)
+    pub fn set_speed(&mut self, id: usize, speed: usize) {
+        self.send(id, Command::Speed { id, speed });
+    }
+
+    /// Sets which keys a machine has held down, as a bitmask with bit n for key n
+    pub fn set_keys(&mut self, id: usize, keys: u16) {
+        self.send(id, Command::Keys { id, keys });
+    }
+
+    /// Runs every machine for a number of frames, all at once across the workers, and waits
+    /// for them to finish. There's no pacing, they run as fast as they can
+    pub fn run(&mut self, frames: usize) {
+        for worker in &self.workers {
+            let _ = worker.commands.send(Command::Run { frames });
+        }
+        for worker in &self.workers {
+            let _ = worker.replies.recv();
+        }
+    }
+
+    /// Collects every machine's screen hashes since the last harvest and any error it's
+    /// stopped with, indexed by id
+    pub fn harvest(&mut self) -> Vec<Harvest> {
+        for worker in &self.workers {
+            let _ = worker.commands.send(Command::Harvest);
+        }
+
+        let mut harvests = vec![Harvest::default(); self.len];
+        for worker in &self.workers {
+            if let Ok(Reply::Harvested(instances)) = worker.replies.recv() {
+                for (id, harvest) in instances {
+                    harvests[id] = harvest;
+                }
+            }
+        }
+        return harvests;
+    }
+
+    /// Machines are dealt out to the workers in turn
+    fn send(&self, id: usize, command: Command) {
+        let _ = self.workers[id % self.workers.len()].commands.send(command);
+    }
+}
+
+impl Drop for EmulatorPool {
+    fn drop(&mut self) {
+        for worker in self.workers.drain(..) {
+            // Hanging up the commands channel is what tells the worker to finish
+            drop(worker.commands);
+            let _ = worker.thread.join();
+        }
+    }
+}
+
+/// A worker thread's loop, running commands until the pool hangs up
+fn work(commands: Receiver<Command>, replies: Sender<Reply>) {
+    let mut instances: Vec<Instance> = Vec::new();
+    for command in commands {
+        match command {
+            Command::Add { id, rom, platform, seed } => {
+                let mut chip = Chip8::with_platform(platform, false);
+                chip.seed_rng(seed);
+                chip.load_rom_bytes(&rom);
+                instances.push(Instance { id, chip, harvest: Harvest::default() });
+            },
+            Command::Speed { id, speed } => {
+                if let Some(instance) = find(&mut instances, id) {
+                    instance.chip.set_cycles_per_frame(speed);
+                }
+            },
+            Command::Keys { id, keys } => {
+                if let Some(instance) = find(&mut instances, id) {
+                    for key in 0..16 {
+                        instance.chip.set_key(key, keys & (1 << key) != 0);
+                    }
+                }
+            },
+            Command::Run { frames } => {
+                for instance in &mut instances {
+                    instance.run(frames);
+                }
+                if replies.send(Reply::Ran).is_err() {
+                    return;
+                }
+            },
+            Command::Harvest => {
+                let harvested = instances
+                    .iter_mut()
+                    .map(|instance| {
+                        let error = instance.harvest.error.clone();
+                        let hashes = std::mem::take(&mut instance.harvest.hashes);
+                        (instance.id, Harvest { hashes, error })
+                    })
+                    .collect();
+                if replies.send(Reply::Harvested(harvested)).is_err() {
+                    return;
+                }
+            },
+        }
+    }
+}
+
+/// Ids are dealt out in turn, so a worker's are in order but not contiguous
+fn find(instances: &mut [Instance], id: usize) -> Option<&mut Instance> {
+    let i = instances.binary_search_by_key(&id, |instance| instance.id).ok()?;
+    return Some(&mut instances[i]);
+}
+
+impl Instance {
+    fn run(&mut self, frames: usize) {
+        for _ in 0..frames {
+            if self.harvest.error.is_some() {
+                return;
+            }
+            if let Err(e) = self.chip.run_frame() {
+                self.harvest.error = Some(e);
+                return;
+            }
+            self.harvest.hashes.push(persist::hash(self.chip.framebuffer().bytes()));
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::audio::Beep;
+use crate::chip::{Quirks, StackPolicy};
+use crate::frontend::input::InputProfile;
+use crate::frontend::rotation::Rotation;
+use crate::frontend::settings::Palette;
+use crate::persist;
+
+// A profile holds settings for one rom, and lives next to its other saved data as
+// <data dir>/<hash>.profile (`chip8 profile <rom>` shows where). One setting a line:
+//
+//   # comments and blank lines are ignored
+//   persist = 2F0-2FF    keep this memory range (inclusive, hex) between runs
+//   speed = 15           instructions per frame, instead of the one `chip8 analyze` suggests
+//   beep = sine          how the beep sounds, along with beep_hz, beep_volume, beep_attack
+//                        and beep_release (see audio.rs)
+//   pause_on_focus_loss = false   keep running when the window isn't focused
+//   rotation = 90        turn the screen clockwise by 90, 180 or 270 degrees (see rotation.rs)
+//   input = pong         which keys are whose, for two player games (see input.rs)
+//   palette = amber      the colours of pixels on and off, a name or two hex colours like
+//                        FFB000 1A0F00 (see settings.rs)
+//   memory_protection = true   stop roms writing below where they're loaded
+//   strict = true        stop on unknown opcodes and reaching past memory, as --strict does
+//   stack = 64           how many calls deep the stack goes, for roms that recurse past 16
+//   stack_overflow = wrap   forget the oldest return address when it's full, rather than
+//                        stopping with an error
+//   shift_vy = true      8XY6 and 8XYE shift VY rather than VX, the first of the quirks
+//                        (see chip.rs' Quirks)
+//   jump_vx = true       BNNN adds VX rather than V0
+//   memory_moves_i = true   FX55 and FX65 move I on past the registers
+//   vf_reset = true      8XY1 to 8XY3 set VF to 0
+//   display_wait = true  DXYN waits for the next frame once there's been a draw
+//   patch = fix.ips      apply an IPS patch as the rom's loaded (see patch.rs), relative to
+//                        the data dir unless the path's absolute
+//
+// persist and patch can be given more than once, the patches applying in order. The ranges
+// are written to <hash>.sav when a run ends and copied back into memory after the rom is
+// loaded, so games that keep their high scores in ordinary RAM hold on to them.
+
+
+/// The settings for one rom
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    /// Memory ranges saved between runs
+    pub persist: Vec<Range<u32>>,
+    /// Instructions per frame, if the rom needs a particular speed
+    pub speed: Option<usize>,
+    pub beep: Beep,
+    /// Whether the frontends pause when their window loses focus, they do if it's not said
+    pub pause_on_focus_loss: Option<bool>,
+    /// How far the frontends turn the screen, if the rom's made for a display on its side
+    pub rotation: Option<Rotation>,
+    /// The input profile, if the rom needs other than the standard one
+    pub input: Option<&'static InputProfile>,
+    /// The colours pixels are shown in, if not white on black
+    pub palette: Option<Palette>,
+    pub memory_protection: Option<bool>,
+    pub strict: Option<bool>,
+    /// How deep the stack goes, if the rom needs more than 16 levels
+    pub stack: Option<usize>,
+    /// What a call does with the stack full, if not stopping with an error
+    pub stack_overflow: Option<StackPolicy>,
+    /// The quirks the rom needs, none if it's not said
+    pub quirks: Quirks,
+    /// IPS patches applied over the rom as it's loaded, in order
+    pub patches: Vec<PathBuf>,
+}
+
+impl Profile {
+    /// Parses the contents of a profile
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut profile = Profile::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected '<setting> = <value>'", i + 1))?;
+            match key.trim() {
+                "persist" => {
+                    let range = parse_range(value.trim()).map_err(|e| format!("line {}: {e}", i + 1))?;
+                    profile.persist.push(range);
+                },
+                "speed" => {
+                    let speed = value.trim().parse::<usize>().ok().filter(|speed| *speed > 0);
+                    profile.speed = Some(speed.ok_or(format!("line {}: invalid speed '{}'", i + 1, value.trim()))?);
+                },
+                "pause_on_focus_loss" => {
+                    let pause = value.trim().parse::<bool>().ok();
+                    profile.pause_on_focus_loss = Some(pause.ok_or(format!("line {}: expected true or false", i + 1))?);
+                },
+                "rotation" => {
+                    let rotation = value.trim().parse::<u32>().ok().and_then(Rotation::from_degrees);
+                    profile.rotation = Some(rotation.ok_or(format!("line {}: expected 0, 90, 180 or 270", i + 1))?);
+                },
+                "input" => {
+                    let input = InputProfile::from_name(value.trim());
+                    profile.input = Some(input.ok_or(format!("line {}: unknown input profile '{}'", i + 1, value.trim()))?);
+                },
+                "patch" => profile.patches.push(persist::data_dir().join(value.trim())),
+                "palette" => {
+                    let palette = Palette::parse(value);
+                    profile.palette = Some(palette.ok_or(format!("line {}: invalid palette '{}'", i + 1, value.trim()))?);
+                },
+                "memory_protection" | "strict" | "shift_vy" | "jump_vx" | "memory_moves_i" | "vf_reset" | "display_wait" => {
+                    let on = value.trim().parse::<bool>().ok().ok_or(format!("line {}: expected true or false", i + 1))?;
+                    match key.trim() {
+                        "strict" => profile.strict = Some(on),
+                        "shift_vy" => profile.quirks.shift_vy = on,
+                        "jump_vx" => profile.quirks.jump_vx = on,
+                        "memory_moves_i" => profile.quirks.memory_moves_i = on,
+                        "vf_reset" => profile.quirks.vf_reset = on,
+                        "display_wait" => profile.quirks.display_wait = on,
+                        _ => profile.memory_protection = Some(on),
+                    }
+                },
+                "stack" => {
+                    let depth = value.trim().parse::<usize>().ok().filter(|depth| (1..=255).contains(depth));
+                    profile.stack = Some(depth.ok_or(format!("line {}: expected a depth from 1 to 255", i + 1))?);
+                },
+                "stack_overflow" => {
+                    let policy = match value.trim() {
+                        "error" => StackPolicy::Error,
+                        "wrap" => StackPolicy::Wrap,
+                        _ => return Err(format!("line {}: expected error or wrap", i + 1)),
+                    };
+                    profile.stack_overflow = Some(policy);
+                },
+                key if Beep::KEYS.contains(&key) => {
+                    profile.beep.set(key, value.trim()).map_err(|e| format!("line {}: {e}", i + 1))?;
+                },
+                key => return Err(format!("line {}: unknown setting '{key}'", i + 1)),
+            }
+        }
+
+        return Ok(profile);
+    }
+
+    /// Where the profile for a rom is kept
+    pub fn path(rom: &[u8]) -> PathBuf {
+        return persist::data_dir().join(format!("{}.profile", persist::rom_id(rom)));
+    }
+
+    /// Loads the profile for a rom, or the default one if it doesn't have one
+    pub fn load(rom: &[u8]) -> Result<Self, String> {
+        return match std::fs::read_to_string(Self::path(rom)) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    /// Sets one setting in a rom's profile, replacing the line it's already on if it's there
+    /// and leaving the rest of the file alone
+    pub fn save_setting(rom: &[u8], key: &str, value: &str) -> std::io::Result<()> {
+        let path = Self::path(rom);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+
+        let setting = format!("{key} = {value}");
+        let mut lines: Vec<&str> = text.lines().collect();
+        let existing = lines.iter().position(|line| line.split_once('=').is_some_and(|(k, _)| k.trim() == key));
+        match existing {
+            Some(i) => lines[i] = &setting,
+            None => lines.push(&setting),
+        }
+
+        std::fs::create_dir_all(persist::data_dir())?;
+        return std::fs::write(path, lines.join("\n") + "\n");
+    }
+}
+
+/// Parses an inclusive hex range like 2F0-2FF
+fn parse_range(value: &str) -> Result<Range<u32>, String> {
+    let parse = |n: &str| {
+        let digits = n.trim().trim_start_matches("0x").trim_start_matches("0X");
+        return u32::from_str_radix(digits, 16).map_err(|_| format!("invalid address '{n}'"));
+    };
+
+    let (start, end) = value.split_once('-').ok_or(format!("expected '<start>-<end>', got '{value}'"))?;
+    let (start, end) = (parse(start)?, parse(end)?);
+    if end < start {
+        return Err(format!("range {start:X}-{end:X} ends before it starts"));
+    }
+
+    let past_end = end.checked_add(1).ok_or(format!("range {start:X}-{end:X} ends past the last address"))?;
+    return Ok(start..past_end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_profile() {
+        let profile = Profile::parse("# Pong\n\npersist = 2F0-2FF\nspeed = 15\n  shift_vy = true\nstack = 64\nstack_overflow = wrap\n").unwrap();
+        assert_eq!(profile.persist, vec![0x2F0..0x300]);
+        assert_eq!(profile.speed, Some(15));
+        assert!(profile.quirks.shift_vy);
+        assert_eq!(profile.stack, Some(64));
+        assert_eq!(profile.stack_overflow, Some(StackPolicy::Wrap));
+        assert_eq!(Profile::parse(""), Ok(Profile::default()));
+    }
+
+    #[test]
+    fn persist_and_patch_can_be_repeated() {
+        let profile = Profile::parse("persist = 0x200-0x20F\npersist = 300-300\npatch = a.ips\npatch = b.ips").unwrap();
+        assert_eq!(profile.persist, vec![0x200..0x210, 0x300..0x301]);
+        assert_eq!(profile.patches, vec![persist::data_dir().join("a.ips"), persist::data_dir().join("b.ips")]);
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        for text in [
+            "persist = 2FF-2F0",
+            "persist = 2G0-2FF",
+            "persist = 2F0",
+            "persist = 0-FFFFFFFF",
+            "speed = 0",
+            "strict = yes",
+            "stack = 256",
+            "speed 15",
+            "colour = red",
+        ] {
+            assert!(Profile::parse(text).is_err(), "'{text}' parsed");
+        }
+        assert_eq!(Profile::parse("speed = 15\npersist = 0-FFFFFFFF"), Err("line 2: range 0-FFFFFFFF ends past the last address".to_string()));
+    }
+}
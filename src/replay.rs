@@ -0,0 +1,207 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::analyze;
+use crate::env::Env;
+use crate::platform::Platform;
+
+// A regression corpus built out of replays, for chip8 test: a directory of roms each with
+// the keys held through a run and a hash of the screen after every frame of it. Running the
+// keys again has to give the same screens, and the first frame that doesn't is where the
+// interpreter's behaviour changed. Replays run on env.rs, so random numbers come from the
+// replay's seed and nothing depends on timing.
+//
+// A case is three files sharing a name, e.g. brix.ch8, brix.inputs and brix.hashes. The
+// inputs file has a line per stretch of frames, the held keys as a bitmask (bit n for key n,
+// as env.rs takes them) and optionally how many frames, 1 if not:
+//
+//     # comments start with #
+//     platform schip
+//     speed 30
+//     seed 7
+//     0000 120
+//     0010 8
+//
+// platform, speed and seed are optional too. Left out they're what analyze.rs guesses and 0,
+// but pinning them keeps a case passing when the guesses improve. The hashes file is a hash
+// of the screen as 16 hex digits a line, and it's written by the first run of a case that
+// doesn't have one.
+
+
+pub struct Replay {
+    pub platform: Option<Platform>,
+    pub speed: Option<usize>,
+    pub seed: u64,
+    /// The keys held for each frame
+    pub inputs: Vec<u16>,
+}
+
+impl Replay {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut replay = Self { platform: None, speed: None, seed: 0, inputs: Vec::new() };
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let error = |what: &str| format!("line {}: {what} in '{line}'", n + 1);
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => {},
+                ["platform", name] => replay.platform = Some(Platform::from_name(name).ok_or(error("unknown platform"))?),
+                ["speed", speed] => replay.speed = Some(speed.parse().map_err(|_| error("invalid speed"))?),
+                ["seed", seed] => replay.seed = seed.parse().map_err(|_| error("invalid seed"))?,
+                [keys] => replay.inputs.push(u16::from_str_radix(keys, 16).map_err(|_| error("invalid keys"))?),
+                [keys, frames] => {
+                    let keys = u16::from_str_radix(keys, 16).map_err(|_| error("invalid keys"))?;
+                    let frames = frames.parse().map_err(|_| error("invalid number of frames"))?;
+                    replay.inputs.extend(std::iter::repeat_n(keys, frames));
+                },
+                _ => return Err(error("expected keys and a number of frames")),
+            }
+        }
+        return Ok(replay);
+    }
+
+    /// Runs the replay on the rom and returns a hash of the screen after every frame, stopping
+    /// early if the rom fails
+    pub fn run(&self, rom: &[u8]) -> Result<Vec<u64>, (Vec<u64>, String)> {
+        let analysis = analyze::analyze(rom);
+        let mut env = Env::new(rom, self.platform.unwrap_or(analysis.platform))
+            .with_speed(self.speed.unwrap_or(analysis.speed));
+        env.seed(self.seed);
+        env.reset();
+
+        let mut hashes = Vec::with_capacity(self.inputs.len());
+        for keys in &self.inputs {
+            let (_, hash, _) = env.step(*keys);
+            if let Some(e) = env.error() {
+                return Err((hashes, e.to_string()));
+            }
+            hashes.push(hash);
+        }
+        return Ok(hashes);
+    }
+}
+
+/// One rom and replay from a corpus
+pub struct Case {
+    pub name: String,
+    pub rom: PathBuf,
+    pub inputs: PathBuf,
+    pub hashes: PathBuf,
+}
+
+/// How a case went
+pub enum Outcome {
+    Passed { frames: usize },
+    /// There were no hashes, so the run's were written
+    Recorded { frames: usize },
+    /// The screen first differed after this frame, counting from 0
+    Diverged { frame: usize, expected: u64, got: u64 },
+    /// The run ended at a different frame than the hashes did, by running out of inputs or
+    /// the rom failing
+    Ended { frame: usize, expected: usize, error: Option<String> },
+    /// The case couldn't be run at all
+    Broken(String),
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        return matches!(self, Self::Passed { .. } | Self::Recorded { .. });
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Passed { frames } => write!(f, "ok, {frames} frames"),
+            Self::Recorded { frames } => write!(f, "recorded {frames} frames"),
+            Self::Diverged { frame, expected, got } => {
+                write!(f, "FAILED, diverged at frame {frame}: expected {expected:016x}, got {got:016x}")
+            },
+            Self::Ended { frame, expected, error: Some(error) } => {
+                write!(f, "FAILED, stopped at frame {frame} of {expected}: {error}")
+            },
+            Self::Ended { frame, expected, error: None } => {
+                write!(f, "FAILED, ran {frame} frames where {expected} were expected")
+            },
+            Self::Broken(e) => write!(f, "FAILED, {e}"),
+        }
+    }
+}
+
+/// The cases in a corpus directory, in name order: every .inputs file with a rom beside it
+pub fn corpus(dir: &Path) -> Result<Vec<Case>, String> {
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("couldn't read {}: {e}", dir.display()))?;
+    let mut files: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    files.sort();
+
+    let mut cases = Vec::new();
+    for inputs in files.iter().filter(|path| path.extension().is_some_and(|ext| ext == "inputs")) {
+        let name = inputs.file_stem().unwrap_or_default();
+        let rom = files.iter().find(|path| {
+            path.is_file()
+                && path.file_stem() == Some(name)
+                && path.extension().is_none_or(|ext| ext != "inputs" && ext != "hashes")
+        });
+        let rom = rom.ok_or(format!("there's no rom beside {}", inputs.display()))?;
+        cases.push(Case {
+            name: name.to_string_lossy().into_owned(),
+            rom: rom.clone(),
+            inputs: inputs.clone(),
+            hashes: inputs.with_extension("hashes"),
+        });
+    }
+    return Ok(cases);
+}
+
+impl Case {
+    /// Runs the case against its hashes, or records them if it has none or `update` is set
+    pub fn run(&self, update: bool) -> Outcome {
+        let (rom, replay) = match self.load() {
+            Ok(loaded) => loaded,
+            Err(e) => return Outcome::Broken(e),
+        };
+
+        let (hashes, error) = match replay.run(&rom) {
+            Ok(hashes) => (hashes, None),
+            Err((hashes, e)) => (hashes, Some(e)),
+        };
+
+        if update || !self.hashes.exists() {
+            if let Some(e) = error {
+                return Outcome::Broken(format!("the rom failed at frame {} while recording: {e}", hashes.len()));
+            }
+            let text: String = hashes.iter().map(|hash| format!("{hash:016x}\n")).collect();
+            if let Err(e) = std::fs::write(&self.hashes, text) {
+                return Outcome::Broken(format!("couldn't write {}: {e}", self.hashes.display()));
+            }
+            return Outcome::Recorded { frames: hashes.len() };
+        }
+
+        let expected = match read_hashes(&self.hashes) {
+            Ok(expected) => expected,
+            Err(e) => return Outcome::Broken(e),
+        };
+        if let Some(frame) = hashes.iter().zip(&expected).position(|(got, expected)| got != expected) {
+            return Outcome::Diverged { frame, expected: expected[frame], got: hashes[frame] };
+        }
+        if hashes.len() != expected.len() || error.is_some() {
+            return Outcome::Ended { frame: hashes.len(), expected: expected.len(), error };
+        }
+        return Outcome::Passed { frames: hashes.len() };
+    }
+
+    fn load(&self) -> Result<(Vec<u8>, Replay), String> {
+        let rom = std::fs::read(&self.rom).map_err(|e| format!("couldn't read {}: {e}", self.rom.display()))?;
+        let inputs = std::fs::read_to_string(&self.inputs).map_err(|e| format!("couldn't read {}: {e}", self.inputs.display()))?;
+        return Ok((rom, Replay::parse(&inputs)?));
+    }
+}
+
+fn read_hashes(path: &Path) -> Result<Vec<u64>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+    return text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| u64::from_str_radix(line, 16).map_err(|_| format!("invalid hash '{line}' in {}", path.display())))
+        .collect();
+}
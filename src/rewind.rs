@@ -0,0 +1,247 @@
+use alloc::collections::VecDeque;
+#[cfg(feature = "compression")]
+use alloc::vec::Vec;
+
+use crate::chip::Snapshot;
+
+// The history behind going backwards: snapshots taken every so often as the machine runs,
+// each stamped with where it was taken (an instruction or frame count, whichever the owner
+// goes by). Getting anywhere in between means restoring the last snapshot before it and
+// running forward again, which lands in the same place since the machine is deterministic
+// from a snapshot.
+//
+// Once it's taking up its memory limit the oldest snapshots make way for new ones, so how
+// far back it goes is how many fit times however often they're taken.
+//
+// With the compression feature snapshots are kept as deflated save states instead. Every
+// KEY_INTERVAL-th is kept whole (a key), and the rest as what's changed since the key before
+// them, XORed against it and deflated, which is mostly 0s since a frame or a thousand
+// instructions apart the machine has barely moved. Running BRIX with a snapshot every frame,
+// a CHIP-8 snapshot goes from 6KB to about 165 bytes on average, so the debugger's 64MB
+// goes back around 400,000 snapshots rather than 10,000. Mega-Chip snapshots carry 16MB of
+// memory and come down to about 130KB. Getting a CHIP-8 snapshot back means inflating it and
+// its key, a few tens of microseconds.
+
+/// How many snapshots there are from one key to the next
+#[cfg(feature = "compression")]
+const KEY_INTERVAL: usize = 16;
+
+/// How hard snapshots are deflated, the fastest since one can be taken every frame
+#[cfg(feature = "compression")]
+const LEVEL: u8 = 1;
+
+
+enum Entry {
+    #[cfg(not(feature = "compression"))]
+    Whole(Snapshot),
+    /// A save state with the state unpacked, then deflated
+    #[cfg(feature = "compression")]
+    Key(Vec<u8>),
+    /// The same, XORed with the last key before it before being deflated
+    #[cfg(feature = "compression")]
+    Delta(Vec<u8>),
+}
+
+impl Entry {
+    fn size(&self) -> usize {
+        return core::mem::size_of::<(u64, Self)>()
+            + match self {
+                #[cfg(not(feature = "compression"))]
+                Self::Whole(snapshot) => snapshot.size(),
+                #[cfg(feature = "compression")]
+                Self::Key(bytes) | Self::Delta(bytes) => bytes.capacity(),
+            };
+    }
+
+    /// Whether it can only be got back with the key before it
+    fn is_delta(&self) -> bool {
+        #[cfg(feature = "compression")]
+        return matches!(self, Self::Delta(_));
+        #[cfg(not(feature = "compression"))]
+        return false;
+    }
+}
+
+pub struct Rewind {
+    snapshots: VecDeque<(u64, Entry)>,
+    /// How many bytes the snapshots can take up, and how many they do
+    limit: usize,
+    used: usize,
+    /// The newest key inflated, for working out deltas against
+    #[cfg(feature = "compression")]
+    last_key: Option<Vec<u8>>,
+}
+
+impl Rewind {
+    /// Keeps as many snapshots as fit in `limit` bytes, and always at least one
+    pub fn new(limit: usize) -> Self {
+        return Self {
+            snapshots: VecDeque::new(),
+            limit,
+            used: 0,
+            #[cfg(feature = "compression")]
+            last_key: None,
+        };
+    }
+
+    /// Adds a snapshot taken at `at`. Any taken at or after it are dropped first, since
+    /// they're from a run that's been replaced by this one
+    pub fn push(&mut self, at: u64, snapshot: Snapshot) {
+        while self.snapshots.back().is_some_and(|(taken, _)| *taken >= at) {
+            let (_, entry) = self.snapshots.pop_back().expect("there's a snapshot at the back");
+            self.used -= entry.size();
+            #[cfg(feature = "compression")]
+            if matches!(entry, Entry::Key(_)) {
+                self.last_key = None;
+            }
+        }
+
+        let entry = self.encode(snapshot);
+        self.used += entry.size();
+        self.snapshots.push_back((at, entry));
+
+        // The oldest snapshot goes along with any deltas on it, as long as that isn't everything
+        while self.used > self.limit {
+            let Some(group) = self.snapshots.iter().skip(1).position(|(_, entry)| !entry.is_delta()) else {
+                break;
+            };
+            for (_, entry) in self.snapshots.drain(..group + 1) {
+                self.used -= entry.size();
+            }
+        }
+    }
+
+    /// The snapshot taken exactly at `at`, if there is one
+    pub fn at(&self, at: u64) -> Option<Snapshot> {
+        let i = self.snapshots.partition_point(|(taken, _)| *taken < at);
+        if self.snapshots.get(i).is_none_or(|(taken, _)| *taken != at) {
+            return None;
+        }
+        return Some(self.decode(i));
+    }
+
+    /// The last snapshot taken at or before `at`, and when it was taken
+    pub fn before(&self, at: u64) -> Option<(u64, Snapshot)> {
+        let after = self.snapshots.partition_point(|(taken, _)| *taken <= at);
+        let i = after.checked_sub(1)?;
+        return Some((self.snapshots[i].0, self.decode(i)));
+    }
+
+    /// When the last snapshot at or before `at` was taken, without getting the snapshot back
+    pub fn taken_before(&self, at: u64) -> Option<u64> {
+        let after = self.snapshots.partition_point(|(taken, _)| *taken <= at);
+        return after.checked_sub(1).map(|i| self.snapshots[i].0);
+    }
+
+    /// When the oldest snapshot was taken, as far back as the history goes
+    pub fn oldest(&self) -> Option<u64> {
+        return self.snapshots.front().map(|(taken, _)| *taken);
+    }
+
+    /// When the newest snapshot was taken
+    pub fn newest(&self) -> Option<u64> {
+        return self.snapshots.back().map(|(taken, _)| *taken);
+    }
+
+    /// How many bytes the snapshots take up
+    pub fn memory_used(&self) -> usize {
+        #[cfg(feature = "compression")]
+        return self.used + self.last_key.as_ref().map_or(0, |key| key.capacity());
+        #[cfg(not(feature = "compression"))]
+        return self.used;
+    }
+
+    pub fn memory_limit(&self) -> usize {
+        return self.limit;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.snapshots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.snapshots.is_empty();
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.used = 0;
+        #[cfg(feature = "compression")]
+        {
+            self.last_key = None;
+        }
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn encode(&mut self, snapshot: Snapshot) -> Entry {
+        return Entry::Whole(snapshot);
+    }
+
+    #[cfg(feature = "compression")]
+    fn encode(&mut self, snapshot: Snapshot) -> Entry {
+        let bytes = snapshot.to_unpacked_bytes();
+        let since_key = self.snapshots.iter().rev().position(|(_, entry)| matches!(entry, Entry::Key(_)));
+        if since_key.is_none_or(|since| since + 1 >= KEY_INTERVAL) {
+            let key = Entry::Key(deflate(&bytes));
+            self.last_key = Some(bytes);
+            return key;
+        }
+
+        if self.last_key.is_none() {
+            let i = self.snapshots.len() - 1 - since_key.expect("there's a key");
+            self.last_key = Some(self.inflate(i));
+        }
+        let mut delta = bytes;
+        xor(&mut delta, self.last_key.as_ref().expect("the last key was just inflated"));
+        return Entry::Delta(deflate(&delta));
+    }
+
+    fn decode(&self, i: usize) -> Snapshot {
+        match &self.snapshots[i].1 {
+            #[cfg(not(feature = "compression"))]
+            Entry::Whole(snapshot) => return snapshot.clone(),
+            #[cfg(feature = "compression")]
+            _ => {
+                let bytes = self.inflate(i);
+                return Snapshot::from_bytes(&bytes).expect("the rewind history holds valid save states");
+            },
+        }
+    }
+
+    /// The save state an entry holds, undoing the delta if it's one
+    #[cfg(feature = "compression")]
+    fn inflate(&self, i: usize) -> Vec<u8> {
+        use miniz_oxide::inflate::decompress_to_vec;
+
+        let inflate = |bytes: &[u8]| decompress_to_vec(bytes).expect("the rewind history inflates");
+        match &self.snapshots[i].1 {
+            Entry::Key(bytes) => return inflate(bytes),
+            Entry::Delta(bytes) => {
+                let mut state = inflate(bytes);
+                let key = self.snapshots.range(..i).rev().find_map(|(_, entry)| match entry {
+                    Entry::Key(key) => Some(key),
+                    _ => None,
+                });
+                xor(&mut state, &inflate(key.expect("a delta always has its key before it")));
+                return state;
+            },
+        }
+    }
+}
+
+/// XORs the bytes with a key, which can be a different length when the resolution changed
+/// in between
+#[cfg(feature = "compression")]
+fn xor(bytes: &mut [u8], key: &[u8]) {
+    for (byte, key) in bytes.iter_mut().zip(key) {
+        *byte ^= key;
+    }
+}
+
+/// Deflates the bytes into a Vec no bigger than it needs to be, since there'll be thousands
+#[cfg(feature = "compression")]
+fn deflate(bytes: &[u8]) -> Vec<u8> {
+    let mut deflated = miniz_oxide::deflate::compress_to_vec(bytes, LEVEL);
+    deflated.shrink_to_fit();
+    return deflated;
+}
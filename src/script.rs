@@ -0,0 +1,183 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use rhai::{CallFnOptions, Dynamic, Engine, Scope, AST};
+
+use crate::chip::Chip8;
+
+// Scripts are written in rhai (https://rhai.rs) and can define any of these hooks:
+//
+//   fn on_frame(frame) { }            called after every frame (60 times a second)
+//   fn on_instruction(pc, opcode) { } called after every instruction, pc is where it was read from
+//   fn on_key(key, pressed) { }       called when a key on the keypad is pressed or released,
+//                                     whether by the player or by a hook's press and release
+//
+// Inside a hook the script can use these functions to inspect and change the machine:
+//
+//   reg(x), set_reg(x, value)     read/write Vx
+//   peek(addr), poke(addr, value) read/write a byte of memory
+//   pc(), index(), delay(), sound() read PC, I and the timers
+//   press(key), release(key)      hold down or let go of a key on the keypad
+//   stop()                        end the run after the current hook
+//
+// A script that throws (e.g. `throw "lives hit zero"`) stops the run with that error,
+// which is how test assertions are written.
+
+
+/// Runs the hooks of a loaded script against a Chip8
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    machine: Rc<RefCell<Chip8>>,
+    stopped: Rc<Cell<bool>>,
+    /// The keypad as on_key last told the script it was
+    keys: [bool; 16],
+    has_on_frame: bool,
+    has_on_instruction: bool,
+    has_on_key: bool,
+}
+
+impl ScriptHost {
+    /// Compiles the script and runs its top level statements once
+    pub fn new(source: &str) -> Result<Self, String> {
+        let mut engine = Engine::new();
+        // Hooks are run with the machine swapped in here, see ScriptHost::call
+        let machine = Rc::new(RefCell::new(Chip8::new(false)));
+        let stopped = Rc::new(Cell::new(false));
+
+        let m = machine.clone();
+        engine.register_fn("reg", move |x: i64| m.borrow().registers()[(x & 0xF) as usize] as i64);
+        let m = machine.clone();
+        engine.register_fn("set_reg", move |x: i64, value: i64| m.borrow_mut().set_register(x as u8, value as u8));
+        let m = machine.clone();
+        engine.register_fn("peek", move |addr: i64| {
+            m.borrow().read_mem(addr as u32, 1).first().copied().unwrap_or(0) as i64
+        });
+        let m = machine.clone();
+        engine.register_fn("poke", move |addr: i64, value: i64| {
+            m.borrow_mut().write_mem(addr as u32, &[value as u8]);
+        });
+        let m = machine.clone();
+        engine.register_fn("pc", move || m.borrow().pc() as i64);
+        let m = machine.clone();
+        engine.register_fn("index", move || m.borrow().ar() as i64);
+        let m = machine.clone();
+        engine.register_fn("delay", move || m.borrow().delay() as i64);
+        let m = machine.clone();
+        engine.register_fn("sound", move || m.borrow().sound() as i64);
+        let m = machine.clone();
+        engine.register_fn("press", move |key: i64| m.borrow_mut().set_key(key as u8, true));
+        let m = machine.clone();
+        engine.register_fn("release", move |key: i64| m.borrow_mut().set_key(key as u8, false));
+        let s = stopped.clone();
+        engine.register_fn("stop", move || s.set(true));
+
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast).map_err(|e| e.to_string())?;
+
+        let has = |name: &str| ast.iter_functions().any(|f| f.name == name);
+        let has_on_frame = has("on_frame");
+        let has_on_instruction = has("on_instruction");
+        let has_on_key = has("on_key");
+
+        return Ok(Self {
+            engine,
+            ast,
+            scope,
+            machine,
+            stopped,
+            keys: [false; 16],
+            has_on_frame,
+            has_on_instruction,
+            has_on_key,
+        });
+    }
+
+    /// Whether the script has called stop()
+    pub fn stopped(&self) -> bool {
+        return self.stopped.get();
+    }
+
+    pub fn on_frame(&mut self, chip: &mut Chip8, frame: u64) -> Result<(), String> {
+        if !self.has_on_frame {
+            return Ok(());
+        }
+        return self.call(chip, "on_frame", vec![Dynamic::from(frame as i64)]);
+    }
+
+    pub fn on_instruction(&mut self, chip: &mut Chip8, pc: u16, opcode: u16) -> Result<(), String> {
+        if !self.has_on_instruction {
+            return Ok(());
+        }
+        return self.call(chip, "on_instruction", vec![Dynamic::from(pc as i64), Dynamic::from(opcode as i64)]);
+    }
+
+    pub fn on_key(&mut self, chip: &mut Chip8, key: u8, pressed: bool) -> Result<(), String> {
+        if !self.has_on_key {
+            return Ok(());
+        }
+        return self.call(chip, "on_key", vec![Dynamic::from(key as i64), Dynamic::from(pressed)]);
+    }
+
+    /// Calls on_key for every key that's been pressed or released since it was last called,
+    /// for runners to call after setting the keys and after every other hook
+    pub fn keys_changed(&mut self, chip: &mut Chip8) -> Result<(), String> {
+        let keys = *chip.state().keys;
+        for (key, pressed) in keys.into_iter().enumerate() {
+            if pressed != self.keys[key] {
+                self.keys[key] = pressed;
+                self.on_key(chip, key as u8, pressed)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// The registered functions can only reach the machine through the shared cell, so the
+    /// caller's Chip8 is swapped into it for the duration of the hook and swapped back after
+    fn call(&mut self, chip: &mut Chip8, name: &str, args: Vec<Dynamic>) -> Result<(), String> {
+        std::mem::swap(chip, &mut *self.machine.borrow_mut());
+
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(false);
+        let result = self
+            .engine
+            .call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args);
+
+        std::mem::swap(chip, &mut *self.machine.borrow_mut());
+
+        return result.map(|_| ()).map_err(|e| format!("{name}: {e}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn on_key_runs_when_a_key_goes_down_or_up() {
+        let source = r#"
+            fn on_key(key, pressed) { poke(0x300 + key, if pressed { 1 } else { 2 }); }
+            fn on_frame(frame) { if frame == 1 { press(7); } }
+        "#;
+        let mut host = ScriptHost::new(source).expect("the script compiles");
+        let mut chip = Chip8::new(false);
+
+        chip.set_key(5, true);
+        host.keys_changed(&mut chip).unwrap();
+        assert_eq!(chip.read_mem(0x305, 1), &[1]);
+        chip.set_key(5, false);
+        host.keys_changed(&mut chip).unwrap();
+        assert_eq!(chip.read_mem(0x305, 1), &[2]);
+
+        // Nothing's changed, so nothing's called
+        chip.write_mem(0x305, &[0]);
+        host.keys_changed(&mut chip).unwrap();
+        assert_eq!(chip.read_mem(0x305, 1), &[0]);
+
+        // A key a hook presses counts too
+        host.on_frame(&mut chip, 1).unwrap();
+        host.keys_changed(&mut chip).unwrap();
+        assert_eq!(chip.read_mem(0x307, 1), &[1]);
+    }
+}
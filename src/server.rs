@@ -0,0 +1,209 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::chip::Chip8;
+use crate::platform::Platform;
+
+// A JSON-RPC 2.0 server for controlling the machine remotely, from a browser control panel
+// or tests written in any language. Requests and responses are one JSON object a line over
+// TCP, and connections are served one at a time against the same machine:
+//
+//   load         {rom, platform?}      loads the base64 rom into a fresh machine
+//   step         {n?}                  runs n instructions (default 1)
+//   frame        {n?}                  runs n frames of instructions and timer ticks
+//   key          {key, pressed}        presses or releases keypad key 0-15
+//   registers    {}                    V0-VF, I, PC, SP, DT, ST and the call stack
+//   memory       {addr, len}           len bytes from addr, as numbers
+//   poke         {addr, bytes}         writes the bytes (numbers) from addr
+//   framebuffer  {format?}             the screen as "base64" raw pixels (default), a
+//                                      byte each row by row, or as a base64 "png"
+//
+// e.g. {"jsonrpc": "2.0", "id": 1, "method": "step", "params": {"n": 10}}
+
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The machine stopped with a Chip8Error
+const MACHINE_ERROR: i64 = -32000;
+
+/// An error response's code and message
+type RpcError = (i64, String);
+
+
+pub struct Server {
+    chip: Chip8,
+}
+
+impl Server {
+    pub fn new(chip: Chip8) -> Self {
+        return Self { chip };
+    }
+
+    pub fn chip(&self) -> &Chip8 {
+        return &self.chip;
+    }
+
+    /// Listens on addr and serves each connection in turn until the listener fails
+    pub fn serve(&mut self, addr: impl ToSocketAddrs) -> std::io::Result<()> {
+        let listener = TcpListener::bind(addr)?;
+        for stream in listener.incoming() {
+            let stream = stream?;
+            // A connection that drops mid request is the client's problem, not the server's
+            let _ = self.serve_connection(stream);
+        }
+        return Ok(());
+    }
+
+    fn serve_connection(&mut self, stream: std::net::TcpStream) -> std::io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                writeln!(writer, "{response}")?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Handles one JSON-RPC request, returning the response. Notifications (requests
+    /// without an id) get no response
+    pub fn handle(&mut self, request: &str) -> Option<String> {
+        let request: Value = match serde_json::from_str(request) {
+            Ok(request) => request,
+            Err(e) => return Some(response(Value::Null, Err((PARSE_ERROR, e.to_string())))),
+        };
+        let id = request.get("id").cloned();
+        let result = match request.get("method").and_then(Value::as_str) {
+            Some(method) => self.call(method, request.get("params").unwrap_or(&Value::Null)),
+            None => Err((INVALID_REQUEST, "the request has no method".to_string())),
+        };
+        return id.map(|id| response(id, result));
+    }
+
+    fn call(&mut self, method: &str, params: &Value) -> Result<Value, RpcError> {
+        return match method {
+            "load" => {
+                let rom = BASE64.decode(str_param(params, "rom")?).map_err(|e| invalid(format!("rom: {e}")))?;
+                let platform = match params.get("platform").and_then(Value::as_str) {
+                    Some(name) => Platform::from_name(name).ok_or_else(|| invalid(format!("unknown platform '{name}'")))?,
+                    None => self.chip.platform(),
+                };
+                self.chip = Chip8::with_platform(platform, false);
+                self.chip.load_rom_bytes(&rom);
+                Ok(Value::Null)
+            },
+            "step" => {
+                for _ in 0..optional_u64_param(params, "n", 1)? {
+                    self.chip.execute().map_err(|e| (MACHINE_ERROR, e.to_string()))?;
+                }
+                Ok(json!({ "pc": self.chip.pc() }))
+            },
+            "frame" => {
+                for _ in 0..optional_u64_param(params, "n", 1)? {
+                    self.chip.run_frame().map_err(|e| (MACHINE_ERROR, e.to_string()))?;
+                }
+                Ok(json!({ "pc": self.chip.pc() }))
+            },
+            "key" => {
+                let key = u64_param(params, "key")?;
+                if key > 0xF {
+                    return Err(invalid(format!("key {key} isn't 0-15")));
+                }
+                self.chip.set_key(key as u8, bool_param(params, "pressed")?);
+                Ok(Value::Null)
+            },
+            "registers" => Ok(json!({
+                "v": self.chip.registers(),
+                "i": self.chip.ar(),
+                "pc": self.chip.pc(),
+                "sp": self.chip.sp(),
+                "dt": self.chip.delay(),
+                "st": self.chip.sound(),
+                "stack": self.chip.call_stack(),
+            })),
+            "memory" => {
+                let addr = u64_param(params, "addr")? as u32;
+                let len = u64_param(params, "len")? as usize;
+                Ok(json!(self.chip.read_mem(addr, len)))
+            },
+            "poke" => {
+                let addr = u64_param(params, "addr")? as u32;
+                let bytes = params
+                    .get("bytes")
+                    .and_then(Value::as_array)
+                    .and_then(|bytes| bytes.iter().map(|b| u8::try_from(b.as_u64()?).ok()).collect::<Option<Vec<u8>>>())
+                    .ok_or_else(|| invalid("bytes must be an array of bytes".to_string()))?;
+                Ok(json!(self.chip.write_mem(addr, &bytes)))
+            },
+            "framebuffer" => {
+                let framebuffer = self.chip.framebuffer();
+                let (width, height) = (framebuffer.width(), framebuffer.height());
+                let data = match params.get("format").and_then(Value::as_str).unwrap_or("base64") {
+                    "base64" => BASE64.encode(framebuffer.to_bytes()),
+                    "png" => BASE64.encode(png(&self.chip).map_err(|e| (MACHINE_ERROR, e.to_string()))?),
+                    format => return Err(invalid(format!("unknown format '{format}', expected base64 or png"))),
+                };
+                Ok(json!({ "width": width, "height": height, "data": data }))
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("no method '{method}'"))),
+        };
+    }
+}
+
+/// Encodes the screen as an RGB PNG
+pub fn png(chip: &Chip8) -> Result<Vec<u8>, png::EncodingError> {
+    let framebuffer = chip.framebuffer();
+    let mut rgb = Vec::with_capacity(framebuffer.width() * framebuffer.height() * 3);
+    for pixel in chip.screen_rgb() {
+        rgb.extend([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, framebuffer.width() as u32, framebuffer.height() as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&rgb)?;
+    writer.finish()?;
+    return Ok(png);
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> String {
+    let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    };
+    return response.to_string();
+}
+
+fn invalid(message: String) -> RpcError {
+    return (INVALID_PARAMS, message);
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    return params.get(name).and_then(Value::as_str).ok_or_else(|| invalid(format!("{name} is missing")));
+}
+
+fn u64_param(params: &Value, name: &str) -> Result<u64, RpcError> {
+    return params.get(name).and_then(Value::as_u64).ok_or_else(|| invalid(format!("{name} is missing")));
+}
+
+fn bool_param(params: &Value, name: &str) -> Result<bool, RpcError> {
+    return params.get(name).and_then(Value::as_bool).ok_or_else(|| invalid(format!("{name} is missing")));
+}
+
+fn optional_u64_param(params: &Value, name: &str, default: u64) -> Result<u64, RpcError> {
+    return match params.get(name) {
+        None => Ok(default),
+        Some(value) => value.as_u64().ok_or_else(|| invalid(format!("{name} must be a number"))),
+    };
+}
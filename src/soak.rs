@@ -0,0 +1,131 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::isa;
+use crate::trace::TraceEntry;
+
+// Soak testing (chip8 soak): running a rom headless for a long time with nothing pressed, to
+// find the ones the interpreter can't run. A run that comes to grief is written up as a
+// report for triage, with the machine as it was, the last TRACE_LINES instructions leading
+// up to it in the same format as trace.rs, and a save state to pick it up from in the
+// debugger or compare with chip8 diff.
+//
+// A run comes to grief by the interpreter returning an error or panicking, or by halting,
+// jumping to the instruction it's on. Halting is how plenty of roms, test roms especially,
+// finish, so it isn't a failure but it's reported since it's also how some of them give up.
+// SUPER-CHIP's exit (00FD) ends the run the same way.
+// A rom waiting for a key when the run ends has run fine, it's just never got past its menu.
+
+
+/// How many instructions a report shows leading up to the end of the run
+pub const TRACE_LINES: usize = 100;
+
+/// How a run ended
+pub enum Ending {
+    /// It ran for every frame
+    Ran,
+    /// It jumped to the instruction it was on
+    Halted,
+    /// It ran SUPER-CHIP's exit
+    Exited,
+    Failed(Chip8Error),
+    /// The interpreter panicked, with its message
+    Panicked(String),
+}
+
+pub struct Soak {
+    /// How many frames ran in full
+    pub frames: usize,
+    pub ending: Ending,
+    /// The state after each of the last TRACE_LINES instructions, oldest first
+    pub trace: VecDeque<TraceEntry>,
+}
+
+/// Runs the rom for up to `frames` frames, or until it halts or fails
+pub fn run(chip: &mut Chip8, frames: usize) -> Soak {
+    let mut soak = Soak { frames: 0, ending: Ending::Ran, trace: VecDeque::with_capacity(TRACE_LINES) };
+    // The trace so far is what's wanted most when the interpreter panics, so it's kept outside
+    let result = panic::catch_unwind(AssertUnwindSafe(|| soak.run(chip, frames)));
+    if let Err(panic) = result {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        soak.ending = Ending::Panicked(message);
+    }
+    return soak;
+}
+
+impl Soak {
+    fn run(&mut self, chip: &mut Chip8, frames: usize) {
+        while self.frames < frames {
+            for _ in 0..chip.cycles_per_frame() {
+                let pc = chip.pc();
+                if let Err(e) = chip.execute() {
+                    self.ending = Ending::Failed(e);
+                    return;
+                }
+                if self.trace.len() == TRACE_LINES {
+                    self.trace.pop_front();
+                }
+                self.trace.push_back(TraceEntry::capture(chip));
+                if chip.pc() == pc && chip.opcode() >> 12 == 0x1 {
+                    self.ending = Ending::Halted;
+                    return;
+                }
+                if chip.exit_requested() {
+                    self.ending = Ending::Exited;
+                    return;
+                }
+            }
+            chip.tick_timers();
+            self.frames += 1;
+        }
+    }
+
+    /// Whether the run needs looking into
+    pub fn failed(&self) -> bool {
+        return matches!(self.ending, Ending::Failed(_) | Ending::Panicked(_));
+    }
+
+    /// One line on how the run went
+    pub fn summary(&self) -> String {
+        return match &self.ending {
+            Ending::Ran => format!("ran {} frames", self.frames),
+            Ending::Halted => format!("halted in frame {}", self.frames),
+            Ending::Exited => format!("exited in frame {}", self.frames),
+            Ending::Failed(e) => format!("FAILED in frame {}, {}: {e}", self.frames, e.kind()),
+            Ending::Panicked(message) => format!("FAILED in frame {}, the interpreter panicked: {message}", self.frames),
+        };
+    }
+
+    /// The report on a run, with the machine as the run left it
+    pub fn report(&self, rom: &str, chip: &Chip8) -> String {
+        let mut report = String::new();
+        writeln!(report, "Rom: {rom}").unwrap();
+        writeln!(report, "Platform: {}, {} instructions a frame", chip.platform().name(), chip.cycles_per_frame()).unwrap();
+        writeln!(report, "Ending: {}", self.summary()).unwrap();
+        let kind = match &self.ending {
+            Ending::Ran => "none",
+            Ending::Halted => "halt",
+            Ending::Exited => "exit",
+            Ending::Failed(e) => e.kind(),
+            Ending::Panicked(_) => "panic",
+        };
+        writeln!(report, "Error: {kind}").unwrap();
+        let opcode = chip.read_mem(chip.pc() as u32, 2);
+        let opcode = opcode.iter().fold(0, |opcode, byte| opcode << 8 | *byte as u16);
+        writeln!(report, "PC: {:04X}, {opcode:04X} {}", chip.pc(), isa::disassemble(opcode, chip.platform())).unwrap();
+
+        writeln!(report, "\nMachine:\n{}", chip.state()).unwrap();
+        writeln!(report, "Last {} instructions, oldest first, with the state after each:", self.trace.len()).unwrap();
+        for entry in &self.trace {
+            writeln!(report, "{entry}").unwrap();
+        }
+        return report;
+    }
+}
@@ -0,0 +1,176 @@
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use tungstenite::{Message, WebSocket};
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+
+// Runs a machine headless and streams its screen to anyone watching over a WebSocket, who
+// can all play it too ("Twitch plays CHIP-8"). Opening the address in a browser gets a page
+// that does both, see stream.html.
+//
+// Each frame the screen changes, every viewer is sent a binary message of the width and
+// height as little endian u16s followed by the pixels a bit each, row by row and most
+// significant bit first. Viewers press keys with text messages of "down" or "up" and the
+// key in hex, e.g. "down A". A key is held while any viewer holds it.
+
+const PAGE: &str = include_str!("stream.html");
+
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+
+struct Viewer {
+    socket: WebSocket<TcpStream>,
+    keys: [bool; 16],
+}
+
+/// Streams the machine to viewers connecting to addr, running it at 60 frames a second
+/// until the listener fails. A rom that stops with an error stays on its last frame
+pub fn serve(chip: &mut Chip8, addr: impl ToSocketAddrs) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let viewers = accept(listener);
+
+    let mut connected: Vec<Viewer> = Vec::new();
+    let mut last_frame: Vec<u8> = Vec::new();
+    let mut error: Option<Chip8Error> = None;
+    let mut next_frame = Instant::now();
+    loop {
+        // Newcomers get the current screen straight away rather than waiting for a change
+        for socket in viewers.try_iter() {
+            let mut viewer = Viewer { socket, keys: [false; 16] };
+            if send(&mut viewer, &last_frame) {
+                connected.push(viewer);
+            }
+        }
+
+        connected.retain_mut(read_keys);
+        for key in 0..16 {
+            chip.set_key(key as u8, connected.iter().any(|viewer| viewer.keys[key]));
+        }
+
+        if error.is_none() {
+            error = chip.run_frame().err();
+            if let Some(e) = &error {
+                eprintln!("{e}");
+            }
+        }
+
+        let frame = encode(chip);
+        if frame != last_frame {
+            connected.retain_mut(|viewer| send(viewer, &frame));
+            last_frame = frame;
+        }
+
+        // Fall back in step rather than racing to catch up after a stall
+        next_frame += FRAME;
+        let now = Instant::now();
+        if next_frame < now {
+            next_frame = now;
+        }
+        std::thread::sleep(next_frame - now);
+    }
+}
+
+/// Accepts connections on a thread of their own, so a slow handshake doesn't hold up the
+/// machine. Browsers asking for the page are sent it, and WebSockets are handed back
+fn accept(listener: TcpListener) -> Receiver<WebSocket<TcpStream>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if !is_websocket(&stream) {
+                send_page(stream);
+                continue;
+            }
+            let Ok(socket) = tungstenite::accept(stream) else {
+                continue;
+            };
+            // The machine's loop polls every viewer each frame, so none can block it
+            if socket.get_ref().set_nonblocking(true).is_err() {
+                continue;
+            }
+            if sender.send(socket).is_err() {
+                return;
+            }
+        }
+    });
+    return receiver;
+}
+
+/// Whether the request waiting on stream is for a WebSocket, without reading it
+fn is_websocket(stream: &TcpStream) -> bool {
+    let mut request = [0; 2048];
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    let Ok(len) = stream.peek(&mut request) else {
+        return false;
+    };
+    return String::from_utf8_lossy(&request[..len]).to_ascii_lowercase().contains("upgrade: websocket");
+}
+
+fn send_page(mut stream: TcpStream) {
+    use std::io::{Read, Write};
+
+    // The request has to be read before answering it, or closing the socket resets it
+    let mut request = [0; 2048];
+    let _ = stream.read(&mut request);
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{PAGE}",
+        PAGE.len()
+    );
+}
+
+/// Reads whatever the viewer has sent, returning false once they've gone
+fn read_keys(viewer: &mut Viewer) -> bool {
+    loop {
+        match viewer.socket.read() {
+            Ok(Message::Text(text)) => {
+                let mut words = text.split_whitespace();
+                let pressed = match words.next() {
+                    Some("down") => true,
+                    Some("up") => false,
+                    _ => continue,
+                };
+                if let Some(key) = words.next().and_then(|key| u8::from_str_radix(key, 16).ok()).filter(|key| *key < 16) {
+                    viewer.keys[key as usize] = pressed;
+                }
+            },
+            Ok(Message::Close(_)) => return false,
+            Ok(_) => {},
+            Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => return true,
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Sends a frame, returning false if the viewer has gone
+fn send(viewer: &mut Viewer, frame: &[u8]) -> bool {
+    if frame.is_empty() {
+        return true;
+    }
+    return match viewer.socket.send(Message::from(frame.to_vec())) {
+        Ok(()) => true,
+        // Left queued in the socket to go out with the next one
+        Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => true,
+        Err(_) => false,
+    };
+}
+
+/// The screen as a frame message, see the top of the file
+fn encode(chip: &Chip8) -> Vec<u8> {
+    let framebuffer = chip.framebuffer();
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let mut frame = Vec::with_capacity(4 + width * height / 8);
+    frame.extend((width as u16).to_le_bytes());
+    frame.extend((height as u16).to_le_bytes());
+    let pixels = framebuffer.to_bytes();
+    for row in pixels.chunks(width) {
+        for byte in row.chunks(8) {
+            let bits = byte.iter().enumerate().fold(0u8, |bits, (i, pixel)| bits | ((*pixel != 0) as u8) << (7 - i));
+            frame.push(bits);
+        }
+    }
+    return frame;
+}
@@ -0,0 +1,113 @@
+// Symbol files give names to addresses in a rom so backtraces and the debugger can show
+// `draw_ball+0x4` instead of a bare address. One symbol a line, hex address then name:
+//
+//   # comments and blank lines are ignored
+//   0x200 main
+//   2F6   draw_score
+//
+// A later line for an address that's already named renames it. Octo's `:label` exports can
+// be turned into this with a quick search and replace.
+
+
+/// Names for addresses, sorted by address so the closest one below any address can be found
+pub struct SymbolTable {
+    symbols: Vec<(u16, String)>,
+}
+
+impl SymbolTable {
+    /// Parses the contents of a symbol file
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut symbols = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (addr, name) = line
+                .split_once(char::is_whitespace)
+                .ok_or(format!("line {}: expected '<addr> <name>'", i + 1))?;
+            let digits = addr.trim_start_matches("0x").trim_start_matches("0X");
+            let addr = u16::from_str_radix(digits, 16)
+                .map_err(|_| format!("line {}: invalid address '{addr}'", i + 1))?;
+
+            symbols.push((addr, name.trim().to_string()));
+        }
+
+        symbols.sort_by_key(|(addr, _)| *addr);
+
+        return Ok(Self { symbols });
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        return Self::parse(&text);
+    }
+
+    /// Finds the symbol at or closest below addr, returning its name and how far past it addr is
+    pub fn lookup(&self, addr: u16) -> Option<(&str, u16)> {
+        let i = self.symbols.partition_point(|(a, _)| *a <= addr);
+        if i == 0 {
+            return None;
+        }
+        let (symbol_addr, name) = &self.symbols[i - 1];
+        return Some((name.as_str(), addr - symbol_addr));
+    }
+
+    /// Formats an address with the symbol it falls in, e.g. 02FA <draw_score+0x4>
+    pub fn describe(&self, addr: u16) -> String {
+        return match self.lookup(addr) {
+            Some((name, 0)) => format!("{addr:04X} <{name}>"),
+            Some((name, offset)) => format!("{addr:04X} <{name}+0x{offset:X}>"),
+            None => format!("{addr:04X}"),
+        };
+    }
+}
+
+/// Formats a backtrace from the current PC and the call stack (return addresses, outermost
+/// first as returned by Chip8::call_stack), innermost frame first. Each caller is shown at
+/// the CALL instruction, two bytes before the address it returns to
+pub fn backtrace(pc: u16, call_stack: &[u16], symbols: Option<&SymbolTable>) -> String {
+    let describe = |addr: u16| match symbols {
+        Some(symbols) => symbols.describe(addr),
+        None => format!("{addr:04X}"),
+    };
+
+    let mut trace = format!("#0  {}\n", describe(pc));
+    for (i, ret) in call_stack.iter().rev().enumerate() {
+        trace.push_str(&format!("#{:<2} {}\n", i + 1, describe(ret.wrapping_sub(2))));
+    }
+
+    return trace;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_symbol_file() {
+        let symbols = SymbolTable::parse("# pong\n\n2F6   draw_score\n0x200 main\n  0X2A0\tdraw_ball  \n").unwrap();
+        assert_eq!(symbols.lookup(0x1FE), None);
+        assert_eq!(symbols.lookup(0x200), Some(("main", 0)));
+        assert_eq!(symbols.lookup(0x2A4), Some(("draw_ball", 4)));
+        assert_eq!(symbols.describe(0x2FA), "02FA <draw_score+0x4>");
+        assert_eq!(SymbolTable::parse("").unwrap().describe(0x200), "0200");
+    }
+
+    #[test]
+    fn a_later_line_for_the_same_address_wins() {
+        let symbols = SymbolTable::parse("200 start\n2F6 draw_score\n200 main\n2F6 draw_score").unwrap();
+        assert_eq!(symbols.lookup(0x202), Some(("main", 2)));
+        assert_eq!(symbols.lookup(0x2F6), Some(("draw_score", 0)));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        assert_eq!(SymbolTable::parse("200 main\nloop").err(), Some("line 2: expected '<addr> <name>'".to_string()));
+        assert_eq!(SymbolTable::parse("2G0 main").err(), Some("line 1: invalid address '2G0'".to_string()));
+        assert!(SymbolTable::parse("10000 past_memory").is_err());
+        assert!(SymbolTable::parse("-200 main").is_err());
+    }
+}
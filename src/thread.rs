@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+use crate::framebuffer::Framebuffer;
+
+// The threading model: a machine is Send and Sync but has no locking of its own, so one
+// thread drives it and everything else talks to that thread. EmulationThread moves a
+// machine onto a thread that runs it at 60 frames a second, so a UI thread that stalls
+// (resizing, a slow vsync, a modal dialog) never slows the game down.
+//
+// The UI sends keys over a channel and gets whole frames back from the machine's frame_rx,
+// each one a copy of the screen taken between frames, so it never sees one half drawn.
+// Frames the UI is too slow to take are dropped rather than queueing up behind it.
+
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+
+enum Command {
+    Key(u8, bool),
+    Pause(bool),
+    Stop,
+}
+
+pub struct EmulationThread {
+    commands: Sender<Command>,
+    frames: Receiver<Framebuffer>,
+    beeping: Arc<AtomicBool>,
+    thread: JoinHandle<(Chip8, Result<(), Chip8Error>)>,
+}
+
+impl EmulationThread {
+    /// Moves the machine onto a thread of its own and starts running it
+    pub fn spawn(mut chip: Chip8) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let frames = chip.frame_rx();
+        let beeping = Arc::new(AtomicBool::new(false));
+
+        let thread_beeping = beeping.clone();
+        let thread = std::thread::spawn(move || run(chip, command_rx, thread_beeping));
+        return Self { commands, frames, beeping, thread };
+    }
+
+    /// The newest frame finished since the last call, if there is one
+    pub fn frame(&self) -> Option<Framebuffer> {
+        return self.frames.try_iter().last();
+    }
+
+    /// Presses or releases one of the 16 keys, from the next frame on
+    pub fn set_key(&self, key: u8, pressed: bool) {
+        let _ = self.commands.send(Command::Key(key, pressed));
+    }
+
+    /// Pauses or resumes the machine
+    pub fn set_paused(&self, paused: bool) {
+        let _ = self.commands.send(Command::Pause(paused));
+    }
+
+    /// Whether the sound timer was running at the end of the last frame
+    pub fn beeping(&self) -> bool {
+        return self.beeping.load(Ordering::Relaxed);
+    }
+
+    /// Whether the machine has stopped, with an error or by the rom exiting
+    pub fn is_finished(&self) -> bool {
+        return self.thread.is_finished();
+    }
+
+    /// Stops the machine and hands it back, with the error it stopped with if it failed
+    pub fn join(self) -> (Chip8, Result<(), Chip8Error>) {
+        let _ = self.commands.send(Command::Stop);
+        return self.thread.join().expect("the emulation thread shouldn't panic");
+    }
+}
+
+fn run(mut chip: Chip8, commands: Receiver<Command>, beeping: Arc<AtomicBool>) -> (Chip8, Result<(), Chip8Error>) {
+    let mut paused = false;
+    let mut next_frame = Instant::now();
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(Command::Key(key, pressed)) => chip.set_key(key, pressed),
+                Ok(Command::Pause(pause)) => paused = pause,
+                // Dropping the EmulationThread stops it as well
+                Ok(Command::Stop) | Err(TryRecvError::Disconnected) => return (chip, Ok(())),
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        if !paused {
+            if let Err(e) = chip.run_frame() {
+                return (chip, Err(e));
+            }
+            if chip.exit_requested() {
+                return (chip, Ok(()));
+            }
+            beeping.store(chip.sound() > 0, Ordering::Relaxed);
+        }
+
+        // Fall back in step rather than racing to catch up after a stall
+        next_frame += FRAME;
+        let now = Instant::now();
+        if next_frame < now {
+            next_frame = now;
+        }
+        std::thread::sleep(next_frame - now);
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::chip::Chip8;
+use crate::rewind::Rewind;
+
+// The gui's timeline: what happened in which frame (sprites drawn, keys pressed, the sound
+// starting, subroutines called) for as far back as its rewind history goes, and a way back to
+// any of those frames exactly as they were.
+//
+// Every SNAPSHOT_INTERVAL-th frame's start is snapshotted (see rewind.rs), and the keys held
+// and the speed at the start of every frame kept, since those are what the player changes as
+// the game runs. Seeking to a frame restores the last snapshot before it and runs forward
+// with the keys and speed each frame had, which lands on the same frame since the machine is
+// deterministic from a snapshot. While it's running forward like that, frames are replayed
+// rather than recorded.
+//
+// Running on live from a frame in the past (or changing the machine by hand anywhere) starts a
+// new history from there: everything recorded after it is dropped, as the rewind history does.
+
+/// How often a frame's start is snapshotted, and how much memory the snapshots can take up
+const SNAPSHOT_INTERVAL: u64 = 10;
+const MEMORY: usize = 32 * 1024 * 1024;
+
+
+/// What kind of thing an event is, for showing some and not others
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Draw,
+    Key,
+    Sound,
+    Call,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 4] = [EventKind::Draw, EventKind::Key, EventKind::Sound, EventKind::Call];
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            EventKind::Draw => "Draws",
+            EventKind::Key => "Keys",
+            EventKind::Sound => "Sound",
+            EventKind::Call => "Calls",
+        };
+    }
+}
+
+/// Something that happened in a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A sprite drawn by the instruction at pc, and whether it hit pixels that were on
+    Draw { pc: u16, collided: bool },
+    /// A key going down
+    Key(u8),
+    /// The sound timer starting, set by the instruction at pc
+    Sound { pc: u16 },
+    /// A subroutine at `to` called from pc
+    Call { pc: u16, to: u16 },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        return match self {
+            Event::Draw { .. } => EventKind::Draw,
+            Event::Key(_) => EventKind::Key,
+            Event::Sound { .. } => EventKind::Sound,
+            Event::Call { .. } => EventKind::Call,
+        };
+    }
+}
+
+/// As the timeline lists them, e.g. "sprite drawn by 0248, colliding"
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            Event::Draw { pc, collided: true } => write!(f, "sprite drawn by {pc:04X}, colliding"),
+            Event::Draw { pc, collided: false } => write!(f, "sprite drawn by {pc:04X}"),
+            Event::Key(key) => write!(f, "key {key:X} pressed"),
+            Event::Sound { pc } => write!(f, "sound started by {pc:04X}"),
+            Event::Call { pc, to } => write!(f, "call to {to:04X} from {pc:04X}"),
+        };
+    }
+}
+
+/// What the player had set at the start of a frame
+#[derive(Debug, Clone, Copy)]
+struct Input {
+    keys: [bool; 16],
+    cycles_per_frame: usize,
+}
+
+/// The events of every frame as far back as the history goes, see the top of the file
+pub struct Timeline {
+    rewind: Rewind,
+    /// The input for every frame from `first` on
+    inputs: VecDeque<Input>,
+    first: u64,
+    /// Every event from `first` on, with its frame, oldest first
+    events: VecDeque<(u64, Event)>,
+    /// The frame the machine's in, and whether it's being recorded rather than replayed
+    frame: u64,
+    live: bool,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        return Self {
+            rewind: Rewind::new(MEMORY),
+            inputs: VecDeque::new(),
+            first: 0,
+            events: VecDeque::new(),
+            frame: 0,
+            live: true,
+        };
+    }
+
+    /// The frame the machine's in
+    pub fn frame(&self) -> u64 {
+        return self.frame;
+    }
+
+    /// The first frame that can be gone back to and the frame after the last one recorded
+    pub fn frames(&self) -> (u64, u64) {
+        return (self.first, self.first + self.inputs.len() as u64);
+    }
+
+    pub fn events(&self) -> &VecDeque<(u64, Event)> {
+        return &self.events;
+    }
+
+    /// Called before each frame runs. A frame that's been recorded is replayed with the keys
+    /// and speed it had, otherwise they're recorded along with the keys that went down
+    pub fn start_frame(&mut self, chip: &mut Chip8) {
+        let (_, end) = self.frames();
+        if self.frame < end {
+            // Coming back through a snapshot puts it back, which changes nothing but the
+            // random number generator, reseeded as it was the first time through
+            if let Some(snapshot) = self.rewind.at(self.frame) {
+                chip.restore(&snapshot);
+            }
+            let input = self.inputs[(self.frame - self.first) as usize];
+            for (key, held) in input.keys.into_iter().enumerate() {
+                chip.set_key(key as u8, held);
+            }
+            chip.set_cycles_per_frame(input.cycles_per_frame);
+            self.live = false;
+            return;
+        }
+
+        self.live = true;
+        if self.frame.is_multiple_of(SNAPSHOT_INTERVAL) || self.rewind.is_empty() {
+            self.rewind.push(self.frame, chip.snapshot());
+            self.forget_before(self.rewind.oldest().unwrap_or(self.frame));
+        }
+        let keys = *chip.state().keys;
+        let before = self.inputs.back().map_or([false; 16], |input| input.keys);
+        for key in 0..16 {
+            if keys[key] && !before[key] {
+                self.events.push_back((self.frame, Event::Key(key as u8)));
+            }
+        }
+        if self.inputs.is_empty() {
+            self.first = self.frame;
+        }
+        self.inputs.push_back(Input { keys, cycles_per_frame: chip.cycles_per_frame() });
+    }
+
+    /// Called after each instruction while it's recording, with the PC, stack pointer and
+    /// sound timer from before it ran
+    pub fn instruction(&mut self, chip: &Chip8, pc: u16, sp: u8, sound: u8) {
+        if !self.live {
+            return;
+        }
+        if chip.opcode() & 0xF000 == 0xD000 {
+            self.events.push_back((self.frame, Event::Draw { pc, collided: chip.registers()[0xF] != 0 }));
+        }
+        if sound == 0 && chip.sound() > 0 {
+            self.events.push_back((self.frame, Event::Sound { pc }));
+        }
+        if chip.sp() > sp {
+            self.events.push_back((self.frame, Event::Call { pc, to: chip.pc() }));
+        }
+    }
+
+    /// Called after each frame's run
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Starts taking the machine back to the start of a frame, or as near as the history
+    /// goes, by restoring the last snapshot before it. Gives the frame to run forward to from
+    /// there, each frame replayed through start_frame and end_frame as usual
+    pub fn seek(&mut self, chip: &mut Chip8, frame: u64) -> Option<u64> {
+        let (first, end) = self.frames();
+        let frame = frame.clamp(first, end);
+        let (at, snapshot) = self.rewind.before(frame)?;
+        chip.restore(&snapshot);
+        self.frame = at;
+        return Some(frame);
+    }
+
+    /// Starts a new history from where the machine is now if it's been taken back into the
+    /// past, before it runs on live
+    pub fn take_over(&mut self, chip: &mut Chip8) {
+        if self.frame < self.frames().1 {
+            self.changed_by_hand(chip);
+        }
+    }
+
+    /// Starts a new history from where the machine is now, dropping everything recorded in
+    /// this frame and after, since the machine's been changed in a way running forward from an
+    /// older snapshot couldn't redo
+    pub fn changed_by_hand(&mut self, chip: &mut Chip8) {
+        while self.events.back().is_some_and(|(frame, _)| *frame >= self.frame) {
+            self.events.pop_back();
+        }
+        self.inputs.truncate(self.frame.saturating_sub(self.first) as usize);
+        // The next frame's snapshotted whatever frame it is, dropping the ones after it
+        self.rewind.push(self.frame, chip.snapshot());
+        if self.inputs.is_empty() {
+            self.first = self.frame;
+        }
+        self.forget_before(self.rewind.oldest().unwrap_or(self.frame));
+        self.live = true;
+    }
+
+    /// Drops the inputs and events from before frame, which the history can't go back to
+    fn forget_before(&mut self, frame: u64) {
+        while self.first < frame && !self.inputs.is_empty() {
+            self.inputs.pop_front();
+            self.first += 1;
+        }
+        while self.events.front().is_some_and(|(at, _)| *at < frame) {
+            self.events.pop_front();
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt::Write;
+
+use crate::chip::Chip8;
+use crate::diff;
+use crate::error::Chip8Error;
+use crate::isa;
+use crate::octo;
+
+// The guided tour (chip8 tour, and the gui's Tour window): a little program, bundled/tour.8o,
+// stepped through an instruction at a time, with what each kind of instruction does said
+// before it runs and what it changed shown after, in the same terms as chip8 diff. The timers
+// count down once a step rather than 60 times a second, or a wait on them would take
+// hundreds of steps.
+//
+// The lessons are for the instructions of the original CHIP-8, found by looking the opcode up
+// in the same table the disassembler uses, so the gui can explain those of any rom. The
+// extensions' instructions don't have any.
+
+/// How wide the tour's text is wrapped to in the terminal
+const WIDTH: usize = 88;
+
+
+/// What one instruction does, for someone new to CHIP-8
+pub struct Lesson {
+    /// The opcode with its operands as letters, e.g. DXYN
+    pub form: &'static str,
+    pub text: &'static str,
+    mask: u16,
+    pattern: u16,
+}
+
+const fn lesson(mask: u16, pattern: u16, form: &'static str, text: &'static str) -> Lesson {
+    return Lesson { form, text, mask, pattern };
+}
+
+const LESSONS: &[Lesson] = &[
+    lesson(0xFFFF, 0x00E0, "00E0", "Clears the screen, turning every pixel off. Programs usually start with one, since the screen isn't cleared for them."),
+    lesson(0xFFFF, 0x00EE, "00EE", "Returns from a subroutine: the address the last 2NNN pushed onto the stack is popped off and the program carries on from there."),
+    lesson(0xF000, 0x0000, "0NNN", "Calls a routine in the machine code of the computer underneath at NNN. It's how the first programs reached outside CHIP-8, and there's nothing an interpreter can run today, so it's skipped."),
+    lesson(0xF000, 0x1000, "1NNN", "Jumps to NNN, carrying on from there rather than with the next instruction. A jump to itself goes round forever, which is how a program stops."),
+    lesson(0xF000, 0x2000, "2NNN", "Calls the subroutine at NNN: the address of the next instruction is pushed onto the stack so 00EE can come back to it, then the program jumps to NNN. The stack holds 16 addresses, so subroutines can call others up to 16 deep."),
+    lesson(0xF000, 0x3000, "3XNN", "Skips the next instruction if VX is NN. There's no if and else in CHIP-8: a skip over a jump is how a program goes one way or the other."),
+    lesson(0xF000, 0x4000, "4XNN", "Skips the next instruction if VX isn't NN."),
+    lesson(0xF00F, 0x5000, "5XY0", "Skips the next instruction if VX and VY are the same."),
+    lesson(0xF000, 0x6000, "6XNN", "Puts NN into register VX. The 16 registers V0 to VF are a byte each and are where a program does its sums. VF doubles as the flag some instructions set, so programs keep their own values out of it."),
+    lesson(0xF000, 0x7000, "7XNN", "Adds NN to VX. A sum past 255 wraps round to the bottom again, and unlike 8XY4 it leaves VF alone, so it's the one for counting."),
+    lesson(0xF00F, 0x8000, "8XY0", "Copies VY into VX."),
+    lesson(0xF00F, 0x8001, "8XY1", "ORs VY into VX, bit by bit: each bit on in either is on in VX. The original interpreter also cleared VF."),
+    lesson(0xF00F, 0x8002, "8XY2", "ANDs VY into VX, bit by bit: only the bits on in both stay on in VX. The original interpreter also cleared VF."),
+    lesson(0xF00F, 0x8003, "8XY3", "XORs VY into VX, bit by bit: the bits on in VY flip over in VX. The original interpreter also cleared VF."),
+    lesson(0xF00F, 0x8004, "8XY4", "Adds VY to VX, and sets VF to 1 if the sum went past 255 and wrapped round, 0 if not. That carry is how sums bigger than a byte are done."),
+    lesson(0xF00F, 0x8005, "8XY5", "Takes VY away from VX, and sets VF to 0 if that went below 0 and wrapped round, 1 if not."),
+    lesson(0xF00F, 0x8006, "8XY6", "Shifts right by a bit, halving, with the bit shifted out going into VF. The original interpreter shifts VY into VX, SUPER-CHIP shifts VX where it is, and which one a rom expects is one of the quirks."),
+    lesson(0xF00F, 0x8007, "8XY7", "Sets VX to VY take away VX, the other way round to 8XY5, with VF 0 if it went below 0 and 1 if not."),
+    lesson(0xF00F, 0x800E, "8XYE", "Shifts left by a bit, doubling, with the bit shifted out going into VF. Like 8XY6, whether it's VY or VX that's shifted is a quirk."),
+    lesson(0xF00F, 0x9000, "9XY0", "Skips the next instruction if VX and VY are different."),
+    lesson(0xF000, 0xA000, "ANNN", "Points I at NNN. I is the address register, the one register that can reach all of memory, and the instructions that work with memory, drawing sprites, the font, bcd, loading and saving, all go to wherever I points."),
+    lesson(0xF000, 0xB000, "BNNN", "Jumps to NNN plus V0, for jump tables. SUPER-CHIP added VX rather than V0 for a jump to XNN, another of the quirks."),
+    lesson(0xF000, 0xC000, "CXNN", "Puts a random number ANDed with NN into VX, so NN picks which bits can come out on: 0F gives 0 to 15."),
+    lesson(0xF000, 0xD000, "DXYN", "Draws the N rows of sprite at I at (VX, VY). A sprite row is a byte, a pixel a bit, and each is XORed onto the screen: a bit that's on flips the pixel under it, on if it was off and off if it was on. Drawing the same sprite twice rubs it out again. VF is set to 1 if any pixel was turned off, which is how games tell things have collided."),
+    lesson(0xF0FF, 0xE09E, "EX9E", "Skips the next instruction if the key in VX is held down. The keypad has 16 keys, 0 to F."),
+    lesson(0xF0FF, 0xE0A1, "EXA1", "Skips the next instruction if the key in VX isn't held down."),
+    lesson(0xF0FF, 0xF007, "FX07", "Reads the delay timer into VX. The timer counts down by 1 sixty times a second until it gets to 0, so a program can wait by setting it and reading it until it's 0."),
+    lesson(0xF0FF, 0xF00A, "FX0A", "Waits for a key to be pressed, and puts which one it was into VX. Nothing else runs until it has one, though the timers carry on counting down."),
+    lesson(0xF0FF, 0xF015, "FX15", "Sets the delay timer to VX. It counts down by 1 sixty times a second, whatever the program's doing, until it gets to 0."),
+    lesson(0xF0FF, 0xF018, "FX18", "Sets the sound timer to VX. It counts down like the delay timer, and the machine beeps for as long as it isn't 0."),
+    lesson(0xF0FF, 0xF01E, "FX1E", "Adds VX to I, for stepping through a table in memory."),
+    lesson(0xF0FF, 0xF029, "FX29", "Points I at the font's sprite for the digit in VX, 0 to F. The font is kept in the interpreter's memory below the program, each digit 5 rows tall, so a sprite draws it."),
+    lesson(0xF0FF, 0xF033, "FX33", "Writes VX in decimal into memory at I: the hundreds at I, the tens at I+1 and the ones at I+2. With FX65 and FX29 after it, that's how scores are shown."),
+    lesson(0xF0FF, 0xF055, "FX55", "Saves V0 up to VX into memory from I. The original interpreter moves I on past them, later ones leave it, and which one a rom expects is a quirk."),
+    lesson(0xF0FF, 0xF065, "FX65", "Loads V0 up to VX from memory at I, the other way round to FX55, with the same quirk over I."),
+];
+
+
+/// The tour's program
+pub fn rom() -> Vec<u8> {
+    return octo::assemble(include_str!("bundled/tour.8o")).expect("the tour assembles");
+}
+
+/// The lesson on an instruction, if it's one of the original CHIP-8's
+pub fn lesson_for(opcode: u16, chip: &Chip8) -> Option<&'static Lesson> {
+    let info = isa::lookup(opcode, chip.platform())?;
+    return LESSONS.iter().find(|lesson| lesson.mask == info.mask && lesson.pattern == info.pattern);
+}
+
+/// The instruction at PC with the instruction it disassembles to, and its lesson
+pub fn explain(chip: &Chip8) -> (String, String) {
+    let opcode = next_opcode(chip);
+    let instruction = format!("{:04X}: {opcode:04X}  {}", chip.pc(), isa::disassemble(opcode, chip.platform()));
+    let lesson = match lesson_for(opcode, chip) {
+        Some(lesson) => format!("{}: {}", lesson.form, lesson.text),
+        None => "There's no lesson on this one, it's from one of the extensions to CHIP-8".to_string(),
+    };
+    return (instruction, lesson);
+}
+
+/// explain's instruction and lesson for the terminal
+pub fn describe(chip: &Chip8) -> String {
+    let (instruction, lesson) = explain(chip);
+    return format!("{instruction}\n{}", wrap(&lesson, WIDTH));
+}
+
+/// Runs the instruction at PC and counts the timers down, returning what it changed
+pub fn step(chip: &mut Chip8) -> Result<String, Chip8Error> {
+    let before = chip.snapshot();
+    chip.execute()?;
+    chip.tick_timers();
+    return Ok(diff::diff(&before, &chip.snapshot()));
+}
+
+/// Whether the program's stopped, jumping to the instruction it's on
+pub fn finished(chip: &Chip8) -> bool {
+    return next_opcode(chip) == 0x1000 | chip.pc();
+}
+
+fn next_opcode(chip: &Chip8) -> u16 {
+    return chip.read_mem(chip.pc() as u32, 2).iter().fold(0, |opcode, byte| opcode << 8 | *byte as u16);
+}
+
+/// Breaks text into lines no wider than width, between words
+fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = String::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            writeln!(wrapped, "{line}").unwrap();
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line += word;
+    }
+    writeln!(wrapped, "{line}").unwrap();
+    return wrapped;
+}
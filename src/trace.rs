@@ -0,0 +1,235 @@
+use std::fmt;
+
+use crate::chip::Chip8;
+use crate::error::Chip8Error;
+
+// Reference traces are plain text, one line per executed instruction, recording the
+// machine state *after* that instruction has run. Each line is a list of key=value
+// pairs, all values in hex:
+//
+//   pc=0202 op=6E05 i=000 v=00000000000000000000000000000000 sp=0 dt=00 st=00
+//
+// Only `pc` is required, any other field that is missing from a line is simply not
+// compared, so traces exported from emulators that don't track e.g. the timers still
+// work. Blank lines and lines starting with '#' are ignored.
+
+
+/// The state of the machine after a single instruction, as read from a trace line
+/// or captured from the interpreter. Fields that are None weren't in the trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: u16,
+    pub opcode: Option<u16>,
+    pub ar: Option<u32>,
+    pub registers: Option<[u8; 16]>,
+    pub sp: Option<u8>,
+    pub delay: Option<u8>,
+    pub sound: Option<u8>,
+}
+
+/// Where and how the interpreter stopped agreeing with the reference trace.
+/// error is set when the interpreter failed on an instruction the reference ran fine
+pub struct Divergence {
+    pub step: usize,
+    pub line: usize,
+    pub expected: TraceEntry,
+    pub actual: TraceEntry,
+    pub error: Option<Chip8Error>,
+}
+
+impl TraceEntry {
+    /// Takes a full snapshot of the interpreter's current state
+    pub fn capture(chip: &Chip8) -> Self {
+        return Self {
+            pc: chip.pc(),
+            opcode: Some(chip.opcode()),
+            ar: Some(chip.ar()),
+            registers: Some(*chip.registers()),
+            sp: Some(chip.sp()),
+            delay: Some(chip.delay()),
+            sound: Some(chip.sound()),
+        };
+    }
+
+    /// Parses one line of a reference trace
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut pc = None;
+        let mut entry = Self {
+            pc: 0,
+            opcode: None,
+            ar: None,
+            registers: None,
+            sp: None,
+            delay: None,
+            sound: None,
+        };
+
+        for field in line.split_whitespace() {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or(format!("expected key=value, found '{field}'"))?;
+
+            match key.to_ascii_lowercase().as_str() {
+                "pc" => pc = Some(parse_hex(key, value)? as u16),
+                "op" => entry.opcode = Some(parse_hex(key, value)? as u16),
+                "i" => entry.ar = Some(parse_hex(key, value)?),
+                "sp" => entry.sp = Some(parse_hex(key, value)? as u8),
+                "dt" => entry.delay = Some(parse_hex(key, value)? as u8),
+                "st" => entry.sound = Some(parse_hex(key, value)? as u8),
+                "v" => {
+                    // Checked as ASCII first so slicing it two bytes at a time can't split a character
+                    if !value.is_ascii() || value.len() != 32 {
+                        return Err(format!("v must be 32 hex digits, found '{value}'"));
+                    }
+                    let mut registers = [0u8; 16];
+                    for i in 0..16 {
+                        registers[i] = parse_hex(key, &value[i * 2..i * 2 + 2])? as u8;
+                    }
+                    entry.registers = Some(registers);
+                },
+                _ => return Err(format!("unknown field '{key}'")),
+            }
+        }
+
+        entry.pc = pc.ok_or("missing pc field")?;
+
+        return Ok(entry);
+    }
+
+    /// Lists the fields the expected entry sets that don't match the actual one,
+    /// as (name, expected, actual) strings
+    pub fn diff(&self, actual: &TraceEntry) -> Vec<(String, String, String)> {
+        let mut diffs = Vec::new();
+
+        if self.pc != actual.pc {
+            diffs.push(("PC".to_string(), format!("{:04X}", self.pc), format!("{:04X}", actual.pc)));
+        }
+
+        let fields = [
+            ("OP", self.opcode.map(u32::from), actual.opcode.map(u32::from), 4),
+            ("I", self.ar, actual.ar, 3),
+            ("SP", self.sp.map(u32::from), actual.sp.map(u32::from), 1),
+            ("DT", self.delay.map(u32::from), actual.delay.map(u32::from), 2),
+            ("ST", self.sound.map(u32::from), actual.sound.map(u32::from), 2),
+        ];
+        for (name, expected, got, width) in fields {
+            if let (Some(e), Some(a)) = (expected, got) {
+                if e != a {
+                    diffs.push((name.to_string(), format!("{e:0width$X}"), format!("{a:0width$X}")));
+                }
+            }
+        }
+
+        if let (Some(e), Some(a)) = (self.registers, actual.registers) {
+            for i in 0..16 {
+                if e[i] != a[i] {
+                    diffs.push((format!("V{i:X}"), format!("{:02X}", e[i]), format!("{:02X}", a[i])));
+                }
+            }
+        }
+
+        return diffs;
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc={:04X}", self.pc)?;
+        if let Some(op) = self.opcode {
+            write!(f, " op={op:04X}")?;
+        }
+        if let Some(ar) = self.ar {
+            write!(f, " i={ar:03X}")?;
+        }
+        if let Some(registers) = self.registers {
+            write!(f, " v=")?;
+            for r in registers {
+                write!(f, "{r:02X}")?;
+            }
+        }
+        if let Some(sp) = self.sp {
+            write!(f, " sp={sp:X}")?;
+        }
+        if let Some(delay) = self.delay {
+            write!(f, " dt={delay:02X}")?;
+        }
+        if let Some(sound) = self.sound {
+            write!(f, " st={sound:02X}")?;
+        }
+        return Ok(());
+    }
+}
+
+fn parse_hex(key: &str, value: &str) -> Result<u32, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    return u32::from_str_radix(digits, 16).map_err(|_| format!("invalid hex value '{value}' for {key}"));
+}
+
+/// Parses a whole reference trace, keeping the line number of each entry for error reports
+pub fn parse_trace(trace: &str) -> Result<Vec<(usize, TraceEntry)>, String> {
+    let mut entries = Vec::new();
+
+    for (i, line) in trace.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let entry = TraceEntry::parse(line).map_err(|e| format!("line {}: {e}", i + 1))?;
+        entries.push((i + 1, entry));
+    }
+
+    return Ok(entries);
+}
+
+/// Runs the interpreter one instruction per trace entry and compares the state after each one.
+/// Returns the number of instructions that matched, or the first point they stopped matching
+pub fn verify(chip: &mut Chip8, trace: &[(usize, TraceEntry)]) -> Result<usize, Box<Divergence>> {
+    for (step, (line, expected)) in trace.iter().enumerate() {
+        let error = chip.execute().err();
+
+        let actual = TraceEntry::capture(chip);
+        if error.is_some() || !expected.diff(&actual).is_empty() {
+            return Err(Box::new(Divergence {
+                step: step + 1,
+                line: *line,
+                expected: expected.clone(),
+                actual,
+                error,
+            }));
+        }
+    }
+
+    return Ok(trace.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_trace() {
+        let trace = "# after each instruction\n\npc=0202 op=6E05 i=000 v=00000000000000000000000000000005 sp=0 dt=00 st=00\n  PC=0x204 OP=A2F0 I=2F0\n";
+        let entries = parse_trace(trace).unwrap();
+        assert_eq!(entries.len(), 2);
+        let (line, first) = &entries[0];
+        assert_eq!((*line, first.pc, first.opcode, first.ar, first.sp), (3, 0x202, Some(0x6E05), Some(0), Some(0)));
+        assert_eq!(first.registers.map(|v| v[15]), Some(5));
+        let (line, second) = &entries[1];
+        assert_eq!((*line, second.pc, second.ar, second.registers, second.delay), (4, 0x204, Some(0x2F0), None, None));
+        assert_eq!(TraceEntry::parse(&first.to_string()).as_ref(), Ok(first));
+    }
+
+    #[test]
+    fn malformed_lines_are_errors() {
+        assert_eq!(parse_trace("pc=0200\nop=6E05 i=000"), Err("line 2: missing pc field".to_string()));
+        assert_eq!(parse_trace("pc=02G0"), Err("line 1: invalid hex value '02G0' for pc".to_string()));
+        assert_eq!(parse_trace("pc=0200 i"), Err("line 1: expected key=value, found 'i'".to_string()));
+        assert_eq!(parse_trace("pc=0200 vf=01"), Err("line 1: unknown field 'vf'".to_string()));
+        // 15 and 17 registers, and one that isn't hex
+        for v in ["000000000000000000000000000000", "0000000000000000000000000000000000", "0000000000000000000000000000000Z"] {
+            assert!(parse_trace(&format!("pc=0200 v={v}")).is_err(), "v={v} parsed");
+        }
+        assert!(parse_trace("pc=0200 v=ÿÿÿÿÿÿÿÿÿÿÿÿÿÿÿÿ").is_err());
+    }
+}
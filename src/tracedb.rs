@@ -0,0 +1,551 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::chip::CodeWrite;
+use crate::debugger::{parse_number, OpcodeMatch};
+use crate::persist;
+use crate::platform::Platform;
+
+// The trace database: every instruction the debugger runs once `trace on` is given, written to
+// disk as it goes so questions can be asked about any of it long after the rewind history's
+// let it go, e.g. when an address was last written or every frame a sprite collided. A
+// trace's kept in a folder, the rom's being <data dir>/traces/<rom id>, as segments of
+// SEGMENT_LEN instructions, numbered from 0, each in two files:
+//
+//   <n>.seg   "C8TS", then the instructions one after another: u64 step (instructions run
+//             before it), u64 frame, u16 pc, u16 opcode, u32 I once it's run, u8 registers
+//             changed, u16 bytes written, then each register as u8 register, u8 old, u8 new
+//             and each byte as u32 addr, u8 old, u8 new
+//   <n>.idx   "C8TI", then what's in the segment: u64 first and last step, u64 first and
+//             last frame, the 16 registers before its first instruction, u32 a bit for each
+//             register changed, u16 a bit for each opcode's first digit, then u32 count and
+//             that many u16 addresses instructions ran from, and u32 count and that many u32
+//             addresses written, both sorted
+//
+// all little endian. Queries read the indexes and only open the segments that could have an
+// answer. Only the last MAX_SEGMENTS are kept, the oldest deleted as new ones fill, about
+// 64MB and 2 million instructions. The segment being written is kept in memory until it's
+// full or flushed.
+//
+// Going back and running forward again runs the same instructions, so they aren't recorded
+// twice. Changing the machine by hand throws away what's recorded after that point, as the
+// rewind history does.
+
+/// How many instructions a segment holds
+pub const SEGMENT_LEN: usize = 1 << 16;
+
+/// How many segments are kept
+pub const MAX_SEGMENTS: usize = 32;
+
+const SEGMENT_MAGIC: &[u8; 4] = b"C8TS";
+const INDEX_MAGIC: &[u8; 4] = b"C8TI";
+
+/// Stands for I where registers are numbered, after V0-VF
+pub const I: u8 = 16;
+
+
+/// A register an instruction changed, V0-VF by number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    pub register: u8,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// An instruction as the trace keeps it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// How many instructions ran before it
+    pub step: u64,
+    pub frame: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    /// I once it had run
+    pub ar: u32,
+    pub registers: Vec<RegisterChange>,
+    pub writes: Vec<CodeWrite>,
+}
+
+/// Shown one a line in query results, e.g. "#1234 frame 12  0204: D015  VF: 00 -> 01"
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} frame {}  {:04X}: {:04X}", self.step, self.frame, self.pc, self.opcode)?;
+        for change in &self.registers {
+            write!(f, "  V{:X}: {:02X} -> {:02X}", change.register, change.old, change.new)?;
+        }
+        for write in &self.writes {
+            write!(f, "  [{:04X}]: {:02X} -> {:02X}", write.addr, write.old, write.new)?;
+        }
+        return Ok(());
+    }
+}
+
+/// What a segment's index says about it
+#[derive(Debug, Clone, Default)]
+struct Index {
+    first: u64,
+    last: u64,
+    first_frame: u64,
+    last_frame: u64,
+    /// The registers before its first instruction ran
+    registers: [u8; 16],
+    /// The registers its instructions changed, a bit each
+    changed: u32,
+    /// The first digits of the opcodes it ran, a bit each
+    ops: u16,
+    /// Where its instructions ran from, sorted
+    ran: Vec<u16>,
+    /// The addresses its instructions wrote, sorted
+    written: Vec<u32>,
+}
+
+impl Index {
+    fn new(records: &[TraceRecord], registers: [u8; 16]) -> Self {
+        let mut index = Self { registers, ..Self::default() };
+        if let (Some(first), Some(last)) = (records.first(), records.last()) {
+            (index.first, index.last, index.first_frame, index.last_frame) = (first.step, last.step, first.frame, last.frame);
+        }
+        for record in records {
+            for change in &record.registers {
+                index.changed |= 1 << change.register;
+            }
+            index.ops |= 1 << (record.opcode >> 12);
+            index.ran.push(record.pc);
+            index.written.extend(record.writes.iter().map(|write| write.addr));
+        }
+        index.ran.sort_unstable();
+        index.ran.dedup();
+        index.written.sort_unstable();
+        index.written.dedup();
+        return index;
+    }
+
+    fn len(&self) -> u64 {
+        return self.last - self.first + 1;
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = INDEX_MAGIC.to_vec();
+        for n in [self.first, self.last, self.first_frame, self.last_frame] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.registers);
+        bytes.extend_from_slice(&self.changed.to_le_bytes());
+        bytes.extend_from_slice(&self.ops.to_le_bytes());
+        bytes.extend_from_slice(&(self.ran.len() as u32).to_le_bytes());
+        for pc in &self.ran {
+            bytes.extend_from_slice(&pc.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.written.len() as u32).to_le_bytes());
+        for addr in &self.written {
+            bytes.extend_from_slice(&addr.to_le_bytes());
+        }
+        return bytes;
+    }
+
+    fn from_bytes(mut bytes: &[u8]) -> io::Result<Self> {
+        if take(&mut bytes, 4)? != INDEX_MAGIC {
+            return Err(invalid("not a trace index"));
+        }
+        let mut index = Self {
+            first: u64::from_le_bytes(array(&mut bytes)?),
+            last: u64::from_le_bytes(array(&mut bytes)?),
+            first_frame: u64::from_le_bytes(array(&mut bytes)?),
+            last_frame: u64::from_le_bytes(array(&mut bytes)?),
+            registers: array(&mut bytes)?,
+            changed: u32::from_le_bytes(array(&mut bytes)?),
+            ops: u16::from_le_bytes(array(&mut bytes)?),
+            ..Self::default()
+        };
+        for _ in 0..u32::from_le_bytes(array(&mut bytes)?) {
+            index.ran.push(u16::from_le_bytes(array(&mut bytes)?));
+        }
+        for _ in 0..u32::from_le_bytes(array(&mut bytes)?) {
+            index.written.push(u32::from_le_bytes(array(&mut bytes)?));
+        }
+        return Ok(index);
+    }
+}
+
+/// Records instructions into a trace, see the top of the file
+pub struct TraceLog {
+    dir: PathBuf,
+    /// The full segments on disk, oldest first, by number
+    segments: VecDeque<u64>,
+    /// The number of the segment being written, what's in it so far and the registers before
+    /// its first instruction
+    number: u64,
+    current: Vec<TraceRecord>,
+    registers: [u8; 16],
+    /// The step after the last one recorded
+    next: Option<u64>,
+}
+
+impl TraceLog {
+    /// Starts a new trace in dir, deleting any trace that's already there
+    pub fn create(dir: &Path) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        for (number, _) in segment_files(dir)? {
+            remove_segment(dir, number)?;
+        }
+        return Ok(Self {
+            dir: dir.to_path_buf(),
+            segments: VecDeque::new(),
+            number: 0,
+            current: Vec::new(),
+            registers: [0; 16],
+            next: None,
+        });
+    }
+
+    pub fn dir(&self) -> &Path {
+        return &self.dir;
+    }
+
+    /// Adds an instruction to the trace, given the registers from before it ran, unless it's
+    /// been recorded already
+    pub fn record(&mut self, record: TraceRecord, registers: [u8; 16]) -> io::Result<()> {
+        if self.next.is_some_and(|next| record.step < next) {
+            return Ok(());
+        }
+        if self.current.is_empty() {
+            self.registers = registers;
+        }
+        self.next = Some(record.step + 1);
+        self.current.push(record);
+
+        if self.current.len() == SEGMENT_LEN {
+            self.flush()?;
+            self.segments.push_back(self.number);
+            self.number += 1;
+            self.current.clear();
+            while self.segments.len() > MAX_SEGMENTS {
+                let oldest = self.segments.pop_front().expect("there are more than MAX_SEGMENTS");
+                remove_segment(&self.dir, oldest)?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Throws away every instruction recorded from step on
+    pub fn truncate(&mut self, step: u64) -> io::Result<()> {
+        self.current.retain(|record| record.step < step);
+        while self.current.is_empty() {
+            let Some(last) = self.segments.pop_back() else {
+                break;
+            };
+            remove_segment(&self.dir, self.number)?;
+            let (index, records) = read_segment(&self.dir, last)?;
+            (self.number, self.registers, self.current) = (last, index.registers, records);
+            self.current.retain(|record| record.step < step);
+        }
+        self.next = self.next.map(|next| next.min(step));
+        return self.flush();
+    }
+
+    /// Writes the segment being filled to disk as far as it goes
+    pub fn flush(&mut self) -> io::Result<()> {
+        if self.current.is_empty() {
+            return remove_segment(&self.dir, self.number);
+        }
+        let mut bytes = SEGMENT_MAGIC.to_vec();
+        for record in &self.current {
+            write_record(&mut bytes, record);
+        }
+        std::fs::write(segment_path(&self.dir, self.number, "seg"), bytes)?;
+        let index = Index::new(&self.current, self.registers);
+        return std::fs::write(segment_path(&self.dir, self.number, "idx"), index.to_bytes());
+    }
+}
+
+impl Drop for TraceLog {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Where the trace for a rom is kept
+pub fn trace_dir(rom: &[u8]) -> PathBuf {
+    return persist::data_dir().join("traces").join(persist::rom_id(rom));
+}
+
+/// A question to ask a trace
+pub enum Query {
+    /// Every write to the byte at an address
+    Writes(u32),
+    /// The last write to it
+    LastWrite(u32),
+    /// Every instruction matching the opcode, if there is one, that changed a register, V0-VF
+    /// by number or I
+    Changes(u8, Option<OpcodeMatch>),
+    /// Every instruction matching the opcode, if there is one, that left a register other
+    /// than 0, e.g. a DXYN leaving VF set when its sprite collided
+    Set(u8, Option<OpcodeMatch>),
+    /// Every time the instruction at an address ran
+    Runs(u16),
+    /// Every instruction in a frame
+    Frame(u64),
+}
+
+impl Query {
+    pub const USAGE: &'static str = "\
+Usage: query [frames] <question>, where the question is one of
+  writes <addr>              every write to the byte at addr
+  last write <addr>          the last write to it
+  changes <reg> [by <op>]    every instruction that changed a register (V0-VF or I), only
+                             those matching op if it's given
+  set <reg> [by <op>]        every instruction that left a register other than 0, e.g. set VF
+                             by DXYN for every sprite that collided
+  runs <addr>                every time the instruction at addr ran
+  frame <n>                  every instruction frame n ran
+and frames lists the frames they were in rather than the instructions";
+
+    /// Reads a question as the debugger's query command takes it, the words after it
+    pub fn parse(words: &[&str]) -> Result<Self, String> {
+        let register = |text: &str| match text.to_ascii_uppercase().as_str() {
+            "I" => Ok(I),
+            upper => upper
+                .strip_prefix('V')
+                .filter(|digit| digit.len() == 1)
+                .and_then(|digit| u8::from_str_radix(digit, 16).ok())
+                .ok_or(format!("'{text}' isn't a register, V0-VF or I")),
+        };
+        let by = |rest: &[&str]| match rest {
+            [] => Ok(None),
+            ["by", op] => OpcodeMatch::parse(op).map(Some),
+            _ => Err(Self::USAGE.to_string()),
+        };
+
+        return match words {
+            ["writes", addr] => Ok(Query::Writes(parse_number(addr)? as u32)),
+            ["last", "write", addr] => Ok(Query::LastWrite(parse_number(addr)? as u32)),
+            ["changes", reg, rest @ ..] => Ok(Query::Changes(register(reg)?, by(rest)?)),
+            ["set", reg, rest @ ..] => match register(reg)? {
+                I => Err("Only V0-VF can be asked about with set".to_string()),
+                register => Ok(Query::Set(register, by(rest)?)),
+            },
+            ["runs", addr] => Ok(Query::Runs(parse_number(addr)? as u16)),
+            ["frame", n] => Ok(Query::Frame(n.parse().map_err(|_| format!("invalid frame '{n}'"))?)),
+            _ => Err(Self::USAGE.to_string()),
+        };
+    }
+
+    /// Whether the segment could have an answer, going by its index
+    fn might_match(&self, index: &Index) -> bool {
+        return match self {
+            Query::Writes(addr) | Query::LastWrite(addr) => index.written.binary_search(addr).is_ok(),
+            Query::Changes(register, _) => *register == I || index.changed & 1 << register != 0,
+            Query::Set(register, _) => index.registers[*register as usize] != 0 || index.changed & 1 << register != 0,
+            Query::Runs(pc) => index.ran.binary_search(pc).is_ok(),
+            Query::Frame(frame) => (index.first_frame..=index.last_frame).contains(frame),
+        };
+    }
+}
+
+/// A trace opened for questions
+pub struct TraceDb {
+    dir: PathBuf,
+    /// The segments, oldest first, by number
+    segments: Vec<(u64, Index)>,
+}
+
+impl TraceDb {
+    /// Opens the trace in dir. Flush a TraceLog writing there first to see everything it's
+    /// recorded. A folder that isn't there is an empty trace
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let files = match segment_files(dir) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            files => files?,
+        };
+        let mut segments = Vec::new();
+        for (number, path) in files {
+            segments.push((number, Index::from_bytes(&std::fs::read(path)?)?));
+        }
+        return Ok(Self { dir: dir.to_path_buf(), segments });
+    }
+
+    /// How many instructions it holds
+    pub fn len(&self) -> u64 {
+        return self.segments.iter().map(|(_, index)| index.len()).sum();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.segments.is_empty();
+    }
+
+    /// The first and last step it holds
+    pub fn steps(&self) -> Option<(u64, u64)> {
+        return Some((self.segments.first()?.1.first, self.segments.last()?.1.last));
+    }
+
+    /// How much disk it takes up, in bytes
+    pub fn size(&self) -> u64 {
+        let size = |number: u64, extension: &str| {
+            std::fs::metadata(segment_path(&self.dir, number, extension)).map_or(0, |metadata| metadata.len())
+        };
+        return self.segments.iter().map(|(number, _)| size(*number, "seg") + size(*number, "idx")).sum();
+    }
+
+    /// The instructions that answer the question, oldest first, with the platform to decode
+    /// mnemonics for. Writes to other addresses are left off the answers about writes
+    pub fn query(&self, query: &Query, platform: Platform) -> io::Result<Vec<TraceRecord>> {
+        let by = |by: &Option<OpcodeMatch>, record: &TraceRecord| by.as_ref().is_none_or(|op| op.matches(record.opcode, platform));
+        let mut found = Vec::new();
+        let mut ar = None;
+        for (number, index) in &self.segments {
+            if !query.might_match(index) {
+                // What I was before the next segment is lost, so what it first changes I to can't be told
+                ar = None;
+                continue;
+            }
+            let (_, records) = read_segment(&self.dir, *number)?;
+            let mut registers = index.registers;
+            for mut record in records {
+                for change in &record.registers {
+                    registers[change.register as usize] = change.new;
+                }
+                let before = ar.replace(record.ar);
+                let answers = match query {
+                    Query::Writes(addr) | Query::LastWrite(addr) => {
+                        record.writes.retain(|write| write.addr == *addr);
+                        !record.writes.is_empty()
+                    },
+                    Query::Changes(I, op) => before.is_some_and(|before| before != record.ar) && by(op, &record),
+                    Query::Changes(register, op) => {
+                        record.registers.iter().any(|change| change.register == *register) && by(op, &record)
+                    },
+                    Query::Set(register, op) => registers[*register as usize] != 0 && by(op, &record),
+                    Query::Runs(pc) => record.pc == *pc,
+                    Query::Frame(frame) => record.frame == *frame,
+                };
+                if answers {
+                    found.push(record);
+                }
+            }
+        }
+        if let Query::LastWrite(_) = query {
+            found = found.pop().into_iter().collect();
+        }
+        return Ok(found);
+    }
+}
+
+/// The frames the instructions ran in, as ranges, e.g. "12-15, 20"
+pub fn frame_ranges(records: &[TraceRecord]) -> String {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for record in records {
+        match ranges.last_mut() {
+            Some((_, end)) if record.frame <= *end + 1 => *end = record.frame.max(*end),
+            _ => ranges.push((record.frame, record.frame)),
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| if start == end { start.to_string() } else { format!("{start}-{end}") })
+        .collect();
+    return ranges.join(", ");
+}
+
+fn segment_path(dir: &Path, number: u64, extension: &str) -> PathBuf {
+    return dir.join(format!("{number:08}.{extension}"));
+}
+
+/// The segments in dir by number, with their index files, in order. Files that aren't a
+/// segment's are left alone
+fn segment_files(dir: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let mut segments = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let number = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<u64>().ok());
+        if let (Some(number), Some("idx")) = (number, path.extension().and_then(|extension| extension.to_str())) {
+            segments.push((number, path));
+        }
+    }
+    segments.sort();
+    return Ok(segments);
+}
+
+fn remove_segment(dir: &Path, number: u64) -> io::Result<()> {
+    for extension in ["seg", "idx"] {
+        match std::fs::remove_file(segment_path(dir, number, extension)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {},
+        }
+    }
+    return Ok(());
+}
+
+fn read_segment(dir: &Path, number: u64) -> io::Result<(Index, Vec<TraceRecord>)> {
+    let index = Index::from_bytes(&std::fs::read(segment_path(dir, number, "idx"))?)?;
+    let bytes = std::fs::read(segment_path(dir, number, "seg"))?;
+    let mut bytes = &bytes[..];
+    if take(&mut bytes, 4)? != SEGMENT_MAGIC {
+        return Err(invalid("not a trace segment"));
+    }
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        records.push(read_record(&mut bytes)?);
+    }
+    return Ok((index, records));
+}
+
+fn write_record(bytes: &mut Vec<u8>, record: &TraceRecord) {
+    bytes.extend_from_slice(&record.step.to_le_bytes());
+    bytes.extend_from_slice(&record.frame.to_le_bytes());
+    bytes.extend_from_slice(&record.pc.to_le_bytes());
+    bytes.extend_from_slice(&record.opcode.to_le_bytes());
+    bytes.extend_from_slice(&record.ar.to_le_bytes());
+    bytes.push(record.registers.len() as u8);
+    bytes.extend_from_slice(&(record.writes.len() as u16).to_le_bytes());
+    for change in &record.registers {
+        bytes.extend_from_slice(&[change.register, change.old, change.new]);
+    }
+    for write in &record.writes {
+        bytes.extend_from_slice(&write.addr.to_le_bytes());
+        bytes.extend_from_slice(&[write.old, write.new]);
+    }
+}
+
+fn read_record(bytes: &mut &[u8]) -> io::Result<TraceRecord> {
+    let mut record = TraceRecord {
+        step: u64::from_le_bytes(array(bytes)?),
+        frame: u64::from_le_bytes(array(bytes)?),
+        pc: u16::from_le_bytes(array(bytes)?),
+        opcode: u16::from_le_bytes(array(bytes)?),
+        ar: u32::from_le_bytes(array(bytes)?),
+        registers: Vec::new(),
+        writes: Vec::new(),
+    };
+    let [registers] = array(bytes)?;
+    let writes = u16::from_le_bytes(array(bytes)?);
+    for _ in 0..registers {
+        let [register, old, new] = array(bytes)?;
+        record.registers.push(RegisterChange { register, old, new });
+    }
+    for _ in 0..writes {
+        let addr = u32::from_le_bytes(array(bytes)?);
+        let [old, new] = array(bytes)?;
+        record.writes.push(CodeWrite { pc: record.pc, addr, old, new });
+    }
+    return Ok(record);
+}
+
+/// Takes the next len bytes off the front
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if bytes.len() < len {
+        return Err(invalid("the trace ends part way through"));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    return Ok(taken);
+}
+
+fn array<const N: usize>(bytes: &mut &[u8]) -> io::Result<[u8; N]> {
+    return Ok(take(bytes, N)?.try_into().expect("took N bytes"));
+}
+
+fn invalid(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
@@ -0,0 +1,203 @@
+// Reading roms straight out of ZIP archives, since rom packs are nearly always zipped. A
+// path to an archive loads the first rom in it, and one to an entry inside loads that:
+//
+//   roms.zip              the first .ch8, .c8, .sc8 or .xo8 in the archive
+//   roms.zip:BRIX.ch8     BRIX.ch8, by its path in the archive or just its file name
+//
+// Entries are found through the central directory at the end of the archive, and can be
+// stored or deflated, the two ways anything still zips files.
+
+/// The extensions of roms picked out of an archive when no entry is named
+const ROM_EXTENSIONS: [&str; 4] = [".ch8", ".c8", ".sc8", ".xo8"];
+
+/// The biggest entry that's unpacked, well past the 16MB Mega-Chip roms can fill, so a
+/// corrupt or malicious archive can't claim gigabytes
+const MAX_ENTRY: usize = 32 << 20;
+
+
+/// Whether a file is a ZIP archive
+pub fn is_zip(bytes: &[u8]) -> bool {
+    return bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06");
+}
+
+/// Splits a path into the archive and the entry asked for inside it, if there is one
+pub fn split_path(path: &str) -> (&str, Option<&str>) {
+    let lower = path.to_ascii_lowercase();
+    return match lower.find(".zip:") {
+        Some(i) => (&path[..i + 4], Some(&path[i + 5..])),
+        None => (path, None),
+    };
+}
+
+/// Unpacks the entry named from an archive, or the first rom in it
+pub fn extract(archive: &[u8], entry: Option<&str>) -> Result<Vec<u8>, String> {
+    let u16_at = |at: usize| archive.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]) as usize);
+    let u32_at = |at: usize| archive.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let corrupt = || "the archive is corrupt".to_string();
+
+    // The end of central directory record is the last thing in the file, before a comment
+    // of up to 64K
+    let search = archive.len().saturating_sub(22 + 0xFFFF);
+    let end = (search..archive.len().saturating_sub(21))
+        .rev()
+        .find(|&at| archive[at..].starts_with(b"PK\x05\x06"))
+        .ok_or("the archive has no central directory")?;
+    let count = u16_at(end + 10).ok_or_else(corrupt)?;
+    let mut at = u32_at(end + 16).ok_or_else(corrupt)?;
+
+    let mut names = Vec::new();
+    for _ in 0..count {
+        if !archive.get(at..).is_some_and(|header| header.starts_with(b"PK\x01\x02")) {
+            return Err(corrupt());
+        }
+        let flags = u16_at(at + 8).ok_or_else(corrupt)?;
+        let method = u16_at(at + 10).ok_or_else(corrupt)?;
+        let compressed = u32_at(at + 20).ok_or_else(corrupt)?;
+        let size = u32_at(at + 24).ok_or_else(corrupt)?;
+        let name_len = u16_at(at + 28).ok_or_else(corrupt)?;
+        let skip = name_len + u16_at(at + 30).ok_or_else(corrupt)? + u16_at(at + 32).ok_or_else(corrupt)?;
+        let local = u32_at(at + 42).ok_or_else(corrupt)?;
+        let name = archive.get(at + 46..at + 46 + name_len).ok_or_else(corrupt)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + skip;
+
+        let lower = name.to_ascii_lowercase();
+        let wanted = match entry {
+            Some(entry) => name == entry || name.rsplit('/').next() == Some(entry),
+            None => ROM_EXTENSIONS.iter().any(|extension| lower.ends_with(extension)),
+        };
+        if name.ends_with('/') || !wanted {
+            names.push(name);
+            continue;
+        }
+
+        if flags & 1 != 0 {
+            return Err(format!("{name} is encrypted"));
+        }
+        if size > MAX_ENTRY {
+            return Err(format!("{name} is {size} bytes unpacked, too big to be a rom"));
+        }
+        let data_start = local + 30 + u16_at(local + 26).ok_or_else(corrupt)? + u16_at(local + 28).ok_or_else(corrupt)?;
+        let data = archive.get(data_start..data_start + compressed).ok_or_else(corrupt)?;
+        return match method {
+            0 => Ok(data.to_vec()),
+            8 => miniz_oxide::inflate::decompress_to_vec_with_limit(data, size)
+                .map_err(|e| format!("{name} doesn't unpack: {e}")),
+            method => Err(format!("{name} is packed with method {method}, only stored and deflated are supported")),
+        };
+    }
+
+    let contents = if names.is_empty() { "nothing".to_string() } else { names.join(", ") };
+    return Err(match entry {
+        Some(entry) => format!("there's no {entry} in the archive, it has {contents}"),
+        None => format!("there's no {} rom in the archive, it has {contents}", ROM_EXTENSIONS.join(", ")),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An archive of entries, each a name, its contents and whether it's deflated
+    fn archive(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut central = Vec::new();
+        for (name, contents, deflated) in entries {
+            let data = match deflated {
+                true => miniz_oxide::deflate::compress_to_vec(contents, 6),
+                false => contents.to_vec(),
+            };
+            let method: u16 = if *deflated { 8 } else { 0 };
+            let sizes = [(data.len() as u32).to_le_bytes(), (contents.len() as u32).to_le_bytes()].concat();
+            let local = archive.len() as u32;
+            archive.extend(b"PK\x03\x04\x14\x00\x00\x00");
+            archive.extend(method.to_le_bytes());
+            archive.extend([0; 8]);
+            archive.extend(&sizes);
+            archive.extend((name.len() as u16).to_le_bytes());
+            archive.extend([0; 2]);
+            archive.extend(name.as_bytes());
+            archive.extend(&data);
+
+            central.extend(b"PK\x01\x02\x14\x00\x14\x00\x00\x00");
+            central.extend(method.to_le_bytes());
+            central.extend([0; 8]);
+            central.extend(&sizes);
+            central.extend((name.len() as u16).to_le_bytes());
+            central.extend([0; 12]);
+            central.extend(local.to_le_bytes());
+            central.extend(name.as_bytes());
+        }
+        let (offset, len) = (archive.len() as u32, central.len() as u32);
+        archive.extend(central);
+        archive.extend(b"PK\x05\x06\x00\x00\x00\x00");
+        archive.extend([(entries.len() as u16).to_le_bytes(), (entries.len() as u16).to_le_bytes()].concat());
+        archive.extend(len.to_le_bytes());
+        archive.extend(offset.to_le_bytes());
+        archive.extend([0; 2]);
+        return archive;
+    }
+
+    /// Where the first entry of the central directory is
+    fn central(archive: &[u8]) -> usize {
+        let at = archive.len() - 22 + 16;
+        return u32::from_le_bytes(archive[at..at + 4].try_into().unwrap()) as usize;
+    }
+
+    #[test]
+    fn roms_come_out_stored_or_deflated() {
+        let brix = [0x6E, 0x05, 0x65, 0x00].repeat(100);
+        let zip = archive(&[
+            ("README.txt", b"not a rom", false),
+            ("games/", b"", false),
+            ("games/PONG.c8", &[0x6A, 0x02], false),
+            ("games/BRIX.ch8", &brix, true),
+        ]);
+        assert!(is_zip(&zip));
+        assert_eq!(extract(&zip, None), Ok(vec![0x6A, 0x02]));
+        assert_eq!(extract(&zip, Some("games/BRIX.ch8")), Ok(brix.clone()));
+        assert_eq!(extract(&zip, Some("BRIX.ch8")), Ok(brix));
+        assert_eq!(extract(&zip, Some("README.txt")), Ok(b"not a rom".to_vec()));
+
+        assert_eq!(split_path("packs/Roms.ZIP:games/BRIX.ch8"), ("packs/Roms.ZIP", Some("games/BRIX.ch8")));
+        assert_eq!(split_path("roms.zip"), ("roms.zip", None));
+        assert_eq!(split_path("BRIX.ch8"), ("BRIX.ch8", None));
+        assert!(!is_zip(&[0x12, 0x00]));
+    }
+
+    #[test]
+    fn malformed_archives_are_errors() {
+        let zip = archive(&[("README.txt", b"not a rom", false), ("BRIX.ch8", &[0x00, 0xE0], true)]);
+        let error = |zip: &[u8]| extract(zip, None).unwrap_err();
+        assert_eq!(extract(&zip, Some("PONG")).unwrap_err(), "there's no PONG in the archive, it has README.txt, BRIX.ch8");
+        assert!(error(&archive(&[])).ends_with("it has nothing"));
+        assert_eq!(error(&zip[..zip.len() - 1]), "the archive has no central directory");
+        assert_eq!(error(b"PK\x03\x04"), "the archive has no central directory");
+
+        // The central directory somewhere it isn't
+        let mut moved = zip.clone();
+        let at = moved.len() - 22 + 16;
+        moved[at] ^= 1;
+        assert_eq!(error(&moved), "the archive is corrupt");
+        // An entry running off the end
+        let entry = central(&zip) + 46 + "README.txt".len();
+        let mut long = zip.clone();
+        long[entry + 20..entry + 24].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(error(&long), "the archive is corrupt");
+
+        let mut encrypted = zip.clone();
+        encrypted[entry + 8] = 1;
+        assert_eq!(error(&encrypted), "BRIX.ch8 is encrypted");
+        let mut imploded = zip.clone();
+        imploded[entry + 10] = 6;
+        assert_eq!(error(&imploded), "BRIX.ch8 is packed with method 6, only stored and deflated are supported");
+        let mut huge = zip.clone();
+        huge[entry + 24..entry + 28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(error(&huge).contains("too big to be a rom"));
+        // Stored bytes that claim to be deflated
+        let mut garbled = archive(&[("BRIX.ch8", &[0xFF; 8], false)]);
+        let at = central(&garbled);
+        garbled[at + 10] = 8;
+        assert!(error(&garbled).starts_with("BRIX.ch8 doesn't unpack"));
+    }
+}
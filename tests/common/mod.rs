@@ -0,0 +1,29 @@
+// Each test file uses what it needs of these, which leaves the rest unused in that one
+#![allow(dead_code)]
+
+use chip8::chip::Chip8;
+use chip8::platform::Platform;
+
+// The helpers the integration tests share, for loading opcodes into a machine and running
+// them.
+
+
+/// A machine on platform with the opcodes loaded at its start address
+pub fn machine_on(platform: Platform, opcodes: &[u16]) -> Chip8 {
+    let rom: Vec<u8> = opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+    let mut chip = Chip8::with_platform(platform, false);
+    chip.load_rom_bytes(&rom);
+    return chip;
+}
+
+/// A CHIP-8 machine with the opcodes loaded
+pub fn machine(opcodes: &[u16]) -> Chip8 {
+    return machine_on(Platform::Chip8, opcodes);
+}
+
+/// Runs steps instructions, which all have to succeed
+pub fn step(chip: &mut Chip8, steps: usize) {
+    for _ in 0..steps {
+        chip.execute().expect("the instruction runs");
+    }
+}
@@ -0,0 +1,525 @@
+mod common;
+
+use chip8::chip::{Chip8, Quirks, SysPolicy};
+use chip8::error::Chip8Error;
+use chip8::platform::Platform;
+use common::{machine, machine_on, step};
+
+// Every base CHIP-8 instruction, one or a few at a time. Each test loads a handful of opcodes
+// at 0x200, runs them, and checks the registers, memory, PC and screen they leave behind,
+// including the edge cases roms lean on: VF as the destination of arithmetic, I at the end of
+// memory and skips over XO-CHIP's 4 byte F000 NNNN.
+
+
+/// Runs each of the opcodes in turn
+fn run(opcodes: &[u16]) -> Chip8 {
+    let mut chip = machine(opcodes);
+    step(&mut chip, opcodes.len());
+    return chip;
+}
+
+/// Whether the pixel at x, y is on
+fn pixel(chip: &Chip8, x: usize, y: usize) -> bool {
+    return chip.framebuffer().get(x, y) != 0;
+}
+
+fn lit(chip: &Chip8) -> usize {
+    return chip.framebuffer().lit();
+}
+
+
+#[test]
+fn cls_clears_the_screen() {
+    // Draw the 0 in the font, then clear it
+    let chip = run(&[0xD005, 0x00E0]);
+    assert_eq!(lit(&chip), 0);
+}
+
+#[test]
+fn call_and_ret() {
+    let mut chip = machine(&[0x2206, 0x6001, 0x1208, 0x00EE]);
+    step(&mut chip, 1);
+    assert_eq!(chip.pc(), 0x206);
+    assert_eq!(chip.call_stack(), &[0x202]);
+    step(&mut chip, 1);
+    assert_eq!(chip.pc(), 0x202);
+    assert_eq!(chip.sp(), 0);
+    step(&mut chip, 1);
+    assert_eq!(chip.registers()[0], 1);
+}
+
+#[test]
+fn ret_with_an_empty_stack_fails_in_place() {
+    let mut chip = machine(&[0x00EE]);
+    assert_eq!(chip.execute(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+    assert_eq!(chip.pc(), 0x200);
+}
+
+#[test]
+fn call_with_a_full_stack_fails() {
+    // Calls itself forever
+    let mut chip = machine(&[0x2200]);
+    step(&mut chip, 16);
+    assert_eq!(chip.call_stack(), &[0x202; 16]);
+    assert!(matches!(chip.execute(), Err(Chip8Error::StackOverflow { pc: 0x200, .. })));
+}
+
+#[test]
+fn sys_is_ignored_unless_asked_not_to() {
+    let chip = run(&[0x0123]);
+    assert_eq!(chip.pc(), 0x202);
+
+    let mut chip = machine(&[0x0123]);
+    chip.set_sys_policy(SysPolicy::Error);
+    assert_eq!(chip.execute(), Err(Chip8Error::MachineCodeCall { pc: 0x200, addr: 0x123 }));
+}
+
+#[test]
+fn jp_uses_the_whole_address() {
+    let chip = run(&[0x1ABC]);
+    assert_eq!(chip.pc(), 0xABC);
+}
+
+#[test]
+fn jp_v0_adds_v0() {
+    let chip = run(&[0x6010, 0xB300]);
+    assert_eq!(chip.pc(), 0x310);
+    let chip = run(&[0x60FF, 0xBFFF]);
+    assert_eq!(chip.pc(), 0x10FE);
+}
+
+#[test]
+fn se_and_sne_with_a_byte() {
+    assert_eq!(run(&[0x6A05, 0x3A05]).pc(), 0x206);
+    assert_eq!(run(&[0x6A05, 0x3A06]).pc(), 0x204);
+    assert_eq!(run(&[0x6A05, 0x4A05]).pc(), 0x204);
+    assert_eq!(run(&[0x6A05, 0x4A06]).pc(), 0x206);
+}
+
+#[test]
+fn se_and_sne_with_a_register() {
+    assert_eq!(run(&[0x6A05, 0x6B05, 0x5AB0]).pc(), 0x208);
+    assert_eq!(run(&[0x6A05, 0x6B06, 0x5AB0]).pc(), 0x206);
+    assert_eq!(run(&[0x6A05, 0x6B05, 0x9AB0]).pc(), 0x206);
+    assert_eq!(run(&[0x6A05, 0x6B06, 0x9AB0]).pc(), 0x208);
+
+    // Only with the low nibble 0, 5XY1 and 9XY1 are no skip even with Vx and Vy the same
+    for opcode in [0x5AB1, 0x9AB1] {
+        let mut chip = machine(&[0x6A05, 0x6B05, opcode]);
+        chip.set_strict(true);
+        step(&mut chip, 2);
+        assert_eq!(chip.execute(), Err(Chip8Error::UnknownInstruction { pc: 0x204, opcode }));
+    }
+    assert_eq!(run(&[0x6A05, 0x6B05, 0x5AB1]).pc(), 0x206);
+}
+
+#[test]
+fn skips_step_over_xochip_long_i() {
+    let mut chip = machine_on(Platform::XoChip, &[0x3000, 0xF000, 0x1234, 0x6101]);
+    step(&mut chip, 1);
+    assert_eq!(chip.pc(), 0x206);
+    step(&mut chip, 1);
+    assert_eq!(chip.registers()[1], 1);
+    assert_eq!(chip.ar(), 0);
+
+    // Everywhere else F000 is an instruction of its own
+    let chip = run(&[0x3000]);
+    assert_eq!(chip.pc(), 0x204);
+}
+
+#[test]
+fn ld_and_add_a_byte() {
+    let chip = run(&[0x6A42, 0x7A01]);
+    assert_eq!(chip.registers()[0xA], 0x43);
+}
+
+#[test]
+fn add_a_byte_wraps_without_touching_vf() {
+    let chip = run(&[0x6F07, 0x60FF, 0x7002]);
+    assert_eq!(chip.registers()[0], 1);
+    assert_eq!(chip.registers()[0xF], 7);
+}
+
+#[test]
+fn logic_between_registers() {
+    let vx = |op: u16| run(&[0x6A0C, 0x6B0A, 0x8AB0 | op]).registers()[0xA];
+    assert_eq!(vx(0x0), 0x0A);
+    assert_eq!(vx(0x1), 0x0E);
+    assert_eq!(vx(0x2), 0x08);
+    assert_eq!(vx(0x3), 0x06);
+}
+
+#[test]
+fn add_sets_carry() {
+    let chip = run(&[0x6AFF, 0x6B02, 0x8AB4]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x01, 1));
+    let chip = run(&[0x6AFE, 0x6B01, 0x8AB4]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0xFF, 0));
+}
+
+#[test]
+fn sub_and_subn_set_not_borrow() {
+    let chip = run(&[0x6A05, 0x6B03, 0x8AB5]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x02, 1));
+    let chip = run(&[0x6A03, 0x6B05, 0x8AB5]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0xFE, 0));
+    // Equal values don't borrow
+    let chip = run(&[0x6A05, 0x6B05, 0x8AB5]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x00, 1));
+
+    let chip = run(&[0x6A03, 0x6B05, 0x8AB7]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x02, 1));
+    let chip = run(&[0x6A05, 0x6B03, 0x8AB7]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0xFE, 0));
+}
+
+#[test]
+fn shifts_set_the_bit_shifted_out() {
+    let chip = run(&[0x6A05, 0x8A06]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x02, 1));
+    let chip = run(&[0x6A81, 0x8A0E]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x02, 1));
+    let chip = run(&[0x6A40, 0x8A0E]);
+    assert_eq!((chip.registers()[0xA], chip.registers()[0xF]), (0x80, 0));
+}
+
+#[test]
+fn vf_as_the_destination_ends_up_as_the_flag() {
+    assert_eq!(run(&[0x6FFF, 0x6101, 0x8F14]).registers()[0xF], 1);
+    assert_eq!(run(&[0x6F05, 0x6103, 0x8F15]).registers()[0xF], 1);
+    assert_eq!(run(&[0x6F02, 0x8F06]).registers()[0xF], 0);
+    assert_eq!(run(&[0x6F03, 0x6105, 0x8F17]).registers()[0xF], 1);
+    assert_eq!(run(&[0x6F81, 0x8F0E]).registers()[0xF], 1);
+}
+
+#[test]
+fn ld_i() {
+    let chip = run(&[0xAABC]);
+    assert_eq!(chip.ar(), 0xABC);
+    assert_eq!(chip.registers(), &[0; 16]);
+}
+
+#[test]
+fn rnd_is_masked() {
+    let mut chip = machine(&[0xC00F, 0xC100]);
+    chip.seed_rng(1);
+    step(&mut chip, 2);
+    assert_eq!(chip.registers()[0] & 0xF0, 0);
+    assert_eq!(chip.registers()[1], 0);
+}
+
+#[test]
+fn drw_xors_and_reports_collisions() {
+    // The 0 in the font is F0 90 90 90 F0
+    let mut chip = machine(&[0xD005, 0xD005]);
+    step(&mut chip, 1);
+    assert_eq!(lit(&chip), 14);
+    let screen = chip.framebuffer().to_ascii('#', '.');
+    let rows: Vec<&str> = screen.lines().take(6).map(|row| &row[..5]).collect();
+    assert_eq!(rows, ["####.", "#..#.", "#..#.", "#..#.", "####.", "....."]);
+    assert_eq!(chip.registers()[0xF], 0);
+    step(&mut chip, 1);
+    assert_eq!(lit(&chip), 0);
+    assert_eq!(chip.registers()[0xF], 1);
+}
+
+#[test]
+fn drw_draws_at_vx_vy() {
+    let chip = run(&[0x600A, 0x6114, 0xD015]);
+    assert!(pixel(&chip, 10, 20) && pixel(&chip, 13, 24));
+    assert!(!pixel(&chip, 20, 10));
+    assert_eq!(lit(&chip), 14);
+}
+
+#[test]
+fn drw_wraps_the_position_but_clips_the_sprite() {
+    // 66, 34 is 2, 2 wrapped around
+    let chip = run(&[0x6042, 0x6122, 0xD015]);
+    assert!(pixel(&chip, 2, 2));
+    assert_eq!(lit(&chip), 14);
+
+    // Only the left half of the 0 fits at x 62, and only its top 2 rows at y 30
+    let chip = run(&[0x603E, 0x611E, 0xD015]);
+    assert!(pixel(&chip, 62, 30) && pixel(&chip, 63, 30) && pixel(&chip, 62, 31));
+    assert_eq!(lit(&chip), 3);
+    assert!(!pixel(&chip, 0, 30) && !pixel(&chip, 62, 0));
+}
+
+#[test]
+fn superchip_switches_between_the_small_and_big_screens() {
+    let mut chip = machine_on(Platform::SuperChip, &[0xD005, 0x00FF, 0x607C, 0xD015, 0x00FE]);
+    step(&mut chip, 2);
+    // Switching clears the screen
+    assert_eq!((chip.framebuffer().width(), chip.framebuffer().height(), lit(&chip)), (128, 64, 0));
+    step(&mut chip, 2);
+    assert!(pixel(&chip, 124, 0) && pixel(&chip, 127, 4));
+    assert_eq!(lit(&chip), 14);
+    step(&mut chip, 1);
+    assert_eq!((chip.framebuffer().width(), chip.framebuffer().height(), lit(&chip)), (64, 32, 0));
+
+    // On CHIP-8 00FF is a machine code call, so it's ignored
+    let chip = run(&[0x00FF]);
+    assert_eq!((chip.framebuffer().width(), chip.framebuffer().height()), (64, 32));
+}
+
+#[test]
+fn superchip_scrolls() {
+    // The 0 scrolled down 3 and right 4
+    let mut chip = machine_on(Platform::SuperChip, &[0xD005, 0x00C3, 0x00FB]);
+    step(&mut chip, 3);
+    assert!(pixel(&chip, 4, 3) && pixel(&chip, 7, 7) && !pixel(&chip, 0, 0));
+    assert_eq!(lit(&chip), 14);
+
+    // Scrolled left 4 it's off the screen and gone
+    let mut chip = machine_on(Platform::SuperChip, &[0xD005, 0x00FC, 0x00FB]);
+    step(&mut chip, 3);
+    assert_eq!(lit(&chip), 0);
+
+    // XO-CHIP scrolls up as well
+    let mut chip = machine_on(Platform::XoChip, &[0x6105, 0xD015, 0x00D2]);
+    step(&mut chip, 3);
+    assert!(pixel(&chip, 0, 3) && !pixel(&chip, 0, 8));
+    assert_eq!(lit(&chip), 14);
+}
+
+#[test]
+fn drw_with_n_0_draws_a_16x16_sprite_on_superchip() {
+    // 32 bytes of FF: a solid square, 2 bytes a row
+    let mut chip = machine_on(Platform::SuperChip, &[0xA300, 0x00FF, 0xD000, 0xD000]);
+    chip.write_mem(0x300, &[0xFF; 32]);
+    step(&mut chip, 3);
+    assert_eq!(lit(&chip), 256);
+    assert!(pixel(&chip, 15, 15) && !pixel(&chip, 16, 0) && !pixel(&chip, 0, 16));
+    assert_eq!(chip.registers()[0xF], 0);
+    step(&mut chip, 1);
+    assert_eq!((lit(&chip), chip.registers()[0xF]), (0, 1));
+
+    // On CHIP-8 it's a sprite of no rows
+    let mut chip = machine(&[0xA300, 0xD000]);
+    chip.write_mem(0x300, &[0xFF; 32]);
+    step(&mut chip, 2);
+    assert_eq!(lit(&chip), 0);
+}
+
+#[test]
+fn xochip_saves_and_loads_a_range_of_registers() {
+    // V1-V3 out to memory, then back in as V3-V1, which reverses them, leaving I alone
+    let mut chip = machine_on(Platform::XoChip, &[0x6101, 0x6202, 0x6303, 0xA300, 0x5132, 0x5313]);
+    step(&mut chip, 5);
+    assert_eq!(chip.read_mem(0x300, 4), &[1, 2, 3, 0]);
+    step(&mut chip, 1);
+    assert_eq!(chip.registers()[1..4], [3, 2, 1]);
+    assert_eq!(chip.ar(), 0x300);
+}
+
+#[test]
+fn skp_and_sknp() {
+    let mut chip = machine(&[0x6A07, 0xEA9E]);
+    chip.set_key(7, true);
+    step(&mut chip, 2);
+    assert_eq!(chip.pc(), 0x206);
+
+    let chip = run(&[0x6A07, 0xEA9E]);
+    assert_eq!(chip.pc(), 0x204);
+    let chip = run(&[0x6A07, 0xEAA1]);
+    assert_eq!(chip.pc(), 0x206);
+}
+
+#[test]
+fn ld_k_waits_for_a_key() {
+    let mut chip = machine(&[0xF30A]);
+    step(&mut chip, 3);
+    assert_eq!(chip.pc(), 0x200);
+    chip.set_key(0xB, true);
+    step(&mut chip, 1);
+    assert_eq!(chip.pc(), 0x202);
+    assert_eq!(chip.registers()[3], 0xB);
+}
+
+#[test]
+fn timers() {
+    let chip = run(&[0x6A20, 0xFA15, 0x6B30, 0xFB18, 0xFC07]);
+    assert_eq!(chip.delay(), 0x20);
+    assert_eq!(chip.sound(), 0x30);
+    assert_eq!(chip.registers()[0xC], 0x20);
+}
+
+#[test]
+fn add_i() {
+    let chip = run(&[0xA300, 0x6A42, 0xFA1E]);
+    assert_eq!(chip.ar(), 0x342);
+}
+
+#[test]
+fn ld_f_points_at_the_digit() {
+    assert_eq!(run(&[0x6A0B, 0xFA29]).ar(), 0xB * 5);
+    // Only the low nibble is a digit
+    assert_eq!(run(&[0x6AFB, 0xFA29]).ar(), 0xB * 5);
+}
+
+#[test]
+fn ld_b_stores_bcd() {
+    let chip = run(&[0xA300, 0x6A7B, 0xFA33]);
+    assert_eq!(chip.read_mem(0x300, 3), &[1, 2, 3]);
+    let chip = run(&[0xA300, 0x6A05, 0xFA33]);
+    assert_eq!(chip.read_mem(0x300, 3), &[0, 0, 5]);
+}
+
+#[test]
+fn ld_registers_to_and_from_memory() {
+    let chip = run(&[0x6011, 0x6122, 0x6233, 0xA300, 0xF155]);
+    assert_eq!(chip.read_mem(0x300, 3), &[0x11, 0x22, 0x00]);
+    assert_eq!(chip.ar(), 0x300);
+
+    let mut chip = machine(&[0xA300, 0xF265]);
+    chip.write_mem(0x300, &[0x44, 0x55, 0x66, 0x77]);
+    step(&mut chip, 2);
+    assert_eq!(&chip.registers()[..4], &[0x44, 0x55, 0x66, 0x00]);
+}
+
+#[test]
+fn quirks_run_the_instructions_interpreters_disagree_over_the_other_way() {
+    let quirky = |opcodes: &[u16], quirks: Quirks| {
+        let mut chip = machine(opcodes);
+        chip.set_quirks(quirks);
+        step(&mut chip, opcodes.len());
+        return chip;
+    };
+
+    // 8XY6 and 8XYE shift VY into VX
+    let chip = quirky(&[0x6081, 0x6103, 0x8016], Quirks { shift_vy: true, ..Quirks::default() });
+    assert_eq!((chip.registers()[0], chip.registers()[0xF]), (0x01, 1));
+    let chip = quirky(&[0x6001, 0x6181, 0x801E], Quirks { shift_vy: true, ..Quirks::default() });
+    assert_eq!((chip.registers()[0], chip.registers()[0xF]), (0x02, 1));
+
+    // BXNN adds VX
+    let chip = quirky(&[0x6004, 0x6210, 0xB234], Quirks { jump_vx: true, ..Quirks::default() });
+    assert_eq!(chip.pc(), 0x244);
+
+    // FX55 and FX65 move I on past the last register
+    let chip = quirky(&[0xA300, 0xF255, 0xF165], Quirks { memory_moves_i: true, ..Quirks::default() });
+    assert_eq!(chip.state().ar, 0x305);
+
+    // 8XY1 to 8XY3 set VF to 0
+    let chip = quirky(&[0x6F01, 0x6003, 0x8011], Quirks { vf_reset: true, ..Quirks::default() });
+    assert_eq!(chip.registers()[0xF], 0);
+    let chip = run(&[0x6F01, 0x6003, 0x8011]);
+    assert_eq!(chip.registers()[0xF], 1);
+
+    // A second draw in a frame waits for the next one
+    let mut chip = machine(&[0xD005, 0xD005, 0x6001]);
+    chip.set_quirks(Quirks { display_wait: true, ..Quirks::default() });
+    step(&mut chip, 3);
+    assert_eq!(chip.pc(), 0x202);
+    chip.tick_timers();
+    step(&mut chip, 2);
+    assert_eq!(chip.pc(), 0x206);
+    assert_eq!(lit(&chip), 0);
+}
+
+#[test]
+fn memory_wraps_with_i_at_the_end() {
+    let chip = run(&[0x6011, 0x6122, 0xAFFF, 0xF155]);
+    assert_eq!(chip.read_mem(0xFFF, 1), &[0x11]);
+    assert_eq!(chip.read_mem(0x000, 1), &[0x22]);
+
+    let chip = run(&[0x60FF, 0xAFFE, 0xF033]);
+    assert_eq!(chip.read_mem(0xFFE, 2), &[2, 5]);
+    assert_eq!(chip.read_mem(0x000, 1), &[5]);
+
+    let mut chip = machine(&[0xAFFF, 0xF165]);
+    chip.write_mem(0xFFF, &[0x99]);
+    step(&mut chip, 2);
+    // Reading wraps round to the font at 0
+    assert_eq!(&chip.registers()[..2], &[0x99, 0xF0]);
+}
+
+#[test]
+fn strict_mode_stops_on_what_permissive_skips() {
+    let strict = |opcodes: &[u16]| {
+        let mut chip = machine(opcodes);
+        chip.set_strict(true);
+        return chip;
+    };
+
+    // 8XYF isn't an instruction
+    assert_eq!(run(&[0x801F]).pc(), 0x202);
+    let mut chip = strict(&[0x801F]);
+    assert_eq!(chip.execute(), Err(Chip8Error::UnknownInstruction { pc: 0x200, opcode: 0x801F }));
+    assert_eq!(chip.pc(), 0x200);
+
+    let mut chip = strict(&[0x0123]);
+    assert_eq!(chip.execute(), Err(Chip8Error::MachineCodeCall { pc: 0x200, addr: 0x123 }));
+
+    let mut chip = strict(&[0xAFFF, 0xF155]);
+    step(&mut chip, 1);
+    assert_eq!(chip.execute(), Err(Chip8Error::OutOfBounds { pc: 0x202, addr: 0x1000 }));
+    let mut chip = strict(&[0xAFFE, 0xF155, 0xAFFC, 0xD015]);
+    step(&mut chip, 3);
+    assert_eq!(chip.execute(), Err(Chip8Error::OutOfBounds { pc: 0x206, addr: 0x1000 }));
+
+    // Fetching the last byte of memory, the second half of the instruction is past the end
+    let mut chip = strict(&[0x6001, 0xBFFE]);
+    step(&mut chip, 2);
+    assert_eq!(chip.execute(), Err(Chip8Error::OutOfBounds { pc: 0xFFF, addr: 0x1000 }));
+}
+
+#[test]
+fn exit_stays_put_and_stops_the_frame() {
+    let mut chip = machine_on(Platform::SuperChip, &[0x6005, 0x00FD, 0x6009]);
+    chip.run_frame().expect("the frame runs");
+    assert!(chip.exit_requested());
+    assert_eq!(chip.pc(), 0x202);
+    assert_eq!(chip.registers()[0], 0x05);
+
+    // 00FD is a machine code call on CHIP-8X, which doesn't have SUPER-CHIP's exit
+    let mut chip = machine_on(Platform::Chip8X, &[0x00FD]);
+    step(&mut chip, 1);
+    assert!(!chip.exit_requested());
+    assert_eq!(chip.pc(), 0x302);
+
+    // and on CHIP-8, where it goes by the sys policy
+    let mut chip = machine(&[0x00FD]);
+    chip.set_sys_policy(SysPolicy::Error);
+    assert_eq!(chip.execute(), Err(Chip8Error::MachineCodeCall { pc: 0x200, addr: 0x0FD }));
+    assert!(!chip.exit_requested());
+}
+
+#[test]
+fn the_pc_wraps_round_the_end_of_memory() {
+    // Running off the end of a 4K machine comes round to the start
+    let mut chip = machine(&[0x1FFE]);
+    chip.write_mem(0xFFE, &[0x60, 0x05]);
+    step(&mut chip, 2);
+    assert_eq!(chip.registers()[0], 5);
+    assert_eq!(chip.pc(), 0);
+
+    // As does running off the end of XO-CHIP's 64K, the last of the PC's 16 bits
+    let v = ["0"; 16].join(", ");
+    let mut chip = Chip8::from_state_json(&format!(r#"{{"platform": "xo-chip", "pc": 65534, "i": 0, "v": [{v}]}}"#)).expect("the state loads");
+    chip.write_mem(0xFFFE, &[0x60, 0x05]);
+    step(&mut chip, 1);
+    assert_eq!(chip.registers()[0], 5);
+    assert_eq!(chip.pc(), 0);
+
+    // Skipping from the last instruction too
+    let mut chip = Chip8::from_state_json(&format!(r#"{{"platform": "xo-chip", "pc": 65534, "i": 0, "v": [{v}]}}"#)).expect("the state loads");
+    chip.write_mem(0xFFFE, &[0x40, 0x01]);
+    step(&mut chip, 1);
+    assert_eq!(chip.pc(), 2);
+}
+
+#[test]
+fn chip8x_colours_every_zone_a_sprite_is_over() {
+    // An 8x1 sprite at x 4 is over the first two columns of zones, and at x 60 over the last
+    // and, wrapping round, the first. Every zone starts red, colour 1
+    let mut chip = machine_on(Platform::Chip8X, &[0x6004, 0x6100, 0x6202, 0xB021, 0x603C, 0x6203, 0xB021]);
+    step(&mut chip, 4);
+    let zones = chip.framebuffer().colour_zones().expect("CHIP-8X has colour zones");
+    assert_eq!([0, 8, 16].map(|x| zones.zone_colour(x, 0)), [2, 2, 1]);
+    assert_eq!(zones.zone_colour(0, 4), 1);
+
+    step(&mut chip, 3);
+    let zones = chip.framebuffer().colour_zones().expect("CHIP-8X has colour zones");
+    assert_eq!([0, 8, 48, 56].map(|x| zones.zone_colour(x, 0)), [3, 2, 1, 3]);
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use chip8::chip::{Chip8, Snapshot};
+use chip8::platform::Platform;
+use common::{machine_on, step};
+
+// Snapshots, the save states they're saved as and the machine's state as JSON. The machine
+// keeps its screens a bit a pixel and so do snapshots, so each of these checks a screen comes
+// back the size it was with the same pixels on.
+
+
+#[test]
+fn a_save_state_keeps_the_screen_a_bit_a_pixel() {
+    // The font's 0 on the 128x64 screen, at an x that isn't on a byte boundary
+    let mut chip = machine_on(Platform::SuperChip, &[0x00FF, 0x6005, 0x6103, 0xD015]);
+    step(&mut chip, 4);
+    assert_eq!((chip.framebuffer().width(), chip.framebuffer().height()), (128, 64));
+    let state = chip.snapshot().to_bytes();
+    let mut restored = Chip8::with_platform(Platform::SuperChip, false);
+    restored.restore(&Snapshot::from_bytes(&state).expect("the state loads"));
+    assert_eq!((restored.framebuffer().width(), restored.framebuffer().height()), (128, 64));
+    assert!(!restored.framebuffer().is_colour());
+    assert_eq!(restored.framebuffer().bytes(), chip.framebuffer().bytes());
+    assert_eq!(restored.framebuffer().to_ascii('#', '.'), chip.framebuffer().to_ascii('#', '.'));
+    assert_eq!(restored.framebuffer().lit(), 14);
+
+    // Both screens a bit a pixel and 4KB of memory, with room for the rest
+    let unpacked = chip.snapshot().to_unpacked_bytes();
+    assert!(unpacked.len() < 0x1000 + 2 * 128 * 64 / 8 + 256, "{} bytes", unpacked.len());
+}
+
+#[test]
+fn a_snapshot_puts_the_screen_back_as_it_was() {
+    // The font's 0, then drawn again over itself after the snapshot, which clears it
+    let mut chip = machine_on(Platform::Chip8, &[0xD015, 0xD015]);
+    step(&mut chip, 1);
+    let snapshot = chip.snapshot();
+    step(&mut chip, 1);
+    assert_eq!((chip.framebuffer().lit(), chip.registers()[0xF]), (0, 1));
+
+    chip.restore(&snapshot);
+    assert_eq!((chip.framebuffer().width(), chip.framebuffer().height()), (64, 32));
+    assert_eq!(chip.framebuffer().to_ascii('#', '.'), snapshot.framebuffer().to_ascii('#', '.'));
+    assert_eq!(chip.framebuffer().lit(), 14);
+}
+
+#[test]
+fn a_state_comes_back_from_json() {
+    // Draw the font's 0 after a call, so there's a stack, a screen and a timer to carry over
+    let mut chip = machine_on(Platform::SuperChip, &[0x00FF, 0x2206, 0x0000, 0x6A05, 0xFA15, 0xD015, 0x00EE]);
+    step(&mut chip, 5);
+    let json = chip.export_state_json();
+    let imported = Chip8::from_state_json(&json).expect("the state imports");
+    assert_eq!(imported.export_state_json(), json);
+    assert_eq!(imported.call_stack(), &[0x204]);
+    // 00FF switched to the big screen, which has to come back as big
+    assert_eq!((imported.framebuffer().width(), imported.framebuffer().height()), (128, 64));
+    assert_eq!(imported.framebuffer().lit(), 14);
+
+    // Other emulators' names and shapes
+    let other = r#"{"PC": "0x300", "index": 16, "registers": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16],
+        "stack": [514, 0, 0], "sp": 1, "DT": 9, "display": [[0, 1], [1, 0]]}"#;
+    let imported = Chip8::from_state_json(other).expect("the state imports");
+    assert_eq!((imported.pc(), imported.ar(), imported.registers()[15], imported.delay()), (0x300, 16, 16, 9));
+    assert_eq!(imported.call_stack(), &[0x202]);
+    assert_eq!((imported.framebuffer().width(), imported.framebuffer().lit()), (2, 2));
+    assert!(Chip8::from_state_json(r#"{"pc": 512, "i": 0, "v": [1, 2]}"#).is_err());
+}
+
+#[test]
+fn a_malformed_json_state_is_refused() {
+    let v = ["0"; 16].join(", ");
+    let state = |fields: &str| Chip8::from_state_json(&format!(r#"{{"pc": 512, "i": 0, {fields}}}"#)).err();
+
+    // More memory than any machine has, 16MB and 2 bytes of it
+    let memory = "AAAA".repeat(0x100_0002 / 3);
+    assert_eq!(state(&format!(r#""v": [{v}], "memory": "{memory}""#)), Some("memory is bigger than the 16MB a machine can have".to_string()));
+
+    assert!(state(r#""v": [1, 2, 3]"#).is_some());
+    assert!(state(&format!(r#""v": [{v}, 0]"#)).is_some());
+
+    // Deeper than the stack goes, and an SP past the end of the stack
+    let stack = ["512"; 256].join(", ");
+    assert_eq!(state(&format!(r#""v": [{v}], "stack": [{stack}]"#)), Some("the stack is 256 calls deep, past the 255 it can go".to_string()));
+    assert!(state(&format!(r#""v": [{v}], "stack": [512], "sp": 2"#)).is_some());
+
+    assert_eq!(state(&format!(r#""v": [{v}], "platform": "CHIP-9""#)), Some("unknown platform 'CHIP-9'".to_string()));
+    assert!(state(&format!(r#""v": [{v}], "platform": 8"#)).is_some());
+}
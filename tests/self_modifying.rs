@@ -0,0 +1,51 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use chip8::chip::{Chip8, CodeWrite};
+use chip8::platform::Platform;
+use common::{machine, step};
+
+// Roms writing over their own code. Nothing's decoded ahead, so what's written runs the next
+// time it's fetched, and the code write hook hears about every byte of code that changes.
+
+
+#[test]
+fn writes_over_code_run_next_time_and_are_reported() {
+    // FX55 writes 6007 over the 6001 two instructions on, which runs as written. It hadn't
+    // run yet, so it wasn't code. Back round the loop, writing 0707 over it when it has is
+    let mut chip = machine(&[0x6060, 0x6107, 0xA20A, 0xF155, 0x6201, 0x6001, 0x1206]);
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let hooked = writes.clone();
+    chip.set_code_write_hook(move |write| hooked.lock().unwrap().push(write));
+    step(&mut chip, 6);
+    assert_eq!(chip.registers()[0], 0x07);
+    assert!(writes.lock().unwrap().is_empty());
+
+    step(&mut chip, 2);
+    // Only the byte that changed
+    assert_eq!(*writes.lock().unwrap(), vec![CodeWrite { pc: 0x206, addr: 0x20A, old: 0x60, new: 0x07 }]);
+}
+
+#[test]
+fn the_code_write_hook_keeps_up_with_memory_a_restore_makes_bigger() {
+    // An XO-CHIP state has 64KB of memory to CHIP-8's 4KB. Writing near the top of it after
+    // restoring, with the hook set from before, has to find room to check
+    let mut xochip = Chip8::with_platform(Platform::XoChip, false);
+    // I := FF00, V0 := 1 and saved there, then the same over the F0 of that FX55
+    xochip.load_rom_bytes(&[0xF0, 0x00, 0xFF, 0x00, 0x60, 0x01, 0xF0, 0x55, 0xA2, 0x06, 0xF0, 0x55]);
+    let snapshot = xochip.snapshot();
+
+    let mut chip = machine(&[0x1200]);
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let hooked = writes.clone();
+    chip.set_code_write_hook(move |write| hooked.lock().unwrap().push(write));
+    step(&mut chip, 1);
+    chip.restore(&snapshot);
+    assert_eq!(chip.memory_size(), 0x10000);
+    step(&mut chip, 3);
+    assert_eq!(chip.read_mem(0xFF00, 1), [0x01]);
+    assert!(writes.lock().unwrap().is_empty());
+    step(&mut chip, 2);
+    assert_eq!(*writes.lock().unwrap(), vec![CodeWrite { pc: 0x20A, addr: 0x206, old: 0xF0, new: 0x01 }]);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use chip8::chip::{StackPolicy, STACK_DEPTH};
+use chip8::error::Chip8Error;
+use common::{machine, step};
+
+// How deep the stack goes and what a call does when it's full. The 16 levels of the original
+// interpreters are the default, see the opcode tests for 2NNN and 00EE on those.
+
+
+#[test]
+fn a_deeper_stack_can_wrap_when_full() {
+    let mut chip = machine(&[0x2200]);
+    chip.set_stack_depth(32);
+    step(&mut chip, 32);
+    assert_eq!(chip.sp(), 32);
+    assert!(matches!(chip.execute(), Err(Chip8Error::StackOverflow { pc: 0x200, .. })));
+
+    // Wrapping forgets the oldest call rather than stopping
+    let mut chip = machine(&[0x2202, 0x2204, 0x2200]);
+    chip.set_stack_policy(StackPolicy::Wrap);
+    step(&mut chip, 17);
+    assert_eq!(chip.sp(), 16);
+    assert_eq!(chip.call_stack()[0], 0x204);
+    assert_eq!(chip.call_stack()[15], 0x204);
+}
+
+#[test]
+fn a_shallower_stack_forgets_the_oldest_calls() {
+    // Each call returns to the next, so the stack counts up from 202
+    let mut chip = machine(&[0x2202, 0x2204, 0x2206, 0x2208]);
+    step(&mut chip, 4);
+    chip.set_stack_depth(2);
+    assert_eq!(chip.call_stack(), &[0x206, 0x208]);
+    assert_eq!(chip.stack_depth(), 2);
+
+    // Kept between 1 and 255
+    chip.set_stack_depth(0);
+    assert_eq!((chip.stack_depth(), chip.call_stack()), (1, &[0x208][..]));
+    chip.set_stack_depth(1000);
+    assert_eq!(chip.stack_depth(), 255);
+    chip.set_stack_depth(STACK_DEPTH);
+    assert_eq!(chip.stack_depth(), 16);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use chip8::chip::CodeWrite;
+use common::{machine, step};
+
+// The write hook, which the trace database records what instructions write to memory
+// through (see tracedb.rs). It's told about every byte written, changed or not.
+
+
+#[test]
+fn the_write_hook_sees_every_byte_written() {
+    // BCD of 0x7B (123) to 0x300, the middle digit writing what's already there
+    let mut chip = machine(&[0x607B, 0xA300, 0xF033]);
+    chip.write_mem(0x301, &[2]);
+    let writes = Arc::new(Mutex::new(Vec::new()));
+    let hooked = writes.clone();
+    chip.set_write_hook(move |write| hooked.lock().unwrap().push(write));
+    step(&mut chip, 3);
+    assert_eq!(
+        *writes.lock().unwrap(),
+        vec![
+            CodeWrite { pc: 0x204, addr: 0x300, old: 0, new: 1 },
+            CodeWrite { pc: 0x204, addr: 0x301, old: 2, new: 2 },
+            CodeWrite { pc: 0x204, addr: 0x302, old: 0, new: 3 },
+        ]
+    );
+}